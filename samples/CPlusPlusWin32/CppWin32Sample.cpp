@@ -8,7 +8,7 @@
 #include <iostream>
 
 #include "constants.h"
-#include "Velopack.h"
+#include "Velopack.hpp"
 
 #pragma comment(linker, \
   "\"/manifestdependency:type='Win32' "\
@@ -1,7 +1,8 @@
 use crate::shared::{self, OperationWait};
-use velopack::{locator, locator::VelopackLocator, constants};
+use velopack::{locator, locator::VelopackLocator, constants, retention::{self, RetentionPolicy}};
 use anyhow::{bail, Result};
-use std::path::PathBuf;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
 
 #[cfg(target_os = "linux")]
 use super::apply_linux_impl::apply_package_impl;
@@ -17,21 +18,26 @@ pub fn apply<'a>(
     package: Option<&PathBuf>,
     exe_args: Option<Vec<&str>>,
     run_hooks: bool,
+    retention: &RetentionPolicy,
 ) -> Result<VelopackLocator> {
     shared::operation_wait(wait);
 
     let packages_dir = locator.get_packages_dir();
     let package = package.cloned().or_else(|| locator::find_latest_full_package(&packages_dir).map(|x| x.0));
 
+    let mut restart_required = false;
     match package {
         Some(package) => {
             info!("Getting ready to apply package to {} ver {}: {}", 
                 locator.get_manifest_id(), 
                 locator.get_manifest_version_full_string(), 
                 package.to_string_lossy());
-            match apply_package_impl(&locator, &package, run_hooks) {
+            match verify_package_sha256(&package).and_then(|_| apply_package_impl(locator, &package, run_hooks)) {
                 Ok(applied_locator) => {
                     info!("Package version {} applied successfully.", applied_locator.get_manifest_version_full_string());
+                    if let Err(e) = retention::enforce(&packages_dir, retention, std::slice::from_ref(&package)) {
+                        warn!("Unable to clean up old packages: {}", e);
+                    }
                     // if successful, we want to restart the new version of the app, which could have different metadata
                     if restart {
                         shared::start_package(&applied_locator, exe_args, Some(constants::HOOK_ENV_RESTART))?;
@@ -40,6 +46,7 @@ pub fn apply<'a>(
                 }
                 Err(e) => {
                     error!("Error applying package: {}", e);
+                    restart_required = e.downcast_ref::<shared::RestartRequiredError>().is_some();
                 }
             }
         }
@@ -53,5 +60,26 @@ pub fn apply<'a>(
         shared::start_package(&locator, exe_args, Some(constants::HOOK_ENV_RESTART))?;
     }
 
+    if restart_required {
+        return Err(shared::RestartRequiredError.into());
+    }
+
     bail!("Apply failed, see logs for details.");
 }
+
+/// Checks the package against the SHA256 which the UpdateManager passed in the environment, if there is one, so that a
+/// package which was changed after it was downloaded and verified is never applied.
+fn verify_package_sha256(package: &Path) -> Result<()> {
+    let expected = match std::env::var(constants::PACKAGE_SHA256_ENV) {
+        Ok(expected) if !expected.trim().is_empty() => expected,
+        _ => return Ok(()),
+    };
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut std::fs::File::open(package)?, &mut hasher)?;
+    let actual = format!("{:x}", hasher.finalize());
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        bail!("The package SHA256 is {}, but {} was expected.", actual, expected.trim());
+    }
+    info!("Verified the package SHA256 ({}).", actual);
+    Ok(())
+}
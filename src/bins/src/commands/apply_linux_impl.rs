@@ -1,57 +1,132 @@
-use crate::shared::dialogs;
-use anyhow::{bail, Result};
+use crate::shared::{self, dialogs};
+use super::{PlatformShortcuts, Shortcuts};
+use anyhow::{anyhow, bail, Result};
 use std::os::unix::fs::PermissionsExt;
-use std::{fs, path::PathBuf, process::Command};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 use velopack::{bundle, locator::VelopackLocator};
 
 pub fn apply_package_impl<'a>(locator: &VelopackLocator, pkg: &PathBuf, _runhooks: bool) -> Result<VelopackLocator> {
-    // on linux, the current "dir" is actually an AppImage file which we need to replace.
+    // on linux, the app is a single AppImage file which we need to replace.
     info!("Loading bundle from {}", pkg.to_string_lossy());
     let mut bundle = bundle::load_bundle_from_file(pkg)?;
     let manifest = bundle.read_manifest()?;
-    let temp_path = locator.get_temp_dir_rand16().to_string_lossy().to_string();
-    let root_path_string = locator.get_root_dir_as_string();
-    let script_path = format!("/var/tmp/velopack_update_{}.sh", manifest.id);
+    let installed_path = shared::get_appimage_path(locator);
+    let appimage_path = get_writable_appimage_path(&installed_path)?;
     let new_locator = locator.clone_self_with_new_manifest(&manifest);
 
-    let action: Result<()> = (|| {
-        info!("Extracting bundle to temp file: {}", temp_path);
-        bundle.extract_zip_predicate_to_path(|z| z.ends_with(".AppImage"), &temp_path)?;
-
-        info!("Chmod as executable");
-        std::fs::set_permissions(&temp_path, fs::Permissions::from_mode(0o755))?;
-
-        info!("Moving temp file to target: {}", &root_path_string);
-        // we use mv instead of fs::rename / fs::copy because rename fails cross-device
-        // and copy fails if the process is running (presumably because rust opens the file for writing)
-        // while mv works in both cases.
-        let mv_args = vec!["-f", &temp_path, &root_path_string];
-        let mv_output = Command::new("mv").args(mv_args).output()?;
-
-        if mv_output.status.success() {
-            info!("AppImage moved successfully to: {}", &root_path_string);
-            return Ok(());
-        }
+    // the new AppImage is extracted next to the old one, so that it can be renamed over it atomically. the running
+    // process keeps the old file open, so it is unaffected until it exits.
+    let file_name = appimage_path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let temp_path = appimage_path.with_file_name(format!(".{}.velopack_{}", file_name, shared::random_string(16)));
+
+    // while the app is running, the root dir is the mount point of the AppImage, so anything else still running from
+    // the old version (eg. helper processes which outlive the app) is stopped before it is replaced
+    let root_dir = locator.get_root_dir();
+    if root_dir != installed_path {
+        let _ = shared::force_stop_package(&root_dir);
+    }
 
-        // if the operation failed, let's try again elevated with pkexec
-        error!("An error occurred ({:?}), will attempt to elevate permissions and try again...", mv_output);
-        dialogs::ask_user_to_elevate(&manifest.title, &manifest.version.to_string())?;
-        let script = format!("#!/bin/sh\nmv -f '{}' '{}'", temp_path, &root_path_string);
-        info!("Writing script for elevation: \n{}", script);
-        fs::write(&script_path, script)?;
-        std::fs::set_permissions(&script_path, <std::fs::Permissions as std::os::unix::fs::PermissionsExt>::from_mode(0o755))?;
-        let args = vec![&script_path];
-        info!("Attempting to elevate: pkexec {:?}", args);
-        let elev_output = Command::new("pkexec").args(args).output()?;
-        if elev_output.status.success() {
-            info!("AppImage moved (elevated) to {}", &root_path_string);
-            return Ok(());
-        } else {
-            bail!("pkexec failed with status: {:?}", elev_output);
+    let action = replace_appimage(&bundle, &temp_path, &appimage_path);
+    let _ = fs::remove_file(&temp_path);
+    match action {
+        Ok(()) => {
+            info!("AppImage replaced successfully: {}", appimage_path.to_string_lossy());
+            if appimage_path != installed_path {
+                // the app is restarted from the AppImage the updater was started by, which is not the one that was updated
+                std::env::set_var("APPIMAGE", &appimage_path);
+                redirect_launchers(&new_locator, &installed_path, &appimage_path);
+            }
+            refresh_integrations(locator, &new_locator, &appimage_path);
+            Ok(new_locator)
+        }
+        Err(e) if shared::is_error_permission_denied(&e) => {
+            // if we can't write to the directory containing the AppImage (eg. it was installed to /opt by a system
+            // package), let's try again elevated with pkexec so the user can authorize it with polkit.
+            error!("An error occurred ({:?}), will attempt to elevate permissions and try again...", e);
+            dialogs::ask_user_to_elevate(&manifest.title, &manifest.version.to_string())?;
+            apply_elevated(locator, &bundle, &temp_path, &appimage_path)?;
+            refresh_integrations(locator, &new_locator, &appimage_path);
+            Ok(new_locator)
         }
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns the path the updated AppImage should be written to. This is the installed AppImage, unless it is on a
+/// read-only file system (eg. it was installed into the system image of an immutable distribution), which can not be
+/// written to even when elevated, so the update is installed for the current user instead.
+fn get_writable_appimage_path(appimage_path: &Path) -> Result<PathBuf> {
+    let dir = appimage_path.parent().unwrap_or(Path::new("/"));
+    if !shared::is_read_only_fs(dir) {
+        return Ok(appimage_path.to_path_buf());
+    }
+
+    let reason = shared::get_immutable_system_reason().unwrap_or_else(|| format!("{} is mounted read-only", dir.to_string_lossy()));
+    let file_name = appimage_path.file_name().ok_or_else(|| anyhow!("AppImage path {:?} has no file name.", appimage_path))?;
+    let user_dir = shared::get_user_bin_dir()?;
+    if shared::is_read_only_fs(&user_dir) {
+        bail!("Unable to apply update, {:?} and {:?} are both on a read-only file system ({}).", dir, user_dir, reason);
+    }
+
+    warn!(
+        "Unable to update the AppImage at {:?} because {}, so the update will be installed to {:?} for the current user instead.",
+        appimage_path, reason, user_dir
+    );
+    fs::create_dir_all(&user_dir)?;
+    Ok(user_dir.join(file_name))
+}
+
+fn redirect_launchers(locator: &VelopackLocator, installed_path: &Path, appimage_path: &Path) {
+    // the launchers installed by the system still start the old AppImage, which would undo the update the next time the
+    // app is started, so they are shadowed by entries for the current user which start the updated copy
+    match super::redirect_desktop_entries(locator, installed_path, appimage_path) {
+        Ok(entries) if !entries.is_empty() => {}
+        Ok(_) => warn!("No launchers start {:?}, so {:?} must be started directly to use the update.", installed_path, appimage_path),
+        Err(e) => warn!("Unable to redirect launchers to {:?} ({}).", appimage_path, e),
+    }
+}
+
+fn refresh_integrations(old_locator: &VelopackLocator, locator: &VelopackLocator, appimage_path: &Path) {
+    // the shortcuts and update timer are not part of the update itself, so failing to refresh them should not fail the update
+    PlatformShortcuts.create_or_update_manifest_shortcuts(locator, Some(old_locator));
+    if let Err(e) = super::refresh_update_timer(locator, appimage_path) {
+        warn!("Unable to update background update timer ({}).", e);
+    }
+}
+
+fn replace_appimage(bundle: &bundle::BundleZip<'_>, temp_path: &Path, appimage_path: &Path) -> Result<()> {
+    // create the file up front, so a directory we can't write to is reported as a permission error
+    fs::File::create(temp_path)?;
+    extract_appimage(bundle, temp_path)?;
+    info!("Renaming temp file to target: {}", appimage_path.to_string_lossy());
+    fs::rename(temp_path, appimage_path)?;
+    Ok(())
+}
+
+fn extract_appimage(bundle: &bundle::BundleZip<'_>, temp_path: &Path) -> Result<()> {
+    info!("Extracting bundle to temp file: {}", temp_path.to_string_lossy());
+    bundle.extract_zip_predicate_to_path(|z| z.ends_with(".AppImage"), temp_path)?;
+    shared::verify_appimage(temp_path)?;
+    info!("Chmod as executable");
+    fs::set_permissions(temp_path, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+fn apply_elevated(locator: &VelopackLocator, bundle: &bundle::BundleZip<'_>, staging: &Path, appimage_path: &Path) -> Result<()> {
+    let temp_path = locator.get_temp_dir_rand16();
+    let action: Result<()> = (|| {
+        extract_appimage(bundle, &temp_path)?;
+
+        // the temp file may be on a different device, so it is first copied next to the target and then renamed
+        // over it, so that the AppImage is still replaced atomically.
+        let script = "set -e\ncp -f \"$1\" \"$2\"\nchmod 755 \"$2\"\nmv -f \"$2\" \"$3\"";
+        shared::run_elevated_script(script, &[temp_path.as_os_str(), staging.as_os_str(), appimage_path.as_os_str()])?;
+        info!("AppImage moved (elevated) to {}", appimage_path.to_string_lossy());
+        Ok(())
     })();
-    let _ = fs::remove_file(&script_path);
     let _ = fs::remove_file(&temp_path);
-    action?;
-    Ok(new_locator)
+    action
 }
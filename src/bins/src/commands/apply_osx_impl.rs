@@ -2,66 +2,132 @@ use crate::shared::{
     self,
     dialogs,
 };
+use super::{PlatformShortcuts, Shortcuts};
 use anyhow::{bail, Result};
-use std::{fs, path::PathBuf, process::Command};
-use velopack::{bundle, locator::VelopackLocator};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use velopack::{arch, bundle, locator::VelopackLocator};
 
 pub fn apply_package_impl<'a>(locator: &VelopackLocator, pkg: &PathBuf, _runhooks: bool) -> Result<VelopackLocator> {
     let root_path = locator.get_root_dir();
-    let tmp_path_new = locator.get_temp_dir_rand16();
-    let tmp_path_old = locator.get_temp_dir_rand16();
     let mut bundle = bundle::load_bundle_from_file(pkg)?;
     let manifest = bundle.read_manifest()?;
     let new_locator = locator.clone_self_with_new_manifest(&manifest);
 
+    if !arch::is_arch_supported(&manifest.machine_architecture) {
+        bail!("This update was built for a CPU architecture ({}) which is not supported by this machine.", &manifest.machine_architecture);
+    }
+
+    // the new bundle is extracted next to the installed one, so that it is on the same volume and can be swapped in
+    // with a single rename. after the swap, this path holds the old bundle until the new one has been checked.
+    let tmp_path_new = get_sibling_temp_path(&root_path);
+
     let action: Result<()> = (|| {
-        // 1. extract the bundle to a temp dir
-        fs::create_dir_all(&tmp_path_new)?;
+        // 1. extract the bundle next to the installed app
+        if let Err(e) = fs::create_dir(&tmp_path_new) {
+            if e.kind() == std::io::ErrorKind::PermissionDenied {
+                error!("A permissions error occurred ({}), will attempt to elevate permissions and try again...", e);
+                return apply_elevated(locator, &mut bundle, &root_path);
+            }
+            return Err(e.into());
+        }
         info!("Extracting bundle to {:?}", &tmp_path_new);
         bundle.extract_lib_contents_to_path(&tmp_path_new, |_| {})?;
+        shared::remove_quarantine_attribute(&tmp_path_new);
 
-        // 2. attempt to replace the current bundle with the new one
-        let result: Result<()> = (|| {
-            info!("Replacing bundle at {:?}", &root_path);
-            fs::rename(&root_path, &tmp_path_old)?;
-            fs::rename(&tmp_path_new, &root_path)?;
-            Ok(())
-        })();
+        // 2. refuse to swap in a bundle which was not signed and notarized by the same team as the installed app
+        shared::verify_bundle_signature(&root_path, &tmp_path_new)?;
 
-        match result {
-            Ok(()) => {
-                info!("Bundle extracted successfully to {:?}", &root_path);
-                Ok(())
-            }
-            Err(e) => {
-                // 3. if fails for permission error, try again escalated via osascript
-                if shared::is_error_permission_denied(&e) {
-                    error!("A permissions error occurred ({}), will attempt to elevate permissions and try again...", e);
-                    dialogs::ask_user_to_elevate(&manifest.title, &manifest.version.to_string())?;
-                    let script = format!(
-                        "do shell script \"mv -f '{}' '{}' && mv -f '{}' '{}' && rm -rf '{}'\" with administrator privileges",
-                        &root_path.to_string_lossy(),
-                        &tmp_path_old.to_string_lossy(),
-                        &tmp_path_new.to_string_lossy(),
-                        &root_path.to_string_lossy(),
-                        &tmp_path_old.to_string_lossy()
-                    );
-                    info!("Running elevated process via osascript: {}", script);
-                    let output = Command::new("osascript").arg("-e").arg(&script).status()?;
-                    if output.success() {
-                        info!("Bundle applied successfully via osascript.");
-                        Ok(())
-                    } else {
-                        bail!("elevated process failed: exited with code: {}", output);
-                    }
-                } else {
-                    bail!("Failed to extract bundle ({})", e);
-                }
+        // 3. atomically swap the new bundle with the installed one, once nothing is running from it any more
+        let _ = shared::force_stop_package(&root_path);
+        info!("Swapping bundle at {:?}", &root_path);
+        if let Err(e) = swap_bundles(&tmp_path_new, &root_path) {
+            if shared::is_error_permission_denied(&e) {
+                error!("A permissions error occurred ({}), will attempt to elevate permissions and try again...", e);
+                let _ = fs::remove_dir_all(&tmp_path_new);
+                return apply_elevated(locator, &mut bundle, &root_path);
             }
+            return Err(e);
         }
+
+        // 4. if the new bundle is not usable, roll back to the old bundle which is still on disk
+        let main_exe_path = new_locator.get_main_exe_path();
+        if !main_exe_path.exists() {
+            error!("The new bundle does not contain the main executable {:?}, rolling back.", &main_exe_path);
+            swap_bundles(&tmp_path_new, &root_path)?;
+            bail!("The new bundle does not contain the main executable, the previous version has been restored.");
+        }
+
+        info!("Bundle applied successfully to {:?}", &root_path);
+        Ok(())
     })();
     let _ = fs::remove_dir_all(&tmp_path_new);
-    let _ = fs::remove_dir_all(&tmp_path_old);
     action?;
+    PlatformShortcuts.create_or_update_manifest_shortcuts(&new_locator, Some(locator));
     Ok(new_locator)
 }
+
+pub(super) fn get_sibling_temp_path(root_path: &Path) -> PathBuf {
+    let bundle_name = root_path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    root_path.with_file_name(format!(".{}.velopack_{}", bundle_name, shared::random_string(16)))
+}
+
+pub(super) fn swap_bundles(a: &Path, b: &Path) -> Result<()> {
+    match shared::swap_paths(a, b) {
+        Ok(()) => Ok(()),
+        Err(e) if e.raw_os_error() == Some(libc::ENOTSUP) => {
+            // RENAME_SWAP is only supported on APFS and HFS+, so fall back to two renames on other file systems
+            warn!("The file system does not support atomic swaps, the bundle will be replaced with two renames.");
+            let tmp_path = get_sibling_temp_path(b);
+            fs::rename(b, &tmp_path)?;
+            fs::rename(a, b)?;
+            fs::rename(&tmp_path, a)?;
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn apply_elevated(locator: &VelopackLocator, bundle: &mut bundle::BundleZip<'_>, root_path: &Path) -> Result<()> {
+    let manifest = bundle.read_manifest()?;
+    let tmp_path_new = locator.get_temp_dir_rand16();
+    let tmp_path_old = get_sibling_temp_path(root_path);
+
+    let action: Result<()> = (|| {
+        fs::create_dir_all(&tmp_path_new)?;
+        info!("Extracting bundle to {:?}", &tmp_path_new);
+        bundle.extract_lib_contents_to_path(&tmp_path_new, |_| {})?;
+        shared::remove_quarantine_attribute(&tmp_path_new);
+        shared::verify_bundle_signature(root_path, &tmp_path_new)?;
+
+        if super::is_privileged_helper_installed(locator) {
+            match super::apply_with_privileged_helper(locator, &tmp_path_new) {
+                Ok(()) => return Ok(()),
+                Err(e) => warn!("Unable to apply with the privileged helper ({}), will ask the user to elevate instead.", e),
+            }
+        }
+
+        dialogs::ask_user_to_elevate(&manifest.title, &manifest.version.to_string())?;
+        let script = format!(
+            "do shell script \"mv -f '{}' '{}' && mv -f '{}' '{}' && rm -rf '{}'\" with administrator privileges",
+            &root_path.to_string_lossy(),
+            &tmp_path_old.to_string_lossy(),
+            &tmp_path_new.to_string_lossy(),
+            &root_path.to_string_lossy(),
+            &tmp_path_old.to_string_lossy()
+        );
+        info!("Running elevated process via osascript: {}", script);
+        let output = Command::new("osascript").arg("-e").arg(&script).status()?;
+        if output.success() {
+            info!("Bundle applied successfully via osascript.");
+            Ok(())
+        } else {
+            bail!("elevated process failed: exited with code: {}", output);
+        }
+    })();
+    let _ = fs::remove_dir_all(&tmp_path_new);
+    action
+}
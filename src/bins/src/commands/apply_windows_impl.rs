@@ -2,15 +2,22 @@ use crate::{
     dialogs,
     shared::{self},
     windows::locksmith,
+    windows::prerequisite::PrerequisiteResult,
     windows::splash,
 };
-use anyhow::{bail, Result};
+use super::{PlatformShortcuts, Shortcuts};
+use anyhow::{anyhow, bail, Result};
 use std::sync::mpsc;
 use std::{
     fs,
     path::{Path, PathBuf},
 };
-use velopack::{bundle::load_bundle_from_file, locator::VelopackLocator, constants};
+use velopack::{
+    bundle::{load_bundle_from_file, Manifest},
+    constants,
+    locator::VelopackLocator,
+    updater,
+};
 
 fn ropycopy<P1: AsRef<Path>, P2: AsRef<Path>>(source: &P1, dest: &P2) -> Result<()> {
     let source = source.as_ref();
@@ -47,19 +54,10 @@ pub fn apply_package_impl(old_locator: &VelopackLocator, package: &PathBuf, run_
     let new_app_manifest = bundle.read_manifest()?;
     let new_locator = old_locator.clone_self_with_new_manifest(&new_app_manifest);
 
-    let root_path = old_locator.get_root_dir();
-    let old_version = old_locator.get_manifest_version();
-    let new_version = new_locator.get_manifest_version();
-
-    info!("Applying package {} to current: {}", new_version, old_version);
-
-    if !crate::windows::prerequisite::prompt_and_install_all_missing(&new_app_manifest, Some(&old_version))? {
-        bail!("Stopping apply. Pre-requisites are missing and user cancelled.");
-    }
+    info!("Applying package {} to current: {}", new_locator.get_manifest_version(), old_locator.get_manifest_version());
+    ensure_prerequisites(old_locator, &new_app_manifest)?;
 
-    let current_dir = old_locator.get_current_bin_dir();
     let temp_path_new = old_locator.get_temp_dir_rand16();
-    let temp_path_old = old_locator.get_temp_dir_rand16();
 
     // open a dialog showing progress...
     let (mut tx, _) = mpsc::channel::<i16>();
@@ -77,7 +75,70 @@ pub fn apply_package_impl(old_locator: &VelopackLocator, package: &PathBuf, run_
         })?;
 
         let _ = tx.send(splash::MSG_INDEFINITE);
+        deploy_extracted_package(old_locator, &new_locator, &temp_path_new, run_hooks, &tx)
+    })();
+
+    let _ = tx.send(splash::MSG_CLOSE);
+    let _ = remove_dir_all::remove_dir_all(&temp_path_new);
+    action?;
+
+    // the package may ship a newer updater, which can only replace this one once the app itself has been updated
+    if let Err(e) = updater::stage_updater_from_bundle(&bundle, &new_locator.get_update_path()) {
+        warn!("Unable to update Update.exe ({}).", e);
+    }
+    Ok(new_locator)
+}
+
+/// Replaces the current version with an update which was already extracted by UpdateManager::stage_updates.
+/// Since the slow extraction step has already happened, this is fast enough to do while the app is starting.
+pub fn apply_staged_impl(old_locator: &VelopackLocator, run_hooks: bool) -> Result<VelopackLocator> {
+    let staged_dir = old_locator.get_staged_dir();
+    let new_app_manifest = old_locator.get_staged_manifest().ok_or_else(|| anyhow!("There is no staged update to apply."))?;
+    let new_locator = old_locator.clone_self_with_new_manifest(&new_app_manifest);
+
+    info!("Applying staged update {} to current: {}", new_locator.get_manifest_version(), old_locator.get_manifest_version());
+    ensure_prerequisites(old_locator, &new_app_manifest)?;
+
+    let (tx, _) = mpsc::channel::<i16>();
+    let action = deploy_extracted_package(old_locator, &new_locator, &staged_dir, run_hooks, &tx);
+    let _ = remove_dir_all::remove_dir_all(&staged_dir);
+    action?;
+
+    // an updater staged while the update was downloaded is swapped in now, since it may have been running at the time
+    if let Err(e) = updater::apply_staged_updater(&new_locator.get_update_path()) {
+        warn!("Unable to update Update.exe ({}).", e);
+    }
+    Ok(new_locator)
+}
+
+fn ensure_prerequisites(old_locator: &VelopackLocator, new_app_manifest: &Manifest) -> Result<()> {
+    match crate::windows::prerequisite::prompt_and_install_all_missing(new_app_manifest, Some(&old_locator.get_manifest_version()))? {
+        PrerequisiteResult::Ready => {
+            let _ = old_locator.set_restart_required(false);
+            Ok(())
+        }
+        PrerequisiteResult::Cancelled => bail!("Stopping apply. Pre-requisites are missing and user cancelled."),
+        PrerequisiteResult::RestartRequired => {
+            if let Err(e) = old_locator.set_restart_required(true) {
+                warn!("Failed to write restart required marker: {}", e);
+            }
+            Err(shared::RestartRequiredError.into())
+        }
+    }
+}
+
+fn deploy_extracted_package(
+    old_locator: &VelopackLocator,
+    new_locator: &VelopackLocator,
+    temp_path_new: &Path,
+    run_hooks: bool,
+    tx: &mpsc::Sender<i16>,
+) -> Result<()> {
+    let root_path = old_locator.get_root_dir();
+    let current_dir = old_locator.get_current_bin_dir();
+    let temp_path_old = old_locator.get_temp_dir_rand16();
 
+    let action: Result<()> = (|| {
         // second, run application hooks (but don't care if it fails)
         if run_hooks {
             crate::windows::run_hook(old_locator, constants::HOOK_CLI_OBSOLETE, 15);
@@ -87,7 +148,7 @@ pub fn apply_package_impl(old_locator: &VelopackLocator, package: &PathBuf, run_
 
         // third, we try _REALLY HARD_ to stop the package
         let _ = shared::force_stop_package(root_path);
-        if winsafe::IsWindows10OrGreater() == Ok(true) && !locksmith::close_processes_locking_dir(&old_locator) {
+        if winsafe::IsWindows10OrGreater() == Ok(true) && !locksmith::close_processes_locking_dir(old_locator) {
             bail!("Failed to close processes locking directory / user cancelled.");
         }
 
@@ -106,7 +167,7 @@ pub fn apply_package_impl(old_locator: &VelopackLocator, package: &PathBuf, run_
 
         if !requires_robocopy {
             // if we didn't need robocopy for the backup, we don't need it for the deploy hopefully
-            if let Err(e1) = fs::rename(&temp_path_new, &current_dir) {
+            if let Err(e1) = fs::rename(temp_path_new, &current_dir) {
                 warn!("Failed to rename temp_path_new to current_dir ({}). Retrying with robocopy...", e1);
                 requires_robocopy = true;
             }
@@ -134,13 +195,13 @@ pub fn apply_package_impl(old_locator: &VelopackLocator, package: &PathBuf, run_
         // from this point on, we're past the point of no return and should not bail
         // sixth, we write the uninstall entry
         if !old_locator.get_is_portable() {
-            if old_locator.get_manifest_id() != new_locator.get_manifest_id() {
+            if old_locator.get_install_id() != new_locator.get_install_id() {
                 info!("The app ID has changed, removing old uninstall registry entry.");
-                if let Err(e) = crate::windows::registry::remove_uninstall_entry(&old_locator) {
+                if let Err(e) = crate::windows::registry::remove_uninstall_entry(old_locator) {
                     warn!("Failed to remove old uninstall entry ({}).", e);
                 }
             }
-            if let Err(e) = crate::windows::registry::write_uninstall_entry(&new_locator) {
+            if let Err(e) = crate::windows::registry::write_uninstall_entry(new_locator) {
                 warn!("Failed to write new uninstall entry ({}).", e);
             }
         } else {
@@ -149,7 +210,7 @@ pub fn apply_package_impl(old_locator: &VelopackLocator, package: &PathBuf, run_
       
         // seventh, we run the post-install hooks
         if run_hooks {
-            crate::windows::run_hook(&new_locator, constants::HOOK_CLI_UPDATED, 15);
+            crate::windows::run_hook(new_locator, constants::HOOK_CLI_UPDATED, 15);
         } else {
             info!("Skipping --veloapp-updated hook.");
         }
@@ -158,11 +219,11 @@ pub fn apply_package_impl(old_locator: &VelopackLocator, package: &PathBuf, run_
         // should try and remove the temp dirs before recalculating the shortcuts,
         // because windows may try to use the "Distributed Link Tracking and Object Identifiers (DLT) service"
         // to update the shortcut to point at the temp/renamed location
-        let _ = remove_dir_all::remove_dir_all(&temp_path_new);
+        let _ = remove_dir_all::remove_dir_all(temp_path_new);
         let _ = remove_dir_all::remove_dir_all(&temp_path_old);
 
         if !old_locator.get_is_portable() {
-            crate::windows::create_or_update_manifest_lnks(&new_locator, Some(old_locator));
+            PlatformShortcuts.create_or_update_manifest_shortcuts(new_locator, Some(old_locator));
         }
 
         // done!
@@ -170,9 +231,6 @@ pub fn apply_package_impl(old_locator: &VelopackLocator, package: &PathBuf, run_
         Ok(())
     })();
 
-    let _ = tx.send(splash::MSG_CLOSE);
-    let _ = remove_dir_all::remove_dir_all(&temp_path_new);
     let _ = remove_dir_all::remove_dir_all(&temp_path_old);
-    action?;
-    Ok(new_locator)
+    action
 }
@@ -0,0 +1,24 @@
+use anyhow::Result;
+use velopack::{locator::VelopackLocator, sources::AutoSource, UpdateCheck, UpdateManager};
+
+pub fn check(locator: &VelopackLocator, source: &str, download: bool) -> Result<()> {
+    let manager = UpdateManager::new(AutoSource::new(source), None, Some(locator.get_paths()))?;
+    let update = match manager.check_for_updates()? {
+        UpdateCheck::UpdateAvailable(update) => update,
+        UpdateCheck::UpdatesManagedExternally => {
+            info!("Updates for this app are managed by its sandbox, nothing to do.");
+            return Ok(());
+        }
+        _ => {
+            info!("No update is available.");
+            return Ok(());
+        }
+    };
+
+    info!("Update available: {} -> {}", locator.get_manifest_version(), update.TargetFullRelease.Version);
+    if download {
+        manager.download_updates(&update, None)?;
+        info!("Update {} has been downloaded, and can be applied the next time the app starts.", update.TargetFullRelease.Version);
+    }
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use anyhow::{bail, Result};
+use regex::Regex;
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+/// How the files created while packing are signed, these are the same options vpk accepts.
+#[derive(Debug, Clone)]
+pub struct SigningOptions {
+    /// A custom signing command (eg. for rcodesign or the azure trusted signing CLI), where {{file}} is substituted with
+    /// one file, or {{file...}} with a batch of files.
+    pub sign_template: Option<String>,
+    /// The parameters passed to 'signtool sign', which must be on the PATH (Windows only).
+    pub sign_params: Option<String>,
+    /// The number of signing commands run at once with {{file}}, or the number of files per command otherwise.
+    pub sign_parallel: usize,
+    /// Only signs executables, and skips libraries.
+    pub sign_skip_dll: bool,
+}
+
+impl Default for SigningOptions {
+    fn default() -> Self {
+        SigningOptions { sign_template: None, sign_params: None, sign_parallel: 10, sign_skip_dll: false }
+    }
+}
+
+impl SigningOptions {
+    /// Returns true if a signing command was configured.
+    pub fn is_enabled(&self) -> bool {
+        self.sign_template.is_some() || self.sign_params.is_some()
+    }
+
+    /// Returns true if a file should be signed, based on its extension.
+    pub fn should_sign_file(&self, path: &Path) -> bool {
+        let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        extension == "exe" || (extension == "dll" && !self.sign_skip_dll)
+    }
+}
+
+/// Signs a list of files with the configured signing command. Files which are already signed are skipped, so third
+/// party libraries keep their own signature. Every file is attempted even if some fail, and the failures are returned
+/// together in one error.
+pub fn sign_files(options: &SigningOptions, files: &[PathBuf]) -> Result<()> {
+    if !options.is_enabled() {
+        return Ok(());
+    }
+
+    let pending: Vec<PathBuf> = files
+        .iter()
+        .filter(|f| {
+            let signed = super::has_authenticode_signature(f);
+            if signed {
+                debug!("{:?} is already signed, skipping...", f);
+            }
+            !signed
+        })
+        .cloned()
+        .collect();
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let parallel = options.sign_parallel.max(1);
+    let (template, batches, threads) = match (&options.sign_template, &options.sign_params) {
+        (Some(_), Some(_)) => bail!("A sign template and signtool parameters can not be used together."),
+        (Some(template), None) if template.contains("{{file...}}") => {
+            let batches: Vec<Vec<PathBuf>> = pending.chunks(parallel).map(|c| c.to_vec()).collect();
+            (template.replace("{{file...}}", "{{file}}"), batches, 1)
+        }
+        (Some(template), None) if template.contains("{{file}}") => {
+            (template.clone(), pending.iter().map(|f| vec![f.clone()]).collect(), parallel)
+        }
+        (Some(_), None) => {
+            bail!("The sign template must contain '{{{{file}}}}' or '{{{{file...}}}}', which are substituted by one, or many files.")
+        }
+        (None, Some(params)) => {
+            if !cfg!(target_os = "windows") {
+                bail!("Signing with signtool is only supported on Windows, use a sign template instead.");
+            }
+            let batches: Vec<Vec<PathBuf>> = pending.chunks(parallel).map(|c| c.to_vec()).collect();
+            (format!("signtool sign {} {{{{file}}}}", params), batches, 1)
+        }
+        (None, None) => unreachable!(),
+    };
+
+    info!("Code-signing {} file(s) with {} command(s), {} at a time.", pending.len(), batches.len(), threads.min(batches.len()));
+    let next = AtomicUsize::new(0);
+    let signed = AtomicUsize::new(0);
+    let failures: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    std::thread::scope(|scope| {
+        for _ in 0..threads.min(batches.len()) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                if index >= batches.len() {
+                    break;
+                }
+                let batch = &batches[index];
+                let file_args: Vec<String> = batch.iter().map(|f| format!("\"{}\"", f.to_string_lossy())).collect();
+                let command = template.replace("{{file}}", &file_args.join(" "));
+                match run_signing_command(&command) {
+                    Ok(()) => {
+                        let count = signed.fetch_add(batch.len(), Ordering::SeqCst) + batch.len();
+                        info!("Code-signed {}/{} files", count, pending.len());
+                    }
+                    Err(e) => {
+                        let names: Vec<String> = batch.iter().map(|f| f.to_string_lossy().to_string()).collect();
+                        error!("Failed to sign {}: {}", names.join(", "), e);
+                        failures.lock().unwrap().push(format!("{}: {}", names.join(", "), e));
+                    }
+                }
+            });
+        }
+    });
+
+    let failures = failures.into_inner().unwrap();
+    if !failures.is_empty() {
+        bail!("Signing failed for {} of {} command(s):\n{}", failures.len(), batches.len(), failures.join("\n"));
+    }
+    Ok(())
+}
+
+/// Runs a signing command with the shell, so templates can use quoting and environment variables.
+fn run_signing_command(command: &str) -> Result<()> {
+    debug!("Running signing command: {}", hide_passwords(command));
+
+    #[cfg(target_os = "windows")]
+    let output = {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        Command::new("cmd.exe").raw_arg("/S /C \"").raw_arg(command).raw_arg("\"").creation_flags(CREATE_NO_WINDOW).output()?
+    };
+    #[cfg(not(target_os = "windows"))]
+    let output = Command::new("/bin/sh").arg("-c").arg(command).output()?;
+
+    if !output.status.success() {
+        let text = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+        bail!("The signing command exited with {} ({}).", output.status, hide_passwords(text.trim()));
+    }
+    Ok(())
+}
+
+/// Hides the password argument of signtool, so it is not written to the log.
+fn hide_passwords(text: &str) -> String {
+    Regex::new(r"/p\s+[^\s]+").unwrap().replace_all(text, "/p ********").to_string()
+}
+
+#[cfg(unix)]
+#[test]
+fn test_sign_files_runs_template_and_collects_failures() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    let files: Vec<PathBuf> = ["a.exe", "b.exe", "c.exe"].iter().map(|n| dir.join(n)).collect();
+    files.iter().for_each(|f| std::fs::write(f, b"MZ").unwrap());
+
+    let options = SigningOptions { sign_template: Some("echo signed >> {{file}}".into()), sign_parallel: 2, ..Default::default() };
+    sign_files(&options, &files).unwrap();
+    for f in &files {
+        assert_eq!(std::fs::read_to_string(f).unwrap(), "MZsigned\n");
+    }
+
+    let options = SigningOptions { sign_template: Some("case {{file}} in *b.exe) exit 1;; esac".into()), ..Default::default() };
+    let err = sign_files(&options, &files).unwrap_err().to_string();
+    assert!(err.contains("1 of 3"));
+    assert!(err.contains("b.exe"));
+
+    let options = SigningOptions { sign_template: Some("sign-it".into()), ..Default::default() };
+    assert!(sign_files(&options, &files).is_err());
+    assert_eq!(hide_passwords("signtool sign /f cert.pfx /p secret /fd sha256"), "signtool sign /f cert.pfx /p ******** /fd sha256");
+}
@@ -0,0 +1,383 @@
+use crate::shared;
+use anyhow::{anyhow, bail, Result};
+use std::os::unix::fs::PermissionsExt;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command as Process,
+    sync::Once,
+};
+use velopack::{escape::escape_desktop_exec_arg, locator::VelopackLocator};
+
+/// The places a desktop entry for the app can be installed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryLocation {
+    /// The applications directory, which application launchers and menus are built from.
+    Applications,
+    /// The user's desktop directory.
+    Desktop,
+    /// The XDG autostart directory, so the app is started when the user logs in.
+    Autostart,
+}
+
+impl EntryLocation {
+    pub const ALL: [EntryLocation; 3] = [EntryLocation::Applications, EntryLocation::Desktop, EntryLocation::Autostart];
+
+    fn get_entry_path(&self, locator: &VelopackLocator) -> Result<PathBuf> {
+        let dir = match self {
+            EntryLocation::Applications => get_data_dir()?.join("applications"),
+            EntryLocation::Desktop => get_desktop_dir()?,
+            EntryLocation::Autostart => get_autostart_dir()?,
+        };
+        Ok(dir.join(format!("{}.desktop", locator.get_install_id())))
+    }
+}
+
+/// Installs the .desktop entry and icon from the running AppImage into the XDG data directory, so the app shows up in
+/// application launchers. When running as root they are installed for every user, otherwise (or on immutable distributions
+/// such as Fedora Silverblue and NixOS) only for the current user.
+pub fn install_desktop_entry(locator: &VelopackLocator) -> Result<()> {
+    if locator.get_portable_dir().is_some() {
+        bail!("A desktop entry can not be installed for a portable app.");
+    }
+    let appimage_path = shared::get_appimage_path(locator);
+    install_from_dir(locator, &locator.get_root_dir(), &appimage_path, &[EntryLocation::Applications])
+}
+
+/// Removes the .desktop entries and icons written by [`install_desktop_entry`] and [`install_desktop_entries_from_appimage`].
+pub fn uninstall_desktop_entry(locator: &VelopackLocator) -> Result<()> {
+    let mut icon_name = None;
+    for location in EntryLocation::ALL {
+        let entry_path = location.get_entry_path(locator)?;
+        if let Ok(contents) = fs::read_to_string(&entry_path) {
+            if icon_name.is_none() {
+                icon_name = Some(get_desktop_value(&contents, "Icon").unwrap_or_else(|| locator.get_install_id()));
+            }
+            fs::remove_file(&entry_path)?;
+            info!("Removed desktop entry {:?}", entry_path);
+        }
+    }
+
+    let icon_name = match icon_name {
+        Some(name) => name,
+        None => {
+            info!("No desktop entries are installed.");
+            return Ok(());
+        }
+    };
+
+    let data_dir = get_data_dir()?;
+    let hicolor = data_dir.join("icons").join("hicolor");
+    if let Ok(sizes) = fs::read_dir(&hicolor) {
+        for size in sizes.flatten() {
+            for ext in ["png", "svg"] {
+                let icon_path = size.path().join("apps").join(format!("{}.{}", icon_name, ext));
+                if icon_path.exists() {
+                    fs::remove_file(&icon_path)?;
+                    info!("Removed icon {:?}", icon_path);
+                }
+            }
+        }
+    }
+
+    refresh_caches(&data_dir);
+    Ok(())
+}
+
+/// Re-installs the desktop entries from a newly applied AppImage, in every location an entry was installed to for the
+/// previous version.
+pub fn update_desktop_entry(locator: &VelopackLocator, appimage_path: &Path) -> Result<()> {
+    let mut locations = Vec::new();
+    for location in EntryLocation::ALL {
+        if location.get_entry_path(locator)?.exists() {
+            locations.push(location);
+        }
+    }
+    if locations.is_empty() {
+        return Ok(());
+    }
+    install_desktop_entries_from_appimage(locator, appimage_path, &locations)
+}
+
+/// Installs desktop entries to each of the locations, using the .desktop file and icon inside an AppImage which does not
+/// need to be mounted (eg. one which was just applied).
+pub fn install_desktop_entries_from_appimage(locator: &VelopackLocator, appimage_path: &Path, locations: &[EntryLocation]) -> Result<()> {
+    // the AppImage may not be mounted, so the files we need are extracted from it with the AppImage runtime.
+    let temp_dir = locator.get_temp_dir_rand16();
+    fs::create_dir_all(&temp_dir)?;
+    let result = (|| {
+        shared::extract_from_appimage(appimage_path, &temp_dir, "*.desktop")?;
+        let extracted = temp_dir.join("squashfs-root");
+        let (_, contents) = find_desktop_file(&extracted, locator)?;
+        let icon_name = get_desktop_value(&contents, "Icon").unwrap_or_else(|| locator.get_install_id());
+        shared::extract_from_appimage(appimage_path, &temp_dir, &format!("{}.*", icon_name))?;
+        install_from_dir(locator, &extracted, appimage_path, locations)
+    })();
+    let _ = remove_dir_all::remove_dir_all(&temp_dir);
+    result
+}
+
+/// Removes the desktop entries in each of the locations, leaving the icon in place for any other entries.
+pub fn remove_desktop_entries(locator: &VelopackLocator, locations: &[EntryLocation]) -> Result<()> {
+    for location in locations {
+        let entry_path = location.get_entry_path(locator)?;
+        if entry_path.exists() {
+            fs::remove_file(&entry_path)?;
+            info!("Removed desktop entry {:?}", entry_path);
+        }
+    }
+    Ok(())
+}
+
+/// Shadows the desktop entries which launch an AppImage that could not be updated in place (eg. one in the read-only
+/// system image of an immutable distribution) with entries for the current user which launch the updated copy instead.
+/// An entry in the user's data directory takes precedence over a system entry with the same name, so launchers start the
+/// new version. Returns the entries which were written.
+pub fn redirect_desktop_entries(locator: &VelopackLocator, old_appimage: &Path, new_appimage: &Path) -> Result<Vec<PathBuf>> {
+    let data_dir = get_data_dir()?;
+    let system_dirs = std::env::var("XDG_DATA_DIRS").ok().filter(|d| !d.is_empty());
+    let system_dirs = system_dirs.unwrap_or_else(|| "/usr/local/share:/usr/share".to_owned());
+    let mut search_dirs = vec![data_dir.join("applications")];
+    search_dirs.extend(system_dirs.split(':').map(PathBuf::from).filter(|p| p.is_absolute()).map(|p| p.join("applications")));
+    let version = locator.get_manifest_version_full_string();
+    let written = redirect_entries_in(&search_dirs, &data_dir.join("applications"), old_appimage, new_appimage, &version)?;
+    if !written.is_empty() {
+        refresh_caches(&data_dir);
+    }
+    Ok(written)
+}
+
+fn redirect_entries_in(
+    search_dirs: &[PathBuf],
+    target_dir: &Path,
+    old_appimage: &Path,
+    new_appimage: &Path,
+    version: &str,
+) -> Result<Vec<PathBuf>> {
+    let old_appimage = old_appimage.to_string_lossy();
+    let mut seen = Vec::new();
+    let mut written = Vec::new();
+    for dir in search_dirs {
+        let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+            Ok(entries) => entries.flatten().map(|e| e.path()).filter(|p| p.extension().is_some_and(|e| e == "desktop")).collect(),
+            Err(_) => continue,
+        };
+        paths.sort();
+        for path in paths {
+            // only the first entry with a given name is used by launchers, which is the one in the user's data directory
+            let file_name = match path.file_name() {
+                Some(name) if !seen.contains(&name.to_owned()) => name.to_owned(),
+                _ => continue,
+            };
+            seen.push(file_name.clone());
+            let contents = match fs::read_to_string(&path) {
+                Ok(contents) => contents,
+                Err(_) => continue,
+            };
+            if !get_desktop_value(&contents, "Exec").is_some_and(|exec| exec.contains(old_appimage.as_ref())) {
+                continue;
+            }
+            let target = target_dir.join(&file_name);
+            fs::create_dir_all(target_dir)?;
+            fs::write(&target, create_desktop_entry(&contents, new_appimage, version))?;
+            info!("Redirected desktop entry {:?} to {:?}", path, target);
+            written.push(target);
+        }
+    }
+    Ok(written)
+}
+
+fn install_from_dir(locator: &VelopackLocator, app_dir: &Path, appimage_path: &Path, locations: &[EntryLocation]) -> Result<()> {
+    let data_dir = get_data_dir()?;
+    let (desktop_path, contents) = find_desktop_file(app_dir, locator)?;
+    info!("Installing desktop entry from {:?}", desktop_path);
+
+    let icon_name = get_desktop_value(&contents, "Icon").unwrap_or_else(|| locator.get_install_id());
+    let entry = create_desktop_entry(&contents, appimage_path, &locator.get_manifest_version_full_string());
+    for location in locations {
+        let entry_path = location.get_entry_path(locator)?;
+        fs::create_dir_all(entry_path.parent().unwrap())?;
+        fs::write(&entry_path, &entry)?;
+        if *location == EntryLocation::Desktop {
+            // file managers refuse to launch desktop entries on the desktop which are not executable
+            fs::set_permissions(&entry_path, fs::Permissions::from_mode(0o755))?;
+        }
+        info!("Wrote desktop entry to {:?}", entry_path);
+    }
+
+    if let Some((icon_path, size)) = find_icon(app_dir, &icon_name) {
+        let ext = icon_path.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
+        let target = data_dir.join("icons").join("hicolor").join(size).join("apps").join(format!("{}.{}", icon_name, ext));
+        fs::create_dir_all(target.parent().unwrap())?;
+        fs::copy(&icon_path, &target)?;
+        info!("Wrote icon to {:?}", target);
+    } else {
+        warn!("Unable to find icon '{}' in {:?}, the desktop entry will not have an icon.", icon_name, app_dir);
+    }
+
+    refresh_caches(&data_dir);
+    Ok(())
+}
+
+/// Returns true if desktop entries should be installed for every user, which is the case when running as root, unless
+/// the system directories are managed by an immutable distribution.
+fn is_system_wide() -> bool {
+    if unsafe { libc::geteuid() } != 0 {
+        return false;
+    }
+    match shared::get_immutable_system_reason() {
+        Some(reason) => {
+            static WARN_ONCE: Once = Once::new();
+            WARN_ONCE.call_once(|| {
+                warn!("System-wide installs are not supported because {}, installing for the current user instead.", reason);
+            });
+            false
+        }
+        None => true,
+    }
+}
+
+fn get_data_dir() -> Result<PathBuf> {
+    if is_system_wide() {
+        return Ok(PathBuf::from("/usr/local/share"));
+    }
+    if let Some(dir) = std::env::var_os("XDG_DATA_HOME").map(PathBuf::from).filter(|p| p.is_absolute()) {
+        return Ok(dir);
+    }
+    Ok(velopack::locator::get_home_dir()?.join(".local").join("share"))
+}
+
+fn get_desktop_dir() -> Result<PathBuf> {
+    // the desktop directory is localized on many distributions, and xdg-user-dir knows what it is called
+    if let Ok(output) = Process::new("xdg-user-dir").arg("DESKTOP").output() {
+        let dir = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+        if output.status.success() && dir.is_absolute() {
+            return Ok(dir);
+        }
+    }
+    Ok(velopack::locator::get_home_dir()?.join("Desktop"))
+}
+
+fn get_autostart_dir() -> Result<PathBuf> {
+    if is_system_wide() {
+        return Ok(PathBuf::from("/etc/xdg/autostart"));
+    }
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from).filter(|p| p.is_absolute()) {
+        return Ok(dir.join("autostart"));
+    }
+    Ok(velopack::locator::get_home_dir()?.join(".config").join("autostart"))
+}
+
+fn find_desktop_file(app_dir: &Path, locator: &VelopackLocator) -> Result<(PathBuf, String)> {
+    // vpk names the desktop file after the pack id, but an AppDir provided by the user may use any name
+    let mut path = app_dir.join(format!("{}.desktop", locator.get_install_id()));
+    if !path.exists() {
+        path = fs::read_dir(app_dir)?
+            .flatten()
+            .map(|e| e.path())
+            .find(|p| p.extension().map(|e| e == "desktop").unwrap_or(false))
+            .ok_or_else(|| anyhow!("Unable to find a .desktop file in {:?}", app_dir))?;
+    }
+    let contents = fs::read_to_string(&path)?;
+    Ok((path, contents))
+}
+
+fn find_icon(app_dir: &Path, icon_name: &str) -> Option<(PathBuf, String)> {
+    let svg = app_dir.join(format!("{}.svg", icon_name));
+    if svg.exists() {
+        return Some((svg, "scalable".to_owned()));
+    }
+    let png = app_dir.join(format!("{}.png", icon_name));
+    let header = fs::read(&png).ok()?;
+    let (width, height) = get_png_size(&header)?;
+    Some((png, format!("{}x{}", width, height)))
+}
+
+fn get_png_size(data: &[u8]) -> Option<(u32, u32)> {
+    if data.len() < 24 || &data[0..8] != b"\x89PNG\r\n\x1a\n" || &data[12..16] != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes(data[16..20].try_into().ok()?);
+    let height = u32::from_be_bytes(data[20..24].try_into().ok()?);
+    Some((width, height))
+}
+
+fn refresh_caches(data_dir: &Path) {
+    let commands = [
+        ("update-desktop-database", vec![data_dir.join("applications")]),
+        ("gtk-update-icon-cache", vec![PathBuf::from("-f"), PathBuf::from("-t"), data_dir.join("icons").join("hicolor")]),
+    ];
+    for (program, args) in commands {
+        if let Err(e) = Process::new(program).args(args).output() {
+            debug!("Unable to run {} ({}).", program, e);
+        }
+    }
+}
+
+fn get_desktop_value(contents: &str, key: &str) -> Option<String> {
+    let prefix = format!("{}=", key);
+    contents.lines().find_map(|l| l.trim().strip_prefix(&prefix).map(|v| v.trim().to_owned())).filter(|v| !v.is_empty())
+}
+
+fn create_desktop_entry(contents: &str, appimage_path: &Path, version: &str) -> String {
+    let appimage = appimage_path.to_string_lossy();
+    let mut entry = String::new();
+    let mut in_main_group = false;
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            in_main_group = trimmed == "[Desktop Entry]";
+        } else if in_main_group && (trimmed.starts_with("TryExec=") || trimmed.starts_with("X-AppImage-Version=")) {
+            continue;
+        } else if let Some(exec) = trimmed.strip_prefix("Exec=") {
+            // keep any field codes or arguments after the executable, which may exist in an entry provided by the user
+            let args = exec.split_once(' ').map(|(_, a)| format!(" {}", a)).unwrap_or_default();
+            entry.push_str(&format!("Exec={}{}\n", escape_desktop_exec_arg(&appimage), args));
+            if in_main_group {
+                entry.push_str(&format!("TryExec={}\n", appimage));
+                entry.push_str(&format!("X-AppImage-Version={}\n", version));
+            }
+            continue;
+        }
+        entry.push_str(line);
+        entry.push('\n');
+    }
+    entry
+}
+
+#[test]
+fn test_create_desktop_entry_points_to_appimage() {
+    let contents = "[Desktop Entry]\nType=Application\nName=My App\nIcon=MyApp\nExec=MyApp\nTryExec=MyApp\nCategories=Utility;\n\n\
+                    [Desktop Action New]\nName=New Window\nExec=MyApp --new %U\n";
+    let entry = create_desktop_entry(contents, Path::new("/home/me/Apps/My $App.AppImage"), "1.2.0");
+    assert_eq!(
+        entry,
+        "[Desktop Entry]\nType=Application\nName=My App\nIcon=MyApp\nExec=\"/home/me/Apps/My \\\\$App.AppImage\"\n\
+         TryExec=/home/me/Apps/My $App.AppImage\nX-AppImage-Version=1.2.0\nCategories=Utility;\n\n\
+         [Desktop Action New]\nName=New Window\nExec=\"/home/me/Apps/My \\\\$App.AppImage\" --new %U\n"
+    );
+    assert_eq!(get_desktop_value(contents, "Icon"), Some("MyApp".to_owned()));
+
+    let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\x01\0\0\0\x01\0";
+    assert_eq!(get_png_size(png), Some((256, 256)));
+}
+
+#[test]
+fn test_redirect_entries_shadows_system_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let (user, system) = (dir.path().join("user"), dir.path().join("system"));
+    fs::create_dir_all(&system).unwrap();
+    let old = Path::new("/usr/lib/myapp/MyApp.AppImage");
+    fs::write(system.join("myapp.desktop"), "[Desktop Entry]\nName=My App\nExec=/usr/lib/myapp/MyApp.AppImage %U\n").unwrap();
+    fs::write(system.join("other.desktop"), "[Desktop Entry]\nName=Other\nExec=/usr/bin/other\n").unwrap();
+
+    let new = Path::new("/home/me/.local/bin/MyApp.AppImage");
+    let written = redirect_entries_in(&[user.clone(), system.clone()], &user, old, new, "2.0.0").unwrap();
+    assert_eq!(written, vec![user.join("myapp.desktop")]);
+    let entry = fs::read_to_string(user.join("myapp.desktop")).unwrap();
+    assert!(entry.contains("Exec=\"/home/me/.local/bin/MyApp.AppImage\" %U\n"));
+    assert!(entry.contains("X-AppImage-Version=2.0.0\n"));
+
+    // the user entry now takes precedence, and no longer launches the old AppImage
+    assert!(redirect_entries_in(&[user.clone(), system], &user, old, new, "2.0.0").unwrap().is_empty());
+}
@@ -3,15 +3,18 @@ use crate::{
     shared::{self},
     windows,
 };
+use crate::windows::prerequisite::PrerequisiteResult;
 use velopack::bundle::BundleZip;
 use velopack::locator::*;
 use velopack::constants;
 
+use super::{PlatformShortcuts, Shortcuts};
 use anyhow::{anyhow, bail, Result};
 use pretty_bytes_rust::pretty_bytes;
 use std::{
     fs::{self},
     path::{Path, PathBuf},
+    time::Duration,
 };
 use ::windows::core::PCWSTR;
 use ::windows::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
@@ -30,11 +33,18 @@ pub fn install(pkg: &mut BundleZip, install_to: Option<&PathBuf>, start_args: Op
     info!("    Package Machine Architecture: {}", &app.machine_architecture);
     info!("    Package Runtime Dependencies: {}", &app.runtime_dependencies);
 
-    let _mutex = shared::retry_io(|| windows::create_global_mutex(&app.id))?;
+    let _mutex = shared::retry_io(|| windows::create_global_mutex(&app.get_install_id()))?;
 
-    if !windows::prerequisite::prompt_and_install_all_missing(&app, None)? {
-        info!("Cancelling setup. Pre-requisites not installed.");
-        return Ok(());
+    match windows::prerequisite::prompt_and_install_all_missing(&app, None)? {
+        PrerequisiteResult::Ready => {}
+        PrerequisiteResult::Cancelled => {
+            info!("Cancelling setup. Pre-requisites not installed.");
+            return Ok(());
+        }
+        PrerequisiteResult::RestartRequired => {
+            info!("Cancelling setup. Pre-requisites require a system restart.");
+            return Err(shared::RestartRequiredError.into());
+        }
     }
 
     info!("Determining install directory...");
@@ -42,7 +52,7 @@ pub fn install(pkg: &mut BundleZip, install_to: Option<&PathBuf>, start_args: Op
         (install_to.unwrap().clone(), false)
     } else {
         let appdata = windows::known_path::get_local_app_data()?;
-        (Path::new(&appdata).join(&app.id), true)
+        (Path::new(&appdata).join(app.get_install_id()), true)
     };
 
     // path needs to exist for future operations (disk space etc)
@@ -78,17 +88,22 @@ pub fn install(pkg: &mut BundleZip, install_to: Option<&PathBuf>, start_args: Op
     );
 
     // does this app support this OS / architecture?
-    if !app.os_min_version.is_empty() && !windows::is_os_version_or_greater(&app.os_min_version)? {
-        bail!("This application requires Windows {} or later.", &app.os_min_version);
-    }
+    windows::check_os_version_supported(&app.os_min_version, &app.os_min_server_version)?;
 
     if !app.machine_architecture.is_empty() && !windows::is_cpu_architecture_supported(&app.machine_architecture)? {
         bail!("This application ({}) does not support your CPU architecture.", &app.machine_architecture);
     }
 
+    let lock_path = root_path.join(constants::ROOT_DIR_LOCK_FILE);
     let mut root_path_renamed = String::new();
     // does the target directory exist and have files? (eg. already installed)
     if !shared::is_dir_empty(&root_path) {
+        // an installer or updater could be running for the existing install, which must not be overwritten underneath it.
+        // the lock is released straight away, because the directory can not be renamed while the lock file is open
+        if lock_path.exists() {
+            drop(shared::lock_file(&lock_path, Duration::ZERO)?);
+        }
+
         // the target directory is not empty, and not dead
         if !dialogs::show_overwrite_repair_dialog(&app, &root_path, root_is_default) {
             // user cancelled overwrite prompt
@@ -114,6 +129,7 @@ pub fn install(pkg: &mut BundleZip, install_to: Option<&PathBuf>, start_args: Op
 
     info!("Preparing and cleaning installation directory...");
     remove_dir_all::ensure_empty_dir(&root_path)?;
+    let lock = shared::lock_file(&lock_path, Duration::ZERO)?;
 
     let tx = if dialogs::get_silent() {
         info!("Will not show splash because silent mode is on.");
@@ -127,6 +143,7 @@ pub fn install(pkg: &mut BundleZip, install_to: Option<&PathBuf>, start_args: Op
 
     let install_result = install_impl(pkg, &root_path, &tx, start_args);
     let _ = tx.send(windows::splash::MSG_CLOSE);
+    drop(lock);
 
     if install_result.is_ok() {
         info!("Installation completed successfully!");
@@ -153,7 +170,7 @@ fn install_impl(pkg: &mut BundleZip, root_path: &PathBuf, tx: &std::sync::mpsc::
 
     let app_manifest = pkg.read_manifest()?;
     let paths = create_config_from_root_dir(root_path);
-    let locator = VelopackLocator::new(paths, app_manifest);
+    let locator = VelopackLocator::new(paths, app_manifest).clone_self_with_packages_dir_override(None);
 
     // all application paths
     let updater_path = locator.get_update_path();
@@ -183,12 +200,12 @@ fn install_impl(pkg: &mut BundleZip, root_path: &PathBuf, tx: &std::sync::mpsc::
 
     if locator.get_manifest_shortcut_locations() != ShortcutLocationFlags::NONE {
         info!("Creating shortcuts...");
-        windows::create_or_update_manifest_lnks(&locator, None);
+        PlatformShortcuts.create_or_update_manifest_shortcuts(&locator, None);
     }
 
     info!("Starting process install hook");
     if !windows::run_hook(&locator, constants::HOOK_CLI_INSTALL, 30) {
-        let setup_name = format!("{} Setup {}", locator.get_manifest_title(), locator.get_manifest_id());
+        let setup_name = format!("{} Setup {}", locator.get_manifest_title(), locator.get_install_id());
         dialogs::show_warn(
             &setup_name,
             None,
@@ -0,0 +1,105 @@
+use anyhow::{bail, Result};
+use std::{fs, path::PathBuf, process::Command as Process, time::Duration};
+use velopack::{escape::escape_xml, locator::VelopackLocator};
+
+pub fn install_launch_agent(locator: &VelopackLocator, source: &str, interval: Duration, download: bool) -> Result<()> {
+    if locator.get_portable_dir().is_some() {
+        bail!("A background update agent can not be installed for a portable app.");
+    }
+    let label = get_agent_label(locator);
+    let plist_path = get_agent_plist_path(&label)?;
+
+    let mut args = vec![locator.get_update_path_as_string(), "check".to_owned(), "--source".to_owned(), source.to_owned()];
+    if download {
+        args.push("--download".to_owned());
+    }
+
+    // an agent which is already loaded must be unloaded first, or launchd will keep using the old arguments and interval
+    if plist_path.exists() {
+        bootout_agent(&label);
+    }
+
+    fs::create_dir_all(plist_path.parent().unwrap())?;
+    fs::write(&plist_path, create_agent_plist(&label, &args, interval))?;
+    info!("Wrote launch agent to {:?}", &plist_path);
+
+    let output = Process::new("/bin/launchctl").arg("bootstrap").arg(get_gui_domain()).arg(&plist_path).output()?;
+    if !output.status.success() {
+        bail!("Failed to load launch agent: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    info!("Launch agent '{}' will check for updates every {} seconds.", label, interval.as_secs());
+    Ok(())
+}
+
+pub fn uninstall_launch_agent(locator: &VelopackLocator) -> Result<()> {
+    let label = get_agent_label(locator);
+    let plist_path = get_agent_plist_path(&label)?;
+    if !plist_path.exists() {
+        info!("Launch agent '{}' is not installed.", label);
+        return Ok(());
+    }
+
+    bootout_agent(&label);
+    fs::remove_file(&plist_path)?;
+    info!("Removed launch agent '{}'.", label);
+    Ok(())
+}
+
+fn get_agent_label(locator: &VelopackLocator) -> String {
+    format!("{}.velopack-update", locator.get_install_id())
+}
+
+fn get_agent_plist_path(label: &str) -> Result<PathBuf> {
+    let home = velopack::locator::get_home_dir()?;
+    Ok(home.join("Library").join("LaunchAgents").join(format!("{}.plist", label)))
+}
+
+fn get_gui_domain() -> String {
+    format!("gui/{}", unsafe { libc::getuid() })
+}
+
+fn bootout_agent(label: &str) {
+    let target = format!("{}/{}", get_gui_domain(), label);
+    match Process::new("/bin/launchctl").arg("bootout").arg(&target).output() {
+        Ok(output) if output.status.success() => info!("Unloaded launch agent '{}'.", label),
+        Ok(output) => debug!("Launch agent '{}' was not loaded ({}).", label, String::from_utf8_lossy(&output.stderr).trim()),
+        Err(e) => warn!("Unable to run launchctl ({}).", e),
+    }
+}
+
+fn create_agent_plist(label: &str, args: &[String], interval: Duration) -> String {
+    let args: String = args.iter().map(|a| format!("        <string>{}</string>\n", escape_xml(a))).collect();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{}</string>
+    <key>ProgramArguments</key>
+    <array>
+{}    </array>
+    <key>StartInterval</key>
+    <integer>{}</integer>
+    <key>ProcessType</key>
+    <string>Background</string>
+    <key>LowPriorityIO</key>
+    <true/>
+</dict>
+</plist>
+"#,
+        escape_xml(label),
+        args,
+        interval.as_secs()
+    )
+}
+
+#[test]
+fn test_create_agent_plist_escapes_arguments() {
+    let args = vec!["/Applications/My App.app/Contents/MacOS/UpdateMac".to_owned(), "https://example.com/?a=1&b=2".to_owned()];
+    let plist = create_agent_plist("com.example.app.velopack-update", &args, Duration::from_secs(3600));
+    assert!(plist.contains("<string>com.example.app.velopack-update</string>"));
+    assert!(plist.contains("        <string>/Applications/My App.app/Contents/MacOS/UpdateMac</string>\n"));
+    assert!(plist.contains("<string>https://example.com/?a=1&amp;b=2</string>"));
+    assert!(plist.contains("<integer>3600</integer>"));
+}
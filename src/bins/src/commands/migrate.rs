@@ -0,0 +1,176 @@
+use crate::{
+    shared::{self},
+    windows::{self, registry::RegistryScope},
+};
+use velopack::{constants, locator::*};
+
+use super::{PlatformShortcuts, Shortcuts};
+use anyhow::{anyhow, bail, Result};
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Moves an app between a per-user install (in %LocalAppData%) and a per-machine install (in Program Files), so the install
+/// scope can be changed without a reinstall. The app root is copied to the new location, including any data the app keeps
+/// next to itself, then the uninstall entry is moved between HKCU and HKLM, shortcuts and the login item are pointed at the
+/// new location, and the old location is removed. Both directions need administrator privileges, so if this process is not
+/// elevated it runs itself again elevated (which shows a UAC prompt) to move the files and the per-machine state. The
+/// elevated process may be running as a different account (eg. when another user's administrator credentials were
+/// entered), so the per-user state is always moved by this process, once the elevated process has finished.
+/// If `machine_only` is set, the per-user state is left for the process which started this one.
+pub fn migrate(locator: &VelopackLocator, to_machine: bool, install_to: Option<&PathBuf>, machine_only: bool) -> Result<()> {
+    if locator.get_is_portable() {
+        bail!("Portable apps can not be migrated, because they are not installed.");
+    }
+    if locator.get_is_machine_wide() == to_machine {
+        info!("The app is already installed {}, there is nothing to migrate.", if to_machine { "per-machine" } else { "per-user" });
+        return Ok(());
+    }
+
+    let target_root = match install_to {
+        Some(dir) => dir.clone(),
+        None if to_machine => Path::new(&windows::known_path::get_program_files_x64()?).join(locator.get_install_id()),
+        None => Path::new(&windows::known_path::get_local_app_data()?).join(locator.get_install_id()),
+    };
+    if !shared::is_dir_empty(&target_root) {
+        bail!("Unable to migrate to '{}', because the directory is not empty.", target_root.to_string_lossy());
+    }
+
+    // a sandboxed migration only writes to the sandbox, so it does not need administrator privileges
+    if !windows::is_elevated() && windows::sandbox::get_sandbox_dir().is_none() {
+        info!("Migrating requires administrator privileges, restarting elevated...");
+        let args = get_elevated_args(to_machine, &target_root, shared::dialogs::get_silent());
+        windows::run_self_elevated_and_wait(&args.iter().map(|a| a.as_str()).collect::<Vec<_>>())?;
+        migrate_user_state(locator, &get_new_locator(locator, &target_root), to_machine);
+        info!("Migrated successfully to '{}'.", target_root.to_string_lossy());
+        return Ok(());
+    }
+
+    let _mutex = shared::retry_io(|| windows::create_global_mutex(&locator.get_install_id()))?;
+    let lock = shared::lock_root_dir(locator, Duration::ZERO)?;
+    let old_root = locator.get_root_dir();
+    info!("Migrating '{}' to '{}'", old_root.to_string_lossy(), target_root.to_string_lossy());
+
+    // the app needs to be closed so its files are not copied half written
+    shared::force_stop_package(&old_root)?;
+    let _ = fs::remove_file(target_root.join(".dead"));
+    if let Err(e) = copy_root_dir(&old_root, &target_root) {
+        let _ = remove_dir_all::remove_dir_all(&target_root);
+        return Err(anyhow!("Unable to copy the app to '{}' ({}).", target_root.to_string_lossy(), e));
+    }
+
+    // from this point on, the app has been copied, so the old install is cleaned up on a best effort basis
+    let new_locator = get_new_locator(locator, &target_root);
+    let result = if to_machine {
+        windows::registry::write_uninstall_entry_to(&new_locator, RegistryScope::Machine)
+    } else {
+        windows::registry::remove_uninstall_entry_from(locator, RegistryScope::Machine)
+    };
+    if let Err(e) = result {
+        warn!("Failed to move the per-machine uninstall entry ({}).", e);
+    }
+    if !machine_only {
+        migrate_user_state(locator, &new_locator, to_machine);
+    }
+
+    // the lock file is in the old directory, so it must be released before the directory can be removed
+    drop(lock);
+    info!("Removing directory '{}'", old_root.to_string_lossy());
+    if let Err(e) = shared::retry_io(|| remove_dir_all::remove_dir_but_not_self(&old_root)) {
+        warn!("Unable to remove the old directory, some files may be in use ({}).", e);
+    }
+    let _ = File::create(old_root.join(".dead"));
+    if let Err(e) = windows::register_intent_to_delete_self_and_root(&old_root, &[]) {
+        warn!("Unable to schedule self delete ({}).", e);
+    }
+
+    info!("Migrated successfully to '{}'.", target_root.to_string_lossy());
+    Ok(())
+}
+
+fn get_new_locator(locator: &VelopackLocator, target_root: &Path) -> VelopackLocator {
+    let paths = create_config_from_root_dir(target_root);
+    VelopackLocator::new(paths, locator.get_manifest()).clone_self_with_packages_dir_override(None)
+}
+
+/// The arguments of the elevated process, which only moves the files and the per-machine state.
+fn get_elevated_args(to_machine: bool, target_root: &Path, silent: bool) -> Vec<String> {
+    let scope = if to_machine { "--to-machine" } else { "--to-user" };
+    let mut args = vec!["migrate".to_owned(), scope.to_owned(), "--installTo".to_owned(), target_root.to_string_lossy().to_string()];
+    args.push("--machineOnly".to_owned());
+    if silent {
+        args.push("--silent".to_owned());
+    }
+    args
+}
+
+/// Moves the state which belongs to the current user (the per-user uninstall entry, shortcuts, the login item and the
+/// first-run marker) to the new location. Failures are logged, because the app has already been moved.
+fn migrate_user_state(locator: &VelopackLocator, new_locator: &VelopackLocator, to_machine: bool) {
+    let result = if to_machine {
+        windows::registry::remove_uninstall_entry_from(locator, RegistryScope::User)
+    } else {
+        windows::registry::write_uninstall_entry_to(new_locator, RegistryScope::User)
+    };
+    if let Err(e) = result {
+        warn!("Failed to move the per-user uninstall entry ({}).", e);
+    }
+
+    PlatformShortcuts.remove_all_shortcuts(locator);
+    PlatformShortcuts.create_or_update_manifest_shortcuts(new_locator, None);
+
+    if velopack::login_item::is_login_item_registered(locator).unwrap_or(false) {
+        if let Err(e) = velopack::login_item::register_login_item(new_locator) {
+            warn!("Failed to move the login item to the new location ({}).", e);
+        }
+    }
+
+    // the current user has already run the app, so the first-run hook of the per-machine install should not fire for them
+    if to_machine {
+        if let Err(e) = new_locator.set_first_run_completed_for_user() {
+            warn!("Unable to record the first run for the current user ({}).", e);
+        }
+    }
+}
+
+/// Copies the contents of the app root to a new directory, skipping the lock file of the install in progress.
+fn copy_root_dir(source: &Path, target: &Path) -> Result<()> {
+    fs::create_dir_all(target)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        if entry.file_name() == constants::ROOT_DIR_LOCK_FILE {
+            continue;
+        }
+        let target_path = target.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_root_dir(&entry.path(), &target_path)?;
+        } else {
+            fs::copy(entry.path(), &target_path)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_copy_root_dir_skips_the_lock_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let (source, target) = (dir.path().join("source"), dir.path().join("target"));
+    fs::create_dir_all(source.join("current").join("sub")).unwrap();
+    fs::write(source.join("Update.exe"), b"update").unwrap();
+    fs::write(source.join("current").join("sub").join("data.json"), b"{}").unwrap();
+    fs::write(source.join(constants::ROOT_DIR_LOCK_FILE), b"").unwrap();
+
+    copy_root_dir(&source, &target).unwrap();
+    assert_eq!(fs::read(target.join("Update.exe")).unwrap(), b"update");
+    assert_eq!(fs::read(target.join("current").join("sub").join("data.json")).unwrap(), b"{}");
+    assert!(!target.join(constants::ROOT_DIR_LOCK_FILE).exists());
+}
+
+#[test]
+fn test_elevated_migration_leaves_user_state_to_the_caller() {
+    let args = get_elevated_args(false, Path::new(r"C:\Users\me\AppData\Local\MyApp"), true);
+    assert_eq!(args, ["migrate", "--to-user", "--installTo", r"C:\Users\me\AppData\Local\MyApp", "--machineOnly", "--silent"]);
+    assert_eq!(get_elevated_args(true, Path::new(r"C:\Program Files\MyApp"), false)[1], "--to-machine");
+}
@@ -4,6 +4,48 @@ pub use apply::*;
 mod start;
 pub use start::*;
 
+mod serve;
+pub use serve::*;
+
+mod check;
+pub use check::*;
+
+mod shortcuts;
+pub use shortcuts::*;
+
+#[cfg(feature = "pack")]
+mod pack;
+#[cfg(feature = "pack")]
+pub use pack::*;
+
+#[cfg(feature = "pack")]
+mod code_sign;
+#[cfg(feature = "pack")]
+pub use code_sign::*;
+
+#[cfg(feature = "pack")]
+mod releases;
+#[cfg(feature = "pack")]
+pub use releases::*;
+
+#[cfg(feature = "pack")]
+mod resource_edit;
+#[cfg(feature = "pack")]
+pub use resource_edit::*;
+
+#[cfg(target_os = "linux")]
+mod shortcuts_linux;
+#[cfg(target_os = "linux")]
+pub use shortcuts_linux::*;
+#[cfg(target_os = "macos")]
+mod shortcuts_osx;
+#[cfg(target_os = "macos")]
+pub use shortcuts_osx::*;
+#[cfg(target_os = "windows")]
+mod shortcuts_windows;
+#[cfg(target_os = "windows")]
+pub use shortcuts_windows::*;
+
 #[cfg(target_os = "linux")]
 mod apply_linux_impl;
 #[cfg(target_os = "macos")]
@@ -14,6 +56,31 @@ mod apply_windows_impl;
 #[cfg(target_os = "windows")]
 mod start_windows_impl;
 
+#[cfg(target_os = "macos")]
+mod launch_agent_osx;
+#[cfg(target_os = "macos")]
+pub use launch_agent_osx::*;
+
+#[cfg(target_os = "macos")]
+mod privileged_helper_osx;
+#[cfg(target_os = "macos")]
+pub use privileged_helper_osx::*;
+
+#[cfg(target_os = "linux")]
+mod desktop_linux;
+#[cfg(target_os = "linux")]
+pub use desktop_linux::*;
+
+#[cfg(target_os = "linux")]
+mod update_timer_linux;
+#[cfg(target_os = "linux")]
+pub use update_timer_linux::*;
+
+#[cfg(target_os = "windows")]
+mod update_task_windows;
+#[cfg(target_os = "windows")]
+pub use update_task_windows::*;
+
 #[cfg(target_os = "windows")]
 mod install;
 #[cfg(target_os = "windows")]
@@ -23,3 +90,13 @@ pub use install::*;
 mod uninstall;
 #[cfg(target_os = "windows")]
 pub use uninstall::*;
+
+#[cfg(target_os = "windows")]
+mod migrate;
+#[cfg(target_os = "windows")]
+pub use migrate::*;
+
+#[cfg(target_os = "linux")]
+mod uninstall_linux;
+#[cfg(target_os = "linux")]
+pub use uninstall_linux::*;
@@ -0,0 +1,571 @@
+use super::{ExecutionLevel, ResourceEdit, SigningOptions, VersionInfo};
+use crate::shared;
+use anyhow::{anyhow, bail, Result};
+use semver::Version;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use velopack::{
+    bundle::{self, BundleWriter, CompressionOptions, Manifest},
+    VelopackAsset,
+};
+
+/// The signature which marks the bundle header in setup.exe, followed by the offset and length of the package which has
+/// been appended to it. This must match BUNDLE_PLACEHOLDER in setup.rs.
+const SETUP_BUNDLE_SIGNATURE: [u8; 32] = [
+    0x94, 0xf0, 0xb1, 0x7b, 0x68, 0x93, 0xe0, 0x29, 0x37, 0xeb, 0x34, 0xef, 0x53, 0xaa, 0xe7, 0xd4, //
+    0x2b, 0x54, 0xf5, 0x70, 0x7e, 0xf5, 0xd6, 0xf5, 0x78, 0x54, 0x98, 0x3e, 0x5e, 0x94, 0xed, 0x7d, //
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct PackOptions {
+    /// The unique id of the app, eg. "MyApp".
+    pub pack_id: String,
+    /// The version of the release being packed.
+    pub pack_version: Option<Version>,
+    /// The directory (or .app bundle on macOS) containing the compiled app.
+    pub pack_dir: PathBuf,
+    /// The name of the executable which is started after install and update.
+    pub main_exe: String,
+    pub pack_title: Option<String>,
+    pub pack_authors: Option<String>,
+    /// The release channel, defaults to the name of the current OS (eg. "win").
+    pub channel: Option<String>,
+    /// The minimum version of Windows (eg. "10.0.17763") the app can be installed on.
+    pub os_min_version: Option<String>,
+    /// The minimum version of Windows Server (eg. "2019"), if it is different to the client version.
+    pub os_min_server_version: Option<String>,
+    /// Installs each channel separately, so several channels of the app can be installed at the same time.
+    pub side_by_side: bool,
+    /// Marks the release as mandatory in the release feed, so apps do not let the user skip it.
+    pub mandatory: bool,
+    /// An .ico file which is shown by the installer, or on Linux a .png or .svg which is shown by the desktop entry.
+    pub icon: Option<PathBuf>,
+    /// The directory the release files are written to.
+    pub output_dir: PathBuf,
+    /// The updater binary which is shipped with the app, this is normally the binary which is running.
+    pub update_exe: PathBuf,
+    /// The setup.exe which the package is appended to, to create the installer. Only used on Windows.
+    pub setup_exe: Option<PathBuf>,
+    /// The stub.exe which is copied into the package as the main executable of portable installs. Only used on Windows.
+    pub stub_exe: Option<PathBuf>,
+    /// The AppImage runtime for the architecture of the app, which the AppDir is appended to. Only used on Linux.
+    pub appimage_runtime: Option<PathBuf>,
+    /// The requestedExecutionLevel written to the manifest of setup.exe, if it should be changed.
+    pub execution_level: Option<ExecutionLevel>,
+    /// The dpiAware setting written to the manifest of setup.exe, if it should be changed.
+    pub dpi_aware: Option<bool>,
+    /// How the executables in the package, the updater and the installer are signed.
+    pub signing: SigningOptions,
+    /// Skips creating a delta from the previous release in the output directory.
+    pub no_delta: bool,
+    /// How the full and delta packages are compressed.
+    pub compression: CompressionOptions,
+    /// Creates an installer which does not contain the package, but installs it from the same directory as the installer.
+    pub external_payload: bool,
+}
+
+/// Creates a full release package for the current OS from a compiled app, and on Windows an installer which contains it
+/// (on Linux, the AppImage installs itself), in the same layout vpk uses. Returns the paths of the files which were created.
+pub fn pack(options: &PackOptions) -> Result<Vec<PathBuf>> {
+    let version = options.pack_version.clone().ok_or_else(|| anyhow!("A version is required."))?;
+    if options.pack_id.is_empty() {
+        bail!("A package id is required.");
+    }
+    if !options.pack_dir.is_dir() {
+        bail!("The pack directory {:?} does not exist.", options.pack_dir);
+    }
+
+    let os = get_current_os();
+    let channel = options.channel.clone().unwrap_or_else(|| os.to_owned());
+    let manifest = Manifest {
+        id: options.pack_id.clone(),
+        version: version.clone(),
+        title: options.pack_title.clone().unwrap_or_else(|| options.pack_id.clone()),
+        authors: options.pack_authors.clone().unwrap_or_else(|| options.pack_id.clone()),
+        // vpk defaults the main executable to "<id>.exe" on Windows and "<id>" on Linux as well
+        main_exe: match (options.main_exe.is_empty(), os) {
+            (true, "win") => format!("{}.exe", options.pack_id),
+            (true, "linux") => options.pack_id.clone(),
+            _ => options.main_exe.clone(),
+        },
+        os: os.to_owned(),
+        os_min_version: options.os_min_version.clone().unwrap_or_default(),
+        os_min_server_version: options.os_min_server_version.clone().unwrap_or_default(),
+        channel: channel.clone(),
+        side_by_side: if options.side_by_side { "true".to_owned() } else { String::new() },
+        mandatory: if options.mandatory { "true".to_owned() } else { String::new() },
+        ..Default::default()
+    };
+
+    info!("Packing {} {} ({}) from {:?}", manifest.id, version, channel, options.pack_dir);
+    fs::create_dir_all(&options.output_dir)?;
+    let staging = options.output_dir.join(format!(".{}.pack_{}", manifest.id, shared::random_string(8)));
+    let result = (|| {
+        let app_dir = prepare_app_dir(options, &manifest, &staging)?;
+        sign_app_dir(options, &app_dir)?;
+        let mut writer = BundleWriter::new(&app_dir, manifest.clone());
+        writer.set_compression(options.compression.clone());
+        if let Some(icon) = options.icon.as_ref().filter(|_| os != "linux") {
+            writer.add_metadata_file("setup.ico", icon);
+        }
+
+        let package_name = bundle::get_suggested_package_file_name(&manifest.id, &version, &channel, os, false);
+        let package_path = options.output_dir.join(&package_name);
+        let package_asset = writer.write_to_file(&package_path)?;
+        info!("Created package {:?} ({} bytes, SHA256 {})", package_path, package_asset.Size, package_asset.SHA256);
+        // the chunk index is not a release asset, it sits next to the package so clients can download it in chunks
+        let chunks_path = velopack::chunks::write_chunk_index(&package_path)?;
+        info!("Created chunk index {:?}", chunks_path);
+        let mut created = vec![package_path.clone()];
+
+        if !options.no_delta {
+            if let Some(base_path) = find_previous_full_package(&options.output_dir, &manifest) {
+                let delta_name = bundle::get_suggested_package_file_name(&manifest.id, &version, &channel, os, true);
+                let delta_path = options.output_dir.join(&delta_name);
+                let (asset, stats) =
+                    velopack::delta::create_delta_package_with_options(&base_path, &package_path, &delta_path, &options.compression)?;
+                info!("Created delta package {:?} ({} bytes, {} changed files)", delta_path, asset.Size, stats.changed);
+                created.push(delta_path);
+            }
+        }
+
+        let index = super::update_releases(&super::ReleasesOptions {
+            releases_dir: options.output_dir.clone(),
+            channel: channel.clone(),
+            add: created.clone(),
+            ..Default::default()
+        })?;
+        info!("Updated the release index of channel '{}' ({} assets)", index.channel(), index.feed().Assets.len());
+
+        if os == "linux" {
+            // the AppImage is the installer on Linux, it installs itself when it is started
+            let appimage_name = match channel == os {
+                true => format!("{}.AppImage", manifest.id),
+                false => format!("{}-{}.AppImage", manifest.id, channel),
+            };
+            let appimage_path = options.output_dir.join(appimage_name);
+            fs::copy(app_dir.join(format!("{}.AppImage", manifest.id)), &appimage_path)?;
+            info!("Created AppImage {:?}", appimage_path);
+            created.push(appimage_path);
+        }
+
+        if os == "win" {
+            let setup_path = options.output_dir.join(format!("{}-{}-Setup.exe", manifest.id, channel));
+            let template = match &options.setup_exe {
+                Some(setup) => setup.clone(),
+                None => options.update_exe.with_file_name("setup.exe"),
+            };
+            // the resources are edited on a copy, because the package must be appended after them
+            let branded_setup = staging.join("setup.exe");
+            fs::copy(&template, &branded_setup).map_err(|e| anyhow!("Unable to read setup.exe from {:?} ({}).", template, e))?;
+            warn_if_signature_is_removed(&template, &options.signing);
+            let mut resources = ResourceEdit::load(&branded_setup)?;
+            if let Some(icon) = &options.icon {
+                resources.set_icon(icon)?;
+            }
+            resources.set_version_info(&get_version_info(&manifest))?;
+            resources.set_manifest_options(options.execution_level, options.dpi_aware)?;
+            resources.commit()?;
+            if options.external_payload {
+                create_setup_bundle_with_external_payload(&branded_setup, &package_asset, &setup_path)?;
+                info!("The installer will install {:?}, which must be shipped next to it.", package_name);
+            } else {
+                create_setup_bundle(&branded_setup, &package_path, &setup_path)?;
+            }
+            // the package is covered by the signature as well, so this is signed after it is appended
+            super::sign_files(&options.signing, std::slice::from_ref(&setup_path))?;
+            info!("Created installer {:?}", setup_path);
+            created.push(setup_path);
+        }
+        Ok(created)
+    })();
+    let _ = remove_dir_all::remove_dir_all(&staging);
+    result
+}
+
+/// Finds the newest full package of the same app and channel in the output directory which is older than the one being
+/// packed, which is what deltas are created from.
+fn find_previous_full_package(output_dir: &Path, manifest: &Manifest) -> Option<PathBuf> {
+    let prefix = format!("{}-", manifest.id.to_lowercase());
+    fs::read_dir(output_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+            name.starts_with(&prefix) && name.ends_with("-full.nupkg")
+        })
+        // the channel is not always part of the file name, so it is read from the manifest instead
+        .filter_map(|path| bundle::load_bundle_from_file(&path).and_then(|mut b| b.read_manifest()).ok().map(|m| (m, path)))
+        .filter(|(m, _)| m.id == manifest.id && m.channel == manifest.channel && m.version < manifest.version)
+        .max_by(|(a, _), (b, _)| a.version.cmp(&b.version))
+        .map(|(_, path)| path)
+}
+
+/// Editing the resources of an executable removes its Authenticode signature, which is only replaced if a signing command
+/// was configured, so a signed template (eg. an official release of setup.exe) would otherwise silently end up unsigned.
+fn warn_if_signature_is_removed(path: &Path, signing: &SigningOptions) {
+    if super::has_authenticode_signature(path) && !signing.is_enabled() {
+        warn!(
+            "{:?} is signed, but editing its resources removes the signature, and no signing command was configured to sign it again.",
+            path
+        );
+    }
+}
+
+/// Returns the short name of the current OS, which is also the default release channel.
+pub fn get_current_os() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "win"
+    } else if cfg!(target_os = "macos") {
+        "osx"
+    } else {
+        "linux"
+    }
+}
+
+/// The version info shown in the file properties of setup.exe.
+fn get_version_info(manifest: &Manifest) -> VersionInfo {
+    VersionInfo {
+        version: Some(manifest.version.clone()),
+        product_name: manifest.title.clone(),
+        company_name: manifest.authors.clone(),
+        description: match manifest.description.is_empty() {
+            true => format!("{} Setup", manifest.title),
+            false => manifest.description.clone(),
+        },
+        copyright: format!("Copyright © {}", manifest.authors),
+    }
+}
+
+/// Copies the app into the staging directory and adds the manifest and updater, where the updater expects to find them.
+/// Returns the directory which should be packaged.
+fn prepare_app_dir(options: &PackOptions, manifest: &Manifest, staging: &Path) -> Result<PathBuf> {
+    let nuspec = bundle::write_manifest_to_string(manifest);
+    if cfg!(target_os = "windows") {
+        if manifest.main_exe.is_empty() || !options.pack_dir.join(&manifest.main_exe).is_file() {
+            bail!("The main executable '{}' was not found in {:?}.", manifest.main_exe, options.pack_dir);
+        }
+        copy_dir_all(&options.pack_dir, staging)?;
+        fs::write(staging.join("sq.version"), nuspec)?;
+        let update_path = staging.join("Squirrel.exe");
+        fs::copy(&options.update_exe, &update_path)?;
+        if let Some(icon) = &options.icon {
+            warn_if_signature_is_removed(&options.update_exe, &options.signing);
+            let mut resources = ResourceEdit::load(&update_path)?;
+            resources.set_icon(icon)?;
+            resources.commit()?;
+        }
+        create_execution_stub(options, manifest, staging)?;
+        Ok(staging.to_path_buf())
+    } else if cfg!(target_os = "macos") {
+        let is_bundle = options.pack_dir.extension().map(|e| e.eq_ignore_ascii_case("app")).unwrap_or(false);
+        if !is_bundle {
+            bail!("On macOS the pack directory must be an .app bundle, but {:?} is not.", options.pack_dir);
+        }
+        copy_dir_all(&options.pack_dir, staging)?;
+        let macos_dir = staging.join("Contents").join("MacOS");
+        fs::write(macos_dir.join("sq.version"), nuspec)?;
+        let update_path = macos_dir.join("UpdateMac");
+        fs::copy(&options.update_exe, &update_path)?;
+        set_executable(&update_path)?;
+        Ok(staging.to_path_buf())
+    } else {
+        let app_dir = staging.join(format!("{}.AppDir", manifest.id));
+        prepare_linux_app_dir(options, manifest, &app_dir)?;
+        let package_dir = staging.join("package");
+        fs::create_dir_all(&package_dir)?;
+        let main_exe = app_dir.join("usr").join("bin").join(&manifest.main_exe);
+        let arch = get_elf_arch(&fs::read(&main_exe)?).map_err(|e| anyhow!("{:?} is not a supported executable ({}).", main_exe, e))?;
+        let runtime = match &options.appimage_runtime {
+            Some(runtime) => runtime.clone(),
+            None => options.update_exe.with_file_name(format!("appimagekit-runtime-{}", arch)),
+        };
+        create_appimage(&app_dir, &runtime, &package_dir.join(format!("{}.AppImage", manifest.id)))?;
+        Ok(package_dir)
+    }
+}
+
+/// Creates `<mainExe>_ExecutionStub.exe`, which is installed to the root of the app as the executable that shortcuts
+/// start, and which starts the current version of the main executable. It has the resources (icon and version info) of
+/// the main executable, so it looks like the app.
+fn create_execution_stub(options: &PackOptions, manifest: &Manifest, staging: &Path) -> Result<()> {
+    let template = match &options.stub_exe {
+        Some(stub) => stub.clone(),
+        None => options.update_exe.with_file_name("stub.exe"),
+    };
+    let main_exe = staging.join(&manifest.main_exe);
+    let stem = main_exe.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let stub_path = main_exe.with_file_name(format!("{}_ExecutionStub.exe", stem));
+    fs::copy(&template, &stub_path).map_err(|e| anyhow!("Unable to read stub.exe from {:?} ({}).", template, e))?;
+    // the stub still works without the resources, it just doesn't look like the app
+    let result = ResourceEdit::load(&stub_path).and_then(|mut resources| {
+        resources.copy_resources_from(&main_exe)?;
+        resources.commit()
+    });
+    if let Err(e) = result {
+        warn!("Unable to copy the resources of {:?} to the execution stub ({}).", main_exe, e);
+    }
+    Ok(())
+}
+
+/// Creates the AppDir which is turned into the AppImage, in the same layout vpk uses: the app and the updater are in
+/// usr/bin, next to the manifest, and AppRun starts the executable of the .desktop entry. If the pack directory is already
+/// an AppDir (its name ends with .AppDir), it is used as it is.
+fn prepare_linux_app_dir(options: &PackOptions, manifest: &Manifest, app_dir: &Path) -> Result<()> {
+    let is_app_dir = options.pack_dir.extension().map(|e| e.eq_ignore_ascii_case("AppDir")).unwrap_or(false);
+    let bin_dir = app_dir.join("usr").join("bin");
+    if is_app_dir {
+        copy_dir_all(&options.pack_dir, app_dir)?;
+    } else {
+        if !options.pack_dir.join(&manifest.main_exe).is_file() {
+            bail!("The main executable '{}' was not found in {:?}.", manifest.main_exe, options.pack_dir);
+        }
+        copy_dir_all(&options.pack_dir, &bin_dir)?;
+        let app_run = app_dir.join("AppRun");
+        fs::write(&app_run, LINUX_APP_RUN)?;
+        set_executable(&app_run)?;
+        let icon_name = match &options.icon {
+            Some(icon) => {
+                let ext = icon.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+                fs::copy(icon, app_dir.join(format!("{}.{}", manifest.id, ext)))?;
+                Some(manifest.id.as_str())
+            }
+            None => None,
+        };
+        fs::write(app_dir.join(format!("{}.desktop", manifest.id)), create_linux_desktop_file(manifest, icon_name))?;
+    }
+    if !bin_dir.join(&manifest.main_exe).is_file() {
+        bail!("The main executable '{}' was not found in {:?}.", manifest.main_exe, bin_dir);
+    }
+    fs::write(bin_dir.join("sq.version"), bundle::write_manifest_to_string(manifest))?;
+    let update_path = bin_dir.join("UpdateNix");
+    fs::copy(&options.update_exe, &update_path)?;
+    set_executable(&update_path)?;
+    Ok(())
+}
+
+/// Starts the executable of the .desktop entry, which is in usr/bin. This is the same script vpk writes.
+const LINUX_APP_RUN: &str = r#"#!/bin/sh
+HERE="$(dirname "$(readlink -f "${0}")")"
+export PATH="${HERE}"/usr/bin/:"${PATH}"
+EXEC=$(grep -e '^Exec=.*' "${HERE}"/*.desktop | head -n 1 | cut -d "=" -f 2 | cut -d " " -f 1 | sed 's/\\s/ /g')
+exec "${EXEC}" "$@"
+"#;
+
+fn create_linux_desktop_file(manifest: &Manifest, icon_name: Option<&str>) -> String {
+    // spaces in the executable are written as \s, https://specifications.freedesktop.org/desktop-entry-spec/latest/
+    let mut entry = format!(
+        "[Desktop Entry]\nType=Application\nName={}\nComment={} {}\nExec={}\nStartupWMClass={}\nCategories=Utility;\n",
+        manifest.title,
+        manifest.title,
+        manifest.version,
+        manifest.main_exe.replace(' ', "\\s"),
+        manifest.id
+    );
+    if let Some(icon_name) = icon_name {
+        entry.push_str(&format!("Icon={}\n", icon_name));
+    }
+    entry
+}
+
+/// Returns the name AppImage runtimes use for the architecture of an ELF executable.
+fn get_elf_arch(header: &[u8]) -> Result<&'static str> {
+    if header.len() < 20 || &header[0..4] != b"\x7fELF" {
+        bail!("missing ELF header");
+    }
+    let machine = match header[5] {
+        1 => u16::from_le_bytes([header[18], header[19]]),
+        _ => u16::from_be_bytes([header[18], header[19]]),
+    };
+    match machine {
+        0x03 => Ok("i686"),
+        0x3e => Ok("x86_64"),
+        0xb7 => Ok("aarch64"),
+        _ => bail!("unsupported machine type {:#x}", machine),
+    }
+}
+
+/// Creates an AppImage by compressing the AppDir into a squashfs image with mksquashfs, and appending it to the runtime.
+fn create_appimage(app_dir: &Path, runtime: &Path, output_path: &Path) -> Result<()> {
+    let squashfs = output_path.with_extension("squashfs");
+    let args = [app_dir.as_os_str(), squashfs.as_os_str()];
+    let flags = ["-comp", "gzip", "-root-owned", "-noappend", "-mkfs-time", "0"];
+    info!("Compressing {:?} into a squashfs image", app_dir);
+    let output = match std::process::Command::new("mksquashfs").args(args).args(flags).output() {
+        Ok(output) => output,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            bail!("mksquashfs was not found, it is needed to create an AppImage (eg. 'sudo apt install squashfs-tools').")
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if !output.status.success() {
+        let _ = fs::remove_file(&squashfs);
+        bail!("mksquashfs failed ({}): {}", output.status, String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    let mut appimage = fs::read(runtime).map_err(|e| anyhow!("Unable to read the AppImage runtime from {:?} ({}).", runtime, e))?;
+    appimage.extend_from_slice(&fs::read(&squashfs)?);
+    let _ = fs::remove_file(&squashfs);
+    fs::write(output_path, appimage)?;
+    set_executable(output_path)?;
+    Ok(())
+}
+
+/// Signs the executables of the app and the updater before they are packaged. On macOS the updater is signed first, and
+/// then the bundle as a whole, because the signature of the bundle seals everything inside it.
+fn sign_app_dir(options: &PackOptions, app_dir: &Path) -> Result<()> {
+    if !options.signing.is_enabled() {
+        return Ok(());
+    }
+    if cfg!(target_os = "macos") {
+        super::sign_files(&options.signing, &[app_dir.join("Contents").join("MacOS").join("UpdateMac")])?;
+        super::sign_files(&options.signing, &[app_dir.to_path_buf()])
+    } else {
+        let mut files = Vec::new();
+        find_files_to_sign(app_dir, &options.signing, &mut files)?;
+        super::sign_files(&options.signing, &files)
+    }
+}
+
+fn find_files_to_sign(dir: &Path, signing: &SigningOptions, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            find_files_to_sign(&entry.path(), signing, files)?;
+        } else if file_type.is_file() && signing.should_sign_file(&entry.path()) {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+fn copy_dir_all(source: &Path, target: &Path) -> Result<()> {
+    fs::create_dir_all(target)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let target_path = target.join(entry.file_name());
+        if file_type.is_dir() {
+            copy_dir_all(&entry.path(), &target_path)?;
+        } else if file_type.is_symlink() {
+            // symlinks are kept as they are, because frameworks in an app bundle are sealed with their symlinks
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(fs::read_link(entry.path())?, &target_path)?;
+            #[cfg(windows)]
+            fs::copy(entry.path(), &target_path)?;
+        } else {
+            fs::copy(entry.path(), &target_path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(windows)]
+fn set_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Creates an installer by appending the package to a copy of setup.exe, and writing the offset and length of the
+/// package into the bundle header which setup.exe reads when it starts.
+pub fn create_setup_bundle(setup_template: &Path, package_path: &Path, output_path: &Path) -> Result<()> {
+    write_setup_bundle(setup_template, &fs::read(package_path)?, output_path)
+}
+
+/// Creates an installer which does not contain the package, but installs it from the same directory as the installer,
+/// so that large apps do not need a single multi-GB installer. The size and SHA256 of the package are appended to the
+/// installer in its place, and it refuses to install a package which does not match.
+pub fn create_setup_bundle_with_external_payload(setup_template: &Path, package: &VelopackAsset, output_path: &Path) -> Result<()> {
+    write_setup_bundle(setup_template, &shared::external_payload::ExternalPayload::from_asset(package).to_bytes(), output_path)
+}
+
+fn write_setup_bundle(setup_template: &Path, payload: &[u8], output_path: &Path) -> Result<()> {
+    let mut setup = fs::read(setup_template).map_err(|e| anyhow!("Unable to read setup.exe from {:?} ({}).", setup_template, e))?;
+    let position = setup
+        .windows(SETUP_BUNDLE_SIGNATURE.len())
+        .position(|w| w == SETUP_BUNDLE_SIGNATURE)
+        .ok_or_else(|| anyhow!("{:?} does not contain a bundle header, is it a Velopack setup.exe?", setup_template))?;
+    if position < 16 {
+        bail!("The bundle header in {:?} is not valid.", setup_template);
+    }
+
+    let offset = setup.len() as i64;
+    let length = payload.len() as i64;
+    // setup.exe reads these in native byte order, which is little-endian on every architecture it is built for
+    setup[position - 16..position - 8].copy_from_slice(&offset.to_le_bytes());
+    setup[position - 8..position].copy_from_slice(&length.to_le_bytes());
+    setup.extend_from_slice(payload);
+    fs::write(output_path, setup)?;
+    Ok(())
+}
+
+#[test]
+fn test_create_setup_bundle_writes_header() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    let mut template = b"MZ-setup-".to_vec();
+    template.extend_from_slice(&[0u8; 16]);
+    template.extend_from_slice(&SETUP_BUNDLE_SIGNATURE);
+    template.extend_from_slice(b"-end");
+    fs::write(dir.join("setup.exe"), &template).unwrap();
+    fs::write(dir.join("app.nupkg"), b"PK-package").unwrap();
+
+    create_setup_bundle(&dir.join("setup.exe"), &dir.join("app.nupkg"), &dir.join("out.exe")).unwrap();
+    let out = fs::read(dir.join("out.exe")).unwrap();
+    let offset = i64::from_le_bytes(out[9..17].try_into().unwrap()) as usize;
+    let length = i64::from_le_bytes(out[17..25].try_into().unwrap()) as usize;
+    assert_eq!(offset, template.len());
+    assert_eq!(&out[offset..offset + length], b"PK-package");
+    assert!(create_setup_bundle(&dir.join("app.nupkg"), &dir.join("app.nupkg"), &dir.join("bad.exe")).is_err());
+
+    let asset = VelopackAsset { FileName: "app.nupkg".to_owned(), Size: 10, SHA256: "AB".repeat(32), ..Default::default() };
+    create_setup_bundle_with_external_payload(&dir.join("setup.exe"), &asset, &dir.join("stub.exe")).unwrap();
+    let out = fs::read(dir.join("stub.exe")).unwrap();
+    let length = i64::from_le_bytes(out[17..25].try_into().unwrap()) as usize;
+    let payload = shared::external_payload::ExternalPayload::parse(&out[template.len()..template.len() + length]).unwrap().unwrap();
+    assert_eq!((payload.file_name.as_str(), payload.size), ("app.nupkg", 10));
+}
+
+#[test]
+fn test_prepare_linux_app_dir_uses_vpk_layout() {
+    let dir = tempfile::tempdir().unwrap();
+    let pack_dir = dir.path().join("publish");
+    fs::create_dir_all(&pack_dir).unwrap();
+    let mut elf = b"\x7fELF\x02\x01\x01\x00".to_vec();
+    elf.extend_from_slice(&[0u8; 10]);
+    elf.extend_from_slice(&0x3eu16.to_le_bytes());
+    fs::write(pack_dir.join("My App"), &elf).unwrap();
+    fs::write(dir.path().join("update"), b"updater").unwrap();
+
+    let manifest = Manifest {
+        id: "MyApp".to_owned(),
+        title: "My App".to_owned(),
+        main_exe: "My App".to_owned(),
+        version: Version::new(1, 0, 0),
+        ..Default::default()
+    };
+    let options = PackOptions { pack_dir, update_exe: dir.path().join("update"), ..Default::default() };
+    let app_dir = dir.path().join("MyApp.AppDir");
+    prepare_linux_app_dir(&options, &manifest, &app_dir).unwrap();
+
+    let bin_dir = app_dir.join("usr").join("bin");
+    assert_eq!(get_elf_arch(&fs::read(bin_dir.join("My App")).unwrap()).unwrap(), "x86_64");
+    assert_eq!(fs::read(bin_dir.join("UpdateNix")).unwrap(), b"updater");
+    assert_eq!(bundle::read_manifest_from_string(&fs::read_to_string(bin_dir.join("sq.version")).unwrap()).unwrap().id, "MyApp");
+    assert!(fs::read_to_string(app_dir.join("AppRun")).unwrap().starts_with("#!/bin/sh\n"));
+    let desktop = fs::read_to_string(app_dir.join("MyApp.desktop")).unwrap();
+    assert!(desktop.contains("Exec=My\\sApp\n") && !desktop.contains("Icon="));
+
+    assert!(get_elf_arch(b"MZ").is_err());
+    elf[18] = 0xb7;
+    assert_eq!(get_elf_arch(&elf).unwrap(), "aarch64");
+}
@@ -0,0 +1,383 @@
+use crate::shared;
+use anyhow::{anyhow, bail, Result};
+use std::{
+    ffi::CString,
+    fs,
+    io::{BufRead, BufReader, Write},
+    os::unix::{
+        ffi::OsStringExt,
+        fs::MetadataExt,
+        io::{AsRawFd, FromRawFd},
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    process::Command as Process,
+    time::Duration,
+};
+use velopack::{escape::escape_xml, locator::VelopackLocator};
+
+// The privileged helper is a launch daemon which runs UpdateMac as root, so that users who are not an administrator
+// can still update an app installed to a protected location such as /Applications. It is registered once by an
+// administrator, and is started on demand by launchd when the updater connects to its socket. Like a helper installed
+// with SMJobBless, the helper executable is copied to /Library/PrivilegedHelperTools and owned by root, so a user who can
+// write to the app bundle can not change the code which runs as root. The socket can only be opened by the user who
+// installed the helper, the updater asking for an update must be signed by the same team as the installed app, and the
+// helper only ever replaces the bundle it was installed for, with a newer bundle signed by the same team.
+
+const RESPONSE_OK: &str = "OK";
+
+/// Registers the privileged helper as a launch daemon, asking for administrator credentials if not already root.
+pub fn install_privileged_helper(locator: &VelopackLocator) -> Result<()> {
+    if locator.get_portable_dir().is_some() {
+        bail!("The privileged helper can not be installed for a portable app.");
+    }
+    let root_dir = locator.get_root_dir();
+    if shared::get_code_signature_team_id(&root_dir)?.is_none() {
+        bail!("The privileged helper can only be installed for an app which is signed with a team identifier.");
+    }
+
+    let label = get_helper_label(locator);
+    let plist_path = get_helper_plist_path(&label);
+    let tool_path = get_helper_tool_path(&label);
+    let owner_uid = get_requesting_uid();
+    let tmp_plist = std::env::temp_dir().join(format!("{}.{}.plist", label, shared::random_string(8)));
+    let plist = create_helper_plist(&label, &tool_path, &root_dir, owner_uid, &get_helper_socket_path(&label));
+    fs::write(&tmp_plist, plist)?;
+
+    // a daemon which is already loaded is unloaded first, or launchd will keep using the old definition
+    let script = "launchctl bootout \"system/$1\" 2>/dev/null; \
+                  mkdir -p /Library/PrivilegedHelperTools && cp -f \"$4\" \"$5\" && chown root:wheel \"$5\" && chmod 544 \"$5\" && \
+                  cp -f \"$2\" \"$3\" && chown root:wheel \"$3\" && chmod 644 \"$3\" && launchctl bootstrap system \"$3\"";
+    let result = run_as_admin(
+        script,
+        &[
+            &label,
+            &tmp_plist.to_string_lossy(),
+            &plist_path.to_string_lossy(),
+            &locator.get_update_path_as_string(),
+            &tool_path.to_string_lossy(),
+        ],
+    );
+    let _ = fs::remove_file(&tmp_plist);
+    result?;
+    info!("Installed privileged helper '{}' for uid {}.", label, owner_uid);
+    Ok(())
+}
+
+/// Unregisters the privileged helper, asking for administrator credentials if not already root.
+pub fn uninstall_privileged_helper(locator: &VelopackLocator) -> Result<()> {
+    let label = get_helper_label(locator);
+    let plist_path = get_helper_plist_path(&label);
+    if !plist_path.exists() {
+        info!("Privileged helper '{}' is not installed.", label);
+        return Ok(());
+    }
+
+    let tool_path = get_helper_tool_path(&label);
+    let script = "launchctl bootout \"system/$1\" 2>/dev/null; rm -f \"$2\" \"$3\"";
+    run_as_admin(script, &[&label, &plist_path.to_string_lossy(), &tool_path.to_string_lossy()])?;
+    info!("Removed privileged helper '{}'.", label);
+    Ok(())
+}
+
+/// Returns true if the privileged helper has been installed for this app.
+pub fn is_privileged_helper_installed(locator: &VelopackLocator) -> bool {
+    get_helper_plist_path(&get_helper_label(locator)).exists()
+}
+
+/// Asks the privileged helper to replace the installed bundle with the extracted bundle at `new_bundle_path`.
+pub fn apply_with_privileged_helper(locator: &VelopackLocator, new_bundle_path: &Path) -> Result<()> {
+    let socket_path = get_helper_socket_path(&get_helper_label(locator));
+    info!("Asking the privileged helper ({:?}) to apply the bundle at {:?}", socket_path, new_bundle_path);
+
+    let mut stream = UnixStream::connect(&socket_path)?;
+    // verifying the signature and copying the bundle can take a while for large apps
+    stream.set_read_timeout(Some(Duration::from_secs(10 * 60)))?;
+    writeln!(stream, "{}", new_bundle_path.to_string_lossy())?;
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response)?;
+    let response = response.trim();
+    if response != RESPONSE_OK {
+        bail!("The privileged helper failed to apply the update: {}", response);
+    }
+    info!("Bundle applied successfully by the privileged helper.");
+    Ok(())
+}
+
+/// Runs the privileged helper, which is started by launchd as root when the updater connects to its socket. Only
+/// requests from `owner_uid` are accepted.
+pub fn run_privileged_helper(locator: &VelopackLocator, owner_uid: u32) -> Result<()> {
+    if unsafe { libc::geteuid() } != 0 {
+        bail!("The privileged helper must be started by launchd as root.");
+    }
+
+    let listener = get_launchd_listener()?;
+    // launchd starts the helper again if another connection is waiting, so only one request is handled at a time
+    let (mut stream, _) = listener.accept()?;
+    if let Err(e) = verify_peer(locator, &stream, owner_uid) {
+        error!("Refusing connection ({}).", e);
+        writeln!(stream, "ERROR: {}", e)?;
+        return Ok(());
+    }
+
+    let mut request = String::new();
+    BufReader::new(&stream).read_line(&mut request)?;
+    let response = match apply_request(locator, Path::new(request.trim_end_matches('\n'))) {
+        Ok(()) => RESPONSE_OK.to_owned(),
+        Err(e) => {
+            error!("Unable to apply bundle ({}).", e);
+            format!("ERROR: {}", e)
+        }
+    };
+    writeln!(stream, "{}", response)?;
+    Ok(())
+}
+
+fn apply_request(locator: &VelopackLocator, request_path: &Path) -> Result<()> {
+    let root_path = locator.get_root_dir();
+    if !request_path.is_absolute() || !request_path.is_dir() {
+        bail!("{:?} is not an extracted app bundle.", request_path);
+    }
+
+    // the unsigned check in verify_bundle_signature is only a warning, but the helper must never install an unsigned bundle
+    if shared::get_code_signature_team_id(&root_path)?.is_none() {
+        bail!("The installed app is not signed with a team identifier.");
+    }
+
+    // the requested bundle is owned by the user who asked for it, so it is copied somewhere they can not write to
+    // before it is verified, otherwise it could be modified after it has been verified
+    let tmp_path_new = super::apply_osx_impl::get_sibling_temp_path(&root_path);
+    let action: Result<()> = (|| {
+        info!("Copying bundle from {:?} to {:?}", request_path, &tmp_path_new);
+        run(Process::new("/usr/bin/ditto").arg(request_path).arg(&tmp_path_new))?;
+
+        let meta = fs::metadata(&root_path)?;
+        run(Process::new("/usr/sbin/chown").arg("-R").arg(format!("{}:{}", meta.uid(), meta.gid())).arg(&tmp_path_new))?;
+        shared::remove_quarantine_attribute(&tmp_path_new);
+        shared::verify_bundle_signature(&root_path, &tmp_path_new)?;
+        verify_not_downgrade(locator, &tmp_path_new)?;
+
+        info!("Swapping bundle at {:?}", &root_path);
+        super::apply_osx_impl::swap_bundles(&tmp_path_new, &root_path)?;
+        info!("Bundle applied successfully to {:?}", &root_path);
+        Ok(())
+    })();
+    let _ = fs::remove_dir_all(&tmp_path_new);
+    action
+}
+
+extern "C" {
+    // from <launch.h>, returns the file descriptors of a socket declared in the job's launchd plist
+    fn launch_activate_socket(name: *const libc::c_char, fds: *mut *mut libc::c_int, cnt: *mut libc::size_t) -> libc::c_int;
+}
+
+fn get_launchd_listener() -> Result<UnixListener> {
+    let name = CString::new("Listener")?;
+    let mut fds: *mut libc::c_int = std::ptr::null_mut();
+    let mut count: libc::size_t = 0;
+    // SAFETY: name is a valid nul-terminated string, and fds / count are valid pointers for the results
+    let ret = unsafe { launch_activate_socket(name.as_ptr(), &mut fds, &mut count) };
+    if ret != 0 {
+        bail!("Unable to get socket from launchd ({}).", std::io::Error::from_raw_os_error(ret));
+    }
+    if fds.is_null() || count == 0 {
+        bail!("launchd did not provide a socket.");
+    }
+
+    // SAFETY: launchd returned an array of `count` file descriptors, which must be freed by the caller
+    let fd = unsafe { *fds };
+    unsafe { libc::free(fds as *mut libc::c_void) };
+    Ok(unsafe { UnixListener::from_raw_fd(fd) })
+}
+
+fn verify_not_downgrade(locator: &VelopackLocator, new_bundle_path: &Path) -> Result<()> {
+    let manifest_path = new_bundle_path.join("Contents").join("MacOS").join("sq.version");
+    let manifest = velopack::bundle::read_manifest_from_reader(BufReader::new(fs::File::open(&manifest_path)?))?;
+    let installed = locator.get_manifest();
+    if manifest.id != installed.id {
+        bail!("The bundle is for app '{}', but the helper was installed for '{}'.", manifest.id, installed.id);
+    }
+    if manifest.version < locator.get_manifest_version() {
+        bail!("The bundle version {} is older than the installed version {}.", manifest.version, locator.get_manifest_version());
+    }
+    Ok(())
+}
+
+/// Checks that the process which connected to the helper is run by the user the helper was installed for, and is signed
+/// by the same team as the installed app, so that other users and processes can not ask for an update.
+fn verify_peer(locator: &VelopackLocator, stream: &UnixStream, owner_uid: u32) -> Result<()> {
+    let uid = get_peer_uid(stream).ok_or_else(|| anyhow!("Unable to get the uid of the peer."))?;
+    if uid != owner_uid && uid != 0 {
+        bail!("uid {} is not allowed to use the helper.", uid);
+    }
+
+    let pid = get_peer_pid(stream).ok_or_else(|| anyhow!("Unable to get the pid of the peer."))?;
+    let exe_path = get_pid_path(pid).ok_or_else(|| anyhow!("Unable to get the executable of pid {}.", pid))?;
+    let expected_team = shared::get_code_signature_team_id(locator.get_root_dir())?;
+    let peer_team = shared::get_code_signature_team_id(&exe_path)?;
+    if expected_team.is_none() || peer_team != expected_team {
+        bail!("{:?} (pid {}) is not signed by team '{}'.", exe_path, pid, expected_team.as_deref().unwrap_or("(none)"));
+    }
+    info!("Accepted connection from {:?} (pid {}, uid {}).", exe_path, pid, uid);
+    Ok(())
+}
+
+fn get_peer_uid(stream: &UnixStream) -> Option<u32> {
+    let mut uid: libc::uid_t = 0;
+    let mut gid: libc::gid_t = 0;
+    // SAFETY: the file descriptor is a connected unix socket, and uid / gid are valid pointers for the results
+    let ret = unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) };
+    if ret == 0 {
+        Some(uid)
+    } else {
+        None
+    }
+}
+
+fn get_peer_pid(stream: &UnixStream) -> Option<libc::pid_t> {
+    let mut pid: libc::pid_t = 0;
+    let mut len = std::mem::size_of::<libc::pid_t>() as libc::socklen_t;
+    // SAFETY: the file descriptor is a connected unix socket, and pid / len are valid pointers for the result
+    let ret = unsafe {
+        libc::getsockopt(stream.as_raw_fd(), libc::SOL_LOCAL, libc::LOCAL_PEERPID, &mut pid as *mut _ as *mut libc::c_void, &mut len)
+    };
+    if ret == 0 {
+        Some(pid)
+    } else {
+        None
+    }
+}
+
+fn get_pid_path(pid: libc::pid_t) -> Option<PathBuf> {
+    let mut buffer = vec![0u8; libc::PROC_PIDPATHINFO_MAXSIZE as usize];
+    // SAFETY: the buffer is valid for the length which is passed
+    let len = unsafe { libc::proc_pidpath(pid, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len() as u32) };
+    if len <= 0 {
+        return None;
+    }
+    buffer.truncate(len as usize);
+    Some(PathBuf::from(std::ffi::OsString::from_vec(buffer)))
+}
+
+/// Returns the user who asked for the helper to be installed, which is the user running this process, or the user who
+/// ran sudo if this process is already root.
+fn get_requesting_uid() -> u32 {
+    let uid = unsafe { libc::getuid() };
+    if uid == 0 {
+        if let Some(sudo_uid) = std::env::var("SUDO_UID").ok().and_then(|u| u.parse().ok()) {
+            return sudo_uid;
+        }
+    }
+    uid
+}
+
+fn run_as_admin(script: &str, args: &[&str]) -> Result<()> {
+    if unsafe { libc::geteuid() } == 0 {
+        run(Process::new("/bin/sh").arg("-c").arg(script).arg("sh").args(args))?;
+        return Ok(());
+    }
+
+    // the arguments are passed to the script as positional parameters, so that they never need to be escaped
+    let apple_script = [
+        "on run argv",
+        "set cmd to item 1 of argv",
+        "repeat with i from 2 to count of argv",
+        "set cmd to cmd & \" \" & quoted form of (item i of argv)",
+        "end repeat",
+        "do shell script cmd with administrator privileges",
+        "end run",
+    ];
+    let mut cmd = Process::new("/usr/bin/osascript");
+    for line in apple_script {
+        cmd.arg("-e").arg(line);
+    }
+    let shell = format!("/bin/sh -c {} sh", quote_shell(script));
+    run(cmd.arg(shell).args(args)).map_err(|e| anyhow!("Unable to run as administrator ({}).", e))?;
+    Ok(())
+}
+
+fn quote_shell(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn run(cmd: &mut Process) -> Result<()> {
+    let output = cmd.output()?;
+    if !output.status.success() {
+        bail!("{:?} failed: {}", cmd, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+fn get_helper_label(locator: &VelopackLocator) -> String {
+    format!("{}.velopack-helper", locator.get_install_id())
+}
+
+fn get_helper_plist_path(label: &str) -> PathBuf {
+    PathBuf::from("/Library/LaunchDaemons").join(format!("{}.plist", label))
+}
+
+fn get_helper_tool_path(label: &str) -> PathBuf {
+    PathBuf::from("/Library/PrivilegedHelperTools").join(label)
+}
+
+fn get_helper_socket_path(label: &str) -> PathBuf {
+    PathBuf::from("/var/run").join(format!("{}.sock", label))
+}
+
+fn create_helper_plist(label: &str, tool_path: &Path, root_dir: &Path, owner_uid: u32, socket_path: &Path) -> String {
+    // SockPathMode is 0600 and SockPathOwner is the user who installed the helper, so that only they can connect to it
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{}</string>
+        <string>privileged-helper</string>
+        <string>--root</string>
+        <string>{}</string>
+        <string>--uid</string>
+        <string>{}</string>
+    </array>
+    <key>Sockets</key>
+    <dict>
+        <key>Listener</key>
+        <dict>
+            <key>SockPathName</key>
+            <string>{}</string>
+            <key>SockPathMode</key>
+            <integer>384</integer>
+            <key>SockPathOwner</key>
+            <integer>{}</integer>
+        </dict>
+    </dict>
+</dict>
+</plist>
+"#,
+        escape_xml(label),
+        escape_xml(&tool_path.to_string_lossy()),
+        escape_xml(&root_dir.to_string_lossy()),
+        owner_uid,
+        escape_xml(&socket_path.to_string_lossy()),
+        owner_uid
+    )
+}
+
+#[test]
+fn test_create_helper_plist_declares_socket() {
+    let label = "com.example.app.velopack-helper";
+    let root_dir = Path::new("/Applications/A & B.app");
+    let plist = create_helper_plist(label, &get_helper_tool_path(label), root_dir, 501, &get_helper_socket_path(label));
+    assert!(plist.contains("<string>com.example.app.velopack-helper</string>"));
+    assert!(plist.contains("<string>/Library/PrivilegedHelperTools/com.example.app.velopack-helper</string>"));
+    assert!(plist.contains("<string>--root</string>\n        <string>/Applications/A &amp; B.app</string>"));
+    assert!(plist.contains("<string>--uid</string>\n        <string>501</string>"));
+    assert!(plist.contains("<string>/var/run/com.example.app.velopack-helper.sock</string>"));
+    assert!(plist.contains("<integer>384</integer>"));
+    assert!(plist.contains("<key>SockPathOwner</key>\n            <integer>501</integer>"));
+    assert_eq!(quote_shell("it's"), "'it'\\''s'");
+}
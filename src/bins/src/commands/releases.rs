@@ -0,0 +1,61 @@
+use anyhow::Result;
+use std::{fs, path::PathBuf};
+use velopack::releases::ReleaseIndex;
+
+#[derive(Debug, Clone, Default)]
+pub struct ReleasesOptions {
+    /// The directory containing the packages and the release index.
+    pub releases_dir: PathBuf,
+    /// The channel whose index is updated.
+    pub channel: String,
+    /// Packages (in the releases directory) to add to the index, replacing any existing entries for them.
+    pub add: Vec<PathBuf>,
+    /// Recreates the index from every package of the channel in the directory, instead of updating the existing one.
+    pub rebuild: bool,
+    /// Removes all but this many of the newest full releases (and their deltas) from the index.
+    pub keep_releases: Option<usize>,
+    /// Deletes the packages which were pruned from the index.
+    pub delete_pruned: bool,
+    /// Recalculates the size and checksums of every package in the index, and the chunk indexes published next to them.
+    /// Packages are not signed by this, it is for packages which were re-signed by another tool after they were added.
+    pub refresh_checksums: bool,
+}
+
+/// Updates the release index of a channel in a directory of packages, and returns it once it has been saved.
+pub fn update_releases(options: &ReleasesOptions) -> Result<ReleaseIndex> {
+    let mut index = match options.rebuild {
+        true => ReleaseIndex::from_directory(&options.releases_dir, &options.channel)?,
+        false => ReleaseIndex::load(&options.releases_dir, &options.channel)?,
+    };
+
+    for package in &options.add {
+        index.add_package(package)?;
+    }
+
+    if let Some(keep) = options.keep_releases {
+        for asset in index.prune(keep) {
+            if options.delete_pruned {
+                let path = options.releases_dir.join(&asset.FileName);
+                info!("Deleting pruned package {:?}", path);
+                if let Err(e) = fs::remove_file(&path) {
+                    warn!("Unable to delete {:?} ({}).", path, e);
+                }
+                let _ = fs::remove_file(options.releases_dir.join(velopack::chunks::get_chunk_index_file_name(&asset.FileName)));
+            }
+        }
+    }
+
+    if options.refresh_checksums {
+        index.refresh_checksums()?;
+        // a chunk index which no longer matches its package is ignored by apps, so it is rewritten along with the checksums
+        for asset in &index.feed().Assets {
+            let package = options.releases_dir.join(&asset.FileName);
+            if options.releases_dir.join(velopack::chunks::get_chunk_index_file_name(&asset.FileName)).is_file() {
+                velopack::chunks::write_chunk_index(&package)?;
+            }
+        }
+    }
+
+    index.save()?;
+    Ok(index)
+}
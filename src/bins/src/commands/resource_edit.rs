@@ -0,0 +1,809 @@
+use anyhow::{anyhow, bail, Result};
+use regex::Regex;
+use semver::Version;
+use std::{collections::BTreeMap, fs, path::Path, path::PathBuf};
+
+const RT_ICON: u16 = 3;
+const RT_GROUP_ICON: u16 = 14;
+const RT_VERSION: u16 = 16;
+const RT_MANIFEST: u16 = 24;
+
+const IMAGE_DIRECTORY_ENTRY_RESOURCE: usize = 2;
+const IMAGE_DIRECTORY_ENTRY_SECURITY: usize = 4;
+const SECTION_HEADER_SIZE: usize = 40;
+const RSRC_CHARACTERISTICS: u32 = 0x4000_0040; // IMAGE_SCN_CNT_INITIALIZED_DATA | IMAGE_SCN_MEM_READ
+
+/// The default manifest of the Velopack binaries, which is used if an executable does not have one.
+const DEFAULT_MANIFEST: &str = include_str!("../../app.manifest");
+
+/// The identifier of a resource type or name, named entries are sorted before numeric ones as the PE format requires.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum ResourceName {
+    Name(String),
+    Id(u16),
+}
+
+/// Resources by type, then by name, then by language.
+type ResourceTree = BTreeMap<ResourceName, BTreeMap<ResourceName, BTreeMap<u16, Vec<u8>>>>;
+
+/// The privileges an executable asks for when it is started, from the requestedExecutionLevel of its manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionLevel {
+    AsInvoker,
+    HighestAvailable,
+    RequireAdministrator,
+}
+
+impl std::str::FromStr for ExecutionLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "asinvoker" => Ok(ExecutionLevel::AsInvoker),
+            "highestavailable" => Ok(ExecutionLevel::HighestAvailable),
+            "requireadministrator" => Ok(ExecutionLevel::RequireAdministrator),
+            _ => bail!("Unknown execution level '{}', expected asInvoker, highestAvailable or requireAdministrator.", s),
+        }
+    }
+}
+
+impl ExecutionLevel {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ExecutionLevel::AsInvoker => "asInvoker",
+            ExecutionLevel::HighestAvailable => "highestAvailable",
+            ExecutionLevel::RequireAdministrator => "requireAdministrator",
+        }
+    }
+}
+
+/// The strings shown on the details tab of the file properties of an executable.
+#[derive(Debug, Clone, Default)]
+pub struct VersionInfo {
+    pub version: Option<Version>,
+    pub product_name: String,
+    pub company_name: String,
+    pub description: String,
+    pub copyright: String,
+}
+
+/// Edits the Win32 resources (icon, version info and manifest) of a Windows executable, so the installer and updater
+/// show the branding of the app. This works on any OS, and replaces the rcedit-like functionality of vpk.
+pub struct ResourceEdit {
+    path: PathBuf,
+    pe: PeFile,
+    resources: ResourceTree,
+}
+
+impl ResourceEdit {
+    /// Loads the resources of an executable, so they can be edited and written back with [ResourceEdit::commit].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let pe = PeFile::parse(fs::read(&path)?).map_err(|e| anyhow!("{:?} is not a valid executable ({}).", path, e))?;
+        let resources = pe.read_resources()?;
+        Ok(ResourceEdit { path, pe, resources })
+    }
+
+    /// The language of the existing version info, which new resources use as well. This is neutral if there is none.
+    fn get_language(&self) -> u16 {
+        self.resources
+            .get(&ResourceName::Id(RT_VERSION))
+            .and_then(|names| names.values().next())
+            .and_then(|langs| langs.keys().next().cloned())
+            .unwrap_or(0)
+    }
+
+    fn set_resource(&mut self, resource_type: u16, name: u16, data: Vec<u8>) {
+        let language = self.get_language();
+        let langs = self.resources.entry(ResourceName::Id(resource_type)).or_default().entry(ResourceName::Id(name)).or_default();
+        langs.clear();
+        langs.insert(language, data);
+    }
+
+    /// Replaces every icon of the executable with the images in an .ico file.
+    pub fn set_icon<P: AsRef<Path>>(&mut self, ico_path: P) -> Result<()> {
+        let ico_path = ico_path.as_ref();
+        let ico = fs::read(ico_path)?;
+        let images = parse_ico(&ico).map_err(|e| anyhow!("{:?} is not a valid .ico file ({}).", ico_path, e))?;
+
+        self.resources.remove(&ResourceName::Id(RT_ICON));
+        self.resources.remove(&ResourceName::Id(RT_GROUP_ICON));
+
+        let mut group = Vec::new();
+        write_u16(&mut group, 0);
+        write_u16(&mut group, 1);
+        write_u16(&mut group, images.len() as u16);
+        for (i, image) in images.into_iter().enumerate() {
+            let id = i as u16 + 1;
+            // the group entry is the same as the .ico entry, with the resource id in place of the offset into the file
+            group.extend_from_slice(&image.header[..12]);
+            write_u16(&mut group, id);
+            self.set_resource(RT_ICON, id, image.data);
+        }
+        self.set_resource(RT_GROUP_ICON, 1, group);
+        Ok(())
+    }
+
+    /// Replaces the version info of the executable.
+    pub fn set_version_info(&mut self, info: &VersionInfo) -> Result<()> {
+        let language = self.get_language();
+        self.resources.remove(&ResourceName::Id(RT_VERSION));
+        let data = create_version_info(info, language);
+        self.set_resource(RT_VERSION, 1, data);
+        Ok(())
+    }
+
+    /// Changes the requestedExecutionLevel and dpiAware settings in the manifest of the executable, and adds a manifest
+    /// if it doesn't have one. Settings which are None are left as they are.
+    pub fn set_manifest_options(&mut self, execution_level: Option<ExecutionLevel>, dpi_aware: Option<bool>) -> Result<()> {
+        let existing = self
+            .resources
+            .get(&ResourceName::Id(RT_MANIFEST))
+            .and_then(|names| names.values().next())
+            .and_then(|langs| langs.values().next())
+            .map(|data| String::from_utf8_lossy(data).trim_start_matches('\u{feff}').to_string());
+        let manifest = update_manifest(existing.as_deref().unwrap_or(DEFAULT_MANIFEST), execution_level, dpi_aware);
+        self.resources.remove(&ResourceName::Id(RT_MANIFEST));
+        self.set_resource(RT_MANIFEST, 1, manifest.into_bytes());
+        Ok(())
+    }
+
+    /// Replaces every resource of the executable with the resources of another one (eg. so an execution stub has the
+    /// icon and version info of the app it starts).
+    pub fn copy_resources_from<P: AsRef<Path>>(&mut self, other_path: P) -> Result<()> {
+        let other_path = other_path.as_ref();
+        let other = PeFile::parse(fs::read(other_path)?).map_err(|e| anyhow!("{:?} is not a valid executable ({}).", other_path, e))?;
+        self.resources = other.read_resources()?;
+        Ok(())
+    }
+
+    /// Writes the executable back to disk with the edited resources. Any Authenticode signature is removed, because it
+    /// is no longer valid once the file has changed, so the file must be signed afterwards.
+    pub fn commit(self) -> Result<()> {
+        let bytes = self.pe.write_resources(&self.resources)?;
+        fs::write(&self.path, bytes)?;
+        Ok(())
+    }
+}
+
+/// Returns true if the file is an executable which has an Authenticode signature (valid or not).
+pub fn has_authenticode_signature<P: AsRef<Path>>(path: P) -> bool {
+    fs::read(path)
+        .ok()
+        .and_then(|data| PeFile::parse(data).ok())
+        .and_then(|pe| pe.get_data_directory(IMAGE_DIRECTORY_ENTRY_SECURITY).ok())
+        .map(|(_, size)| size > 0)
+        .unwrap_or(false)
+}
+
+struct IcoImage {
+    header: [u8; 16],
+    data: Vec<u8>,
+}
+
+fn parse_ico(ico: &[u8]) -> Result<Vec<IcoImage>> {
+    if read_u16(ico, 0)? != 0 || read_u16(ico, 2)? != 1 {
+        bail!("invalid header");
+    }
+    let count = read_u16(ico, 4)? as usize;
+    if count == 0 {
+        bail!("it contains no images");
+    }
+
+    let mut images = Vec::new();
+    for i in 0..count {
+        let offset = 6 + i * 16;
+        let mut header: [u8; 16] = ico.get(offset..offset + 16).ok_or_else(|| anyhow!("truncated header"))?.try_into()?;
+        let size = read_u32(&header, 8)? as usize;
+        let data_offset = read_u32(&header, 12)? as usize;
+        let data = ico.get(data_offset..data_offset + size).ok_or_else(|| anyhow!("image {} is out of bounds", i))?.to_vec();
+        // some tools leave the planes and bit count empty, which Windows needs to pick the best image
+        if read_u16(&header, 4)? == 0 {
+            header[4..6].copy_from_slice(&1u16.to_le_bytes());
+        }
+        if read_u16(&header, 6)? == 0 && !data.starts_with(b"\x89PNG") {
+            let bit_count = read_u16(&data, 14)?;
+            header[6..8].copy_from_slice(&bit_count.to_le_bytes());
+        }
+        images.push(IcoImage { header, data });
+    }
+    Ok(images)
+}
+
+fn create_version_info(info: &VersionInfo, language: u16) -> Vec<u8> {
+    const CODE_PAGE_UTF16: u16 = 1200;
+    let version = info.version.clone().unwrap_or_else(|| Version::new(0, 0, 0));
+    let version_ms = ((version.major.min(0xffff) as u32) << 16) | version.minor.min(0xffff) as u32;
+    let version_ls = (version.patch.min(0xffff) as u32) << 16;
+
+    let mut fixed = Vec::new();
+    for value in [
+        0xfeef04bd, // signature
+        0x00010000, // struct version
+        version_ms, // file version (major, minor)
+        version_ls, // file version (patch, build)
+        version_ms, // product version (major, minor)
+        version_ls, // product version (patch, build)
+        0x3f,       // flags mask
+        0,          // flags
+        0x40004,    // VOS_NT_WINDOWS32
+        1,          // VFT_APP
+        0,          // sub type
+        0,          // date (high)
+        0,          // date (low)
+    ] {
+        write_u32(&mut fixed, value);
+    }
+
+    let version_string = version.to_string();
+    let comments = format!("Generated by Velopack {}", env!("NGBV_VERSION"));
+    let strings = [
+        ("CompanyName", info.company_name.as_str()),
+        ("FileDescription", info.description.as_str()),
+        ("FileVersion", version_string.as_str()),
+        ("LegalCopyright", info.copyright.as_str()),
+        ("ProductName", info.product_name.as_str()),
+        ("ProductVersion", version_string.as_str()),
+        ("Comments", comments.as_str()),
+    ];
+    let string_nodes: Vec<Vec<u8>> = strings
+        .iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(key, value)| {
+            let text = to_utf16z(value);
+            // the value length of a string is in characters, including the terminating null
+            version_node(key, &text, (text.len() / 2) as u16, 1, &[])
+        })
+        .collect();
+    let table_key = format!("{:04X}{:04X}", language, CODE_PAGE_UTF16);
+    let string_table = version_node(&table_key, &[], 0, 1, &string_nodes);
+    let string_file_info = version_node("StringFileInfo", &[], 0, 1, &[string_table]);
+
+    let mut translation = Vec::new();
+    write_u16(&mut translation, language);
+    write_u16(&mut translation, CODE_PAGE_UTF16);
+    let var = version_node("Translation", &translation, translation.len() as u16, 0, &[]);
+    let var_file_info = version_node("VarFileInfo", &[], 0, 1, &[var]);
+
+    version_node("VS_VERSION_INFO", &fixed, fixed.len() as u16, 0, &[string_file_info, var_file_info])
+}
+
+/// Writes one of the nested structures a version info resource is made of, which are all laid out the same way.
+fn version_node(key: &str, value: &[u8], value_length: u16, value_type: u16, children: &[Vec<u8>]) -> Vec<u8> {
+    let mut node = vec![0, 0];
+    write_u16(&mut node, value_length);
+    write_u16(&mut node, value_type);
+    node.extend_from_slice(&to_utf16z(key));
+    pad_to(&mut node, 4);
+    node.extend_from_slice(value);
+    for child in children {
+        pad_to(&mut node, 4);
+        node.extend_from_slice(child);
+    }
+    let length = node.len() as u16;
+    node[0..2].copy_from_slice(&length.to_le_bytes());
+    node
+}
+
+fn update_manifest(manifest: &str, execution_level: Option<ExecutionLevel>, dpi_aware: Option<bool>) -> String {
+    let mut manifest = manifest.to_owned();
+
+    if let Some(level) = execution_level {
+        let re = Regex::new(r#"(<requestedExecutionLevel\b[^>]*?\blevel\s*=\s*")[^"]*(")"#).unwrap();
+        if re.is_match(&manifest) {
+            manifest = re.replace(&manifest, format!("${{1}}{}${{2}}", level.as_str())).to_string();
+        } else {
+            let trust_info = format!(
+                "  <trustInfo xmlns=\"urn:schemas-microsoft-com:asm.v2\">\n    <security>\n      \
+                 <requestedPrivileges xmlns=\"urn:schemas-microsoft-com:asm.v3\">\n        \
+                 <requestedExecutionLevel level=\"{}\" uiAccess=\"false\" />\n      </requestedPrivileges>\n    \
+                 </security>\n  </trustInfo>\n",
+                level.as_str()
+            );
+            manifest = insert_before_assembly_end(&manifest, &trust_info);
+        }
+    }
+
+    if let Some(dpi_aware) = dpi_aware {
+        let value = if dpi_aware { "True" } else { "False" };
+        let re = Regex::new(r"(?s)(<dpiAware\b[^>]*>).*?(</dpiAware>)").unwrap();
+        if re.is_match(&manifest) {
+            manifest = re.replace(&manifest, format!("${{1}}{}${{2}}", value)).to_string();
+        } else {
+            let settings = format!(
+                "  <application xmlns=\"urn:schemas-microsoft-com:asm.v3\">\n    <windowsSettings>\n      \
+                 <dpiAware xmlns=\"http://schemas.microsoft.com/SMI/2005/WindowsSettings\">{}</dpiAware>\n    \
+                 </windowsSettings>\n  </application>\n",
+                value
+            );
+            manifest = insert_before_assembly_end(&manifest, &settings);
+        }
+        if !dpi_aware {
+            // dpiAwareness takes precedence over dpiAware on Windows 10 and later, so it has to go as well
+            let re = Regex::new(r"(?s)\s*<dpiAwareness\b[^>]*>.*?</dpiAwareness>").unwrap();
+            manifest = re.replace_all(&manifest, "").to_string();
+        }
+    }
+
+    manifest
+}
+
+fn insert_before_assembly_end(manifest: &str, xml: &str) -> String {
+    match manifest.rfind("</assembly>") {
+        Some(idx) => format!("{}{}{}", &manifest[..idx], xml, &manifest[idx..]),
+        None => format!("{}\n{}", manifest, xml),
+    }
+}
+
+struct SectionHeader {
+    header_offset: usize,
+    virtual_size: u32,
+    virtual_address: u32,
+    raw_size: u32,
+    raw_offset: u32,
+}
+
+/// The parts of a PE file which are needed to read and replace its resources.
+struct PeFile {
+    data: Vec<u8>,
+    optional_header_offset: usize,
+    data_directories_offset: usize,
+    number_of_rva_and_sizes: usize,
+    sections: Vec<SectionHeader>,
+}
+
+impl PeFile {
+    fn parse(data: Vec<u8>) -> Result<Self> {
+        if !data.starts_with(b"MZ") {
+            bail!("missing MZ header");
+        }
+        let pe_offset = read_u32(&data, 0x3c)? as usize;
+        if data.get(pe_offset..pe_offset + 4) != Some(b"PE\0\0") {
+            bail!("missing PE header");
+        }
+        let coff_offset = pe_offset + 4;
+        let number_of_sections = read_u16(&data, coff_offset + 2)? as usize;
+        let optional_header_size = read_u16(&data, coff_offset + 16)? as usize;
+        let optional_header_offset = coff_offset + 20;
+        let (data_directories_offset, number_of_rva_and_sizes) = match read_u16(&data, optional_header_offset)? {
+            0x10b => (optional_header_offset + 96, read_u32(&data, optional_header_offset + 92)? as usize),
+            0x20b => (optional_header_offset + 112, read_u32(&data, optional_header_offset + 108)? as usize),
+            magic => bail!("unknown optional header magic {:#x}", magic),
+        };
+
+        let section_table_offset = optional_header_offset + optional_header_size;
+        let mut sections = Vec::new();
+        for i in 0..number_of_sections {
+            let header_offset = section_table_offset + i * SECTION_HEADER_SIZE;
+            sections.push(SectionHeader {
+                header_offset,
+                virtual_size: read_u32(&data, header_offset + 8)?,
+                virtual_address: read_u32(&data, header_offset + 12)?,
+                raw_size: read_u32(&data, header_offset + 16)?,
+                raw_offset: read_u32(&data, header_offset + 20)?,
+            });
+        }
+        Ok(PeFile { data, optional_header_offset, data_directories_offset, number_of_rva_and_sizes, sections })
+    }
+
+    fn get_data_directory(&self, index: usize) -> Result<(u32, u32)> {
+        if index >= self.number_of_rva_and_sizes {
+            return Ok((0, 0));
+        }
+        let offset = self.data_directories_offset + index * 8;
+        Ok((read_u32(&self.data, offset)?, read_u32(&self.data, offset + 4)?))
+    }
+
+    fn set_data_directory(&mut self, index: usize, rva: u32, size: u32) -> Result<()> {
+        if index >= self.number_of_rva_and_sizes {
+            bail!("The executable does not have a data directory for index {}.", index);
+        }
+        let offset = self.data_directories_offset + index * 8;
+        write_u32_at(&mut self.data, offset, rva);
+        write_u32_at(&mut self.data, offset + 4, size);
+        Ok(())
+    }
+
+    fn rva_to_offset(&self, rva: u32) -> Result<usize> {
+        // the section headers come from the file, so a corrupt file must not be able to overflow these
+        self.sections
+            .iter()
+            .find(|s| {
+                rva >= s.virtual_address && s.virtual_address.checked_add(s.virtual_size.max(s.raw_size)).is_some_and(|end| rva < end)
+            })
+            .and_then(|s| (rva - s.virtual_address).checked_add(s.raw_offset))
+            .map(|offset| offset as usize)
+            .ok_or_else(|| anyhow!("RVA {:#x} is not in any section", rva))
+    }
+
+    fn read_resources(&self) -> Result<ResourceTree> {
+        let mut tree = ResourceTree::new();
+        let (rva, _) = self.get_data_directory(IMAGE_DIRECTORY_ENTRY_RESOURCE)?;
+        if rva == 0 {
+            return Ok(tree);
+        }
+
+        let base = self.rva_to_offset(rva)?;
+        for (type_name, names_offset) in self.read_resource_directory(base, 0)? {
+            let names = tree.entry(type_name).or_default();
+            for (name, langs_offset) in self.read_resource_directory(base, subdirectory(names_offset)?)? {
+                let langs = names.entry(name).or_default();
+                for (lang, entry_offset) in self.read_resource_directory(base, subdirectory(langs_offset)?)? {
+                    let lang = match lang {
+                        ResourceName::Id(id) => id,
+                        ResourceName::Name(_) => bail!("invalid resource language"),
+                    };
+                    if entry_offset & 0x8000_0000 != 0 {
+                        bail!("resource tree is too deep");
+                    }
+                    let entry = base + entry_offset as usize;
+                    let data_offset = self.rva_to_offset(read_u32(&self.data, entry)?)?;
+                    let size = read_u32(&self.data, entry + 4)? as usize;
+                    let data = data_offset
+                        .checked_add(size)
+                        .and_then(|end| self.data.get(data_offset..end))
+                        .ok_or_else(|| anyhow!("resource data is out of bounds"))?;
+                    langs.insert(lang, data.to_vec());
+                }
+            }
+        }
+        Ok(tree)
+    }
+
+    /// Returns the entries of the resource directory at an offset relative to the start of the resources, with the
+    /// offset of what each entry points to. The high bit of the offset is set if it points to another directory.
+    fn read_resource_directory(&self, base: usize, offset: usize) -> Result<Vec<(ResourceName, u32)>> {
+        let dir = base + offset;
+        let count = read_u16(&self.data, dir + 12)? as usize + read_u16(&self.data, dir + 14)? as usize;
+        let mut entries = Vec::new();
+        for i in 0..count {
+            let entry = dir + 16 + i * 8;
+            let name = read_u32(&self.data, entry)?;
+            let name = if name & 0x8000_0000 != 0 {
+                let name_offset = base + (name & 0x7fff_ffff) as usize;
+                let length = read_u16(&self.data, name_offset)? as usize;
+                let units: Result<Vec<u16>> = (0..length).map(|c| read_u16(&self.data, name_offset + 2 + c * 2)).collect();
+                ResourceName::Name(String::from_utf16_lossy(&units?))
+            } else {
+                ResourceName::Id(name as u16)
+            };
+            entries.push((name, read_u32(&self.data, entry + 4)?));
+        }
+        Ok(entries)
+    }
+
+    /// Returns a copy of the file with the resources replaced. The resources are rewritten in place if they are in the
+    /// last section of the file, otherwise they are moved to a new section at the end.
+    fn write_resources(&self, resources: &ResourceTree) -> Result<Vec<u8>> {
+        let mut pe = PeFile::parse(self.data.clone())?;
+        let section_alignment = read_u32(&pe.data, pe.optional_header_offset + 32)?;
+        let file_alignment = read_u32(&pe.data, pe.optional_header_offset + 36)?;
+
+        // the signature is at the end of the file, and is invalid once the resources change
+        let (cert_offset, cert_size) = pe.get_data_directory(IMAGE_DIRECTORY_ENTRY_SECURITY)?;
+        if cert_size > 0 {
+            pe.set_data_directory(IMAGE_DIRECTORY_ENTRY_SECURITY, 0, 0)?;
+            pe.data.truncate(cert_offset as usize);
+        }
+
+        let last = pe.sections.iter().max_by_key(|s| s.virtual_address).ok_or_else(|| anyhow!("The executable has no sections."))?;
+        let end_of_sections = pe.sections.iter().map(|s| s.raw_offset as usize + s.raw_size as usize).max().unwrap_or(0);
+        if pe.data.len() > end_of_sections {
+            bail!("The executable has data appended to it, its resources must be edited before anything is appended.");
+        }
+
+        let (rsrc_rva, _) = pe.get_data_directory(IMAGE_DIRECTORY_ENTRY_RESOURCE)?;
+        let rewrite_last = rsrc_rva != 0 && rsrc_rva == last.virtual_address;
+        let (header_offset, virtual_address, raw_offset) = if rewrite_last {
+            (last.header_offset, last.virtual_address, last.raw_offset)
+        } else {
+            let section_table_end = pe.sections.iter().map(|s| s.header_offset + SECTION_HEADER_SIZE).max().unwrap_or(0);
+            let first_raw = pe.sections.iter().map(|s| s.raw_offset as usize).filter(|o| *o > 0).min().unwrap_or(0);
+            if section_table_end + SECTION_HEADER_SIZE > first_raw {
+                bail!("The executable does not have room for another section header.");
+            }
+            let coff_offset = pe.optional_header_offset - 20;
+            let count = read_u16(&pe.data, coff_offset + 2)?;
+            write_u16_at(&mut pe.data, coff_offset + 2, count + 1);
+            let virtual_address = align(last.virtual_address + last.virtual_size.max(last.raw_size), section_alignment);
+            (section_table_end, virtual_address, align(end_of_sections as u32, file_alignment))
+        };
+
+        let rsrc = build_resource_section(resources, virtual_address);
+        let raw_size = align(rsrc.len() as u32, file_alignment);
+        pe.data.resize(raw_offset as usize, 0);
+        pe.data.extend_from_slice(&rsrc);
+        pe.data.resize(raw_offset as usize + raw_size as usize, 0);
+
+        let header = &mut pe.data[header_offset..header_offset + SECTION_HEADER_SIZE];
+        header[0..8].copy_from_slice(b".rsrc\0\0\0");
+        header[8..12].copy_from_slice(&(rsrc.len() as u32).to_le_bytes());
+        header[12..16].copy_from_slice(&virtual_address.to_le_bytes());
+        header[16..20].copy_from_slice(&raw_size.to_le_bytes());
+        header[20..24].copy_from_slice(&raw_offset.to_le_bytes());
+        header[24..36].fill(0);
+        header[36..40].copy_from_slice(&RSRC_CHARACTERISTICS.to_le_bytes());
+
+        pe.set_data_directory(IMAGE_DIRECTORY_ENTRY_RESOURCE, virtual_address, rsrc.len() as u32)?;
+        let size_of_image = align(virtual_address + rsrc.len() as u32, section_alignment);
+        write_u32_at(&mut pe.data, pe.optional_header_offset + 56, size_of_image);
+        let checksum = calculate_checksum(&pe.data, pe.optional_header_offset + 64);
+        write_u32_at(&mut pe.data, pe.optional_header_offset + 64, checksum);
+        Ok(pe.data)
+    }
+}
+
+fn subdirectory(offset: u32) -> Result<usize> {
+    if offset & 0x8000_0000 == 0 {
+        bail!("resource tree is too shallow");
+    }
+    Ok((offset & 0x7fff_ffff) as usize)
+}
+
+/// Lays out a resource section: the directory tables, then the data entries, then the names, and finally the data.
+fn build_resource_section(resources: &ResourceTree, section_rva: u32) -> Vec<u8> {
+    let dir_size = |entries: usize| 16 + entries * 8;
+    let mut tables_size = dir_size(resources.len());
+    for names in resources.values() {
+        tables_size += dir_size(names.len());
+        tables_size += names.values().map(|langs| dir_size(langs.len())).sum::<usize>();
+    }
+    let leaf_count: usize = resources.values().flat_map(|names| names.values()).map(|langs| langs.len()).sum();
+    let data_entries_offset = tables_size;
+    let mut strings_offset = data_entries_offset + leaf_count * 16;
+
+    // assign offsets to the names, which are shared by every directory which uses them
+    let mut string_offsets = BTreeMap::new();
+    let mut strings = Vec::new();
+    let all_names = resources.keys().chain(resources.values().flat_map(|names| names.keys()));
+    for name in all_names {
+        if let ResourceName::Name(text) = name {
+            if !string_offsets.contains_key(text) {
+                string_offsets.insert(text.clone(), strings_offset + strings.len());
+                let units: Vec<u16> = text.encode_utf16().collect();
+                write_u16(&mut strings, units.len() as u16);
+                units.iter().for_each(|u| write_u16(&mut strings, *u));
+            }
+        }
+    }
+    strings_offset += strings.len();
+    let mut data_offset = strings_offset;
+    data_offset += (8 - data_offset % 8) % 8;
+
+    let mut tables = Vec::new();
+    let mut data_entries = Vec::new();
+    let mut data = Vec::new();
+    let write_entry = |tables: &mut Vec<u8>, name: &ResourceName, target: u32| {
+        match name {
+            ResourceName::Name(text) => write_u32(tables, 0x8000_0000 | string_offsets[text] as u32),
+            ResourceName::Id(id) => write_u32(tables, *id as u32),
+        }
+        write_u32(tables, target);
+    };
+    let write_header = |tables: &mut Vec<u8>, named: usize, ids: usize| {
+        write_u32(tables, 0);
+        write_u32(tables, 0);
+        write_u32(tables, 0);
+        write_u16(tables, named as u16);
+        write_u16(tables, ids as u16);
+    };
+    let count_named = |names: &mut dyn Iterator<Item = &ResourceName>| names.filter(|n| matches!(n, ResourceName::Name(_))).count();
+
+    // the root directory, then the directory of every type, then the directory of every name, which lists its languages
+    let mut next_dir = dir_size(resources.len());
+    let named = count_named(&mut resources.keys());
+    write_header(&mut tables, named, resources.len() - named);
+    for (type_name, names) in resources {
+        write_entry(&mut tables, type_name, 0x8000_0000 | next_dir as u32);
+        next_dir += dir_size(names.len());
+    }
+    for names in resources.values() {
+        let named = count_named(&mut names.keys());
+        write_header(&mut tables, named, names.len() - named);
+        for (name, langs) in names {
+            write_entry(&mut tables, name, 0x8000_0000 | next_dir as u32);
+            next_dir += dir_size(langs.len());
+        }
+    }
+    for langs in resources.values().flat_map(|names| names.values()) {
+        write_header(&mut tables, 0, langs.len());
+        for (lang, bytes) in langs {
+            let entry_offset = data_entries_offset + data_entries.len();
+            write_entry(&mut tables, &ResourceName::Id(*lang), entry_offset as u32);
+            write_u32(&mut data_entries, section_rva + (data_offset + data.len()) as u32);
+            write_u32(&mut data_entries, bytes.len() as u32);
+            write_u32(&mut data_entries, 0);
+            write_u32(&mut data_entries, 0);
+            data.extend_from_slice(bytes);
+            pad_to(&mut data, 8);
+        }
+    }
+    debug_assert_eq!(tables.len(), tables_size);
+
+    let mut section = tables;
+    section.extend_from_slice(&data_entries);
+    section.extend_from_slice(&strings);
+    section.resize(data_offset, 0);
+    section.extend_from_slice(&data);
+    section
+}
+
+/// Calculates the PE checksum of a file, which is the same as what CheckSumMappedFile returns.
+fn calculate_checksum(data: &[u8], checksum_offset: usize) -> u32 {
+    let mut sum: u64 = 0;
+    for (i, chunk) in data.chunks(2).enumerate() {
+        if i * 2 == checksum_offset || i * 2 == checksum_offset + 2 {
+            continue;
+        }
+        let word = if chunk.len() == 2 { u16::from_le_bytes([chunk[0], chunk[1]]) } else { chunk[0] as u16 };
+        sum += word as u64;
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    sum = (sum & 0xffff) + (sum >> 16);
+    (sum as u32) + data.len() as u32
+}
+
+fn align(value: u32, alignment: u32) -> u32 {
+    if alignment == 0 {
+        value
+    } else {
+        value.div_ceil(alignment) * alignment
+    }
+}
+
+fn pad_to(buffer: &mut Vec<u8>, alignment: usize) {
+    while buffer.len() % alignment != 0 {
+        buffer.push(0);
+    }
+}
+
+fn to_utf16z(text: &str) -> Vec<u8> {
+    text.encode_utf16().chain(std::iter::once(0)).flat_map(|u| u.to_le_bytes()).collect()
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    let bytes = data.get(offset..offset + 2).ok_or_else(|| anyhow!("unexpected end of file at {:#x}", offset))?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    let bytes = data.get(offset..offset + 4).ok_or_else(|| anyhow!("unexpected end of file at {:#x}", offset))?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn write_u16(buffer: &mut Vec<u8>, value: u16) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_u16_at(buffer: &mut [u8], offset: usize, value: u16) {
+    buffer[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn write_u32_at(buffer: &mut [u8], offset: usize, value: u32) {
+    buffer[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+fn create_test_executable() -> Vec<u8> {
+    let mut pe = vec![0u8; 0x400];
+    pe[0..2].copy_from_slice(b"MZ");
+    write_u32_at(&mut pe, 0x3c, 0x80);
+    pe[0x80..0x84].copy_from_slice(b"PE\0\0");
+    write_u16_at(&mut pe, 0x84, 0x8664); // machine
+    write_u16_at(&mut pe, 0x86, 1); // number of sections
+    write_u16_at(&mut pe, 0x94, 240); // size of optional header
+    let optional = 0x98;
+    write_u16_at(&mut pe, optional, 0x20b);
+    write_u32_at(&mut pe, optional + 32, 0x1000); // section alignment
+    write_u32_at(&mut pe, optional + 36, 0x200); // file alignment
+    write_u32_at(&mut pe, optional + 56, 0x2000); // size of image
+    write_u32_at(&mut pe, optional + 60, 0x200); // size of headers
+    write_u32_at(&mut pe, optional + 108, 16); // number of data directories
+    let section = optional + 240;
+    pe[section..section + 5].copy_from_slice(b".text");
+    write_u32_at(&mut pe, section + 8, 0x10);
+    write_u32_at(&mut pe, section + 12, 0x1000);
+    write_u32_at(&mut pe, section + 16, 0x200);
+    write_u32_at(&mut pe, section + 20, 0x200);
+    pe[0x200..0x204].copy_from_slice(&[0xc3, 0xcc, 0xcc, 0xcc]);
+    pe
+}
+
+#[test]
+fn test_resource_edit_round_trips_resources() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    let exe = dir.join("setup.exe");
+    fs::write(&exe, create_test_executable()).unwrap();
+
+    let mut ico = Vec::new();
+    [0u16, 1, 2].iter().for_each(|v| write_u16(&mut ico, *v));
+    for (i, image) in [b"\x89PNG-small".as_slice(), b"\x89PNG-large-image".as_slice()].iter().enumerate() {
+        let offset = 6 + 2 * 16 + if i == 0 { 0 } else { 10 };
+        ico.extend_from_slice(&[16 * (i as u8 + 1), 16 * (i as u8 + 1), 0, 0]);
+        [1u16, 32].iter().for_each(|v| write_u16(&mut ico, *v));
+        [image.len() as u32, offset].iter().for_each(|v| write_u32(&mut ico, *v));
+    }
+    ico.extend_from_slice(b"\x89PNG-small\x89PNG-large-image");
+    fs::write(dir.join("app.ico"), &ico).unwrap();
+
+    let mut edit = ResourceEdit::load(&exe).unwrap();
+    edit.set_icon(dir.join("app.ico")).unwrap();
+    edit.set_version_info(&VersionInfo { version: Some(Version::new(1, 2, 3)), product_name: "My App".into(), ..Default::default() })
+        .unwrap();
+    edit.set_manifest_options(Some(ExecutionLevel::RequireAdministrator), Some(false)).unwrap();
+    edit.commit().unwrap();
+
+    let edit = ResourceEdit::load(&exe).unwrap();
+    assert_eq!(edit.pe.sections.len(), 2);
+    let get = |t: u16, n: u16| edit.resources[&ResourceName::Id(t)][&ResourceName::Id(n)][&0].clone();
+    assert_eq!(get(RT_ICON, 2), b"\x89PNG-large-image");
+    let group = get(RT_GROUP_ICON, 1);
+    assert_eq!(read_u16(&group, 4).unwrap(), 2);
+    assert_eq!(read_u16(&group, 6 + 14 + 12).unwrap(), 2);
+    let version = get(RT_VERSION, 1);
+    assert_eq!(read_u32(&version, 40).unwrap(), 0xfeef04bd);
+    assert_eq!(read_u32(&version, 48).unwrap(), 0x0001_0002);
+    assert!(version.windows(14).any(|w| w == to_utf16z("My App").as_slice()));
+    let manifest = String::from_utf8(get(RT_MANIFEST, 1)).unwrap();
+    assert!(manifest.contains(r#"level="requireAdministrator""#));
+    assert!(manifest.contains(">False</dpiAware>"));
+    assert!(!manifest.contains("dpiAwareness"));
+    assert_eq!(calculate_checksum(&edit.pe.data, edit.pe.optional_header_offset + 64), read_u32(&edit.pe.data, 0x98 + 64).unwrap());
+
+    // the resource section is now last, so editing again replaces it rather than adding another
+    let mut edit = edit;
+    edit.set_manifest_options(Some(ExecutionLevel::AsInvoker), None).unwrap();
+    edit.commit().unwrap();
+    let edit = ResourceEdit::load(&exe).unwrap();
+    assert_eq!(edit.pe.sections.len(), 2);
+    assert_eq!(edit.resources[&ResourceName::Id(RT_ICON)].len(), 2);
+    let manifest = String::from_utf8(edit.resources[&ResourceName::Id(RT_MANIFEST)][&ResourceName::Id(1)][&0].clone()).unwrap();
+    assert!(manifest.contains(r#"level="asInvoker""#));
+    assert!(manifest.contains(">False</dpiAware>"));
+
+    // an execution stub takes every resource of the executable it starts
+    let stub = dir.join("stub.exe");
+    fs::write(&stub, create_test_executable()).unwrap();
+    let mut edit = ResourceEdit::load(&stub).unwrap();
+    edit.copy_resources_from(&exe).unwrap();
+    edit.commit().unwrap();
+    assert_eq!(ResourceEdit::load(&stub).unwrap().resources, ResourceEdit::load(&exe).unwrap().resources);
+}
+
+#[test]
+fn test_resource_edit_removes_signature_of_signed_executable() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("..").join("..").join("test").join("fixtures").join("atom.exe");
+    let dir = tempfile::tempdir().unwrap();
+    let exe = dir.path().join("atom.exe");
+    fs::copy(&fixture, &exe).unwrap();
+    assert!(has_authenticode_signature(&exe));
+
+    let original = ResourceEdit::load(&exe).unwrap();
+    let mut edit = ResourceEdit::load(&exe).unwrap();
+    edit.set_version_info(&VersionInfo { version: Some(Version::new(9, 8, 7)), product_name: "Atom".into(), ..Default::default() })
+        .unwrap();
+    edit.commit().unwrap();
+
+    // the signature is removed rather than left invalid, and everything else in the file is kept
+    assert!(!has_authenticode_signature(&exe));
+    let edit = ResourceEdit::load(&exe).unwrap();
+    assert_eq!(edit.pe.get_data_directory(IMAGE_DIRECTORY_ENTRY_SECURITY).unwrap(), (0, 0));
+    assert_eq!(edit.resources.len(), original.resources.len());
+    for (kind, names) in &original.resources {
+        if *kind != ResourceName::Id(RT_VERSION) {
+            assert_eq!(&edit.resources[kind], names);
+        }
+    }
+    let version = &edit.resources[&ResourceName::Id(RT_VERSION)][&ResourceName::Id(1)];
+    let version = version.values().next().unwrap();
+    assert_eq!(read_u32(version, 48).unwrap(), 0x0009_0008);
+    assert_eq!(
+        calculate_checksum(&edit.pe.data, edit.pe.optional_header_offset + 64),
+        read_u32(&edit.pe.data, edit.pe.optional_header_offset + 64).unwrap()
+    );
+}
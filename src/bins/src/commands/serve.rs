@@ -0,0 +1,112 @@
+use crate::shared::OperationWait;
+use anyhow::Result;
+use std::{sync::mpsc, thread};
+use velopack::{
+    download::CancellationToken,
+    ipc::{self, IpcCommand, IpcEvent, IpcSender, IpcServer},
+    locator::VelopackLocator,
+    progress::ProgressEvent,
+    sources::AutoSource,
+    UpdateCheck, UpdateInfo, UpdateManager,
+};
+
+pub fn serve(locator: &VelopackLocator, source: &str, host_pid: u32) -> Result<()> {
+    let name = ipc::get_ipc_name(&locator.get_install_id(), host_pid);
+    let server = IpcServer::bind(&name)?;
+    info!("Waiting for host process {} to connect on '{}'", host_pid, name);
+    let mut conn = server.accept()?;
+    info!("Host process connected.");
+
+    let manager = UpdateManager::new(AutoSource::new(source), None, Some(locator.get_paths()))?;
+    let sender = conn.sender();
+    let mut latest_update: Option<UpdateInfo> = None;
+    let mut download_thread: Option<thread::JoinHandle<()>> = None;
+    let cancel = CancellationToken::new();
+
+    // downloads run in the background, so that we can still receive a Cancel command while they are in progress
+    while let Some(command) = conn.receive::<IpcCommand>()? {
+        info!("Received IPC command: {:?}", command);
+        match command {
+            IpcCommand::CheckForUpdates => match manager.check_for_updates() {
+                Ok(UpdateCheck::UpdateAvailable(update)) => {
+                    latest_update = Some(update.clone());
+                    sender.send(&IpcEvent::UpdateCheck { Update: Some(update) })?;
+                }
+                Ok(_) => sender.send(&IpcEvent::UpdateCheck { Update: None })?,
+                Err(e) => send_error(&sender, format!("Failed to check for updates: {}", e)),
+            },
+            IpcCommand::DownloadUpdates => {
+                if download_thread.as_ref().is_some_and(|t| !t.is_finished()) {
+                    send_error(&sender, "A download is already in progress.".to_owned());
+                    continue;
+                }
+                match latest_update.clone() {
+                    Some(update) => download_thread = Some(start_download(manager.clone(), update, cancel.clone(), sender.clone())),
+                    None => send_error(&sender, "There is no update to download, check for updates first.".to_owned()),
+                }
+            }
+            IpcCommand::ApplyOnExit { Silent: silent, Restart: restart, RestartArgs: restart_args } => {
+                if let Some(t) = download_thread.take() {
+                    info!("Waiting for download to complete before queueing apply...");
+                    let _ = t.join();
+                }
+                sender.send(&IpcEvent::ApplyQueued)?;
+                drop(conn);
+                return apply_on_exit(locator, host_pid, silent, restart, restart_args);
+            }
+            IpcCommand::Cancel => {
+                // stop any in-progress download, and wait for it to clean up its partial file before exiting
+                cancel.cancel();
+                if let Some(t) = download_thread.take() {
+                    let _ = t.join();
+                }
+                sender.send(&IpcEvent::Cancelled)?;
+                return Ok(());
+            }
+        }
+    }
+
+    info!("Host process disconnected, exiting.");
+    Ok(())
+}
+
+fn start_download(manager: UpdateManager, update: UpdateInfo, cancel: CancellationToken, sender: IpcSender) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let (progress_tx, progress_rx) = mpsc::channel::<ProgressEvent>();
+        let progress_sender = sender.clone();
+        let progress_thread = thread::spawn(move || {
+            for event in progress_rx {
+                let _ = progress_sender.send(&IpcEvent::Progress { Percent: event.Percent, Details: Some(event) });
+            }
+        });
+
+        let result = manager.download_updates_cancellable(&update, &cancel, Some(progress_tx));
+        let _ = progress_thread.join();
+        match result {
+            Ok(()) => {
+                let _ = sender.send(&IpcEvent::DownloadCompleted);
+            }
+            Err(velopack::Error::Cancelled) => info!("Download was cancelled."),
+            Err(e) => send_error(&sender, format!("Failed to download updates: {}", e)),
+        }
+    })
+}
+
+fn apply_on_exit(locator: &VelopackLocator, host_pid: u32, silent: bool, restart: bool, restart_args: Vec<String>) -> Result<()> {
+    crate::dialogs::set_silent(silent);
+    let exe_args: Vec<&str> = restart_args.iter().map(|s| s.as_str()).collect();
+    let exe_args = if exe_args.is_empty() { None } else { Some(exe_args) };
+
+    info!("Waiting for host process {} to exit before applying update...", host_pid);
+    crate::shared::operation_wait(OperationWait::WaitPidIndefinitely(host_pid));
+    #[cfg(target_os = "windows")]
+    let _mutex = crate::shared::retry_io(|| crate::windows::create_global_mutex(&locator.get_install_id()))?;
+    let _lock = crate::shared::lock_root_dir(locator, std::time::Duration::ZERO)?;
+    super::apply(locator, restart, OperationWait::NoWait, None, exe_args, true, &Default::default())?;
+    Ok(())
+}
+
+fn send_error(sender: &IpcSender, message: String) {
+    error!("{}", message);
+    let _ = sender.send(&IpcEvent::Error { Message: message });
+}
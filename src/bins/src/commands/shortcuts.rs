@@ -0,0 +1,53 @@
+use velopack::locator::{ShortcutLocationFlags, VelopackLocator};
+
+/// Creates and removes the shortcuts declared in the package manifest (eg. `--shortcuts Desktop,StartMenuRoot`). Each
+/// platform maps the declared locations to its closest equivalent, so commands can manage shortcuts without knowing
+/// which platform they are running on. Failures are logged rather than returned, as shortcuts are never critical.
+pub trait Shortcuts {
+    /// Creates the shortcuts which are declared by `next_app` but were not declared by `previous_app`, and updates any
+    /// existing shortcuts so they point at `next_app`. Shortcuts which were declared by both are not re-created, as the
+    /// user may have deleted them on purpose.
+    fn create_or_update_manifest_shortcuts(&self, next_app: &VelopackLocator, previous_app: Option<&VelopackLocator>);
+
+    /// Removes every shortcut which points at the app.
+    fn remove_all_shortcuts(&self, locator: &VelopackLocator);
+}
+
+/// Returns the shortcut locations which need to be created when moving from the previous to the next locations.
+pub fn get_new_shortcut_locations(next_locations: ShortcutLocationFlags, prev_locations: ShortcutLocationFlags) -> ShortcutLocationFlags {
+    let mut new_locations = next_locations - prev_locations;
+    if new_locations.contains(ShortcutLocationFlags::START_MENU_ROOT) && new_locations.contains(ShortcutLocationFlags::START_MENU) {
+        // if both start menu locations are specified, we prefer ROOT.
+        new_locations.remove(ShortcutLocationFlags::START_MENU);
+    }
+    new_locations
+}
+
+/// Logs the shortcut locations being moved between, and returns the locations which need to be created. Portable apps never
+/// have shortcuts, so nothing is created for them.
+pub(crate) fn log_new_shortcut_locations(next_app: &VelopackLocator, previous_app: Option<&VelopackLocator>) -> ShortcutLocationFlags {
+    if next_app.get_portable_dir().is_some() {
+        info!("Skipping shortcuts for portable app.");
+        return ShortcutLocationFlags::NONE;
+    }
+    let next_locations = next_app.get_manifest_shortcut_locations();
+    let prev_locations = previous_app.map(|a| a.get_manifest_shortcut_locations()).unwrap_or(ShortcutLocationFlags::NONE);
+
+    info!("Shortcut Previous Locations: {:?} ({:?})", prev_locations, previous_app.map(|a| a.get_manifest_version_full_string()));
+    info!("Shortcut Next Locations: {:?} ({:?})", next_locations, next_app.get_manifest_version_full_string());
+    get_new_shortcut_locations(next_locations, prev_locations)
+}
+
+#[test]
+fn test_new_shortcut_locations_skip_existing_and_prefer_start_menu_root() {
+    let prev_locations = ShortcutLocationFlags::DESKTOP;
+    let next_locations = ShortcutLocationFlags::DESKTOP | ShortcutLocationFlags::STARTUP | ShortcutLocationFlags::START_MENU;
+    assert_eq!(
+        get_new_shortcut_locations(next_locations, prev_locations),
+        ShortcutLocationFlags::STARTUP | ShortcutLocationFlags::START_MENU
+    );
+
+    let next_locations = ShortcutLocationFlags::START_MENU | ShortcutLocationFlags::START_MENU_ROOT;
+    assert_eq!(get_new_shortcut_locations(next_locations, ShortcutLocationFlags::NONE), ShortcutLocationFlags::START_MENU_ROOT);
+    assert_eq!(get_new_shortcut_locations(ShortcutLocationFlags::DESKTOP, ShortcutLocationFlags::DESKTOP), ShortcutLocationFlags::NONE);
+}
@@ -0,0 +1,56 @@
+use super::{EntryLocation, Shortcuts};
+use crate::shared;
+use velopack::locator::{ShortcutLocationFlags, VelopackLocator};
+
+/// Creates .desktop entries for the AppImage. Start Menu shortcuts are added to the applications directory, Desktop
+/// shortcuts to the user's desktop, and Startup shortcuts to the XDG autostart directory.
+pub struct PlatformShortcuts;
+
+impl Shortcuts for PlatformShortcuts {
+    fn create_or_update_manifest_shortcuts(&self, next_app: &VelopackLocator, previous_app: Option<&VelopackLocator>) {
+        let appimage_path = shared::get_appimage_path(next_app);
+
+        // existing entries are refreshed when the AppImage is replaced, so only the new locations are handled here
+        if previous_app.is_some() {
+            if let Err(e) = super::update_desktop_entry(next_app, &appimage_path) {
+                warn!("Failed to update shortcuts: {}", e);
+            }
+        }
+
+        let locations = get_entry_locations(super::log_new_shortcut_locations(next_app, previous_app));
+        if locations.is_empty() {
+            return;
+        }
+        if let Err(e) = super::install_desktop_entries_from_appimage(next_app, &appimage_path, &locations) {
+            warn!("Failed to create shortcuts: {}", e);
+        }
+    }
+
+    fn remove_all_shortcuts(&self, locator: &VelopackLocator) {
+        if let Err(e) = super::uninstall_desktop_entry(locator) {
+            warn!("Failed to remove shortcuts: {}", e);
+        }
+    }
+}
+
+fn get_entry_locations(flags: ShortcutLocationFlags) -> Vec<EntryLocation> {
+    let mut locations = Vec::new();
+    if flags.intersects(ShortcutLocationFlags::START_MENU | ShortcutLocationFlags::START_MENU_ROOT) {
+        locations.push(EntryLocation::Applications);
+    }
+    if flags.contains(ShortcutLocationFlags::DESKTOP) {
+        locations.push(EntryLocation::Desktop);
+    }
+    if flags.contains(ShortcutLocationFlags::STARTUP) {
+        locations.push(EntryLocation::Autostart);
+    }
+    locations
+}
+
+#[test]
+fn test_shortcut_locations_map_to_desktop_entries() {
+    let flags = ShortcutLocationFlags::START_MENU | ShortcutLocationFlags::START_MENU_ROOT | ShortcutLocationFlags::DESKTOP;
+    assert_eq!(get_entry_locations(flags), vec![EntryLocation::Applications, EntryLocation::Desktop]);
+    assert_eq!(get_entry_locations(ShortcutLocationFlags::STARTUP), vec![EntryLocation::Autostart]);
+    assert!(get_entry_locations(ShortcutLocationFlags::NONE).is_empty());
+}
@@ -0,0 +1,179 @@
+use super::Shortcuts;
+use crate::shared;
+use anyhow::{anyhow, bail, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command as Process,
+};
+use velopack::locator::{ShortcutLocationFlags, VelopackLocator};
+
+/// Start Menu shortcuts are an alias (symbolic link) to the app bundle in ~/Applications, which is skipped if the app
+/// is already installed to an Applications folder. Desktop shortcuts are a tile in the Dock, and Startup shortcuts are
+/// a login item. The bundle is updated in place, so existing shortcuts never need to be updated.
+pub struct PlatformShortcuts;
+
+impl Shortcuts for PlatformShortcuts {
+    fn create_or_update_manifest_shortcuts(&self, next_app: &VelopackLocator, previous_app: Option<&VelopackLocator>) {
+        let root_dir = next_app.get_root_dir();
+        let new_locations = super::log_new_shortcut_locations(next_app, previous_app);
+
+        if new_locations.intersects(ShortcutLocationFlags::START_MENU | ShortcutLocationFlags::START_MENU_ROOT) {
+            if let Err(e) = create_applications_alias(&root_dir) {
+                warn!("Failed to create Applications alias: {}", e);
+            }
+        }
+        if new_locations.contains(ShortcutLocationFlags::DESKTOP) {
+            if let Err(e) = add_dock_tile(&root_dir) {
+                warn!("Failed to add Dock tile: {}", e);
+            }
+        }
+        if new_locations.contains(ShortcutLocationFlags::STARTUP) {
+            if let Err(e) = velopack::login_item::register_login_item(next_app) {
+                warn!("Failed to register login item: {}", e);
+            }
+        }
+    }
+
+    fn remove_all_shortcuts(&self, locator: &VelopackLocator) {
+        let root_dir = locator.get_root_dir();
+        if let Err(e) = remove_applications_alias(&root_dir) {
+            warn!("Failed to remove Applications alias: {}", e);
+        }
+        if let Err(e) = remove_dock_tile(&root_dir) {
+            warn!("Failed to remove Dock tile: {}", e);
+        }
+        if let Err(e) = velopack::login_item::unregister_login_item(locator) {
+            warn!("Failed to unregister login item: {}", e);
+        }
+    }
+}
+
+fn get_user_applications_dir() -> Result<PathBuf> {
+    let home = velopack::locator::get_home_dir()?;
+    Ok(home.join("Applications"))
+}
+
+fn get_alias_path(root_dir: &Path) -> Result<PathBuf> {
+    let bundle_name = root_dir.file_name().ok_or_else(|| anyhow!("Unable to get the name of the app bundle {:?}", root_dir))?;
+    Ok(get_user_applications_dir()?.join(bundle_name))
+}
+
+fn create_applications_alias(root_dir: &Path) -> Result<()> {
+    let user_applications = get_user_applications_dir()?;
+    let in_applications = |dir: &Path| shared::is_sub_path(root_dir, dir).unwrap_or(false);
+    if in_applications(Path::new("/Applications")) || in_applications(&user_applications) {
+        info!("App is already in an Applications folder, no alias is needed.");
+        return Ok(());
+    }
+
+    let alias_path = get_alias_path(root_dir)?;
+    if alias_path.symlink_metadata().is_ok() {
+        bail!("{:?} already exists.", alias_path);
+    }
+    fs::create_dir_all(&user_applications)?;
+    std::os::unix::fs::symlink(root_dir, &alias_path)?;
+    info!("Created alias {:?}", alias_path);
+    Ok(())
+}
+
+fn remove_applications_alias(root_dir: &Path) -> Result<()> {
+    let alias_path = get_alias_path(root_dir)?;
+    // only remove the alias if it is ours, the user may have another copy of the app installed there
+    if fs::read_link(&alias_path).map(|target| target == root_dir).unwrap_or(false) {
+        fs::remove_file(&alias_path)?;
+        info!("Removed alias {:?}", alias_path);
+    }
+    Ok(())
+}
+
+fn get_bundle_url(root_dir: &Path) -> String {
+    // the Dock stores tiles as percent-encoded file urls with a trailing slash
+    let mut url = String::from("file://");
+    for b in root_dir.to_string_lossy().bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => url.push(b as char),
+            _ => url.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    url.push('/');
+    url
+}
+
+fn add_dock_tile(root_dir: &Path) -> Result<()> {
+    let url = get_bundle_url(root_dir);
+    let output = Process::new("/usr/bin/defaults").args(["read", "com.apple.dock", "persistent-apps"]).output()?;
+    if String::from_utf8_lossy(&output.stdout).contains(&url) {
+        info!("App is already in the Dock.");
+        return Ok(());
+    }
+
+    let tile = format!(
+        "<dict><key>tile-data</key><dict><key>file-data</key><dict>\
+         <key>_CFURLString</key><string>{}</string><key>_CFURLStringType</key><integer>15</integer>\
+         </dict></dict></dict>",
+        url
+    );
+    run(Process::new("/usr/bin/defaults").args(["write", "com.apple.dock", "persistent-apps", "-array-add", &tile]))?;
+    restart_dock();
+    info!("Added {} to the Dock.", url);
+    Ok(())
+}
+
+fn remove_dock_tile(root_dir: &Path) -> Result<()> {
+    let url = get_bundle_url(root_dir);
+    let plist = std::env::temp_dir().join(format!("velopack_dock_{}.plist", shared::random_string(8)));
+    let plist_str = plist.to_string_lossy().to_string();
+
+    // defaults can not remove a single array element, so the Dock preferences are exported, edited and imported again
+    let action: Result<()> = (|| {
+        run(Process::new("/usr/bin/defaults").args(["export", "com.apple.dock", &plist_str]))?;
+        let mut count = 0;
+        while plist_buddy(&plist_str, &format!("Print :persistent-apps:{}", count)).is_ok() {
+            count += 1;
+        }
+
+        // elements are removed from the end, so the indexes of the elements still to be checked do not change
+        let mut removed = false;
+        for i in (0..count).rev() {
+            let tile_url = plist_buddy(&plist_str, &format!("Print :persistent-apps:{}:tile-data:file-data:_CFURLString", i));
+            if tile_url.map(|u| u == url).unwrap_or(false) {
+                plist_buddy(&plist_str, &format!("Delete :persistent-apps:{}", i))?;
+                removed = true;
+            }
+        }
+
+        if removed {
+            run(Process::new("/usr/bin/defaults").args(["import", "com.apple.dock", &plist_str]))?;
+            restart_dock();
+            info!("Removed {} from the Dock.", url);
+        }
+        Ok(())
+    })();
+    let _ = fs::remove_file(&plist);
+    action
+}
+
+fn plist_buddy(plist: &str, command: &str) -> Result<String> {
+    run(Process::new("/usr/libexec/PlistBuddy").arg("-c").arg(command).arg(plist))
+}
+
+fn restart_dock() {
+    // the Dock only reads its preferences when it starts, and is restarted by launchd straight away
+    if let Err(e) = Process::new("/usr/bin/killall").arg("Dock").output() {
+        warn!("Unable to restart the Dock ({}).", e);
+    }
+}
+
+fn run(cmd: &mut Process) -> Result<String> {
+    let output = cmd.output()?;
+    if !output.status.success() {
+        bail!("{:?} failed: {}", cmd, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+#[test]
+fn test_bundle_url_is_percent_encoded() {
+    assert_eq!(get_bundle_url(Path::new("/Applications/My App.app")), "file:///Applications/My%20App.app/");
+}
@@ -0,0 +1,15 @@
+use super::Shortcuts;
+use velopack::locator::VelopackLocator;
+
+/// Creates .lnk shortcuts in the Start Menu, Desktop and Startup folders.
+pub struct PlatformShortcuts;
+
+impl Shortcuts for PlatformShortcuts {
+    fn create_or_update_manifest_shortcuts(&self, next_app: &VelopackLocator, previous_app: Option<&VelopackLocator>) {
+        crate::windows::create_or_update_manifest_lnks(next_app, previous_app);
+    }
+
+    fn remove_all_shortcuts(&self, locator: &VelopackLocator) {
+        crate::windows::remove_all_shortcuts_for_root_dir(locator.get_root_dir());
+    }
+}
@@ -10,6 +10,7 @@ use std::{
     path::Path,
     path::PathBuf,
     process::Command as Process,
+    time::Duration,
 };
 use velopack::{bundle::Manifest, constants};
 use velopack::locator::{self, LocationContext, VelopackLocator};
@@ -105,11 +106,42 @@ pub fn start_impl(
             }
         }
     } else {
+        let locator = match locator {
+            LocatorResult::Normal(locator) => LocatorResult::Normal(apply_staged_update(locator)),
+            legacy => legacy,
+        };
         start_regular(locator, exe_name, exe_args, legacy_args)?;
         Ok(())
     }
 }
 
+fn apply_staged_update(locator: VelopackLocator) -> VelopackLocator {
+    let staged_dir = locator.get_staged_dir();
+    if !staged_dir.exists() {
+        return locator;
+    }
+
+    if locator.get_staged_manifest().is_none() {
+        info!("Removing staged update which is not newer than the current version.");
+        let _ = remove_dir_all::remove_dir_all(&staged_dir);
+        return locator;
+    }
+
+    let result = (|| -> Result<VelopackLocator> {
+        let _mutex = shared::retry_io(|| win::create_global_mutex(&locator.get_install_id()))?;
+        let _lock = shared::lock_root_dir(&locator, Duration::ZERO)?;
+        super::apply_windows_impl::apply_staged_impl(&locator, true)
+    })();
+
+    match result {
+        Ok(new_locator) => new_locator,
+        Err(e) => {
+            warn!("Failed to apply staged update ({}), starting the current version instead.", e);
+            locator
+        }
+    }
+}
+
 fn start_regular(
     locator: LocatorResult,
     exe_name: Option<&String>,
@@ -147,7 +179,7 @@ fn try_legacy_migration(root_dir: &PathBuf, manifest: &Manifest) -> Result<Velop
     // meaning we can not clean up properly.
     std::env::set_current_dir(&root_dir)?;
 
-    let _mutex = shared::retry_io(|| crate::windows::create_global_mutex(&manifest.id))?;
+    let _mutex = shared::retry_io(|| crate::windows::create_global_mutex(&manifest.get_install_id()))?;
     let path_config = locator::create_config_from_root_dir(root_dir);
     
     let package = locator::find_latest_full_package(&path_config.PackagesDir).ok_or_else(|| anyhow!("Unable to find latest full package."))?;
@@ -174,7 +206,7 @@ fn try_legacy_migration(root_dir: &PathBuf, manifest: &Manifest) -> Result<Velop
     info!("Applying latest full package...");
     let buf = Path::new(&package.0).to_path_buf();
     let locator = VelopackLocator::new(path_config, modified_manifest);
-    let new_locator = super::apply(&locator, false, OperationWait::NoWait, Some(&buf), None, false)?;
+    let new_locator = super::apply(&locator, false, OperationWait::NoWait, Some(&buf), None, false, &Default::default())?;
 
     info!("Removing old app-* folders...");
     shared::delete_app_prefixed_folders(&root_dir)?;
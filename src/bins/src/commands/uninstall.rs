@@ -1,64 +1,156 @@
 use crate::shared::{self};
-use velopack::{constants, locator::VelopackLocator};
+use velopack::{constants, instance::FileLock, locator::VelopackLocator};
 
-use crate::windows;
+use super::{PlatformShortcuts, Shortcuts};
+use crate::windows::{
+    self,
+    cleanup::{self, CleanupReport},
+};
 use anyhow::Result;
-use std::fs::File;
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use winsafe::{self as w, co, prelude::*};
 
-pub fn uninstall(locator: &VelopackLocator, delete_self: bool) -> Result<()> {
+pub fn uninstall(locator: &VelopackLocator, delete_self: bool, reason: Option<&str>) -> Result<()> {
     info!("Command: Uninstall");
     
     let root_path = locator.get_root_dir();
+    let _mutex = shared::retry_io(|| windows::create_global_mutex(&locator.get_install_id()))?;
+    let lock = shared::lock_root_dir(locator, Duration::ZERO)?;
 
-    fn _uninstall_impl(locator: &VelopackLocator) -> bool {
+    fn _uninstall_impl(locator: &VelopackLocator, lock: FileLock, reason: Option<&str>) -> CleanupReport {
         let root_path = locator.get_root_dir();
-        
+        let mut report = CleanupReport::default();
+
         // the real app could be running at the moment
         let _ = shared::force_stop_package(&root_path);
 
-        let mut finished_with_errors = false;
-
         // run uninstall hook
-        windows::run_hook(&locator, constants::HOOK_CLI_UNINSTALL, 60);
+        let reason_args: Vec<&str> = reason.into_iter().collect();
+        windows::run_hook_with_args(&locator, constants::HOOK_CLI_UNINSTALL, &reason_args, 60);
 
-        // remove all shortcuts pointing to the app
-        windows::remove_all_shortcuts_for_root_dir(&root_path);
+        // remove all shortcuts pointing to the app, and the start menu folder if nothing else is left in it
+        PlatformShortcuts.remove_all_shortcuts(locator);
+        let authors = locator.get_manifest_authors();
+        if let (false, Ok(start_menu)) = (authors.is_empty(), windows::known_path::get_start_menu()) {
+            let _ = fs::remove_dir(Path::new(&start_menu).join(authors));
+        }
+
+        // a sandboxed login item is recorded in the sandbox, so it is removed from there
+        if velopack::login_item::is_login_item_registered(locator).unwrap_or(false) {
+            report.record("login item", velopack::login_item::unregister_login_item(locator));
+        }
 
+        // the lock file is in the directory, so it must be released before the directory can be removed
+        drop(lock);
         info!("Removing directory '{}'", root_path.to_string_lossy());
         if let Err(e) = shared::retry_io(|| remove_dir_all::remove_dir_but_not_self(&root_path)) {
-            error!("Unable to remove directory, some files may be in use ({}).", e);
-            finished_with_errors = true;
+            report.failed(format!("directory '{}'", root_path.to_string_lossy()), format!("some files may be in use, {}", e));
         }
-        
-        if let Err(e) = windows::registry::remove_uninstall_entry(&locator) {
-            error!("Unable to remove uninstall registry entry ({}).", e);
-            // finished_with_errors = true;
+
+        // packages redirected outside of the app directory are not removed along with it
+        let packages_dir = locator.get_packages_dir();
+        if !packages_dir.starts_with(&root_path) && packages_dir.exists() {
+            let item = format!("packages directory '{}'", packages_dir.to_string_lossy());
+            report.record(item, shared::retry_io(|| remove_dir_all::remove_dir_all(&packages_dir)));
+        }
+        if let Some(state_dir) = locator.get_user_state_dir().filter(|d| d.exists()) {
+            let item = format!("user state directory '{}'", state_dir.to_string_lossy());
+            report.record(item, remove_dir_all::remove_dir_all(&state_dir));
+        }
+
+        if !locator.get_is_portable() {
+            if let Err(e) = windows::registry::remove_uninstall_entry(&locator) {
+                error!("Unable to remove uninstall registry entry ({}).", e);
+            }
         }
 
-        !finished_with_errors
+        // anything else the app registered which would otherwise be left pointing at files which no longer exist.
+        // these live in the real registry, which a sandboxed install never writes to, so there is nothing to clean up
+        if windows::sandbox::get_sandbox_dir().is_none() {
+            let owned_names: Vec<String> =
+                [Some(locator.get_manifest_id()), Some(locator.get_install_id()), locator.get_manifest_shortcut_amuid()]
+                    .into_iter()
+                    .flatten()
+                    .collect();
+            cleanup::remove_run_values(&root_path, &mut report);
+            cleanup::remove_class_registrations(&root_path, &owned_names, &mut report);
+            cleanup::remove_scheduled_tasks(&root_path, &mut report);
+            cleanup::remove_services(&root_path, &owned_names, &mut report);
+            cleanup::remove_pending_renames(&root_path, &mut report);
+        }
+        report
     }
 
-    // if it returns true, it was a success.
-    // if it returns false, it was completed with errors which the user should be notified of.
-    let result = _uninstall_impl(&locator);
+    let report = _uninstall_impl(&locator, lock, reason);
     let app_title = locator.get_manifest_title();
 
-    if result {
+    if report.is_success() {
         info!("Finished successfully.");
         shared::dialogs::show_info(format!("{} Uninstall", app_title).as_str(), None, "The application was successfully uninstalled.");
+        open_uninstall_survey(&locator);
     } else {
-        error!("Finished with errors.");
-        shared::dialogs::show_uninstall_complete_with_errors_dialog(&app_title, None);
+        // the report is written outside of the app directory, so it is still there after the directory is deleted
+        let summary = report.to_summary(&app_title);
+        error!("Finished with errors.\n{}", summary);
+        let report_path = std::env::temp_dir().join(format!("{}-uninstall-report.txt", locator.get_install_id()));
+        let report_path = match fs::write(&report_path, summary) {
+            Ok(()) => Some(report_path),
+            Err(e) => {
+                warn!("Unable to write uninstall report ({}).", e);
+                None
+            }
+        };
+        shared::dialogs::show_uninstall_complete_with_errors_dialog(&app_title, report_path.as_ref());
     }
 
     let dead_path = root_path.join(".dead");
     let _ = File::create(dead_path);
 
     if delete_self {
-        if let Err(e) = windows::register_intent_to_delete_self(3, &root_path) {
+        let protected_dirs: Vec<PathBuf> = [
+            windows::known_path::get_local_app_data(),
+            windows::known_path::get_roaming_app_data(),
+            windows::known_path::get_user_profile(),
+            windows::known_path::get_program_files_x64(),
+            windows::known_path::get_program_files_x86(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(PathBuf::from)
+        .collect();
+        let parent_dirs = cleanup::get_parent_dirs_to_remove(&root_path, &protected_dirs);
+        if let Err(e) = windows::register_intent_to_delete_self_and_root(&root_path, &parent_dirs) {
             warn!("Unable to schedule self delete ({}).", e);
         }
     }
 
     Ok(())
 }
+
+fn open_uninstall_survey(locator: &VelopackLocator) {
+    let url = match locator.get_manifest_uninstall_survey_url() {
+        Some(url) => url,
+        None => return,
+    };
+
+    if shared::dialogs::get_silent() {
+        info!("Not opening uninstall survey because silent mode is on.");
+        return;
+    }
+
+    // the url comes from the package manifest, so make sure we only ever hand a web page to the shell
+    let lower = url.to_ascii_lowercase();
+    if !lower.starts_with("https://") && !lower.starts_with("http://") {
+        warn!("Ignoring uninstall survey url which is not http(s): {}", url);
+        return;
+    }
+
+    info!("Opening uninstall survey: {}", url);
+    if let Err(e) = w::HWND::GetDesktopWindow().ShellExecute("open", &url, None, None, co::SW::SHOWDEFAULT) {
+        warn!("Failed to open uninstall survey ({}).", e);
+    }
+}
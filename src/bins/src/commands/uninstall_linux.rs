@@ -0,0 +1,75 @@
+use super::{PlatformShortcuts, Shortcuts};
+use crate::shared;
+use anyhow::Result;
+use std::{fs, io::ErrorKind, path::Path};
+use velopack::locator::VelopackLocator;
+
+/// Removes the AppImage, along with its shortcuts, background update timer, downloaded packages and components. AppImages
+/// installed system-wide (eg. to /opt by a .deb or .rpm package) are removed elevated with pkexec.
+pub fn uninstall(locator: &VelopackLocator) -> Result<()> {
+    let appimage_path = shared::get_appimage_path(locator);
+    // get_appimage_path falls back to the root dir, so make sure we are never about to delete something else
+    shared::verify_appimage(&appimage_path)?;
+
+    // the app could be running at the moment
+    let root_dir = locator.get_root_dir();
+    if root_dir != appimage_path {
+        let _ = shared::force_stop_package(&root_dir);
+    }
+
+    let mut finished_with_errors = false;
+    if let Err(e) = super::uninstall_update_timer(locator) {
+        error!("Unable to remove background update timer ({}).", e);
+        finished_with_errors = true;
+    }
+    PlatformShortcuts.remove_all_shortcuts(locator);
+
+    info!("Removing AppImage '{}'", appimage_path.to_string_lossy());
+    if let Err(e) = remove_appimage(&appimage_path) {
+        error!("Unable to remove AppImage ({}).", e);
+        finished_with_errors = true;
+    }
+
+    let packages_dir = locator.get_packages_dir();
+    info!("Removing directory '{}'", packages_dir.to_string_lossy());
+    if let Err(e) = remove_dir_all::remove_dir_all(&packages_dir) {
+        warn!("Unable to remove packages directory ({}).", e);
+    }
+    // this holds the optional components of the app, and anything else it stored for the current user
+    if let Some(state_dir) = locator.get_user_state_dir().filter(|d| d.exists()) {
+        info!("Removing directory '{}'", state_dir.to_string_lossy());
+        if let Err(e) = remove_dir_all::remove_dir_all(&state_dir) {
+            warn!("Unable to remove user state directory ({}).", e);
+        }
+    }
+
+    let app_title = locator.get_manifest_title();
+    if finished_with_errors {
+        error!("Finished with errors.");
+        shared::dialogs::show_error(
+            format!("{} Uninstall", app_title).as_str(),
+            None,
+            "The application was uninstalled, but some files could not be removed. See the log for details.",
+        );
+    } else {
+        info!("Finished successfully.");
+        shared::dialogs::show_info(format!("{} Uninstall", app_title).as_str(), None, "The application was successfully uninstalled.");
+    }
+    Ok(())
+}
+
+fn remove_appimage(appimage_path: &Path) -> Result<()> {
+    match fs::remove_file(appimage_path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+            error!("An error occurred ({}), will attempt to elevate permissions and try again...", e);
+            if shared::is_system_install_path(appimage_path) {
+                warn!("The AppImage is in a system directory, if it was installed by a package manager it should be removed with it.");
+            }
+            shared::run_elevated_script("rm -f \"$1\"", &[appimage_path.as_os_str()])?;
+            info!("AppImage removed (elevated).");
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,166 @@
+use crate::windows;
+use anyhow::{bail, Result};
+use std::{fs, os::windows::process::CommandExt, process::Command as Process, time::Duration};
+use velopack::{escape::escape_xml, locator::VelopackLocator};
+
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+/// Installs a per-user scheduled task which periodically runs "check" for this app. Update.exe stays in the install root
+/// across updates, so the task runs it from there, and it is removed along with every other task which runs something in
+/// the install root when the app is uninstalled. Downloads go to the packages directory, so they are applied the next
+/// time the app starts.
+pub fn install_update_task(locator: &VelopackLocator, source: &str, interval: Duration, download: bool) -> Result<()> {
+    if locator.get_is_portable() {
+        bail!("A background update task can not be installed for a portable app.");
+    }
+    if windows::sandbox::get_sandbox_dir().is_some() {
+        bail!("A background update task can not be installed in the sandbox, because it is registered with the system.");
+    }
+
+    let name = get_task_name(locator);
+    let mut args = vec!["check".to_owned(), "--source".to_owned(), source.to_owned()];
+    if download {
+        args.push("--download".to_owned());
+    }
+    let xml = create_task_xml(
+        &locator.get_manifest_title(),
+        &locator.get_update_path_as_string(),
+        &args,
+        &locator.get_root_dir_as_string(),
+        interval,
+    );
+
+    // schtasks only reads task definitions from a file, which must be UTF-16 to match the encoding it declares
+    let temp_dir = locator.get_temp_dir_rand16();
+    fs::create_dir_all(&temp_dir)?;
+    let xml_path = temp_dir.join("task.xml");
+    let mut contents = vec![0xFF, 0xFE];
+    contents.extend(xml.encode_utf16().flat_map(|c| c.to_le_bytes()));
+    fs::write(&xml_path, contents)?;
+    // /F replaces the task if it was already installed, which picks up any change to the arguments or interval
+    let result = schtasks(&["/create", "/f", "/tn", &name, "/xml", &xml_path.to_string_lossy()]);
+    let _ = remove_dir_all::remove_dir_all(&temp_dir);
+    result?;
+    info!("Scheduled task '{}' will check for updates every {} seconds.", name, interval.as_secs());
+    Ok(())
+}
+
+pub fn uninstall_update_task(locator: &VelopackLocator) -> Result<()> {
+    let name = get_task_name(locator);
+    if schtasks(&["/query", "/tn", &name]).is_err() {
+        info!("Scheduled task '{}' is not installed.", name);
+        return Ok(());
+    }
+    schtasks(&["/delete", "/tn", &name, "/f"])?;
+    info!("Removed scheduled task '{}'.", name);
+    Ok(())
+}
+
+fn get_task_name(locator: &VelopackLocator) -> String {
+    format!("{}-velopack-update", locator.get_install_id())
+}
+
+fn schtasks(args: &[&str]) -> Result<()> {
+    let output = Process::new("schtasks").args(args).creation_flags(CREATE_NO_WINDOW).output()?;
+    if !output.status.success() {
+        bail!("schtasks {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+/// Quotes an argument so that it is parsed back unchanged by CommandLineToArgvW.
+fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_owned();
+    }
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                backslashes = 0;
+            }
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                backslashes = 0;
+            }
+        }
+        if c != '\\' {
+            quoted.push(c);
+        }
+    }
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+fn create_task_xml(title: &str, update_exe: &str, args: &[String], working_dir: &str, interval: Duration) -> String {
+    // the repetition interval of a task must be between 1 minute and 31 days
+    let minutes = (interval.as_secs() / 60).clamp(1, 31 * 24 * 60);
+    let args: Vec<String> = args.iter().map(|a| quote_arg(a)).collect();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-16"?>
+<Task version="1.2" xmlns="http://schemas.microsoft.com/windows/2004/02/mit/task">
+  <RegistrationInfo>
+    <Description>Checks for updates to {}</Description>
+  </RegistrationInfo>
+  <Triggers>
+    <TimeTrigger>
+      <StartBoundary>2000-01-01T00:00:00</StartBoundary>
+      <Repetition>
+        <Interval>PT{}M</Interval>
+      </Repetition>
+      <RandomDelay>PT1M</RandomDelay>
+      <Enabled>true</Enabled>
+    </TimeTrigger>
+  </Triggers>
+  <Principals>
+    <Principal id="Author">
+      <LogonType>InteractiveToken</LogonType>
+      <RunLevel>LeastPrivilege</RunLevel>
+    </Principal>
+  </Principals>
+  <Settings>
+    <MultipleInstancesPolicy>IgnoreNew</MultipleInstancesPolicy>
+    <DisallowStartIfOnBatteries>false</DisallowStartIfOnBatteries>
+    <StopIfGoingOnBatteries>false</StopIfGoingOnBatteries>
+    <StartWhenAvailable>true</StartWhenAvailable>
+    <RunOnlyIfNetworkAvailable>true</RunOnlyIfNetworkAvailable>
+    <ExecutionTimeLimit>PT1H</ExecutionTimeLimit>
+    <Hidden>true</Hidden>
+    <Priority>7</Priority>
+  </Settings>
+  <Actions Context="Author">
+    <Exec>
+      <Command>{}</Command>
+      <Arguments>{}</Arguments>
+      <WorkingDirectory>{}</WorkingDirectory>
+    </Exec>
+  </Actions>
+</Task>
+"#,
+        escape_xml(title),
+        minutes,
+        escape_xml(update_exe),
+        escape_xml(&args.join(" ")),
+        escape_xml(working_dir)
+    )
+}
+
+#[test]
+fn test_create_task_xml_quotes_arguments() {
+    assert_eq!(quote_arg("check"), "check");
+    assert_eq!(quote_arg(r"C:\My Updates\"), r#""C:\My Updates\\""#);
+    assert_eq!(quote_arg(r#"a"b"#), r#""a\"b""#);
+    assert_eq!(quote_arg(""), r#""""#);
+
+    let args = vec!["check".to_owned(), "--source".to_owned(), "https://example.com/?a=1&b=2".to_owned()];
+    let xml = create_task_xml("My <App>", r"C:\Apps\MyApp\Update.exe", &args, r"C:\Apps\MyApp", Duration::from_secs(4 * 60 * 60));
+    assert!(xml.contains("<Description>Checks for updates to My &lt;App&gt;</Description>"));
+    assert!(xml.contains("<Interval>PT240M</Interval>"));
+    assert!(xml.contains(r"<Command>C:\Apps\MyApp\Update.exe</Command>"));
+    assert!(xml.contains("<Arguments>check --source https://example.com/?a=1&amp;b=2</Arguments>"));
+    assert!(create_task_xml("x", "x", &[], "x", Duration::from_secs(5)).contains("<Interval>PT1M</Interval>"));
+}
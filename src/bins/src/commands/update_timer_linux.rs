@@ -0,0 +1,154 @@
+use crate::shared;
+use anyhow::{bail, Result};
+use std::os::unix::fs::PermissionsExt;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command as Process,
+    time::Duration,
+};
+use velopack::locator::VelopackLocator;
+
+/// Installs a systemd user timer which periodically runs "check" for this app. The AppImage is only mounted while
+/// the app is running, so the updater and its manifest are copied to a persistent directory which the timer runs from.
+/// Downloads go to the same packages directory the app uses, so they are applied the next time the app starts.
+pub fn install_update_timer(locator: &VelopackLocator, source: &str, interval: Duration, download: bool) -> Result<()> {
+    if locator.get_portable_dir().is_some() {
+        bail!("A background update timer can not be installed for a portable app.");
+    }
+    let name = get_unit_name(locator);
+    let unit_dir = get_unit_dir()?;
+    let update_exe = copy_updater(locator, &locator.get_update_path(), &locator.get_paths().ManifestPath)?;
+
+    let mut args = vec![update_exe.to_string_lossy().to_string(), "check".to_owned(), "--source".to_owned(), source.to_owned()];
+    if download {
+        args.push("--download".to_owned());
+    }
+
+    fs::create_dir_all(&unit_dir)?;
+    fs::write(unit_dir.join(format!("{}.service", name)), create_service_unit(&locator.get_manifest_title(), &args))?;
+    fs::write(unit_dir.join(format!("{}.timer", name)), create_timer_unit(&locator.get_manifest_title(), interval))?;
+    info!("Wrote systemd units '{}' to {:?}", name, unit_dir);
+
+    // reloading picks up any change to the arguments or interval if the timer was already installed
+    systemctl(&["daemon-reload"])?;
+    systemctl(&["enable", &format!("{}.timer", name)])?;
+    systemctl(&["restart", &format!("{}.timer", name)])?;
+    info!("Timer '{}' will check for updates every {} seconds.", name, interval.as_secs());
+    Ok(())
+}
+
+pub fn uninstall_update_timer(locator: &VelopackLocator) -> Result<()> {
+    let name = get_unit_name(locator);
+    let unit_dir = get_unit_dir()?;
+    let timer_path = unit_dir.join(format!("{}.timer", name));
+    if !timer_path.exists() {
+        info!("Timer '{}' is not installed.", name);
+        return Ok(());
+    }
+
+    if let Err(e) = systemctl(&["disable", "--now", &format!("{}.timer", name)]) {
+        warn!("Unable to stop timer '{}' ({}).", name, e);
+    }
+    fs::remove_file(&timer_path)?;
+    let _ = fs::remove_file(unit_dir.join(format!("{}.service", name)));
+    let _ = systemctl(&["daemon-reload"]);
+    let _ = remove_dir_all::remove_dir_all(get_updater_dir(locator)?);
+    info!("Removed timer '{}'.", name);
+    Ok(())
+}
+
+/// Refreshes the copy of the updater used by the timer from a newly applied AppImage, if a timer is installed. Without
+/// this the timer would keep comparing releases against the version which was installed when the timer was created.
+pub fn refresh_update_timer(locator: &VelopackLocator, appimage_path: &Path) -> Result<()> {
+    if !get_unit_dir()?.join(format!("{}.timer", get_unit_name(locator))).exists() {
+        return Ok(());
+    }
+
+    let temp_dir = locator.get_temp_dir_rand16();
+    fs::create_dir_all(&temp_dir)?;
+    let result = (|| {
+        shared::extract_from_appimage(appimage_path, &temp_dir, "usr/bin/*")?;
+        let bin_dir = temp_dir.join("squashfs-root").join("usr").join("bin");
+        copy_updater(locator, &bin_dir.join("UpdateNix"), &bin_dir.join("sq.version"))?;
+        Ok(())
+    })();
+    let _ = remove_dir_all::remove_dir_all(&temp_dir);
+    result
+}
+
+fn get_unit_name(locator: &VelopackLocator) -> String {
+    format!("{}-velopack-update", locator.get_install_id())
+}
+
+fn get_unit_dir() -> Result<PathBuf> {
+    let config = match std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from).filter(|p| p.is_absolute()) {
+        Some(dir) => dir,
+        None => velopack::locator::get_home_dir()?.join(".config"),
+    };
+    Ok(config.join("systemd").join("user"))
+}
+
+fn get_updater_dir(locator: &VelopackLocator) -> Result<PathBuf> {
+    let data = match std::env::var_os("XDG_DATA_HOME").map(PathBuf::from).filter(|p| p.is_absolute()) {
+        Some(dir) => dir,
+        None => velopack::locator::get_home_dir()?.join(".local").join("share"),
+    };
+    Ok(data.join("velopack").join(locator.get_install_id()))
+}
+
+fn copy_updater(locator: &VelopackLocator, update_exe: &Path, manifest: &Path) -> Result<PathBuf> {
+    // the updater locates the app by looking for the /usr/bin/ directory it is in, so the AppImage layout is kept
+    let bin_dir = get_updater_dir(locator)?.join("usr").join("bin");
+    fs::create_dir_all(&bin_dir)?;
+    let target = bin_dir.join("UpdateNix");
+    let temp = bin_dir.join(format!(".UpdateNix.{}", shared::random_string(8)));
+    fs::copy(update_exe, &temp)?;
+    fs::set_permissions(&temp, fs::Permissions::from_mode(0o755))?;
+    fs::rename(&temp, &target)?;
+    fs::copy(manifest, bin_dir.join("sq.version"))?;
+    Ok(target)
+}
+
+fn systemctl(args: &[&str]) -> Result<()> {
+    let output = Process::new("systemctl").arg("--user").args(args).output()?;
+    if !output.status.success() {
+        bail!("systemctl --user {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+fn escape_exec_arg(arg: &str) -> String {
+    // https://www.freedesktop.org/software/systemd/man/latest/systemd.service.html#Command%20lines
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\"").replace('%', "%%").replace('$', "$$"))
+}
+
+fn create_service_unit(title: &str, args: &[String]) -> String {
+    let exec: Vec<String> = args.iter().map(|a| escape_exec_arg(a)).collect();
+    format!(
+        "[Unit]\nDescription=Check for updates to {}\n\n\
+         [Service]\nType=oneshot\nExecStart={}\nNice=10\nIOSchedulingClass=idle\n",
+        title,
+        exec.join(" ")
+    )
+}
+
+fn create_timer_unit(title: &str, interval: Duration) -> String {
+    format!(
+        "[Unit]\nDescription=Periodically check for updates to {}\n\n\
+         [Timer]\nOnBootSec=5min\nOnUnitActiveSec={}s\nRandomizedDelaySec=60\n\n\
+         [Install]\nWantedBy=timers.target\n",
+        title,
+        interval.as_secs()
+    )
+}
+
+#[test]
+fn test_create_service_unit_escapes_arguments() {
+    let args = vec!["/home/me/.local/share/velopack/MyApp/usr/bin/UpdateNix".to_owned(), "https://example.com/?a=100%&b=$x".to_owned()];
+    let service = create_service_unit("My App", &args);
+    assert!(service.contains("Description=Check for updates to My App\n"));
+    let exec = "ExecStart=\"/home/me/.local/share/velopack/MyApp/usr/bin/UpdateNix\" \"https://example.com/?a=100%%&b=$$x\"\n";
+    assert!(service.contains(exec));
+    assert!(create_timer_unit("My App", Duration::from_secs(3600)).contains("OnUnitActiveSec=3600s\n"));
+}
@@ -7,9 +7,10 @@ extern crate log;
 use anyhow::{bail, Result};
 use clap::{arg, value_parser, Command};
 use memmap2::Mmap;
-use std::fs::File;
+use std::fs::{File, OpenOptions};
+use std::os::windows::fs::OpenOptionsExt;
 use std::{env, path::PathBuf};
-use velopack_bins::*;
+use velopack_bins::{shared::external_payload::ExternalPayload, *};
 
 #[used]
 #[no_mangle]
@@ -62,6 +63,7 @@ fn main() -> Result<()> {
         .arg(arg!(-v --verbose "Print debug messages to console"))
         .arg(arg!(-l --log <FILE> "Enable file logging and set location").required(false).value_parser(value_parser!(PathBuf)))
         .arg(arg!(-t --installto <DIR> "Installation directory to install the application").required(false).value_parser(value_parser!(PathBuf)))
+        .arg(arg!(--sandbox <DIR> "Redirect the install, shortcuts and registry entries into a directory, for testing").required(false).value_parser(value_parser!(PathBuf)))
         .arg(arg!([EXE_ARGS] "Arguments to pass to the started executable. Must be preceded by '--'.").required(false).last(true).num_args(0..));
 
     if cfg!(debug_assertions) {
@@ -71,6 +73,11 @@ fn main() -> Result<()> {
 
     let res = run_inner(arg_config);
     if let Err(e) = &res {
+        if e.downcast_ref::<shared::RestartRequiredError>().is_some() {
+            // the user has already been told about this by the prerequisite installer
+            error!("{}", e);
+            std::process::exit(velopack::constants::EXIT_CODE_RESTART_REQUIRED);
+        }
         error!("An error has occurred: {}", e);
         dialogs::show_error("Setup Error", None, format!("An error has occurred: {}", e).as_str());
     }
@@ -87,9 +94,13 @@ fn run_inner(arg_config: Command) -> Result<()>
     let debug = matches.get_one::<PathBuf>("debug");
     let logfile = matches.get_one::<PathBuf>("log");
     let install_to = matches.get_one::<PathBuf>("installto");
+    let sandbox = matches.get_one::<PathBuf>("sandbox");
     let exe_args: Option<Vec<&str>> = matches.get_many::<String>("EXE_ARGS").map(|v| v.map(|f| f.as_str()).collect());
 
     dialogs::set_silent(silent);
+    if let Some(sandbox) = sandbox {
+        windows::sandbox::set_sandbox_dir(sandbox)?;
+    }
     logging::setup_logging("setup", logfile, true, verbose)?;
 
     info!("Starting Velopack Setup ({})", env!("NGBV_VERSION"));
@@ -98,6 +109,7 @@ fn run_inner(arg_config: Command) -> Result<()>
     info!("    Verbose: {}", verbose);
     info!("    Log: {:?}", logfile);
     info!("    Install To: {:?}", install_to);
+    info!("    Sandbox: {:?}", windows::sandbox::get_sandbox_dir());
     if cfg!(debug_assertions) {
         info!("    Debug: {:?}", debug);
     }
@@ -130,12 +142,15 @@ fn run_inner(arg_config: Command) -> Result<()>
     let (offset, length) = header_offset_and_length();
     info!("Bundle offset = {}, length = {}", offset, length);
 
-    // try to load the bundle from embedded zip
+    // try to load the bundle from embedded zip, or the package next to setup.exe if that is what was embedded
     if offset > 0 && length > 0 {
-        info!("Loading bundle from embedded zip...");
         let file = File::open(env::current_exe()?)?;
         let mmap = unsafe { Mmap::map(&file)? };
         let zip_range: &[u8] = &mmap[offset as usize..(offset + length) as usize];
+        if let Some(payload) = ExternalPayload::parse(zip_range) {
+            return install_external_payload(&payload?, install_to, exe_args);
+        }
+        info!("Loading bundle from embedded zip...");
         let mut bundle = velopack::bundle::load_bundle_from_memory(&zip_range)?;
         commands::install(&mut bundle, install_to, exe_args)?;
         return Ok(())
@@ -144,3 +159,31 @@ fn run_inner(arg_config: Command) -> Result<()>
     bail!("Could not find embedded zip file. Please contact the application author.");
 }
 
+/// Installs a package which is shipped next to setup.exe instead of inside it. The package is only installed if it
+/// matches the size and SHA256 which were embedded when the installer was created, and it is opened so that it can not
+/// be changed between being verified and installed.
+fn install_external_payload(payload: &ExternalPayload, install_to: Option<&PathBuf>, exe_args: Option<Vec<&str>>) -> Result<()> {
+    const FILE_SHARE_READ: u32 = 0x00000001;
+    let dir = env::current_dir()?;
+    info!("Looking for external package '{}' ({} bytes) in {:?}...", payload.file_name, payload.size, dir);
+    for path in payload.find_candidates(&dir) {
+        info!("Verifying package {:?}...", path);
+        let file = match OpenOptions::new().read(true).share_mode(FILE_SHARE_READ).open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("Unable to open {:?} ({}).", path, e);
+                continue;
+            }
+        };
+        let mmap = unsafe { Mmap::map(&file)? };
+        if let Err(e) = payload.verify(&mmap) {
+            warn!("{:?} is not the package for this installer ({}).", path, e);
+            continue;
+        }
+        info!("Loading bundle from external package...");
+        let mut bundle = velopack::bundle::load_bundle_from_memory(&mmap)?;
+        commands::install(&mut bundle, install_to, exe_args)?;
+        return Ok(());
+    }
+    bail!("The package '{}' is missing or damaged, it must be copied to the same folder as the installer.", payload.file_name);
+}
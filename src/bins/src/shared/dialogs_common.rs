@@ -1,9 +1,61 @@
 use super::dialogs::{generate_alert, generate_confirm};
 use super::dialogs_const::*;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, RwLock,
+};
 use anyhow::{Result, bail};
 
 static SILENT: AtomicBool = AtomicBool::new(false);
+static PROVIDER: RwLock<Option<Arc<dyn DialogProvider>>> = RwLock::new(None);
+
+/// Abstraction for showing alerts and confirmations to the user, so that the prompts shown by the updater (eg. asking
+/// to elevate) can be answered by a fake in tests instead of a native dialog. Prompts are never passed to the provider
+/// while the silent flag is set.
+pub trait DialogProvider: Send + Sync {
+    /// Show a message with a single button to dismiss it.
+    fn alert(&self, title: &str, header: Option<&str>, body: &str, ico: DialogIcon) -> Result<()>;
+    /// Show a question, and return the button which was pressed.
+    fn confirm(
+        &self,
+        title: &str,
+        header: Option<&str>,
+        body: &str,
+        ok_text: Option<&str>,
+        btns: DialogButton,
+        ico: DialogIcon,
+    ) -> Result<DialogResult>;
+}
+
+/// Shows the native dialogs of the current operating system.
+pub struct NativeDialogProvider;
+
+impl DialogProvider for NativeDialogProvider {
+    fn alert(&self, title: &str, header: Option<&str>, body: &str, ico: DialogIcon) -> Result<()> {
+        generate_alert(title, header, body, None, DialogButton::Ok, ico)
+    }
+
+    fn confirm(
+        &self,
+        title: &str,
+        header: Option<&str>,
+        body: &str,
+        ok_text: Option<&str>,
+        btns: DialogButton,
+        ico: DialogIcon,
+    ) -> Result<DialogResult> {
+        generate_confirm(title, header, body, ok_text, btns, ico)
+    }
+}
+
+/// Replaces the provider which dialogs are shown with, or restores the native dialogs if None.
+pub fn set_dialog_provider(provider: Option<Arc<dyn DialogProvider>>) {
+    *PROVIDER.write().unwrap_or_else(|e| e.into_inner()) = provider;
+}
+
+fn get_dialog_provider() -> Arc<dyn DialogProvider> {
+    PROVIDER.read().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_else(|| Arc::new(NativeDialogProvider))
+}
 
 pub fn set_silent(silent: bool) {
     SILENT.store(silent, Ordering::Relaxed);
@@ -15,19 +67,19 @@ pub fn get_silent() -> bool {
 
 pub fn show_error(title: &str, header: Option<&str>, body: &str) {
     if !get_silent() {
-        let _ = generate_alert(title, header, body, None, DialogButton::Ok, DialogIcon::Error).map(|_| ());
+        let _ = get_dialog_provider().alert(title, header, body, DialogIcon::Error);
     }
 }
 
 pub fn show_warn(title: &str, header: Option<&str>, body: &str) {
     if !get_silent() {
-        let _ = generate_alert(title, header, body, None, DialogButton::Ok, DialogIcon::Warning).map(|_| ());
+        let _ = get_dialog_provider().alert(title, header, body, DialogIcon::Warning);
     }
 }
 
 pub fn show_info(title: &str, header: Option<&str>, body: &str) {
     if !get_silent() {
-        let _ = generate_alert(title, header, body, None, DialogButton::Ok, DialogIcon::Information).map(|_| ());
+        let _ = get_dialog_provider().alert(title, header, body, DialogIcon::Information);
     }
 }
 
@@ -40,7 +92,10 @@ pub fn show_ok_cancel(title: &str, header: Option<&str>, body: &str, ok_text: Op
     if ok_text.is_none() {
         btns |= DialogButton::Ok;
     }
-    generate_confirm(title, header, body, ok_text, btns, DialogIcon::Warning).map(|dlg_id| dlg_id == DialogResult::Ok).unwrap_or(false)
+    get_dialog_provider()
+        .confirm(title, header, body, ok_text, btns, DialogIcon::Warning)
+        .map(|dlg_id| dlg_id == DialogResult::Ok)
+        .unwrap_or(false)
 }
 
 pub fn ask_user_to_elevate(app_title: &str, new_version: &str) -> Result<()> {
@@ -61,19 +116,72 @@ pub fn ask_user_to_elevate(app_title: &str, new_version: &str) -> Result<()> {
     }
 }
 
+/// Records the dialogs it is asked to show instead of showing them, and answers every confirmation with the same result.
+#[cfg(test)]
+#[derive(Default)]
+struct RecordingDialogProvider {
+    shown: std::sync::Mutex<Vec<(&'static str, String, Option<String>)>>,
+    answer: Option<DialogResult>,
+}
+
+#[cfg(test)]
+impl DialogProvider for RecordingDialogProvider {
+    fn alert(&self, title: &str, _header: Option<&str>, _body: &str, ico: DialogIcon) -> Result<()> {
+        self.shown.lock().unwrap().push((ico.into(), title.to_owned(), None));
+        Ok(())
+    }
+
+    fn confirm(
+        &self,
+        title: &str,
+        _header: Option<&str>,
+        _body: &str,
+        ok_text: Option<&str>,
+        _btns: DialogButton,
+        ico: DialogIcon,
+    ) -> Result<DialogResult> {
+        self.shown.lock().unwrap().push((ico.into(), title.to_owned(), ok_text.map(|t| t.to_owned())));
+        self.answer.ok_or_else(|| anyhow::anyhow!("The dialog could not be shown."))
+    }
+}
+
 #[test]
 #[ntest::timeout(2000)]
 fn test_no_dialogs_show_if_silent() {
+    // this is the only test which changes the silent flag and the provider, because they are shared by every test
+    let recorder = Arc::new(RecordingDialogProvider { answer: Some(DialogResult::Ok), ..Default::default() });
+    set_dialog_provider(Some(recorder.clone()));
     set_silent(true);
     show_error("Error", None, "This is an error.");
     show_warn("Warning", None, "This is a warning.");
     show_info("Information", None, "This is information.");
     assert!(!show_ok_cancel("Ok/Cancel", None, "This is a question.", None));
+    assert!(ask_user_to_elevate("MyApp", "2.0.0").is_err());
+    assert!(recorder.shown.lock().unwrap().is_empty());
+
+    set_silent(false);
+    show_error("Error", None, "This is an error.");
+    assert!(show_ok_cancel("Ok/Cancel", None, "This is a question.", None));
+    ask_user_to_elevate("MyApp", "2.0.0").unwrap();
+    let shown = recorder.shown.lock().unwrap().clone();
+    assert_eq!(shown[0], ("Error", "Error".to_owned(), None));
+    assert_eq!(shown[1], ("Warning", "Ok/Cancel".to_owned(), None));
+    assert_eq!(shown[2], ("Warning", "MyApp Update".to_owned(), Some("Install Update".to_owned())));
+
+    // a dialog which can not be shown, or is cancelled, is never taken as consent
+    set_dialog_provider(Some(Arc::new(RecordingDialogProvider::default())));
+    assert!(!show_ok_cancel("Ok/Cancel", None, "This is a question.", None));
+    set_dialog_provider(Some(Arc::new(RecordingDialogProvider { answer: Some(DialogResult::Cancel), ..Default::default() })));
+    assert!(ask_user_to_elevate("MyApp", "2.0.0").is_err());
+
+    set_dialog_provider(None);
+    set_silent(true);
 }
 
 #[test]
 #[ignore]
 fn test_show_all_dialogs() {
+    set_dialog_provider(None);
     set_silent(false);
     show_error("Error", None, "This is an error.");
     show_warn("Warning", None, "This is a warning.");
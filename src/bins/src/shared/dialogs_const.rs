@@ -70,14 +70,6 @@ impl DialogIcon {
             DialogIcon::Information => winsafe::co::TD_ICON::INFORMATION,
         }
     }
-    #[cfg(target_os = "macos")]
-    pub fn to_native(&self) -> native_dialog::MessageType {
-        match self {
-            DialogIcon::Warning => native_dialog::MessageType::Warning,
-            DialogIcon::Error => native_dialog::MessageType::Error,
-            DialogIcon::Information => native_dialog::MessageType::Info,
-        }
-    }
 }
 
 #[cfg(target_os = "windows")]
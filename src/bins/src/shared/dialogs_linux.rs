@@ -1,28 +1,142 @@
 use super::dialogs_const::*;
-use anyhow::{anyhow, Result};
-use dialog::{Choice, DialogBox};
+use anyhow::{bail, Result};
+use std::{io::ErrorKind, process::Command as Process};
 
-pub fn generate_alert(title: &str, header: Option<&str>, body: &str, _ok_text: Option<&str>, _btns: DialogButton, _ico: DialogIcon) -> Result<()> {
-    let mut body = body.to_string();
-    if let Some(h) = header {
-        body = format!("{}\n{}", h, body);
+/// The programs which can show native dialogs. zenity is part of GNOME and kdialog is part of KDE, and most desktops
+/// ship at least one of them, so both are tried (in order of preference) before giving up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Backend {
+    Zenity,
+    Kdialog,
+}
+
+impl Backend {
+    fn program(&self) -> &'static str {
+        match self {
+            Backend::Zenity => "zenity",
+            Backend::Kdialog => "kdialog",
+        }
     }
+}
 
-    dialog::Message::new(body).title(title).show().map_err(|e| anyhow!("Failed to open dialog ({})", e))?;
+pub fn generate_alert(title: &str, header: Option<&str>, body: &str, _ok_text: Option<&str>, _btns: DialogButton, ico: DialogIcon) -> Result<()> {
+    let text = join_header(header, body);
+    run_dialog(|backend| match backend {
+        Backend::Zenity => get_zenity_alert_args(title, &text, ico),
+        Backend::Kdialog => get_kdialog_alert_args(title, &text, ico),
+    })?;
     Ok(())
 }
 
-pub fn generate_confirm(title: &str, header: Option<&str>, body: &str, _ok_text: Option<&str>, _btns: DialogButton, _ico: DialogIcon) -> Result<DialogResult> {
-    let mut body = body.to_string();
-    if let Some(h) = header {
-        body = format!("{}\n{}", h, body);
+pub fn generate_confirm(title: &str, header: Option<&str>, body: &str, ok_text: Option<&str>, _btns: DialogButton, _ico: DialogIcon) -> Result<DialogResult> {
+    let text = join_header(header, body);
+    let ok_text = ok_text.unwrap_or("OK");
+    let accepted = run_dialog(|backend| match backend {
+        Backend::Zenity => get_zenity_confirm_args(title, &text, ok_text),
+        Backend::Kdialog => get_kdialog_confirm_args(title, &text, ok_text),
+    })?;
+    Ok(if accepted { DialogResult::Ok } else { DialogResult::Cancel })
+}
+
+fn join_header(header: Option<&str>, body: &str) -> String {
+    match header {
+        Some(h) => format!("{}\n{}", h, body),
+        None => body.to_string(),
+    }
+}
+
+/// Shows a dialog with the first backend which is installed, and returns true if it was accepted.
+fn run_dialog<F: Fn(Backend) -> Vec<String>>(get_args: F) -> Result<bool> {
+    if std::env::var_os("DISPLAY").is_none() && std::env::var_os("WAYLAND_DISPLAY").is_none() {
+        bail!("Unable to show dialog, there is no graphical session.");
+    }
+
+    let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+    for backend in get_backends(&desktop) {
+        let program = backend.program();
+        match Process::new(program).args(get_args(backend)).output() {
+            Ok(output) => {
+                // zenity exits with 1 when the dialog is cancelled or closed, and kdialog with 1 or 2 depending on the dialog
+                return match output.status.code() {
+                    Some(0) => Ok(true),
+                    Some(1) | Some(2) => Ok(false),
+                    _ => bail!("{} failed ({}): {}", program, output.status, String::from_utf8_lossy(&output.stderr).trim()),
+                };
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => debug!("{} is not installed.", program),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    bail!("Unable to show dialog, neither zenity nor kdialog is installed.");
+}
+
+fn get_backends(current_desktop: &str) -> [Backend; 2] {
+    // desktops built on Qt get kdialog first, so the dialog looks like the rest of the desktop
+    let is_qt = current_desktop.split(':').any(|d| d.eq_ignore_ascii_case("KDE") || d.eq_ignore_ascii_case("LXQt"));
+    if is_qt {
+        [Backend::Kdialog, Backend::Zenity]
+    } else {
+        [Backend::Zenity, Backend::Kdialog]
     }
+}
+
+fn escape_pango(text: &str) -> String {
+    // zenity renders --text as Pango markup
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn get_zenity_alert_args(title: &str, text: &str, ico: DialogIcon) -> Vec<String> {
+    let kind = match ico {
+        DialogIcon::Error => "--error",
+        DialogIcon::Warning => "--warning",
+        DialogIcon::Information => "--info",
+    };
+    vec![kind.to_owned(), format!("--title={}", title), format!("--text={}", escape_pango(text))]
+}
+
+fn get_zenity_confirm_args(title: &str, text: &str, ok_text: &str) -> Vec<String> {
+    vec![
+        "--question".to_owned(),
+        format!("--title={}", title),
+        format!("--text={}", escape_pango(text)),
+        format!("--ok-label={}", ok_text),
+        "--cancel-label=Cancel".to_owned(),
+    ]
+}
+
+fn get_kdialog_alert_args(title: &str, text: &str, ico: DialogIcon) -> Vec<String> {
+    let kind = match ico {
+        DialogIcon::Error => "--error",
+        DialogIcon::Warning => "--sorry",
+        DialogIcon::Information => "--msgbox",
+    };
+    vec!["--title".to_owned(), title.to_owned(), kind.to_owned(), text.to_owned()]
+}
+
+fn get_kdialog_confirm_args(title: &str, text: &str, ok_text: &str) -> Vec<String> {
+    vec![
+        "--title".to_owned(),
+        title.to_owned(),
+        "--warningcontinuecancel".to_owned(),
+        text.to_owned(),
+        "--continue-label".to_owned(),
+        ok_text.to_owned(),
+    ]
+}
 
-    let result = dialog::Question::new(body).title(title).show().map_err(|e| anyhow!("Failed to open dialog ({})", e))?;
+#[test]
+fn test_dialog_backend_args() {
+    assert_eq!(get_backends("ubuntu:GNOME"), [Backend::Zenity, Backend::Kdialog]);
+    assert_eq!(get_backends("KDE"), [Backend::Kdialog, Backend::Zenity]);
+    assert_eq!(get_backends(""), [Backend::Zenity, Backend::Kdialog]);
 
-    Ok(match result {
-        Choice::Cancel => DialogResult::Cancel,
-        Choice::No => DialogResult::Cancel,
-        Choice::Yes => DialogResult::Ok,
-    })
+    assert_eq!(
+        get_zenity_alert_args("My App", "Tom & Jerry <3", DialogIcon::Error),
+        vec!["--error", "--title=My App", "--text=Tom &amp; Jerry &lt;3"]
+    );
+    assert_eq!(
+        get_zenity_confirm_args("My App", "Update?", "Install Update"),
+        vec!["--question", "--title=My App", "--text=Update?", "--ok-label=Install Update", "--cancel-label=Cancel"]
+    );
+    assert_eq!(get_kdialog_alert_args("My App", "Tom & Jerry", DialogIcon::Warning), vec!["--title", "My App", "--sorry", "Tom & Jerry"]);
 }
@@ -1,28 +1,71 @@
 use super::dialogs_const::*;
-use anyhow::Result;
-use native_dialog::MessageDialog;
+use anyhow::{bail, Result};
+use std::process::Command as Process;
 
 pub fn generate_alert(title: &str, header: Option<&str>, body: &str, _ok_text: Option<&str>, _btns: DialogButton, ico: DialogIcon) -> Result<()> {
-    let mut body = body.to_string();
-    if let Some(h) = header {
-        body = format!("{}\n{}", h, body);
-    }
-
-    MessageDialog::new().set_type(ico.to_native()).set_title(title).set_text(&body).show_alert()?;
-
+    show_alert(header.unwrap_or(title), body, &["OK"], ico)?;
     Ok(())
 }
 
-pub fn generate_confirm(title: &str, header: Option<&str>, body: &str, _ok_text: Option<&str>, _btns: DialogButton, ico: DialogIcon) -> Result<DialogResult> {
-    let mut body = body.to_string();
-    if let Some(h) = header {
-        body = format!("{}\n{}", h, body);
+pub fn generate_confirm(title: &str, header: Option<&str>, body: &str, ok_text: Option<&str>, _btns: DialogButton, ico: DialogIcon) -> Result<DialogResult> {
+    let ok_text = ok_text.unwrap_or("OK");
+    match show_alert(header.unwrap_or(title), body, &["Cancel", ok_text], ico)? {
+        Some(clicked) if clicked == ok_text => Ok(DialogResult::Ok),
+        _ => Ok(DialogResult::Cancel),
     }
+}
 
-    let result = MessageDialog::new().set_type(ico.to_native()).set_title(title).set_text(&body).show_confirm()?;
-    if result {
-        Ok(DialogResult::Ok)
-    } else {
-        Ok(DialogResult::Cancel)
+/// Shows an NSAlert with osascript, and returns the button which was clicked, or None if it was cancelled.
+fn show_alert(message: &str, informative: &str, buttons: &[&str], ico: DialogIcon) -> Result<Option<String>> {
+    let mut cmd = Process::new("/usr/bin/osascript");
+    for line in get_alert_script(buttons.len(), ico) {
+        cmd.arg("-e").arg(line);
+    }
+    // the text is passed to the script as arguments, so it never needs to be escaped
+    let output = cmd.arg(message).arg(informative).args(buttons).output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        // clicking the cancel button raises a "User canceled" (-128) error
+        if stderr.contains("-128") {
+            return Ok(None);
+        }
+        bail!("osascript failed ({}): {}", output.status, stderr.trim());
     }
+    Ok(stdout.trim().strip_prefix("button returned:").map(|b| b.to_owned()))
+}
+
+fn get_alert_script(button_count: usize, ico: DialogIcon) -> Vec<String> {
+    let kind = match ico {
+        DialogIcon::Error => "critical",
+        DialogIcon::Warning => "warning",
+        DialogIcon::Information => "informational",
+    };
+    let buttons: Vec<String> = (0..button_count).map(|i| format!("item {} of argv", i + 3)).collect();
+    let cancel = if button_count > 1 { " cancel button 1" } else { "" };
+    vec![
+        "on run argv".to_owned(),
+        // osascript is not a foreground app, so without this the alert would open behind other windows
+        "activate".to_owned(),
+        format!(
+            "display alert (item 1 of argv) message (item 2 of argv) as {} buttons {{{}}} default button {}{}",
+            kind,
+            buttons.join(", "),
+            button_count,
+            cancel
+        ),
+        "end run".to_owned(),
+    ]
+}
+
+#[test]
+fn test_alert_script_reads_text_from_arguments() {
+    let script = get_alert_script(2, DialogIcon::Warning);
+    assert_eq!(
+        script[2],
+        "display alert (item 1 of argv) message (item 2 of argv) as warning buttons {item 3 of argv, item 4 of argv} \
+         default button 2 cancel button 1"
+    );
+    let script = get_alert_script(1, DialogIcon::Error);
+    assert_eq!(script[2], "display alert (item 1 of argv) message (item 2 of argv) as critical buttons {item 3 of argv} default button 1");
 }
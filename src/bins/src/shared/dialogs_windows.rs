@@ -7,7 +7,7 @@ use velopack::locator::{auto_locate_app_manifest, LocationContext};
 
 pub fn show_restart_required(app: &Manifest) {
     show_warn(
-        format!("{} Setup {}", app.title, app.version).as_str(),
+        format!("{} Setup {}", app.get_install_title(), app.version).as_str(),
         Some("Restart Required"),
         "A restart is required before Setup can continue. Please restart your computer and try again.",
     );
@@ -28,11 +28,11 @@ pub fn show_update_missing_dependencies_dialog(
     }
 
     show_ok_cancel(
-        format!("{} Update", app.title).as_str(),
-        Some(format!("{} would like to update from {} to {}", app.title, from, to).as_str()),
+        format!("{} Update", app.get_install_title()).as_str(),
+        Some(format!("{} would like to update from {} to {}", app.get_install_title(), from, to).as_str()),
         format!(
             "{} {to} has missing dependencies which need to be installed: {}, would you like to continue?",
-            app.title, depedency_string
+            app.get_install_title(), depedency_string
         )
             .as_str(),
         Some("Install & Update"),
@@ -44,11 +44,11 @@ pub fn show_setup_missing_dependencies_dialog(app: &Manifest, depedency_string:
         return true;
     }
 
+    let title = app.get_install_title();
     show_ok_cancel(
-        format!("{} Setup {}", app.title, app.version).as_str(),
-        Some(format!("{} has missing system dependencies.", app.title).as_str()),
-        format!("{} requires the following packages to be installed: {}, would you like to continue?", app.title, depedency_string)
-            .as_str(),
+        format!("{} Setup {}", title, app.version).as_str(),
+        Some(format!("{} has missing system dependencies.", title).as_str()),
+        format!("{} requires the following packages to be installed: {}, would you like to continue?", title, depedency_string).as_str(),
         Some("Install"),
     )
 }
@@ -138,8 +138,8 @@ pub fn show_overwrite_repair_dialog(app: &Manifest, root_path: &PathBuf, root_is
     let mut config: w::TASKDIALOGCONFIG = Default::default();
     config.set_pszMainIcon(w::IconIdTdicon::Tdicon(co::TD_ICON::WARNING));
 
-    let mut setup_name = WString::from_str(format!("{} Setup {}", app.title, app.version));
-    let mut instruction = WString::from_str(format!("{} is already installed.", app.title));
+    let mut setup_name = WString::from_str(format!("{} Setup {}", app.get_install_title(), app.version));
+    let mut instruction = WString::from_str(format!("{} is already installed.", app.get_install_title()));
     let mut content = WString::from_str(
         "This application is installed on your computer. If it is not functioning correctly, you can attempt to repair it.",
     );
@@ -150,13 +150,13 @@ pub fn show_overwrite_repair_dialog(app: &Manifest, root_path: &PathBuf, root_is
     let old_app = auto_locate_app_manifest(LocationContext::FromSpecifiedRootDir(root_path.to_owned()));
     if let Ok(old) = old_app {
         let old_version = old.get_manifest_version();
-        if old_version < app.version {
-            instruction = WString::from_str(format!("An older version of {} is installed.", app.title));
+        if velopack::version::is_newer(&app.version, &old_version) {
+            instruction = WString::from_str(format!("An older version of {} is installed.", app.get_install_title()));
             content = WString::from_str(format!("Would you like to update from {} to {}?", old_version, app.version));
             btn_yes_txt = WString::from_str(format!("Update\nTo version {}", app.version));
             config.set_pszMainIcon(w::IconIdTdicon::Tdicon(co::TD_ICON::INFORMATION));
-        } else if old_version > app.version {
-            instruction = WString::from_str(format!("A newer version of {} is installed.", app.title));
+        } else if velopack::version::is_newer(&old_version, &app.version) {
+            instruction = WString::from_str(format!("A newer version of {} is installed.", app.get_install_title()));
             content = WString::from_str(format!(
                 "You already have {} installed. Would you like to downgrade this application to an older version?",
                 old_version
@@ -166,7 +166,7 @@ pub fn show_overwrite_repair_dialog(app: &Manifest, root_path: &PathBuf, root_is
     }
 
     let mut footer = if root_is_default {
-        WString::from_str(format!("The install directory is '<A HREF=\"na\">%LocalAppData%\\{}</A>'", app.id))
+        WString::from_str(format!("The install directory is '<A HREF=\"na\">%LocalAppData%\\{}</A>'", app.get_install_id()))
     } else {
         WString::from_str(format!("The install directory is '<A HREF=\"na\">{}</A>'", root_path.display()))
     };
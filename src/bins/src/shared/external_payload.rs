@@ -0,0 +1,120 @@
+use anyhow::{anyhow, bail, Result};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use velopack::VelopackAsset;
+
+/// Marks the data appended to setup.exe as the description of an external package, rather than the package itself
+/// (which is a zip, so always starts with "PK").
+const EXTERNAL_PAYLOAD_MAGIC: &str = "velopack-external-payload";
+
+/// Describes a package which is shipped next to setup.exe (eg. on a USB stick or network share) instead of inside it, so
+/// that large apps do not need a multi-GB installer, which some anti-virus engines refuse to scan. The description is
+/// appended to setup.exe in place of the package, so it is covered by the signature of setup.exe, and the package is
+/// only installed if its size and SHA256 match the description.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalPayload {
+    /// The file name of the package, which is looked for first.
+    pub file_name: String,
+    /// The size of the package in bytes.
+    pub size: u64,
+    /// The SHA256 of the package, as hex.
+    pub sha256: String,
+}
+
+impl ExternalPayload {
+    /// Creates the description of a package which was written by vpk pack.
+    pub fn from_asset(asset: &VelopackAsset) -> ExternalPayload {
+        ExternalPayload { file_name: asset.FileName.clone(), size: asset.Size, sha256: asset.SHA256.clone() }
+    }
+
+    /// Parses the data appended to setup.exe, returning None if it is not the description of an external package.
+    pub fn parse(data: &[u8]) -> Option<Result<ExternalPayload>> {
+        let text = data.strip_prefix(EXTERNAL_PAYLOAD_MAGIC.as_bytes())?;
+        let text = match std::str::from_utf8(text) {
+            Ok(text) => text,
+            Err(_) => return Some(Err(anyhow!("The external package description is not valid."))),
+        };
+        let get = |key: &str| text.lines().find_map(|l| l.strip_prefix(key)?.strip_prefix('=')).map(str::trim).filter(|v| !v.is_empty());
+        Some((|| {
+            let file_name = get("file").ok_or_else(|| anyhow!("The external package description has no file name."))?;
+            // the file name is only ever looked for next to setup.exe
+            if file_name.contains(['/', '\\', ':']) || file_name.starts_with('.') {
+                bail!("The external package file name '{}' is not valid.", file_name);
+            }
+            Ok(ExternalPayload {
+                file_name: file_name.to_owned(),
+                size: get("size").and_then(|s| s.parse().ok()).ok_or_else(|| anyhow!("The external package description has no size."))?,
+                sha256: get("sha256").ok_or_else(|| anyhow!("The external package description has no SHA256."))?.to_owned(),
+            })
+        })())
+    }
+
+    /// Writes the description, in the format read by [ExternalPayload::parse].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        format!("{}\nfile={}\nsize={}\nsha256={}\n", EXTERNAL_PAYLOAD_MAGIC, self.file_name, self.size, self.sha256).into_bytes()
+    }
+
+    /// Returns the files in a directory which may be the package, in the order they should be tried: the file with the
+    /// expected name, and then any other .nupkg of the expected size, in case the package was renamed when it was copied.
+    pub fn find_candidates(&self, dir: &Path) -> Vec<PathBuf> {
+        let mut candidates = Vec::new();
+        let expected = dir.join(&self.file_name);
+        if expected.is_file() {
+            candidates.push(expected.clone());
+        }
+        if let Ok(entries) = fs::read_dir(dir) {
+            let mut others: Vec<PathBuf> = entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| *p != expected && p.extension().is_some_and(|e| e.eq_ignore_ascii_case("nupkg")))
+                .filter(|p| p.metadata().map(|m| m.is_file() && m.len() == self.size).unwrap_or(false))
+                .collect();
+            others.sort();
+            candidates.extend(others);
+        }
+        candidates
+    }
+
+    /// Returns an error if the contents of a file do not match the size and SHA256 of the package.
+    pub fn verify(&self, data: &[u8]) -> Result<()> {
+        if data.len() as u64 != self.size {
+            bail!("The package is {} bytes, but {} bytes were expected.", data.len(), self.size);
+        }
+        let sha256 = format!("{:x}", Sha256::digest(data));
+        if !sha256.eq_ignore_ascii_case(&self.sha256) {
+            bail!("The package SHA256 is {}, but {} was expected.", sha256, self.sha256);
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_external_payload_is_described_found_and_verified() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    let package = b"PK-package-contents".to_vec();
+    let payload = ExternalPayload {
+        file_name: "MyApp-1.0.0-full.nupkg".to_owned(),
+        size: package.len() as u64,
+        sha256: format!("{:X}", Sha256::digest(&package)),
+    };
+
+    assert_eq!(ExternalPayload::parse(&payload.to_bytes()).unwrap().unwrap(), payload);
+    assert!(ExternalPayload::parse(&package).is_none());
+    let escaping = String::from_utf8(payload.to_bytes()).unwrap().replace("file=", "file=..\\");
+    assert!(ExternalPayload::parse(escaping.as_bytes()).unwrap().is_err());
+
+    // a renamed copy of the package is found by its size, after the expected file name
+    fs::write(dir.join("MyApp-full.nupkg"), &package).unwrap();
+    fs::write(dir.join("Other-full.nupkg"), b"other").unwrap();
+    assert_eq!(payload.find_candidates(&dir), vec![dir.join("MyApp-full.nupkg")]);
+    fs::write(dir.join(&payload.file_name), &package).unwrap();
+    assert_eq!(payload.find_candidates(&dir), vec![dir.join(&payload.file_name), dir.join("MyApp-full.nupkg")]);
+
+    payload.verify(&package).unwrap();
+    assert!(payload.verify(b"PK-package-contentz").is_err());
+    assert!(payload.verify(b"PK").is_err());
+}
@@ -3,6 +3,7 @@ pub enum RuntimeArch {
     X86,
     X64,
     Arm64,
+    Arm,
 }
 
 impl RuntimeArch {
@@ -12,6 +13,7 @@ impl RuntimeArch {
             0x014c => Some(RuntimeArch::X86),
             0x8664 => Some(RuntimeArch::X64),
             0xAA64 => Some(RuntimeArch::Arm64),
+            0x01c4 => Some(RuntimeArch::Arm),
             _ => None,
         }
     }
@@ -24,6 +26,10 @@ impl RuntimeArch {
             "x86_64" => Some(RuntimeArch::X64),
             "arm64" => Some(RuntimeArch::Arm64),
             "aarch64" => Some(RuntimeArch::Arm64),
+            "arm" => Some(RuntimeArch::Arm),
+            "armv7" => Some(RuntimeArch::Arm),
+            "armv7l" => Some(RuntimeArch::Arm),
+            "armhf" => Some(RuntimeArch::Arm),
             _ => None,
         }
     }
@@ -119,4 +125,7 @@ fn test_cpu_arch_from_str() {
     assert_eq!(RuntimeArch::from_str("X86"), Some(RuntimeArch::X86));
     assert_eq!(RuntimeArch::from_str("X64"), Some(RuntimeArch::X64));
     assert_eq!(RuntimeArch::from_str("ARM64"), Some(RuntimeArch::Arm64));
+    assert_eq!(RuntimeArch::from_str("aarch64"), Some(RuntimeArch::Arm64));
+    assert_eq!(RuntimeArch::from_str("armv7l"), Some(RuntimeArch::Arm));
+    assert_eq!(RuntimeArch::from_str("armhf"), Some(RuntimeArch::Arm));
 }
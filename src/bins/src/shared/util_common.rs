@@ -1,13 +1,34 @@
 use anyhow::{anyhow, Result};
 use rand::distributions::{Alphanumeric, DistString};
 use regex::Regex;
-use std::{path::Path, thread, time::Duration};
+use std::{fs, path::Path, thread, time::Duration};
+use velopack::{instance::FileLock, locator::VelopackLocator};
+
+#[cfg(not(target_os = "windows"))]
+use std::{
+    collections::HashMap,
+    path::{Component, PathBuf},
+    time::Instant,
+};
+
+/// Returned when a prerequisite was installed successfully, but the system must be restarted before the app can run.
+#[derive(Debug)]
+pub struct RestartRequiredError;
+
+impl std::fmt::Display for RestartRequiredError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "A system restart is required to complete the installation of prerequisites.")
+    }
+}
+
+impl std::error::Error for RestartRequiredError {}
 
 #[derive(Debug, Clone, Copy)]
 pub enum OperationWait {
     NoWait,
     WaitParent,
     WaitPid(u32),
+    WaitPidIndefinitely(u32),
 }
 
 pub fn operation_wait(wait: OperationWait) {
@@ -15,6 +36,10 @@ pub fn operation_wait(wait: OperationWait) {
         if let Err(e) = super::wait_for_pid_to_exit(pid, 60_000) {
             warn!("Failed to wait for process ({}) to exit ({}). Continuing...", pid, e);
         }
+    } else if let OperationWait::WaitPidIndefinitely(pid) = wait {
+        if let Err(e) = super::wait_for_pid_to_exit(pid, u32::MAX) {
+            warn!("Failed to wait for process ({}) to exit ({}). Continuing...", pid, e);
+        }
     } else if let OperationWait::WaitParent = wait {
         if let Err(e) = super::wait_for_parent_to_exit(60_000) {
             warn!("Failed to wait for parent process to exit ({}). Continuing...", e);
@@ -24,6 +49,28 @@ pub fn operation_wait(wait: OperationWait) {
     }
 }
 
+/// Named mutexes only exist on Windows and are only visible on the local machine, so every install, update and uninstall
+/// also holds a lock file, which protects installs on a network share and apps on every other platform.
+pub fn lock_root_dir(locator: &VelopackLocator, timeout: Duration) -> Result<FileLock> {
+    lock_file(&locator.get_lock_file_path(), timeout)
+}
+
+/// Locks the specified file, waiting up to `timeout` for another installer or updater to release it.
+pub fn lock_file(lock_path: &Path, timeout: Duration) -> Result<FileLock> {
+    info!("Attempting to lock root directory: '{}'", lock_path.to_string_lossy());
+    if let Some(parent) = lock_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match FileLock::acquire_with_timeout(lock_path, timeout) {
+        Ok(Some(lock)) => Ok(lock),
+        Ok(None) => match FileLock::get_owner(lock_path) {
+            Some(owner) => Err(anyhow!("Another installer or updater for this application is running ({}), quit that process and try again.", owner)),
+            None => Err(anyhow!("Another installer or updater for this application is running, quit that process and try again.")),
+        },
+        Err(e) => Err(anyhow!("Unable to lock root directory. {}", e)),
+    }
+}
+
 pub fn retry_io<F, T, E>(op: F) -> Result<T, E>
 where
     F: Fn() -> Result<T, E>,
@@ -85,6 +132,183 @@ pub fn is_dir_empty<P: AsRef<Path>>(path: P) -> bool {
     return is_dead || is_empty;
 }
 
+/// Stops every process (other than this one) whose executable is inside the directory, eg. the app bundle or the mount
+/// point of the running AppImage. Processes are asked to exit with SIGTERM first, and are only killed if they are still
+/// running a few seconds later.
+#[cfg(not(target_os = "windows"))]
+pub fn force_stop_package<P: AsRef<Path>>(root_dir: P) -> Result<()> {
+    let root_dir = root_dir.as_ref();
+    retry_io(|| _force_stop_package(root_dir))?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn _force_stop_package(dir: &Path) -> Result<()> {
+    if dir.parent().is_none() {
+        anyhow::bail!("Refusing to stop every process running from {:?}.", dir);
+    }
+    info!("Checking for running processes in: {}", dir.display());
+    let processes = get_processes_running_in_directory(dir)?;
+    let my_pid = std::process::id();
+    let mut stopping = Vec::new();
+    for (pid, exe) in processes {
+        if pid == my_pid {
+            warn!("Skipping killing self: {} ({})", exe.display(), pid);
+            continue;
+        }
+        warn!("Stopping process: {} ({})", exe.display(), pid);
+        if send_signal(pid, libc::SIGTERM)? {
+            stopping.push((pid, exe));
+        }
+    }
+
+    // give the processes a chance to exit cleanly (eg. to save their state), and kill any which are still running. a
+    // process is only considered to still be running if its pid has not been reused by another executable.
+    let is_running = |pid: u32, exe: &PathBuf| super::get_pid_path(pid).as_ref() == Some(exe);
+    let deadline = Instant::now() + Duration::from_secs(3);
+    while Instant::now() < deadline && stopping.iter().any(|(pid, exe)| is_running(*pid, exe)) {
+        thread::sleep(Duration::from_millis(100));
+    }
+    for (pid, exe) in stopping {
+        if is_running(pid, &exe) {
+            warn!("Killing process which did not exit: {} ({})", exe.display(), pid);
+            send_signal(pid, libc::SIGKILL)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn get_processes_running_in_directory(dir: &Path) -> Result<HashMap<u32, PathBuf>> {
+    let mut oup = HashMap::new();
+    for pid in super::get_pids()? {
+        // processes owned by other users (or which have just exited) can not be read, and are skipped
+        if let Some(exe) = super::get_pid_path(pid) {
+            if let Ok(true) = is_sub_path(&exe, dir) {
+                oup.insert(pid, exe);
+            }
+        }
+    }
+    Ok(oup)
+}
+
+/// Sends a signal to a process, returning false if it has already exited.
+#[cfg(not(target_os = "windows"))]
+fn send_signal(pid: u32, signal: libc::c_int) -> Result<bool> {
+    // SAFETY: kill has no memory safety requirements
+    let ret = unsafe { libc::kill(pid as libc::pid_t, signal) };
+    if ret != 0 {
+        let err = std::io::Error::last_os_error();
+        // the process may have exited on its own while we were looking for it
+        if err.raw_os_error() == Some(libc::ESRCH) {
+            return Ok(false);
+        }
+        return Err(err.into());
+    }
+    Ok(true)
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_force_stop_package_kills_processes_in_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let exe = dir.path().join("sleep");
+    std::fs::copy("/bin/sleep", &exe).unwrap();
+    let mut child = std::process::Command::new(&exe).arg("30").spawn().unwrap();
+    thread::sleep(Duration::from_millis(200));
+
+    let processes = get_processes_running_in_directory(dir.path()).unwrap();
+    assert_eq!(processes.get(&child.id()), Some(&exe.canonicalize().unwrap()));
+    force_stop_package(dir.path()).unwrap();
+    let status = child.wait().unwrap();
+    assert!(!status.success());
+    // the process exited on SIGTERM, so it was never killed
+    use std::os::unix::process::ExitStatusExt;
+    assert_eq!(status.signal(), Some(libc::SIGTERM));
+}
+
+/// Returns true if `path` is `parent` or is inside it. Paths are compared by component after removing `.` and `..` and
+/// resolving symlinks in the parts which exist, and are only compared case-insensitively if the file system containing
+/// `parent` is case-insensitive (eg. most macOS volumes). Relative paths are never considered to be sub paths.
+#[cfg(not(target_os = "windows"))]
+pub fn is_sub_path<P1: AsRef<Path>, P2: AsRef<Path>>(path: P1, parent: P2) -> Result<bool> {
+    let path = path.as_ref();
+    let parent = parent.as_ref();
+    if !path.is_absolute() || !parent.is_absolute() {
+        return Ok(false);
+    }
+
+    let path = normalize_path(path);
+    let parent = normalize_path(parent);
+    Ok(path_starts_with(&path, &parent, super::is_case_insensitive_fs(&parent)))
+}
+
+#[cfg(not(target_os = "windows"))]
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut lexical = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                lexical.pop();
+            }
+            c => lexical.push(c),
+        }
+    }
+
+    // canonicalize the deepest ancestor which exists (eg. /tmp is really /private/tmp on macOS) and re-append the rest
+    let mut existing = lexical.as_path();
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            return rest.iter().rev().fold(canonical, |p, name| p.join(name));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(p), Some(name)) => {
+                rest.push(name);
+                existing = p;
+            }
+            _ => return lexical.clone(),
+        }
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn path_starts_with(path: &Path, parent: &Path, case_insensitive: bool) -> bool {
+    if case_insensitive {
+        let lower = |p: &Path| PathBuf::from(p.to_string_lossy().to_lowercase());
+        lower(path).starts_with(lower(parent))
+    } else {
+        path.starts_with(parent)
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+#[test]
+fn test_path_starts_with_respects_case_sensitivity() {
+    let path = Path::new("/Users/Me/Apps/MyApp.app/Contents");
+    assert!(path_starts_with(path, Path::new("/Users/Me/Apps"), false));
+    assert!(!path_starts_with(path, Path::new("/users/me/apps"), false));
+    assert!(path_starts_with(path, Path::new("/users/me/apps"), true));
+    assert!(!path_starts_with(path, Path::new("/Users/Me/App"), true));
+    assert!(!path_starts_with(Path::new("/AppData/JamLogicDev"), Path::new("/appdata/jamlogic"), true));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_is_sub_path_is_case_sensitive_on_linux() {
+    let tmp = tempfile::tempdir().unwrap();
+    let parent = tmp.path().join("Parent");
+    std::fs::create_dir(&parent).unwrap();
+
+    assert!(is_sub_path(parent.join("child/file.txt"), &parent).unwrap());
+    assert!(is_sub_path(parent.join("../Parent/./child"), &parent).unwrap());
+    assert!(is_sub_path(&parent, &parent).unwrap());
+    assert!(!is_sub_path(tmp.path().join("parent/child"), &parent).unwrap());
+    assert!(!is_sub_path(tmp.path().join("ParentDev"), &parent).unwrap());
+    assert!(!is_sub_path(Path::new("Parent/child"), Path::new("Parent")).unwrap());
+}
+
 lazy_static! {
     static ref REGEX_VERSION: Regex = Regex::new(r"^(?P<major>\d+)(\.(?P<minor>\d+))?(\.(?P<build>\d+))?(\.(?P<revision>\d+))?$").unwrap();
 }
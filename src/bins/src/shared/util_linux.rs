@@ -1,5 +1,13 @@
 use anyhow::{anyhow, bail, Result};
-use std::{process::Command as Process, time::Duration};
+use std::{
+    ffi::{CString, OsStr},
+    fs::File,
+    io::{ErrorKind, Read},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    process::Command as Process,
+    time::Duration,
+};
 use velopack::locator::VelopackLocator;
 
 pub fn wait_for_pid_to_exit(pid: u32, ms_to_wait: u32) -> Result<()> {
@@ -23,8 +31,163 @@ pub fn wait_for_parent_to_exit(ms_to_wait: u32) -> Result<()> {
     Ok(())
 }
 
+/// Returns the path of the .AppImage file this app was started from. While the app is running, the locator root is
+/// the temporary mount point of the AppImage, so the file itself is taken from the APPIMAGE variable which the AppImage
+/// runtime sets for every process it starts.
+pub fn get_appimage_path(locator: &VelopackLocator) -> PathBuf {
+    if let Some(path) = std::env::var_os("APPIMAGE").map(PathBuf::from) {
+        if path.is_file() {
+            return path;
+        }
+        warn!("APPIMAGE is set to '{}', but it is not a file.", path.to_string_lossy());
+    }
+    locator.get_root_dir()
+}
+
+/// Returns an error if the file is not a type 2 AppImage, which is an ELF executable with "AI\x02" in the padding
+/// of its ELF identification bytes.
+pub fn verify_appimage<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+    let mut header = [0u8; 11];
+    File::open(path)?
+        .read_exact(&mut header)
+        .map_err(|e| anyhow!("Unable to read AppImage header of '{}' ({}).", path.to_string_lossy(), e))?;
+    verify_appimage_header(&header).map_err(|e| anyhow!("'{}' is not a valid AppImage ({}).", path.to_string_lossy(), e))
+}
+
+fn verify_appimage_header(header: &[u8]) -> Result<()> {
+    if header.len() < 11 || &header[0..4] != b"\x7fELF" {
+        bail!("missing ELF header");
+    }
+    if &header[8..11] != b"AI\x02" {
+        bail!("missing type 2 AppImage magic bytes");
+    }
+    Ok(())
+}
+
+/// Extracts the files matching a glob pattern from an AppImage which is not mounted, using the AppImage runtime. The
+/// files are written to a "squashfs-root" directory inside the target directory.
+pub fn extract_from_appimage(appimage_path: &Path, target_dir: &Path, pattern: &str) -> Result<()> {
+    let output = Process::new(appimage_path).arg("--appimage-extract").arg(pattern).current_dir(target_dir).output()?;
+    if !output.status.success() {
+        bail!("Failed to extract '{}' from AppImage: {}", pattern, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+/// Returns true if the path is in a system-wide location (/opt or /usr/local), which is usually only writable by root.
+pub fn is_system_install_path(path: &Path) -> bool {
+    path.starts_with("/opt") || path.starts_with("/usr/local")
+}
+
+/// Returns the reason this is an immutable distribution (eg. Fedora Silverblue or NixOS), where the system directories are
+/// read-only and only changed by the system's own package manager, or None if it is not.
+pub fn get_immutable_system_reason() -> Option<String> {
+    get_immutable_system_reason_in(Path::new("/"))
+}
+
+fn get_immutable_system_reason_in(root: &Path) -> Option<String> {
+    if root.join("run").join("ostree-booted").exists() {
+        return Some("the system is managed by ostree".to_owned());
+    }
+    if root.join("etc").join("NIXOS").exists() {
+        return Some("the system is NixOS".to_owned());
+    }
+    let usr = root.join("usr");
+    if usr.is_dir() && is_read_only_fs(&usr) {
+        return Some(format!("{} is mounted read-only", usr.to_string_lossy()));
+    }
+    None
+}
+
+/// Returns true if the file system containing the path is mounted read-only, so writing to it fails with EROFS even
+/// when running as root.
+pub fn is_read_only_fs(path: &Path) -> bool {
+    // statvfs needs a path which exists, so check the nearest ancestor which does
+    let path = match path.ancestors().find(|p| p.exists()) {
+        Some(p) => p,
+        None => return false,
+    };
+    let c_path = match CString::new(path.as_os_str().as_bytes()) {
+        Ok(p) => p,
+        Err(_) => return false,
+    };
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path is a valid nul-terminated string, and stat is a valid buffer for the result
+    let ret = unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) };
+    ret == 0 && (stat.f_flag & libc::ST_RDONLY) != 0
+}
+
+/// Returns the per-user directory that AppImages are installed to when their own location can not be written to.
+pub fn get_user_bin_dir() -> Result<PathBuf> {
+    Ok(velopack::locator::get_home_dir()?.join(".local").join("bin"))
+}
+
+/// Runs a shell script as root with pkexec, so the user is asked to authenticate by the polkit agent of their desktop.
+/// The arguments are passed to the script as positional parameters ($1, $2, ...) so that they never need quoting.
+pub fn run_elevated_script(script: &str, args: &[&OsStr]) -> Result<()> {
+    info!("Attempting to elevate with pkexec: {}", script);
+    let output = match Process::new("pkexec").arg("/bin/sh").arg("-c").arg(script).arg("sh").args(args).output() {
+        Ok(output) => output,
+        Err(e) if e.kind() == ErrorKind::NotFound => bail!("Unable to elevate, pkexec was not found (is polkit installed?)."),
+        Err(e) => return Err(e.into()),
+    };
+
+    // https://www.freedesktop.org/software/polkit/docs/latest/pkexec.1.html#pkexec-return-value
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    match output.status.code() {
+        Some(0) => Ok(()),
+        Some(126) => bail!("Elevation was cancelled, the authentication dialog was dismissed."),
+        Some(127) => bail!("Elevation was not authorized: {}", stderr.trim()),
+        _ => bail!("Elevated script failed ({}): {}", output.status, stderr.trim()),
+    }
+}
+
+/// Returns true if the file system containing the path compares names case-insensitively. Linux file systems are
+/// case-sensitive, except for FAT and exFAT volumes (eg. USB drives) which are mounted case-insensitive.
+pub fn is_case_insensitive_fs(path: &Path) -> bool {
+    const MSDOS_SUPER_MAGIC: i64 = 0x4d44;
+    const EXFAT_SUPER_MAGIC: i64 = 0x2011bab0;
+    match statfs_type(path) {
+        Some(fs_type) => fs_type == MSDOS_SUPER_MAGIC || fs_type == EXFAT_SUPER_MAGIC,
+        None => false,
+    }
+}
+
+fn statfs_type(path: &Path) -> Option<i64> {
+    // statfs needs a path which exists, so check the nearest ancestor which does
+    let path = path.ancestors().find(|p| p.exists())?;
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path is a valid nul-terminated string, and stat is a valid buffer for the result
+    let ret = unsafe { libc::statfs(c_path.as_ptr(), &mut stat) };
+    if ret != 0 {
+        return None;
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Some(stat.f_type as i64)
+}
+
+/// Returns the id of every running process.
+pub(super) fn get_pids() -> Result<Vec<u32>> {
+    Ok(std::fs::read_dir("/proc")?.flatten().filter_map(|e| e.file_name().to_str().and_then(|n| n.parse::<u32>().ok())).collect())
+}
+
+/// Returns the executable of a process, or None if it can not be read (eg. it is owned by another user, or has exited).
+pub(super) fn get_pid_path(pid: u32) -> Option<PathBuf> {
+    std::fs::read_link(format!("/proc/{}/exe", pid)).ok().map(strip_deleted_suffix)
+}
+
+fn strip_deleted_suffix(exe: PathBuf) -> PathBuf {
+    // the kernel appends " (deleted)" to the link when the executable has been replaced or removed since it was started
+    match exe.to_str().and_then(|e| e.strip_suffix(" (deleted)")) {
+        Some(stripped) => PathBuf::from(stripped),
+        None => exe,
+    }
+}
+
 pub fn start_package(locator: &VelopackLocator, exe_args: Option<Vec<&str>>, set_env: Option<&str>) -> Result<()> {
-    let root_dir = locator.get_root_dir();
+    let root_dir = get_appimage_path(locator);
     let mut cmd = Process::new(root_dir);
     if let Some(args) = exe_args {
         cmd.args(args);
@@ -34,4 +197,44 @@ pub fn start_package(locator: &VelopackLocator, exe_args: Option<Vec<&str>>, set
     }
     cmd.spawn().map_err(|z| anyhow!("Failed to start_package ({}).", z))?;
     Ok(())
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_is_system_install_path() {
+    assert!(is_system_install_path(Path::new("/opt/MyApp/MyApp.AppImage")));
+    assert!(is_system_install_path(Path::new("/usr/local/bin/MyApp.AppImage")));
+    assert!(!is_system_install_path(Path::new("/optional/MyApp.AppImage")));
+    assert!(!is_system_install_path(Path::new("/home/me/Applications/MyApp.AppImage")));
+}
+
+#[test]
+fn test_verify_appimage_header() {
+    let mut header = b"\x7fELF\x02\x01\x01\x00AI\x02\x00\x00\x00\x00\x00".to_vec();
+    assert!(verify_appimage_header(&header).is_ok());
+    header[10] = 0x01;
+    assert!(verify_appimage_header(&header).is_err());
+    assert!(verify_appimage_header(b"#!/bin/sh\nexit 0\n").is_err());
+    assert!(verify_appimage_header(b"\x7fELF").is_err());
+}
+
+#[test]
+fn test_get_immutable_system_reason() {
+    let root = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(root.path().join("usr")).unwrap();
+    assert_eq!(get_immutable_system_reason_in(root.path()), None);
+
+    std::fs::create_dir_all(root.path().join("etc")).unwrap();
+    std::fs::write(root.path().join("etc").join("NIXOS"), "").unwrap();
+    assert_eq!(get_immutable_system_reason_in(root.path()), Some("the system is NixOS".to_owned()));
+
+    std::fs::create_dir_all(root.path().join("run")).unwrap();
+    std::fs::write(root.path().join("run").join("ostree-booted"), "").unwrap();
+    assert_eq!(get_immutable_system_reason_in(root.path()), Some("the system is managed by ostree".to_owned()));
+    assert!(!is_read_only_fs(root.path()));
+}
+
+#[test]
+fn test_strip_deleted_suffix() {
+    assert_eq!(strip_deleted_suffix(PathBuf::from("/tmp/a b (deleted)")), PathBuf::from("/tmp/a b"));
+    assert_eq!(strip_deleted_suffix(PathBuf::from("/tmp/a b")), PathBuf::from("/tmp/a b"));
+}
@@ -1,5 +1,11 @@
 use anyhow::{anyhow, bail, Result};
-use std::{path::Path, process::Command as Process, time::Duration};
+use std::{
+    ffi::CString,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    process::Command as Process,
+    time::Duration,
+};
 use velopack::locator::VelopackLocator;
 
 pub fn wait_for_pid_to_exit(pid: u32, ms_to_wait: u32) -> Result<()> {
@@ -23,11 +29,36 @@ pub fn wait_for_parent_to_exit(ms_to_wait: u32) -> Result<()> {
     Ok(())
 }
 
-pub fn force_stop_package<P: AsRef<Path>>(root_dir: P) -> Result<()> {
-    let root_dir = root_dir.as_ref().to_string_lossy().to_string();
-    let command = format!("quit app \"{}\"", root_dir);
-    Process::new("/usr/bin/osascript").arg("-e").arg(command).spawn().map_err(|z| anyhow!("Failed to stop application ({}).", z))?;
-    Ok(())
+/// Returns the id of every running process.
+pub(super) fn get_pids() -> Result<Vec<u32>> {
+    // SAFETY: a null buffer asks libproc for the number of processes, without writing anything
+    let count = unsafe { libc::proc_listallpids(std::ptr::null_mut(), 0) };
+    if count <= 0 {
+        bail!("Unable to list processes ({}).", std::io::Error::last_os_error());
+    }
+
+    // processes may be started between the two calls, so leave some room for them
+    let mut pids: Vec<libc::pid_t> = vec![0; count as usize + 64];
+    let size = (pids.len() * std::mem::size_of::<libc::pid_t>()) as libc::c_int;
+    // SAFETY: the buffer is valid for `size` bytes, and libproc writes at most that many
+    let count = unsafe { libc::proc_listallpids(pids.as_mut_ptr() as *mut libc::c_void, size) };
+    if count < 0 {
+        bail!("Unable to list processes ({}).", std::io::Error::last_os_error());
+    }
+    pids.truncate(count as usize);
+    Ok(pids.into_iter().filter(|p| *p > 0).map(|p| p as u32).collect())
+}
+
+/// Returns the executable of a process, or None if it can not be read (eg. it is owned by another user, or has exited).
+pub(super) fn get_pid_path(pid: u32) -> Option<PathBuf> {
+    let mut buf = vec![0u8; libc::PROC_PIDPATHINFO_MAXSIZE as usize];
+    // SAFETY: the buffer is valid for PROC_PIDPATHINFO_MAXSIZE bytes, which is the most proc_pidpath will write
+    let len = unsafe { libc::proc_pidpath(pid as libc::pid_t, buf.as_mut_ptr() as *mut libc::c_void, buf.len() as u32) };
+    if len <= 0 {
+        return None;
+    }
+    buf.truncate(len as usize);
+    Some(PathBuf::from(std::ffi::OsStr::from_bytes(&buf)))
 }
 
 pub fn start_package(locator: &VelopackLocator, exe_args: Option<Vec<&str>>, set_env: Option<&str>) -> Result<()> {
@@ -47,6 +78,116 @@ pub fn start_package(locator: &VelopackLocator, exe_args: Option<Vec<&str>>, set
     Ok(())
 }
 
+/// Returns true if the file system containing the path compares names case-insensitively, which APFS and HFS+ volumes
+/// do unless they were formatted as case-sensitive.
+pub fn is_case_insensitive_fs(path: &Path) -> bool {
+    // from <sys/unistd.h>, pathconf returns 1 if the volume is case-sensitive and 0 if it is not
+    const _PC_CASE_SENSITIVE: libc::c_int = 11;
+    // pathconf needs a path which exists, so check the nearest ancestor which does
+    let path = match path.ancestors().find(|p| p.exists()) {
+        Some(p) => p,
+        None => return true,
+    };
+    let c_path = match CString::new(path.as_os_str().as_bytes()) {
+        Ok(p) => p,
+        Err(_) => return true,
+    };
+    // SAFETY: c_path is a valid nul-terminated string
+    unsafe { libc::pathconf(c_path.as_ptr(), _PC_CASE_SENSITIVE) == 0 }
+}
+
+/// Atomically exchanges the two paths, which must be on the same volume, using renamex_np(RENAME_SWAP).
+pub fn swap_paths<P1: AsRef<Path>, P2: AsRef<Path>>(a: P1, b: P2) -> std::io::Result<()> {
+    let to_cstring =
+        |p: &Path| CString::new(p.as_os_str().as_bytes()).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e));
+    let a = to_cstring(a.as_ref())?;
+    let b = to_cstring(b.as_ref())?;
+    let ret = unsafe { libc::renamex_np(a.as_ptr(), b.as_ptr(), libc::RENAME_SWAP) };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Removes the com.apple.quarantine attribute from a newly extracted bundle and everything inside it. The files extracted
+/// from a downloaded package inherit this attribute, which would cause Gatekeeper to prompt the user or run the app from a
+/// translocated read-only copy the next time it is launched.
+pub fn remove_quarantine_attribute<P: AsRef<Path>>(bundle_path: P) {
+    let bundle_path = bundle_path.as_ref();
+    match Process::new("/usr/bin/xattr").args(["-d", "-r", "com.apple.quarantine"]).arg(bundle_path).output() {
+        Ok(output) if output.status.success() => info!("Removed quarantine attribute from {:?}", bundle_path),
+        Ok(output) => warn!("Unable to remove quarantine attribute ({}).", String::from_utf8_lossy(&output.stderr).trim()),
+        Err(e) => warn!("Unable to remove quarantine attribute ({}).", e),
+    }
+}
+
+/// Returns the team identifier the bundle at the specified path was signed with, or None if the bundle is not signed
+/// or was signed ad-hoc (without a team identifier).
+pub fn get_code_signature_team_id<P: AsRef<Path>>(bundle_path: P) -> Result<Option<String>> {
+    let bundle_path = bundle_path.as_ref();
+    let output = Process::new("/usr/bin/codesign").arg("-dv").arg("--verbose=2").arg(bundle_path).output()?;
+    // codesign writes the signature details to stderr
+    let details = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        if details.contains("not signed at all") {
+            return Ok(None);
+        }
+        bail!("Unable to read the code signature of '{}': {}", bundle_path.to_string_lossy(), details.trim());
+    }
+    Ok(parse_team_identifier(&details))
+}
+
+fn parse_team_identifier(details: &str) -> Option<String> {
+    details
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("TeamIdentifier="))
+        .map(|team| team.trim())
+        .find(|team| !team.is_empty() && *team != "not set")
+        .map(|team| team.to_owned())
+}
+
+/// Verifies that a new app bundle can safely replace the installed one. If the installed app is signed with a team
+/// identifier, the new bundle must have a valid signature from the same team and be notarized by Apple, so that
+/// a compromised release feed can not swap in code which was not signed by the app's developer. Unsigned apps (eg.
+/// local development builds) are not verified.
+pub fn verify_bundle_signature<P1: AsRef<Path>, P2: AsRef<Path>>(installed_path: P1, new_bundle_path: P2) -> Result<()> {
+    let new_bundle_path = new_bundle_path.as_ref();
+    let installed_team = match get_code_signature_team_id(installed_path)? {
+        Some(team) => team,
+        None => {
+            warn!("The installed app is not signed with a team identifier, so the code signature of the update will not be verified.");
+            return Ok(());
+        }
+    };
+
+    let output = Process::new("/usr/bin/codesign").args(["--verify", "--deep", "--strict"]).arg(new_bundle_path).output()?;
+    if !output.status.success() {
+        bail!("The code signature of the update is not valid: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    let new_team = get_code_signature_team_id(new_bundle_path)?;
+    if new_team.as_deref() != Some(installed_team.as_str()) {
+        bail!(
+            "The update is signed by team '{}', but the installed app is signed by team '{}'.",
+            new_team.as_deref().unwrap_or("(none)"),
+            installed_team
+        );
+    }
+
+    // xcrun is only available if the developer tools are installed, so the notarization is checked with codesign, which
+    // uses the stapled ticket, or asks Apple for it if none is stapled
+    let output = Process::new("/usr/bin/codesign")
+        .args(["--verify", "--deep", "--strict", "--check-notarization", "--test-requirement", "=notarized"])
+        .arg(new_bundle_path)
+        .output()?;
+    if !output.status.success() {
+        bail!("The update has not been notarized: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    info!("Verified the code signature and notarization of the update (team '{}').", installed_team);
+    Ok(())
+}
+
 #[test]
 #[ignore]
 fn test_start_and_stop_package() {
@@ -73,3 +214,14 @@ fn test_start_and_stop_package() {
     std::thread::sleep(Duration::from_secs(1));
     assert!(!is_running());
 }
+
+#[test]
+fn test_parse_team_identifier() {
+    let signed = "Executable=/Applications/Calcbot.app/Contents/MacOS/Calcbot\n\
+                  Authority=Developer ID Application: Tapbots (ABCDE12345)\n\
+                  TeamIdentifier=ABCDE12345\n\
+                  Sealed Resources version=2";
+    assert_eq!(parse_team_identifier(signed), Some("ABCDE12345".to_owned()));
+    let adhoc = "Executable=/Applications/Calcbot.app/Contents/MacOS/Calcbot\nSignature=adhoc\nTeamIdentifier=not set";
+    assert_eq!(parse_team_identifier(adhoc), None);
+}
@@ -15,6 +15,8 @@ use winsafe::{self as w, co, prelude::*};
 
 use velopack::locator::VelopackLocator;
 
+pub use crate::windows::is_sub_path;
+
 pub fn wait_for_pid_to_exit(pid: u32, ms_to_wait: u32) -> Result<()> {
     info!("Waiting {}ms for process ({}) to exit.", ms_to_wait, pid);
     let handle = w::HPROCESS::OpenProcess(co::PROCESS::SYNCHRONIZE, false, pid)?;
@@ -235,7 +237,7 @@ pub fn get_latest_app_version_folder<P: AsRef<Path>>(parent_path: P) -> Result<O
     for entry in get_app_prefixed_folders(&parent_path)? {
         if let Some(name) = entry.file_name().and_then(|n| n.to_str()) {
             if let Some(version) = parse_version_from_folder_name(name) {
-                if latest_version.is_none() || version > latest_version.clone().unwrap() {
+                if latest_version.as_ref().map_or(true, |latest| velopack::version::is_newer(&version, latest)) {
                     latest_version = Some(version);
                     latest_folder = Some(entry);
                 }
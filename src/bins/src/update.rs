@@ -6,8 +6,8 @@ extern crate log;
 
 use anyhow::{anyhow, bail, Result};
 use clap::{arg, value_parser, ArgMatches, Command};
-use std::{env, path::PathBuf};
-use velopack::locator;
+use std::{env, path::PathBuf, time::Duration};
+use velopack::{constants, locator, retention::RetentionPolicy};
 use velopack::locator::{auto_locate_app_manifest, LocationContext};
 use velopack_bins::*;
 
@@ -21,7 +21,11 @@ fn root_command() -> Command {
         .arg(arg!(--norestart "Do not restart the application after the update"))
         .arg(arg!(-w --wait "Wait for the parent process to terminate before applying the update").hide(true))
         .arg(arg!(--waitPid <PID> "Wait for the specified process to terminate before applying the update").value_parser(value_parser!(u32)))
+        .arg(arg!(--onExit "Queue the update until the process specified by --waitPid exits, no matter how long it runs"))
         .arg(arg!(-p --package <FILE> "Update package to apply").value_parser(value_parser!(PathBuf)))
+        .arg(arg!(--keepPackages <COUNT> "Number of full packages to keep after the update is applied").value_parser(value_parser!(usize)))
+        .arg(arg!(--maxPackagesSize <BYTES> "Delete the oldest packages until the packages kept are smaller than this").value_parser(value_parser!(u64)))
+        .arg(arg!(--maxPackageAgeDays <DAYS> "Delete packages which were downloaded more than this many days ago").value_parser(value_parser!(u64)))
         .arg(arg!([EXE_ARGS] "Arguments to pass to the started executable. Must be preceded by '--'.").required(false).last(true).num_args(0..))
     )
     .subcommand(Command::new("start")
@@ -39,12 +43,28 @@ fn root_command() -> Command {
         .arg(arg!(--patch <FILE> "The Zstd patch to apply to the old file").required(true).value_parser(value_parser!(PathBuf)))
         .arg(arg!(--output <FILE> "The file to create with the patch applied").required(true).value_parser(value_parser!(PathBuf)))
     )
+    .subcommand(Command::new("serve")
+        .about("Accepts update commands from a running application over a local IPC channel")
+        .arg(arg!(--source <URL_OR_PATH> "The url or local directory to check for updates").required(true))
+        .arg(arg!(--hostPid <PID> "The process id of the application which will connect to this updater").required(true).value_parser(value_parser!(u32)))
+        .hide(true)
+    )
+    .subcommand(Command::new("check")
+        .about("Checks for updates, and optionally downloads them, without applying anything")
+        .arg(arg!(--source <URL_OR_PATH> "The url or local directory to check for updates").required(true))
+        .arg(arg!(--download "Download the update if one is available, so it can be applied the next time the app starts"))
+    )
+    .subcommand(Command::new("shortcuts")
+        .about("Creates the app shortcuts for the current user")
+        .hide(true)
+    )
     .subcommand(Command::new("get-version")
         .about("Prints the current version of the application")
     )
     .arg(arg!(--verbose "Print debug messages to console / log").global(true))
     .arg(arg!(-s --silent "Don't show any prompts / dialogs").global(true))
     .arg(arg!(-l --log <PATH> "Override the default log file location").global(true).value_parser(value_parser!(PathBuf)))
+    .arg(arg!(--lockTimeout <SECONDS> "Wait up to this many seconds for another installer or updater to finish").global(true).value_parser(value_parser!(u64)))
         // Legacy arguments should not be fully removed if it's possible to keep them
         // Reason being is clap.ignore_errors(true) is not 100%, and sometimes old args can trip things up.
     .arg(arg!(--forceLatest "Legacy argument").hide(true).global(true))
@@ -54,10 +74,102 @@ fn root_command() -> Command {
     .disable_help_subcommand(true)
     .flatten_help(true);
 
+    #[cfg(feature = "pack")]
+    let cmd = cmd.subcommand(Command::new("pack")
+        .about("Creates a release package (and an installer or AppImage) for the current OS from a compiled app")
+        .arg(arg!(-u --packId <ID> "The unique id of the app").required(true))
+        .arg(arg!(-v --packVersion <VERSION> "The semver version of the release").required(true).value_parser(value_parser!(semver::Version)))
+        .arg(arg!(-p --packDir <DIR> "The directory (or .app bundle on macOS) containing the compiled app").required(true).value_parser(value_parser!(PathBuf)))
+        .arg(arg!(-e --mainExe <NAME> "The name of the executable which is started after install and update"))
+        .arg(arg!(--packTitle <NAME> "The display name of the app"))
+        .arg(arg!(--packAuthors <AUTHORS> "The company or author names of the app"))
+        .arg(arg!(-c --channel <NAME> "The release channel, defaults to the name of the current OS"))
+        .arg(arg!(--osMinVersion <VERSION> "The minimum version of Windows the app can be installed on, eg. '10.0.17763' or '11'"))
+        .arg(arg!(--osMinServerVersion <VERSION> "The minimum version of Windows Server, eg. '2019', if it differs from --osMinVersion"))
+        .arg(arg!(--sideBySide "Install each channel separately, so eg. stable and beta builds can be installed at the same time"))
+        .arg(arg!(--mandatory "Mark the release as mandatory in the release feed, so apps do not let the user skip it"))
+        .arg(arg!(-i --icon <FILE> "An .ico file which is shown by the installer, or on Linux a .png or .svg for the desktop entry").value_parser(value_parser!(PathBuf)))
+        .arg(arg!(-o --outputDir <DIR> "The directory to write the release files to (default 'Releases')").value_parser(value_parser!(PathBuf)))
+        .arg(arg!(--setupExe <FILE> "The setup.exe to create the installer from (default is next to this binary)").value_parser(value_parser!(PathBuf)))
+        .arg(arg!(--stubExe <FILE> "The stub.exe which starts the app of portable installs (default is next to this binary)").value_parser(value_parser!(PathBuf)))
+        .arg(arg!(--appImageRuntime <FILE> "The AppImage runtime for the app's architecture (default is next to this binary)").value_parser(value_parser!(PathBuf)))
+        .arg(arg!(--executionLevel <LEVEL> "The privileges setup.exe requests, 'asInvoker' (default), 'highestAvailable' or 'requireAdministrator'").value_parser(value_parser!(commands::ExecutionLevel)))
+        .arg(arg!(--dpiAware <BOOL> "Whether setup.exe is DPI aware, 'true' or 'false', the manifest of setup.exe is left unchanged if not set").value_parser(value_parser!(bool)))
+        .arg(arg!(--signTemplate <COMMAND> "A custom signing command, where {{file}} is replaced with one file or {{file...}} with many"))
+        .arg(arg!(-n --signParams <PARAMS> "Sign files with 'signtool sign' and these parameters (Windows only)"))
+        .arg(arg!(--signParallel <COUNT> "The number of files signed at once (default 10)").value_parser(value_parser!(usize)))
+        .arg(arg!(--signSkipDll "Only sign executables, and skip libraries"))
+        .arg(arg!(--noDelta "Do not create a delta package from the previous release in the output directory"))
+        .arg(arg!(--externalPayload "Create an installer which installs the package from the same directory, instead of containing it"))
+        .arg(arg!(--compression <METHOD> "How the packages are compressed, 'deflate' (default) or 'store'").value_parser(value_parser!(velopack::bundle::CompressionMethod)))
+        .arg(arg!(--compressionLevel <LEVEL> "The compression level from 0 (fastest) to 9 (smallest), the default is 6").value_parser(value_parser!(i64).range(0..=9)))
+        .arg(arg!(--threads <COUNT> "The number of files to compress or diff at once, defaults to the number of CPU cores (up to 8)").value_parser(value_parser!(usize)))
+    );
+
+    #[cfg(feature = "pack")]
+    let cmd = cmd.subcommand(Command::new("releases")
+        .about("Creates or updates the release index (releases.{channel}.json and RELEASES) of a directory of packages")
+        .arg(arg!(-o --outputDir <DIR> "The directory containing the packages (default 'Releases')").value_parser(value_parser!(PathBuf)))
+        .arg(arg!(-c --channel <NAME> "The release channel, defaults to the name of the current OS"))
+        .arg(arg!(-a --add <FILE> "A package in the directory to add to the index, can be specified more than once").action(clap::ArgAction::Append).value_parser(value_parser!(PathBuf)))
+        .arg(arg!(--rebuild "Recreate the index from every package of the channel in the directory"))
+        .arg(arg!(--keep <COUNT> "Remove all but this many of the newest full releases from the index").value_parser(value_parser!(usize)))
+        .arg(arg!(--deletePruned "Delete the packages which were removed from the index by --keep"))
+        .arg(arg!(--rehash "Recalculate the size and checksums of every package in the index, after they were re-signed by another tool"))
+    );
+
     #[cfg(target_os = "windows")]
     let cmd = cmd.subcommand(Command::new("uninstall")
         .about("Remove all app shortcuts, files, and registry entries.")
         .long_flag_alias("uninstall")
+        .arg(arg!(--reason <TEXT> "Why the app is being uninstalled, this is passed on to the app uninstall hook"))
+    )
+    .subcommand(Command::new("migrate")
+        .about("Moves the app between a per-user and a per-machine install, asking for administrator privileges")
+        .arg(arg!(--"to-machine" "Move the app to Program Files, and make it available to every user"))
+        .arg(arg!(--"to-user" "Move the app to %LocalAppData%, and make it available to the current user only"))
+        .arg(arg!(--installTo <DIR> "The directory to move the app to, instead of the default for the new scope").value_parser(value_parser!(PathBuf)))
+        .arg(arg!(--machineOnly "Only move the files and per-machine state, the per-user state is moved by the process which started this one").hide(true))
+        .group(clap::ArgGroup::new("scope").args(["to-machine", "to-user"]).required(true))
+    );
+
+    // this is accepted on every OS, so that it is rejected with a clear error where it is not supported
+    let cmd = cmd.arg(arg!(--sandbox <DIR> "Redirect the install, shortcuts and registry entries into a directory, for testing (Windows only)").global(true).value_parser(value_parser!(PathBuf)));
+
+    let cmd = cmd.subcommand(Command::new("install-agent")
+        .about("Installs a per-user scheduled task (Windows), launch agent (macOS) or systemd timer (Linux) which periodically checks for updates in the background")
+        .arg(arg!(--source <URL_OR_PATH> "The url or local directory to check for updates").required(true))
+        .arg(arg!(--interval <SECONDS> "How often to check for updates, in seconds (default 4 hours)").value_parser(value_parser!(u64)))
+        .arg(arg!(--download "Also download updates, so they can be applied the next time the app starts"))
+    )
+    .subcommand(Command::new("uninstall-agent")
+        .about("Removes the background update task, launch agent or timer")
+    );
+
+    #[cfg(target_os = "macos")]
+    let cmd = cmd.subcommand(Command::new("install-helper")
+        .about("Installs a privileged helper, so users who are not an administrator can update an app in a protected location")
+    )
+    .subcommand(Command::new("uninstall-helper")
+        .about("Removes the privileged helper")
+    )
+    .subcommand(Command::new("privileged-helper")
+        .about("Runs the privileged helper, this is started by launchd")
+        .arg(arg!(--root <DIR> "The app bundle the helper applies updates to").required(true).value_parser(value_parser!(PathBuf)))
+        .arg(arg!(--uid <UID> "The user who is allowed to ask the helper for an update").required(true).value_parser(value_parser!(u32)))
+        .hide(true)
+    );
+
+    #[cfg(target_os = "linux")]
+    let cmd = cmd.subcommand(Command::new("uninstall")
+        .about("Remove the AppImage, its desktop entry, update timer and downloaded packages.")
+        .long_flag_alias("uninstall")
+    )
+    .subcommand(Command::new("install-desktop-entry")
+        .about("Adds the app to application launchers, for every user if run as root or otherwise for the current user")
+    )
+    .subcommand(Command::new("uninstall-desktop-entry")
+        .about("Removes the app from application launchers")
     );
     cmd
 }
@@ -104,7 +216,10 @@ fn get_flag_or_false(matches: &ArgMatches, id: &str) -> bool {
 fn get_op_wait(matches: &ArgMatches) -> shared::OperationWait {
     let wait_for_parent = get_flag_or_false(&matches, "wait");
     let wait_pid = matches.try_get_one::<u32>("waitPid").unwrap_or(None).map(|v| v.to_owned());
-    if wait_pid.is_some() {
+    let on_exit = get_flag_or_false(&matches, "onExit");
+    if wait_pid.is_some() && on_exit {
+        shared::OperationWait::WaitPidIndefinitely(wait_pid.unwrap())
+    } else if wait_pid.is_some() {
         shared::OperationWait::WaitPid(wait_pid.unwrap())
     } else if wait_for_parent {
         shared::OperationWait::WaitParent
@@ -113,14 +228,39 @@ fn get_op_wait(matches: &ArgMatches) -> shared::OperationWait {
     }
 }
 
+fn get_lock_timeout(matches: &ArgMatches) -> Duration {
+    let seconds = matches.try_get_one::<u64>("lockTimeout").unwrap_or(None).map(|v| v.to_owned()).unwrap_or(0);
+    Duration::from_secs(seconds)
+}
+
+fn get_retention_policy(matches: &ArgMatches) -> RetentionPolicy {
+    let mut policy = RetentionPolicy::default();
+    if let Some(keep) = matches.try_get_one::<usize>("keepPackages").unwrap_or(None) {
+        policy.keep_full_packages = (*keep).max(1);
+    }
+    policy.max_total_bytes = matches.try_get_one::<u64>("maxPackagesSize").unwrap_or(None).copied().filter(|b| *b > 0);
+    let max_age_days = matches.try_get_one::<u64>("maxPackageAgeDays").unwrap_or(None).copied().filter(|d| *d > 0);
+    policy.max_age = max_age_days.map(|d| Duration::from_secs(d * 24 * 60 * 60));
+    policy
+}
+
 fn main() -> Result<()> {
     #[cfg(windows)]
     windows::mitigate::pre_main_sideload_mitigation();
 
     #[cfg(windows)]
-    let matches = try_parse_command_line_matches(env::args().collect())?;
+    let matches = try_parse_command_line_matches(env::args().collect());
     #[cfg(unix)]
-    let matches = root_command().try_get_matches()?;
+    let matches = root_command().try_get_matches().map_err(anyhow::Error::from);
+    let matches = match matches {
+        Ok(matches) => matches,
+        // clap reports --help and --version as errors, but they must print to stdout and exit successfully, because
+        // a new updater is only trusted once it has printed its version
+        Err(e) => match e.downcast_ref::<clap::Error>() {
+            Some(e) if matches!(e.kind(), clap::error::ErrorKind::DisplayHelp | clap::error::ErrorKind::DisplayVersion) => e.exit(),
+            _ => return Err(e),
+        },
+    };
 
     let (subcommand, subcommand_matches) = matches.subcommand().ok_or_else(|| anyhow!("No subcommand was used. Try `--help` for more information."))?;
 
@@ -129,9 +269,19 @@ fn main() -> Result<()> {
     let log_file = matches.get_one("log");
 
     dialogs::set_silent(silent);
-    let desired_log_file = log_file.cloned().unwrap_or(locator::default_log_location(LocationContext::IAmUpdateExe));
+    if let Some(sandbox) = matches.get_one::<PathBuf>("sandbox") {
+        #[cfg(target_os = "windows")]
+        windows::sandbox::set_sandbox_dir(sandbox)?;
+        #[cfg(not(target_os = "windows"))]
+        bail!("The sandbox ({:?}) is only supported on Windows.", sandbox);
+    }
+    let desired_log_file = log_file.cloned().unwrap_or_else(|| locator::default_log_location(LocationContext::IAmUpdateExe));
     logging::setup_logging("update", Some(&desired_log_file), true, verbose)?;
 
+    // relative paths passed to pack are resolved from where the command was run, before the working directory changes
+    #[cfg(feature = "pack")]
+    let initial_dir = env::current_dir()?;
+
     // change working directory to the parent directory of the exe
     let mut containing_dir = env::current_exe()?;
     containing_dir.pop();
@@ -144,13 +294,36 @@ fn main() -> Result<()> {
     info!("    Verbose: {}", verbose);
     info!("    Silent: {}", silent);
     info!("    Log File: {:?}", log_file);
+    #[cfg(target_os = "windows")]
+    info!("    Sandbox: {:?}", windows::sandbox::get_sandbox_dir());
 
     let result = match subcommand {
-        #[cfg(target_os = "windows")]
+        #[cfg(any(target_os = "windows", target_os = "linux"))]
         "uninstall" => uninstall(subcommand_matches).map_err(|e| anyhow!("Uninstall error: {}", e)),
+        #[cfg(target_os = "windows")]
+        "migrate" => migrate(subcommand_matches).map_err(|e| anyhow!("Migrate error: {}", e)),
+        "shortcuts" => shortcuts(subcommand_matches).map_err(|e| anyhow!("Shortcuts error: {}", e)),
         "start" => start(subcommand_matches).map_err(|e| anyhow!("Start error: {}", e)),
         "apply" => apply(subcommand_matches).map_err(|e| anyhow!("Apply error: {}", e)),
         "patch" => patch(subcommand_matches).map_err(|e| anyhow!("Patch error: {}", e)),
+        "serve" => serve(subcommand_matches).map_err(|e| anyhow!("Serve error: {}", e)),
+        "check" => check(subcommand_matches).map_err(|e| anyhow!("Check error: {}", e)),
+        #[cfg(feature = "pack")]
+        "pack" => pack(subcommand_matches, &initial_dir).map_err(|e| anyhow!("Pack error: {}", e)),
+        #[cfg(feature = "pack")]
+        "releases" => releases(subcommand_matches, &initial_dir).map_err(|e| anyhow!("Releases error: {}", e)),
+        "install-agent" => install_agent(subcommand_matches).map_err(|e| anyhow!("Install agent error: {}", e)),
+        "uninstall-agent" => uninstall_agent(subcommand_matches).map_err(|e| anyhow!("Uninstall agent error: {}", e)),
+        #[cfg(target_os = "macos")]
+        "install-helper" => install_helper(subcommand_matches).map_err(|e| anyhow!("Install helper error: {}", e)),
+        #[cfg(target_os = "macos")]
+        "uninstall-helper" => uninstall_helper(subcommand_matches).map_err(|e| anyhow!("Uninstall helper error: {}", e)),
+        #[cfg(target_os = "macos")]
+        "privileged-helper" => privileged_helper(subcommand_matches).map_err(|e| anyhow!("Privileged helper error: {}", e)),
+        #[cfg(target_os = "linux")]
+        "install-desktop-entry" => install_desktop_entry(subcommand_matches).map_err(|e| anyhow!("Install desktop entry error: {}", e)),
+        #[cfg(target_os = "linux")]
+        "uninstall-desktop-entry" => uninstall_desktop_entry(subcommand_matches).map_err(|e| anyhow!("Uninstall desktop entry error: {}", e)),
         _ => bail!("Unknown subcommand '{subcommand}'. Try `--help` for more information."),
     };
 
@@ -176,25 +349,209 @@ fn patch(matches: &ArgMatches) -> Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "pack")]
+fn pack(matches: &ArgMatches, initial_dir: &std::path::Path) -> Result<()> {
+    let options = commands::PackOptions {
+        pack_id: matches.get_one::<String>("packId").cloned().unwrap_or_default(),
+        pack_version: matches.get_one::<semver::Version>("packVersion").cloned(),
+        pack_dir: initial_dir.join(matches.get_one::<PathBuf>("packDir").cloned().unwrap_or_default()),
+        main_exe: matches.get_one::<String>("mainExe").cloned().unwrap_or_default(),
+        pack_title: matches.get_one::<String>("packTitle").cloned(),
+        pack_authors: matches.get_one::<String>("packAuthors").cloned(),
+        channel: matches.get_one::<String>("channel").cloned(),
+        os_min_version: matches.get_one::<String>("osMinVersion").cloned(),
+        os_min_server_version: matches.get_one::<String>("osMinServerVersion").cloned(),
+        side_by_side: get_flag_or_false(matches, "sideBySide"),
+        mandatory: get_flag_or_false(matches, "mandatory"),
+        icon: matches.get_one::<PathBuf>("icon").map(|p| initial_dir.join(p)),
+        output_dir: initial_dir.join(matches.get_one::<PathBuf>("outputDir").cloned().unwrap_or_else(|| PathBuf::from("Releases"))),
+        update_exe: env::current_exe()?,
+        setup_exe: matches.get_one::<PathBuf>("setupExe").map(|p| initial_dir.join(p)),
+        stub_exe: matches.get_one::<PathBuf>("stubExe").map(|p| initial_dir.join(p)),
+        appimage_runtime: matches.get_one::<PathBuf>("appImageRuntime").map(|p| initial_dir.join(p)),
+        execution_level: matches.get_one::<commands::ExecutionLevel>("executionLevel").cloned(),
+        dpi_aware: matches.get_one::<bool>("dpiAware").cloned(),
+        signing: commands::SigningOptions {
+            sign_template: matches.get_one::<String>("signTemplate").cloned(),
+            sign_params: matches.get_one::<String>("signParams").cloned(),
+            sign_parallel: matches.get_one::<usize>("signParallel").cloned().unwrap_or(10),
+            sign_skip_dll: get_flag_or_false(matches, "signSkipDll"),
+        },
+        no_delta: get_flag_or_false(matches, "noDelta"),
+        external_payload: get_flag_or_false(matches, "externalPayload"),
+        compression: velopack::bundle::CompressionOptions {
+            method: matches.get_one::<velopack::bundle::CompressionMethod>("compression").cloned().unwrap_or_default(),
+            level: matches.get_one::<i64>("compressionLevel").cloned(),
+            threads: matches.get_one::<usize>("threads").cloned(),
+        },
+    };
+
+    info!("Command: Pack");
+    info!("    {:?}", options);
+    for file in commands::pack(&options)? {
+        println!("{}", file.to_string_lossy());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "pack")]
+fn releases(matches: &ArgMatches, initial_dir: &std::path::Path) -> Result<()> {
+    let releases_dir = initial_dir.join(matches.get_one::<PathBuf>("outputDir").cloned().unwrap_or_else(|| PathBuf::from("Releases")));
+    // packages are always read from the releases directory, so only their file names matter
+    let add = matches.get_many::<PathBuf>("add").map(|v| v.filter_map(|p| p.file_name()).map(|n| releases_dir.join(n)).collect());
+    let options = commands::ReleasesOptions {
+        add: add.unwrap_or_default(),
+        releases_dir,
+        channel: matches.get_one::<String>("channel").cloned().unwrap_or_else(|| commands::get_current_os().to_owned()),
+        rebuild: get_flag_or_false(matches, "rebuild"),
+        keep_releases: matches.get_one::<usize>("keep").cloned(),
+        delete_pruned: get_flag_or_false(matches, "deletePruned"),
+        refresh_checksums: get_flag_or_false(matches, "rehash"),
+    };
+
+    info!("Command: Releases");
+    info!("    {:?}", options);
+    let index = commands::update_releases(&options)?;
+    for asset in &index.feed().Assets {
+        println!("{}", asset.FileName);
+    }
+    Ok(())
+}
+
 fn apply(matches: &ArgMatches) -> Result<()> {
     let restart = !get_flag_or_false(&matches, "norestart");
     let package = matches.get_one::<PathBuf>("package");
     let exe_args: Option<Vec<&str>> = matches.get_many::<String>("EXE_ARGS").map(|v| v.map(|f| f.as_str()).collect());
-    let wait = get_op_wait(&matches);
+    let mut wait = get_op_wait(&matches);
+    let lock_timeout = get_lock_timeout(&matches);
+    let retention = get_retention_policy(&matches);
 
     info!("Command: Apply");
     info!("    Restart: {:?}", restart);
     info!("    Wait: {:?}", wait);
     info!("    Package: {:?}", package);
     info!("    Exe Args: {:?}", exe_args);
+    info!("    Lock Timeout: {:?}", lock_timeout);
+    info!("    Retention: {:?}", retention);
+
+    // a queued update may wait for a long time, so don't block other installers / updaters until the app has exited
+    if let shared::OperationWait::WaitPidIndefinitely(_) = wait {
+        shared::operation_wait(wait);
+        wait = shared::OperationWait::NoWait;
+    }
 
     let locator = auto_locate_app_manifest(LocationContext::IAmUpdateExe)?;
     #[cfg(target_os = "windows")]
-    let _mutex = shared::retry_io(|| windows::create_global_mutex(&locator.get_manifest_id()))?;
-    let _ = commands::apply(&locator, restart, wait, package, exe_args, true)?;
+    let _mutex = if lock_timeout.is_zero() {
+        shared::retry_io(|| windows::create_global_mutex(&locator.get_install_id()))?
+    } else {
+        windows::wait_for_global_mutex(&locator.get_install_id(), lock_timeout)?
+    };
+    let _lock = shared::lock_root_dir(&locator, lock_timeout)?;
+    if let Err(e) = commands::apply(&locator, restart, wait, package, exe_args, true, &retention) {
+        if e.downcast_ref::<shared::RestartRequiredError>().is_some() {
+            error!("{}", e);
+            std::process::exit(constants::EXIT_CODE_RESTART_REQUIRED);
+        }
+        return Err(e);
+    }
     Ok(())
 }
 
+fn serve(matches: &ArgMatches) -> Result<()> {
+    let source = matches.get_one::<String>("source").unwrap();
+    let host_pid = *matches.get_one::<u32>("hostPid").unwrap();
+
+    info!("Command: Serve");
+    info!("    Source: {:?}", source);
+    info!("    Host Pid: {:?}", host_pid);
+
+    let locator = auto_locate_app_manifest(LocationContext::IAmUpdateExe)?;
+    commands::serve(&locator, source, host_pid)
+}
+
+fn check(matches: &ArgMatches) -> Result<()> {
+    let source = matches.get_one::<String>("source").unwrap();
+    let download = get_flag_or_false(matches, "download");
+
+    info!("Command: Check");
+    info!("    Source: {:?}", source);
+    info!("    Download: {:?}", download);
+
+    let locator = auto_locate_app_manifest(LocationContext::IAmUpdateExe)?;
+    commands::check(&locator, source, download)
+}
+
+fn install_agent(matches: &ArgMatches) -> Result<()> {
+    let source = matches.get_one::<String>("source").unwrap();
+    let interval = matches.try_get_one::<u64>("interval").unwrap_or(None).copied().filter(|s| *s > 0).unwrap_or(4 * 60 * 60);
+    let download = get_flag_or_false(matches, "download");
+
+    info!("Command: Install Agent");
+    info!("    Source: {:?}", source);
+    info!("    Interval: {:?}", interval);
+    info!("    Download: {:?}", download);
+
+    let locator = auto_locate_app_manifest(LocationContext::IAmUpdateExe)?;
+    #[cfg(target_os = "macos")]
+    return commands::install_launch_agent(&locator, source, Duration::from_secs(interval), download);
+    #[cfg(target_os = "linux")]
+    return commands::install_update_timer(&locator, source, Duration::from_secs(interval), download);
+    #[cfg(target_os = "windows")]
+    return commands::install_update_task(&locator, source, Duration::from_secs(interval), download);
+}
+
+fn uninstall_agent(_matches: &ArgMatches) -> Result<()> {
+    info!("Command: Uninstall Agent");
+    let locator = auto_locate_app_manifest(LocationContext::IAmUpdateExe)?;
+    #[cfg(target_os = "macos")]
+    return commands::uninstall_launch_agent(&locator);
+    #[cfg(target_os = "linux")]
+    return commands::uninstall_update_timer(&locator);
+    #[cfg(target_os = "windows")]
+    return commands::uninstall_update_task(&locator);
+}
+
+#[cfg(target_os = "macos")]
+fn install_helper(_matches: &ArgMatches) -> Result<()> {
+    info!("Command: Install Helper");
+    let locator = auto_locate_app_manifest(LocationContext::IAmUpdateExe)?;
+    commands::install_privileged_helper(&locator)
+}
+
+#[cfg(target_os = "macos")]
+fn uninstall_helper(_matches: &ArgMatches) -> Result<()> {
+    info!("Command: Uninstall Helper");
+    let locator = auto_locate_app_manifest(LocationContext::IAmUpdateExe)?;
+    commands::uninstall_privileged_helper(&locator)
+}
+
+#[cfg(target_os = "macos")]
+fn privileged_helper(matches: &ArgMatches) -> Result<()> {
+    let root_dir = matches.get_one::<PathBuf>("root").unwrap();
+    let owner_uid = *matches.get_one::<u32>("uid").unwrap();
+    info!("Command: Privileged Helper");
+    info!("    Root Dir: {:?}", root_dir);
+    info!("    Owner Uid: {}", owner_uid);
+    // the helper is run from /Library/PrivilegedHelperTools, so the app it applies updates to is passed by launchd
+    let locator = auto_locate_app_manifest(LocationContext::FromSpecifiedRootDir(root_dir.clone()))?;
+    commands::run_privileged_helper(&locator, owner_uid)
+}
+
+#[cfg(target_os = "linux")]
+fn install_desktop_entry(_matches: &ArgMatches) -> Result<()> {
+    info!("Command: Install Desktop Entry");
+    let locator = auto_locate_app_manifest(LocationContext::IAmUpdateExe)?;
+    commands::install_desktop_entry(&locator)
+}
+
+#[cfg(target_os = "linux")]
+fn uninstall_desktop_entry(_matches: &ArgMatches) -> Result<()> {
+    info!("Command: Uninstall Desktop Entry");
+    let locator = auto_locate_app_manifest(LocationContext::IAmUpdateExe)?;
+    commands::uninstall_desktop_entry(&locator)
+}
+
 fn start(matches: &ArgMatches) -> Result<()> {
     let legacy_args = matches.get_one::<String>("args");
     let exe_name = matches.get_one::<String>("EXE_NAME");
@@ -214,10 +571,45 @@ fn start(matches: &ArgMatches) -> Result<()> {
 }
 
 #[cfg(target_os = "windows")]
+fn uninstall(matches: &ArgMatches) -> Result<()> {
+    let reason = matches.get_one::<String>("reason");
+
+    info!("Command: Uninstall");
+    info!("    Reason: {:?}", reason);
+
+    let locator = auto_locate_app_manifest(LocationContext::IAmUpdateExe)?;
+    commands::uninstall(&locator, true, reason.map(|r| r.as_str()))
+}
+
+#[cfg(target_os = "windows")]
+fn migrate(matches: &ArgMatches) -> Result<()> {
+    let to_machine = get_flag_or_false(matches, "to-machine");
+    let install_to = matches.get_one::<PathBuf>("installTo");
+    let machine_only = get_flag_or_false(matches, "machineOnly");
+
+    info!("Command: Migrate");
+    info!("    To Machine: {:?}", to_machine);
+    info!("    Install To: {:?}", install_to);
+    info!("    Machine Only: {:?}", machine_only);
+
+    let locator = auto_locate_app_manifest(LocationContext::IAmUpdateExe)?;
+    commands::migrate(&locator, to_machine, install_to, machine_only)
+}
+
+#[cfg(target_os = "linux")]
 fn uninstall(_matches: &ArgMatches) -> Result<()> {
     info!("Command: Uninstall");
     let locator = auto_locate_app_manifest(LocationContext::IAmUpdateExe)?;
-    commands::uninstall(&locator, true)
+    let _lock = shared::lock_root_dir(&locator, Duration::ZERO)?;
+    commands::uninstall(&locator)
+}
+
+fn shortcuts(_matches: &ArgMatches) -> Result<()> {
+    use commands::Shortcuts;
+    info!("Command: Shortcuts");
+    let locator = auto_locate_app_manifest(LocationContext::IAmUpdateExe)?;
+    commands::PlatformShortcuts.create_or_update_manifest_shortcuts(&locator, None);
+    Ok(())
 }
 
 #[cfg(target_os = "windows")]
@@ -311,3 +703,16 @@ fn test_start_command_supports_legacy_commands() {
     assert_eq!(legacy_args, Some(&"-- -c \" asda --aasd".to_string()));
     assert_eq!(exe_args, None);
 }
+
+#[test]
+fn test_apply_command_parses_retention_policy() {
+    let command = vec!["Update", "apply", "--keepPackages", "3", "--maxPackageAgeDays", "30"];
+    let matches = root_command().try_get_matches_from(command).unwrap();
+    let policy = get_retention_policy(matches.subcommand_matches("apply").unwrap());
+    assert_eq!(policy.keep_full_packages, 3);
+    assert_eq!(policy.max_total_bytes, None);
+    assert_eq!(policy.max_age, Some(Duration::from_secs(30 * 24 * 60 * 60)));
+
+    let matches = root_command().try_get_matches_from(vec!["Update", "apply"]).unwrap();
+    assert_eq!(get_retention_policy(matches.subcommand_matches("apply").unwrap()), RetentionPolicy::default());
+}
@@ -0,0 +1,526 @@
+use anyhow::{bail, Result};
+use std::{
+    collections::BTreeSet,
+    fmt::Display,
+    os::windows::process::CommandExt,
+    path::{Path, PathBuf},
+    process::Command as Process,
+};
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::ERROR_FILE_NOT_FOUND,
+        System::Registry::{
+            RegCloseKey, RegOpenKeyExW, RegQueryValueExW, RegSetValueExW, HKEY, HKEY_LOCAL_MACHINE, KEY_QUERY_VALUE, KEY_SET_VALUE,
+            REG_MULTI_SZ, REG_SAM_FLAGS,
+        },
+    },
+};
+
+use super::strings::string_to_u16;
+
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+const RUN_KEYS: [&str; 4] = [
+    "HKCU\\Software\\Microsoft\\Windows\\CurrentVersion\\Run",
+    "HKCU\\Software\\Microsoft\\Windows\\CurrentVersion\\RunOnce",
+    "HKLM\\Software\\Microsoft\\Windows\\CurrentVersion\\Run",
+    "HKLM\\Software\\Microsoft\\Windows\\CurrentVersion\\RunOnce",
+];
+const CLASSES_KEYS: [&str; 2] = ["HKCU\\Software\\Classes", "HKLM\\Software\\Classes"];
+const SERVICES_KEY: &str = "HKLM\\SYSTEM\\CurrentControlSet\\Services";
+const SESSION_MANAGER_KEY: &str = "SYSTEM\\CurrentControlSet\\Control\\Session Manager";
+const PENDING_RENAMES_VALUE: &str = "PendingFileRenameOperations";
+
+/// Classes which are shared by every app, so only the shell verb an app added to them may be removed.
+const SHARED_CLASSES: [&str; 9] = [
+    "*",
+    "AllFilesystemObjects",
+    "DesktopBackground",
+    "Directory",
+    "Drive",
+    "Folder",
+    "Local Settings",
+    "SystemFileAssociations",
+    "Unknown",
+];
+/// Containers of per-registration keys (eg. CLSID\{guid}), where only the registration itself may be removed.
+const CLASS_CONTAINERS: [&str; 5] = ["AppID", "Applications", "CLSID", "Interface", "TypeLib"];
+/// Container of AppUserModelId registrations, where only the app's own AppUserModelId may be removed.
+const AMUID_CONTAINER: &str = "AppUserModelId";
+
+/// Collects everything uninstall removed, and everything it was unable to remove along with the reason, so the user
+/// can be told exactly what was left behind.
+#[derive(Debug, Default)]
+pub struct CleanupReport {
+    removed: Vec<String>,
+    failed: Vec<(String, String)>,
+}
+
+impl CleanupReport {
+    /// Records an item which was removed successfully.
+    pub fn removed<S: Into<String>>(&mut self, item: S) {
+        let item = item.into();
+        info!("Removed {}", item);
+        self.removed.push(item);
+    }
+
+    /// Records an item which could not be removed, and why.
+    pub fn failed<S: Into<String>, E: Display>(&mut self, item: S, reason: E) {
+        let item = item.into();
+        let reason = reason.to_string();
+        error!("Unable to remove {} ({}).", item, reason);
+        self.failed.push((item, reason));
+    }
+
+    /// Records the result of removing an item.
+    pub fn record<S: Into<String>, T, E: Display>(&mut self, item: S, result: std::result::Result<T, E>) {
+        match result {
+            Ok(_) => self.removed(item),
+            Err(e) => self.failed(item, e),
+        }
+    }
+
+    /// Returns true if everything was removed.
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Returns a summary of the cleanup which is suitable to show to a user.
+    pub fn to_summary(&self, app_title: &str) -> String {
+        let mut text = format!("{} uninstall report\r\n\r\n", app_title);
+        if self.failed.is_empty() {
+            text += "Everything was removed successfully.\r\n";
+        } else {
+            text += &format!("The following {} item(s) could not be removed:\r\n", self.failed.len());
+            for (item, reason) in &self.failed {
+                text += &format!("  - {}: {}\r\n", item, reason);
+            }
+        }
+        if !self.removed.is_empty() {
+            text += &format!("\r\nThe following {} item(s) were removed:\r\n", self.removed.len());
+            for item in &self.removed {
+                text += &format!("  - {}\r\n", item);
+            }
+        }
+        text
+    }
+}
+
+/// Removes startup entries (Run and RunOnce values) which launch something inside the app directory.
+pub fn remove_run_values(root_dir: &Path, report: &mut CleanupReport) {
+    for key in RUN_KEYS {
+        for value in parse_reg_query(&query_tool("reg", &["query", key])) {
+            if refers_to_dir(&value.data, root_dir) {
+                let name_args = if value.name == "(Default)" { vec!["/ve"] } else { vec!["/v", value.name.as_str()] };
+                let mut args = vec!["delete", value.key.as_str()];
+                args.extend(name_args);
+                args.push("/f");
+                report.record(format!("startup entry '{}\\{}'", value.key, value.name), run_tool("reg", &args));
+            }
+        }
+    }
+}
+
+/// Removes file associations, url protocols, shell verbs and COM registrations which refer to the app directory.
+/// ProgIDs and AppUserModelIds named after the app (see [is_owned_name]) are deleted entirely, along with file
+/// extensions which default to them, while classes shared with other apps (eg. Directory) only lose the shell verbs the
+/// app added. Any other key is left in place, even if it refers to the app directory.
+pub fn remove_class_registrations(root_dir: &Path, owned_names: &[String], report: &mut CleanupReport) {
+    let root_str = root_dir.to_string_lossy().to_string();
+    for classes_key in CLASSES_KEYS {
+        let output = query_tool("reg", &["query", classes_key, "/s", "/f", &root_str, "/d"]);
+        let mut to_remove = BTreeSet::new();
+        for value in parse_reg_query(&output) {
+            if !refers_to_dir(&value.data, root_dir) {
+                continue;
+            }
+            match get_relative_key(&value.key, classes_key).and_then(|k| get_class_key_to_remove(&k, owned_names)) {
+                Some(key) => {
+                    to_remove.insert(key);
+                }
+                None => info!("Not removing '{}', it was not registered by the app.", value.key),
+            }
+        }
+
+        for key in &to_remove {
+            let full_key = format!("{}\\{}", classes_key, key);
+            report.record(format!("registry key '{}'", full_key), run_tool("reg", &["delete", &full_key, "/f"]));
+
+            // file extensions keep pointing at a deleted ProgID unless their default value is removed too
+            if key.contains('\\') {
+                continue;
+            }
+            let output = query_tool("reg", &["query", classes_key, "/f", key.as_str(), "/d", "/e", "/s"]);
+            for value in parse_reg_query(&output) {
+                let is_extension = get_relative_key(&value.key, classes_key).map(|k| k.starts_with('.') && !k.contains('\\'));
+                if value.name == "(Default)" && is_extension == Some(true) {
+                    let item = format!("file association '{}'", value.key);
+                    report.record(item, run_tool("reg", &["delete", &value.key, "/ve", "/f"]));
+                }
+            }
+        }
+    }
+}
+
+/// Removes scheduled tasks which run something inside the app directory.
+pub fn remove_scheduled_tasks(root_dir: &Path, report: &mut CleanupReport) {
+    let output = query_tool("schtasks", &["/query", "/fo", "csv", "/v", "/nh"]);
+    let mut tasks = BTreeSet::new();
+    for line in output.lines() {
+        let fields = parse_csv_line(line);
+        // the columns are always in the same order, even though their headers are localized
+        if let (Some(name), Some(action)) = (fields.get(1), fields.get(8)) {
+            if refers_to_dir(action, root_dir) {
+                tasks.insert(name.clone());
+            }
+        }
+    }
+    for task in tasks {
+        report.record(format!("scheduled task '{}'", task), run_tool("schtasks", &["/delete", "/tn", &task, "/f"]));
+    }
+}
+
+/// Stops and deletes services whose executable is inside the app directory, and which are named after the app (see
+/// [is_owned_name]). Other services which run from the app directory are reported as left behind, rather than deleted.
+pub fn remove_services(root_dir: &Path, owned_names: &[String], report: &mut CleanupReport) {
+    let output = query_tool("reg", &["query", SERVICES_KEY, "/s", "/v", "ImagePath"]);
+    for value in parse_reg_query(&output) {
+        if !refers_to_dir(&value.data, root_dir) {
+            continue;
+        }
+        let service = match value.key.rsplit('\\').next() {
+            Some(service) if !service.is_empty() => service.to_string(),
+            _ => continue,
+        };
+        if !is_owned_name(&service, owned_names) {
+            report.failed(format!("service '{}'", service), "it was not registered by the app, so it was left in place");
+            continue;
+        }
+        let _ = run_tool("sc.exe", &["stop", &service]);
+        report.record(format!("service '{}'", service), run_tool("sc.exe", &["delete", &service]));
+    }
+}
+
+/// Removes file operations which were queued to happen at the next reboot (eg. because a file was locked when it was
+/// deleted), and which refer to the app directory, so they do not clobber a later install of the app.
+pub fn remove_pending_renames(root_dir: &Path, report: &mut CleanupReport) {
+    let entries = match read_pending_renames() {
+        Ok(Some(entries)) => entries,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Unable to read pending file rename operations ({}).", e);
+            return;
+        }
+    };
+
+    let (remaining, removed) = remove_pending_renames_in_dir(&entries, root_dir);
+    if removed > 0 {
+        report.record(format!("{} pending file rename operation(s)", removed), write_pending_renames(&remaining));
+    }
+}
+
+/// Returns the parent directories of the app directory which should be removed if they are left empty after the app
+/// directory is deleted. Well known folders (eg. Program Files) and the folders above them are never included.
+pub fn get_parent_dirs_to_remove(root_dir: &Path, protected_dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let normalize = |p: &Path| p.to_string_lossy().trim_end_matches('\\').to_lowercase();
+    let protected: Vec<String> = protected_dirs.iter().map(|p| normalize(p)).collect();
+    let mut dirs = Vec::new();
+    for dir in root_dir.ancestors().skip(1) {
+        let dir_str = normalize(dir);
+        if dir.parent().is_none() || dir_str.is_empty() {
+            break;
+        }
+        if protected.iter().any(|p| *p == dir_str || p.starts_with(&format!("{}\\", dir_str))) {
+            break;
+        }
+        dirs.push(dir.to_path_buf());
+    }
+    dirs
+}
+
+/// Runs a tool without a console window, and returns its output, or an error containing its output if it failed.
+fn run_tool(program: &str, args: &[&str]) -> Result<String> {
+    debug!("Running: {} {}", program, args.join(" "));
+    let output = Process::new(program).args(args).creation_flags(CREATE_NO_WINDOW).output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("{} exited with {}: {}", program, output.status, format!("{}{}", stderr, stdout).trim());
+    }
+    Ok(stdout)
+}
+
+/// Runs a query, where a failure (eg. no matches, or a key which does not exist) means there is nothing to clean up.
+fn query_tool(program: &str, args: &[&str]) -> String {
+    match run_tool(program, args) {
+        Ok(output) => output,
+        Err(e) => {
+            debug!("Query returned no results ({}).", e);
+            String::new()
+        }
+    }
+}
+
+/// One value printed by 'reg query', along with the key it belongs to.
+#[derive(Debug, PartialEq, Eq)]
+struct RegValue {
+    key: String,
+    name: String,
+    data: String,
+}
+
+/// Parses the output of 'reg query', which prints a line per key, followed by an indented line per value.
+fn parse_reg_query(output: &str) -> Vec<RegValue> {
+    let mut key = String::new();
+    let mut values = Vec::new();
+    for line in output.lines().map(|l| l.trim_end()) {
+        if line.starts_with("HKEY_") {
+            key = line.to_string();
+        } else if let Some(line) = line.strip_prefix("    ") {
+            if let Some(type_index) = line.find("    REG_") {
+                let data = line[type_index + 4..].split_once("    ").map(|(_, data)| data).unwrap_or("");
+                values.push(RegValue { key: key.clone(), name: line[..type_index].to_string(), data: data.to_string() });
+            }
+        }
+    }
+    values
+}
+
+/// Returns the path of a key printed by 'reg query' (eg. HKEY_CURRENT_USER\Software\Classes\x) relative to a parent key
+/// in its abbreviated form (eg. HKCU\Software\Classes).
+fn get_relative_key(key: &str, parent: &str) -> Option<String> {
+    let (parent_hive, parent_path) = parent.split_once('\\')?;
+    let (hive, path) = key.split_once('\\')?;
+    let hive_matches = match parent_hive {
+        "HKCU" => hive == "HKEY_CURRENT_USER",
+        "HKLM" => hive == "HKEY_LOCAL_MACHINE",
+        _ => hive.eq_ignore_ascii_case(parent_hive),
+    };
+    let path_lower = path.to_lowercase();
+    let prefix = format!("{}\\", parent_path.to_lowercase());
+    if !hive_matches || !path_lower.starts_with(&prefix) {
+        return None;
+    }
+    Some(path[prefix.len()..].to_string())
+}
+
+/// Returns true if a ProgID, AppUserModelId or service name belongs to the app, because it is one of the owned names
+/// (the app id and its AppUserModelId) or starts with one followed by a dot (eg. MyApp.Document).
+pub fn is_owned_name(name: &str, owned_names: &[String]) -> bool {
+    let name = name.to_lowercase();
+    owned_names.iter().map(|o| o.to_lowercase()).filter(|o| !o.is_empty()).any(|o| name == o || name.starts_with(&format!("{}.", o)))
+}
+
+/// Given a key under Software\Classes which refers to the app, returns the key which should be deleted, or None if the
+/// key is not safe to delete because other apps depend on it, or it was not registered by the app.
+fn get_class_key_to_remove(relative_key: &str, owned_names: &[String]) -> Option<String> {
+    let parts: Vec<&str> = relative_key.split('\\').filter(|p| !p.is_empty()).collect();
+    let first = *parts.first()?;
+    if first.eq_ignore_ascii_case("WOW6432Node") {
+        return get_class_key_to_remove(&parts[1..].join("\\"), owned_names).map(|k| format!("{}\\{}", first, k));
+    }
+    if first.eq_ignore_ascii_case(AMUID_CONTAINER) {
+        return parts.get(1).filter(|amuid| is_owned_name(amuid, owned_names)).map(|amuid| format!("{}\\{}", first, amuid));
+    }
+    if CLASS_CONTAINERS.iter().any(|c| c.eq_ignore_ascii_case(first)) {
+        return if parts.len() >= 2 { Some(parts[..2].join("\\")) } else { None };
+    }
+    if first.starts_with('.') || SHARED_CLASSES.iter().any(|c| c.eq_ignore_ascii_case(first)) {
+        let shell = parts.iter().position(|p| p.eq_ignore_ascii_case("shell"))?;
+        return if parts.len() > shell + 1 { Some(parts[..shell + 2].join("\\")) } else { None };
+    }
+    if is_owned_name(first, owned_names) {
+        Some(first.to_string())
+    } else {
+        None
+    }
+}
+
+/// Returns true if a registry value or command line refers to a file inside the specified directory.
+fn refers_to_dir(data: &str, dir: &Path) -> bool {
+    let dir = dir.to_string_lossy().trim_end_matches('\\').to_lowercase();
+    if dir.is_empty() {
+        return false;
+    }
+    let data = data.to_lowercase();
+    data.contains(&format!("{}\\", dir)) || data.trim_matches('"') == dir
+}
+
+/// Parses a line of the quoted CSV written by schtasks.
+fn parse_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// Splits a REG_MULTI_SZ value into its strings, keeping empty strings, which are meaningful in pending renames.
+fn split_multi_sz(data: &[u16]) -> Vec<String> {
+    let data = data.strip_suffix(&[0]).unwrap_or(data);
+    let mut strings: Vec<String> = data.split(|c| *c == 0).map(String::from_utf16_lossy).collect();
+    if strings.last().map(|s| s.is_empty()).unwrap_or(false) {
+        strings.pop();
+    }
+    strings
+}
+
+/// Joins strings into a REG_MULTI_SZ value.
+fn join_multi_sz(strings: &[String]) -> Vec<u16> {
+    let mut data = Vec::new();
+    for s in strings {
+        data.extend(s.encode_utf16());
+        data.push(0);
+    }
+    data.push(0);
+    data
+}
+
+/// Pending renames are pairs of a source and a target (which is empty for a delete). Returns the pairs which do not
+/// refer to the specified directory, and the number of pairs which were removed.
+fn remove_pending_renames_in_dir(entries: &[String], dir: &Path) -> (Vec<String>, usize) {
+    let mut remaining = Vec::new();
+    let mut removed = 0;
+    for pair in entries.chunks(2) {
+        if pair.iter().any(|p| refers_to_dir(p, dir)) {
+            removed += 1;
+        } else {
+            remaining.extend(pair.iter().cloned());
+        }
+    }
+    (remaining, removed)
+}
+
+fn open_session_manager_key(access: REG_SAM_FLAGS) -> Result<Option<HKEY>> {
+    let subkey = string_to_u16(SESSION_MANAGER_KEY);
+    let mut key = HKEY::default();
+    let err = unsafe { RegOpenKeyExW(HKEY_LOCAL_MACHINE, PCWSTR(subkey.as_ptr()), 0, access, &mut key) };
+    if err == ERROR_FILE_NOT_FOUND {
+        return Ok(None);
+    }
+    err.ok()?;
+    Ok(Some(key))
+}
+
+fn read_pending_renames() -> Result<Option<Vec<String>>> {
+    let key = match open_session_manager_key(KEY_QUERY_VALUE)? {
+        Some(key) => key,
+        None => return Ok(None),
+    };
+    let result = query_multi_sz_value(key, PENDING_RENAMES_VALUE);
+    unsafe {
+        let _ = RegCloseKey(key);
+    }
+    result
+}
+
+fn query_multi_sz_value(key: HKEY, value_name: &str) -> Result<Option<Vec<String>>> {
+    let name = string_to_u16(value_name);
+    let mut size = 0u32;
+    let err = unsafe { RegQueryValueExW(key, PCWSTR(name.as_ptr()), None, None, None, Some(&mut size)) };
+    if err == ERROR_FILE_NOT_FOUND {
+        return Ok(None);
+    }
+    err.ok()?;
+
+    let mut buffer = vec![0u8; size as usize];
+    unsafe { RegQueryValueExW(key, PCWSTR(name.as_ptr()), None, None, Some(buffer.as_mut_ptr()), Some(&mut size)) }.ok()?;
+    let data: Vec<u16> = buffer[..size as usize].chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+    Ok(Some(split_multi_sz(&data)))
+}
+
+fn write_pending_renames(entries: &[String]) -> Result<()> {
+    let key = match open_session_manager_key(KEY_SET_VALUE)? {
+        Some(key) => key,
+        None => return Ok(()),
+    };
+    let name = string_to_u16(PENDING_RENAMES_VALUE);
+    let bytes: Vec<u8> = join_multi_sz(entries).iter().flat_map(|c| c.to_le_bytes()).collect();
+    let result = unsafe { RegSetValueExW(key, PCWSTR(name.as_ptr()), 0, REG_MULTI_SZ, Some(&bytes)) };
+    unsafe {
+        let _ = RegCloseKey(key);
+    }
+    result.ok()?;
+    Ok(())
+}
+
+#[test]
+fn test_cleanup_parses_tool_output_and_only_targets_the_app() {
+    let root = Path::new("C:\\Users\\me\\AppData\\Local\\MyApp");
+    let output = concat!(
+        "\r\nHKEY_CURRENT_USER\\Software\\Microsoft\\Windows\\CurrentVersion\\Run\r\n",
+        "    OneDrive    REG_SZ    \"C:\\Program Files\\OneDrive.exe\" /background\r\n",
+        "    MyApp    REG_SZ    \"C:\\Users\\me\\AppData\\Local\\MyApp\\current\\MyApp.exe\"\r\n\r\n"
+    );
+    let values = parse_reg_query(output);
+    assert_eq!(values.len(), 2);
+    assert_eq!(values[1].key, "HKEY_CURRENT_USER\\Software\\Microsoft\\Windows\\CurrentVersion\\Run");
+    assert_eq!(values[1].name, "MyApp");
+    assert!(!refers_to_dir(&values[0].data, root));
+    assert!(refers_to_dir(&values[1].data, root));
+    assert!(!refers_to_dir("C:\\Users\\me\\AppData\\Local\\MyApp2\\MyApp2.exe", root));
+
+    let classes = "HKCU\\Software\\Classes";
+    let key = get_relative_key("HKEY_CURRENT_USER\\Software\\Classes\\myapp\\shell\\open\\command", classes).unwrap();
+    assert_eq!(key, "myapp\\shell\\open\\command");
+    assert_eq!(get_relative_key("HKEY_LOCAL_MACHINE\\Software\\Classes\\myapp", classes), None);
+    let owned = vec!["MyApp".to_string(), "com.squirrel.MyApp.MyApp".to_string()];
+    assert_eq!(get_class_key_to_remove(&key, &owned), Some("myapp".to_string()));
+    assert_eq!(get_class_key_to_remove("MyApp.Document\\shell\\open\\command", &owned), Some("MyApp.Document".to_string()));
+    assert_eq!(get_class_key_to_remove("OtherApp\\shell\\open\\command", &owned), None);
+    assert_eq!(get_class_key_to_remove("MyApp2\\shell\\open\\command", &owned), None);
+    assert_eq!(get_class_key_to_remove("Directory\\shell\\MyAppOpen\\command", &owned), Some("Directory\\shell\\MyAppOpen".to_string()));
+    assert_eq!(get_class_key_to_remove(".txt\\shell\\edit\\command", &owned), Some(".txt\\shell\\edit".to_string()));
+    assert_eq!(get_class_key_to_remove("CLSID\\{1234}\\LocalServer32", &owned), Some("CLSID\\{1234}".to_string()));
+    let wow = get_class_key_to_remove("WOW6432Node\\CLSID\\{1234}\\InprocServer32", &owned);
+    assert_eq!(wow, Some("WOW6432Node\\CLSID\\{1234}".to_string()));
+    let amuid = get_class_key_to_remove("AppUserModelId\\com.squirrel.MyApp.MyApp\\Icon", &owned);
+    assert_eq!(amuid, Some("AppUserModelId\\com.squirrel.MyApp.MyApp".to_string()));
+    assert_eq!(get_class_key_to_remove("AppUserModelId\\Microsoft.Windows.Explorer", &owned), None);
+    assert_eq!(get_class_key_to_remove("AppUserModelId", &owned), None);
+    assert_eq!(get_class_key_to_remove("Extensions\\ContractId\\Windows.Protocol\\PackageId", &owned), None);
+    assert_eq!(get_class_key_to_remove("PackagedCom\\ClassIndex\\{1234}", &owned), None);
+    assert_eq!(get_class_key_to_remove("ActivatableClasses\\Package\\MyApp", &owned), None);
+    assert_eq!(get_class_key_to_remove("MIME\\Database\\Content Type\\text/x-myapp", &owned), None);
+    assert_eq!(get_class_key_to_remove("Directory\\DefaultIcon", &owned), None);
+    assert_eq!(get_class_key_to_remove("Local Settings\\Software\\Microsoft", &owned), None);
+    assert!(is_owned_name("myapp.updater", &owned) && !is_owned_name("MyAppUpdater", &owned) && !is_owned_name("", &owned));
+
+    let csv = concat!(
+        "\"PC\",\"\\MyApp Update\",\"N/A\",\"Ready\",\"Interactive\",\"N/A\",\"0\",\"me\",",
+        "\"\"\"C:\\Users\\me\\AppData\\Local\\MyApp\\Update.exe\"\" --check\""
+    );
+    let fields = parse_csv_line(csv);
+    assert_eq!(fields[1], "\\MyApp Update");
+    assert_eq!(fields[8], "\"C:\\Users\\me\\AppData\\Local\\MyApp\\Update.exe\" --check");
+
+    let entries: Vec<String> =
+        ["\\??\\C:\\Temp\\a.tmp", "", "\\??\\C:\\Users\\me\\AppData\\Local\\MyApp\\x.dll", "", "\\??\\C:\\b", "\\??\\C:\\c"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+    assert_eq!(split_multi_sz(&join_multi_sz(&entries)), entries);
+    let (remaining, removed) = remove_pending_renames_in_dir(&entries, root);
+    assert_eq!(removed, 1);
+    assert_eq!(remaining, vec!["\\??\\C:\\Temp\\a.tmp", "", "\\??\\C:\\b", "\\??\\C:\\c"]);
+
+    let protected = vec![PathBuf::from("C:\\Users\\me\\AppData\\Local"), PathBuf::from("C:\\Program Files")];
+    assert!(get_parent_dirs_to_remove(root, &protected).is_empty());
+    let custom = Path::new("D:\\Tools\\Vendor\\MyApp");
+    assert_eq!(get_parent_dirs_to_remove(custom, &protected), vec![PathBuf::from("D:\\Tools\\Vendor"), PathBuf::from("D:\\Tools")]);
+    let nested = Path::new("C:\\Program Files\\Vendor\\MyApp");
+    assert_eq!(get_parent_dirs_to_remove(nested, &protected), vec![PathBuf::from("C:\\Program Files\\Vendor")]);
+    assert!(get_parent_dirs_to_remove(Path::new("C:\\Users\\me\\MyApp"), &protected).is_empty());
+}
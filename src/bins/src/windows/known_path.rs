@@ -8,7 +8,10 @@ use windows::{
     },
 };
 
-fn get_known_folder(rfid: *const GUID) -> Result<String> {
+fn get_known_folder(rfid: *const GUID, name: &str) -> Result<String> {
+    if let Some(dir) = super::sandbox::get_sandbox_known_folder(name) {
+        return dir;
+    }
     unsafe {
         let flag = windows::Win32::UI::Shell::KNOWN_FOLDER_FLAG(0);
         let result = SHGetKnownFolderPath(rfid, flag, None)?;
@@ -17,41 +20,41 @@ fn get_known_folder(rfid: *const GUID) -> Result<String> {
 }
 
 pub fn get_local_app_data() -> Result<String> {
-    get_known_folder(&FOLDERID_LocalAppData)
+    get_known_folder(&FOLDERID_LocalAppData, "LocalAppData")
 }
 
 pub fn get_roaming_app_data() -> Result<String> {
-    get_known_folder(&FOLDERID_RoamingAppData)
+    get_known_folder(&FOLDERID_RoamingAppData, "RoamingAppData")
 }
 
 pub fn get_user_desktop() -> Result<String> {
-    get_known_folder(&FOLDERID_Desktop)
+    get_known_folder(&FOLDERID_Desktop, "Desktop")
 }
 
 pub fn get_user_profile() -> Result<String> {
-    get_known_folder(&FOLDERID_Profile)
+    get_known_folder(&FOLDERID_Profile, "Profile")
 }
 
 pub fn get_start_menu() -> Result<String> {
-    let start_menu = get_known_folder(&FOLDERID_StartMenu)?;
+    let start_menu = get_known_folder(&FOLDERID_StartMenu, "StartMenu")?;
     let programs_path = Path::new(&start_menu).join("Programs");
     Ok(programs_path.to_string_lossy().to_string())
 }
 
 pub fn get_startup() -> Result<String> {
-    get_known_folder(&FOLDERID_Startup)
+    get_known_folder(&FOLDERID_Startup, "Startup")
 }
 
 pub fn get_downloads() -> Result<String> {
-    get_known_folder(&FOLDERID_Downloads)
+    get_known_folder(&FOLDERID_Downloads, "Downloads")
 }
 
 pub fn get_program_files_x64() -> Result<String> {
-    get_known_folder(&FOLDERID_ProgramFilesX64)
+    get_known_folder(&FOLDERID_ProgramFilesX64, "ProgramFilesX64")
 }
 
 pub fn get_program_files_x86() -> Result<String> {
-    get_known_folder(&FOLDERID_ProgramFilesX86)
+    get_known_folder(&FOLDERID_ProgramFilesX86, "ProgramFilesX86")
 }
 
 pub fn get_user_pinned() -> Result<String> {
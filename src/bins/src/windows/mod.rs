@@ -6,6 +6,8 @@ pub mod splash;
 pub mod known_path;
 pub mod strings;
 pub mod registry;
+pub mod cleanup;
+pub mod sandbox;
 
 mod self_delete;
 mod shortcuts;
@@ -5,7 +5,14 @@ use velopack::{bundle, download};
 use anyhow::Result;
 use std::path::Path;
 
-pub fn prompt_and_install_all_missing(app: &bundle::Manifest, updating_from: Option<&semver::Version>) -> Result<bool> {
+#[derive(PartialEq, Debug)]
+pub enum PrerequisiteResult {
+    Ready,
+    Cancelled,
+    RestartRequired,
+}
+
+pub fn prompt_and_install_all_missing(app: &bundle::Manifest, updating_from: Option<&semver::Version>) -> Result<PrerequisiteResult> {
     info!("Checking application pre-requisites...");
     let dependencies = super::runtimes::parse_dependency_list(&app.runtime_dependencies);
     let mut missing: Vec<&Box<dyn runtimes::RuntimeInfo>> = Vec::new();
@@ -25,16 +32,23 @@ pub fn prompt_and_install_all_missing(app: &bundle::Manifest, updating_from: Opt
         missing_str += dep.display_name();
     }
 
+    if !missing.is_empty() && super::sandbox::get_sandbox_dir().is_some() {
+        warn!("The sandbox is enabled, so the missing pre-requisites will not be installed ({}).", missing_str);
+        let names: Vec<&str> = missing.iter().map(|d| d.display_name()).collect();
+        super::sandbox::record_missing_prerequisites(&names)?;
+        return Ok(PrerequisiteResult::Ready);
+    }
+
     if !missing.is_empty() {
         if let Some(from_version) = updating_from {
             if !dialogs::show_update_missing_dependencies_dialog(&app, &missing_str, &from_version, &app.version) {
                 error!("User cancelled pre-requisite installation.");
-                return Ok(false);
+                return Ok(PrerequisiteResult::Cancelled);
             }
         } else {
             if !dialogs::show_setup_missing_dependencies_dialog(&app, &missing_str) {
                 error!("User cancelled pre-requisite installation.");
-                return Ok(false);
+                return Ok(PrerequisiteResult::Cancelled);
             }
         }
 
@@ -68,10 +82,10 @@ pub fn prompt_and_install_all_missing(app: &bundle::Manifest, updating_from: Opt
             if result == runtimes::RuntimeInstallResult::RestartRequired {
                 warn!("A restart is required to complete the installation of {}.", dep.display_name());
                 dialogs::show_restart_required(&app);
-                return Ok(false);
+                return Ok(PrerequisiteResult::RestartRequired);
             }
         }
     }
 
-    Ok(true)
+    Ok(PrerequisiteResult::Ready)
 }
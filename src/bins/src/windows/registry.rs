@@ -1,14 +1,59 @@
 use anyhow::Result;
 use chrono::{Datelike, Local as DateTime};
+use std::{os::windows::process::CommandExt, process::Command as Process};
 use velopack::locator::VelopackLocator;
 use winsafe::{self as w, co, prelude::*};
 
 const UNINSTALL_REGISTRY_KEY: &'static str = "Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall";
 
+/// Whether an uninstall entry is shown to the current user only (HKCU), or to every user of the machine (HKLM).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegistryScope {
+    User,
+    Machine,
+}
+
+impl RegistryScope {
+    fn hkey(&self) -> w::HKEY {
+        match self {
+            RegistryScope::User => w::HKEY::CURRENT_USER,
+            RegistryScope::Machine => w::HKEY::LOCAL_MACHINE,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            RegistryScope::User => "HKEY_CURRENT_USER",
+            RegistryScope::Machine => "HKEY_LOCAL_MACHINE",
+        }
+    }
+}
+
+/// Returns the scope the uninstall entry of the app is currently written to. Entries are per-user unless the app was
+/// migrated to a per-machine install, which moves its entry to HKLM.
+pub fn get_uninstall_entry_scope(locator: &VelopackLocator) -> RegistryScope {
+    if super::sandbox::get_sandbox_dir().is_some() {
+        let key = format!("{}\\{}\\{}", RegistryScope::Machine.name(), UNINSTALL_REGISTRY_KEY, locator.get_install_id());
+        return if super::sandbox::registry_key_exists(&key) { RegistryScope::Machine } else { RegistryScope::User };
+    }
+
+    // reg.exe is used so this can be queried without the write access RegCreateKeyEx needs
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+    let key = format!("HKLM\\{}\\{}", UNINSTALL_REGISTRY_KEY, locator.get_install_id());
+    match Process::new("reg").args(["query", &key]).creation_flags(CREATE_NO_WINDOW).output() {
+        Ok(output) if output.status.success() => RegistryScope::Machine,
+        _ => RegistryScope::User,
+    }
+}
+
 pub fn write_uninstall_entry(locator: &VelopackLocator) -> Result<()> {
-    info!("Writing uninstall registry key...");
+    write_uninstall_entry_to(locator, get_uninstall_entry_scope(locator))
+}
+
+pub fn write_uninstall_entry_to(locator: &VelopackLocator, scope: RegistryScope) -> Result<()> {
+    info!("Writing uninstall registry key ({:?})...", scope);
 
-    let app_id = locator.get_manifest_id();
+    let app_id = locator.get_install_id();
     let app_title = locator.get_manifest_title();
     let app_authors = locator.get_manifest_authors();
 
@@ -25,29 +70,48 @@ pub fn write_uninstall_entry(locator: &VelopackLocator) -> Result<()> {
     let uninstall_cmd = format!("\"{}\" --uninstall", updater_path);
     let uninstall_quiet = format!("\"{}\" --uninstall --silent", updater_path);
 
+    let values = [
+        ("DisplayIcon", w::RegistryValue::Sz(main_exe_path)),
+        ("DisplayName", w::RegistryValue::Sz(app_title)),
+        ("DisplayVersion", w::RegistryValue::Sz(short_version)),
+        ("InstallDate", w::RegistryValue::Sz(formatted_date)),
+        ("InstallLocation", w::RegistryValue::Sz(root_path_str)),
+        ("Publisher", w::RegistryValue::Sz(app_authors)),
+        ("QuietUninstallString", w::RegistryValue::Sz(uninstall_quiet)),
+        ("UninstallString", w::RegistryValue::Sz(uninstall_cmd)),
+        ("EstimatedSize", w::RegistryValue::Dword((folder_size / 1024).try_into()?)),
+        ("NoModify", w::RegistryValue::Dword(1)),
+        ("NoRepair", w::RegistryValue::Dword(1)),
+        ("Language", w::RegistryValue::Dword(0x0409)),
+    ];
+
+    if super::sandbox::get_sandbox_dir().is_some() {
+        let key = format!("{}\\{}\\{}", scope.name(), UNINSTALL_REGISTRY_KEY, app_id);
+        return super::sandbox::write_registry_key(&key, &values);
+    }
+
     let reg_uninstall =
-        w::HKEY::CURRENT_USER.RegCreateKeyEx(UNINSTALL_REGISTRY_KEY, None, co::REG_OPTION::NoValue, co::KEY::CREATE_SUB_KEY, None)?.0;
+        scope.hkey().RegCreateKeyEx(UNINSTALL_REGISTRY_KEY, None, co::REG_OPTION::NoValue, co::KEY::CREATE_SUB_KEY, None)?.0;
     let reg_app = reg_uninstall.RegCreateKeyEx(&app_id, None, co::REG_OPTION::NoValue, co::KEY::ALL_ACCESS, None)?.0;
-    reg_app.RegSetKeyValue(None, Some("DisplayIcon"), w::RegistryValue::Sz(main_exe_path))?;
-    reg_app.RegSetKeyValue(None, Some("DisplayName"), w::RegistryValue::Sz(app_title))?;
-    reg_app.RegSetKeyValue(None, Some("DisplayVersion"), w::RegistryValue::Sz(short_version))?;
-    reg_app.RegSetKeyValue(None, Some("InstallDate"), w::RegistryValue::Sz(formatted_date))?;
-    reg_app.RegSetKeyValue(None, Some("InstallLocation"), w::RegistryValue::Sz(root_path_str))?;
-    reg_app.RegSetKeyValue(None, Some("Publisher"), w::RegistryValue::Sz(app_authors))?;
-    reg_app.RegSetKeyValue(None, Some("QuietUninstallString"), w::RegistryValue::Sz(uninstall_quiet))?;
-    reg_app.RegSetKeyValue(None, Some("UninstallString"), w::RegistryValue::Sz(uninstall_cmd))?;
-    reg_app.RegSetKeyValue(None, Some("EstimatedSize"), w::RegistryValue::Dword((folder_size / 1024).try_into()?))?;
-    reg_app.RegSetKeyValue(None, Some("NoModify"), w::RegistryValue::Dword(1))?;
-    reg_app.RegSetKeyValue(None, Some("NoRepair"), w::RegistryValue::Dword(1))?;
-    reg_app.RegSetKeyValue(None, Some("Language"), w::RegistryValue::Dword(0x0409))?;
+    for (name, value) in values {
+        reg_app.RegSetKeyValue(None, Some(name), value)?;
+    }
     Ok(())
 }
 
 pub fn remove_uninstall_entry(locator: &VelopackLocator) -> Result<()> {
-    info!("Removing uninstall registry keys...");
-    let app_id = locator.get_manifest_id();
+    remove_uninstall_entry_from(locator, get_uninstall_entry_scope(locator))
+}
+
+pub fn remove_uninstall_entry_from(locator: &VelopackLocator, scope: RegistryScope) -> Result<()> {
+    info!("Removing uninstall registry keys ({:?})...", scope);
+    let app_id = locator.get_install_id();
+    if super::sandbox::get_sandbox_dir().is_some() {
+        return super::sandbox::remove_registry_key(&format!("{}\\{}\\{}", scope.name(), UNINSTALL_REGISTRY_KEY, app_id));
+    }
+
     let reg_uninstall =
-        w::HKEY::CURRENT_USER.RegCreateKeyEx(UNINSTALL_REGISTRY_KEY, None, co::REG_OPTION::NoValue, co::KEY::CREATE_SUB_KEY, None)?.0;
+        scope.hkey().RegCreateKeyEx(UNINSTALL_REGISTRY_KEY, None, co::REG_OPTION::NoValue, co::KEY::CREATE_SUB_KEY, None)?.0;
     reg_uninstall.RegDeleteKey(&app_id)?;
     Ok(())
 }
\ No newline at end of file
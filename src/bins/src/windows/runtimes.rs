@@ -410,6 +410,7 @@ impl RuntimeInfo for DotnetInfo {
             RuntimeArch::X86 => "x86",
             RuntimeArch::X64 => "x64",
             RuntimeArch::Arm64 => "arm64",
+            RuntimeArch::Arm => "arm",
         };
 
         let download_url = match self.runtime_type {
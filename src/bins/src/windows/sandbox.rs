@@ -0,0 +1,135 @@
+use anyhow::{anyhow, bail, Result};
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+use velopack::constants::SANDBOX_ENV;
+use winsafe as w;
+
+const REGISTRY_FILE: &str = "registry.reg";
+const PREREQUISITES_FILE: &str = "prerequisites.txt";
+const REGISTRY_HEADER: &str = "Windows Registry Editor Version 5.00";
+
+/// Enables the sandbox, where the install root, known folders (and therefore shortcuts), and registry writes are
+/// redirected into the specified directory, so install / update / uninstall can be tested without changing the machine.
+pub fn set_sandbox_dir(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let dir = dir.canonicalize()?;
+    // canonicalize returns a \\?\ path, which some of the tools we start do not understand
+    let dir = PathBuf::from(dir.to_string_lossy().trim_start_matches("\\\\?\\"));
+    env::set_var(SANDBOX_ENV, &dir);
+    Ok(())
+}
+
+/// Returns the sandbox directory, or None if the sandbox is not enabled. The sandbox is passed to child processes
+/// (eg. Update.exe started by the installed app) through the environment.
+pub fn get_sandbox_dir() -> Option<PathBuf> {
+    env::var_os(SANDBOX_ENV).filter(|d| !d.is_empty()).map(PathBuf::from)
+}
+
+/// Returns the directory a known folder (eg. LocalAppData) is redirected to in the sandbox, if it is enabled.
+pub fn get_sandbox_known_folder(name: &str) -> Option<Result<String>> {
+    let dir = get_sandbox_dir()?.join("folders").join(name);
+    Some(fs::create_dir_all(&dir).map(|_| dir.to_string_lossy().to_string()).map_err(|e| e.into()))
+}
+
+/// Records the prerequisites which would have been installed, instead of installing them, because runtime installers
+/// change the machine and can not be redirected into the sandbox.
+pub fn record_missing_prerequisites(names: &[&str]) -> Result<()> {
+    let dir = get_sandbox_dir().ok_or_else(|| anyhow!("The sandbox is not enabled."))?;
+    fs::write(dir.join(PREREQUISITES_FILE), names.iter().map(|n| format!("{}\r\n", n)).collect::<String>())?;
+    Ok(())
+}
+
+/// Writes the values of a registry key (eg. HKEY_CURRENT_USER\Software\x) to the sandbox registry file, replacing
+/// the values which are already there.
+pub fn write_registry_key(key: &str, values: &[(&str, w::RegistryValue)]) -> Result<()> {
+    let path = get_registry_path()?;
+    let mut registry = parse_registry_file(&fs::read_to_string(&path).unwrap_or_default());
+    let entry = registry.entry(key.to_string()).or_default();
+    for (name, value) in values {
+        match format_registry_value(value) {
+            Some(value) => {
+                entry.insert(name.to_string(), value);
+            }
+            None => warn!("The type of registry value '{}' is not supported in the sandbox, it will not be written.", name),
+        }
+    }
+    fs::write(path, write_registry_file(&registry))?;
+    Ok(())
+}
+
+/// Removes a registry key from the sandbox registry file.
+pub fn remove_registry_key(key: &str) -> Result<()> {
+    let path = get_registry_path()?;
+    let mut registry = parse_registry_file(&fs::read_to_string(&path).unwrap_or_default());
+    if registry.remove(key).is_none() {
+        bail!("The registry key '{}' does not exist in the sandbox.", key);
+    }
+    fs::write(path, write_registry_file(&registry))?;
+    Ok(())
+}
+
+/// Returns true if a registry key exists in the sandbox registry file.
+pub fn registry_key_exists(key: &str) -> bool {
+    get_registry_path().and_then(|p| Ok(fs::read_to_string(p)?)).map(|r| parse_registry_file(&r).contains_key(key)).unwrap_or(false)
+}
+
+fn get_registry_path() -> Result<PathBuf> {
+    let dir = get_sandbox_dir().ok_or_else(|| anyhow!("The sandbox is not enabled."))?;
+    Ok(dir.join(REGISTRY_FILE))
+}
+
+fn format_registry_value(value: &w::RegistryValue) -> Option<String> {
+    match value {
+        w::RegistryValue::Sz(s) => Some(format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))),
+        w::RegistryValue::Dword(d) => Some(format!("dword:{:08x}", d)),
+        _ => None,
+    }
+}
+
+/// Parses a .reg file into its keys and their values. The values are kept as they are written in the file.
+fn parse_registry_file(contents: &str) -> BTreeMap<String, BTreeMap<String, String>> {
+    let mut registry: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+    let mut key = None;
+    for line in contents.lines().map(|l| l.trim()) {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            registry.entry(name.to_string()).or_default();
+            key = Some(name.to_string());
+        } else if let (Some(key), Some((name, value))) = (&key, line.strip_prefix('"').and_then(|l| l.split_once("\"="))) {
+            registry.entry(key.clone()).or_default().insert(name.to_string(), value.to_string());
+        }
+    }
+    registry
+}
+
+/// Writes keys and their values in the format of a .reg file, so the sandbox registry can be inspected, or imported.
+fn write_registry_file(registry: &BTreeMap<String, BTreeMap<String, String>>) -> String {
+    let mut contents = format!("{}\r\n", REGISTRY_HEADER);
+    for (key, values) in registry {
+        contents += &format!("\r\n[{}]\r\n", key);
+        for (name, value) in values {
+            contents += &format!("\"{}\"={}\r\n", name, value);
+        }
+    }
+    contents
+}
+
+#[test]
+fn test_sandbox_registry_file_round_trips_values() {
+    let key = "HKEY_CURRENT_USER\\Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\MyApp";
+    let mut registry = BTreeMap::new();
+    let mut values = BTreeMap::new();
+    values.insert("DisplayName".to_string(), format_registry_value(&w::RegistryValue::Sz("My \"App\"".to_string())).unwrap());
+    values.insert("InstallLocation".to_string(), format_registry_value(&w::RegistryValue::Sz("C:\\Apps\\MyApp".to_string())).unwrap());
+    values.insert("NoModify".to_string(), format_registry_value(&w::RegistryValue::Dword(1)).unwrap());
+    registry.insert(key.to_string(), values);
+
+    let contents = write_registry_file(&registry);
+    assert!(contents.starts_with(REGISTRY_HEADER));
+    assert!(contents.contains("\"DisplayName\"=\"My \\\"App\\\"\"\r\n"));
+    assert!(contents.contains("\"InstallLocation\"=\"C:\\\\Apps\\\\MyApp\"\r\n"));
+    assert!(contents.contains("\"NoModify\"=dword:00000001\r\n"));
+    assert_eq!(parse_registry_file(&contents), registry);
+}
@@ -1,13 +1,117 @@
+use crate::shared;
 use anyhow::Result;
-use std::{env, os::windows::process::CommandExt, path::Path, process::Command as Process};
+use std::{
+    env, fs,
+    os::windows::process::CommandExt,
+    path::{Path, PathBuf},
+    process::{self, Command as Process},
+};
+use windows::{
+    core::PCWSTR,
+    Win32::Storage::FileSystem::{MoveFileExW, MOVEFILE_DELAY_UNTIL_REBOOT},
+};
 
-pub fn register_intent_to_delete_self(delay_seconds: usize, current_directory: &Path) -> Result<()> {
-    info!("Deleting self...");
-    let my_self = env::current_exe()?.to_string_lossy().to_string();
-    let command = format!("choice /C Y /N /D Y /T {} & Del \"{}\"", delay_seconds, my_self);
-    info!("Running: cmd.exe /C {}", command);
+use super::strings::string_to_u16;
 
-    const CREATE_NO_WINDOW: u32 = 0x08000000;
-    Process::new("cmd.exe").arg("/C").raw_arg(command).current_dir(current_directory).creation_flags(CREATE_NO_WINDOW).spawn()?;
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+const CREATE_BREAKAWAY_FROM_JOB: u32 = 0x01000000;
+/// How many seconds the helper waits for this process to exit before it tries to delete it anyway.
+const HELPER_WAIT_SECONDS: usize = 60;
+/// How many times the helper retries deleting files which are still locked (eg. by an anti-virus scan).
+const HELPER_RETRIES: usize = 10;
+
+/// Deletes this executable once it has exited, followed by the app root directory, and then any of the parent
+/// directories which were left empty. Parent directories which still contain something are left alone.
+pub fn register_intent_to_delete_self_and_root(root_dir: &Path, parent_dirs: &[PathBuf]) -> Result<()> {
+    // a running executable can not delete itself, so the deletes are done by a script in %TEMP%, which waits for this
+    // process to exit, retries files which are still locked, and then deletes itself. if the script can not be started,
+    // the files are scheduled to be deleted on the next reboot instead, which only works with administrator privileges.
+    info!("Deleting self and root directory...");
+    let my_self = env::current_exe()?;
+    let script = get_delete_script(process::id(), &my_self, root_dir, parent_dirs);
+    let script_path = env::temp_dir().join(format!("velopack_delete_{}.cmd", shared::random_string(8)));
+
+    let spawned = fs::write(&script_path, script).and_then(|_| {
+        info!("Running delete helper: {}", script_path.to_string_lossy());
+        let mut command = Process::new("cmd.exe");
+        command.arg("/C").raw_arg(format!("\"{}\"", script_path.to_string_lossy())).current_dir(env::temp_dir());
+        // the helper must outlive this process, even if it was started in a job which is closed when it exits
+        command
+            .creation_flags(CREATE_NO_WINDOW | CREATE_BREAKAWAY_FROM_JOB)
+            .spawn()
+            .or_else(|_| command.creation_flags(CREATE_NO_WINDOW).spawn())
+    });
+
+    if let Err(e) = spawned {
+        warn!("Unable to start delete helper ({}), scheduling delete on reboot instead.", e);
+        let _ = fs::remove_file(&script_path);
+        schedule_delete_on_reboot(&my_self)?;
+        schedule_delete_on_reboot(root_dir)?;
+    }
+    Ok(())
+}
+
+fn get_delete_script(pid: u32, exe: &Path, root_dir: &Path, parent_dirs: &[PathBuf]) -> String {
+    // percent signs would be expanded as variables, even inside quotes
+    let quote = |p: &Path| format!("\"{}\"", p.to_string_lossy().replace('%', "%%"));
+    let exe = quote(exe);
+    let root_dir = quote(root_dir);
+
+    let mut lines = vec![
+        "@echo off".to_string(),
+        "chcp 65001 >nul".to_string(),
+        "set tries=0".to_string(),
+        ":wait".to_string(),
+        format!("tasklist /FI \"PID eq {}\" /NH | find \" {} \" >nul", pid, pid),
+        "if errorlevel 1 goto delete".to_string(),
+        "set /a tries+=1".to_string(),
+        format!("if %tries% geq {} goto delete", HELPER_WAIT_SECONDS),
+        "choice /C Y /N /D Y /T 1 >nul".to_string(),
+        "goto wait".to_string(),
+        ":delete".to_string(),
+        "set tries=0".to_string(),
+        ":retry".to_string(),
+        format!("del /f /q {} >nul 2>&1", exe),
+        format!("rmdir /s /q {} >nul 2>&1", root_dir),
+        format!("if not exist {} if not exist {} goto done", exe, root_dir),
+        "set /a tries+=1".to_string(),
+        format!("if %tries% geq {} goto done", HELPER_RETRIES),
+        "choice /C Y /N /D Y /T 1 >nul".to_string(),
+        "goto retry".to_string(),
+        ":done".to_string(),
+    ];
+    for dir in parent_dirs {
+        lines.push(format!("rmdir {} >nul 2>&1", quote(dir)));
+    }
+    // a script can delete itself if it is the very last thing it does
+    lines.push("(goto) 2>nul & del \"%~f0\"".to_string());
+    lines.join("\r\n") + "\r\n"
+}
+
+/// Schedules a file, or a directory and everything in it, to be deleted when the machine next restarts.
+fn schedule_delete_on_reboot(path: &Path) -> Result<()> {
+    if path.is_dir() {
+        for entry in fs::read_dir(path)? {
+            schedule_delete_on_reboot(&entry?.path())?;
+        }
+    }
+    info!("Scheduling delete on reboot: {}", path.to_string_lossy());
+    let path = string_to_u16(path.to_string_lossy());
+    unsafe { MoveFileExW(PCWSTR(path.as_ptr()), PCWSTR::null(), MOVEFILE_DELAY_UNTIL_REBOOT)? };
     Ok(())
 }
+
+#[test]
+fn test_delete_script_waits_for_exit_and_removes_itself() {
+    let exe = Path::new("C:\\Users\\me\\AppData\\Local\\100%App\\Update.exe");
+    let root = Path::new("C:\\Users\\me\\AppData\\Local\\100%App");
+    let parents = vec![PathBuf::from("D:\\Tools")];
+
+    let script = get_delete_script(1234, exe, root, &parents);
+    assert!(script.contains("tasklist /FI \"PID eq 1234\" /NH | find \" 1234 \" >nul"));
+    assert!(script.contains("del /f /q \"C:\\Users\\me\\AppData\\Local\\100%%App\\Update.exe\""));
+    assert!(script.ends_with("(goto) 2>nul & del \"%~f0\"\r\n"));
+    assert!(script.contains("rmdir /s /q \"C:\\Users\\me\\AppData\\Local\\100%%App\""));
+    assert!(script.contains("rmdir \"D:\\Tools\" >nul 2>&1"));
+    assert!(script.find(":done").unwrap() < script.find("rmdir \"D:\\Tools\"").unwrap());
+}
@@ -76,23 +76,13 @@ fn get_path_for_shortcut_location(app_id: &str, app_title: &str, app_author: &st
 }
 
 unsafe fn unsafe_update_app_manifest_lnks(next_app: &VelopackLocator, previous_app: Option<&VelopackLocator>) {
-    let next_locations = next_app.get_manifest_shortcut_locations();
-    let prev_locations = previous_app.map(|a| a.get_manifest_shortcut_locations()).unwrap_or(ShortcutLocationFlags::NONE);
-
-    info!("Shortcut Previous Locations: {:?} ({:?})", prev_locations, previous_app.map(|a| a.get_manifest_version_full_string()));
-    info!("Shortcut Next Locations: {:?} ({:?})", next_locations, next_app.get_manifest_version_full_string());
-
     // we must end with shortcuts which exist in the next app but not the previous app.
     // any shortcuts which exist in both are optional - they could have been deleted by the user,
     // and we do not want to re-create them.
-    let mut new_locations = next_locations - prev_locations;
+    let mut new_locations = crate::commands::log_new_shortcut_locations(next_app, previous_app);
 
-    if new_locations.contains(ShortcutLocationFlags::START_MENU_ROOT) && new_locations.contains(ShortcutLocationFlags::START_MENU) {
-        // if both start menu locations are specified, we prefer ROOT.
-        new_locations.remove(ShortcutLocationFlags::START_MENU);
-    }
     let root_path = next_app.get_root_dir();
-    let app_id = next_app.get_manifest_id();
+    let app_id = next_app.get_install_id();
     let app_title = next_app.get_manifest_title();
     let app_authors = next_app.get_manifest_authors();
     let app_model_id: Option<String> = next_app.get_manifest_shortcut_amuid();
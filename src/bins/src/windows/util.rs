@@ -5,30 +5,34 @@ use std::{
     time::Duration,
 };
 
-use velopack::locator::VelopackLocator;
+use velopack::{
+    instance::GlobalMutex,
+    locator::VelopackLocator,
+};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use normpath::PathExt;
 use wait_timeout::ChildExt;
 use windows::core::PCWSTR;
 use windows::Win32::Storage::FileSystem::GetLongPathNameW;
 use windows::Win32::System::SystemInformation::{VerSetConditionMask, VerifyVersionInfoW, OSVERSIONINFOEXW, VER_FLAGS};
 use windows::Win32::UI::WindowsAndMessaging::AllowSetForegroundWindow;
-use windows::Win32::{
-    Foundation::{self, GetLastError},
-    System::Threading::CreateMutexW,
-};
 
 use crate::shared::{self, runtime_arch::RuntimeArch};
 use crate::windows::strings::{string_to_u16, u16_to_string};
 
 pub fn run_hook(locator: &VelopackLocator, hook_name: &str, timeout_secs: u64) -> bool {
+    run_hook_with_args(locator, hook_name, &[], timeout_secs)
+}
+
+pub fn run_hook_with_args(locator: &VelopackLocator, hook_name: &str, extra_args: &[&str], timeout_secs: u64) -> bool {
     let sw = simple_stopwatch::Stopwatch::start_new();
     let root_dir = locator.get_root_dir();
     let current_path = locator.get_current_bin_dir();
     let main_exe_path = locator.get_main_exe_path();
     let ver_string = locator.get_manifest_version_full_string();
-    let args = vec![hook_name, &ver_string];
+    let mut args = vec![hook_name, &ver_string];
+    args.extend_from_slice(extra_args);
     let mut success = false;
 
     info!("Running {} hook...", hook_name);
@@ -66,30 +70,63 @@ pub fn run_hook(locator: &VelopackLocator, hook_name: &str, timeout_secs: u64) -
     success
 }
 
-pub struct MutexDropGuard {
-    mutex: Foundation::HANDLE,
+/// Returns true if the current process is running with administrator privileges.
+pub fn is_elevated() -> bool {
+    unsafe { windows::Win32::UI::Shell::IsUserAnAdmin().as_bool() }
 }
 
-impl Drop for MutexDropGuard {
-    fn drop(&mut self) {
-        unsafe {
-            Foundation::CloseHandle(self.mutex).ok();
-        }
+/// Runs the current executable again with administrator privileges, which shows a UAC prompt, and waits for it to exit.
+/// Returns an error if the user declined the prompt, or the elevated process failed.
+pub fn run_self_elevated_and_wait(args: &[&str]) -> Result<()> {
+    let exe = std::env::current_exe()?;
+    let script = get_start_elevated_script(&exe.to_string_lossy(), args);
+    info!("Running elevated: {}", script);
+
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+    let output = Process::new("powershell.exe")
+        .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("The elevated process failed with {} ({}).", output.status, stderr.trim());
     }
+    Ok(())
+}
+
+/// Start-Process joins the argument list with spaces, so every argument is wrapped in double quotes, inside a single
+/// quoted PowerShell string.
+fn get_start_elevated_script(exe: &str, args: &[&str]) -> String {
+    let quote = |s: &str| format!("'{}'", s.replace('\'', "''"));
+    let arg_list: Vec<String> = args.iter().map(|a| quote(&format!("\"{}\"", a))).collect();
+    format!(
+        "$p = Start-Process -FilePath {} -ArgumentList {} -Verb RunAs -Wait -PassThru; exit $p.ExitCode",
+        quote(exe),
+        arg_list.join(",")
+    )
 }
 
-pub fn create_global_mutex(app_id: &str) -> Result<MutexDropGuard> {
+#[test]
+pub fn test_start_elevated_script_quotes_arguments() {
+    let script = get_start_elevated_script(r"C:\O'Neil\Update.exe", &["migrate", r"C:\Program Files\App"]);
+    assert!(script.starts_with(r"$p = Start-Process -FilePath 'C:\O''Neil\Update.exe' -ArgumentList "));
+    assert!(script.contains(r#" '"migrate"','"C:\Program Files\App"' -Verb RunAs -Wait -PassThru;"#));
+}
+
+pub fn create_global_mutex(app_id: &str) -> Result<GlobalMutex> {
+    wait_for_global_mutex(app_id, Duration::ZERO)
+}
+
+pub fn wait_for_global_mutex(app_id: &str, timeout: Duration) -> Result<GlobalMutex> {
     let mutex_name = format!("velopack-{}", app_id);
     info!("Attempting to open global system mutex: '{}'", &mutex_name);
-    let encodedu16 = super::strings::string_to_u16(mutex_name);
-    let encoded = PCWSTR(encodedu16.as_ptr());
-    let mutex = unsafe { CreateMutexW(None, true, encoded) }?;
-    match unsafe { GetLastError() } {
-        Foundation::ERROR_SUCCESS => Ok(MutexDropGuard { mutex }),
-        Foundation::ERROR_ALREADY_EXISTS => {
-            Err(anyhow!("Another installer or updater for this application is running, quit that process and try again."))
-        }
-        err => Err(anyhow!("Unable to create global mutex. Error code {:?}", err)),
+    match GlobalMutex::acquire_with_timeout(&mutex_name, timeout) {
+        Ok(Some(mutex)) => Ok(mutex),
+        Ok(None) => match GlobalMutex::get_owner(&mutex_name) {
+            Some(owner) => Err(anyhow!("Another installer or updater for this application is running ({}), quit that process and try again.", owner)),
+            None => Err(anyhow!("Another installer or updater for this application is running, quit that process and try again.")),
+        },
+        Err(e) => Err(anyhow!("Unable to create global mutex. {}", e)),
     }
 }
 
@@ -273,9 +310,12 @@ const VER_MINORVERSION: VER_FLAGS = VER_FLAGS(0x0000001);
 const VER_MAJORVERSION: VER_FLAGS = VER_FLAGS(0x0000002);
 const VER_BUILDNUMBER: VER_FLAGS = VER_FLAGS(0x0000004);
 const VER_SERVICEPACKMAJOR: VER_FLAGS = VER_FLAGS(0x0000020);
+const VER_PRODUCT_TYPE: VER_FLAGS = VER_FLAGS(0x0000080);
+const VER_EQUAL: u8 = 1;
+const VER_NT_WORKSTATION: u8 = 1;
 
 fn is_os_version_or_greater_internal(major: u16, minor: u16, build: u16, service_pack: u16) -> bool {
-    let flags = VER_MAJORVERSION | VER_MINORVERSION | VER_SERVICEPACKMAJOR;
+    let flags = VER_MAJORVERSION | VER_MINORVERSION | VER_BUILDNUMBER | VER_SERVICEPACKMAJOR;
 
     unsafe {
         let mut mask: u64 = 0;
@@ -318,7 +358,7 @@ pub fn is_os_version_or_greater(version: &str) -> Result<bool> {
     }
 
     if major == 8 {
-        return Ok(if minor >= 1 { is_windows_8_or_greater() } else { is_windows_8_1_or_greater() });
+        return Ok(if minor >= 1 { is_windows_8_1_or_greater() } else { is_windows_8_or_greater() });
     }
 
     // https://en.wikipedia.org/wiki/List_of_Microsoft_Windows_versions
@@ -346,6 +386,76 @@ pub fn test_os_returns_true_for_everything_on_windows_11_and_below() {
     assert!(!is_os_version_or_greater("12").unwrap());
 }
 
+/// Returns true if this is Windows Server (or a domain controller), rather than a client edition of Windows.
+pub fn is_windows_server() -> bool {
+    unsafe {
+        let mask = VerSetConditionMask(0, VER_PRODUCT_TYPE, VER_EQUAL);
+        let mut osvi: OSVERSIONINFOEXW = Default::default();
+        osvi.wProductType = VER_NT_WORKSTATION;
+        VerifyVersionInfoW(&mut osvi, VER_PRODUCT_TYPE, mask).is_err()
+    }
+}
+
+/// Converts a Windows Server release (eg. "2019", "Server 2012 R2") or a version number (eg. "10.0.17763") to the
+/// major, minor and build number of its kernel.
+fn parse_server_version(version: &str) -> Result<(u32, u32, u32)> {
+    let name = version.to_ascii_lowercase().replace(' ', "");
+    let name = name.trim_start_matches("windows").trim_start_matches("server");
+    // https://en.wikipedia.org/wiki/List_of_Microsoft_Windows_versions#Server_versions
+    let known = match name {
+        "2008" => Some((6, 0, 0)),
+        "2008r2" => Some((6, 1, 0)),
+        "2012" => Some((6, 2, 0)),
+        "2012r2" => Some((6, 3, 0)),
+        "2016" => Some((10, 0, 14393)),
+        "2019" => Some((10, 0, 17763)),
+        "2022" => Some((10, 0, 20348)),
+        "2025" => Some((10, 0, 26100)),
+        _ => None,
+    };
+    if let Some(known) = known {
+        return Ok(known);
+    }
+    let (major, minor, build, _) = shared::parse_version(version)?;
+    if major > 10 {
+        bail!("Unknown Windows Server version: '{}'", version);
+    }
+    Ok((major, minor, build))
+}
+
+pub fn is_server_version_or_greater(version: &str) -> Result<bool> {
+    let (major, minor, build) = parse_server_version(version)?;
+    Ok(is_os_version_or_greater_internal(major.try_into()?, minor.try_into()?, build.try_into()?, 0))
+}
+
+/// Checks whether this OS meets the minimum versions in a package manifest, and returns an error describing the
+/// requirement if it does not. Windows Server is checked against the server minimum if there is one, otherwise the
+/// client minimum is compared with the server's kernel version (eg. Windows 10 1809 and Server 2019 are both 17763).
+pub fn check_os_version_supported(min_version: &str, min_server_version: &str) -> Result<()> {
+    if is_windows_server() {
+        if !min_server_version.is_empty() {
+            if !is_server_version_or_greater(min_server_version)? {
+                bail!("This application requires Windows Server {} or later.", min_server_version);
+            }
+        } else if !min_version.is_empty() && !is_os_version_or_greater(min_version)? {
+            bail!("This application requires Windows {} or later, and does not support this version of Windows Server.", min_version);
+        }
+    } else if !min_version.is_empty() && !is_os_version_or_greater(min_version)? {
+        bail!("This application requires Windows {} or later.", min_version);
+    }
+    Ok(())
+}
+
+#[test]
+pub fn test_parse_server_version_supports_release_names_and_numbers() {
+    assert_eq!(parse_server_version("2019").unwrap(), (10, 0, 17763));
+    assert_eq!(parse_server_version("Server 2012 R2").unwrap(), (6, 3, 0));
+    assert_eq!(parse_server_version("Windows Server 2022").unwrap(), (10, 0, 20348));
+    assert_eq!(parse_server_version("10.0.14393").unwrap(), (10, 0, 14393));
+    assert!(parse_server_version("2030").is_err());
+    assert!(parse_server_version("vista").is_err());
+}
+
 pub fn is_cpu_architecture_supported(architecture: &str) -> Result<bool> {
     let machine = RuntimeArch::from_current_system();
     if machine.is_none() {
@@ -66,7 +66,7 @@ pub fn test_install_apply_uninstall() {
     let locator = auto_locate_app_manifest(LocationContext::FromSpecifiedRootDir(tmp_buf.clone())).unwrap();
     assert_eq!(semver::Version::parse("1.0.15").unwrap(), locator.get_manifest_version());
 
-    commands::uninstall(&locator, false).unwrap();
+    commands::uninstall(&locator, false, None).unwrap();
     assert!(!tmp_buf.join("current").exists());
     assert!(tmp_buf.join(".dead").exists());
 
@@ -129,3 +129,26 @@ pub fn test_patch_apply() {
     fs::remove_file(&tmp_file).unwrap();
     assert_eq!(expected_sha1, tmp_sha1);
 } 
+
+
+#[test]
+pub fn test_update_prints_version_and_help() {
+    // a new updater is only trusted once it has printed its version and exited successfully
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_update")).arg("--version").output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    semver::Version::parse(stdout.split_whitespace().last().unwrap()).unwrap();
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_update")).arg("--help").output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Usage"));
+}
+
+#[cfg(unix)]
+#[test]
+pub fn test_update_rejects_sandbox_outside_windows() {
+    let dir = tempdir().unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_update")).arg("--sandbox").arg(dir.path()).arg("apply").output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("only supported on Windows"));
+}
@@ -1,19 +1,18 @@
+use std::path::Path;
+
 fn main() {
-    cxx_build::bridge("src/lib.rs")
-        .file("src/bridge.cc")
-        .warnings_into_errors(true)
-        .flag_if_supported("/std:c++17")
-        .flag_if_supported("/EHsc") // exception unwind handling
-        .flag_if_supported("-Wno-unused-function") // allow unused functions
-        .define("VELOPACK_LIBC_EXPORTS", Some("1"))
-        .std("c++17")
-        .compile("velopack_libc");
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let crate_dir = Path::new(&crate_dir);
 
-    println!("cargo:rerun-if-changed=include/Velopack.h");
-    println!("cargo:rerun-if-changed=src/lib.rs");
-    println!("cargo:rerun-if-changed=src/bridge.hpp");
-    println!("cargo:rerun-if-changed=src/bridge.cc");
+    // the C header is generated from the extern "C" functions and #[repr(C)] types, so it always matches the library
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).expect("Unable to read cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(crate_dir.join("src").join("lib.rs"))
+        .generate()
+        .expect("Unable to generate Velopack.h")
+        .write_to_file(crate_dir.join("include").join("Velopack.h"));
 
-    #[cfg(target_os = "windows")]
-    println!("cargo:rustc-link-arg=/WHOLEARCHIVE:velopack_libc.lib");
-}
\ No newline at end of file
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-changed=src");
+}
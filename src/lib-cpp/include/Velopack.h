@@ -1,70 +1,64 @@
 #ifndef VELOPACK_H
 #define VELOPACK_H
 
-#include <stddef.h>  // For size_t
-#include <stdbool.h> // For bool
-#include <stdint.h> // For uint64_t, uint32_t
+// This file is generated by cbindgen from the velopack_libc crate (src/lib-cpp), do not edit it by hand.
+// The C++ wrapper classes are in Velopack.hpp.
 
-#ifdef __cplusplus
-#include <string>
-#include <optional>
-#include <vector>
-#include <stdexcept>
-#endif
-
-#if !defined(_WIN32)
-#include <string.h>
-#endif
-
-#if defined(VELOPACK_LIBC_EXPORTS) && defined(_WIN32)
-#define VPKC_EXPORT __declspec(dllexport)
-#define VPKC_CALL __cdecl
-#pragma comment(linker, "/EXPORT:vpkc_new_update_manager")
-#pragma comment(linker, "/EXPORT:vpkc_get_current_version")
-#pragma comment(linker, "/EXPORT:vpkc_get_app_id")
-#pragma comment(linker, "/EXPORT:vpkc_is_portable")
-#pragma comment(linker, "/EXPORT:vpkc_update_pending_restart")
-#pragma comment(linker, "/EXPORT:vpkc_download_updates")
-#pragma comment(linker, "/EXPORT:vpkc_wait_exit_then_apply_update")
-#pragma comment(linker, "/EXPORT:vpkc_app_set_auto_apply_on_startup")
-#pragma comment(linker, "/EXPORT:vpkc_app_set_args")
-#pragma comment(linker, "/EXPORT:vpkc_app_set_locator")
-#pragma comment(linker, "/EXPORT:vpkc_app_set_hook_after_install")
-#pragma comment(linker, "/EXPORT:vpkc_app_set_hook_before_uninstall")
-#pragma comment(linker, "/EXPORT:vpkc_app_set_hook_before_update")
-#pragma comment(linker, "/EXPORT:vpkc_app_set_hook_after_update")
-#pragma comment(linker, "/EXPORT:vpkc_app_set_hook_first_run")
-#pragma comment(linker, "/EXPORT:vpkc_app_set_hook_restarted")
-#pragma comment(linker, "/EXPORT:vpkc_app_run")
-#pragma comment(linker, "/EXPORT:vpkc_get_last_error")
-#pragma comment(linker, "/EXPORT:vpkc_set_log")
-#pragma comment(linker, "/EXPORT:vpkc_free_update_manager")
-#pragma comment(linker, "/EXPORT:vpkc_free_update_info")
-#pragma comment(linker, "/EXPORT:vpkc_free_asset")
-#elif defined(VELOPACK_LIBC_EXPORTS) && !defined(_WIN32)
-#define VPKC_EXPORT __attribute__((visibility("default"))) __attribute__((used))
-#define VPKC_CALL
-#else
-#define VPKC_EXPORT
-#define VPKC_CALL
-#endif
+#include <stddef.h>
+#include <stdbool.h>
+#include <stdint.h>
 
-#ifdef __cplusplus
-extern "C" {
-#endif
-
-typedef void vpkc_update_manager_t;
-typedef void (*vpkc_progress_callback_t)(size_t progress);
-typedef void (*vpkc_log_callback_t)(const char* pszLevel, const char* pszMessage);
-typedef void (*vpkc_hook_callback_t)(const char* pszAppVersion);
+typedef enum {
+    VPKC_OK = 0,
+    VPKC_ERROR_GENERIC = 1,
+    VPKC_ERROR_INVALID_ARGUMENT = 2,
+    VPKC_ERROR_IO = 3,
+    VPKC_ERROR_NETWORK = 4,
+    VPKC_ERROR_NOT_INSTALLED = 5,
+    VPKC_ERROR_INVALID_DATA = 6,
+    VPKC_ERROR_CANCELLED = 7,
+    VPKC_ERROR_BUSY = 8,
+    VPKC_ERROR_HASH_MISMATCH = 9,
+    VPKC_ERROR_UNSUPPORTED = 10,
+    VPKC_ERROR_TRANSLOCATED = 11,
+    VPKC_ERROR_MANAGED_EXTERNALLY = 12,
+    VPKC_ERROR_LOCKED = 13,
+} vpkc_error_code_t;
 
 typedef enum {
     UPDATE_AVAILABLE = 0,
     NO_UPDATE_AVAILABLE = 1,
     UPDATE_ERROR = 2,
+    /**
+     * The update source could not be reached, and the release feed cached by the last successful update check did not
+     * have an update available either. This is only returned if UseCachedFeedWhenOffline is enabled.
+     */
+    NO_UPDATE_AVAILABLE_OFFLINE = 3,
 } vpkc_update_check_t;
 
-// !! AUTO-GENERATED-START C_TYPES
+/**
+ * Options to customise the behaviour of UpdateManager.
+ */
+typedef struct {
+    /**
+     * Allows UpdateManager to update to a version that's lower than the current version (i.e. downgrading).
+     * This could happen if a release has bugs and was retracted from the release feed, or if you're using
+     * ExplicitChannel to switch channels to another channel where the latest version on that
+     * channel is lower than the current version.
+     */
+    bool AllowVersionDowngrade;
+    /**
+     * **This option should usually be left None**. <br/>
+     * Overrides the default channel used to fetch updates.
+     * The default channel will be whatever channel was specified on the command line when building this release.
+     * For example, if the current release was packaged with '--channel beta', then the default channel will be 'beta'.
+     * This allows users to automatically receive updates from the same channel they installed from. This options
+     * allows you to explicitly switch channels, for example if the user wished to switch back to the 'stable' channel
+     * without having to reinstall the application.
+     */
+    char *ExplicitChannel;
+} vpkc_update_options_t;
+
 /**
  * VelopackLocator provides some utility functions for locating the current app important paths (eg. path to packages, update binary, and so forth).
  */
@@ -72,23 +66,23 @@ typedef struct {
     /**
      * The root directory of the current app.
      */
-    char* RootAppDir;
+    char *RootAppDir;
     /**
      * The path to the Update.exe binary.
      */
-    char* UpdateExePath;
+    char *UpdateExePath;
     /**
      * The path to the packages' directory.
      */
-    char* PackagesDir;
+    char *PackagesDir;
     /**
      * The current app manifest.
      */
-    char* ManifestPath;
+    char *ManifestPath;
     /**
      * The directory containing the application's user binaries.
      */
-    char* CurrentBinaryDir;
+    char *CurrentBinaryDir;
     /**
      * Whether the current application is portable or installed.
      */
@@ -96,233 +90,185 @@ typedef struct {
 } vpkc_locator_config_t;
 
 /**
- * An individual Velopack asset, could refer to an asset on-disk or in a remote package feed.
+ * An opaque handle to an UpdateManager, which must be freed with vpkc_free_update_manager.
+ */
+typedef void vpkc_update_manager_t;
+
+/**
+ * Options to customise the behaviour of UpdateManager.
  */
 typedef struct {
     /**
-     * The name or Id of the package containing this release.
+     * The size of this struct in bytes, which must be set to sizeof(vpkc_update_options_v2_t).
      */
-    char* PackageId;
+    size_t cbSize;
     /**
-     * The version of this release.
+     * Allows UpdateManager to update to a version that's lower than the current version (i.e. downgrading).
+     * This could happen if a release has bugs and was retracted from the release feed, or if you're using
+     * ExplicitChannel to switch channels to another channel where the latest version on that
+     * channel is lower than the current version.
      */
-    char* Version;
+    bool AllowVersionDowngrade;
     /**
-     * The type of asset (eg. "Full" or "Delta").
+     * **This option should usually be left None**. <br/>
+     * Overrides the default channel used to fetch updates.
+     * The default channel will be whatever channel was specified on the command line when building this release.
+     * For example, if the current release was packaged with '--channel beta', then the default channel will be 'beta'.
+     * This allows users to automatically receive updates from the same channel they installed from. This options
+     * allows you to explicitly switch channels, for example if the user wished to switch back to the 'stable' channel
+     * without having to reinstall the application.
      */
-    char* Type;
+    char *ExplicitChannel;
     /**
-     * The filename of the update package containing this release.
+     * Allows UpdateManager to update to prerelease versions (eg. '2.0.0-beta.1') from the feed, so users can opt into
+     * prerelease builds without switching to a separate channel. Prereleases are always allowed if the current version is
+     * itself a prerelease, or if ExplicitChannel selects a channel other than the one this version was packaged for.
      */
-    char* FileName;
+    bool AllowPrerelease;
     /**
-     * The SHA1 checksum of the update package containing this release.
+     * If greater than zero, check_for_updates will not contact the update source if the last check was less than this many
+     * seconds ago, and will report that no update is available instead. The time of the last check is persisted next to the
+     * app, and a random delay of up to 10% of the interval is added so that clients started at the same time spread out
+     * their checks rather than polling the feed all at once.
      */
-    char* SHA1;
+    uint64_t UpdateCheckIntervalSeconds;
     /**
-     * The SHA256 checksum of the update package containing this release.
+     * The number of full packages to keep in the packages directory once an update has been applied, including the package
+     * for the installed version. Keeping more than one allows rolling back to a previous version without a network connection.
+     * Zero uses the default, which is to keep only the package for the installed version.
      */
-    char* SHA256;
+    uint32_t PackagesToKeep;
     /**
-     * The size in bytes of the update package containing this release.
+     * If greater than zero, the oldest packages are deleted until the packages kept add up to less than this many bytes. The
+     * package for the installed version is never deleted.
      */
-    uint64_t Size;
+    uint64_t MaxPackagesSizeBytes;
     /**
-     * The release notes in markdown format, as passed to Velopack when packaging the release. This may be an empty string.
+     * If greater than zero, packages which were downloaded more than this many days ago are deleted. The package for the
+     * installed version is never deleted.
      */
-    char* NotesMarkdown;
+    uint32_t MaxPackageAgeDays;
     /**
-     * The release notes in HTML format, transformed from Markdown when packaging the release. This may be an empty string.
+     * Overrides the directory downloaded packages and temporary files are stored in (eg. a different drive with more free
+     * space, or a cache directory shared by several apps). Packages are stored in a sub-directory named after the app id, and
+     * relative paths are resolved against the app install directory. This takes priority over the VELOPACK_PACKAGES_DIR
+     * environment variable and the packages directory specified when the app was packaged.
      */
-    char* NotesHtml;
-} vpkc_asset_t;
-
-/**
- * Holds information about the current version and pending updates, such as how many there are, and access to release notes.
- */
-typedef struct {
+    char *PackagesDir;
     /**
-     * The available version that we are updating to.
+     * A machine-level directory where downloaded packages are cached by their checksum, so that apps (or channels of the
+     * same app) which are configured with the same directory do not download identical packages again. The directory must
+     * be writable by every user which runs these apps. Nothing is cached if this is None.
      */
-    vpkc_asset_t TargetFullRelease;
+    char *SharedCacheDir;
     /**
-     * True if the update is a version downgrade or lateral move (such as when switching channels to the same version number).
-     * In this case, only full updates are allowed, and any local packages on disk newer than the downloaded version will be
-     * deleted.
+     * The maximum size of the shared cache, the least recently used packages are deleted once it grows past this size.
+     * Zero uses the default, which is 2 GB.
      */
-    bool IsDowngrade;
-} vpkc_update_info_t;
+    uint64_t SharedCacheMaxSizeBytes;
+    /**
+     * The url (or network directory) of an intranet cache which packages are downloaded from before trying the update
+     * source, see CacheServerSource for the protocol. If this is None, the VELOPACK_CACHE_SERVER environment
+     * variable is used instead, so the cache can be configured for every app on a machine.
+     */
+    char *CacheServerUrl;
+    /**
+     * Replaces the User-Agent sent with every request to the update source, eg. 'MyApp/1.2.3 (beta)', so that the server
+     * can tell which app (and version) is asking for updates. The default User-Agent is used if this is None.
+     */
+    char *UserAgent;
+    /**
+     * Extra query parameters added to requests for the release feed, as a query string (eg. 'cohort=3&region=eu'), so
+     * that server-side analytics or staged rollouts can target groups of clients. Nothing is added if this is None.
+     */
+    char *FeedQueryParameters;
+    /**
+     * Extra headers sent with every request to the update source, one per line as 'Name: value' (eg. 'X-Api-Key: abc123'),
+     * so that servers which need authentication or routing headers can be used. Nothing is added if this is None.
+     */
+    char *RequestHeaders;
+    /**
+     * If true, the release feed of each successful update check is cached in the packages directory, and check_for_updates uses the
+     * cached feed if the update source can not be reached (eg. because the machine is offline). Updates found in the cached
+     * feed are marked with IsStale, and UpdateCheck::NoUpdateAvailableOffline is returned if there are none.
+     */
+    bool UseCachedFeedWhenOffline;
+} vpkc_update_options_v2_t;
 
 /**
- * Options to customise the behaviour of UpdateManager.
+ * An individual Velopack asset, could refer to an asset on-disk or in a remote package feed.
  */
 typedef struct {
     /**
-     * Allows UpdateManager to update to a version that's lower than the current version (i.e. downgrading).
-     * This could happen if a release has bugs and was retracted from the release feed, or if you're using
-     * ExplicitChannel to switch channels to another channel where the latest version on that
-     * channel is lower than the current version.
+     * The name or Id of the package containing this release.
      */
-    bool AllowVersionDowngrade;
+    char *PackageId;
     /**
-     * **This option should usually be left None**. <br/>
-     * Overrides the default channel used to fetch updates.
-     * The default channel will be whatever channel was specified on the command line when building this release.
-     * For example, if the current release was packaged with '--channel beta', then the default channel will be 'beta'.
-     * This allows users to automatically receive updates from the same channel they installed from. This options
-     * allows you to explicitly switch channels, for example if the user wished to switch back to the 'stable' channel
-     * without having to reinstall the application.
+     * The version of this release.
      */
-    char* ExplicitChannel;
-} vpkc_update_options_t;
-// !! AUTO-GENERATED-END C_TYPES
-
-// Update Manager
-VPKC_EXPORT bool VPKC_CALL vpkc_new_update_manager(const char* pszUrlOrString, vpkc_update_options_t* pOptions, vpkc_locator_config_t* pLocator, vpkc_update_manager_t** pManager);
-VPKC_EXPORT size_t VPKC_CALL vpkc_get_current_version(vpkc_update_manager_t* pManager, char* pszVersion, size_t cVersion);
-VPKC_EXPORT size_t VPKC_CALL vpkc_get_app_id(vpkc_update_manager_t* pManager, char* pszId, size_t cId);
-VPKC_EXPORT bool VPKC_CALL vpkc_is_portable(vpkc_update_manager_t* pManager);
-VPKC_EXPORT bool VPKC_CALL vpkc_update_pending_restart(vpkc_update_manager_t* pManager, vpkc_asset_t* pAsset);
-VPKC_EXPORT vpkc_update_check_t VPKC_CALL vpkc_check_for_updates(vpkc_update_manager_t* pManager, vpkc_update_info_t* pUpdate);
-VPKC_EXPORT bool VPKC_CALL vpkc_download_updates(vpkc_update_manager_t* pManager, vpkc_update_info_t* pUpdate, vpkc_progress_callback_t cbProgress);
-VPKC_EXPORT bool VPKC_CALL vpkc_wait_exit_then_apply_update(vpkc_update_manager_t* pManager, vpkc_asset_t* pAsset, bool bSilent, bool bRestart, char** pRestartArgs, size_t cRestartArgs);
-
-// VelopackApp
-VPKC_EXPORT void VPKC_CALL vpkc_app_set_auto_apply_on_startup(bool bAutoApply);
-VPKC_EXPORT void VPKC_CALL vpkc_app_set_args(char** pArgs, size_t cArgs);
-VPKC_EXPORT void VPKC_CALL vpkc_app_set_locator(vpkc_locator_config_t* pLocator);
-VPKC_EXPORT void VPKC_CALL vpkc_app_set_hook_after_install(vpkc_hook_callback_t cbAfterInstall);
-VPKC_EXPORT void VPKC_CALL vpkc_app_set_hook_before_uninstall(vpkc_hook_callback_t cbBeforeUninstall);
-VPKC_EXPORT void VPKC_CALL vpkc_app_set_hook_before_update(vpkc_hook_callback_t cbBeforeUpdate);
-VPKC_EXPORT void VPKC_CALL vpkc_app_set_hook_after_update(vpkc_hook_callback_t cbAfterUpdate);
-VPKC_EXPORT void VPKC_CALL vpkc_app_set_hook_first_run(vpkc_hook_callback_t cbFirstRun);
-VPKC_EXPORT void VPKC_CALL vpkc_app_set_hook_restarted(vpkc_hook_callback_t cbRestarted);
-VPKC_EXPORT void VPKC_CALL vpkc_app_run();
-
-// Misc functions
-VPKC_EXPORT size_t VPKC_CALL vpkc_get_last_error(char* pszError, size_t cError);
-VPKC_EXPORT void VPKC_CALL vpkc_set_log(vpkc_log_callback_t cbLog);
-VPKC_EXPORT void VPKC_CALL vpkc_free_update_manager(vpkc_update_manager_t* pManager);
-VPKC_EXPORT void VPKC_CALL vpkc_free_update_info(vpkc_update_info_t* pUpdateInfo);
-VPKC_EXPORT void VPKC_CALL vpkc_free_asset(vpkc_asset_t* pAsset);
-
-#ifdef __cplusplus
-}
-#endif
-
-#ifdef __cplusplus
-
-namespace Velopack {
-
-static inline void throw_last_error() {
-    size_t neededSize = vpkc_get_last_error(nullptr, 0);
-    std::string strError(neededSize, '\0');
-    vpkc_get_last_error(&strError[0], neededSize);
-    throw std::runtime_error(strError);
-}
-
-static inline std::string to_cppstring(const char* psz) {
-    return psz == nullptr ? "" : psz;
-}
-
-static inline char* to_cstring(const std::string& str) {
-    return const_cast<char*>(str.c_str());
-}
-
-static inline char* to_cstring_opt(const std::optional<std::string>& str) {
-    return str.has_value() ? to_cstring(str.value()) : nullptr;
-}
-
-static inline std::optional<std::string> to_cppstring_opt(const char* psz) {
-    return psz == nullptr ? std::nullopt : std::optional<std::string>(psz);
-}
-
-static inline bool to_cppbool(bool b) { return b; }
-static inline bool to_cbool(bool b) { return b; }
-static inline uint64_t to_cu64(uint64_t i) { return i; }
-static inline uint64_t to_cppu64(uint64_t i) { return i; }
-
-// !! AUTO-GENERATED-START CPP_TYPES
-/**
- * VelopackLocator provides some utility functions for locating the current app important paths (eg. path to packages, update binary, and so forth).
- */
-struct VelopackLocatorConfig {
+    char *Version;
     /**
-     * The root directory of the current app.
+     * The type of asset (eg. "Full" or "Delta").
      */
-    std::string RootAppDir;
+    char *Type;
     /**
-     * The path to the Update.exe binary.
+     * The filename of the update package containing this release.
      */
-    std::string UpdateExePath;
+    char *FileName;
     /**
-     * The path to the packages' directory.
+     * The SHA1 checksum of the update package containing this release.
      */
-    std::string PackagesDir;
+    char *SHA1;
     /**
-     * The current app manifest.
+     * The SHA256 checksum of the update package containing this release.
      */
-    std::string ManifestPath;
+    char *SHA256;
     /**
-     * The directory containing the application's user binaries.
+     * The size in bytes of the update package containing this release.
      */
-    std::string CurrentBinaryDir;
+    uint64_t Size;
     /**
-     * Whether the current application is portable or installed.
+     * The release notes in markdown format, as passed to Velopack when packaging the release. This may be an empty string.
      */
-    bool IsPortable;
-};
-
-static inline vpkc_locator_config_t to_c(const VelopackLocatorConfig& dto) {
-    return {
-        to_cstring(dto.RootAppDir),
-        to_cstring(dto.UpdateExePath),
-        to_cstring(dto.PackagesDir),
-        to_cstring(dto.ManifestPath),
-        to_cstring(dto.CurrentBinaryDir),
-        to_cbool(dto.IsPortable),
-    };
-}
-
-static inline VelopackLocatorConfig to_cpp(const vpkc_locator_config_t& dto) {
-    return {
-        to_cppstring(dto.RootAppDir),
-        to_cppstring(dto.UpdateExePath),
-        to_cppstring(dto.PackagesDir),
-        to_cppstring(dto.ManifestPath),
-        to_cppstring(dto.CurrentBinaryDir),
-        to_cppbool(dto.IsPortable),
-    };
-}
+    char *NotesMarkdown;
+    /**
+     * The release notes in HTML format, transformed from Markdown when packaging the release. This may be an empty string.
+     */
+    char *NotesHtml;
+} vpkc_asset_t;
 
 /**
  * An individual Velopack asset, could refer to an asset on-disk or in a remote package feed.
  */
-struct VelopackAsset {
+typedef struct {
+    /**
+     * The size of this struct in bytes, which must be set to sizeof(vpkc_asset_v2_t).
+     */
+    size_t cbSize;
     /**
      * The name or Id of the package containing this release.
      */
-    std::string PackageId;
+    char *PackageId;
     /**
      * The version of this release.
      */
-    std::string Version;
+    char *Version;
     /**
      * The type of asset (eg. "Full" or "Delta").
      */
-    std::string Type;
+    char *Type;
     /**
      * The filename of the update package containing this release.
      */
-    std::string FileName;
+    char *FileName;
     /**
      * The SHA1 checksum of the update package containing this release.
      */
-    std::string SHA1;
+    char *SHA1;
     /**
      * The SHA256 checksum of the update package containing this release.
      */
-    std::string SHA256;
+    char *SHA256;
     /**
      * The size in bytes of the update package containing this release.
      */
@@ -330,268 +276,293 @@ struct VelopackAsset {
     /**
      * The release notes in markdown format, as passed to Velopack when packaging the release. This may be an empty string.
      */
-    std::string NotesMarkdown;
+    char *NotesMarkdown;
     /**
      * The release notes in HTML format, transformed from Markdown when packaging the release. This may be an empty string.
      */
-    std::string NotesHtml;
-};
-
-static inline vpkc_asset_t to_c(const VelopackAsset& dto) {
-    return {
-        to_cstring(dto.PackageId),
-        to_cstring(dto.Version),
-        to_cstring(dto.Type),
-        to_cstring(dto.FileName),
-        to_cstring(dto.SHA1),
-        to_cstring(dto.SHA256),
-        to_cu64(dto.Size),
-        to_cstring(dto.NotesMarkdown),
-        to_cstring(dto.NotesHtml),
-    };
-}
-
-static inline VelopackAsset to_cpp(const vpkc_asset_t& dto) {
-    return {
-        to_cppstring(dto.PackageId),
-        to_cppstring(dto.Version),
-        to_cppstring(dto.Type),
-        to_cppstring(dto.FileName),
-        to_cppstring(dto.SHA1),
-        to_cppstring(dto.SHA256),
-        to_cppu64(dto.Size),
-        to_cppstring(dto.NotesMarkdown),
-        to_cppstring(dto.NotesHtml),
-    };
-}
+    char *NotesHtml;
+    /**
+     * The release has been marked as mandatory by the publisher, and should not be skipped or postponed.
+     */
+    bool IsMandatory;
+    /**
+     * The CPU architecture this release was built for (eg. "x64", "arm64" or "universal"). This may be an empty string,
+     * in which case the release is assumed to run on any machine.
+     */
+    char *Architecture;
+} vpkc_asset_v2_t;
 
 /**
  * Holds information about the current version and pending updates, such as how many there are, and access to release notes.
  */
-struct UpdateInfo {
+typedef struct {
     /**
      * The available version that we are updating to.
      */
-    VelopackAsset TargetFullRelease;
+    vpkc_asset_t TargetFullRelease;
     /**
      * True if the update is a version downgrade or lateral move (such as when switching channels to the same version number).
      * In this case, only full updates are allowed, and any local packages on disk newer than the downloaded version will be
      * deleted.
      */
     bool IsDowngrade;
-};
-
-static inline vpkc_update_info_t to_c(const UpdateInfo& dto) {
-    return {
-        to_c(dto.TargetFullRelease),
-        to_cbool(dto.IsDowngrade),
-    };
-}
-
-static inline UpdateInfo to_cpp(const vpkc_update_info_t& dto) {
-    return {
-        to_cpp(dto.TargetFullRelease),
-        to_cppbool(dto.IsDowngrade),
-    };
-}
+} vpkc_update_info_t;
 
 /**
- * Options to customise the behaviour of UpdateManager.
+ * Holds information about the current version and pending updates, such as how many there are, and access to release notes.
  */
-struct UpdateOptions {
+typedef struct {
     /**
-     * Allows UpdateManager to update to a version that's lower than the current version (i.e. downgrading).
-     * This could happen if a release has bugs and was retracted from the release feed, or if you're using
-     * ExplicitChannel to switch channels to another channel where the latest version on that
-     * channel is lower than the current version.
+     * The size of this struct in bytes, which must be set to sizeof(vpkc_update_info_v2_t).
      */
-    bool AllowVersionDowngrade;
+    size_t cbSize;
     /**
-     * **This option should usually be left None**. <br/>
-     * Overrides the default channel used to fetch updates.
-     * The default channel will be whatever channel was specified on the command line when building this release.
-     * For example, if the current release was packaged with '--channel beta', then the default channel will be 'beta'.
-     * This allows users to automatically receive updates from the same channel they installed from. This options
-     * allows you to explicitly switch channels, for example if the user wished to switch back to the 'stable' channel
-     * without having to reinstall the application.
+     * The available version that we are updating to.
      */
-    std::optional<std::string> ExplicitChannel;
-};
-
-static inline vpkc_update_options_t to_c(const UpdateOptions& dto) {
-    return {
-        to_cbool(dto.AllowVersionDowngrade),
-        to_cstring_opt(dto.ExplicitChannel),
-    };
-}
-
-static inline UpdateOptions to_cpp(const vpkc_update_options_t& dto) {
-    return {
-        to_cppbool(dto.AllowVersionDowngrade),
-        to_cppstring_opt(dto.ExplicitChannel),
-    };
-}
-// !! AUTO-GENERATED-END CPP_TYPES
-
-static inline char** to_cstring_array(const std::vector<std::string>& vec) {
-    char** result = new char*[vec.size()];
-    for (size_t i = 0; i < vec.size(); ++i) {
-        result[i] = new char[vec[i].size() + 1]; // +1 for null-terminator
-#ifdef _WIN32
-        strcpy_s(result[i], vec[i].size() + 1, vec[i].c_str());  // Copy string content
-#else
-        strcpy(result[i], vec[i].c_str());  // Copy string content
-#endif
-    }
-    return result;
-}
-
-static inline void free_cstring_array(char** arr, size_t size) {
-    for (size_t i = 0; i < size; ++i) {
-        delete[] arr[i];
-    }
-    delete[] arr;
-}
-
-
-class VelopackApp {
-private:
-    VelopackApp() {};
-public:
-    static VelopackApp Build() { 
-        return VelopackApp(); 
-    };
-    VelopackApp& SetAutoApplyOnStartup(bool bAutoApply) {
-        vpkc_app_set_auto_apply_on_startup(bAutoApply);
-        return *this;
-    };
-    VelopackApp& SetArgs(const std::vector<std::string>& args) {
-        char** pArgs = to_cstring_array(args);
-        vpkc_app_set_args(pArgs, args.size());
-        free_cstring_array(pArgs, args.size());
-        return *this;
-    };
-    VelopackApp& SetLocator(const VelopackLocatorConfig& locator) {
-        vpkc_locator_config_t vpkc_locator = to_c(locator);
-        vpkc_app_set_locator(&vpkc_locator);
-        return *this;
-    };
-    VelopackApp& OnAfterInstall(vpkc_hook_callback_t cbInstall) {
-        vpkc_app_set_hook_after_install(cbInstall);
-        return *this;
-    };
-    VelopackApp& OnBeforeUninstall(vpkc_hook_callback_t cbInstall) {
-        vpkc_app_set_hook_before_uninstall(cbInstall);
-        return *this;
-    };
-    VelopackApp& OnBeforeUpdate(vpkc_hook_callback_t cbInstall) {
-        vpkc_app_set_hook_before_update(cbInstall);
-        return *this;
-    };
-    VelopackApp& OnAfterUpdate(vpkc_hook_callback_t cbInstall) {
-        vpkc_app_set_hook_after_update(cbInstall);
-        return *this;
-    };
-    VelopackApp& OnFirstRun(vpkc_hook_callback_t cbInstall) {
-        vpkc_app_set_hook_first_run(cbInstall);
-        return *this;
-    };
-    VelopackApp& OnRestarted(vpkc_hook_callback_t cbInstall) {
-        vpkc_app_set_hook_restarted(cbInstall);
-        return *this;
-    };
-    void Run() {
-        vpkc_app_run();
-    };
-};
-
-class UpdateManager {
-private:
-    vpkc_update_manager_t* m_pManager = 0;
-public:
-    UpdateManager(const std::string& urlOrPath, const UpdateOptions* options = nullptr, const VelopackLocatorConfig* locator = nullptr) {
-        vpkc_update_options_t* pOptions = nullptr;
-        if (options != nullptr) {
-            vpkc_update_options_t vpkc_options = to_c(*options);
-            pOptions = &vpkc_options;
-        }
-        
-        vpkc_locator_config_t* pLocator = nullptr;
-        if (locator != nullptr) {
-            vpkc_locator_config_t vpkc_locator = to_c(*locator);
-            pLocator = &vpkc_locator;
-        }
-        
-        if (!vpkc_new_update_manager(urlOrPath.c_str(), pOptions, pLocator, &m_pManager)) {
-            throw_last_error();
-        }
-    };
-    ~UpdateManager() {
-        vpkc_free_update_manager(m_pManager);
-    };
-    bool IsPortable() noexcept {
-        return vpkc_is_portable(m_pManager);
-    };
-    std::string GetCurrentVersion() noexcept {
-        size_t neededSize = vpkc_get_current_version(m_pManager, nullptr, 0);
-        std::string strVersion(neededSize, '\0');
-        vpkc_get_current_version(m_pManager, &strVersion[0], neededSize);
-        return strVersion;
-    };
-    std::string GetAppId() noexcept {
-        size_t neededSize = vpkc_get_app_id(m_pManager, nullptr, 0);
-        std::string strId(neededSize, '\0');
-        vpkc_get_app_id(m_pManager, &strId[0], neededSize);
-        return strId;
-    };
-    std::optional<VelopackAsset> UpdatePendingRestart() noexcept {
-        vpkc_asset_t asset;
-        if (vpkc_update_pending_restart(m_pManager, &asset)) {
-            VelopackAsset cpp_asset = to_cpp(asset);
-            vpkc_free_asset(&asset);
-            return cpp_asset;
-        }
-        return std::nullopt;
-    };
-    std::optional<UpdateInfo> CheckForUpdates() {
-        vpkc_update_info_t update;
-        vpkc_update_check_t result = vpkc_check_for_updates(m_pManager, &update);
-        switch (result) {
-            case vpkc_update_check_t::UPDATE_ERROR:
-                throw_last_error();
-                return std::nullopt;
-            case vpkc_update_check_t::NO_UPDATE_AVAILABLE:
-                return std::nullopt;
-            case vpkc_update_check_t::UPDATE_AVAILABLE:
-                UpdateInfo cpp_info = to_cpp(update);
-                vpkc_free_update_info(&update);
-                return cpp_info;
-        }
-        return std::nullopt;
-    };
-    void DownloadUpdates(const UpdateInfo& update, vpkc_progress_callback_t progress = nullptr) {
-        vpkc_update_info_t vpkc_update = to_c(update);
-        if (!vpkc_download_updates(m_pManager, &vpkc_update, progress)) {
-            throw_last_error();
-        }
-    };
-    void WaitExitThenApplyUpdate(const VelopackAsset& asset, bool silent = false, bool restart = true, std::vector<std::string> restartArgs = {}) {
-        char** pRestartArgs = to_cstring_array(restartArgs);
-        vpkc_asset_t vpkc_asset = to_c(asset);
-        bool result = vpkc_wait_exit_then_apply_update(m_pManager, &vpkc_asset, silent, restart, pRestartArgs, restartArgs.size());
-        free_cstring_array(pRestartArgs, restartArgs.size());
-        
-        if (!result) {
-            throw_last_error();
-        }
-    };
-    void WaitExitThenApplyUpdate(const UpdateInfo& asset, bool silent = false, bool restart = true, std::vector<std::string> restartArgs = {}) {
-        this->WaitExitThenApplyUpdate(asset.TargetFullRelease, silent, restart, restartArgs);
-    };
-};
-
-} // namespace Velopack
+    vpkc_asset_v2_t TargetFullRelease;
+    /**
+     * True if the update is a version downgrade or lateral move (such as when switching channels to the same version number).
+     * In this case, only full updates are allowed, and any local packages on disk newer than the downloaded version will be
+     * deleted.
+     * A release of the same version built for an architecture which suits this machine better (eg. a native ARM64 build
+     * replacing an x64 build running under emulation) is also a lateral move.
+     */
+    bool IsDowngrade;
+    /**
+     * True if the target release, or any release between the current version and the target version, is marked as mandatory.
+     * Apps should not allow this update to be skipped, and may prevent usage until it has been applied.
+     */
+    bool IsMandatory;
+    /**
+     * True if the update source could not be reached, and this update was found in the release feed cached by the last
+     * successful update check (see UseCachedFeedWhenOffline). The update may no longer be available, or may be out of date.
+     */
+    bool IsStale;
+} vpkc_update_info_v2_t;
+
+/**
+ * Receives the progress of a download or stage operation, from 0 to 100.
+ */
+typedef void (*vpkc_progress_callback_t)(size_t progress);
+
+/**
+ * Receives the version of the app which a hook is being run for.
+ */
+typedef void (*vpkc_hook_callback_t)(const char *pszAppVersion);
+
+/**
+ * Receives the version of the app being uninstalled, and the reason for uninstalling it (or NULL if there is none).
+ */
+typedef void (*vpkc_uninstall_hook_callback_t)(const char *pszAppVersion, const char *pszReason);
+
+/**
+ * Receives a log message, the level is one of "error", "warn", "info", "debug" or "trace".
+ */
+typedef void (*vpkc_log_callback_t)(const char *pszLevel, const char *pszMessage);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates an UpdateManager, which must be freed with vpkc_free_update_manager. The options and locator are optional.
+ */
+bool vpkc_new_update_manager(const char *pszUrlOrString, vpkc_update_options_t *pOptions, vpkc_locator_config_t *pLocator, vpkc_update_manager_t **pManager);
+
+/**
+ * Creates an UpdateManager, which must be freed with vpkc_free_update_manager. The options and locator are optional, and
+ * pOptions->cbSize must be set to sizeof(vpkc_update_options_v2_t).
+ */
+bool vpkc_new_update_manager_v2(const char *pszUrlOrString, vpkc_update_options_v2_t *pOptions, vpkc_locator_config_t *pLocator, vpkc_update_manager_t **pManager);
+
+/**
+ * Copies the current version of the app into pszVersion (without a null terminator), and returns the number of bytes
+ * copied. If pszVersion is NULL, returns the length of the version instead.
+ */
+size_t vpkc_get_current_version(vpkc_update_manager_t *pManager, char *pszVersion, size_t cVersion);
+
+/**
+ * Copies the id of the app into pszId (without a null terminator), and returns the number of bytes copied. If pszId is
+ * NULL, returns the length of the id instead.
+ */
+size_t vpkc_get_app_id(vpkc_update_manager_t *pManager, char *pszId, size_t cId);
+
+bool vpkc_is_portable(vpkc_update_manager_t *pManager);
+
+bool vpkc_is_restart_required(vpkc_update_manager_t *pManager);
+
+bool vpkc_is_first_run_for_user(vpkc_update_manager_t *pManager);
+
+/**
+ * Returns true if a download, stage, apply or component operation is running for this app. Starting another one
+ * while this is true fails with VPKC_ERROR_BUSY.
+ */
+bool vpkc_is_operation_in_progress(vpkc_update_manager_t *pManager);
+
+/**
+ * Returns true if macOS is running the app from a translocated (read-only) location, eg. straight from a DMG. Updates
+ * fail with VPKC_ERROR_TRANSLOCATED until the user moves the app to /Applications.
+ */
+bool vpkc_is_translocated(vpkc_update_manager_t *pManager);
+
+/**
+ * Returns true if the app is running in a sandbox such as Flatpak or Snap, which delivers its updates. Update checks
+ * always return NO_UPDATE_AVAILABLE, and downloading or applying updates fails with VPKC_ERROR_MANAGED_EXTERNALLY.
+ */
+bool vpkc_is_managed_externally(vpkc_update_manager_t *pManager);
+
+/**
+ * Returns true and fills in pAsset (which must be freed with vpkc_free_asset) if an update has been downloaded and is
+ * waiting for the app to restart.
+ */
+bool vpkc_update_pending_restart(vpkc_update_manager_t *pManager, vpkc_asset_t *pAsset);
+
+/**
+ * Returns true and fills in pAsset (which must be freed with vpkc_free_asset_v2) if an update has been downloaded and is
+ * waiting for the app to restart. pAsset->cbSize must be set to sizeof(vpkc_asset_v2_t).
+ */
+bool vpkc_update_pending_restart_v2(vpkc_update_manager_t *pManager, vpkc_asset_v2_t *pAsset);
+
+/**
+ * Checks for updates. pUpdate is only filled in (and must be freed with vpkc_free_update_info) if UPDATE_AVAILABLE is returned.
+ */
+vpkc_update_check_t vpkc_check_for_updates(vpkc_update_manager_t *pManager, vpkc_update_info_t *pUpdate);
 
+/**
+ * Checks for updates. pUpdate is only filled in (and must be freed with vpkc_free_update_info_v2) if UPDATE_AVAILABLE is
+ * returned. pUpdate->cbSize must be set to sizeof(vpkc_update_info_v2_t).
+ */
+vpkc_update_check_t vpkc_check_for_updates_v2(vpkc_update_manager_t *pManager, vpkc_update_info_v2_t *pUpdate);
+
+/**
+ * Downloads the update described by pUpdate. The progress callback is invoked on the calling thread, before this returns.
+ */
+bool vpkc_download_updates(vpkc_update_manager_t *pManager, vpkc_update_info_t *pUpdate, vpkc_progress_callback_t cbProgress);
+
+/**
+ * Downloads the update described by pUpdate. The progress callback is invoked on the calling thread, before this returns.
+ */
+bool vpkc_download_updates_v2(vpkc_update_manager_t *pManager, vpkc_update_info_v2_t *pUpdate, vpkc_progress_callback_t cbProgress);
+
+/**
+ * Extracts a downloaded update ready to be applied. The progress callback is invoked on the calling thread, before this returns.
+ */
+bool vpkc_stage_updates(vpkc_update_manager_t *pManager, vpkc_asset_t *pAsset, vpkc_progress_callback_t cbProgress);
+
+/**
+ * Extracts a downloaded update ready to be applied. The progress callback is invoked on the calling thread, before this returns.
+ */
+bool vpkc_stage_updates_v2(vpkc_update_manager_t *pManager, vpkc_asset_v2_t *pAsset, vpkc_progress_callback_t cbProgress);
+
+/**
+ * Applies a staged or downloaded update, restarting the app with the given arguments. This will exit the process.
+ */
+bool vpkc_apply_updates_and_restart(vpkc_update_manager_t *pManager, vpkc_asset_t *pAsset, char **pRestartArgs, size_t cRestartArgs);
+
+/**
+ * Applies a staged or downloaded update, restarting the app with the given arguments. This will exit the process.
+ */
+bool vpkc_apply_updates_and_restart_v2(vpkc_update_manager_t *pManager, vpkc_asset_v2_t *pAsset, char **pRestartArgs, size_t cRestartArgs);
+
+/**
+ * Applies a staged or downloaded update without restarting the app. This will exit the process.
+ */
+bool vpkc_apply_updates_and_exit(vpkc_update_manager_t *pManager, vpkc_asset_t *pAsset);
+
+/**
+ * Applies a staged or downloaded update without restarting the app. This will exit the process.
+ */
+bool vpkc_apply_updates_and_exit_v2(vpkc_update_manager_t *pManager, vpkc_asset_v2_t *pAsset);
+
+/**
+ * Starts the updater, which waits for this process to exit before applying the update. The app should exit soon after.
+ */
+bool vpkc_wait_exit_then_apply_update(vpkc_update_manager_t *pManager, vpkc_asset_t *pAsset, bool bSilent, bool bRestart, char **pRestartArgs, size_t cRestartArgs);
+
+/**
+ * Starts the updater, which waits for this process to exit before applying the update. The app should exit soon after.
+ */
+bool vpkc_wait_exit_then_apply_update_v2(vpkc_update_manager_t *pManager, vpkc_asset_v2_t *pAsset, bool bSilent, bool bRestart, char **pRestartArgs, size_t cRestartArgs);
+
+/**
+ * Starts the updater, which waits for this process to exit whenever that happens, and then applies the update.
+ */
+bool vpkc_apply_updates_on_exit(vpkc_update_manager_t *pManager, vpkc_asset_t *pAsset, bool bSilent, bool bRestart, char **pRestartArgs, size_t cRestartArgs);
+
+/**
+ * Starts the updater, which waits for this process to exit whenever that happens, and then applies the update.
+ */
+bool vpkc_apply_updates_on_exit_v2(vpkc_update_manager_t *pManager, vpkc_asset_v2_t *pAsset, bool bSilent, bool bRestart, char **pRestartArgs, size_t cRestartArgs);
+
+void vpkc_app_set_auto_apply_on_startup(bool bAutoApply);
+
+void vpkc_app_set_args(char **pArgs, size_t cArgs);
+
+void vpkc_app_set_locator(vpkc_locator_config_t *pLocator);
+
+void vpkc_app_set_hook_after_install(vpkc_hook_callback_t cbAfterInstall);
+
+void vpkc_app_set_hook_before_uninstall(vpkc_hook_callback_t cbBeforeUninstall);
+
+void vpkc_app_set_hook_before_uninstall_with_reason(vpkc_uninstall_hook_callback_t cbBeforeUninstall);
+
+void vpkc_app_set_hook_before_update(vpkc_hook_callback_t cbBeforeUpdate);
+
+void vpkc_app_set_hook_after_update(vpkc_hook_callback_t cbAfterUpdate);
+
+void vpkc_app_set_hook_first_run(vpkc_hook_callback_t cbFirstRun);
+
+void vpkc_app_set_hook_restarted(vpkc_hook_callback_t cbRestarted);
+
+/**
+ * Runs the Velopack startup logic, which may run a hook and exit, or apply a pending update and restart the app. This
+ * should be called as early as possible in the application's main function.
+ */
+void vpkc_app_run(void);
+
+/**
+ * Copies the message of the last error on the calling thread into pszError (without a null terminator), and returns the
+ * number of bytes copied. If pszError is NULL, returns the length of the message instead.
+ */
+size_t vpkc_get_last_error(char *pszError, size_t cError);
+
+/**
+ * Returns the code of the last error on the calling thread, or VPKC_OK if the last call succeeded.
+ */
+vpkc_error_code_t vpkc_get_last_error_code(void);
+
+/**
+ * Sets the callback which receives log messages. By default, the log callback is invoked directly from whichever thread
+ * produced the message (Velopack does some work on background threads), but calls are serialized so it is never invoked
+ * concurrently. Messages logged while the callback is running on the same thread (eg. by a vpkc_* function it calls)
+ * are discarded.
+ */
+void vpkc_set_log(vpkc_log_callback_t cbLog);
+
+/**
+ * If your logging system is not thread-safe, enable queued mode: messages are then buffered (up to 10,000, oldest dropped
+ * first) and only delivered to the callback on the thread which calls vpkc_drain_log. Disabling queued mode drains any
+ * buffered messages.
+ */
+void vpkc_set_log_queued(bool bQueued);
+
+/**
+ * Delivers any queued log messages to the log callback on the calling thread, and returns the number delivered.
+ */
+size_t vpkc_drain_log(void);
+
+void vpkc_free_update_manager(vpkc_update_manager_t *pManager);
+
+void vpkc_free_update_info(vpkc_update_info_t *pUpdateInfo);
+
+void vpkc_free_update_info_v2(vpkc_update_info_v2_t *pUpdateInfo);
+
+void vpkc_free_asset(vpkc_asset_t *pAsset);
+
+void vpkc_free_asset_v2(vpkc_asset_v2_t *pAsset);
+
+#ifdef __cplusplus
+} // extern "C"
 #endif // __cplusplus
 
-#endif // VELOPACK_H
\ No newline at end of file
+#endif /* VELOPACK_H */
@@ -0,0 +1,612 @@
+#ifndef VELOPACK_HPP
+#define VELOPACK_HPP
+
+#include <string>
+#include <optional>
+#include <vector>
+#include <stdexcept>
+
+#if !defined(_WIN32)
+#include <string.h>
+#endif
+
+#include "Velopack.h"
+
+namespace Velopack {
+
+class VelopackError : public std::runtime_error {
+private:
+    vpkc_error_code_t m_code;
+public:
+    VelopackError(const std::string& message, vpkc_error_code_t code) : std::runtime_error(message), m_code(code) {};
+    vpkc_error_code_t code() const noexcept {
+        return m_code;
+    };
+};
+
+static inline void throw_last_error() {
+    size_t neededSize = vpkc_get_last_error(nullptr, 0);
+    std::string strError(neededSize, '\0');
+    vpkc_get_last_error(&strError[0], neededSize);
+    throw VelopackError(strError, vpkc_get_last_error_code());
+}
+
+static inline std::string to_cppstring(const char* psz) {
+    return psz == nullptr ? "" : psz;
+}
+
+static inline char* to_cstring(const std::string& str) {
+    return const_cast<char*>(str.c_str());
+}
+
+static inline char* to_cstring_opt(const std::optional<std::string>& str) {
+    return str.has_value() ? to_cstring(str.value()) : nullptr;
+}
+
+static inline std::optional<std::string> to_cppstring_opt(const char* psz) {
+    return psz == nullptr ? std::nullopt : std::optional<std::string>(psz);
+}
+
+static inline bool to_cppbool(bool b) { return b; }
+static inline bool to_cbool(bool b) { return b; }
+static inline uint64_t to_cu64(uint64_t i) { return i; }
+static inline uint64_t to_cppu64(uint64_t i) { return i; }
+static inline uint32_t to_cu32(uint32_t i) { return i; }
+static inline uint32_t to_cppu32(uint32_t i) { return i; }
+
+// !! AUTO-GENERATED-START CPP_TYPES
+/**
+ * VelopackLocator provides some utility functions for locating the current app important paths (eg. path to packages, update binary, and so forth).
+ */
+struct VelopackLocatorConfig {
+    /**
+     * The root directory of the current app.
+     */
+    std::string RootAppDir;
+    /**
+     * The path to the Update.exe binary.
+     */
+    std::string UpdateExePath;
+    /**
+     * The path to the packages' directory.
+     */
+    std::string PackagesDir;
+    /**
+     * The current app manifest.
+     */
+    std::string ManifestPath;
+    /**
+     * The directory containing the application's user binaries.
+     */
+    std::string CurrentBinaryDir;
+    /**
+     * Whether the current application is portable or installed.
+     */
+    bool IsPortable;
+};
+
+static inline vpkc_locator_config_t to_c(const VelopackLocatorConfig& dto) {
+    return {
+        to_cstring(dto.RootAppDir),
+        to_cstring(dto.UpdateExePath),
+        to_cstring(dto.PackagesDir),
+        to_cstring(dto.ManifestPath),
+        to_cstring(dto.CurrentBinaryDir),
+        to_cbool(dto.IsPortable),
+    };
+}
+
+static inline VelopackLocatorConfig to_cpp(const vpkc_locator_config_t& dto) {
+    return {
+        to_cppstring(dto.RootAppDir),
+        to_cppstring(dto.UpdateExePath),
+        to_cppstring(dto.PackagesDir),
+        to_cppstring(dto.ManifestPath),
+        to_cppstring(dto.CurrentBinaryDir),
+        to_cppbool(dto.IsPortable),
+    };
+}
+
+/**
+ * An individual Velopack asset, could refer to an asset on-disk or in a remote package feed.
+ */
+struct VelopackAsset {
+    /**
+     * The name or Id of the package containing this release.
+     */
+    std::string PackageId;
+    /**
+     * The version of this release.
+     */
+    std::string Version;
+    /**
+     * The type of asset (eg. "Full" or "Delta").
+     */
+    std::string Type;
+    /**
+     * The filename of the update package containing this release.
+     */
+    std::string FileName;
+    /**
+     * The SHA1 checksum of the update package containing this release.
+     */
+    std::string SHA1;
+    /**
+     * The SHA256 checksum of the update package containing this release.
+     */
+    std::string SHA256;
+    /**
+     * The size in bytes of the update package containing this release.
+     */
+    uint64_t Size;
+    /**
+     * The release notes in markdown format, as passed to Velopack when packaging the release. This may be an empty string.
+     */
+    std::string NotesMarkdown;
+    /**
+     * The release notes in HTML format, transformed from Markdown when packaging the release. This may be an empty string.
+     */
+    std::string NotesHtml;
+    /**
+     * The release has been marked as mandatory by the publisher, and should not be skipped or postponed.
+     */
+    bool IsMandatory;
+    /**
+     * The CPU architecture this release was built for (eg. "x64", "arm64" or "universal"). This may be an empty string,
+     * in which case the release is assumed to run on any machine.
+     */
+    std::string Architecture;
+};
+
+static inline vpkc_asset_v2_t to_c(const VelopackAsset& dto) {
+    return {
+        sizeof(vpkc_asset_v2_t),
+        to_cstring(dto.PackageId),
+        to_cstring(dto.Version),
+        to_cstring(dto.Type),
+        to_cstring(dto.FileName),
+        to_cstring(dto.SHA1),
+        to_cstring(dto.SHA256),
+        to_cu64(dto.Size),
+        to_cstring(dto.NotesMarkdown),
+        to_cstring(dto.NotesHtml),
+        to_cbool(dto.IsMandatory),
+        to_cstring(dto.Architecture),
+    };
+}
+
+static inline VelopackAsset to_cpp(const vpkc_asset_v2_t& dto) {
+    return {
+        to_cppstring(dto.PackageId),
+        to_cppstring(dto.Version),
+        to_cppstring(dto.Type),
+        to_cppstring(dto.FileName),
+        to_cppstring(dto.SHA1),
+        to_cppstring(dto.SHA256),
+        to_cppu64(dto.Size),
+        to_cppstring(dto.NotesMarkdown),
+        to_cppstring(dto.NotesHtml),
+        to_cppbool(dto.IsMandatory),
+        to_cppstring(dto.Architecture),
+    };
+}
+
+/**
+ * Holds information about the current version and pending updates, such as how many there are, and access to release notes.
+ */
+struct UpdateInfo {
+    /**
+     * The available version that we are updating to.
+     */
+    VelopackAsset TargetFullRelease;
+    /**
+     * True if the update is a version downgrade or lateral move (such as when switching channels to the same version number).
+     * In this case, only full updates are allowed, and any local packages on disk newer than the downloaded version will be
+     * deleted.
+     * A release of the same version built for an architecture which suits this machine better (eg. a native ARM64 build
+     * replacing an x64 build running under emulation) is also a lateral move.
+     */
+    bool IsDowngrade;
+    /**
+     * True if the target release, or any release between the current version and the target version, is marked as mandatory.
+     * Apps should not allow this update to be skipped, and may prevent usage until it has been applied.
+     */
+    bool IsMandatory;
+    /**
+     * True if the update source could not be reached, and this update was found in the release feed cached by the last
+     * successful update check (see UseCachedFeedWhenOffline). The update may no longer be available, or may be out of date.
+     */
+    bool IsStale;
+};
+
+static inline vpkc_update_info_v2_t to_c(const UpdateInfo& dto) {
+    return {
+        sizeof(vpkc_update_info_v2_t),
+        to_c(dto.TargetFullRelease),
+        to_cbool(dto.IsDowngrade),
+        to_cbool(dto.IsMandatory),
+        to_cbool(dto.IsStale),
+    };
+}
+
+static inline UpdateInfo to_cpp(const vpkc_update_info_v2_t& dto) {
+    return {
+        to_cpp(dto.TargetFullRelease),
+        to_cppbool(dto.IsDowngrade),
+        to_cppbool(dto.IsMandatory),
+        to_cppbool(dto.IsStale),
+    };
+}
+
+/**
+ * Options to customise the behaviour of UpdateManager.
+ */
+struct UpdateOptions {
+    /**
+     * Allows UpdateManager to update to a version that's lower than the current version (i.e. downgrading).
+     * This could happen if a release has bugs and was retracted from the release feed, or if you're using
+     * ExplicitChannel to switch channels to another channel where the latest version on that
+     * channel is lower than the current version.
+     */
+    bool AllowVersionDowngrade;
+    /**
+     * **This option should usually be left None**. <br/>
+     * Overrides the default channel used to fetch updates.
+     * The default channel will be whatever channel was specified on the command line when building this release.
+     * For example, if the current release was packaged with '--channel beta', then the default channel will be 'beta'.
+     * This allows users to automatically receive updates from the same channel they installed from. This options
+     * allows you to explicitly switch channels, for example if the user wished to switch back to the 'stable' channel
+     * without having to reinstall the application.
+     */
+    std::optional<std::string> ExplicitChannel;
+    /**
+     * Allows UpdateManager to update to prerelease versions (eg. '2.0.0-beta.1') from the feed, so users can opt into
+     * prerelease builds without switching to a separate channel. Prereleases are always allowed if the current version is
+     * itself a prerelease, or if ExplicitChannel selects a channel other than the one this version was packaged for.
+     */
+    bool AllowPrerelease;
+    /**
+     * If greater than zero, check_for_updates will not contact the update source if the last check was less than this many
+     * seconds ago, and will report that no update is available instead. The time of the last check is persisted next to the
+     * app, and a random delay of up to 10% of the interval is added so that clients started at the same time spread out
+     * their checks rather than polling the feed all at once.
+     */
+    uint64_t UpdateCheckIntervalSeconds;
+    /**
+     * The number of full packages to keep in the packages directory once an update has been applied, including the package
+     * for the installed version. Keeping more than one allows rolling back to a previous version without a network connection.
+     * Zero uses the default, which is to keep only the package for the installed version.
+     */
+    uint32_t PackagesToKeep;
+    /**
+     * If greater than zero, the oldest packages are deleted until the packages kept add up to less than this many bytes. The
+     * package for the installed version is never deleted.
+     */
+    uint64_t MaxPackagesSizeBytes;
+    /**
+     * If greater than zero, packages which were downloaded more than this many days ago are deleted. The package for the
+     * installed version is never deleted.
+     */
+    uint32_t MaxPackageAgeDays;
+    /**
+     * Overrides the directory downloaded packages and temporary files are stored in (eg. a different drive with more free
+     * space, or a cache directory shared by several apps). Packages are stored in a sub-directory named after the app id, and
+     * relative paths are resolved against the app install directory. This takes priority over the VELOPACK_PACKAGES_DIR
+     * environment variable and the packages directory specified when the app was packaged.
+     */
+    std::optional<std::string> PackagesDir;
+    /**
+     * A machine-level directory where downloaded packages are cached by their checksum, so that apps (or channels of the
+     * same app) which are configured with the same directory do not download identical packages again. The directory must
+     * be writable by every user which runs these apps. Nothing is cached if this is None.
+     */
+    std::optional<std::string> SharedCacheDir;
+    /**
+     * The maximum size of the shared cache, the least recently used packages are deleted once it grows past this size.
+     * Zero uses the default, which is 2 GB.
+     */
+    uint64_t SharedCacheMaxSizeBytes;
+    /**
+     * The url (or network directory) of an intranet cache which packages are downloaded from before trying the update
+     * source, see CacheServerSource for the protocol. If this is None, the VELOPACK_CACHE_SERVER environment
+     * variable is used instead, so the cache can be configured for every app on a machine.
+     */
+    std::optional<std::string> CacheServerUrl;
+    /**
+     * Replaces the User-Agent sent with every request to the update source, eg. 'MyApp/1.2.3 (beta)', so that the server
+     * can tell which app (and version) is asking for updates. The default User-Agent is used if this is None.
+     */
+    std::optional<std::string> UserAgent;
+    /**
+     * Extra query parameters added to requests for the release feed, as a query string (eg. 'cohort=3&region=eu'), so
+     * that server-side analytics or staged rollouts can target groups of clients. Nothing is added if this is None.
+     */
+    std::optional<std::string> FeedQueryParameters;
+    /**
+     * Extra headers sent with every request to the update source, one per line as 'Name: value' (eg. 'X-Api-Key: abc123'),
+     * so that servers which need authentication or routing headers can be used. Nothing is added if this is None.
+     */
+    std::optional<std::string> RequestHeaders;
+    /**
+     * If true, the release feed of each successful update check is cached in the packages directory, and check_for_updates uses the
+     * cached feed if the update source can not be reached (eg. because the machine is offline). Updates found in the cached
+     * feed are marked with IsStale, and UpdateCheck::NoUpdateAvailableOffline is returned if there are none.
+     */
+    bool UseCachedFeedWhenOffline;
+};
+
+static inline vpkc_update_options_v2_t to_c(const UpdateOptions& dto) {
+    return {
+        sizeof(vpkc_update_options_v2_t),
+        to_cbool(dto.AllowVersionDowngrade),
+        to_cstring_opt(dto.ExplicitChannel),
+        to_cbool(dto.AllowPrerelease),
+        to_cu64(dto.UpdateCheckIntervalSeconds),
+        to_cu32(dto.PackagesToKeep),
+        to_cu64(dto.MaxPackagesSizeBytes),
+        to_cu32(dto.MaxPackageAgeDays),
+        to_cstring_opt(dto.PackagesDir),
+        to_cstring_opt(dto.SharedCacheDir),
+        to_cu64(dto.SharedCacheMaxSizeBytes),
+        to_cstring_opt(dto.CacheServerUrl),
+        to_cstring_opt(dto.UserAgent),
+        to_cstring_opt(dto.FeedQueryParameters),
+        to_cstring_opt(dto.RequestHeaders),
+        to_cbool(dto.UseCachedFeedWhenOffline),
+    };
+}
+
+static inline UpdateOptions to_cpp(const vpkc_update_options_v2_t& dto) {
+    return {
+        to_cppbool(dto.AllowVersionDowngrade),
+        to_cppstring_opt(dto.ExplicitChannel),
+        to_cppbool(dto.AllowPrerelease),
+        to_cppu64(dto.UpdateCheckIntervalSeconds),
+        to_cppu32(dto.PackagesToKeep),
+        to_cppu64(dto.MaxPackagesSizeBytes),
+        to_cppu32(dto.MaxPackageAgeDays),
+        to_cppstring_opt(dto.PackagesDir),
+        to_cppstring_opt(dto.SharedCacheDir),
+        to_cppu64(dto.SharedCacheMaxSizeBytes),
+        to_cppstring_opt(dto.CacheServerUrl),
+        to_cppstring_opt(dto.UserAgent),
+        to_cppstring_opt(dto.FeedQueryParameters),
+        to_cppstring_opt(dto.RequestHeaders),
+        to_cppbool(dto.UseCachedFeedWhenOffline),
+    };
+}
+// !! AUTO-GENERATED-END CPP_TYPES
+
+static inline char** to_cstring_array(const std::vector<std::string>& vec) {
+    char** result = new char*[vec.size()];
+    for (size_t i = 0; i < vec.size(); ++i) {
+        result[i] = new char[vec[i].size() + 1]; // +1 for null-terminator
+#ifdef _WIN32
+        strcpy_s(result[i], vec[i].size() + 1, vec[i].c_str());  // Copy string content
+#else
+        strcpy(result[i], vec[i].c_str());  // Copy string content
+#endif
+    }
+    return result;
+}
+
+static inline void free_cstring_array(char** arr, size_t size) {
+    for (size_t i = 0; i < size; ++i) {
+        delete[] arr[i];
+    }
+    delete[] arr;
+}
+
+
+class VelopackApp {
+private:
+    VelopackApp() {};
+public:
+    static VelopackApp Build() { 
+        return VelopackApp(); 
+    };
+    VelopackApp& SetAutoApplyOnStartup(bool bAutoApply) {
+        vpkc_app_set_auto_apply_on_startup(bAutoApply);
+        return *this;
+    };
+    VelopackApp& SetArgs(const std::vector<std::string>& args) {
+        char** pArgs = to_cstring_array(args);
+        vpkc_app_set_args(pArgs, args.size());
+        free_cstring_array(pArgs, args.size());
+        return *this;
+    };
+    VelopackApp& SetLocator(const VelopackLocatorConfig& locator) {
+        vpkc_locator_config_t vpkc_locator = to_c(locator);
+        vpkc_app_set_locator(&vpkc_locator);
+        return *this;
+    };
+    VelopackApp& OnAfterInstall(vpkc_hook_callback_t cbInstall) {
+        vpkc_app_set_hook_after_install(cbInstall);
+        return *this;
+    };
+    VelopackApp& OnBeforeUninstall(vpkc_hook_callback_t cbInstall) {
+        vpkc_app_set_hook_before_uninstall(cbInstall);
+        return *this;
+    };
+    VelopackApp& OnBeforeUninstallWithReason(vpkc_uninstall_hook_callback_t cbInstall) {
+        vpkc_app_set_hook_before_uninstall_with_reason(cbInstall);
+        return *this;
+    };
+    VelopackApp& OnBeforeUpdate(vpkc_hook_callback_t cbInstall) {
+        vpkc_app_set_hook_before_update(cbInstall);
+        return *this;
+    };
+    VelopackApp& OnAfterUpdate(vpkc_hook_callback_t cbInstall) {
+        vpkc_app_set_hook_after_update(cbInstall);
+        return *this;
+    };
+    VelopackApp& OnFirstRun(vpkc_hook_callback_t cbInstall) {
+        vpkc_app_set_hook_first_run(cbInstall);
+        return *this;
+    };
+    VelopackApp& OnRestarted(vpkc_hook_callback_t cbInstall) {
+        vpkc_app_set_hook_restarted(cbInstall);
+        return *this;
+    };
+    void Run() {
+        vpkc_app_run();
+    };
+};
+
+class UpdateManager {
+private:
+    vpkc_update_manager_t* m_pManager = 0;
+public:
+    UpdateManager(const std::string& urlOrPath, const UpdateOptions* options = nullptr, const VelopackLocatorConfig* locator = nullptr) {
+        vpkc_update_options_v2_t vpkc_options;
+        vpkc_update_options_v2_t* pOptions = nullptr;
+        if (options != nullptr) {
+            vpkc_options = to_c(*options);
+            pOptions = &vpkc_options;
+        }
+        
+        vpkc_locator_config_t vpkc_locator;
+        vpkc_locator_config_t* pLocator = nullptr;
+        if (locator != nullptr) {
+            vpkc_locator = to_c(*locator);
+            pLocator = &vpkc_locator;
+        }
+        
+        if (!vpkc_new_update_manager_v2(urlOrPath.c_str(), pOptions, pLocator, &m_pManager)) {
+            throw_last_error();
+        }
+    };
+    ~UpdateManager() {
+        vpkc_free_update_manager(m_pManager);
+    };
+    bool IsPortable() noexcept {
+        return vpkc_is_portable(m_pManager);
+    };
+    bool IsRestartRequired() noexcept {
+        return vpkc_is_restart_required(m_pManager);
+    };
+    bool IsFirstRunForUser() noexcept {
+        return vpkc_is_first_run_for_user(m_pManager);
+    };
+    bool IsOperationInProgress() noexcept {
+        return vpkc_is_operation_in_progress(m_pManager);
+    };
+    bool IsTranslocated() noexcept {
+        return vpkc_is_translocated(m_pManager);
+    };
+    bool IsManagedExternally() noexcept {
+        return vpkc_is_managed_externally(m_pManager);
+    };
+    std::string GetCurrentVersion() noexcept {
+        size_t neededSize = vpkc_get_current_version(m_pManager, nullptr, 0);
+        std::string strVersion(neededSize, '\0');
+        vpkc_get_current_version(m_pManager, &strVersion[0], neededSize);
+        return strVersion;
+    };
+    std::string GetAppId() noexcept {
+        size_t neededSize = vpkc_get_app_id(m_pManager, nullptr, 0);
+        std::string strId(neededSize, '\0');
+        vpkc_get_app_id(m_pManager, &strId[0], neededSize);
+        return strId;
+    };
+    std::optional<VelopackAsset> UpdatePendingRestart() noexcept {
+        vpkc_asset_v2_t asset{};
+        asset.cbSize = sizeof(vpkc_asset_v2_t);
+        if (vpkc_update_pending_restart_v2(m_pManager, &asset)) {
+            VelopackAsset cpp_asset = to_cpp(asset);
+            vpkc_free_asset_v2(&asset);
+            return cpp_asset;
+        }
+        return std::nullopt;
+    };
+    // Checks for updates, returning std::nullopt if there are none available. If pIsOffline is provided, it is set to true
+    // if the update source could not be reached and the cached release feed was used instead (see UseCachedFeedWhenOffline).
+    std::optional<UpdateInfo> CheckForUpdates(bool* pIsOffline = nullptr) {
+        vpkc_update_info_v2_t update{};
+        update.cbSize = sizeof(vpkc_update_info_v2_t);
+        vpkc_update_check_t result = vpkc_check_for_updates_v2(m_pManager, &update);
+        if (pIsOffline) {
+            *pIsOffline = result == vpkc_update_check_t::NO_UPDATE_AVAILABLE_OFFLINE;
+        }
+        switch (result) {
+            case vpkc_update_check_t::UPDATE_ERROR:
+                throw_last_error();
+                return std::nullopt;
+            case vpkc_update_check_t::NO_UPDATE_AVAILABLE:
+            case vpkc_update_check_t::NO_UPDATE_AVAILABLE_OFFLINE:
+                return std::nullopt;
+            case vpkc_update_check_t::UPDATE_AVAILABLE:
+                UpdateInfo cpp_info = to_cpp(update);
+                vpkc_free_update_info_v2(&update);
+                return cpp_info;
+        }
+        return std::nullopt;
+    };
+    void DownloadUpdates(const UpdateInfo& update, vpkc_progress_callback_t progress = nullptr) {
+        vpkc_update_info_v2_t vpkc_update = to_c(update);
+        if (!vpkc_download_updates_v2(m_pManager, &vpkc_update, progress)) {
+            throw_last_error();
+        }
+    };
+    void StageUpdates(const VelopackAsset& asset, vpkc_progress_callback_t progress = nullptr) {
+        vpkc_asset_v2_t vpkc_asset = to_c(asset);
+        if (!vpkc_stage_updates_v2(m_pManager, &vpkc_asset, progress)) {
+            throw_last_error();
+        }
+    };
+    void StageUpdates(const UpdateInfo& update, vpkc_progress_callback_t progress = nullptr) {
+        this->StageUpdates(update.TargetFullRelease, progress);
+    };
+    void ApplyUpdatesAndRestart(const VelopackAsset& asset, std::vector<std::string> restartArgs = {}) {
+        char** pRestartArgs = to_cstring_array(restartArgs);
+        vpkc_asset_v2_t vpkc_asset = to_c(asset);
+        bool result = vpkc_apply_updates_and_restart_v2(m_pManager, &vpkc_asset, pRestartArgs, restartArgs.size());
+        free_cstring_array(pRestartArgs, restartArgs.size());
+
+        if (!result) {
+            throw_last_error();
+        }
+    };
+    void ApplyUpdatesAndRestart(const UpdateInfo& update, std::vector<std::string> restartArgs = {}) {
+        this->ApplyUpdatesAndRestart(update.TargetFullRelease, restartArgs);
+    };
+    void ApplyUpdatesAndExit(const VelopackAsset& asset) {
+        vpkc_asset_v2_t vpkc_asset = to_c(asset);
+        if (!vpkc_apply_updates_and_exit_v2(m_pManager, &vpkc_asset)) {
+            throw_last_error();
+        }
+    };
+    void ApplyUpdatesAndExit(const UpdateInfo& update) {
+        this->ApplyUpdatesAndExit(update.TargetFullRelease);
+    };
+    void ApplyUpdatesOnExit(const VelopackAsset& asset, bool silent = false, bool restart = false, std::vector<std::string> restartArgs = {}) {
+        char** pRestartArgs = to_cstring_array(restartArgs);
+        vpkc_asset_v2_t vpkc_asset = to_c(asset);
+        bool result = vpkc_apply_updates_on_exit_v2(m_pManager, &vpkc_asset, silent, restart, pRestartArgs, restartArgs.size());
+        free_cstring_array(pRestartArgs, restartArgs.size());
+
+        if (!result) {
+            throw_last_error();
+        }
+    };
+    void ApplyUpdatesOnExit(const UpdateInfo& update, bool silent = false, bool restart = false, std::vector<std::string> restartArgs = {}) {
+        this->ApplyUpdatesOnExit(update.TargetFullRelease, silent, restart, restartArgs);
+    };
+    void WaitExitThenApplyUpdate(const VelopackAsset& asset, bool silent = false, bool restart = true, std::vector<std::string> restartArgs = {}) {
+        char** pRestartArgs = to_cstring_array(restartArgs);
+        vpkc_asset_v2_t vpkc_asset = to_c(asset);
+        bool result = vpkc_wait_exit_then_apply_update_v2(m_pManager, &vpkc_asset, silent, restart, pRestartArgs, restartArgs.size());
+        free_cstring_array(pRestartArgs, restartArgs.size());
+        
+        if (!result) {
+            throw_last_error();
+        }
+    };
+    void WaitExitThenApplyUpdate(const UpdateInfo& asset, bool silent = false, bool restart = true, std::vector<std::string> restartArgs = {}) {
+        this->WaitExitThenApplyUpdate(asset.TargetFullRelease, silent, restart, restartArgs);
+    };
+};
+
+} // namespace Velopack
+
+#endif // VELOPACK_HPP
@@ -1,145 +1,120 @@
 #![allow(dead_code)]
 #![allow(non_snake_case)]
+#![allow(non_camel_case_types)]
+#![allow(clippy::missing_safety_doc)]
 
 mod map;
+mod types;
+
 use map::*;
+use types::*;
 
 use anyhow::{bail, Result};
 use log::{Level, Log, Metadata, Record};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use velopack::{sources, Error as VelopackError, UpdateCheck, UpdateManager, VelopackApp};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::ffi::c_char;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use velopack::locator::VelopackLocatorConfig;
+use velopack::{sources, Error as VelopackError, UpdateCheck, UpdateInfo, UpdateManager, UpdateOptions, VelopackApp, VelopackAsset};
+
+thread_local! {
+    // each thread sees the error of the last call it made, so calls from several threads do not overwrite each other
+    static LAST_ERROR: RefCell<(vpkc_error_code_t, String)> = const { RefCell::new((vpkc_error_code_t::VPKC_OK, String::new())) };
+    // set while this thread is invoking the log callback, which can not be re-entered because LOG_DELIVERY is held
+    static IN_LOG_CALLBACK: Cell<bool> = const { Cell::new(false) };
+}
+
+/// An error caused by the arguments passed to the C API, rather than by the operation itself.
+#[derive(Debug)]
+struct InvalidArgument(String);
+
+impl std::fmt::Display for InvalidArgument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidArgument {}
+
+fn invalid_argument(message: &str) -> anyhow::Error {
+    InvalidArgument(message.to_string()).into()
+}
+
+fn error_to_code(e: &anyhow::Error) -> vpkc_error_code_t {
+    use vpkc_error_code_t::*;
+    if e.downcast_ref::<InvalidArgument>().is_some() {
+        return VPKC_ERROR_INVALID_ARGUMENT;
+    }
+    match e.downcast_ref::<VelopackError>() {
+        Some(VelopackError::FileNotFound(_)) | Some(VelopackError::Io(_)) => VPKC_ERROR_IO,
+        Some(VelopackError::Network(_)) => VPKC_ERROR_NETWORK,
+        Some(VelopackError::MissingNuspec)
+        | Some(VelopackError::MissingNuspecProperty(_))
+        | Some(VelopackError::MissingUpdateExe)
+        | Some(VelopackError::NotInstalled(_)) => VPKC_ERROR_NOT_INSTALLED,
+        Some(VelopackError::Zip(_))
+        | Some(VelopackError::Json(_))
+        | Some(VelopackError::Semver(_))
+        | Some(VelopackError::FeedParse(_))
+        | Some(VelopackError::InvalidNuspec(_)) => VPKC_ERROR_INVALID_DATA,
+        Some(VelopackError::HashMismatch(_)) => VPKC_ERROR_HASH_MISMATCH,
+        Some(VelopackError::Unsupported(_)) => VPKC_ERROR_UNSUPPORTED,
+        Some(VelopackError::AppTranslocated(_)) => VPKC_ERROR_TRANSLOCATED,
+        Some(VelopackError::UpdatesManagedExternally(_)) => VPKC_ERROR_MANAGED_EXTERNALLY,
+        Some(VelopackError::Cancelled) => VPKC_ERROR_CANCELLED,
+        Some(VelopackError::OperationInProgress(_)) => VPKC_ERROR_BUSY,
+        Some(VelopackError::Locked(_)) => VPKC_ERROR_LOCKED,
+        _ => VPKC_ERROR_GENERIC,
+    }
+}
 
-#[cxx::bridge]
-mod ffi {
-    // Shared structs with fields visible to both languages.
-    #[derive(Default)]
-    pub struct StringOption {
-        pub data: String,
-        pub has_data: bool,
-    }
-
-    #[derive(Default)]
-    pub struct StringArrayOption {
-        pub data: Vec<String>,
-        pub has_data: bool,
-    }
-
-    // !! AUTO-GENERATED-START BRIDGE_DTOS
-    #[derive(Default)]
-    pub struct VelopackLocatorConfigDto {
-        pub RootAppDir: String,
-        pub UpdateExePath: String,
-        pub PackagesDir: String,
-        pub ManifestPath: String,
-        pub CurrentBinaryDir: String,
-        pub IsPortable: bool,
-    }
-
-    #[derive(Default)]
-    pub struct VelopackLocatorConfigDtoOption {
-        pub data: VelopackLocatorConfigDto,
-        pub has_data: bool,
-    }
-
-    #[derive(Default)]
-    pub struct VelopackAssetDto {
-        pub PackageId: String,
-        pub Version: String,
-        pub Type: String,
-        pub FileName: String,
-        pub SHA1: String,
-        pub SHA256: String,
-        pub Size: u64,
-        pub NotesMarkdown: String,
-        pub NotesHtml: String,
-    }
-
-    #[derive(Default)]
-    pub struct VelopackAssetDtoOption {
-        pub data: VelopackAssetDto,
-        pub has_data: bool,
-    }
-
-    #[derive(Default)]
-    pub struct UpdateInfoDto {
-        pub TargetFullRelease: VelopackAssetDto,
-        pub IsDowngrade: bool,
-    }
-
-    #[derive(Default)]
-    pub struct UpdateInfoDtoOption {
-        pub data: UpdateInfoDto,
-        pub has_data: bool,
-    }
-
-    #[derive(Default)]
-    pub struct UpdateOptionsDto {
-        pub AllowVersionDowngrade: bool,
-        pub ExplicitChannel: StringOption,
-    }
-
-    #[derive(Default)]
-    pub struct UpdateOptionsDtoOption {
-        pub data: UpdateOptionsDto,
-        pub has_data: bool,
-    }
-    // !! AUTO-GENERATED-END BRIDGE_DTOS
-
-    // C++ types and signatures exposed to Rust.
-    unsafe extern "C++" {
-        include!("velopack_libc/include/Velopack.h");
-        include!("velopack_libc/src/bridge.hpp");
-        
-        type HookCallbackManager;
-        fn install_hook(self: &HookCallbackManager, app_version: String);
-        fn update_hook(self: &HookCallbackManager, app_version: String);
-        fn obsolete_hook(self: &HookCallbackManager, app_version: String);
-        fn uninstall_hook(self: &HookCallbackManager, app_version: String);
-        fn firstrun_hook(self: &HookCallbackManager, app_version: String);
-        fn restarted_hook(self: &HookCallbackManager, app_version: String);
-
-        type DownloadCallbackManager;
-        fn download_progress(self: &DownloadCallbackManager, progress: i16);
-
-        type LoggerCallbackManager;
-        fn log(self: &LoggerCallbackManager, level: String, message: String);
-    }
-
-    // Rust types and signatures exposed to C++.
-    extern "Rust" {
-        type UpdateManagerOpaque;
-        fn bridge_new_update_manager(
-            url_or_path: &String,
-            options: &UpdateOptionsDtoOption,
-            locator: &VelopackLocatorConfigDtoOption,
-        ) -> Result<Box<UpdateManagerOpaque>>;
-        fn bridge_get_current_version(manager: &UpdateManagerOpaque) -> String;
-        fn bridge_get_app_id(manager: &UpdateManagerOpaque) -> String;
-        fn bridge_is_portable(manager: &UpdateManagerOpaque) -> bool;
-        fn bridge_update_pending_restart(manager: &UpdateManagerOpaque) -> VelopackAssetDtoOption;
-        fn bridge_check_for_updates(manager: &UpdateManagerOpaque) -> Result<UpdateInfoDtoOption>;
-        fn bridge_download_updates(
-            manager: &UpdateManagerOpaque,
-            to_download: &UpdateInfoDto,
-            progress: &DownloadCallbackManager,
-        ) -> Result<()>;
-        fn bridge_wait_exit_then_apply_update(
-            manager: &UpdateManagerOpaque,
-            to_apply: &VelopackAssetDto,
-            silent: bool,
-            restart: bool,
-            restart_args: &Vec<String>,
-        ) -> Result<()>;
-        fn bridge_appbuilder_run(
-            cb: &HookCallbackManager,
-            custom_args: &StringArrayOption,
-            locator: &VelopackLocatorConfigDtoOption,
-            auto_apply: bool,
-        );
-        unsafe fn bridge_set_logger_callback(cb: *mut LoggerCallbackManager);
+/// Runs a fallible operation, recording its error for the C API to return from vpkc_get_last_error and vpkc_get_last_error_code.
+fn track_error<T, F: FnOnce() -> Result<T>>(operation: F) -> Option<T> {
+    LAST_ERROR.with(|e| *e.borrow_mut() = (vpkc_error_code_t::VPKC_OK, String::new()));
+    match operation() {
+        Ok(result) => Some(result),
+        Err(e) => {
+            LAST_ERROR.with(|last| *last.borrow_mut() = (error_to_code(&e), e.to_string()));
+            None
+        }
     }
 }
 
+/// Checks the cbSize of a versioned struct passed in by the caller, so that a struct from an older header is never read
+/// or written past its end.
+fn check_struct_size<T>(cb_size: usize, arg_name: &str, type_name: &str) -> Result<()> {
+    if cb_size < std::mem::size_of::<T>() {
+        return Err(invalid_argument(&format!("{}->cbSize must be set to sizeof({})", arg_name, type_name)));
+    }
+    Ok(())
+}
+
+unsafe fn return_c_string(value: &str, psz: *mut c_char, csz: usize) -> usize {
+    let bytes = value.as_bytes();
+    if bytes.is_empty() {
+        return 0;
+    }
+
+    if psz.is_null() || csz == 0 {
+        // no buffer has been provided, return the length
+        return bytes.len();
+    }
+
+    // shorten the length if it's longer than the buffer
+    let len = bytes.len().min(csz);
+    std::ptr::copy_nonoverlapping(bytes.as_ptr(), psz as *mut u8, len);
+    len
+}
+
+unsafe fn string_array_to_core(pArgs: *mut *mut c_char, cArgs: usize) -> Vec<String> {
+    if pArgs.is_null() {
+        return Vec::new();
+    }
+    std::slice::from_raw_parts(pArgs, cArgs).iter().map(|psz| string_to_core(*psz)).collect()
+}
+
 #[derive(Clone)]
 struct UpdateManagerOpaque {
     obj: UpdateManager,
@@ -158,53 +133,297 @@ impl Drop for UpdateManagerOpaque {
     }
 }
 
-fn bridge_new_update_manager(
-    url_or_path: &String,
-    options: &ffi::UpdateOptionsDtoOption,
-    locator: &ffi::VelopackLocatorConfigDtoOption,
-) -> Result<Box<UpdateManagerOpaque>> {
-    let source = sources::AutoSource::new(url_or_path);
-    let options = updateoptions_to_core_option(options);
-    let locator = velopacklocatorconfig_to_core_option(locator);
+unsafe fn get_manager<'a>(pManager: *mut vpkc_update_manager_t) -> Option<&'a UpdateManagerOpaque> {
+    (pManager as *const UpdateManagerOpaque).as_ref()
+}
+
+unsafe fn require_manager<'a>(pManager: *mut vpkc_update_manager_t) -> Result<&'a UpdateManagerOpaque> {
+    get_manager(pManager).ok_or_else(|| invalid_argument("pManager cannot be null"))
+}
+
+// Update Manager
+unsafe fn new_update_manager(
+    pszUrlOrString: *const c_char,
+    options: Option<UpdateOptions>,
+    locator: Option<VelopackLocatorConfig>,
+    pManager: *mut *mut vpkc_update_manager_t,
+) -> Result<()> {
+    if pManager.is_null() {
+        bail!(invalid_argument("pManager cannot be null"));
+    }
+    let url_or_path = string_to_core_option(pszUrlOrString).ok_or_else(|| invalid_argument("pszUrlOrString cannot be null"))?;
+    let source = sources::AutoSource::new(&url_or_path);
     let update_manager = UpdateManager::new(source, options, locator)?;
-    Ok(Box::new(UpdateManagerOpaque::new(update_manager)))
+    let opaque = Box::new(UpdateManagerOpaque::new(update_manager));
+    *pManager = Box::into_raw(opaque) as *mut vpkc_update_manager_t;
+    Ok(())
 }
 
-fn bridge_get_current_version(manager: &UpdateManagerOpaque) -> String {
-    manager.obj.get_current_version_as_string()
+/// Creates an UpdateManager, which must be freed with vpkc_free_update_manager. The options and locator are optional.
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_new_update_manager(
+    pszUrlOrString: *const c_char,
+    pOptions: *mut vpkc_update_options_t,
+    pLocator: *mut vpkc_locator_config_t,
+    pManager: *mut *mut vpkc_update_manager_t,
+) -> bool {
+    track_error(|| {
+        let options = pOptions.as_ref().map(|o| updateoptions_v1_to_core(o));
+        let locator = pLocator.as_ref().map(|l| velopacklocatorconfig_to_core(l));
+        new_update_manager(pszUrlOrString, options, locator, pManager)
+    })
+    .is_some()
 }
 
-fn bridge_get_app_id(manager: &UpdateManagerOpaque) -> String {
-    manager.obj.get_app_id()
+/// Creates an UpdateManager, which must be freed with vpkc_free_update_manager. The options and locator are optional, and
+/// pOptions->cbSize must be set to sizeof(vpkc_update_options_v2_t).
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_new_update_manager_v2(
+    pszUrlOrString: *const c_char,
+    pOptions: *mut vpkc_update_options_v2_t,
+    pLocator: *mut vpkc_locator_config_t,
+    pManager: *mut *mut vpkc_update_manager_t,
+) -> bool {
+    track_error(|| {
+        let options = match pOptions.as_ref() {
+            Some(o) => {
+                check_struct_size::<vpkc_update_options_v2_t>(o.cbSize, "pOptions", "vpkc_update_options_v2_t")?;
+                Some(updateoptions_to_core(o))
+            }
+            None => None,
+        };
+        let locator = pLocator.as_ref().map(|l| velopacklocatorconfig_to_core(l));
+        new_update_manager(pszUrlOrString, options, locator, pManager)
+    })
+    .is_some()
 }
 
-fn bridge_is_portable(manager: &UpdateManagerOpaque) -> bool {
-    manager.obj.get_is_portable()
+/// Copies the current version of the app into pszVersion (without a null terminator), and returns the number of bytes
+/// copied. If pszVersion is NULL, returns the length of the version instead.
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_get_current_version(pManager: *mut vpkc_update_manager_t, pszVersion: *mut c_char, cVersion: usize) -> usize {
+    match get_manager(pManager) {
+        Some(manager) => return_c_string(&manager.obj.get_current_version_as_string(), pszVersion, cVersion),
+        None => 0,
+    }
 }
 
-fn bridge_update_pending_restart(manager: &UpdateManagerOpaque) -> ffi::VelopackAssetDtoOption {
-    let asset_opt = manager.obj.get_update_pending_restart();
-    velopackasset_to_bridge_option(&asset_opt)
+/// Copies the id of the app into pszId (without a null terminator), and returns the number of bytes copied. If pszId is
+/// NULL, returns the length of the id instead.
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_get_app_id(pManager: *mut vpkc_update_manager_t, pszId: *mut c_char, cId: usize) -> usize {
+    match get_manager(pManager) {
+        Some(manager) => return_c_string(&manager.obj.get_app_id(), pszId, cId),
+        None => 0,
+    }
 }
 
-fn bridge_check_for_updates(manager: &UpdateManagerOpaque) -> Result<ffi::UpdateInfoDtoOption> {
-    let info_opt = if let UpdateCheck::UpdateAvailable(info) = manager.obj.check_for_updates()? { Some(info) } else { None };
-    Ok(updateinfo_to_bridge_option(&info_opt))
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_is_portable(pManager: *mut vpkc_update_manager_t) -> bool {
+    get_manager(pManager).map(|m| m.obj.get_is_portable()).unwrap_or_default()
 }
 
-fn bridge_download_updates(
-    manager: &UpdateManagerOpaque,
-    to_download: &ffi::UpdateInfoDto,
-    cb: &ffi::DownloadCallbackManager,
-) -> Result<()> {
-    let info = updateinfo_to_core(&to_download);
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_is_restart_required(pManager: *mut vpkc_update_manager_t) -> bool {
+    get_manager(pManager).map(|m| m.obj.get_is_restart_required()).unwrap_or_default()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_is_first_run_for_user(pManager: *mut vpkc_update_manager_t) -> bool {
+    get_manager(pManager).map(|m| m.obj.get_is_first_run_for_user()).unwrap_or_default()
+}
+
+/// Returns true if a download, stage, apply or component operation is running for this app. Starting another one
+/// while this is true fails with VPKC_ERROR_BUSY.
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_is_operation_in_progress(pManager: *mut vpkc_update_manager_t) -> bool {
+    get_manager(pManager).map(|m| m.obj.get_operation_in_progress().is_some()).unwrap_or_default()
+}
+
+/// Returns true if macOS is running the app from a translocated (read-only) location, eg. straight from a DMG. Updates
+/// fail with VPKC_ERROR_TRANSLOCATED until the user moves the app to /Applications.
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_is_translocated(pManager: *mut vpkc_update_manager_t) -> bool {
+    get_manager(pManager).map(|m| m.obj.get_is_translocated()).unwrap_or_default()
+}
+
+/// Returns true if the app is running in a sandbox such as Flatpak or Snap, which delivers its updates. Update checks
+/// always return NO_UPDATE_AVAILABLE, and downloading or applying updates fails with VPKC_ERROR_MANAGED_EXTERNALLY.
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_is_managed_externally(pManager: *mut vpkc_update_manager_t) -> bool {
+    get_manager(pManager).map(|m| m.obj.get_is_managed_externally()).unwrap_or_default()
+}
+
+/// Returns true and fills in pAsset (which must be freed with vpkc_free_asset) if an update has been downloaded and is
+/// waiting for the app to restart.
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_update_pending_restart(pManager: *mut vpkc_update_manager_t, pAsset: *mut vpkc_asset_t) -> bool {
+    match (get_manager(pManager).and_then(|m| m.obj.get_update_pending_restart()), pAsset.as_mut()) {
+        (Some(asset), Some(pAsset)) => {
+            allocate_velopackasset_v1(&asset, pAsset);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Returns true and fills in pAsset (which must be freed with vpkc_free_asset_v2) if an update has been downloaded and is
+/// waiting for the app to restart. pAsset->cbSize must be set to sizeof(vpkc_asset_v2_t).
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_update_pending_restart_v2(pManager: *mut vpkc_update_manager_t, pAsset: *mut vpkc_asset_v2_t) -> bool {
+    let pAsset = match pAsset.as_mut() {
+        Some(pAsset) if pAsset.cbSize >= std::mem::size_of::<vpkc_asset_v2_t>() => pAsset,
+        _ => return false,
+    };
+    match get_manager(pManager).and_then(|m| m.obj.get_update_pending_restart()) {
+        Some(asset) => {
+            allocate_velopackasset(&asset, pAsset);
+            true
+        }
+        None => false,
+    }
+}
+
+unsafe fn check_for_updates<F: FnOnce(&UpdateInfo)>(pManager: *mut vpkc_update_manager_t, fill: F) -> Result<vpkc_update_check_t> {
+    let manager = require_manager(pManager)?;
+    Ok(match manager.obj.check_for_updates()? {
+        UpdateCheck::UpdateAvailable(info) => {
+            fill(&info);
+            vpkc_update_check_t::UPDATE_AVAILABLE
+        }
+        UpdateCheck::NoUpdateAvailableOffline => vpkc_update_check_t::NO_UPDATE_AVAILABLE_OFFLINE,
+        _ => vpkc_update_check_t::NO_UPDATE_AVAILABLE,
+    })
+}
+
+/// Checks for updates. pUpdate is only filled in (and must be freed with vpkc_free_update_info) if UPDATE_AVAILABLE is returned.
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_check_for_updates(
+    pManager: *mut vpkc_update_manager_t,
+    pUpdate: *mut vpkc_update_info_t,
+) -> vpkc_update_check_t {
+    track_error(|| {
+        check_for_updates(pManager, |info| {
+            if let Some(pUpdate) = pUpdate.as_mut() {
+                allocate_updateinfo_v1(info, pUpdate);
+            }
+        })
+    })
+    .unwrap_or(vpkc_update_check_t::UPDATE_ERROR)
+}
+
+/// Checks for updates. pUpdate is only filled in (and must be freed with vpkc_free_update_info_v2) if UPDATE_AVAILABLE is
+/// returned. pUpdate->cbSize must be set to sizeof(vpkc_update_info_v2_t).
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_check_for_updates_v2(
+    pManager: *mut vpkc_update_manager_t,
+    pUpdate: *mut vpkc_update_info_v2_t,
+) -> vpkc_update_check_t {
+    track_error(|| {
+        let pUpdate = pUpdate.as_mut();
+        if let Some(update) = &pUpdate {
+            check_struct_size::<vpkc_update_info_v2_t>(update.cbSize, "pUpdate", "vpkc_update_info_v2_t")?;
+        }
+        check_for_updates(pManager, |info| {
+            if let Some(pUpdate) = pUpdate {
+                allocate_updateinfo(info, pUpdate);
+            }
+        })
+    })
+    .unwrap_or(vpkc_update_check_t::UPDATE_ERROR)
+}
+
+unsafe fn require_update_v2<'a>(pUpdate: *mut vpkc_update_info_v2_t) -> Result<&'a vpkc_update_info_v2_t> {
+    let update = pUpdate.as_ref().ok_or_else(|| invalid_argument("pUpdate is a required parameter"))?;
+    check_struct_size::<vpkc_update_info_v2_t>(update.cbSize, "pUpdate", "vpkc_update_info_v2_t")?;
+    check_struct_size::<vpkc_asset_v2_t>(update.TargetFullRelease.cbSize, "pUpdate->TargetFullRelease", "vpkc_asset_v2_t")?;
+    Ok(update)
+}
+
+unsafe fn require_asset_v1<'a>(pAsset: *mut vpkc_asset_t) -> Result<&'a vpkc_asset_t> {
+    pAsset.as_ref().ok_or_else(|| invalid_argument("pAsset is a required parameter"))
+}
+
+unsafe fn require_asset_v2<'a>(pAsset: *mut vpkc_asset_v2_t) -> Result<&'a vpkc_asset_v2_t> {
+    let asset = pAsset.as_ref().ok_or_else(|| invalid_argument("pAsset is a required parameter"))?;
+    check_struct_size::<vpkc_asset_v2_t>(asset.cbSize, "pAsset", "vpkc_asset_v2_t")?;
+    Ok(asset)
+}
+
+unsafe fn download_updates(pManager: *mut vpkc_update_manager_t, update: UpdateInfo, cbProgress: vpkc_progress_callback_t) -> Result<()> {
+    let manager = require_manager(pManager)?.clone();
+    run_with_progress(cbProgress, move |progress_sender| manager.obj.download_updates(&update, Some(progress_sender)))
+}
+
+/// Downloads the update described by pUpdate. The progress callback is invoked on the calling thread, before this returns.
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_download_updates(
+    pManager: *mut vpkc_update_manager_t,
+    pUpdate: *mut vpkc_update_info_t,
+    cbProgress: vpkc_progress_callback_t,
+) -> bool {
+    track_error(|| {
+        let update = pUpdate.as_ref().ok_or_else(|| invalid_argument("pUpdate is a required parameter"))?;
+        download_updates(pManager, updateinfo_v1_to_core(update), cbProgress)
+    })
+    .is_some()
+}
+
+/// Downloads the update described by pUpdate. The progress callback is invoked on the calling thread, before this returns.
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_download_updates_v2(
+    pManager: *mut vpkc_update_manager_t,
+    pUpdate: *mut vpkc_update_info_v2_t,
+    cbProgress: vpkc_progress_callback_t,
+) -> bool {
+    track_error(|| {
+        let update = require_update_v2(pUpdate)?;
+        download_updates(pManager, updateinfo_to_core(update), cbProgress)
+    })
+    .is_some()
+}
+
+unsafe fn stage_updates(pManager: *mut vpkc_update_manager_t, asset: VelopackAsset, cbProgress: vpkc_progress_callback_t) -> Result<()> {
+    let manager = require_manager(pManager)?.clone();
+    run_with_progress(cbProgress, move |progress_sender| manager.obj.stage_updates(&asset, Some(progress_sender)))
+}
+
+/// Extracts a downloaded update ready to be applied. The progress callback is invoked on the calling thread, before this returns.
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_stage_updates(
+    pManager: *mut vpkc_update_manager_t,
+    pAsset: *mut vpkc_asset_t,
+    cbProgress: vpkc_progress_callback_t,
+) -> bool {
+    track_error(|| stage_updates(pManager, velopackasset_v1_to_core(require_asset_v1(pAsset)?), cbProgress)).is_some()
+}
+
+/// Extracts a downloaded update ready to be applied. The progress callback is invoked on the calling thread, before this returns.
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_stage_updates_v2(
+    pManager: *mut vpkc_update_manager_t,
+    pAsset: *mut vpkc_asset_v2_t,
+    cbProgress: vpkc_progress_callback_t,
+) -> bool {
+    track_error(|| stage_updates(pManager, velopackasset_to_core(require_asset_v2(pAsset)?), cbProgress)).is_some()
+}
+
+/// Runs a long operation on a background thread, while delivering its progress to the callback on the caller's thread.
+fn run_with_progress<F>(cb: vpkc_progress_callback_t, operation: F) -> Result<()>
+where
+    F: FnOnce(std::sync::mpsc::Sender<i16>) -> std::result::Result<(), VelopackError> + Send + 'static,
+{
     let (progress_sender, progress_receiver) = std::sync::mpsc::channel::<i16>();
     let (completion_sender, completion_receiver) = std::sync::mpsc::channel::<std::result::Result<(), VelopackError>>();
+    let report = |progress: i16| {
+        if let Some(cb) = cb {
+            cb(progress.max(0) as usize);
+        }
+    };
 
-    // Move the download_updates call into a new thread
-    let manager = manager.clone();
+    // Move the operation into a new thread
     std::thread::spawn(move || {
-        let result = manager.obj.download_updates(&info, Some(progress_sender));
+        let result = operation(progress_sender);
         let _ = completion_sender.send(result);
     });
 
@@ -213,7 +432,7 @@ fn bridge_download_updates(
         // Try to receive progress updates without blocking
         match progress_receiver.try_recv() {
             Ok(progress) => {
-                cb.download_progress(progress);
+                report(progress);
             }
             _ => {
                 // No progress updates available, sleep for a short time to avoid busy-waiting
@@ -221,67 +440,324 @@ fn bridge_download_updates(
             }
         }
 
-        // Check if download is complete
+        // Check if the operation is complete
         match completion_receiver.try_recv() {
             Ok(result) => {
-                // Download is complete, return the result (propagating any errors)
+                // Deliver any progress which was reported just before completion, so the callback always sees the final value
+                for progress in progress_receiver.try_iter() {
+                    report(progress);
+                }
+                // Operation is complete, return the result (propagating any errors)
                 result?;
                 return Ok(());
             }
             Err(std::sync::mpsc::TryRecvError::Empty) => {
-                // Download is still in progress, continue processing progress updates
+                // Operation is still in progress, continue processing progress updates
             }
             Err(std::sync::mpsc::TryRecvError::Disconnected) => {
-                bail!("Download thread disconnected unexpectedly without returning a result");
+                bail!("Worker thread disconnected unexpectedly without returning a result");
             }
         }
     }
 }
 
-fn bridge_wait_exit_then_apply_update(
-    manager: &UpdateManagerOpaque,
-    to_apply: &ffi::VelopackAssetDto,
-    silent: bool,
-    restart: bool,
-    restart_args: &Vec<String>,
+unsafe fn apply_updates_and_restart(pManager: *mut vpkc_update_manager_t, asset: VelopackAsset, restart_args: Vec<String>) -> Result<()> {
+    require_manager(pManager)?.obj.apply_updates_and_restart_with_args(&asset, &restart_args)?;
+    Ok(())
+}
+
+/// Applies a staged or downloaded update, restarting the app with the given arguments. This will exit the process.
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_apply_updates_and_restart(
+    pManager: *mut vpkc_update_manager_t,
+    pAsset: *mut vpkc_asset_t,
+    pRestartArgs: *mut *mut c_char,
+    cRestartArgs: usize,
+) -> bool {
+    track_error(|| {
+        let asset = velopackasset_v1_to_core(require_asset_v1(pAsset)?);
+        apply_updates_and_restart(pManager, asset, string_array_to_core(pRestartArgs, cRestartArgs))
+    })
+    .is_some()
+}
+
+/// Applies a staged or downloaded update, restarting the app with the given arguments. This will exit the process.
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_apply_updates_and_restart_v2(
+    pManager: *mut vpkc_update_manager_t,
+    pAsset: *mut vpkc_asset_v2_t,
+    pRestartArgs: *mut *mut c_char,
+    cRestartArgs: usize,
+) -> bool {
+    track_error(|| {
+        let asset = velopackasset_to_core(require_asset_v2(pAsset)?);
+        apply_updates_and_restart(pManager, asset, string_array_to_core(pRestartArgs, cRestartArgs))
+    })
+    .is_some()
+}
+
+unsafe fn apply_updates_and_exit(pManager: *mut vpkc_update_manager_t, asset: VelopackAsset) -> Result<()> {
+    require_manager(pManager)?.obj.apply_updates_and_exit(&asset)?;
+    Ok(())
+}
+
+/// Applies a staged or downloaded update without restarting the app. This will exit the process.
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_apply_updates_and_exit(pManager: *mut vpkc_update_manager_t, pAsset: *mut vpkc_asset_t) -> bool {
+    track_error(|| apply_updates_and_exit(pManager, velopackasset_v1_to_core(require_asset_v1(pAsset)?))).is_some()
+}
+
+/// Applies a staged or downloaded update without restarting the app. This will exit the process.
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_apply_updates_and_exit_v2(pManager: *mut vpkc_update_manager_t, pAsset: *mut vpkc_asset_v2_t) -> bool {
+    track_error(|| apply_updates_and_exit(pManager, velopackasset_to_core(require_asset_v2(pAsset)?))).is_some()
+}
+
+unsafe fn wait_exit_then_apply_update(
+    pManager: *mut vpkc_update_manager_t,
+    asset: VelopackAsset,
+    bSilent: bool,
+    bRestart: bool,
+    restart_args: Vec<String>,
 ) -> Result<()> {
-    let asset = velopackasset_to_core(&to_apply);
-    manager.obj.wait_exit_then_apply_updates(&asset, silent, restart, restart_args)?;
+    require_manager(pManager)?.obj.wait_exit_then_apply_updates(&asset, bSilent, bRestart, &restart_args)?;
     Ok(())
 }
 
-fn bridge_appbuilder_run(
-    cb: &ffi::HookCallbackManager,
-    custom_args: &ffi::StringArrayOption,
-    locator: &ffi::VelopackLocatorConfigDtoOption,
+/// Starts the updater, which waits for this process to exit before applying the update. The app should exit soon after.
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_wait_exit_then_apply_update(
+    pManager: *mut vpkc_update_manager_t,
+    pAsset: *mut vpkc_asset_t,
+    bSilent: bool,
+    bRestart: bool,
+    pRestartArgs: *mut *mut c_char,
+    cRestartArgs: usize,
+) -> bool {
+    track_error(|| {
+        let asset = velopackasset_v1_to_core(require_asset_v1(pAsset)?);
+        wait_exit_then_apply_update(pManager, asset, bSilent, bRestart, string_array_to_core(pRestartArgs, cRestartArgs))
+    })
+    .is_some()
+}
+
+/// Starts the updater, which waits for this process to exit before applying the update. The app should exit soon after.
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_wait_exit_then_apply_update_v2(
+    pManager: *mut vpkc_update_manager_t,
+    pAsset: *mut vpkc_asset_v2_t,
+    bSilent: bool,
+    bRestart: bool,
+    pRestartArgs: *mut *mut c_char,
+    cRestartArgs: usize,
+) -> bool {
+    track_error(|| {
+        let asset = velopackasset_to_core(require_asset_v2(pAsset)?);
+        wait_exit_then_apply_update(pManager, asset, bSilent, bRestart, string_array_to_core(pRestartArgs, cRestartArgs))
+    })
+    .is_some()
+}
+
+unsafe fn apply_updates_on_exit(
+    pManager: *mut vpkc_update_manager_t,
+    asset: VelopackAsset,
+    bSilent: bool,
+    bRestart: bool,
+    restart_args: Vec<String>,
+) -> Result<()> {
+    require_manager(pManager)?.obj.apply_updates_on_exit(&asset, bSilent, bRestart, &restart_args)?;
+    Ok(())
+}
+
+/// Starts the updater, which waits for this process to exit whenever that happens, and then applies the update.
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_apply_updates_on_exit(
+    pManager: *mut vpkc_update_manager_t,
+    pAsset: *mut vpkc_asset_t,
+    bSilent: bool,
+    bRestart: bool,
+    pRestartArgs: *mut *mut c_char,
+    cRestartArgs: usize,
+) -> bool {
+    track_error(|| {
+        let asset = velopackasset_v1_to_core(require_asset_v1(pAsset)?);
+        apply_updates_on_exit(pManager, asset, bSilent, bRestart, string_array_to_core(pRestartArgs, cRestartArgs))
+    })
+    .is_some()
+}
+
+/// Starts the updater, which waits for this process to exit whenever that happens, and then applies the update.
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_apply_updates_on_exit_v2(
+    pManager: *mut vpkc_update_manager_t,
+    pAsset: *mut vpkc_asset_v2_t,
+    bSilent: bool,
+    bRestart: bool,
+    pRestartArgs: *mut *mut c_char,
+    cRestartArgs: usize,
+) -> bool {
+    track_error(|| {
+        let asset = velopackasset_to_core(require_asset_v2(pAsset)?);
+        apply_updates_on_exit(pManager, asset, bSilent, bRestart, string_array_to_core(pRestartArgs, cRestartArgs))
+    })
+    .is_some()
+}
+
+// VelopackApp
+#[derive(Clone, Copy)]
+struct AppHooks {
+    after_install: vpkc_hook_callback_t,
+    before_uninstall: vpkc_hook_callback_t,
+    before_uninstall_with_reason: vpkc_uninstall_hook_callback_t,
+    before_update: vpkc_hook_callback_t,
+    after_update: vpkc_hook_callback_t,
+    first_run: vpkc_hook_callback_t,
+    restarted: vpkc_hook_callback_t,
+}
+
+struct AppOptions {
     auto_apply: bool,
-) {
+    args: Option<Vec<String>>,
+    locator: Option<VelopackLocatorConfig>,
+    hooks: AppHooks,
+}
+
+static APP_OPTIONS: Mutex<AppOptions> = Mutex::new(AppOptions {
+    auto_apply: true,
+    args: None,
+    locator: None,
+    hooks: AppHooks {
+        after_install: None,
+        before_uninstall: None,
+        before_uninstall_with_reason: None,
+        before_update: None,
+        after_update: None,
+        first_run: None,
+        restarted: None,
+    },
+});
+
+fn update_app_options<F: FnOnce(&mut AppOptions)>(update: F) {
+    if let Ok(mut options) = APP_OPTIONS.lock() {
+        update(&mut options);
+    }
+}
+
+fn invoke_hook(hook: vpkc_hook_callback_t, app_version: &str) {
+    if let Some(hook) = hook {
+        let app_version = string_to_cstring(app_version);
+        hook(app_version.as_ptr());
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn vpkc_app_set_auto_apply_on_startup(bAutoApply: bool) {
+    update_app_options(|o| o.auto_apply = bAutoApply);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_app_set_args(pArgs: *mut *mut c_char, cArgs: usize) {
+    let args = string_array_to_core(pArgs, cArgs);
+    update_app_options(|o| o.args = Some(args));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_app_set_locator(pLocator: *mut vpkc_locator_config_t) {
+    let locator = pLocator.as_ref().map(|l| velopacklocatorconfig_to_core(l));
+    update_app_options(|o| o.locator = locator);
+}
+
+#[no_mangle]
+pub extern "C" fn vpkc_app_set_hook_after_install(cbAfterInstall: vpkc_hook_callback_t) {
+    update_app_options(|o| o.hooks.after_install = cbAfterInstall);
+}
+
+#[no_mangle]
+pub extern "C" fn vpkc_app_set_hook_before_uninstall(cbBeforeUninstall: vpkc_hook_callback_t) {
+    update_app_options(|o| o.hooks.before_uninstall = cbBeforeUninstall);
+}
+
+#[no_mangle]
+pub extern "C" fn vpkc_app_set_hook_before_uninstall_with_reason(cbBeforeUninstall: vpkc_uninstall_hook_callback_t) {
+    update_app_options(|o| o.hooks.before_uninstall_with_reason = cbBeforeUninstall);
+}
+
+#[no_mangle]
+pub extern "C" fn vpkc_app_set_hook_before_update(cbBeforeUpdate: vpkc_hook_callback_t) {
+    update_app_options(|o| o.hooks.before_update = cbBeforeUpdate);
+}
+
+#[no_mangle]
+pub extern "C" fn vpkc_app_set_hook_after_update(cbAfterUpdate: vpkc_hook_callback_t) {
+    update_app_options(|o| o.hooks.after_update = cbAfterUpdate);
+}
+
+#[no_mangle]
+pub extern "C" fn vpkc_app_set_hook_first_run(cbFirstRun: vpkc_hook_callback_t) {
+    update_app_options(|o| o.hooks.first_run = cbFirstRun);
+}
+
+#[no_mangle]
+pub extern "C" fn vpkc_app_set_hook_restarted(cbRestarted: vpkc_hook_callback_t) {
+    update_app_options(|o| o.hooks.restarted = cbRestarted);
+}
+
+/// Runs the Velopack startup logic, which may run a hook and exit, or apply a pending update and restart the app. This
+/// should be called as early as possible in the application's main function.
+#[no_mangle]
+pub extern "C" fn vpkc_app_run() {
+    // copy the options out, so that the hooks can call vpkc_app_* functions without deadlocking
+    let (auto_apply, args, locator, hooks) = match APP_OPTIONS.lock() {
+        Ok(o) => (o.auto_apply, o.args.clone(), o.locator.clone(), o.hooks),
+        Err(_) => return,
+    };
+
     let mut app = VelopackApp::build()
         .set_auto_apply_on_startup(auto_apply)
-        .on_first_run(|v| cb.firstrun_hook(v.to_string()))
-        .on_restarted(|v| cb.restarted_hook(v.to_string()));
+        .on_first_run(|v| invoke_hook(hooks.first_run, &v.to_string()))
+        .on_restarted(|v| invoke_hook(hooks.restarted, &v.to_string()));
 
     #[cfg(windows)]
     {
         app = app
-            .on_after_install_fast_callback(|v| cb.install_hook(v.to_string()))
-            .on_after_update_fast_callback(|v| cb.update_hook(v.to_string()))
-            .on_before_update_fast_callback(|v| cb.obsolete_hook(v.to_string()))
-            .on_before_uninstall_fast_callback(|v| cb.uninstall_hook(v.to_string()));
+            .on_after_install_fast_callback(|v| invoke_hook(hooks.after_install, &v.to_string()))
+            .on_after_update_fast_callback(|v| invoke_hook(hooks.after_update, &v.to_string()))
+            .on_before_update_fast_callback(|v| invoke_hook(hooks.before_update, &v.to_string()))
+            .on_before_uninstall_fast_callback(|v| invoke_hook(hooks.before_uninstall, &v.to_string()))
+            .on_before_uninstall_with_reason_fast_callback(|v, r| {
+                if let Some(hook) = hooks.before_uninstall_with_reason {
+                    let app_version = string_to_cstring(&v.to_string());
+                    let reason = r.filter(|r| !r.is_empty()).map(|r| string_to_cstring(&r));
+                    hook(app_version.as_ptr(), reason.as_ref().map(|r| r.as_ptr()).unwrap_or(std::ptr::null()));
+                }
+            });
     }
 
-    if locator.has_data {
-        let locator = velopacklocatorconfig_to_core(&locator.data);
+    if let Some(locator) = locator {
         app = app.set_locator(locator);
     }
 
-    if custom_args.has_data {
-        app = app.set_args(custom_args.data.clone());
+    if let Some(args) = args {
+        app = app.set_args(args);
     }
 
     app.run();
 }
 
+// Misc functions
+/// Copies the message of the last error on the calling thread into pszError (without a null terminator), and returns the
+/// number of bytes copied. If pszError is NULL, returns the length of the message instead.
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_get_last_error(pszError: *mut c_char, cError: usize) -> usize {
+    LAST_ERROR.with(|e| return_c_string(&e.borrow().1, pszError, cError))
+}
+
+/// Returns the code of the last error on the calling thread, or VPKC_OK if the last call succeeded.
+#[no_mangle]
+pub extern "C" fn vpkc_get_last_error_code() -> vpkc_error_code_t {
+    LAST_ERROR.with(|e| e.borrow().0)
+}
+
 struct LoggerImpl {}
 
 static LOGGER: LoggerImpl = LoggerImpl {};
@@ -307,35 +783,116 @@ impl Log for LoggerImpl {
         }
         .to_string();
 
-        if let Some(cb) = get_logger() {
-            if let Some(cb) = unsafe { cb.as_mut() } {
-                cb.log(level, text);
+        if LOG_QUEUED.load(Ordering::SeqCst) {
+            if let Ok(mut queue) = LOG_QUEUE.lock() {
+                if queue.len() >= LOG_QUEUE_CAPACITY {
+                    queue.pop_front();
+                }
+                queue.push_back((level, text));
             }
+        } else {
+            deliver_log(level, text);
         }
     }
 
     fn flush(&self) {}
 }
 
-lazy_static::lazy_static! {
-    static ref LOGGER_CB: AtomicUsize = AtomicUsize::new(0);
-}
+static LOGGER_CB: Mutex<vpkc_log_callback_t> = Mutex::new(None);
+static LOG_QUEUE: Mutex<VecDeque<(String, String)>> = Mutex::new(VecDeque::new());
+// held while invoking the callback, so that it is never called concurrently from multiple threads
+static LOG_DELIVERY: Mutex<()> = Mutex::new(());
 
-fn store_logger(ptr: *mut ffi::LoggerCallbackManager) {
-    LOGGER_CB.store(ptr as usize, Ordering::SeqCst);
-}
+static LOG_QUEUED: AtomicBool = AtomicBool::new(false);
+const LOG_QUEUE_CAPACITY: usize = 10_000;
 
-fn get_logger() -> Option<*mut ffi::LoggerCallbackManager> {
-    let ptr = LOGGER_CB.load(Ordering::SeqCst);
-    if ptr == 0 {
-        None
-    } else {
-        Some(ptr as *mut ffi::LoggerCallbackManager)
+fn deliver_log(level: String, text: String) {
+    // messages logged by the callback itself (eg. by calling a vpkc_* function) are dropped, since taking the delivery
+    // lock again on the same thread would deadlock
+    if IN_LOG_CALLBACK.with(|c| c.get()) {
+        return;
+    }
+    if let Some(cb) = LOGGER_CB.lock().ok().and_then(|cb| *cb) {
+        let level = string_to_cstring(&level);
+        let text = string_to_cstring(&text);
+        let _guard = LOG_DELIVERY.lock();
+        IN_LOG_CALLBACK.with(|c| c.set(true));
+        cb(level.as_ptr(), text.as_ptr());
+        IN_LOG_CALLBACK.with(|c| c.set(false));
     }
 }
 
-unsafe fn bridge_set_logger_callback(cb: *mut ffi::LoggerCallbackManager) {
+/// Sets the callback which receives log messages. By default, the log callback is invoked directly from whichever thread
+/// produced the message (Velopack does some work on background threads), but calls are serialized so it is never invoked
+/// concurrently. Messages logged while the callback is running on the same thread (eg. by a vpkc_* function it calls)
+/// are discarded.
+#[no_mangle]
+pub extern "C" fn vpkc_set_log(cbLog: vpkc_log_callback_t) {
     let _ = log::set_logger(&LOGGER);
     log::set_max_level(log::LevelFilter::Trace);
-    store_logger(cb);
+    if let Ok(mut cb) = LOGGER_CB.lock() {
+        *cb = cbLog;
+    }
+}
+
+/// If your logging system is not thread-safe, enable queued mode: messages are then buffered (up to 10,000, oldest dropped
+/// first) and only delivered to the callback on the thread which calls vpkc_drain_log. Disabling queued mode drains any
+/// buffered messages.
+#[no_mangle]
+pub extern "C" fn vpkc_set_log_queued(bQueued: bool) {
+    LOG_QUEUED.store(bQueued, Ordering::SeqCst);
+    if !bQueued {
+        // deliver anything which was queued before switching back to direct delivery
+        vpkc_drain_log();
+    }
+}
+
+/// Delivers any queued log messages to the log callback on the calling thread, and returns the number delivered.
+#[no_mangle]
+pub extern "C" fn vpkc_drain_log() -> usize {
+    // take the messages out of the queue first, so the callback can log without deadlocking
+    let messages: Vec<(String, String)> = match LOG_QUEUE.lock() {
+        Ok(mut queue) => queue.drain(..).collect(),
+        Err(_) => return 0,
+    };
+    let count = messages.len();
+    for (level, text) in messages {
+        deliver_log(level, text);
+    }
+    count
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_free_update_manager(pManager: *mut vpkc_update_manager_t) {
+    if !pManager.is_null() {
+        drop(Box::from_raw(pManager as *mut UpdateManagerOpaque));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_free_update_info(pUpdateInfo: *mut vpkc_update_info_t) {
+    if let Some(update) = pUpdateInfo.as_mut() {
+        free_updateinfo_v1(update);
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_free_update_info_v2(pUpdateInfo: *mut vpkc_update_info_v2_t) {
+    if let Some(update) = pUpdateInfo.as_mut() {
+        free_updateinfo(update);
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_free_asset(pAsset: *mut vpkc_asset_t) {
+    if let Some(asset) = pAsset.as_mut() {
+        free_velopackasset_v1(asset);
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn vpkc_free_asset_v2(pAsset: *mut vpkc_asset_v2_t) {
+    if let Some(asset) = pAsset.as_mut() {
+        free_velopackasset(asset);
+    }
 }
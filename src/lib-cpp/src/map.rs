@@ -1,160 +1,251 @@
+use crate::types::*;
+use std::ffi::{c_char, CStr, CString};
 use std::path::PathBuf;
 use velopack::locator::VelopackLocatorConfig;
 use velopack::{UpdateInfo, UpdateOptions, VelopackAsset};
-use crate::ffi::*;
 
-fn pathbuf_to_core(dto: &String) -> PathBuf {
-    PathBuf::from(dto)
+pub unsafe fn string_to_core(psz: *const c_char) -> String {
+    string_to_core_option(psz).unwrap_or_default()
 }
 
-fn pathbuf_to_bridge(dto: &PathBuf) -> String {
-    dto.to_string_lossy().to_string()
-}
-
-fn string_to_core(dto: &String) -> String {
-    dto.clone()
+pub unsafe fn string_to_core_option(psz: *const c_char) -> Option<String> {
+    if psz.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(psz).to_string_lossy().to_string())
+    }
 }
 
-fn string_to_bridge(dto: &String) -> String {
-    dto.clone()
+unsafe fn pathbuf_to_core(psz: *const c_char) -> PathBuf {
+    PathBuf::from(string_to_core(psz))
 }
 
-fn bool_to_core(dto: &bool) -> bool {
-    *dto
+/// Converts a string to a C string, dropping any interior nul characters which can not be represented.
+pub fn string_to_cstring(value: &str) -> CString {
+    CString::new(value.replace('\0', "")).unwrap_or_default()
 }
 
-fn bool_to_bridge(dto: &bool) -> bool {
-    *dto
+/// Allocates a C string which must be released with free_string.
+pub fn allocate_string(value: &str, ppsz: &mut *mut c_char) {
+    *ppsz = string_to_cstring(value).into_raw();
 }
 
-fn u64_to_core(dto: &u64) -> u64 {
-    *dto
+fn allocate_string_option(value: &Option<String>, ppsz: &mut *mut c_char) {
+    match value {
+        Some(value) => allocate_string(value, ppsz),
+        None => *ppsz = std::ptr::null_mut(),
+    }
 }
 
-fn u64_to_bridge(dto: &u64) -> u64 {
-    *dto
+fn allocate_pathbuf(value: &PathBuf, ppsz: &mut *mut c_char) {
+    allocate_string(&value.to_string_lossy(), ppsz);
 }
 
-// !! AUTO-GENERATED-START CORE_MAPPING
-pub fn velopacklocatorconfig_to_core(dto: &VelopackLocatorConfigDto) -> VelopackLocatorConfig {
-    VelopackLocatorConfig {
-        RootAppDir: pathbuf_to_core(&dto.RootAppDir),
-        UpdateExePath: pathbuf_to_core(&dto.UpdateExePath),
-        PackagesDir: pathbuf_to_core(&dto.PackagesDir),
-        ManifestPath: pathbuf_to_core(&dto.ManifestPath),
-        CurrentBinaryDir: pathbuf_to_core(&dto.CurrentBinaryDir),
-        IsPortable: bool_to_core(&dto.IsPortable),
+/// Frees a C string which was allocated by allocate_string.
+pub unsafe fn free_string(ppsz: &mut *mut c_char) {
+    if !ppsz.is_null() {
+        drop(CString::from_raw(*ppsz));
+        *ppsz = std::ptr::null_mut();
     }
 }
 
-pub fn velopacklocatorconfig_to_bridge(dto: &VelopackLocatorConfig) -> VelopackLocatorConfigDto {
-    VelopackLocatorConfigDto {
-        RootAppDir: pathbuf_to_bridge(&dto.RootAppDir),
-        UpdateExePath: pathbuf_to_bridge(&dto.UpdateExePath),
-        PackagesDir: pathbuf_to_bridge(&dto.PackagesDir),
-        ManifestPath: pathbuf_to_bridge(&dto.ManifestPath),
-        CurrentBinaryDir: pathbuf_to_bridge(&dto.CurrentBinaryDir),
-        IsPortable: bool_to_bridge(&dto.IsPortable),
+pub unsafe fn velopackasset_v1_to_core(dto: &vpkc_asset_t) -> VelopackAsset {
+    VelopackAsset {
+        PackageId: string_to_core(dto.PackageId),
+        Version: string_to_core(dto.Version),
+        Type: string_to_core(dto.Type),
+        FileName: string_to_core(dto.FileName),
+        SHA1: string_to_core(dto.SHA1),
+        SHA256: string_to_core(dto.SHA256),
+        Size: dto.Size,
+        NotesMarkdown: string_to_core(dto.NotesMarkdown),
+        NotesHtml: string_to_core(dto.NotesHtml),
+        ..Default::default()
     }
 }
 
-pub fn velopacklocatorconfig_to_core_option(dto: &VelopackLocatorConfigDtoOption) -> Option<VelopackLocatorConfig> {
-    if dto.has_data { Some(velopacklocatorconfig_to_core(&dto.data)) } else { None }
+pub fn allocate_velopackasset_v1(obj: &VelopackAsset, dto: &mut vpkc_asset_t) {
+    allocate_string(&obj.PackageId, &mut dto.PackageId);
+    allocate_string(&obj.Version, &mut dto.Version);
+    allocate_string(&obj.Type, &mut dto.Type);
+    allocate_string(&obj.FileName, &mut dto.FileName);
+    allocate_string(&obj.SHA1, &mut dto.SHA1);
+    allocate_string(&obj.SHA256, &mut dto.SHA256);
+    dto.Size = obj.Size;
+    allocate_string(&obj.NotesMarkdown, &mut dto.NotesMarkdown);
+    allocate_string(&obj.NotesHtml, &mut dto.NotesHtml);
 }
 
-pub fn velopacklocatorconfig_to_bridge_option(dto: &Option<VelopackLocatorConfig>) -> VelopackLocatorConfigDtoOption {
-    match dto {
-        Some(dto) => VelopackLocatorConfigDtoOption { data: velopacklocatorconfig_to_bridge(dto), has_data: true },
-        None => VelopackLocatorConfigDtoOption { data: Default::default(), has_data: false },
-    }
+pub unsafe fn free_velopackasset_v1(dto: &mut vpkc_asset_t) {
+    free_string(&mut dto.PackageId);
+    free_string(&mut dto.Version);
+    free_string(&mut dto.Type);
+    free_string(&mut dto.FileName);
+    free_string(&mut dto.SHA1);
+    free_string(&mut dto.SHA256);
+    free_string(&mut dto.NotesMarkdown);
+    free_string(&mut dto.NotesHtml);
 }
 
-pub fn velopackasset_to_core(dto: &VelopackAssetDto) -> VelopackAsset {
-    VelopackAsset {
-        PackageId: string_to_core(&dto.PackageId),
-        Version: string_to_core(&dto.Version),
-        Type: string_to_core(&dto.Type),
-        FileName: string_to_core(&dto.FileName),
-        SHA1: string_to_core(&dto.SHA1),
-        SHA256: string_to_core(&dto.SHA256),
-        Size: u64_to_core(&dto.Size),
-        NotesMarkdown: string_to_core(&dto.NotesMarkdown),
-        NotesHtml: string_to_core(&dto.NotesHtml),
-    }
+pub unsafe fn updateinfo_v1_to_core(dto: &vpkc_update_info_t) -> UpdateInfo {
+    UpdateInfo { TargetFullRelease: velopackasset_v1_to_core(&dto.TargetFullRelease), IsDowngrade: dto.IsDowngrade, ..Default::default() }
 }
 
-pub fn velopackasset_to_bridge(dto: &VelopackAsset) -> VelopackAssetDto {
-    VelopackAssetDto {
-        PackageId: string_to_bridge(&dto.PackageId),
-        Version: string_to_bridge(&dto.Version),
-        Type: string_to_bridge(&dto.Type),
-        FileName: string_to_bridge(&dto.FileName),
-        SHA1: string_to_bridge(&dto.SHA1),
-        SHA256: string_to_bridge(&dto.SHA256),
-        Size: u64_to_bridge(&dto.Size),
-        NotesMarkdown: string_to_bridge(&dto.NotesMarkdown),
-        NotesHtml: string_to_bridge(&dto.NotesHtml),
-    }
+pub fn allocate_updateinfo_v1(obj: &UpdateInfo, dto: &mut vpkc_update_info_t) {
+    allocate_velopackasset_v1(&obj.TargetFullRelease, &mut dto.TargetFullRelease);
+    dto.IsDowngrade = obj.IsDowngrade;
 }
 
-pub fn velopackasset_to_core_option(dto: &VelopackAssetDtoOption) -> Option<VelopackAsset> {
-    if dto.has_data { Some(velopackasset_to_core(&dto.data)) } else { None }
+pub unsafe fn free_updateinfo_v1(dto: &mut vpkc_update_info_t) {
+    free_velopackasset_v1(&mut dto.TargetFullRelease);
 }
 
-pub fn velopackasset_to_bridge_option(dto: &Option<VelopackAsset>) -> VelopackAssetDtoOption {
-    match dto {
-        Some(dto) => VelopackAssetDtoOption { data: velopackasset_to_bridge(dto), has_data: true },
-        None => VelopackAssetDtoOption { data: Default::default(), has_data: false },
+pub unsafe fn updateoptions_v1_to_core(dto: &vpkc_update_options_t) -> UpdateOptions {
+    UpdateOptions {
+        AllowVersionDowngrade: dto.AllowVersionDowngrade,
+        ExplicitChannel: string_to_core_option(dto.ExplicitChannel),
+        ..Default::default()
     }
 }
 
-pub fn updateinfo_to_core(dto: &UpdateInfoDto) -> UpdateInfo {
-    UpdateInfo {
-        TargetFullRelease: velopackasset_to_core(&dto.TargetFullRelease),
-        IsDowngrade: bool_to_core(&dto.IsDowngrade),
+// !! AUTO-GENERATED-START CORE_MAPPING
+pub unsafe fn velopacklocatorconfig_to_core(dto: &vpkc_locator_config_t) -> VelopackLocatorConfig {
+    VelopackLocatorConfig {
+        RootAppDir: pathbuf_to_core(dto.RootAppDir),
+        UpdateExePath: pathbuf_to_core(dto.UpdateExePath),
+        PackagesDir: pathbuf_to_core(dto.PackagesDir),
+        ManifestPath: pathbuf_to_core(dto.ManifestPath),
+        CurrentBinaryDir: pathbuf_to_core(dto.CurrentBinaryDir),
+        IsPortable: dto.IsPortable,
     }
 }
 
-pub fn updateinfo_to_bridge(dto: &UpdateInfo) -> UpdateInfoDto {
-    UpdateInfoDto {
-        TargetFullRelease: velopackasset_to_bridge(&dto.TargetFullRelease),
-        IsDowngrade: bool_to_bridge(&dto.IsDowngrade),
-    }
+pub fn allocate_velopacklocatorconfig(obj: &VelopackLocatorConfig, dto: &mut vpkc_locator_config_t) {
+    allocate_pathbuf(&obj.RootAppDir, &mut dto.RootAppDir);
+    allocate_pathbuf(&obj.UpdateExePath, &mut dto.UpdateExePath);
+    allocate_pathbuf(&obj.PackagesDir, &mut dto.PackagesDir);
+    allocate_pathbuf(&obj.ManifestPath, &mut dto.ManifestPath);
+    allocate_pathbuf(&obj.CurrentBinaryDir, &mut dto.CurrentBinaryDir);
+    dto.IsPortable = obj.IsPortable;
 }
 
-pub fn updateinfo_to_core_option(dto: &UpdateInfoDtoOption) -> Option<UpdateInfo> {
-    if dto.has_data { Some(updateinfo_to_core(&dto.data)) } else { None }
+pub unsafe fn free_velopacklocatorconfig(dto: &mut vpkc_locator_config_t) {
+    free_string(&mut dto.RootAppDir);
+    free_string(&mut dto.UpdateExePath);
+    free_string(&mut dto.PackagesDir);
+    free_string(&mut dto.ManifestPath);
+    free_string(&mut dto.CurrentBinaryDir);
 }
 
-pub fn updateinfo_to_bridge_option(dto: &Option<UpdateInfo>) -> UpdateInfoDtoOption {
-    match dto {
-        Some(dto) => UpdateInfoDtoOption { data: updateinfo_to_bridge(dto), has_data: true },
-        None => UpdateInfoDtoOption { data: Default::default(), has_data: false },
+pub unsafe fn velopackasset_to_core(dto: &vpkc_asset_v2_t) -> VelopackAsset {
+    VelopackAsset {
+        PackageId: string_to_core(dto.PackageId),
+        Version: string_to_core(dto.Version),
+        Type: string_to_core(dto.Type),
+        FileName: string_to_core(dto.FileName),
+        SHA1: string_to_core(dto.SHA1),
+        SHA256: string_to_core(dto.SHA256),
+        Size: dto.Size,
+        NotesMarkdown: string_to_core(dto.NotesMarkdown),
+        NotesHtml: string_to_core(dto.NotesHtml),
+        IsMandatory: dto.IsMandatory,
+        Architecture: string_to_core(dto.Architecture),
     }
 }
 
-pub fn updateoptions_to_core(dto: &UpdateOptionsDto) -> UpdateOptions {
-    UpdateOptions {
-        AllowVersionDowngrade: bool_to_core(&dto.AllowVersionDowngrade),
-        ExplicitChannel: if dto.ExplicitChannel.has_data { Some(string_to_core(&dto.ExplicitChannel.data)) } else { None },
+pub fn allocate_velopackasset(obj: &VelopackAsset, dto: &mut vpkc_asset_v2_t) {
+    allocate_string(&obj.PackageId, &mut dto.PackageId);
+    allocate_string(&obj.Version, &mut dto.Version);
+    allocate_string(&obj.Type, &mut dto.Type);
+    allocate_string(&obj.FileName, &mut dto.FileName);
+    allocate_string(&obj.SHA1, &mut dto.SHA1);
+    allocate_string(&obj.SHA256, &mut dto.SHA256);
+    dto.Size = obj.Size;
+    allocate_string(&obj.NotesMarkdown, &mut dto.NotesMarkdown);
+    allocate_string(&obj.NotesHtml, &mut dto.NotesHtml);
+    dto.IsMandatory = obj.IsMandatory;
+    allocate_string(&obj.Architecture, &mut dto.Architecture);
+}
+
+pub unsafe fn free_velopackasset(dto: &mut vpkc_asset_v2_t) {
+    free_string(&mut dto.PackageId);
+    free_string(&mut dto.Version);
+    free_string(&mut dto.Type);
+    free_string(&mut dto.FileName);
+    free_string(&mut dto.SHA1);
+    free_string(&mut dto.SHA256);
+    free_string(&mut dto.NotesMarkdown);
+    free_string(&mut dto.NotesHtml);
+    free_string(&mut dto.Architecture);
+}
+
+pub unsafe fn updateinfo_to_core(dto: &vpkc_update_info_v2_t) -> UpdateInfo {
+    UpdateInfo {
+        TargetFullRelease: velopackasset_to_core(&dto.TargetFullRelease),
+        IsDowngrade: dto.IsDowngrade,
+        IsMandatory: dto.IsMandatory,
+        IsStale: dto.IsStale,
+        ..Default::default()
     }
 }
 
-pub fn updateoptions_to_bridge(dto: &UpdateOptions) -> UpdateOptionsDto {
-    UpdateOptionsDto {
-        AllowVersionDowngrade: bool_to_bridge(&dto.AllowVersionDowngrade),
-        ExplicitChannel: StringOption { data: string_to_bridge(&dto.ExplicitChannel.clone().unwrap_or_default()), has_data: dto.ExplicitChannel.is_some() },
-    }
+pub fn allocate_updateinfo(obj: &UpdateInfo, dto: &mut vpkc_update_info_v2_t) {
+    dto.TargetFullRelease.cbSize = std::mem::size_of::<vpkc_asset_v2_t>();
+    allocate_velopackasset(&obj.TargetFullRelease, &mut dto.TargetFullRelease);
+    dto.IsDowngrade = obj.IsDowngrade;
+    dto.IsMandatory = obj.IsMandatory;
+    dto.IsStale = obj.IsStale;
 }
 
-pub fn updateoptions_to_core_option(dto: &UpdateOptionsDtoOption) -> Option<UpdateOptions> {
-    if dto.has_data { Some(updateoptions_to_core(&dto.data)) } else { None }
+pub unsafe fn free_updateinfo(dto: &mut vpkc_update_info_v2_t) {
+    free_velopackasset(&mut dto.TargetFullRelease);
 }
 
-pub fn updateoptions_to_bridge_option(dto: &Option<UpdateOptions>) -> UpdateOptionsDtoOption {
-    match dto {
-        Some(dto) => UpdateOptionsDtoOption { data: updateoptions_to_bridge(dto), has_data: true },
-        None => UpdateOptionsDtoOption { data: Default::default(), has_data: false },
+pub unsafe fn updateoptions_to_core(dto: &vpkc_update_options_v2_t) -> UpdateOptions {
+    UpdateOptions {
+        AllowVersionDowngrade: dto.AllowVersionDowngrade,
+        ExplicitChannel: string_to_core_option(dto.ExplicitChannel),
+        AllowPrerelease: dto.AllowPrerelease,
+        UpdateCheckIntervalSeconds: dto.UpdateCheckIntervalSeconds,
+        PackagesToKeep: dto.PackagesToKeep,
+        MaxPackagesSizeBytes: dto.MaxPackagesSizeBytes,
+        MaxPackageAgeDays: dto.MaxPackageAgeDays,
+        PackagesDir: string_to_core_option(dto.PackagesDir),
+        SharedCacheDir: string_to_core_option(dto.SharedCacheDir),
+        SharedCacheMaxSizeBytes: dto.SharedCacheMaxSizeBytes,
+        CacheServerUrl: string_to_core_option(dto.CacheServerUrl),
+        UserAgent: string_to_core_option(dto.UserAgent),
+        FeedQueryParameters: string_to_core_option(dto.FeedQueryParameters),
+        RequestHeaders: string_to_core_option(dto.RequestHeaders),
+        UseCachedFeedWhenOffline: dto.UseCachedFeedWhenOffline,
     }
 }
-// !! AUTO-GENERATED-END CORE_MAPPING
\ No newline at end of file
+
+pub fn allocate_updateoptions(obj: &UpdateOptions, dto: &mut vpkc_update_options_v2_t) {
+    dto.AllowVersionDowngrade = obj.AllowVersionDowngrade;
+    allocate_string_option(&obj.ExplicitChannel, &mut dto.ExplicitChannel);
+    dto.AllowPrerelease = obj.AllowPrerelease;
+    dto.UpdateCheckIntervalSeconds = obj.UpdateCheckIntervalSeconds;
+    dto.PackagesToKeep = obj.PackagesToKeep;
+    dto.MaxPackagesSizeBytes = obj.MaxPackagesSizeBytes;
+    dto.MaxPackageAgeDays = obj.MaxPackageAgeDays;
+    allocate_string_option(&obj.PackagesDir, &mut dto.PackagesDir);
+    allocate_string_option(&obj.SharedCacheDir, &mut dto.SharedCacheDir);
+    dto.SharedCacheMaxSizeBytes = obj.SharedCacheMaxSizeBytes;
+    allocate_string_option(&obj.CacheServerUrl, &mut dto.CacheServerUrl);
+    allocate_string_option(&obj.UserAgent, &mut dto.UserAgent);
+    allocate_string_option(&obj.FeedQueryParameters, &mut dto.FeedQueryParameters);
+    allocate_string_option(&obj.RequestHeaders, &mut dto.RequestHeaders);
+    dto.UseCachedFeedWhenOffline = obj.UseCachedFeedWhenOffline;
+}
+
+pub unsafe fn free_updateoptions(dto: &mut vpkc_update_options_v2_t) {
+    free_string(&mut dto.ExplicitChannel);
+    free_string(&mut dto.PackagesDir);
+    free_string(&mut dto.SharedCacheDir);
+    free_string(&mut dto.CacheServerUrl);
+    free_string(&mut dto.UserAgent);
+    free_string(&mut dto.FeedQueryParameters);
+    free_string(&mut dto.RequestHeaders);
+}
+// !! AUTO-GENERATED-END CORE_MAPPING
@@ -0,0 +1,235 @@
+use std::ffi::{c_char, c_void};
+
+/// An opaque handle to an UpdateManager, which must be freed with vpkc_free_update_manager.
+pub type vpkc_update_manager_t = c_void;
+/// Receives the progress of a download or stage operation, from 0 to 100.
+pub type vpkc_progress_callback_t = Option<extern "C" fn(progress: usize)>;
+/// Receives a log message, the level is one of "error", "warn", "info", "debug" or "trace".
+pub type vpkc_log_callback_t = Option<extern "C" fn(pszLevel: *const c_char, pszMessage: *const c_char)>;
+/// Receives the version of the app which a hook is being run for.
+pub type vpkc_hook_callback_t = Option<extern "C" fn(pszAppVersion: *const c_char)>;
+/// Receives the version of the app being uninstalled, and the reason for uninstalling it (or NULL if there is none).
+pub type vpkc_uninstall_hook_callback_t = Option<extern "C" fn(pszAppVersion: *const c_char, pszReason: *const c_char)>;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum vpkc_update_check_t {
+    UPDATE_AVAILABLE = 0,
+    NO_UPDATE_AVAILABLE = 1,
+    UPDATE_ERROR = 2,
+    /// The update source could not be reached, and the release feed cached by the last successful update check did not
+    /// have an update available either. This is only returned if UseCachedFeedWhenOffline is enabled.
+    NO_UPDATE_AVAILABLE_OFFLINE = 3,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum vpkc_error_code_t {
+    VPKC_OK = 0,
+    VPKC_ERROR_GENERIC = 1,
+    VPKC_ERROR_INVALID_ARGUMENT = 2,
+    VPKC_ERROR_IO = 3,
+    VPKC_ERROR_NETWORK = 4,
+    VPKC_ERROR_NOT_INSTALLED = 5,
+    VPKC_ERROR_INVALID_DATA = 6,
+    VPKC_ERROR_CANCELLED = 7,
+    VPKC_ERROR_BUSY = 8,
+    VPKC_ERROR_HASH_MISMATCH = 9,
+    VPKC_ERROR_UNSUPPORTED = 10,
+    VPKC_ERROR_TRANSLOCATED = 11,
+    VPKC_ERROR_MANAGED_EXTERNALLY = 12,
+    VPKC_ERROR_LOCKED = 13,
+}
+
+// The structs below are the original layouts which applications have been built against, so they must never change.
+// New fields are only added to the _v2 structs, which start with a cbSize field and are generated by the type-generator.
+
+/// An individual Velopack asset, could refer to an asset on-disk or in a remote package feed.
+#[repr(C)]
+pub struct vpkc_asset_t {
+    /// The name or Id of the package containing this release.
+    pub PackageId: *mut c_char,
+    /// The version of this release.
+    pub Version: *mut c_char,
+    /// The type of asset (eg. "Full" or "Delta").
+    pub Type: *mut c_char,
+    /// The filename of the update package containing this release.
+    pub FileName: *mut c_char,
+    /// The SHA1 checksum of the update package containing this release.
+    pub SHA1: *mut c_char,
+    /// The SHA256 checksum of the update package containing this release.
+    pub SHA256: *mut c_char,
+    /// The size in bytes of the update package containing this release.
+    pub Size: u64,
+    /// The release notes in markdown format, as passed to Velopack when packaging the release. This may be an empty string.
+    pub NotesMarkdown: *mut c_char,
+    /// The release notes in HTML format, transformed from Markdown when packaging the release. This may be an empty string.
+    pub NotesHtml: *mut c_char,
+}
+
+/// Holds information about the current version and pending updates, such as how many there are, and access to release notes.
+#[repr(C)]
+pub struct vpkc_update_info_t {
+    /// The available version that we are updating to.
+    pub TargetFullRelease: vpkc_asset_t,
+    /// True if the update is a version downgrade or lateral move (such as when switching channels to the same version number).
+    /// In this case, only full updates are allowed, and any local packages on disk newer than the downloaded version will be
+    /// deleted.
+    pub IsDowngrade: bool,
+}
+
+/// Options to customise the behaviour of UpdateManager.
+#[repr(C)]
+pub struct vpkc_update_options_t {
+    /// Allows UpdateManager to update to a version that's lower than the current version (i.e. downgrading).
+    /// This could happen if a release has bugs and was retracted from the release feed, or if you're using
+    /// ExplicitChannel to switch channels to another channel where the latest version on that
+    /// channel is lower than the current version.
+    pub AllowVersionDowngrade: bool,
+    /// **This option should usually be left None**. <br/>
+    /// Overrides the default channel used to fetch updates.
+    /// The default channel will be whatever channel was specified on the command line when building this release.
+    /// For example, if the current release was packaged with '--channel beta', then the default channel will be 'beta'.
+    /// This allows users to automatically receive updates from the same channel they installed from. This options
+    /// allows you to explicitly switch channels, for example if the user wished to switch back to the 'stable' channel
+    /// without having to reinstall the application.
+    pub ExplicitChannel: *mut c_char,
+}
+
+// !! AUTO-GENERATED-START C_TYPES
+/// VelopackLocator provides some utility functions for locating the current app important paths (eg. path to packages, update binary, and so forth).
+#[repr(C)]
+pub struct vpkc_locator_config_t {
+    /// The root directory of the current app.
+    pub RootAppDir: *mut c_char,
+    /// The path to the Update.exe binary.
+    pub UpdateExePath: *mut c_char,
+    /// The path to the packages' directory.
+    pub PackagesDir: *mut c_char,
+    /// The current app manifest.
+    pub ManifestPath: *mut c_char,
+    /// The directory containing the application's user binaries.
+    pub CurrentBinaryDir: *mut c_char,
+    /// Whether the current application is portable or installed.
+    pub IsPortable: bool,
+}
+
+/// An individual Velopack asset, could refer to an asset on-disk or in a remote package feed.
+#[repr(C)]
+pub struct vpkc_asset_v2_t {
+    /// The size of this struct in bytes, which must be set to sizeof(vpkc_asset_v2_t).
+    pub cbSize: usize,
+    /// The name or Id of the package containing this release.
+    pub PackageId: *mut c_char,
+    /// The version of this release.
+    pub Version: *mut c_char,
+    /// The type of asset (eg. "Full" or "Delta").
+    pub Type: *mut c_char,
+    /// The filename of the update package containing this release.
+    pub FileName: *mut c_char,
+    /// The SHA1 checksum of the update package containing this release.
+    pub SHA1: *mut c_char,
+    /// The SHA256 checksum of the update package containing this release.
+    pub SHA256: *mut c_char,
+    /// The size in bytes of the update package containing this release.
+    pub Size: u64,
+    /// The release notes in markdown format, as passed to Velopack when packaging the release. This may be an empty string.
+    pub NotesMarkdown: *mut c_char,
+    /// The release notes in HTML format, transformed from Markdown when packaging the release. This may be an empty string.
+    pub NotesHtml: *mut c_char,
+    /// The release has been marked as mandatory by the publisher, and should not be skipped or postponed.
+    pub IsMandatory: bool,
+    /// The CPU architecture this release was built for (eg. "x64", "arm64" or "universal"). This may be an empty string,
+    /// in which case the release is assumed to run on any machine.
+    pub Architecture: *mut c_char,
+}
+
+/// Holds information about the current version and pending updates, such as how many there are, and access to release notes.
+#[repr(C)]
+pub struct vpkc_update_info_v2_t {
+    /// The size of this struct in bytes, which must be set to sizeof(vpkc_update_info_v2_t).
+    pub cbSize: usize,
+    /// The available version that we are updating to.
+    pub TargetFullRelease: vpkc_asset_v2_t,
+    /// True if the update is a version downgrade or lateral move (such as when switching channels to the same version number).
+    /// In this case, only full updates are allowed, and any local packages on disk newer than the downloaded version will be
+    /// deleted.
+    /// A release of the same version built for an architecture which suits this machine better (eg. a native ARM64 build
+    /// replacing an x64 build running under emulation) is also a lateral move.
+    pub IsDowngrade: bool,
+    /// True if the target release, or any release between the current version and the target version, is marked as mandatory.
+    /// Apps should not allow this update to be skipped, and may prevent usage until it has been applied.
+    pub IsMandatory: bool,
+    /// True if the update source could not be reached, and this update was found in the release feed cached by the last
+    /// successful update check (see UseCachedFeedWhenOffline). The update may no longer be available, or may be out of date.
+    pub IsStale: bool,
+}
+
+/// Options to customise the behaviour of UpdateManager.
+#[repr(C)]
+pub struct vpkc_update_options_v2_t {
+    /// The size of this struct in bytes, which must be set to sizeof(vpkc_update_options_v2_t).
+    pub cbSize: usize,
+    /// Allows UpdateManager to update to a version that's lower than the current version (i.e. downgrading).
+    /// This could happen if a release has bugs and was retracted from the release feed, or if you're using
+    /// ExplicitChannel to switch channels to another channel where the latest version on that
+    /// channel is lower than the current version.
+    pub AllowVersionDowngrade: bool,
+    /// **This option should usually be left None**. <br/>
+    /// Overrides the default channel used to fetch updates.
+    /// The default channel will be whatever channel was specified on the command line when building this release.
+    /// For example, if the current release was packaged with '--channel beta', then the default channel will be 'beta'.
+    /// This allows users to automatically receive updates from the same channel they installed from. This options
+    /// allows you to explicitly switch channels, for example if the user wished to switch back to the 'stable' channel
+    /// without having to reinstall the application.
+    pub ExplicitChannel: *mut c_char,
+    /// Allows UpdateManager to update to prerelease versions (eg. '2.0.0-beta.1') from the feed, so users can opt into
+    /// prerelease builds without switching to a separate channel. Prereleases are always allowed if the current version is
+    /// itself a prerelease, or if ExplicitChannel selects a channel other than the one this version was packaged for.
+    pub AllowPrerelease: bool,
+    /// If greater than zero, check_for_updates will not contact the update source if the last check was less than this many
+    /// seconds ago, and will report that no update is available instead. The time of the last check is persisted next to the
+    /// app, and a random delay of up to 10% of the interval is added so that clients started at the same time spread out
+    /// their checks rather than polling the feed all at once.
+    pub UpdateCheckIntervalSeconds: u64,
+    /// The number of full packages to keep in the packages directory once an update has been applied, including the package
+    /// for the installed version. Keeping more than one allows rolling back to a previous version without a network connection.
+    /// Zero uses the default, which is to keep only the package for the installed version.
+    pub PackagesToKeep: u32,
+    /// If greater than zero, the oldest packages are deleted until the packages kept add up to less than this many bytes. The
+    /// package for the installed version is never deleted.
+    pub MaxPackagesSizeBytes: u64,
+    /// If greater than zero, packages which were downloaded more than this many days ago are deleted. The package for the
+    /// installed version is never deleted.
+    pub MaxPackageAgeDays: u32,
+    /// Overrides the directory downloaded packages and temporary files are stored in (eg. a different drive with more free
+    /// space, or a cache directory shared by several apps). Packages are stored in a sub-directory named after the app id, and
+    /// relative paths are resolved against the app install directory. This takes priority over the VELOPACK_PACKAGES_DIR
+    /// environment variable and the packages directory specified when the app was packaged.
+    pub PackagesDir: *mut c_char,
+    /// A machine-level directory where downloaded packages are cached by their checksum, so that apps (or channels of the
+    /// same app) which are configured with the same directory do not download identical packages again. The directory must
+    /// be writable by every user which runs these apps. Nothing is cached if this is None.
+    pub SharedCacheDir: *mut c_char,
+    /// The maximum size of the shared cache, the least recently used packages are deleted once it grows past this size.
+    /// Zero uses the default, which is 2 GB.
+    pub SharedCacheMaxSizeBytes: u64,
+    /// The url (or network directory) of an intranet cache which packages are downloaded from before trying the update
+    /// source, see CacheServerSource for the protocol. If this is None, the VELOPACK_CACHE_SERVER environment
+    /// variable is used instead, so the cache can be configured for every app on a machine.
+    pub CacheServerUrl: *mut c_char,
+    /// Replaces the User-Agent sent with every request to the update source, eg. 'MyApp/1.2.3 (beta)', so that the server
+    /// can tell which app (and version) is asking for updates. The default User-Agent is used if this is None.
+    pub UserAgent: *mut c_char,
+    /// Extra query parameters added to requests for the release feed, as a query string (eg. 'cohort=3&region=eu'), so
+    /// that server-side analytics or staged rollouts can target groups of clients. Nothing is added if this is None.
+    pub FeedQueryParameters: *mut c_char,
+    /// Extra headers sent with every request to the update source, one per line as 'Name: value' (eg. 'X-Api-Key: abc123'),
+    /// so that servers which need authentication or routing headers can be used. Nothing is added if this is None.
+    pub RequestHeaders: *mut c_char,
+    /// If true, the release feed of each successful update check is cached in the packages directory, and check_for_updates uses the
+    /// cached feed if the update source can not be reached (eg. because the machine is offline). Updates found in the cached
+    /// feed are marked with IsStale, and UpdateCheck::NoUpdateAvailableOffline is returned if there are none.
+    pub UseCachedFeedWhenOffline: bool,
+}
+// !! AUTO-GENERATED-END C_TYPES
@@ -27,6 +27,15 @@ public void AppendDocComment(string comment)
         }
     }
 
+    public void AppendRustDocComment(string comment)
+    {
+        if (comment != null) {
+            foreach (var line in comment.ReplaceLineEndings("\n").Split('\n')) {
+                AppendLine($"/// {line}");
+            }
+        }
+    }
+
     private void AppendIndent()
     {
         _sb.Append(' ', _indent * 4);
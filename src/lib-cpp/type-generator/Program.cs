@@ -15,13 +15,22 @@
     "VelopackLocatorConfig",
 ];
 
+// the original vpkc_asset_t, vpkc_update_info_t and vpkc_update_options_t layouts are frozen in types.rs, because they are
+// allocated by the caller. the generated structs start with a cbSize field, and new fields may only ever be appended to them.
+// the locator config is not versioned, so if it ever needs new fields it must get a new _v2 struct first.
 Dictionary<string, string> basic_libc_names = new() {
-    { "VelopackAsset", "vpkc_asset_t" },
-    { "UpdateInfo", "vpkc_update_info_t" },
-    { "UpdateOptions", "vpkc_update_options_t" },
+    { "VelopackAsset", "vpkc_asset_v2_t" },
+    { "UpdateInfo", "vpkc_update_info_v2_t" },
+    { "UpdateOptions", "vpkc_update_options_v2_t" },
     { "VelopackLocatorConfig", "vpkc_locator_config_t" },
 };
 
+string[] versionedStructs = [
+    "VelopackAsset",
+    "UpdateInfo",
+    "UpdateOptions",
+];
+
 List<RustStruct> availableStructs = new();
 string[] searchStrings = desiredStructs.Select(s => "struct " + s + " {").ToArray();
 
@@ -46,47 +55,32 @@
     return -1;
 }
 
-// rust bridge code
-string rustCppLib = Path.Combine(libcppDir, "src", "lib.rs");
+// Velopack.h is generated from the rust types by cbindgen when velopack_libc is built
+string rustCppTypes = Path.Combine(libcppDir, "src", "types.rs");
 string rustCppMap = Path.Combine(libcppDir, "src", "map.rs");
-string rustCppInclude = Path.Combine(libcppDir, "include", "Velopack.h");
-string rustBridgeC = Path.Combine(libcppDir, "src", "bridge.cc");
-
-Console.WriteLine("Generating bridge dtos");
-var sbBridgeDto = new IndentStringBuilder();
-foreach(var rs in availableStructs) {
-    Templates.WriteBridgeDto(desiredStructs, sbBridgeDto, rs);
-}
-
-Console.WriteLine("Generating bridge to core mappings");
-var sbBridgeMapping = new IndentStringBuilder();
-foreach(var rs in availableStructs) {
-    Templates.WriteBridgeToCoreMapping(desiredStructs, sbBridgeMapping, rs);
-}
+string cppInclude = Path.Combine(libcppDir, "include", "Velopack.hpp");
 
 Console.WriteLine("Generating C types");
 var cTypes = new IndentStringBuilder();
 foreach(var rs in availableStructs) {
-    Templates.WriteBasicC(basic_libc_names, cTypes, rs);
+    Templates.WriteRustC(basic_libc_names, versionedStructs, cTypes, rs);
 }
 
-Console.WriteLine("Generating C++ types");
-var cppTypes = new IndentStringBuilder();
+Console.WriteLine("Generating C to core mappings");
+var coreMapping = new IndentStringBuilder();
 foreach(var rs in availableStructs) {
-    Templates.WriteCPlusPlus(basic_libc_names, cppTypes, rs);
+    Templates.WriteRustCoreMapping(basic_libc_names, versionedStructs, coreMapping, rs);
 }
 
-Console.WriteLine("Generating C to bridge mappings");
-var cToBridgeMapping = new IndentStringBuilder();
+Console.WriteLine("Generating C++ types");
+var cppTypes = new IndentStringBuilder();
 foreach(var rs in availableStructs) {
-    Templates.WriteCBridgeMapping(basic_libc_names, cToBridgeMapping, rs);
+    Templates.WriteCPlusPlus(basic_libc_names, versionedStructs, cppTypes, rs);
 }
 
 Console.WriteLine("Writing all to file");
-Util.ReplaceTextInFile(rustCppLib, "BRIDGE_DTOS", sbBridgeDto.ToString());
-Util.ReplaceTextInFile(rustCppMap, "CORE_MAPPING", sbBridgeMapping.ToString());
-Util.ReplaceTextInFile(rustCppInclude, "C_TYPES", cTypes.ToString());
-Util.ReplaceTextInFile(rustCppInclude, "CPP_TYPES", cppTypes.ToString());
-Util.ReplaceTextInFile(rustBridgeC, "BRIDGE_MAPPING", cToBridgeMapping.ToString());
+Util.ReplaceTextInFile(rustCppTypes, "C_TYPES", cTypes.ToString());
+Util.ReplaceTextInFile(rustCppMap, "CORE_MAPPING", coreMapping.ToString());
+Util.ReplaceTextInFile(cppInclude, "CPP_TYPES", cppTypes.ToString());
 
 return 0;
\ No newline at end of file
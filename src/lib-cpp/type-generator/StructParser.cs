@@ -17,6 +17,7 @@ public class RustStruct
     public string DocComment { get; set; }
     public string Name { get; set; }
     public List<RustField> Fields { get; set; }
+    public bool HasUnmappedFields { get; set; }
 }
 
 public enum RustToken
@@ -154,6 +155,8 @@ public static IEnumerable<RustStruct> ParseStructs(string code)
         
         foreach(var s in structs)
         {
+            // lists can not be represented in the flat C structs, so they are left out and filled with a default value
+            s.HasUnmappedFields = s.Fields.RemoveAll(f => f.Type.StartsWith("Vec<")) > 0;
             foreach(var f in s.Fields)
             {
                 var match = Regex.Match(f.Type, @"Option<(.*)>");
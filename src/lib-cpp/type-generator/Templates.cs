@@ -1,27 +1,24 @@
 ﻿public static class Templates
 {
-    private static string GetBasicCType(Dictionary<string, string> nameMap, string rustType)
+    private static string GetRustCType(Dictionary<string, string> nameMap, string rustType)
     {
         switch (rustType) {
         case "PathBuf":
         case "String":
-            return "char*";
+            return "*mut c_char";
         case "bool":
-            return "bool";
-        case "i32":
-            return "int64_t";
-        case "i64":
-            return "int64_t";
         case "u32":
-            return "uint32_t";
         case "u64":
-            return "uint64_t";
+            return rustType;
+        case "i32":
+        case "i64":
+            return "i64";
         default:
             if (nameMap.TryGetValue(rustType, out var type)) {
                 return type;
             }
 
-            throw new NotSupportedException("Unsupported type for basic-c: " + rustType);
+            throw new NotSupportedException("Unsupported type for rust-c: " + rustType);
         }
     }
 
@@ -41,100 +38,98 @@ private static string GetCPlusPlusType(string[] coreTypes, string rustType, bool
         return optional ? "std::optional<" + type + ">" : type;
     }
 
-    public static void WriteCBridgeMapping(Dictionary<string, string> nameMap, IndentStringBuilder sb, RustStruct rs)
+    public static void WriteRustC(Dictionary<string, string> nameMap, string[] versionedTypes, IndentStringBuilder sb, RustStruct rs)
     {
         var cName = nameMap[rs.Name];
-
-        sb.AppendLine($"static inline {rs.Name}Dto to_bridge({cName}* pDto) {{");
+        sb.AppendRustDocComment(rs.DocComment);
+        sb.AppendLine("#[repr(C)]");
+        sb.AppendLine($"pub struct {cName} {{");
         using (sb.Indent()) {
-            sb.AppendLine($"if (pDto == nullptr) {{ return {{}}; }}");
-            sb.AppendLine($"return {{");
-            using (sb.Indent()) {
-                foreach (var field in rs.Fields) {
-                    string suffix = field.Optional ? "_opt" : "";
-                    string type = field.Type == "PathBuf" ? "string" : field.Type.ToLower();
-                    if (nameMap.ContainsKey(field.Type)) {
-                        sb.AppendLine($"to_bridge{suffix}(&pDto->{field.Name}),");
-                    } else if (type == "string") {
-                        sb.AppendLine($"to_bridge{type}{suffix}(pDto->{field.Name}),");
-                    } else {
-                        sb.AppendLine($"pDto->{field.Name},");
-                    }
-                }
+            if (versionedTypes.Contains(rs.Name)) {
+                sb.AppendLine($"/// The size of this struct in bytes, which must be set to sizeof({cName}).");
+                sb.AppendLine("pub cbSize: usize,");
             }
 
-            sb.AppendLine("};");
+            foreach (var field in rs.Fields) {
+                sb.AppendRustDocComment(field.DocComment);
+                sb.AppendLine($"pub {field.Name}: {GetRustCType(nameMap, field.Type)},");
+            }
         }
 
-        sb.AppendLine($"}}");
+        sb.AppendLine("}");
         sb.AppendLine();
+    }
+
+    public static void WriteRustCoreMapping(Dictionary<string, string> nameMap, string[] versionedTypes, IndentStringBuilder sb, RustStruct rs)
+    {
+        var cName = nameMap[rs.Name];
+        var fnName = rs.Name.ToLower();
 
-        sb.AppendLine($"static inline {rs.Name}DtoOption to_bridge_opt({cName}* pDto) {{");
+        sb.AppendLine($"pub unsafe fn {fnName}_to_core(dto: &{cName}) -> {rs.Name} {{");
         using (sb.Indent()) {
-            sb.AppendLine($"{rs.Name}DtoOption opt;");
-            sb.AppendLine($"if (pDto == nullptr) {{");
+            sb.AppendLine($"{rs.Name} {{");
             using (sb.Indent()) {
-                sb.AppendLine($"opt.has_data = false;");
-                sb.AppendLine($"return opt;");
+                foreach (var field in rs.Fields) {
+                    if (nameMap.ContainsKey(field.Type)) {
+                        if (field.Optional) {
+                            throw new NotSupportedException("Optional struct fields are not supported: " + field.Name);
+                        }
+                        sb.AppendLine($"{field.Name}: {field.Type.ToLower()}_to_core(&dto.{field.Name}),");
+                    } else if (field.Type == "String" || field.Type == "PathBuf") {
+                        string suffix = field.Optional ? "_option" : "";
+                        sb.AppendLine($"{field.Name}: {field.Type.ToLower()}_to_core{suffix}(dto.{field.Name}),");
+                    } else {
+                        sb.AppendLine($"{field.Name}: dto.{field.Name},");
+                    }
+                }
+
+                if (rs.HasUnmappedFields) {
+                    sb.AppendLine("..Default::default()");
+                }
             }
 
-            sb.AppendLine($"}}");
-            sb.AppendLine();
-            sb.AppendLine($"opt.has_data = true;");
-            sb.AppendLine($"opt.data = to_bridge(pDto);");
-            sb.AppendLine($"return opt;");
+            sb.AppendLine("}");
         }
 
-        sb.AppendLine($"}}");
+        sb.AppendLine("}");
         sb.AppendLine();
 
-        sb.AppendLine($"static inline void allocate_{rs.Name.ToLower()}({rs.Name}Dto bridgeDto, {cName}* pDto) {{");
+        sb.AppendLine($"pub fn allocate_{fnName}(obj: &{rs.Name}, dto: &mut {cName}) {{");
         using (sb.Indent()) {
-            sb.AppendLine($"if (pDto == nullptr) {{ return; }}");
             foreach (var field in rs.Fields) {
-                string type = field.Type == "PathBuf" ? "string" : field.Type.ToLower();
-                string suffix = field.Optional ? "_opt" : "";
-                sb.AppendLine(
-                    nameMap.ContainsKey(field.Type) || type == "string"
-                        ? $"allocate_{type}{suffix}(bridgeDto.{field.Name}, &pDto->{field.Name});"
-                        : $"pDto->{field.Name} = bridgeDto.{field.Name};");
+                if (nameMap.ContainsKey(field.Type)) {
+                    if (versionedTypes.Contains(field.Type)) {
+                        sb.AppendLine($"dto.{field.Name}.cbSize = std::mem::size_of::<{nameMap[field.Type]}>();");
+                    }
+                    sb.AppendLine($"allocate_{field.Type.ToLower()}(&obj.{field.Name}, &mut dto.{field.Name});");
+                } else if (field.Type == "String" || field.Type == "PathBuf") {
+                    string suffix = field.Optional ? "_option" : "";
+                    sb.AppendLine($"allocate_{field.Type.ToLower()}{suffix}(&obj.{field.Name}, &mut dto.{field.Name});");
+                } else {
+                    sb.AppendLine($"dto.{field.Name} = obj.{field.Name};");
+                }
             }
         }
-        sb.AppendLine($"}}");
+
+        sb.AppendLine("}");
         sb.AppendLine();
 
-        sb.AppendLine($"static inline void free_{rs.Name.ToLower()}({cName}* pDto) {{");
+        sb.AppendLine($"pub unsafe fn free_{fnName}(dto: &mut {cName}) {{");
         using (sb.Indent()) {
-            sb.AppendLine($"if (pDto == nullptr) {{ return; }}");
             foreach (var field in rs.Fields) {
-                string type = field.Type == "PathBuf" ? "string" : field.Type.ToLower();
                 if (nameMap.ContainsKey(field.Type)) {
-                    sb.AppendLine($"free_{type}(&pDto->{field.Name});");
-                } else if (type == "string") {
-                    sb.AppendLine($"free(pDto->{field.Name});");
+                    sb.AppendLine($"free_{field.Type.ToLower()}(&mut dto.{field.Name});");
+                } else if (field.Type == "String" || field.Type == "PathBuf") {
+                    sb.AppendLine($"free_string(&mut dto.{field.Name});");
                 }
             }
         }
-        sb.AppendLine($"}}");
-        sb.AppendLine();
-    }
-
-    public static void WriteBasicC(Dictionary<string, string> nameMap, IndentStringBuilder sb, RustStruct rs)
-    {
-        sb.AppendDocComment(rs.DocComment);
-        sb.AppendLine($"typedef struct {{");
-        foreach (var field in rs.Fields) {
-            using (sb.Indent()) {
-                sb.AppendDocComment(field.DocComment);
-                sb.AppendLine($"{GetBasicCType(nameMap, field.Type)} {field.Name};");
-            }
-        }
 
-        sb.AppendLine($"}} {nameMap[rs.Name]};");
+        sb.AppendLine("}");
         sb.AppendLine();
     }
 
-    public static void WriteCPlusPlus(Dictionary<string, string> nameMap, IndentStringBuilder sb, RustStruct rs)
+    public static void WriteCPlusPlus(Dictionary<string, string> nameMap, string[] versionedTypes, IndentStringBuilder sb, RustStruct rs)
     {
         var coreTypes = nameMap.Keys.ToArray();
         sb.AppendDocComment(rs.DocComment);
@@ -153,6 +148,10 @@ public static void WriteCPlusPlus(Dictionary<string, string> nameMap, IndentStri
         using (sb.Indent()) {
             sb.AppendLine("return {");
             using (sb.Indent()) {
+                if (versionedTypes.Contains(rs.Name)) {
+                    sb.AppendLine($"sizeof({nameMap[rs.Name]}),");
+                }
+
                 foreach (var field in rs.Fields) {
                     string suffix = field.Optional ? "_opt" : "";
                     string type = field.Type == "PathBuf" ? "string" : field.Type.ToLower();
@@ -189,112 +188,4 @@ public static void WriteCPlusPlus(Dictionary<string, string> nameMap, IndentStri
         sb.AppendLine($"}}");
         sb.AppendLine();
     }
-
-    public static void WriteBridgeDto(string[] coreTypes, IndentStringBuilder sb, RustStruct rs)
-    {
-        Func<string, string> nameMapper = (str) =>
-            coreTypes.Contains(str) ? str + "Dto" : str;
-
-        using (sb.Indent()) {
-            sb.AppendLine($"#[derive(Default)]");
-            sb.AppendLine($"pub struct {nameMapper(rs.Name)} {{");
-            foreach (var field in rs.Fields) {
-                string type = field.Type;
-                if (type == "PathBuf") {
-                    type = "String";
-                }
-
-                using (sb.Indent()) {
-                    if (field.Optional) {
-                        sb.AppendLine($"pub {field.Name}: {nameMapper(type)}Option,");
-                    } else {
-                        sb.AppendLine($"pub {field.Name}: {nameMapper(type)},");
-                    }
-                }
-            }
-
-            sb.AppendLine($"}}");
-            sb.AppendLine();
-
-            sb.AppendLine($"#[derive(Default)]");
-            sb.AppendLine($"pub struct {nameMapper(rs.Name)}Option {{");
-            using (sb.Indent()) {
-                sb.AppendLine($"pub data: {nameMapper(rs.Name)},");
-                sb.AppendLine($"pub has_data: bool,");
-            }
-
-            sb.AppendLine($"}}");
-            sb.AppendLine();
-        }
-    }
-
-    public static void WriteBridgeToCoreMapping(string[] coreTypes, IndentStringBuilder sb, RustStruct rs)
-    {
-        Func<string, string> nameMapper = (str) => coreTypes.Contains(str) ? str + "Dto" : str;
-
-        sb.AppendLine($"pub fn {rs.Name.ToLower()}_to_core(dto: &{nameMapper(rs.Name)}) -> {rs.Name} {{");
-        ;
-        using (sb.Indent()) {
-            sb.AppendLine($"{rs.Name} {{");
-            foreach (var field in rs.Fields) {
-                using (sb.Indent()) {
-                    if (field.Optional) {
-                        sb.AppendLine(
-                            $"{field.Name}: if dto.{field.Name}.has_data {{ Some({field.Type.ToLower()}_to_core(&dto.{field.Name}.data)) }} else {{ None }},");
-                    } else {
-                        sb.AppendLine($"{field.Name}: {field.Type.ToLower()}_to_core(&dto.{field.Name}),");
-                    }
-                }
-            }
-
-            sb.AppendLine($"}}");
-        }
-
-        sb.AppendLine($"}}");
-        sb.AppendLine();
-
-        sb.AppendLine($"pub fn {rs.Name.ToLower()}_to_bridge(dto: &{rs.Name}) -> {nameMapper(rs.Name)} {{");
-        using (sb.Indent()) {
-            sb.AppendLine($"{nameMapper(rs.Name)} {{");
-            foreach (var field in rs.Fields) {
-                using (sb.Indent()) {
-                    if (field.Optional) {
-                        sb.AppendLine(
-                            $"{field.Name}: {nameMapper(field.Type)}Option {{ data: {field.Type.ToLower()}_to_bridge(&dto.{field.Name}.clone().unwrap_or_default()), has_data: dto.{field.Name}.is_some() }},");
-                    } else {
-                        sb.AppendLine($"{field.Name}: {field.Type.ToLower()}_to_bridge(&dto.{field.Name}),");
-                    }
-                }
-            }
-
-            sb.AppendLine($"}}");
-        }
-
-        sb.AppendLine($"}}");
-        sb.AppendLine();
-
-        sb.AppendLine($"pub fn {rs.Name.ToLower()}_to_core_option(dto: &{nameMapper(rs.Name)}Option) -> Option<{rs.Name}> {{");
-        ;
-        using (sb.Indent()) {
-            sb.AppendLine($"if dto.has_data {{ Some({rs.Name.ToLower()}_to_core(&dto.data)) }} else {{ None }}");
-        }
-
-        sb.AppendLine($"}}");
-        sb.AppendLine();
-
-        sb.AppendLine($"pub fn {rs.Name.ToLower()}_to_bridge_option(dto: &Option<{rs.Name}>) -> {nameMapper(rs.Name)}Option {{");
-        ;
-        using (sb.Indent()) {
-            sb.AppendLine($"match dto {{");
-            using (sb.Indent()) {
-                sb.AppendLine($"Some(dto) => {nameMapper(rs.Name)}Option {{ data: {rs.Name.ToLower()}_to_bridge(dto), has_data: true }},");
-                sb.AppendLine($"None => {nameMapper(rs.Name)}Option {{ data: Default::default(), has_data: false }},");
-            }
-
-            sb.AppendLine($"}}");
-        }
-
-        sb.AppendLine($"}}");
-        sb.AppendLine();
-    }
 }
\ No newline at end of file
@@ -30,8 +30,9 @@ public static void ExtractZipToDirectory(ILogger logger, string inputFile, strin
                     }
                 }
 
-                // process symlinks after, because creating them requires the target to exist
-                foreach (var sym in symlinks) {
+                // process symlinks after, because creating them requires the target to exist. links often point through
+                // deeper links (eg. Foo.framework/Foo -> Versions/Current/Foo), so the deepest links are created first.
+                foreach (var sym in symlinks.OrderByDescending(s => s.FullName.Count(c => c == '/'))) {
                     sym.ExtractRelativeToDirectory(outputDirectory, true, expandSymlinks);
                 }
             }
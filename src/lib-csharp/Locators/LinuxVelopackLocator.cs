@@ -39,11 +39,21 @@ public class LinuxVelopackLocator : VelopackLocator
         /// <inheritdoc />
         public override string? PackagesDir => CreateSubDirIfDoesNotExist(PersistentTempDir, "packages");
 
-        /// <summary> /var/tmp/{velopack}/{appid}, for storing app specific files which need to be preserved. </summary>
+        /// <summary> ~/.cache/velopack/{appid}, for storing app specific files which need to be preserved. </summary>
         public string? PersistentTempDir => CreateSubDirIfDoesNotExist(PersistentVelopackDir, AppId);
 
-        /// <summary> A pointer to /var/tmp/{velopack}, a location on linux which is semi-persistent. </summary>
-        public string? PersistentVelopackDir => CreateSubDirIfDoesNotExist("/var/tmp", "velopack");
+        /// <summary> A pointer to ~/.cache/velopack (or $XDG_CACHE_HOME/velopack), a location on linux which is
+        /// semi-persistent, and private to the current user. </summary>
+        public string? PersistentVelopackDir => CreateSubDirIfDoesNotExist(CacheDir, "velopack");
+
+        private string? CacheDir {
+            get {
+                var xdgCache = Environment.GetEnvironmentVariable("XDG_CACHE_HOME");
+                return !String.IsNullOrWhiteSpace(xdgCache) && Directory.Exists(xdgCache) ? xdgCache : CreateSubDirIfDoesNotExist(HomeDir, ".cache");
+            }
+        }
+
+        private string? HomeDir => Environment.GetFolderPath(Environment.SpecialFolder.UserProfile);
 
         /// <summary> File path of the .AppImage which mounted and ran this application. </summary>
         public string? AppImagePath => Environment.GetEnvironmentVariable("APPIMAGE");
@@ -28,6 +28,8 @@ public class PackageManifest
         public string? Summary { get; private set; }
         public string? Copyright { get; private set; }
         public string? ShortcutAmuid { get; private set; }
+        public string? MachineArchitecture { get; private set; }
+        public bool IsMandatory { get; private set; }
         public IEnumerable<string> ShortcutLocations { get; private set; } = Enumerable.Empty<string>();
         public IEnumerable<string> Authors { get; private set; } = Enumerable.Empty<string>();
         public IEnumerable<string> RuntimeDependencies { get; private set; } = Enumerable.Empty<string>();
@@ -145,6 +147,12 @@ IEnumerable<string> getCommaDelimitedValue(string v)
             case "shortcutAmuid":
                 ShortcutAmuid = value;
                 break;
+            case "machineArchitecture":
+                MachineArchitecture = value;
+                break;
+            case "mandatory":
+                IsMandatory = "true".Equals(value, StringComparison.OrdinalIgnoreCase);
+                break;
             }
         }
 
@@ -14,6 +14,8 @@ public class UpdateInfo
         /// True if the update is a version downgrade or lateral move (such as when switching channels to the same version number).
         /// In this case, only full updates are allowed, and any local packages on disk newer than the downloaded version will be
         /// deleted.
+        /// A release of the same version built for an architecture which suits this machine better (eg. a native ARM64 build
+        /// replacing an x64 build running under emulation) is also a lateral move.
         /// </summary>
         public bool IsDowngrade { get; }
 
@@ -280,6 +280,7 @@ await DownloadAndApplyDeltaUpdates(deltaStagingDir, updates, x => reportProgress
                                 File.Delete(incompleteFile);
                                 await EasyZip.CreateZipFromDirectoryAsync(Log, incompleteFile, deltaStagingDir, x => reportProgress(CoreUtil.CalculateProgress(x, 80, 100)),
                                     cancelToken: cancelToken).ConfigureAwait(false);
+                                VerifyDeltaReconstructedPackage(incompleteFile);
                                 File.Delete(completeFile);
                                 File.Move(incompleteFile, completeFile);
                                 Log.Info("Delta release preparations complete. Package moved to: " + completeFile);
@@ -326,6 +327,19 @@ await DownloadAndApplyDeltaUpdates(deltaStagingDir, updates, x => reportProgress
             }
         }
 
+        /// <summary>
+        /// Verifies a release package which was rebuilt from delta updates before it is used. On macOS, the code signature
+        /// of the rebuilt app bundle is checked, because a bundle which does not exactly match what was signed will
+        /// fail Gatekeeper. If this throws, the full release is downloaded instead.
+        /// </summary>
+        /// <param name="packageFile">The rebuilt release package.</param>
+        protected virtual void VerifyDeltaReconstructedPackage(string packageFile)
+        {
+            if (VelopackRuntimeInfo.IsOSX && Locator.RootAppDir != null) {
+                CodeSignUtil.VerifyPackageBundleSignature(Log, packageFile, Locator.RootAppDir, Locator.AppTempDir!);
+            }
+        }
+
         /// <summary>
         /// Given a folder containing the extracted base package, and a list of delta updates, downloads and applies the 
         /// delta updates to the base package.
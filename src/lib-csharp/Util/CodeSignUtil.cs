@@ -0,0 +1,97 @@
+﻿using System;
+using System.Diagnostics;
+using System.IO;
+using System.Linq;
+using Microsoft.Extensions.Logging;
+using Velopack.Compression;
+
+namespace Velopack.Util
+{
+    internal static class CodeSignUtil
+    {
+        private const int CodeSignTimeoutMs = 5 * 60 * 1000;
+
+        /// <summary>
+        /// Extracts the app bundle from a (delta reconstructed) release package and verifies that its code signature is
+        /// still intact, and that it was signed by the same team as the bundle which is currently installed. Throws if
+        /// the signature of the package can not be verified.
+        /// </summary>
+        public static void VerifyPackageBundleSignature(ILogger logger, string packageFile, string installedBundle, string tempDir)
+        {
+            using var _1 = TempUtil.GetTempDirectory(out var extractDir, tempDir);
+
+            // symlinks must be expanded, because frameworks inside the bundle are sealed with their symlinks
+            EasyZip.ExtractZipToDirectory(logger, packageFile, extractDir, expandSymlinks: true);
+            var contentDir = Path.Combine(extractDir, "lib", "app");
+            if (!Directory.Exists(contentDir)) {
+                throw new Exception("Release package does not contain an app bundle.");
+            }
+
+            // codesign only treats a directory as a bundle if it has the .app extension
+            var bundle = Path.Combine(extractDir, "verify.app");
+            Directory.Move(contentDir, bundle);
+
+            var installedTeam = GetTeamIdentifier(logger, installedBundle);
+            if (installedTeam == null) {
+                logger.Warn("Installed app is not signed, skipping signature verification of delta update.");
+                return;
+            }
+
+            logger.Info("Verifying code signature of delta update...");
+            var (exitCode, output) = RunCodeSign("--verify", "--deep", "--strict", bundle);
+            if (exitCode != 0) {
+                throw new Exception($"Code signature of delta update is not valid: {output}");
+            }
+
+            var newTeam = GetTeamIdentifier(logger, bundle);
+            if (newTeam != installedTeam) {
+                throw new Exception($"Delta update is signed by team '{newTeam}', but the installed app is signed by '{installedTeam}'.");
+            }
+
+            logger.Info($"Code signature of delta update verified (team '{newTeam}').");
+        }
+
+        /// <summary>
+        /// Returns the team identifier a bundle was signed with, or null if it is not signed (or signed ad-hoc).
+        /// </summary>
+        public static string? GetTeamIdentifier(ILogger logger, string bundle)
+        {
+            var (exitCode, output) = RunCodeSign("-dv", bundle);
+            if (exitCode != 0) {
+                logger.Debug($"codesign could not read the signature of '{bundle}': {output}");
+                return null;
+            }
+
+            var team = output.Split('\n')
+                .Select(l => l.Trim())
+                .Where(l => l.StartsWith("TeamIdentifier=", StringComparison.Ordinal))
+                .Select(l => l.Substring("TeamIdentifier=".Length))
+                .FirstOrDefault();
+
+            return String.IsNullOrEmpty(team) || team == "not set" ? null : team;
+        }
+
+        private static (int ExitCode, string Output) RunCodeSign(params string[] args)
+        {
+            var psi = new ProcessStartInfo("/usr/bin/codesign");
+            psi.AppendArgumentListSafe(args, out var _);
+            psi.CreateNoWindow = true;
+            psi.UseShellExecute = false;
+            psi.RedirectStandardOutput = true;
+            psi.RedirectStandardError = true;
+
+            var p = Process.Start(psi);
+            if (p == null) throw new Exception("Process.Start returned null.");
+
+            // codesign writes everything of interest to stderr, stdout is read asynchronously so neither pipe can fill up
+            var stdout = p.StandardOutput.ReadToEndAsync();
+            var stderr = p.StandardError.ReadToEnd();
+            if (!p.WaitForExit(CodeSignTimeoutMs)) {
+                p.Kill();
+                throw new TimeoutException("codesign did not exit within allotted time.");
+            }
+
+            return (p.ExitCode, (stdout.Result + stderr).Trim());
+        }
+    }
+}
@@ -69,6 +69,12 @@ public record VelopackAsset
         /// <summary> The release notes in HTML format, transformed from Markdown when packaging the release. </summary>
         public string NotesHTML { get; set; }
 
+        /// <summary> The CPU architecture this release was built for (eg. x64 or arm64), or empty if it runs on any machine. </summary>
+        public string Architecture { get; set; }
+
+        /// <summary> The release has been marked as mandatory by the publisher, and should not be skipped or postponed. </summary>
+        public bool IsMandatory { get; set; }
+
         /// <summary>
         /// Convert a <see cref="ZipPackage"/> to a <see cref="VelopackAsset"/>.
         /// </summary>
@@ -80,6 +86,8 @@ public static VelopackAsset FromZipPackage(ZipPackage zip)
                 Version = zip.Version,
                 NotesMarkdown = zip.ReleaseNotes,
                 NotesHTML = zip.ReleaseNotesHtml,
+                Architecture = zip.MachineArchitecture,
+                IsMandatory = zip.IsMandatory,
                 Size = new FileInfo(filePath).Length,
                 SHA1 = IoUtil.CalculateFileSHA1(filePath),
                 SHA256 = IoUtil.CalculateFileSHA256(filePath),
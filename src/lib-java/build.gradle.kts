@@ -0,0 +1,14 @@
+plugins {
+    `java-library`
+}
+
+group = "com.velopack"
+
+java {
+    toolchain {
+        languageVersion.set(JavaLanguageVersion.of(11))
+    }
+}
+
+// The native library (velopack_jni) is built separately with CMake, see CMakeLists.txt,
+// and must be on java.library.path (or next to the application) at runtime.
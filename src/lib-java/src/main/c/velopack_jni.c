@@ -0,0 +1,379 @@
+// JNI glue between the com.velopack Java classes and the Velopack C API (Velopack.h).
+// The C API callbacks carry no user data, so the Java objects they need are kept in static / thread-local state.
+// Every callback is invoked on the thread which called into the C API, so the JNIEnv captured by the caller is valid.
+
+#include <jni.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "Velopack.h"
+
+#if defined(_MSC_VER)
+#define VPKJ_THREAD_LOCAL __declspec(thread)
+#else
+#define VPKJ_THREAD_LOCAL _Thread_local
+#endif
+
+#define VPKJ_HOOK_COUNT 6
+
+static VPKJ_THREAD_LOCAL JNIEnv* progressEnv = NULL;
+static VPKJ_THREAD_LOCAL jobject progressListener = NULL;
+
+static JNIEnv* hookEnv = NULL;
+static jobject hooks[VPKJ_HOOK_COUNT];
+
+static void throw_velopack_exception(JNIEnv* env) {
+    size_t needed = vpkc_get_last_error(NULL, 0);
+    char* message = (char*)calloc(needed + 1, 1);
+    if (message != NULL) {
+        vpkc_get_last_error(message, needed);
+    }
+    jclass cls = (*env)->FindClass(env, "com/velopack/VelopackException");
+    jmethodID ctor = (*env)->GetMethodID(env, cls, "<init>", "(Ljava/lang/String;I)V");
+    jstring jmessage = (*env)->NewStringUTF(env, message != NULL ? message : "Unknown error");
+    jobject ex = (*env)->NewObject(env, cls, ctor, jmessage, (jint)vpkc_get_last_error_code());
+    (*env)->Throw(env, (jthrowable)ex);
+    free(message);
+}
+
+static void throw_illegal_argument(JNIEnv* env, const char* message) {
+    jclass cls = (*env)->FindClass(env, "java/lang/IllegalArgumentException");
+    (*env)->ThrowNew(env, cls, message);
+}
+
+static char* to_cstring(JNIEnv* env, jstring str) {
+    if (str == NULL) {
+        return NULL;
+    }
+    const char* utf = (*env)->GetStringUTFChars(env, str, NULL);
+    char* copy = NULL;
+    if (utf != NULL) {
+        size_t len = strlen(utf);
+        copy = (char*)malloc(len + 1);
+        memcpy(copy, utf, len + 1);
+    }
+    (*env)->ReleaseStringUTFChars(env, str, utf);
+    return copy;
+}
+
+static jstring to_jstring(JNIEnv* env, const char* psz) {
+    return (*env)->NewStringUTF(env, psz != NULL ? psz : "");
+}
+
+static char** to_cstring_array(JNIEnv* env, jobjectArray arr, size_t* count) {
+    *count = arr != NULL ? (size_t)(*env)->GetArrayLength(env, arr) : 0;
+    if (*count == 0) {
+        return NULL;
+    }
+    char** result = (char**)calloc(*count, sizeof(char*));
+    for (size_t i = 0; i < *count; i++) {
+        jstring item = (jstring)(*env)->GetObjectArrayElement(env, arr, (jsize)i);
+        result[i] = to_cstring(env, item);
+        (*env)->DeleteLocalRef(env, item);
+    }
+    return result;
+}
+
+static void free_cstring_array(char** arr, size_t count) {
+    if (arr == NULL) {
+        return;
+    }
+    for (size_t i = 0; i < count; i++) {
+        free(arr[i]);
+    }
+    free(arr);
+}
+
+static jobject asset_to_java(JNIEnv* env, vpkc_asset_v2_t* asset) {
+    jclass cls = (*env)->FindClass(env, "com/velopack/VelopackAsset");
+    jmethodID ctor = (*env)->GetMethodID(env, cls, "<init>",
+        "(Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;JLjava/lang/String;Ljava/lang/String;Z)V");
+    return (*env)->NewObject(env, cls, ctor,
+        to_jstring(env, asset->PackageId),
+        to_jstring(env, asset->Version),
+        to_jstring(env, asset->Type),
+        to_jstring(env, asset->FileName),
+        to_jstring(env, asset->SHA1),
+        to_jstring(env, asset->SHA256),
+        (jlong)asset->Size,
+        to_jstring(env, asset->NotesMarkdown),
+        to_jstring(env, asset->NotesHtml),
+        asset->IsMandatory ? JNI_TRUE : JNI_FALSE);
+}
+
+static char* get_string_field(JNIEnv* env, jclass cls, jobject obj, const char* name) {
+    jfieldID field = (*env)->GetFieldID(env, cls, name, "Ljava/lang/String;");
+    jstring value = (jstring)(*env)->GetObjectField(env, obj, field);
+    char* result = to_cstring(env, value);
+    (*env)->DeleteLocalRef(env, value);
+    return result;
+}
+
+static bool asset_from_java(JNIEnv* env, jobject obj, vpkc_asset_v2_t* asset) {
+    memset(asset, 0, sizeof(vpkc_asset_v2_t));
+    asset->cbSize = sizeof(vpkc_asset_v2_t);
+    if (obj == NULL) {
+        throw_illegal_argument(env, "asset is required");
+        return false;
+    }
+    jclass cls = (*env)->GetObjectClass(env, obj);
+    asset->PackageId = get_string_field(env, cls, obj, "packageId");
+    asset->Version = get_string_field(env, cls, obj, "version");
+    asset->Type = get_string_field(env, cls, obj, "type");
+    asset->FileName = get_string_field(env, cls, obj, "fileName");
+    asset->SHA1 = get_string_field(env, cls, obj, "sha1");
+    asset->SHA256 = get_string_field(env, cls, obj, "sha256");
+    asset->Size = (uint64_t)(*env)->GetLongField(env, obj, (*env)->GetFieldID(env, cls, "size", "J"));
+    asset->NotesMarkdown = get_string_field(env, cls, obj, "notesMarkdown");
+    asset->NotesHtml = get_string_field(env, cls, obj, "notesHtml");
+    asset->IsMandatory = (*env)->GetBooleanField(env, obj, (*env)->GetFieldID(env, cls, "mandatory", "Z")) == JNI_TRUE;
+    return true;
+}
+
+// strings read from java objects are allocated by us, so must not be passed to vpkc_free_asset_v2
+static void free_java_asset(vpkc_asset_v2_t* asset) {
+    free(asset->PackageId);
+    free(asset->Version);
+    free(asset->Type);
+    free(asset->FileName);
+    free(asset->SHA1);
+    free(asset->SHA256);
+    free(asset->NotesMarkdown);
+    free(asset->NotesHtml);
+}
+
+static void progress_callback(size_t progress) {
+    JNIEnv* env = progressEnv;
+    if (env == NULL || progressListener == NULL || (*env)->ExceptionCheck(env)) {
+        return;
+    }
+    jclass cls = (*env)->GetObjectClass(env, progressListener);
+    jmethodID accept = (*env)->GetMethodID(env, cls, "accept", "(I)V");
+    (*env)->CallVoidMethod(env, progressListener, accept, (jint)progress);
+    (*env)->DeleteLocalRef(env, cls);
+}
+
+static void invoke_hook(int index, const char* arg) {
+    JNIEnv* env = hookEnv;
+    if (env == NULL || hooks[index] == NULL || (*env)->ExceptionCheck(env)) {
+        return;
+    }
+    jclass cls = (*env)->GetObjectClass(env, hooks[index]);
+    jmethodID accept = (*env)->GetMethodID(env, cls, "accept", "(Ljava/lang/Object;)V");
+    jstring jarg = to_jstring(env, arg);
+    (*env)->CallVoidMethod(env, hooks[index], accept, jarg);
+    (*env)->DeleteLocalRef(env, jarg);
+    (*env)->DeleteLocalRef(env, cls);
+}
+
+// the order of these must match the hook indices in VelopackApp.java
+static void hook_after_install(const char* version) { invoke_hook(0, version); }
+static void hook_before_uninstall(const char* version) { invoke_hook(1, version); }
+static void hook_before_update(const char* version) { invoke_hook(2, version); }
+static void hook_after_update(const char* version) { invoke_hook(3, version); }
+static void hook_first_run(const char* version) { invoke_hook(4, version); }
+static void hook_restarted(const char* version) { invoke_hook(5, version); }
+
+static const vpkc_hook_callback_t hookCallbacks[VPKJ_HOOK_COUNT] = {
+    hook_after_install, hook_before_uninstall, hook_before_update, hook_after_update, hook_first_run, hook_restarted,
+};
+
+JNIEXPORT jlong JNICALL Java_com_velopack_UpdateManager_nativeNew(
+    JNIEnv* env, jclass cls, jstring urlOrPath, jboolean allowDowngrade, jstring explicitChannel, jboolean allowPrerelease,
+    jlong updateCheckIntervalSeconds) {
+    (void)cls;
+    char* url = to_cstring(env, urlOrPath);
+    if (url == NULL) {
+        throw_illegal_argument(env, "urlOrPath is required");
+        return 0;
+    }
+    vpkc_update_options_v2_t options;
+    memset(&options, 0, sizeof(options));
+    options.cbSize = sizeof(options);
+    options.AllowVersionDowngrade = allowDowngrade == JNI_TRUE;
+    options.ExplicitChannel = to_cstring(env, explicitChannel);
+    options.AllowPrerelease = allowPrerelease == JNI_TRUE;
+    options.UpdateCheckIntervalSeconds = updateCheckIntervalSeconds > 0 ? (uint64_t)updateCheckIntervalSeconds : 0;
+
+    vpkc_update_manager_t* manager = NULL;
+    bool ok = vpkc_new_update_manager_v2(url, &options, NULL, &manager);
+    free(url);
+    free(options.ExplicitChannel);
+    if (!ok) {
+        throw_velopack_exception(env);
+        return 0;
+    }
+    return (jlong)(intptr_t)manager;
+}
+
+JNIEXPORT jstring JNICALL Java_com_velopack_UpdateManager_nativeGetCurrentVersion(JNIEnv* env, jclass cls, jlong handle) {
+    (void)cls;
+    vpkc_update_manager_t* manager = (vpkc_update_manager_t*)(intptr_t)handle;
+    size_t needed = vpkc_get_current_version(manager, NULL, 0);
+    char* version = (char*)calloc(needed + 1, 1);
+    vpkc_get_current_version(manager, version, needed);
+    jstring result = to_jstring(env, version);
+    free(version);
+    return result;
+}
+
+JNIEXPORT jstring JNICALL Java_com_velopack_UpdateManager_nativeGetAppId(JNIEnv* env, jclass cls, jlong handle) {
+    (void)cls;
+    vpkc_update_manager_t* manager = (vpkc_update_manager_t*)(intptr_t)handle;
+    size_t needed = vpkc_get_app_id(manager, NULL, 0);
+    char* id = (char*)calloc(needed + 1, 1);
+    vpkc_get_app_id(manager, id, needed);
+    jstring result = to_jstring(env, id);
+    free(id);
+    return result;
+}
+
+JNIEXPORT jboolean JNICALL Java_com_velopack_UpdateManager_nativeIsPortable(JNIEnv* env, jclass cls, jlong handle) {
+    (void)env;
+    (void)cls;
+    return vpkc_is_portable((vpkc_update_manager_t*)(intptr_t)handle) ? JNI_TRUE : JNI_FALSE;
+}
+
+JNIEXPORT jobject JNICALL Java_com_velopack_UpdateManager_nativeUpdatePendingRestart(JNIEnv* env, jclass cls, jlong handle) {
+    (void)cls;
+    vpkc_asset_v2_t asset = {0};
+    asset.cbSize = sizeof(asset);
+    if (!vpkc_update_pending_restart_v2((vpkc_update_manager_t*)(intptr_t)handle, &asset)) {
+        return NULL;
+    }
+    jobject result = asset_to_java(env, &asset);
+    vpkc_free_asset_v2(&asset);
+    return result;
+}
+
+JNIEXPORT jobject JNICALL Java_com_velopack_UpdateManager_nativeCheckForUpdates(JNIEnv* env, jclass cls, jlong handle) {
+    (void)cls;
+    vpkc_update_info_v2_t update = {0};
+    update.cbSize = sizeof(update);
+    vpkc_update_check_t check = vpkc_check_for_updates_v2((vpkc_update_manager_t*)(intptr_t)handle, &update);
+    if (check == UPDATE_ERROR) {
+        throw_velopack_exception(env);
+        return NULL;
+    }
+    if (check != UPDATE_AVAILABLE) {
+        return NULL;
+    }
+    jobject asset = asset_to_java(env, &update.TargetFullRelease);
+    jclass infoCls = (*env)->FindClass(env, "com/velopack/UpdateInfo");
+    jmethodID ctor = (*env)->GetMethodID(env, infoCls, "<init>", "(Lcom/velopack/VelopackAsset;ZZ)V");
+    jobject result = (*env)->NewObject(
+        env, infoCls, ctor, asset, update.IsDowngrade ? JNI_TRUE : JNI_FALSE, update.IsMandatory ? JNI_TRUE : JNI_FALSE);
+    vpkc_free_update_info_v2(&update);
+    return result;
+}
+
+JNIEXPORT void JNICALL Java_com_velopack_UpdateManager_nativeDownloadUpdates(
+    JNIEnv* env, jclass cls, jlong handle, jobject jasset, jboolean isDowngrade, jobject listener) {
+    (void)cls;
+    vpkc_update_info_v2_t update = {0};
+    update.cbSize = sizeof(update);
+    if (!asset_from_java(env, jasset, &update.TargetFullRelease)) {
+        return;
+    }
+    update.IsDowngrade = isDowngrade == JNI_TRUE;
+    update.IsMandatory = false;
+
+    progressEnv = env;
+    progressListener = listener;
+    bool ok = vpkc_download_updates_v2((vpkc_update_manager_t*)(intptr_t)handle, &update, listener != NULL ? progress_callback : NULL);
+    progressEnv = NULL;
+    progressListener = NULL;
+
+    free_java_asset(&update.TargetFullRelease);
+    // an exception thrown by the progress listener takes priority over any error from the download
+    if (!ok && !(*env)->ExceptionCheck(env)) {
+        throw_velopack_exception(env);
+    }
+}
+
+JNIEXPORT void JNICALL Java_com_velopack_UpdateManager_nativeApplyUpdatesAndRestart(
+    JNIEnv* env, jclass cls, jlong handle, jobject jasset, jobjectArray restartArgs) {
+    (void)cls;
+    vpkc_asset_v2_t asset;
+    if (!asset_from_java(env, jasset, &asset)) {
+        return;
+    }
+    size_t argc = 0;
+    char** argv = to_cstring_array(env, restartArgs, &argc);
+    bool ok = vpkc_apply_updates_and_restart_v2((vpkc_update_manager_t*)(intptr_t)handle, &asset, argv, argc);
+    free_cstring_array(argv, argc);
+    free_java_asset(&asset);
+    if (!ok) {
+        throw_velopack_exception(env);
+    }
+}
+
+JNIEXPORT void JNICALL Java_com_velopack_UpdateManager_nativeApplyUpdatesAndExit(JNIEnv* env, jclass cls, jlong handle, jobject jasset) {
+    (void)cls;
+    vpkc_asset_v2_t asset;
+    if (!asset_from_java(env, jasset, &asset)) {
+        return;
+    }
+    bool ok = vpkc_apply_updates_and_exit_v2((vpkc_update_manager_t*)(intptr_t)handle, &asset);
+    free_java_asset(&asset);
+    if (!ok) {
+        throw_velopack_exception(env);
+    }
+}
+
+JNIEXPORT void JNICALL Java_com_velopack_UpdateManager_nativeWaitExitThenApplyUpdates(
+    JNIEnv* env, jclass cls, jlong handle, jobject jasset, jboolean silent, jboolean restart, jobjectArray restartArgs) {
+    (void)cls;
+    vpkc_asset_v2_t asset;
+    if (!asset_from_java(env, jasset, &asset)) {
+        return;
+    }
+    size_t argc = 0;
+    char** argv = to_cstring_array(env, restartArgs, &argc);
+    bool ok = vpkc_wait_exit_then_apply_update_v2(
+        (vpkc_update_manager_t*)(intptr_t)handle, &asset, silent == JNI_TRUE, restart == JNI_TRUE, argv, argc);
+    free_cstring_array(argv, argc);
+    free_java_asset(&asset);
+    if (!ok) {
+        throw_velopack_exception(env);
+    }
+}
+
+JNIEXPORT void JNICALL Java_com_velopack_UpdateManager_nativeFree(JNIEnv* env, jclass cls, jlong handle) {
+    (void)env;
+    (void)cls;
+    vpkc_free_update_manager((vpkc_update_manager_t*)(intptr_t)handle);
+}
+
+JNIEXPORT void JNICALL Java_com_velopack_VelopackApp_nativeRun(JNIEnv* env, jclass cls, jobjectArray jhooks, jobjectArray args, jboolean autoApply) {
+    (void)cls;
+    size_t argc = 0;
+    char** argv = to_cstring_array(env, args, &argc);
+    if (args != NULL) {
+        vpkc_app_set_args(argv, argc);
+    }
+    vpkc_app_set_auto_apply_on_startup(autoApply == JNI_TRUE);
+
+    // hooks are only ever run synchronously from vpkc_app_run, so local references are valid for the whole call
+    for (int i = 0; i < VPKJ_HOOK_COUNT; i++) {
+        hooks[i] = (*env)->GetObjectArrayElement(env, jhooks, i);
+    }
+    hookEnv = env;
+    vpkc_app_set_hook_after_install(hooks[0] != NULL ? hookCallbacks[0] : NULL);
+    vpkc_app_set_hook_before_uninstall(hooks[1] != NULL ? hookCallbacks[1] : NULL);
+    vpkc_app_set_hook_before_update(hooks[2] != NULL ? hookCallbacks[2] : NULL);
+    vpkc_app_set_hook_after_update(hooks[3] != NULL ? hookCallbacks[3] : NULL);
+    vpkc_app_set_hook_first_run(hooks[4] != NULL ? hookCallbacks[4] : NULL);
+    vpkc_app_set_hook_restarted(hooks[5] != NULL ? hookCallbacks[5] : NULL);
+
+    vpkc_app_run();
+
+    hookEnv = NULL;
+    for (int i = 0; i < VPKJ_HOOK_COUNT; i++) {
+        if (hooks[i] != NULL) {
+            (*env)->DeleteLocalRef(env, hooks[i]);
+            hooks[i] = NULL;
+        }
+    }
+    free_cstring_array(argv, argc);
+}
@@ -0,0 +1,19 @@
+package com.velopack;
+
+final class NativeLibrary {
+    private static boolean loaded;
+
+    private NativeLibrary() {
+    }
+
+    /**
+     * Loads the JNI library, which links against the Velopack C library. Both must be on java.library.path
+     * (or next to the application launcher when packaged with jpackage).
+     */
+    static synchronized void load() {
+        if (!loaded) {
+            System.loadLibrary("velopack_jni");
+            loaded = true;
+        }
+    }
+}
@@ -0,0 +1,40 @@
+package com.velopack;
+
+/**
+ * Holds information about the current version and pending updates, such as how many there are, and access to release notes.
+ */
+public final class UpdateInfo {
+    final VelopackAsset targetFullRelease;
+    final boolean isDowngrade;
+    final boolean isMandatory;
+
+    UpdateInfo(VelopackAsset targetFullRelease, boolean isDowngrade, boolean isMandatory) {
+        this.targetFullRelease = targetFullRelease;
+        this.isDowngrade = isDowngrade;
+        this.isMandatory = isMandatory;
+    }
+
+    /** The available version that we are updating to. */
+    public VelopackAsset getTargetFullRelease() {
+        return targetFullRelease;
+    }
+
+    /**
+     * True if the update is a version downgrade or lateral move (such as when switching channels to the same version number).
+     * In this case, only full updates are allowed, and any local packages on disk newer than the downloaded version will be
+     * deleted.
+     * A release of the same version built for an architecture which suits this machine better (eg. a native ARM64 build
+     * replacing an x64 build running under emulation) is also a lateral move.
+     */
+    public boolean isDowngrade() {
+        return isDowngrade;
+    }
+
+    /**
+     * True if the target release, or any release between the current version and the target version, is marked as mandatory.
+     * Apps should not allow this update to be skipped, and may prevent usage until it has been applied.
+     */
+    public boolean isMandatory() {
+        return isMandatory;
+    }
+}
@@ -0,0 +1,175 @@
+package com.velopack;
+
+import java.util.List;
+import java.util.function.IntConsumer;
+
+/**
+ * Provides functionality for checking for updates, downloading updates, and applying updates to the current application.
+ * This holds a native handle, so it should be closed when it is no longer needed.
+ */
+public final class UpdateManager implements AutoCloseable {
+    static {
+        NativeLibrary.load();
+    }
+
+    private long handle;
+
+    /**
+     * Create a new UpdateManager which checks for updates at the specified url or local directory.
+     */
+    public UpdateManager(String urlOrPath) {
+        this(urlOrPath, false, null);
+    }
+
+    /**
+     * Create a new UpdateManager which checks for updates at the specified url or local directory.
+     *
+     * @param allowVersionDowngrade allows updating to a version that's lower than the current version.
+     * @param explicitChannel overrides the default channel used to fetch updates, this should usually be null.
+     */
+    public UpdateManager(String urlOrPath, boolean allowVersionDowngrade, String explicitChannel) {
+        this(urlOrPath, allowVersionDowngrade, explicitChannel, false);
+    }
+
+    /**
+     * Create a new UpdateManager which checks for updates at the specified url or local directory.
+     *
+     * @param allowVersionDowngrade allows updating to a version that's lower than the current version.
+     * @param explicitChannel overrides the default channel used to fetch updates, this should usually be null.
+     * @param allowPrerelease allows updating to prerelease versions (eg. 2.0.0-beta.1) published to the same channel.
+     */
+    public UpdateManager(String urlOrPath, boolean allowVersionDowngrade, String explicitChannel, boolean allowPrerelease) {
+        this(urlOrPath, allowVersionDowngrade, explicitChannel, allowPrerelease, 0);
+    }
+
+    /**
+     * Create a new UpdateManager which checks for updates at the specified url or local directory.
+     *
+     * @param allowVersionDowngrade allows updating to a version that's lower than the current version.
+     * @param explicitChannel overrides the default channel used to fetch updates, this should usually be null.
+     * @param allowPrerelease allows updating to prerelease versions (eg. 2.0.0-beta.1) published to the same channel.
+     * @param updateCheckIntervalSeconds if greater than zero, update checks within this many seconds of the last check are skipped.
+     */
+    public UpdateManager(String urlOrPath, boolean allowVersionDowngrade, String explicitChannel, boolean allowPrerelease,
+            long updateCheckIntervalSeconds) {
+        handle = nativeNew(urlOrPath, allowVersionDowngrade, explicitChannel, allowPrerelease, updateCheckIntervalSeconds);
+    }
+
+    /** Returns the currently installed version of the app. */
+    public String getCurrentVersion() {
+        return nativeGetCurrentVersion(getHandle());
+    }
+
+    /** Returns the currently installed app id. */
+    public String getAppId() {
+        return nativeGetAppId(getHandle());
+    }
+
+    /** Returns whether the app is in portable mode. */
+    public boolean isPortable() {
+        return nativeIsPortable(getHandle());
+    }
+
+    /**
+     * Returns an asset if there is an update downloaded which still needs to be applied, otherwise null.
+     */
+    public VelopackAsset getUpdatePendingRestart() {
+        return nativeUpdatePendingRestart(getHandle());
+    }
+
+    /**
+     * Checks for updates, returning null if there are none available.
+     */
+    public UpdateInfo checkForUpdates() {
+        return nativeCheckForUpdates(getHandle());
+    }
+
+    /**
+     * Downloads the specified update to the local app packages directory.
+     */
+    public void downloadUpdates(UpdateInfo update) {
+        downloadUpdates(update, null);
+    }
+
+    /**
+     * Downloads the specified update to the local app packages directory. The progress listener (which may be null)
+     * receives the percentage complete (0-100), and is always called on the thread which called this method.
+     */
+    public void downloadUpdates(UpdateInfo update, IntConsumer progress) {
+        nativeDownloadUpdates(getHandle(), requireUpdate(update).targetFullRelease, update.isDowngrade, progress);
+    }
+
+    /**
+     * This will exit your app immediately, apply updates, and then relaunch the app with the specified arguments.
+     * If you need to save state or clean up, you should do that before calling this method.
+     */
+    public void applyUpdatesAndRestart(UpdateInfo update, List<String> restartArgs) {
+        nativeApplyUpdatesAndRestart(getHandle(), requireUpdate(update).targetFullRelease, toArray(restartArgs));
+    }
+
+    /**
+     * This will exit your app immediately and apply updates, without restarting the app afterwards.
+     */
+    public void applyUpdatesAndExit(UpdateInfo update) {
+        nativeApplyUpdatesAndExit(getHandle(), requireUpdate(update).targetFullRelease);
+    }
+
+    /**
+     * This will launch the Velopack updater and tell it to wait for this program to exit gracefully.
+     * You should then clean up any state and exit your app. The updater will apply updates and then
+     * optionally restart your app. The updater will only wait for 60 seconds before giving up.
+     */
+    public void waitExitThenApplyUpdates(UpdateInfo update, boolean silent, boolean restart, List<String> restartArgs) {
+        nativeWaitExitThenApplyUpdates(getHandle(), requireUpdate(update).targetFullRelease, silent, restart, toArray(restartArgs));
+    }
+
+    @Override
+    public synchronized void close() {
+        if (handle != 0) {
+            nativeFree(handle);
+            handle = 0;
+        }
+    }
+
+    private synchronized long getHandle() {
+        if (handle == 0) {
+            throw new IllegalStateException("This UpdateManager has been closed.");
+        }
+        return handle;
+    }
+
+    private static UpdateInfo requireUpdate(UpdateInfo update) {
+        if (update == null) {
+            throw new IllegalArgumentException("update is required");
+        }
+        return update;
+    }
+
+    private static String[] toArray(List<String> args) {
+        return args == null ? new String[0] : args.toArray(new String[0]);
+    }
+
+    private static native long nativeNew(String urlOrPath, boolean allowVersionDowngrade, String explicitChannel, boolean allowPrerelease,
+            long updateCheckIntervalSeconds);
+
+    private static native String nativeGetCurrentVersion(long handle);
+
+    private static native String nativeGetAppId(long handle);
+
+    private static native boolean nativeIsPortable(long handle);
+
+    private static native VelopackAsset nativeUpdatePendingRestart(long handle);
+
+    private static native UpdateInfo nativeCheckForUpdates(long handle);
+
+    private static native void nativeDownloadUpdates(long handle, VelopackAsset asset, boolean isDowngrade, IntConsumer progress);
+
+    private static native void nativeApplyUpdatesAndRestart(long handle, VelopackAsset asset, String[] restartArgs);
+
+    private static native void nativeApplyUpdatesAndExit(long handle, VelopackAsset asset);
+
+    private static native void nativeWaitExitThenApplyUpdates(long handle, VelopackAsset asset, boolean silent, boolean restart,
+            String[] restartArgs);
+
+    private static native void nativeFree(long handle);
+}
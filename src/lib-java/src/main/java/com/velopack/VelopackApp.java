@@ -0,0 +1,114 @@
+package com.velopack;
+
+import java.util.function.Consumer;
+
+/**
+ * The main Velopack startup builder. This should be run as early as possible in your application (the first thing in
+ * main), as it may terminate or restart the process to perform install / update / uninstall tasks.
+ */
+public final class VelopackApp {
+    static {
+        NativeLibrary.load();
+    }
+
+    // the order of these must match the hook indices in velopack_jni.c
+    private static final int HOOK_AFTER_INSTALL = 0;
+    private static final int HOOK_BEFORE_UNINSTALL = 1;
+    private static final int HOOK_BEFORE_UPDATE = 2;
+    private static final int HOOK_AFTER_UPDATE = 3;
+    private static final int HOOK_FIRST_RUN = 4;
+    private static final int HOOK_RESTARTED = 5;
+
+    @SuppressWarnings({ "unchecked", "rawtypes" })
+    private final Consumer<String>[] hooks = new Consumer[6];
+    private String[] args;
+    private boolean autoApply = true;
+
+    private VelopackApp() {
+    }
+
+    public static VelopackApp build() {
+        return new VelopackApp();
+    }
+
+    /**
+     * Set the command line arguments for the app, usually the args passed to main. This should always be set, because
+     * the arguments of the native process include the java launcher arguments when not packaged with jpackage.
+     */
+    public VelopackApp setArgs(String[] args) {
+        this.args = args;
+        return this;
+    }
+
+    /** Set whether to automatically apply downloaded updates on startup. This is ON by default. */
+    public VelopackApp setAutoApplyOnStartup(boolean autoApply) {
+        this.autoApply = autoApply;
+        return this;
+    }
+
+    /** This hook is triggered when the application is started for the first time after installation. */
+    public VelopackApp onFirstRun(Consumer<String> hook) {
+        hooks[HOOK_FIRST_RUN] = hook;
+        return this;
+    }
+
+    /** This hook is triggered when the application is restarted by Velopack after installing updates. */
+    public VelopackApp onRestarted(Consumer<String> hook) {
+        hooks[HOOK_RESTARTED] = hook;
+        return this;
+    }
+
+    /**
+     * WARNING: FastCallback hooks are run during critical stages of Velopack operations.
+     * Your code will be run and then the process will exit.
+     * If your code has not completed within 30 seconds, it will be terminated.
+     * Only supported on windows; On other operating systems, this will never be called.
+     */
+    public VelopackApp onAfterInstallFastCallback(Consumer<String> hook) {
+        hooks[HOOK_AFTER_INSTALL] = hook;
+        return this;
+    }
+
+    /**
+     * WARNING: FastCallback hooks are run during critical stages of Velopack operations.
+     * Your code will be run and then the process will exit.
+     * If your code has not completed within 30 seconds, it will be terminated.
+     * Only supported on windows; On other operating systems, this will never be called.
+     */
+    public VelopackApp onBeforeUninstallFastCallback(Consumer<String> hook) {
+        hooks[HOOK_BEFORE_UNINSTALL] = hook;
+        return this;
+    }
+
+    /**
+     * WARNING: FastCallback hooks are run during critical stages of Velopack operations.
+     * Your code will be run and then the process will exit.
+     * If your code has not completed within 15 seconds, it will be terminated.
+     * Only supported on windows; On other operating systems, this will never be called.
+     */
+    public VelopackApp onBeforeUpdateFastCallback(Consumer<String> hook) {
+        hooks[HOOK_BEFORE_UPDATE] = hook;
+        return this;
+    }
+
+    /**
+     * WARNING: FastCallback hooks are run during critical stages of Velopack operations.
+     * Your code will be run and then the process will exit.
+     * If your code has not completed within 15 seconds, it will be terminated.
+     * Only supported on windows; On other operating systems, this will never be called.
+     */
+    public VelopackApp onAfterUpdateFastCallback(Consumer<String> hook) {
+        hooks[HOOK_AFTER_UPDATE] = hook;
+        return this;
+    }
+
+    /**
+     * Runs the Velopack startup logic. This should be the first thing to run in your app.
+     * In some circumstances it may terminate/restart the process to perform tasks.
+     */
+    public void run() {
+        nativeRun(hooks, args, autoApply);
+    }
+
+    private static native void nativeRun(Consumer<String>[] hooks, String[] args, boolean autoApply);
+}
@@ -0,0 +1,81 @@
+package com.velopack;
+
+/**
+ * An individual Velopack asset, could refer to an asset on-disk or in a remote package feed.
+ */
+public final class VelopackAsset {
+    final String packageId;
+    final String version;
+    final String type;
+    final String fileName;
+    final String sha1;
+    final String sha256;
+    final long size;
+    final String notesMarkdown;
+    final String notesHtml;
+    final boolean mandatory;
+
+    VelopackAsset(String packageId, String version, String type, String fileName, String sha1, String sha256, long size,
+            String notesMarkdown, String notesHtml, boolean mandatory) {
+        this.packageId = packageId;
+        this.version = version;
+        this.type = type;
+        this.fileName = fileName;
+        this.sha1 = sha1;
+        this.sha256 = sha256;
+        this.size = size;
+        this.notesMarkdown = notesMarkdown;
+        this.notesHtml = notesHtml;
+        this.mandatory = mandatory;
+    }
+
+    /** The name or Id of the package containing this release. */
+    public String getPackageId() {
+        return packageId;
+    }
+
+    /** The version of this release. */
+    public String getVersion() {
+        return version;
+    }
+
+    /** The type of asset (eg. "Full" or "Delta"). */
+    public String getType() {
+        return type;
+    }
+
+    /** The filename of the update package containing this release. */
+    public String getFileName() {
+        return fileName;
+    }
+
+    /** The SHA1 checksum of the update package containing this release. */
+    public String getSha1() {
+        return sha1;
+    }
+
+    /** The SHA256 checksum of the update package containing this release. */
+    public String getSha256() {
+        return sha256;
+    }
+
+    /** The size in bytes of the update package containing this release. */
+    public long getSize() {
+        return size;
+    }
+
+    /** The release notes in markdown format, as passed to Velopack when packaging the release. This may be an empty string. */
+    public String getNotesMarkdown() {
+        return notesMarkdown;
+    }
+
+    /** The release notes in HTML format, transformed from Markdown when packaging the release. This may be an empty string. */
+    public String getNotesHtml() {
+        return notesHtml;
+    }
+
+    /** The release has been marked as mandatory by the publisher, and should not be skipped or postponed. */
+    public boolean isMandatory() {
+        return mandatory;
+    }
+}
@@ -0,0 +1,22 @@
+package com.velopack;
+
+/**
+ * Thrown when a Velopack operation fails.
+ */
+public class VelopackException extends RuntimeException {
+    private static final long serialVersionUID = 1L;
+
+    private final int code;
+
+    public VelopackException(String message, int code) {
+        super(message);
+        this.code = code;
+    }
+
+    /**
+     * The vpkc_error_code_t reported by the native library, which describes the kind of failure (eg. IO or network).
+     */
+    public int getCode() {
+        return code;
+    }
+}
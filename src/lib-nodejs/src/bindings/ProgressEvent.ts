@@ -0,0 +1,31 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ProgressPhase } from "./ProgressPhase";
+
+/**
+ * A detailed progress report for a long running operation.
+ */
+export type ProgressEvent = { 
+/**
+ * The stage of the operation which is in progress.
+ */
+Phase: ProgressPhase, 
+/**
+ * The percentage complete of the current phase, from 0 to 100.
+ */
+Percent: number, 
+/**
+ * The number of bytes processed so far.
+ */
+BytesDone: number, 
+/**
+ * The total number of bytes to process, if known.
+ */
+BytesTotal: number | null, 
+/**
+ * The recent processing speed, in bytes per second.
+ */
+BytesPerSecond: number, 
+/**
+ * The estimated number of seconds remaining, if it can be calculated.
+ */
+EtaSeconds: number | null, };
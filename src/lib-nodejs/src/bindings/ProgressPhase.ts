@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * The stage of an operation which a [`ProgressEvent`] refers to. Updates are applied by Update.exe after the app has
+ * exited, so there is no phase for applying them.
+ */
+export type ProgressPhase = "Download" | "Extract";
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * The release notes for a single version in the update feed.
+ */
+export type ReleaseNotes = { 
+/**
+ * The version these release notes belong to.
+ */
+Version: string, 
+/**
+ * The release notes in markdown format. This may be an empty string.
+ */
+NotesMarkdown: string, 
+/**
+ * The release notes in HTML format, if they were available in the feed. This may be an empty string.
+ */
+NotesHtml: string, };
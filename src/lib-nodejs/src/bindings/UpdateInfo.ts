@@ -1,4 +1,5 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ReleaseNotes } from "./ReleaseNotes";
 import type { VelopackAsset } from "./VelopackAsset";
 
 /**
@@ -13,5 +14,23 @@ TargetFullRelease: VelopackAsset,
  * True if the update is a version downgrade or lateral move (such as when switching channels to the same version number).
  * In this case, only full updates are allowed, and any local packages on disk newer than the downloaded version will be
  * deleted.
+ * A release of the same version built for an architecture which suits this machine better (eg. a native ARM64 build
+ * replacing an x64 build running under emulation) is also a lateral move.
  */
-IsDowngrade: boolean, };
+IsDowngrade: boolean, 
+/**
+ * The release notes for the target version and every intermediate version between the current version and the
+ * target version, ordered from newest to oldest. This is useful for showing a "What's new" summary of everything
+ * that will change, rather than only the notes for the latest release.
+ */
+ReleaseNotes: Array<ReleaseNotes>, 
+/**
+ * True if the target release, or any release between the current version and the target version, is marked as mandatory.
+ * Apps should not allow this update to be skipped, and may prevent usage until it has been applied.
+ */
+IsMandatory: boolean, 
+/**
+ * True if the update source could not be reached, and this update was found in the release feed cached by the last
+ * successful update check (see UseCachedFeedWhenOffline). The update may no longer be available, or may be out of date.
+ */
+IsStale: boolean, };
@@ -20,4 +20,78 @@ AllowVersionDowngrade: boolean,
  * allows you to explicitly switch channels, for example if the user wished to switch back to the 'stable' channel
  * without having to reinstall the application.
  */
-ExplicitChannel: string | null, };
+ExplicitChannel: string | null, 
+/**
+ * Allows UpdateManager to update to prerelease versions (eg. '2.0.0-beta.1') from the feed, so users can opt into
+ * prerelease builds without switching to a separate channel. Prereleases are always allowed if the current version is
+ * itself a prerelease, or if ExplicitChannel selects a channel other than the one this version was packaged for.
+ */
+AllowPrerelease: boolean, 
+/**
+ * If greater than zero, check_for_updates will not contact the update source if the last check was less than this many
+ * seconds ago, and will report that no update is available instead. The time of the last check is persisted next to the
+ * app, and a random delay of up to 10% of the interval is added so that clients started at the same time spread out
+ * their checks rather than polling the feed all at once.
+ */
+UpdateCheckIntervalSeconds: number, 
+/**
+ * The number of full packages to keep in the packages directory once an update has been applied, including the package
+ * for the installed version. Keeping more than one allows rolling back to a previous version without a network connection.
+ * Zero uses the default, which is to keep only the package for the installed version.
+ */
+PackagesToKeep: number, 
+/**
+ * If greater than zero, the oldest packages are deleted until the packages kept add up to less than this many bytes. The
+ * package for the installed version is never deleted.
+ */
+MaxPackagesSizeBytes: number, 
+/**
+ * If greater than zero, packages which were downloaded more than this many days ago are deleted. The package for the
+ * installed version is never deleted.
+ */
+MaxPackageAgeDays: number, 
+/**
+ * Overrides the directory downloaded packages and temporary files are stored in (eg. a different drive with more free
+ * space, or a cache directory shared by several apps). Packages are stored in a sub-directory named after the app id, and
+ * relative paths are resolved against the app install directory. This takes priority over the VELOPACK_PACKAGES_DIR
+ * environment variable and the packages directory specified when the app was packaged.
+ */
+PackagesDir: string | null, 
+/**
+ * A machine-level directory where downloaded packages are cached by their checksum, so that apps (or channels of the
+ * same app) which are configured with the same directory do not download identical packages again. The directory must
+ * be writable by every user which runs these apps. Nothing is cached if this is None.
+ */
+SharedCacheDir: string | null, 
+/**
+ * The maximum size of the shared cache, the least recently used packages are deleted once it grows past this size.
+ * Zero uses the default, which is 2 GB.
+ */
+SharedCacheMaxSizeBytes: number, 
+/**
+ * The url (or network directory) of an intranet cache which packages are downloaded from before trying the update
+ * source, see CacheServerSource for the protocol. If this is None, the VELOPACK_CACHE_SERVER environment
+ * variable is used instead, so the cache can be configured for every app on a machine.
+ */
+CacheServerUrl: string | null, 
+/**
+ * Replaces the User-Agent sent with every request to the update source, eg. 'MyApp/1.2.3 (beta)', so that the server
+ * can tell which app (and version) is asking for updates. The default User-Agent is used if this is None.
+ */
+UserAgent: string | null, 
+/**
+ * Extra query parameters added to requests for the release feed, as a query string (eg. 'cohort=3&region=eu'), so
+ * that server-side analytics or staged rollouts can target groups of clients. Nothing is added if this is None.
+ */
+FeedQueryParameters: string | null, 
+/**
+ * Extra headers sent with every request to the update source, one per line as 'Name: value' (eg. 'X-Api-Key: abc123'),
+ * so that servers which need authentication or routing headers can be used. Nothing is added if this is None.
+ */
+RequestHeaders: string | null, 
+/**
+ * If true, the release feed of each successful update check is cached in the packages directory, and check_for_updates uses the
+ * cached feed if the update source can not be reached (eg. because the machine is offline). Updates found in the cached
+ * feed are marked with IsStale, and UpdateCheck::NoUpdateAvailableOffline is returned if there are none.
+ */
+UseCachedFeedWhenOffline: boolean, };
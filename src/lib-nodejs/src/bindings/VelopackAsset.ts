@@ -39,4 +39,13 @@ NotesMarkdown: string,
 /**
  * The release notes in HTML format, transformed from Markdown when packaging the release. This may be an empty string.
  */
-NotesHtml: string, };
+NotesHtml: string, 
+/**
+ * The release has been marked as mandatory by the publisher, and should not be skipped or postponed.
+ */
+IsMandatory: boolean, 
+/**
+ * The CPU architecture this release was built for (eg. "x64", "arm64" or "universal"). This may be an empty string,
+ * in which case the release is assumed to run on any machine.
+ */
+Architecture: string, };
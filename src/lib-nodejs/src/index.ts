@@ -2,8 +2,18 @@ import * as addon from "./load";
 import type { UpdateInfo } from "./bindings/UpdateInfo";
 import type { UpdateOptions } from "./bindings/UpdateOptions";
 import type { VelopackLocatorConfig } from "./bindings/VelopackLocatorConfig";
-
-export { UpdateInfo, UpdateOptions, VelopackLocatorConfig };
+import type { ProgressEvent } from "./bindings/ProgressEvent";
+import type { ProgressPhase } from "./bindings/ProgressPhase";
+import type { ReleaseNotes } from "./bindings/ReleaseNotes";
+
+export {
+  UpdateInfo,
+  UpdateOptions,
+  VelopackLocatorConfig,
+  ProgressEvent,
+  ProgressPhase,
+  ReleaseNotes,
+};
 
 type UpdateManagerOpaque = {};
 declare module "./load" {
@@ -19,6 +29,10 @@ declare module "./load" {
 
   function js_is_portable(um: UpdateManagerOpaque): boolean;
 
+  function js_is_translocated(um: UpdateManagerOpaque): boolean;
+
+  function js_is_managed_externally(um: UpdateManagerOpaque): boolean;
+
   function js_update_pending_restart(
     um: UpdateManagerOpaque,
   ): UpdateInfo | null;
@@ -33,6 +47,12 @@ declare module "./load" {
     progress: (perc: number) => void,
   ): Promise<void>;
 
+  function js_download_update_with_events_async(
+    um: UpdateManagerOpaque,
+    update: string,
+    progress: (event: string) => void,
+  ): Promise<void>;
+
   function js_wait_exit_then_apply_update(
     um: UpdateManagerOpaque,
     update: string,
@@ -215,6 +235,24 @@ export class UpdateManager {
     return addon.js_is_portable(this.opaque);
   }
 
+  /**
+   * Returns whether MacOS is running the app from a translocated (read-only) location, which happens when
+   * it is launched from a DMG or the downloads folder. Updates will fail until the app is moved to /Applications,
+   * so you should ask the user to do that. On Windows and Linux this will always be false.
+   */
+  isTranslocated(): boolean {
+    return addon.js_is_translocated(this.opaque);
+  }
+
+  /**
+   * Returns whether the app is running in a sandbox such as Flatpak or Snap, which delivers its updates.
+   * If this is true, checkForUpdatesAsync will always return null and downloading or applying updates will fail,
+   * so you should hide any update UI. On Windows and MacOS this will always be false.
+   */
+  isManagedExternally(): boolean {
+    return addon.js_is_managed_externally(this.opaque);
+  }
+
   /**
    * Returns an UpdateInfo object if there is an update downloaded which still needs to be applied.
    * You can pass the UpdateInfo object to waitExitThenApplyUpdate to apply the update.
@@ -261,6 +299,25 @@ export class UpdateManager {
     );
   }
 
+  /**
+   * Downloads the specified updates to the local app packages directory, the same as downloadUpdateAsync, but
+   * reports detailed progress events (phase, bytes transferred, speed, and estimated time remaining).
+   * Progress events are delivered on the main JS thread.
+   */
+  downloadUpdateWithEventsAsync(
+    update: UpdateInfo,
+    progress: (event: ProgressEvent) => void,
+  ): Promise<void> {
+    if (!update) {
+      throw new Error("update is required");
+    }
+    return addon.js_download_update_with_events_async(
+      this.opaque,
+      JSON.stringify(update),
+      (json: string) => progress(JSON.parse(json)),
+    );
+  }
+
   /**
    * This will launch the Velopack updater and tell it to wait for this program to exit gracefully.
    * You should then clean up any state and exit your app. The updater will apply updates and then
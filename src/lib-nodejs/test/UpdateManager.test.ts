@@ -1,5 +1,6 @@
 import { copyFileSync, existsSync } from "fs";
 import {
+  ProgressEvent,
   UpdateManager,
   UpdateOptions,
   VelopackApp,
@@ -86,3 +87,42 @@ test("UpdateManager downloads full update", async () => {
     });
   });
 });
+
+test("UpdateManager reports download progress events", async () => {
+  await captureLogs(async () => {
+    await tempd3(async (feedDir, packagesDir, rootDir) => {
+      const locator: VelopackLocatorConfig = {
+        ManifestPath: "../../test/fixtures/Test.Squirrel-App.nuspec",
+        PackagesDir: packagesDir,
+        RootAppDir: rootDir,
+        UpdateExePath: updateExe(),
+        CurrentBinaryDir: path.join(rootDir, "current"),
+        IsPortable: true,
+      };
+
+      const options: UpdateOptions = {
+        ExplicitChannel: "beta",
+        AllowVersionDowngrade: false,
+      };
+
+      const um = new UpdateManager(feedDir, options, locator);
+      copyFileSync(
+        fixture("testfeed.json"),
+        path.join(feedDir, "releases.beta.json"),
+      );
+
+      copyFileSync(
+        fixture("AvaloniaCrossPlat-1.0.11-win-full.nupkg"),
+        path.join(feedDir, "AvaloniaCrossPlat-1.0.11-full.nupkg"),
+      );
+
+      const update = await um.checkForUpdatesAsync();
+      const events: ProgressEvent[] = [];
+      await um.downloadUpdateWithEventsAsync(update!, (e) => events.push(e));
+
+      expect(events.length).toBeGreaterThan(0);
+      expect(events.every((e) => e.Phase === "Download")).toBe(true);
+      expect(events[events.length - 1].Percent).toBe(100);
+    });
+  });
+});
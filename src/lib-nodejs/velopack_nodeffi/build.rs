@@ -9,4 +9,5 @@ fn main() {
     UpdateInfo::export_all_to(&bindings_dir).unwrap();
     UpdateOptions::export_all_to(&bindings_dir).unwrap();
     VelopackLocatorConfig::export_all_to(&bindings_dir).unwrap();
+    progress::ProgressEvent::export_all_to(&bindings_dir).unwrap();
 }
\ No newline at end of file
@@ -5,6 +5,7 @@ use std::cell::RefCell;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 use std::thread;
+use velopack::progress::ProgressEvent;
 use velopack::sources::*;
 use velopack::*;
 
@@ -86,6 +87,20 @@ fn js_is_portable(mut cx: FunctionContext) -> JsResult<JsBoolean> {
     Ok(cx.boolean(is_portable))
 }
 
+fn js_is_translocated(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    let mgr_boxed = cx.argument::<BoxedUpdateManager>(0)?;
+    let mgr_ref = &mgr_boxed.borrow().manager;
+    let is_translocated = mgr_ref.get_is_translocated();
+    Ok(cx.boolean(is_translocated))
+}
+
+fn js_is_managed_externally(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    let mgr_boxed = cx.argument::<BoxedUpdateManager>(0)?;
+    let mgr_ref = &mgr_boxed.borrow().manager;
+    let is_managed_externally = mgr_ref.get_is_managed_externally();
+    Ok(cx.boolean(is_managed_externally))
+}
+
 fn js_update_pending_restart(mut cx: FunctionContext) -> JsResult<JsValue> {
     let mgr_boxed = cx.argument::<BoxedUpdateManager>(0)?;
     let mgr_ref = &mgr_boxed.borrow().manager;
@@ -141,31 +156,58 @@ fn js_check_for_updates_async(mut cx: FunctionContext) -> JsResult<JsPromise> {
     Ok(promise)
 }
 
-fn js_download_update_async(mut cx: FunctionContext) -> JsResult<JsPromise> {
+fn js_download_update_async(cx: FunctionContext) -> JsResult<JsPromise> {
+    download_update_with_progress(cx, |cx, progress: i16| Ok(cx.number(progress).upcast()), |mgr, update, sender| {
+        mgr.download_updates(update, Some(sender))
+    })
+}
+
+fn js_download_update_with_events_async(cx: FunctionContext) -> JsResult<JsPromise> {
+    download_update_with_progress(
+        cx,
+        |cx, event: ProgressEvent| {
+            let json = serde_json::to_string(&event).or_else(|e| cx.throw_error(e.to_string()))?;
+            Ok(cx.string(json).upcast())
+        },
+        |mgr, update, sender| mgr.download_updates_with_events(update, Some(sender)),
+    )
+}
+
+/// Starts a download on a background thread, delivering each progress value to the JS callback (converted with to_js)
+/// on the main JS thread, and returns a promise which settles when the download completes.
+fn download_update_with_progress<T, C, D>(mut cx: FunctionContext, to_js: C, download: D) -> JsResult<JsPromise>
+where
+    T: Send + 'static,
+    C: Fn(&mut TaskContext, T) -> NeonResult<Handle<JsValue>> + Send + Sync + 'static,
+    D: FnOnce(&UpdateManager, &UpdateInfo, std::sync::mpsc::Sender<T>) -> Result<(), Error> + Send + 'static,
+{
     let mgr_boxed = cx.argument::<BoxedUpdateManager>(0)?;
     let mgr_ref = &mgr_boxed.borrow().manager;
     let mgr_clone = mgr_ref.clone();
 
     let arg_update = cx.argument::<JsString>(1)?.value(&mut cx);
     let callback_rc = cx.argument::<JsFunction>(2)?.root(&mut cx);
+    // progress and completion share one channel, so the promise never settles before all progress has been delivered
     let channel1 = cx.channel();
-    let channel2 = cx.channel();
+    let channel2 = channel1.clone();
 
     let update_info = serde_json::from_str::<UpdateInfo>(&arg_update).or_else(|e| cx.throw_error(e.to_string()))?;
     let (deferred, promise) = cx.promise();
-    let (sender, receiver) = std::sync::mpsc::channel::<i16>();
+    let (sender, receiver) = std::sync::mpsc::channel::<T>();
+    let to_js = Arc::new(to_js);
 
     // spawn a thread to handle the progress updates
-    thread::spawn(move || {
+    let progress_thread = thread::spawn(move || {
         let callback_moved = Arc::new(Mutex::new(Some(callback_rc)));
         while let Ok(progress) = receiver.recv() {
             let callback_clone = callback_moved.clone();
+            let to_js = to_js.clone();
             channel1.send(move |mut cx| {
                 if let Ok(guard) = callback_clone.lock() {
                     if let Some(cb_s) = guard.as_ref() {
                         let callback_inner = cb_s.to_inner(&mut cx);
                         let this = cx.undefined();
-                        let args = vec![cx.number(progress).upcast()];
+                        let args = vec![to_js(&mut cx, progress)?];
                         callback_inner.call(&mut cx, this, args).unwrap();
                     }
                 }
@@ -186,17 +228,21 @@ fn js_download_update_async(mut cx: FunctionContext) -> JsResult<JsPromise> {
     });
 
     // spawn a thread to download the updates
-    thread::spawn(move || match mgr_clone.download_updates(&update_info, Some(sender)) {
-        Ok(_) => channel2.send(|mut cx| {
-            let val = cx.undefined();
-            deferred.resolve(&mut cx, val);
-            Ok(())
-        }),
-        Err(e) => channel2.send(move |mut cx| {
-            let err = cx.error(e.to_string()).unwrap();
-            deferred.reject(&mut cx, err);
-            Ok(())
-        }),
+    thread::spawn(move || {
+        let result = download(&mgr_clone, &update_info, sender);
+        let _ = progress_thread.join();
+        match result {
+            Ok(_) => channel2.send(|mut cx| {
+                let val = cx.undefined();
+                deferred.resolve(&mut cx, val);
+                Ok(())
+            }),
+            Err(e) => channel2.send(move |mut cx| {
+                let err = cx.error(e.to_string()).unwrap();
+                deferred.reject(&mut cx, err);
+                Ok(())
+            }),
+        };
     });
 
     Ok(promise)
@@ -290,9 +336,12 @@ fn main(mut cx: ModuleContext) -> NeonResult<()> {
     cx.export_function("js_get_current_version", js_get_current_version)?;
     cx.export_function("js_get_app_id", js_get_app_id)?;
     cx.export_function("js_is_portable", js_is_portable)?;
+    cx.export_function("js_is_translocated", js_is_translocated)?;
+    cx.export_function("js_is_managed_externally", js_is_managed_externally)?;
     cx.export_function("js_update_pending_restart", js_update_pending_restart)?;
     cx.export_function("js_check_for_updates_async", js_check_for_updates_async)?;
     cx.export_function("js_download_update_async", js_download_update_async)?;
+    cx.export_function("js_download_update_with_events_async", js_download_update_with_events_async)?;
     cx.export_function("js_wait_exit_then_apply_update", js_wait_exit_then_apply_update)?;
     cx.export_function("js_appbuilder_run", js_appbuilder_run)?;
     cx.export_function("js_set_logger_callback", js_set_logger_callback)?;
@@ -0,0 +1,482 @@
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use semver::Version;
+use std::sync::mpsc;
+use std::thread;
+use velopack::sources::AutoSource;
+use velopack::{UpdateCheck, UpdateInfo, UpdateManager, UpdateOptions, VelopackApp, VelopackAsset};
+
+create_exception!(velopack, VelopackError, PyException, "Raised when a Velopack operation fails.");
+create_exception!(velopack, NetworkError, VelopackError, "Raised when the update source could not be reached.");
+create_exception!(velopack, HashMismatchError, VelopackError, "Raised when a downloaded package does not match its expected checksum.");
+create_exception!(velopack, FeedParseError, VelopackError, "Raised when the release feed could not be parsed.");
+create_exception!(velopack, NotInstalledError, VelopackError, "Raised when the app is not installed, or is missing files Velopack needs.");
+create_exception!(velopack, BusyError, VelopackError, "Raised when another update operation is already in progress.");
+create_exception!(velopack, LockedError, BusyError, "Raised when an installer or updater for the app is running in another process.");
+create_exception!(velopack, CancelledError, VelopackError, "Raised when an operation was cancelled.");
+create_exception!(velopack, ManagedExternallyError, VelopackError, "Raised when the app is updated by its sandbox (eg. Flatpak or Snap).");
+create_exception!(velopack, TranslocatedError, VelopackError, "Raised when the app is translocated and must be moved before updating.");
+
+fn to_py_err(e: velopack::Error) -> PyErr {
+    use velopack::Error;
+    let message = e.to_string();
+    match e {
+        Error::Network(_) => NetworkError::new_err(message),
+        Error::HashMismatch(_) => HashMismatchError::new_err(message),
+        Error::FeedParse(_) => FeedParseError::new_err(message),
+        Error::MissingNuspec | Error::MissingNuspecProperty(_) | Error::MissingUpdateExe | Error::NotInstalled(_) => {
+            NotInstalledError::new_err(message)
+        }
+        Error::OperationInProgress(_) => BusyError::new_err(message),
+        Error::Locked(_) => LockedError::new_err(message),
+        Error::Cancelled => CancelledError::new_err(message),
+        Error::AppTranslocated(_) => TranslocatedError::new_err(message),
+        Error::UpdatesManagedExternally(_) => ManagedExternallyError::new_err(message),
+        _ => VelopackError::new_err(message),
+    }
+}
+
+/// An individual Velopack asset, could refer to an asset on-disk or in a remote package feed.
+#[pyclass(name = "VelopackAsset", module = "velopack", frozen)]
+#[derive(Clone)]
+struct PyVelopackAsset(VelopackAsset);
+
+#[pymethods]
+impl PyVelopackAsset {
+    /// The name or Id of the package containing this release.
+    #[getter]
+    fn package_id(&self) -> String {
+        self.0.PackageId.clone()
+    }
+
+    /// The version of this release.
+    #[getter]
+    fn version(&self) -> String {
+        self.0.Version.clone()
+    }
+
+    /// The type of asset (eg. "Full" or "Delta").
+    #[getter]
+    fn r#type(&self) -> String {
+        self.0.Type.clone()
+    }
+
+    /// The filename of the update package containing this release.
+    #[getter]
+    fn file_name(&self) -> String {
+        self.0.FileName.clone()
+    }
+
+    /// The SHA1 checksum of the update package containing this release.
+    #[getter]
+    fn sha1(&self) -> String {
+        self.0.SHA1.clone()
+    }
+
+    /// The SHA256 checksum of the update package containing this release.
+    #[getter]
+    fn sha256(&self) -> String {
+        self.0.SHA256.clone()
+    }
+
+    /// The size in bytes of the update package containing this release.
+    #[getter]
+    fn size(&self) -> u64 {
+        self.0.Size
+    }
+
+    /// The release notes in markdown format, as passed to Velopack when packaging the release.
+    #[getter]
+    fn notes_markdown(&self) -> String {
+        self.0.NotesMarkdown.clone()
+    }
+
+    /// The release notes in HTML format, transformed from Markdown when packaging the release.
+    #[getter]
+    fn notes_html(&self) -> String {
+        self.0.NotesHtml.clone()
+    }
+
+    /// True if the release has been marked as mandatory by the publisher.
+    #[getter]
+    fn is_mandatory(&self) -> bool {
+        self.0.IsMandatory
+    }
+
+    /// The CPU architecture this release was built for (eg. "x64", "arm64" or "universal"), or an empty string if not specified.
+    #[getter]
+    fn architecture(&self) -> String {
+        self.0.Architecture.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("VelopackAsset(package_id='{}', version='{}', file_name='{}')", self.0.PackageId, self.0.Version, self.0.FileName)
+    }
+}
+
+/// Holds information about the current version and pending updates, such as how many there are, and access to release notes.
+#[pyclass(name = "UpdateInfo", module = "velopack", frozen)]
+#[derive(Clone)]
+struct PyUpdateInfo(UpdateInfo);
+
+#[pymethods]
+impl PyUpdateInfo {
+    /// The available version that we are updating to.
+    #[getter]
+    fn target_full_release(&self) -> PyVelopackAsset {
+        PyVelopackAsset(self.0.TargetFullRelease.clone())
+    }
+
+    /// True if the update is a version downgrade or lateral move (such as when switching channels to the same version number).
+    /// A release of the same version built for an architecture which suits this machine better (eg. a native ARM64 build
+    /// replacing an x64 build running under emulation) is also a lateral move.
+    #[getter]
+    fn is_downgrade(&self) -> bool {
+        self.0.IsDowngrade
+    }
+
+    /// True if the target release, or any release between the current version and the target version, is marked as mandatory.
+    #[getter]
+    fn is_mandatory(&self) -> bool {
+        self.0.IsMandatory
+    }
+
+    /// True if the update source could not be reached, and this update was found in the release feed cached by the last
+    /// successful update check. The update may no longer be available, or may be out of date.
+    #[getter]
+    fn is_stale(&self) -> bool {
+        self.0.IsStale
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "UpdateInfo(version='{}', is_downgrade={}, is_mandatory={}, is_stale={})",
+            self.0.TargetFullRelease.Version, self.0.IsDowngrade, self.0.IsMandatory, self.0.IsStale
+        )
+    }
+}
+
+/// The update to apply, which can be either an UpdateInfo or a VelopackAsset.
+#[derive(FromPyObject)]
+enum AssetArg {
+    Info(PyUpdateInfo),
+    Asset(PyVelopackAsset),
+}
+
+impl AssetArg {
+    fn into_asset(self) -> VelopackAsset {
+        match self {
+            AssetArg::Info(info) => info.0.TargetFullRelease,
+            AssetArg::Asset(asset) => asset.0,
+        }
+    }
+}
+
+/// Provides functionality for checking for updates, downloading updates, and applying updates to the current application.
+#[pyclass(name = "UpdateManager", module = "velopack", frozen)]
+struct PyUpdateManager {
+    manager: UpdateManager,
+}
+
+#[pymethods]
+impl PyUpdateManager {
+    /// Create a new UpdateManager which checks for updates at the specified url or local directory.
+    #[new]
+    #[pyo3(signature = (
+        url_or_path,
+        allow_version_downgrade = false,
+        explicit_channel = None,
+        allow_prerelease = false,
+        update_check_interval_seconds = 0,
+        packages_to_keep = 0,
+        max_packages_size_bytes = 0,
+        max_package_age_days = 0,
+        packages_dir = None,
+        shared_cache_dir = None,
+        shared_cache_max_size_bytes = 0,
+        cache_server_url = None,
+        user_agent = None,
+        feed_query_parameters = None,
+        use_cached_feed_when_offline = false,
+        request_headers = None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        url_or_path: &str,
+        allow_version_downgrade: bool,
+        explicit_channel: Option<String>,
+        allow_prerelease: bool,
+        update_check_interval_seconds: u64,
+        packages_to_keep: u32,
+        max_packages_size_bytes: u64,
+        max_package_age_days: u32,
+        packages_dir: Option<String>,
+        shared_cache_dir: Option<String>,
+        shared_cache_max_size_bytes: u64,
+        cache_server_url: Option<String>,
+        user_agent: Option<String>,
+        feed_query_parameters: Option<String>,
+        use_cached_feed_when_offline: bool,
+        request_headers: Option<String>,
+    ) -> PyResult<Self> {
+        let source = AutoSource::new(url_or_path);
+        let options = UpdateOptions {
+            AllowVersionDowngrade: allow_version_downgrade,
+            ExplicitChannel: explicit_channel,
+            AllowPrerelease: allow_prerelease,
+            UpdateCheckIntervalSeconds: update_check_interval_seconds,
+            PackagesToKeep: packages_to_keep,
+            MaxPackagesSizeBytes: max_packages_size_bytes,
+            MaxPackageAgeDays: max_package_age_days,
+            PackagesDir: packages_dir,
+            SharedCacheDir: shared_cache_dir,
+            SharedCacheMaxSizeBytes: shared_cache_max_size_bytes,
+            CacheServerUrl: cache_server_url,
+            UserAgent: user_agent,
+            FeedQueryParameters: feed_query_parameters,
+            RequestHeaders: request_headers,
+            UseCachedFeedWhenOffline: use_cached_feed_when_offline,
+        };
+        let manager = UpdateManager::new(source, Some(options), None).map_err(to_py_err)?;
+        Ok(PyUpdateManager { manager })
+    }
+
+    /// The currently installed app version.
+    fn get_current_version(&self) -> String {
+        self.manager.get_current_version_as_string()
+    }
+
+    /// The currently installed app id.
+    fn get_app_id(&self) -> String {
+        self.manager.get_app_id()
+    }
+
+    /// Check if the app is portable or installed.
+    fn get_is_portable(&self) -> bool {
+        self.manager.get_is_portable()
+    }
+
+    /// Check if macOS is running the app from a translocated (read-only) location. Updates can not be downloaded or
+    /// applied until the user moves the app to /Applications, and will raise TranslocatedError.
+    fn get_is_translocated(&self) -> bool {
+        self.manager.get_is_translocated()
+    }
+
+    /// Check if the app is running in a sandbox such as Flatpak or Snap, which delivers its updates. check_for_updates
+    /// returns None, and downloading or applying updates will raise ManagedExternallyError.
+    fn get_is_managed_externally(&self) -> bool {
+        self.manager.get_is_managed_externally()
+    }
+
+    /// Returns a VelopackAsset if there is a local update prepared that requires a call to apply_updates_and_restart
+    /// to be applied, otherwise None.
+    fn get_update_pending_restart(&self) -> Option<PyVelopackAsset> {
+        self.manager.get_update_pending_restart().map(PyVelopackAsset)
+    }
+
+    /// Checks for updates, returning None if there are none available. If there are updates available, this method will
+    /// return an UpdateInfo object containing the latest available release, and any delta updates that can be applied.
+    fn check_for_updates(&self, py: Python<'_>) -> PyResult<Option<PyUpdateInfo>> {
+        let result = py.allow_threads(|| self.manager.check_for_updates()).map_err(to_py_err)?;
+        match result {
+            UpdateCheck::UpdateAvailable(info) => Ok(Some(PyUpdateInfo(info))),
+            _ => Ok(None),
+        }
+    }
+
+    /// Checks for updates like check_for_updates, returning a tuple of the UpdateInfo (or None) and whether the update
+    /// source could not be reached, in which case the release feed cached by the last successful update check was used.
+    /// The source is only treated as offline if use_cached_feed_when_offline is enabled, otherwise a NetworkError is raised.
+    fn check_for_updates_with_status(&self, py: Python<'_>) -> PyResult<(Option<PyUpdateInfo>, bool)> {
+        let result = py.allow_threads(|| self.manager.check_for_updates()).map_err(to_py_err)?;
+        match result {
+            UpdateCheck::UpdateAvailable(info) => {
+                let is_offline = info.IsStale;
+                Ok((Some(PyUpdateInfo(info)), is_offline))
+            }
+            UpdateCheck::NoUpdateAvailableOffline => Ok((None, true)),
+            _ => Ok((None, false)),
+        }
+    }
+
+    /// Downloads the specified updates to the local app packages directory. If a progress callback is provided, it is
+    /// called with the percentage complete (0-100) on the calling thread while the download is in progress.
+    #[pyo3(signature = (update, progress = None))]
+    fn download_updates(&self, py: Python<'_>, update: PyUpdateInfo, progress: Option<PyObject>) -> PyResult<()> {
+        let (sender, receiver) = mpsc::channel::<i16>();
+        let manager = self.manager.clone();
+        let download = thread::spawn(move || manager.download_updates(&update.0, Some(sender)));
+
+        // release the GIL while waiting, so other python threads (eg. a UI) can keep running during the download
+        while let Ok(percent) = py.allow_threads(|| receiver.recv()) {
+            if let Some(progress) = &progress {
+                progress.call1(py, (percent,))?;
+            }
+        }
+
+        match py.allow_threads(|| download.join()) {
+            Ok(result) => result.map_err(to_py_err),
+            Err(_) => Err(VelopackError::new_err("The download thread panicked.")),
+        }
+    }
+
+    /// This will exit your app immediately, apply updates, and then relaunch the app with the specified restart args.
+    /// If you need to save state or clean up, you should do that before calling this method.
+    #[pyo3(signature = (update, restart_args = None))]
+    fn apply_updates_and_restart(&self, update: AssetArg, restart_args: Option<Vec<String>>) -> PyResult<()> {
+        let restart_args = restart_args.unwrap_or_default();
+        self.manager.apply_updates_and_restart_with_args(update.into_asset(), restart_args).map_err(to_py_err)
+    }
+
+    /// This will exit your app immediately, apply updates, and then optionally relaunch the app.
+    /// If you need to save state or clean up, you should do that before calling this method.
+    fn apply_updates_and_exit(&self, update: AssetArg) -> PyResult<()> {
+        self.manager.apply_updates_and_exit(update.into_asset()).map_err(to_py_err)
+    }
+
+    /// This will launch the Velopack updater and tell it to wait for this program to exit gracefully.
+    /// You should then clean up any state and exit your app. The updater will apply updates and then
+    /// optionally restart your app. The updater will only wait for 60 seconds before giving up.
+    #[pyo3(signature = (update, silent = false, restart = true, restart_args = None))]
+    fn wait_exit_then_apply_updates(
+        &self,
+        update: AssetArg,
+        silent: bool,
+        restart: bool,
+        restart_args: Option<Vec<String>>,
+    ) -> PyResult<()> {
+        let restart_args = restart_args.unwrap_or_default();
+        self.manager.wait_exit_then_apply_updates(update.into_asset(), silent, restart, restart_args).map_err(to_py_err)
+    }
+}
+
+/// The main Velopack startup builder. This should be run as early as possible in your application, as it may
+/// terminate or restart the process to perform install / update / uninstall tasks.
+#[pyclass(name = "App", module = "velopack")]
+#[derive(Default)]
+struct PyApp {
+    args: Option<Vec<String>>,
+    manual_apply: bool,
+    first_run: Option<PyObject>,
+    restarted: Option<PyObject>,
+    after_install: Option<PyObject>,
+    before_uninstall: Option<PyObject>,
+    before_update: Option<PyObject>,
+    after_update: Option<PyObject>,
+}
+
+#[pymethods]
+impl PyApp {
+    #[new]
+    fn new() -> Self {
+        PyApp::default()
+    }
+
+    /// Override the command line arguments used by the app (instead of sys.argv).
+    fn set_args(mut slf: PyRefMut<'_, Self>, args: Vec<String>) -> PyRefMut<'_, Self> {
+        slf.args = Some(args);
+        slf
+    }
+
+    /// Set whether to automatically apply downloaded updates on startup. This is on by default.
+    fn set_auto_apply_on_startup(mut slf: PyRefMut<'_, Self>, apply: bool) -> PyRefMut<'_, Self> {
+        slf.manual_apply = !apply;
+        slf
+    }
+
+    /// This hook is triggered when the application is started for the first time after installation.
+    fn on_first_run(mut slf: PyRefMut<'_, Self>, hook: PyObject) -> PyRefMut<'_, Self> {
+        slf.first_run = Some(hook);
+        slf
+    }
+
+    /// This hook is triggered when the application is restarted by Velopack after installing updates.
+    fn on_restarted(mut slf: PyRefMut<'_, Self>, hook: PyObject) -> PyRefMut<'_, Self> {
+        slf.restarted = Some(hook);
+        slf
+    }
+
+    /// Fast callback hook, run after the app is installed. Only supported on Windows.
+    fn on_after_install_fast_callback(mut slf: PyRefMut<'_, Self>, hook: PyObject) -> PyRefMut<'_, Self> {
+        slf.after_install = Some(hook);
+        slf
+    }
+
+    /// Fast callback hook, run before the app is uninstalled. Only supported on Windows.
+    fn on_before_uninstall_fast_callback(mut slf: PyRefMut<'_, Self>, hook: PyObject) -> PyRefMut<'_, Self> {
+        slf.before_uninstall = Some(hook);
+        slf
+    }
+
+    /// Fast callback hook, run on the old version before it is replaced by an update. Only supported on Windows.
+    fn on_before_update_fast_callback(mut slf: PyRefMut<'_, Self>, hook: PyObject) -> PyRefMut<'_, Self> {
+        slf.before_update = Some(hook);
+        slf
+    }
+
+    /// Fast callback hook, run on the new version after an update has been applied. Only supported on Windows.
+    fn on_after_update_fast_callback(mut slf: PyRefMut<'_, Self>, hook: PyObject) -> PyRefMut<'_, Self> {
+        slf.after_update = Some(hook);
+        slf
+    }
+
+    /// Runs the Velopack startup logic. This should be the first thing to run in your app.
+    fn run(&self, py: Python<'_>) {
+        let call_hook = |hook: &Option<PyObject>, version: Version| {
+            if let Some(hook) = hook {
+                if let Err(e) = hook.call1(py, (version.to_string(),)) {
+                    e.print(py);
+                }
+            }
+        };
+
+        // the process is the python interpreter, so the default env::args() would include the script path
+        let args = match &self.args {
+            Some(args) => args.clone(),
+            None => {
+                let argv: Vec<String> =
+                    py.import_bound("sys").and_then(|sys| sys.getattr("argv")).and_then(|argv| argv.extract()).unwrap_or_default();
+                argv.into_iter().skip(1).collect()
+            }
+        };
+
+        let mut app = VelopackApp::build()
+            .set_args(args)
+            .set_auto_apply_on_startup(!self.manual_apply)
+            .on_first_run(|v| call_hook(&self.first_run, v))
+            .on_restarted(|v| call_hook(&self.restarted, v));
+
+        #[cfg(target_os = "windows")]
+        {
+            app = app
+                .on_after_install_fast_callback(|v| call_hook(&self.after_install, v))
+                .on_before_uninstall_fast_callback(|v| call_hook(&self.before_uninstall, v))
+                .on_before_update_fast_callback(|v| call_hook(&self.before_update, v))
+                .on_after_update_fast_callback(|v| call_hook(&self.after_update, v));
+        }
+
+        app.run();
+    }
+}
+
+/// Python bindings for Velopack, an installer and automatic update framework for cross-platform desktop applications.
+#[pymodule]
+#[pyo3(name = "velopack")]
+fn velopack_python(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyVelopackAsset>()?;
+    m.add_class::<PyUpdateInfo>()?;
+    m.add_class::<PyUpdateManager>()?;
+    m.add_class::<PyApp>()?;
+    m.add("VelopackError", m.py().get_type_bound::<VelopackError>())?;
+    m.add("NetworkError", m.py().get_type_bound::<NetworkError>())?;
+    m.add("HashMismatchError", m.py().get_type_bound::<HashMismatchError>())?;
+    m.add("FeedParseError", m.py().get_type_bound::<FeedParseError>())?;
+    m.add("NotInstalledError", m.py().get_type_bound::<NotInstalledError>())?;
+    m.add("BusyError", m.py().get_type_bound::<BusyError>())?;
+    m.add("LockedError", m.py().get_type_bound::<LockedError>())?;
+    m.add("CancelledError", m.py().get_type_bound::<CancelledError>())?;
+    m.add("TranslocatedError", m.py().get_type_bound::<TranslocatedError>())?;
+    m.add("ManagedExternallyError", m.py().get_type_bound::<ManagedExternallyError>())?;
+    Ok(())
+}
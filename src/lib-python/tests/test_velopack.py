@@ -0,0 +1,93 @@
+import sys
+
+import pytest
+
+import velopack
+
+
+def test_errors_share_a_common_base():
+    for error in [
+        velopack.NetworkError,
+        velopack.HashMismatchError,
+        velopack.FeedParseError,
+        velopack.NotInstalledError,
+        velopack.BusyError,
+        velopack.LockedError,
+        velopack.CancelledError,
+        velopack.ManagedExternallyError,
+        velopack.TranslocatedError,
+    ]:
+        assert issubclass(error, velopack.VelopackError)
+    assert issubclass(velopack.VelopackError, Exception)
+
+
+def test_locked_error_is_a_busy_error():
+    assert issubclass(velopack.LockedError, velopack.BusyError)
+    with pytest.raises(velopack.BusyError):
+        raise velopack.LockedError("locked")
+
+
+def test_update_manager_raises_when_not_installed(tmp_path):
+    # the python interpreter running the tests is never part of a velopack install
+    with pytest.raises(velopack.NotInstalledError):
+        velopack.UpdateManager(str(tmp_path))
+
+
+def test_update_manager_accepts_every_option(tmp_path):
+    with pytest.raises(velopack.NotInstalledError):
+        velopack.UpdateManager(
+            str(tmp_path),
+            allow_version_downgrade=True,
+            explicit_channel="beta",
+            allow_prerelease=True,
+            update_check_interval_seconds=60,
+            packages_to_keep=2,
+            max_packages_size_bytes=1024,
+            max_package_age_days=7,
+            packages_dir=str(tmp_path / "packages"),
+            shared_cache_dir=str(tmp_path / "cache"),
+            shared_cache_max_size_bytes=1024,
+            cache_server_url="http://localhost:8080",
+            user_agent="tests",
+            feed_query_parameters="a=1",
+            use_cached_feed_when_offline=True,
+            request_headers="Authorization: Bearer token",
+        )
+
+
+def test_update_manager_rejects_unknown_options(tmp_path):
+    with pytest.raises(TypeError):
+        velopack.UpdateManager(str(tmp_path), not_an_option=True)
+
+
+def test_app_builder_methods_chain():
+    app = velopack.App()
+    hook = lambda version: None
+    assert app.set_args([]) is app
+    assert app.set_auto_apply_on_startup(False) is app
+    assert app.on_first_run(hook) is app
+    assert app.on_restarted(hook) is app
+    assert app.on_after_install_fast_callback(hook) is app
+    assert app.on_before_uninstall_fast_callback(hook) is app
+    assert app.on_before_update_fast_callback(hook) is app
+    assert app.on_after_update_fast_callback(hook) is app
+
+
+def test_app_run_does_nothing_when_not_installed():
+    calls = []
+    velopack.App().set_args([]).on_first_run(calls.append).on_restarted(calls.append).run()
+    assert calls == []
+
+
+def test_app_run_reads_sys_argv_by_default(monkeypatch):
+    monkeypatch.setattr(sys, "argv", [sys.executable])
+    velopack.App().run()
+
+
+@pytest.mark.skipif(sys.platform != "win32", reason="fast callback hooks are only supported on Windows")
+def test_app_run_calls_fast_callback_hook(monkeypatch):
+    # debug mode keeps the process alive after a fast callback hook, which would otherwise exit
+    monkeypatch.setenv("VELOPACK_DEBUG", "true")
+    calls = []
+    velopack.App().set_args(["--veloapp-install", "1.2.3"]).on_after_install_fast_callback(calls.append).run()
+    assert calls == ["1.2.3"]
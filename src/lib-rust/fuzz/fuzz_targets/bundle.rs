@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(mut bundle) = velopack::bundle::load_bundle_from_memory(data) {
+        let _ = bundle.read_manifest();
+    }
+});
@@ -1,14 +1,22 @@
 use semver::Version;
 use std::env;
 use std::process::exit;
+use std::sync::Mutex;
 
 use crate::{
     locator::{VelopackLocatorConfig}, 
     constants::*,
+    instance::{self, SingleInstanceGuard},
     manager,
     sources,
 };
 
+type UninstallReasonHook<'a> = Box<dyn FnOnce(Version, Option<String>) + 'a>;
+
+lazy_static::lazy_static! {
+    static ref SINGLE_INSTANCE: Mutex<Option<SingleInstanceGuard>> = Mutex::new(None);
+}
+
 /// VelopackApp helps you to handle app activation events correctly.
 /// This should be used as early as possible in your application startup code.
 /// (eg. the beginning of main() or wherever your entry point is)
@@ -17,8 +25,10 @@ pub struct VelopackApp<'a> {
     update_hook: Option<Box<dyn FnOnce(Version) + 'a>>,
     obsolete_hook: Option<Box<dyn FnOnce(Version) + 'a>>,
     uninstall_hook: Option<Box<dyn FnOnce(Version) + 'a>>,
+    uninstall_reason_hook: Option<UninstallReasonHook<'a>>,
     firstrun_hook: Option<Box<dyn FnOnce(Version) + 'a>>,
     restarted_hook: Option<Box<dyn FnOnce(Version) + 'a>>,
+    single_instance_hook: Option<Box<dyn Fn(Vec<String>) + Send + 'static>>,
     auto_apply: bool,
     args: Vec<String>,
     locator: Option<VelopackLocatorConfig>,
@@ -32,8 +42,10 @@ impl<'a> VelopackApp<'a> {
             update_hook: None,
             obsolete_hook: None,
             uninstall_hook: None,
+            uninstall_reason_hook: None,
             firstrun_hook: None,
             restarted_hook: None,
+            single_instance_hook: None,
             auto_apply: true, // Default to true
             args: env::args().skip(1).collect(),
             locator: None,
@@ -70,6 +82,14 @@ impl<'a> VelopackApp<'a> {
         self
     }
 
+    /// Enforce that only one instance of this application is running at a time. If another instance is
+    /// already running, the arguments of this process will be forwarded to it and this process will exit.
+    /// Otherwise, the hook will be called (on a background thread) with the arguments of any instance started later.
+    pub fn single_instance<F: Fn(Vec<String>) + Send + 'static>(mut self, on_activated: F) -> Self {
+        self.single_instance_hook = Some(Box::new(on_activated));
+        self
+    }
+
     /// WARNING: FastCallback hooks are run during critical stages of Velopack operations.
     /// Your code will be run and then the process will exit.
     /// If your code has not completed within 30 seconds, it will be terminated.
@@ -110,6 +130,17 @@ impl<'a> VelopackApp<'a> {
         self
     }
 
+    /// WARNING: FastCallback hooks are run during critical stages of Velopack operations.
+    /// Your code will be run and then the process will exit.
+    /// If your code has not completed within 30 seconds, it will be terminated.
+    /// The second argument is the reason for the uninstall, if one was provided to the updater (via `--reason`).
+    /// Only supported on windows; On other operating systems, this will never be called.
+    #[cfg(target_os = "windows")]
+    pub fn on_before_uninstall_with_reason_fast_callback<F: FnOnce(Version, Option<String>) + 'a>(mut self, hook: F) -> Self {
+        self.uninstall_reason_hook = Some(Box::new(hook));
+        self
+    }
+
     /// Runs the Velopack startup logic. This should be the first thing to run in your app.
     /// In some circumstances it may terminate/restart the process to perform tasks.
     pub fn run(&mut self) {
@@ -122,11 +153,22 @@ impl<'a> VelopackApp<'a> {
                 HOOK_CLI_INSTALL => Self::call_fast_hook(&mut self.install_hook, &args[1]),
                 HOOK_CLI_UPDATED => Self::call_fast_hook(&mut self.update_hook, &args[1]),
                 HOOK_CLI_OBSOLETE => Self::call_fast_hook(&mut self.obsolete_hook, &args[1]),
-                HOOK_CLI_UNINSTALL => Self::call_fast_hook(&mut self.uninstall_hook, &args[1]),
+                HOOK_CLI_UNINSTALL => {
+                    if let (Some(hook), Ok(version)) = (self.uninstall_reason_hook.take(), Version::parse(&args[1])) {
+                        hook(version, args.get(2).cloned());
+                    }
+                    Self::call_fast_hook(&mut self.uninstall_hook, &args[1])
+                }
                 _ => {} // do nothing
             }
         }
 
+        // always clear these, so they are not inherited by processes we start (eg. the updater and the restarted app)
+        let installer_firstrun = env::var(HOOK_ENV_FIRSTRUN).is_ok();
+        env::remove_var(HOOK_ENV_FIRSTRUN);
+        let restarted = env::var(HOOK_ENV_RESTART).is_ok();
+        env::remove_var(HOOK_ENV_RESTART);
+
         let manager = manager::UpdateManager::new(sources::NoneSource{}, None, self.locator.clone());
         if let Err(e) = manager {
             error!("VelopackApp: Error loading manager/locator: {:?}", e);
@@ -136,19 +178,26 @@ impl<'a> VelopackApp<'a> {
 
         let my_version = manager.get_current_version();
 
-        let firstrun = env::var(HOOK_ENV_FIRSTRUN).is_ok();
-        env::remove_var(HOOK_ENV_FIRSTRUN);
-        
-        let restarted = env::var(HOOK_ENV_RESTART).is_ok();
-        env::remove_var(HOOK_ENV_RESTART);
+        // on machine-wide installs, every other user of this machine should also get a first run. the marker outlives
+        // updates, so the hook never fires twice for a user, even if the installer variable is passed on again.
+        let installer_firstrun = installer_firstrun && !manager.get_first_run_completed_for_user();
+        let user_firstrun = !installer_firstrun && manager.get_is_first_run_for_user();
+        let firstrun = installer_firstrun || user_firstrun;
         
+        // a translocated app is a temporary read-only copy, so there is nothing we can apply updates to.
+        let translocated = manager.get_is_translocated();
+        if translocated {
+            warn!("VelopackApp: The app is running translocated, updates are disabled until it is moved to /Applications.");
+        }
+        let managed_externally = manager.get_is_managed_externally();
+
         // if auto apply is true, we should check for a local package downloaded with a version
         // greater than ours. If it exists, we should quit and apply it now.
-        if self.auto_apply {
+        if self.auto_apply && !translocated && !managed_externally {
             if let Some(asset) = manager.get_update_pending_restart() {
                 match Version::parse(&asset.Version) {
                     Ok(asset_version) => {
-                        if asset_version > my_version {
+                        if crate::version::is_newer(&asset_version, &my_version) {
                             if let Err(e) = manager.apply_updates_and_restart_with_args(&asset, &args) {
                                 error!("VelopackApp: Error applying pending updates on startup: {:?}", e);
                             }
@@ -161,7 +210,30 @@ impl<'a> VelopackApp<'a> {
             }
         }
 
+        if let Some(hook) = self.single_instance_hook.take() {
+            match instance::acquire_single_instance(&manager.get_app_id(), &args, hook) {
+                Ok(Some(guard)) => {
+                    *SINGLE_INSTANCE.lock().unwrap() = Some(guard);
+                }
+                Ok(None) => {
+                    info!("VelopackApp: Another instance is already running, exiting.");
+                    exit(0);
+                }
+                Err(e) => {
+                    error!("VelopackApp: Error enforcing single instance: {:?}", e);
+                }
+            }
+        }
+
         if firstrun {
+            if let Err(e) = manager.set_first_run_completed_for_user() {
+                error!("VelopackApp: Error recording first run for the current user: {:?}", e);
+            }
+            if user_firstrun {
+                if let Err(e) = manager.create_shortcuts_for_user() {
+                    error!("VelopackApp: Error creating shortcuts for the current user: {:?}", e);
+                }
+            }
             Self::call_hook(&mut self.firstrun_hook, &my_version);
         }
 
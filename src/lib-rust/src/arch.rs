@@ -0,0 +1,140 @@
+/// The CPU architecture a release was built for, as published in the release feed and package manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PackageArch {
+    /// Intel 64-bit (x86_64).
+    X64,
+    /// ARM 64-bit, such as Apple Silicon.
+    Arm64,
+    /// ARM 32-bit (armv7 hard-float), such as Raspberry Pi OS 32-bit.
+    Arm,
+    /// A macOS universal2 binary, which runs natively on both Intel and Apple Silicon machines.
+    Universal,
+}
+
+impl PackageArch {
+    /// Parses an architecture string (eg. "x64", "arm64", "arm" or "universal"), returning None if it is empty or not recognised.
+    pub fn parse(arch: &str) -> Option<PackageArch> {
+        match arch.trim().to_ascii_lowercase().as_str() {
+            "x64" | "x86_64" | "amd64" => Some(PackageArch::X64),
+            "arm64" | "aarch64" => Some(PackageArch::Arm64),
+            "arm" | "armv7" | "armv7l" | "armhf" => Some(PackageArch::Arm),
+            "universal" | "universal2" => Some(PackageArch::Universal),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the native architecture of this machine. On macOS this returns Arm64 on Apple Silicon, even if the current
+/// process is an Intel binary running under Rosetta.
+#[cfg(target_os = "macos")]
+pub fn get_machine_arch() -> Option<PackageArch> {
+    let mut value: libc::c_int = 0;
+    let mut size = std::mem::size_of::<libc::c_int>();
+    let name = b"hw.optional.arm64\0".as_ptr() as *const libc::c_char;
+    let value_ptr = &mut value as *mut libc::c_int as *mut libc::c_void;
+    let ret = unsafe { libc::sysctlbyname(name, value_ptr, &mut size, std::ptr::null_mut(), 0) };
+    // this sysctl does not exist at all on Intel machines
+    Some(if ret == 0 && value == 1 { PackageArch::Arm64 } else { PackageArch::X64 })
+}
+
+/// Returns the native architecture of this machine. On Windows this returns Arm64 on an ARM machine, even if the current
+/// process is an Intel binary running under emulation.
+#[cfg(target_os = "windows")]
+pub fn get_machine_arch() -> Option<PackageArch> {
+    use windows::core::{s, w};
+    use windows::Win32::Foundation::{BOOL, HANDLE};
+    use windows::Win32::System::LibraryLoader::{GetModuleHandleW, GetProcAddress};
+    use windows::Win32::System::SystemInformation::IMAGE_FILE_MACHINE;
+    use windows::Win32::System::Threading::GetCurrentProcess;
+
+    type IsWow64Process2Fn = unsafe extern "system" fn(HANDLE, *mut IMAGE_FILE_MACHINE, *mut IMAGE_FILE_MACHINE) -> BOOL;
+
+    // IsWow64Process2 does not exist before Windows 10 1511, so it is looked up at runtime rather than linked
+    let native = unsafe {
+        let kernel32 = GetModuleHandleW(w!("kernel32.dll")).ok();
+        kernel32.and_then(|k| GetProcAddress(k, s!("IsWow64Process2"))).and_then(|func| {
+            let func: IsWow64Process2Fn = std::mem::transmute(func);
+            let mut process_machine = IMAGE_FILE_MACHINE::default();
+            let mut native_machine = IMAGE_FILE_MACHINE::default();
+            func(GetCurrentProcess(), &mut process_machine, &mut native_machine).as_bool().then_some(native_machine.0)
+        })
+    };
+    match native {
+        Some(0x8664) => Some(PackageArch::X64),
+        Some(0xAA64) => Some(PackageArch::Arm64),
+        Some(0x01c4) => Some(PackageArch::Arm),
+        Some(_) => None,
+        None => get_build_arch(),
+    }
+}
+
+/// Returns the native architecture of this machine. On Linux this is the architecture the updater was built for, so a
+/// 32-bit userland on a 64-bit ARM kernel (eg. Raspberry Pi OS) is detected as Arm, which is what it can run.
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+pub fn get_machine_arch() -> Option<PackageArch> {
+    get_build_arch()
+}
+
+/// Returns the architecture this library was built for.
+#[cfg(not(target_os = "macos"))]
+fn get_build_arch() -> Option<PackageArch> {
+    if cfg!(target_arch = "x86_64") {
+        Some(PackageArch::X64)
+    } else if cfg!(target_arch = "aarch64") {
+        Some(PackageArch::Arm64)
+    } else if cfg!(target_arch = "arm") {
+        Some(PackageArch::Arm)
+    } else {
+        None
+    }
+}
+
+/// Ranks how well a release built for `package_arch` suits a machine, where a higher number is a better match. Returns None
+/// if the release can not run on the machine at all. Native releases are preferred over universal ones, which are preferred
+/// over Intel releases running under emulation (Rosetta on macOS, or Windows 11 on ARM). Linux has no such emulation, so
+/// only native releases are used there. Releases which do not specify an architecture are assumed to run anywhere,
+/// but are ranked below every release which does.
+pub fn get_arch_preference(package_arch: &str, machine_arch: Option<PackageArch>) -> Option<u8> {
+    let (package_arch, machine_arch) = match (PackageArch::parse(package_arch), machine_arch) {
+        (Some(p), Some(m)) => (p, m),
+        _ => return Some(0),
+    };
+    match (package_arch, machine_arch) {
+        (p, m) if p == m => Some(3),
+        (PackageArch::Universal, _) => Some(2),
+        (PackageArch::X64, PackageArch::Arm64) if !cfg!(target_os = "linux") => Some(1),
+        _ => None,
+    }
+}
+
+/// Returns true if a release built for `package_arch` can run on this machine.
+pub fn is_arch_supported(package_arch: &str) -> bool {
+    get_arch_preference(package_arch, get_machine_arch()).is_some()
+}
+
+#[test]
+fn test_arch_preference_prefers_native_releases() {
+    let arm = Some(PackageArch::Arm64);
+    let intel = Some(PackageArch::X64);
+    assert_eq!(PackageArch::parse("Universal2"), Some(PackageArch::Universal));
+    assert_eq!(PackageArch::parse("x86"), None);
+
+    assert!(get_arch_preference("arm64", arm) > get_arch_preference("universal", arm));
+    if cfg!(target_os = "linux") {
+        assert_eq!(get_arch_preference("x64", arm), None);
+    } else {
+        assert!(get_arch_preference("universal", arm) > get_arch_preference("x64", arm));
+        assert!(get_arch_preference("x64", arm) > get_arch_preference("", arm));
+    }
+
+    assert!(get_arch_preference("x64", intel) > get_arch_preference("universal", intel));
+    assert_eq!(get_arch_preference("arm64", intel), None);
+    assert_eq!(get_arch_preference("arm64", None), Some(0));
+
+    let arm32 = Some(PackageArch::Arm);
+    assert_eq!(PackageArch::parse("armv7l"), Some(PackageArch::Arm));
+    assert!(get_arch_preference("arm", arm32) > get_arch_preference("", arm32));
+    assert_eq!(get_arch_preference("arm64", arm32), None);
+    assert_eq!(get_arch_preference("x64", arm32), None);
+    assert_eq!(get_arch_preference("arm", arm), None);
+}
@@ -4,18 +4,20 @@ use std::{
     cell::RefCell,
     fs::{self, File},
     io::{Read, Seek, Write},
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
     rc::Rc,
 };
 use std::io::Cursor;
 
 use regex::Regex;
 use semver::Version;
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
 use xml::EventReader;
-use xml::reader::XmlEvent;
+use xml::reader::{ParserConfig, XmlEvent};
 use zip::ZipArchive;
 
-use crate::{Error, util};
+use crate::{constants::PACKAGE_FILE_LIST, escape::escape_xml, Error, util};
 
 #[cfg(target_os = "macos")]
 use std::os::unix::fs::PermissionsExt;
@@ -187,15 +189,22 @@ impl BundleZip<'_> {
         let nuspec_idx = self.find_zip_file(|name| name.ends_with(".nuspec"))
             .ok_or(Error::MissingNuspec)?;
 
-        let mut contents = String::new();
         let mut archive = self.zip.borrow_mut();
-        archive.by_index(nuspec_idx)?.read_to_string(&mut contents)?;
-        let app = read_manifest_from_string(&contents)?;
+        let app = read_manifest_from_reader(archive.by_index(nuspec_idx)?)?;
         
         self.manifest = Some(app.clone());
         Ok(app)
     }
 
+    /// Reads the list of app files and their checksums from the package. Packages created by vpk do not have one, in
+    /// which case this returns [`Error::FileNotFound`].
+    pub fn read_file_list(&self) -> Result<Vec<PackageFile>, Error> {
+        let idx = self.find_zip_file(|name| name == PACKAGE_FILE_LIST).ok_or_else(|| Error::FileNotFound(PACKAGE_FILE_LIST.to_owned()))?;
+        let mut archive = self.zip.borrow_mut();
+        let files = serde_json::from_reader(archive.by_index(idx)?)?;
+        Ok(files)
+    }
+
     pub fn len(&self) -> usize {
         let archive = self.zip.borrow();
         archive.len()
@@ -212,6 +221,14 @@ impl BundleZip<'_> {
         Ok(files)
     }
 
+    /// Returns true if the zip entry at the specified index is a symlink stored natively in the zip (eg. by ditto or
+    /// zip -y), whose contents are the path of the link target.
+    #[cfg(not(target_os = "linux"))]
+    fn is_zip_idx_symlink(&self, index: usize) -> bool {
+        let mut archive = self.zip.borrow_mut();
+        archive.by_index_raw(index).is_ok_and(|file| file.is_symlink())
+    }
+
     #[cfg(not(target_os = "linux"))]
     fn create_symlink(link_path: &PathBuf, target_path: &PathBuf) -> Result<(), Error> {
         #[cfg(target_os = "windows")]
@@ -281,6 +298,11 @@ impl BundleZip<'_> {
                 continue;
             }
 
+            if self.is_zip_idx_symlink(i) {
+                symlinks.push((i, file_path_on_disk.to_path_buf()));
+                continue;
+            }
+
             if stub_regex.is_match(&file_path_in_zip) {
                 // let stub_key = stub_regex.replace(&file_path_in_zip, ".exe").to_string();
                 // file_path_on_disk = root_path.join(&stub_key);
@@ -313,6 +335,7 @@ impl BundleZip<'_> {
         }
 
         // we extract the symlinks after, because the target must exist.
+        let mut pending: Vec<(PathBuf, PathBuf)> = Vec::new();
         for (i, link_path) in symlinks {
             let mut archive = self.zip.borrow_mut();
             let mut file = archive.by_index(i)?;
@@ -325,18 +348,404 @@ impl BundleZip<'_> {
             let contents = contents.replace("/", "\\");
             let contents = PathBuf::from(contents);
 
+            if !is_symlink_target_in_dir(&link_path, &contents, current_path) {
+                return Err(Error::Generic(format!(
+                    "Symlink '{}' points outside of the app directory ('{}').",
+                    link_path.to_string_lossy(),
+                    contents.to_string_lossy()
+                )));
+            }
+
             let parent = link_path.parent().unwrap();
             if !parent.exists() {
                 debug!("Creating parent directory: {:?}", parent);
                 util::retry_io(|| fs::create_dir_all(parent))?;
             }
-            util::retry_io(|| Self::create_symlink(&link_path, &contents))?;
+            pending.push((link_path, contents));
+        }
+
+        // symlinks may point through other symlinks (eg. Foo.framework/Foo -> Versions/Current/Foo), which on Windows
+        // must exist before the link can be created, so links which fail are tried again once the others are in place.
+        while !pending.is_empty() {
+            let before = pending.len();
+            pending.retain(|(link_path, target)| Self::create_symlink(link_path, target).is_err());
+            if pending.len() == before {
+                let (link_path, target) = pending.remove(0);
+                util::retry_io(|| Self::create_symlink(&link_path, &target))?;
+            }
         }
 
         Ok(())
     }
 }
 
+/// Creates a release package (.nupkg) from a directory containing the app, which can be installed and applied by the
+/// updater in the same way as a package created by vpk. The directory is stored in the package as lib/app, alongside the
+/// nuspec manifest and the metadata files NuGet expects, and any symlinks in it are preserved.
+pub struct BundleWriter {
+    app_dir: PathBuf,
+    manifest: Manifest,
+    metadata_files: Vec<(String, PathBuf)>,
+    compression: CompressionOptions,
+}
+
+/// A file of the app in a package, which is listed with its checksum in the package's file list (see
+/// [`BundleZip::read_file_list`]). Symlinks are not listed, because they have no contents of their own.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct PackageFile {
+    /// The path of the file relative to the app directory, separated by forward slashes.
+    pub Path: String,
+    /// The upper case SHA256 checksum of the file.
+    pub SHA256: String,
+    /// The size of the file in bytes.
+    pub Size: u64,
+}
+
+/// The extension of a file written to a package, and its entry in the file list (if it is not a symlink).
+type WrittenAppFile = (Option<String>, Option<PackageFile>);
+
+/// The compression method used for the files in a package. Only methods which every version of the updater can extract
+/// are supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompressionMethod {
+    /// Deflate, which is what vpk uses.
+    #[default]
+    Deflate,
+    /// No compression, which is the fastest to create and extract, but makes for the largest downloads.
+    Store,
+}
+
+impl std::str::FromStr for CompressionMethod {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "deflate" | "deflated" => Ok(CompressionMethod::Deflate),
+            "store" | "stored" | "none" => Ok(CompressionMethod::Store),
+            _ => Err(Error::Unsupported(format!("Unknown compression method '{}', expected 'deflate' or 'store'.", s))),
+        }
+    }
+}
+
+/// Controls how packages are compressed, which trades the time it takes to create a package against its size.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompressionOptions {
+    /// The compression method used for the files in the package.
+    pub method: CompressionMethod,
+    /// The compression level, from 0 (fastest) to 9 (smallest). Defaults to 6 if not set.
+    pub level: Option<i64>,
+    /// The number of files to compress at once. Defaults to the number of CPU cores (up to 8) if not set.
+    pub threads: Option<usize>,
+}
+
+impl CompressionOptions {
+    /// Returns an error if the level is not valid for the compression method.
+    pub fn validate(&self) -> Result<(), Error> {
+        match (self.method, self.level) {
+            (CompressionMethod::Deflate, Some(level)) if !(0..=9).contains(&level) => {
+                Err(Error::Unsupported(format!("Compression level {} is not valid, it must be between 0 and 9.", level)))
+            }
+            (CompressionMethod::Store, Some(_)) => Err(Error::Unsupported("A compression level can not be used with 'store'.".to_owned())),
+            (_, _) => Ok(()),
+        }
+    }
+
+    /// Returns the number of threads to use, which is at least one.
+    pub fn get_threads(&self) -> usize {
+        match self.threads {
+            Some(threads) => threads.max(1),
+            None => std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1).clamp(1, 8),
+        }
+    }
+
+    pub(crate) fn to_file_options(&self) -> zip::write::SimpleFileOptions {
+        let options = zip::write::SimpleFileOptions::default().last_modified_time(zip::DateTime::default());
+        match self.method {
+            CompressionMethod::Deflate => options.compression_method(zip::CompressionMethod::Deflated).compression_level(self.level),
+            CompressionMethod::Store => options.compression_method(zip::CompressionMethod::Stored),
+        }
+    }
+}
+
+impl BundleWriter {
+    /// Creates a writer which will package the contents of `app_dir` with the given manifest. The manifest is also
+    /// written to sq.version in the app directory by vpk, which callers must do themselves if their platform needs it.
+    pub fn new<P: AsRef<Path>>(app_dir: P, manifest: Manifest) -> Self {
+        BundleWriter { app_dir: app_dir.as_ref().to_path_buf(), manifest, metadata_files: Vec::new(), compression: Default::default() }
+    }
+
+    /// Sets how the package is compressed, by default it is compressed with deflate using every CPU core (up to 8).
+    pub fn set_compression(&mut self, compression: CompressionOptions) -> &mut Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Adds a file to the root of the package next to the manifest, such as setup.ico or splashimage.png, which are
+    /// read by the installer.
+    pub fn add_metadata_file<P: AsRef<Path>>(&mut self, name: &str, path: P) -> &mut Self {
+        self.metadata_files.push((name.to_owned(), path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Writes the package to `output_file`, and returns an asset describing it (including its checksums) which can be
+    /// added to a release feed. The package also contains a list of the app files and their checksums.
+    pub fn write_to_file<P: AsRef<Path>>(&self, output_file: P) -> Result<crate::VelopackAsset, Error> {
+        let output_file = output_file.as_ref();
+        let manifest = &self.manifest;
+        if manifest.id.is_empty() {
+            return Err(Error::MissingNuspecProperty("id".to_owned()));
+        }
+        if manifest.version == Version::new(0, 0, 0) {
+            return Err(Error::MissingNuspecProperty("version".to_owned()));
+        }
+        if !self.app_dir.is_dir() {
+            return Err(Error::FileNotFound(self.app_dir.to_string_lossy().to_string()));
+        }
+
+        self.compression.validate()?;
+
+        info!("Writing package for {} {} to '{}'...", manifest.id, manifest.version, output_file.to_string_lossy());
+        let mut files = Vec::new();
+        collect_files_recursive(&self.app_dir, &mut files)?;
+        files.sort();
+
+        let nuspec_name = format!("{}.nuspec", manifest.id);
+        let mut zip = zip::ZipWriter::new(File::create(output_file)?);
+        // entries have a fixed timestamp, so that packing the same files twice produces an identical package
+        let options = self.compression.to_file_options();
+
+        let mut extensions = vec!["nuspec".to_owned()];
+        zip.start_file(nuspec_name.as_str(), options)?;
+        zip.write_all(write_manifest_to_string(manifest).as_bytes())?;
+
+        for (name, path) in &self.metadata_files {
+            debug!("    Adding metadata file '{}'", name);
+            zip.start_file(name.as_str(), options)?;
+            std::io::copy(&mut util::retry_io(|| File::open(path))?, &mut zip)?;
+            extensions.extend(get_extension(name));
+        }
+
+        let threads = self.compression.get_threads().min(files.len()).max(1);
+        let mut file_list = Vec::new();
+        if threads == 1 {
+            for path in &files {
+                let (extension, file) = self.write_app_file(&mut zip, path, options)?;
+                extensions.extend(extension);
+                file_list.extend(file);
+            }
+        } else {
+            let (app_extensions, app_files) = self.write_app_files_parallel(&mut zip, &files, options, threads, output_file)?;
+            extensions.extend(app_extensions);
+            file_list.extend(app_files);
+        }
+
+        zip.start_file(PACKAGE_FILE_LIST, options)?;
+        zip.write_all(serde_json::to_string(&file_list)?.as_bytes())?;
+        extensions.extend(get_extension(PACKAGE_FILE_LIST));
+
+        extensions.sort();
+        extensions.dedup();
+        zip.start_file("[Content_Types].xml", options)?;
+        zip.write_all(create_content_types(&extensions).as_bytes())?;
+        zip.start_file("_rels/.rels", options)?;
+        zip.write_all(create_rels(&nuspec_name).as_bytes())?;
+        zip.finish()?;
+
+        Ok(crate::VelopackAsset {
+            PackageId: manifest.id.clone(),
+            Version: manifest.version.to_string(),
+            Type: "Full".to_owned(),
+            FileName: output_file.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+            // vpk writes upper case hashes, and older apps compare the SHA256 case-sensitively
+            SHA1: util::calculate_file_sha1(output_file)?.to_uppercase(),
+            SHA256: util::calculate_file_sha256(output_file)?.to_uppercase(),
+            Size: fs::metadata(output_file)?.len(),
+            NotesMarkdown: manifest.release_notes.clone(),
+            NotesHtml: manifest.release_notes_html.clone(),
+            IsMandatory: manifest.is_mandatory(),
+            Architecture: manifest.machine_architecture.clone(),
+        })
+    }
+
+    /// Compresses the files of the app on several threads, each into its own temporary archive next to the output file,
+    /// and then copies them into the package in order without compressing them again. This produces the same package as
+    /// compressing them one by one.
+    fn write_app_files_parallel<W: Write + Seek>(
+        &self,
+        zip: &mut zip::ZipWriter<W>,
+        files: &[PathBuf],
+        options: zip::write::SimpleFileOptions,
+        threads: usize,
+        output_file: &Path,
+    ) -> Result<(Vec<String>, Vec<PackageFile>), Error> {
+        let part_paths: Vec<PathBuf> =
+            (0..threads).map(|i| output_file.with_extension(format!("part{}_{}", i, util::random_string(4)))).collect();
+        let result = (|| {
+            let results: Vec<Result<Vec<WrittenAppFile>, Error>> = std::thread::scope(|scope| {
+                let handles: Vec<_> = part_paths
+                    .iter()
+                    .enumerate()
+                    .map(|(i, part_path)| {
+                        scope.spawn(move || {
+                            let mut part = zip::ZipWriter::new(File::create(part_path)?);
+                            let mut written = Vec::new();
+                            for path in files.iter().skip(i).step_by(threads) {
+                                written.push(self.write_app_file(&mut part, path, options)?);
+                            }
+                            part.finish()?;
+                            Ok(written)
+                        })
+                    })
+                    .collect();
+                handles.into_iter().map(|h| h.join().unwrap_or_else(|_| Err(Error::Generic("A compression thread panicked.".to_owned())))).collect()
+            });
+
+            let mut written = Vec::new();
+            for r in results {
+                written.push(r?.into_iter());
+            }
+            // list the files in the same order as they are in the package, which is the order they were distributed in
+            let mut extensions = Vec::new();
+            let mut file_list = Vec::new();
+            for i in 0..files.len() {
+                if let Some((extension, file)) = written[i % threads].next() {
+                    extensions.extend(extension);
+                    file_list.extend(file);
+                }
+            }
+
+            let mut parts = Vec::new();
+            for part_path in &part_paths {
+                parts.push(ZipArchive::new(File::open(part_path)?)?);
+            }
+            for (i, _) in files.iter().enumerate() {
+                // the files were distributed between the parts in order, so each part holds its entries in order too
+                let part = &mut parts[i % threads];
+                let file = part.by_index_raw(i / threads)?;
+                zip.raw_copy_file(file)?;
+            }
+            Ok((extensions, file_list))
+        })();
+
+        for part_path in &part_paths {
+            let _ = fs::remove_file(part_path);
+        }
+        result
+    }
+
+    /// Adds a single file (or symlink) of the app to the package, and returns its extension and its entry in the file list.
+    fn write_app_file<W: Write + Seek>(
+        &self,
+        zip: &mut zip::ZipWriter<W>,
+        path: &Path,
+        options: zip::write::SimpleFileOptions,
+    ) -> Result<WrittenAppFile, Error> {
+        let relative = path.strip_prefix(&self.app_dir).map_err(|e| Error::Generic(e.to_string()))?;
+        let relative = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+        let entry_name = format!("lib/app/{}", relative);
+        let meta = fs::symlink_metadata(path)?;
+
+        if meta.file_type().is_symlink() {
+            // symlinks are stored as a file containing the link target, which is what vpk does as well
+            let target = fs::read_link(path)?;
+            if !is_symlink_target_in_dir(path, &target, &self.app_dir) {
+                return Err(Error::Generic(format!(
+                    "Symlink '{}' points outside of the app directory ('{}').",
+                    path.to_string_lossy(),
+                    target.to_string_lossy()
+                )));
+            }
+            debug!("    Adding symlink '{}' -> '{}'", entry_name, target.to_string_lossy());
+            zip.start_file(format!("{}.__symlink", entry_name), options)?;
+            zip.write_all(target.to_string_lossy().replace('\\', "/").as_bytes())?;
+            return Ok((Some("__symlink".to_owned()), None));
+        }
+
+        debug!("    Adding '{}'", entry_name);
+        let file_options = options.large_file(meta.len() >= u32::MAX as u64);
+        // keep the executable bit, which the updater relies on for the binaries in the package on macOS and Linux
+        #[cfg(unix)]
+        let file_options = {
+            use std::os::unix::fs::PermissionsExt as _;
+            file_options.unix_permissions(meta.permissions().mode())
+        };
+        zip.start_file(entry_name, file_options)?;
+        let mut file = util::retry_io(|| File::open(path))?;
+        let mut sha256 = sha2::Sha256::new();
+        let mut buffer = [0; 64000];
+        let mut size = 0;
+        loop {
+            let len = file.read(&mut buffer)?;
+            if len == 0 {
+                break;
+            }
+            sha256.update(&buffer[..len]);
+            zip.write_all(&buffer[..len])?;
+            size += len as u64;
+        }
+        let listed = PackageFile { SHA256: format!("{:X}", sha256.finalize()), Size: size, Path: relative.clone() };
+        Ok((get_extension(&relative), Some(listed)))
+    }
+}
+
+pub(crate) fn collect_files_recursive(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), Error> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        // symlinks to directories are stored as links, rather than following them and packaging the contents twice
+        if entry.file_type()?.is_dir() {
+            collect_files_recursive(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn get_extension(name: &str) -> Option<String> {
+    let file_name = name.rsplit('/').next()?;
+    let (_, ext) = file_name.rsplit_once('.')?;
+    if ext.is_empty() {
+        None
+    } else {
+        Some(ext.to_lowercase())
+    }
+}
+
+fn create_content_types(extensions: &[String]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">\n");
+    xml.push_str("  <Default Extension=\"rels\" ContentType=\"application/vnd.openxmlformats-package.relationships+xml\" />\n");
+    for ext in extensions.iter().filter(|e| *e != "rels") {
+        xml.push_str(&format!("  <Default Extension=\"{}\" ContentType=\"application/octet\" />\n", escape_xml(ext)));
+    }
+    xml.push_str("</Types>\n");
+    xml
+}
+
+fn create_rels(nuspec_name: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\n  \
+         <Relationship Type=\"http://schemas.microsoft.com/packaging/2010/07/manifest\" Target=\"/{}\" Id=\"R1\" />\n\
+         </Relationships>\n",
+        escape_xml(nuspec_name)
+    )
+}
+
+/// Returns the file name vpk would give a package, eg. "MyApp-1.0.0-osx-full.nupkg". The channel is left out for
+/// packages in the default Windows channel, for compatibility with releases made before channels existed.
+pub fn get_suggested_package_file_name(id: &str, version: &Version, channel: &str, os: &str, is_delta: bool) -> String {
+    let kind = if is_delta { "delta" } else { "full" };
+    if os == "win" && channel == "win" {
+        format!("{}-{}-{}.nupkg", id, version, kind)
+    } else {
+        format!("{}-{}-{}-{}.nupkg", id, version, channel, kind)
+    }
+}
+
 #[derive(Debug, derivative::Derivative, Clone)]
 #[derivative(Default)]
 #[allow(missing_docs)]
@@ -352,68 +761,114 @@ pub struct Manifest {
     pub main_exe: String,
     pub os: String,
     pub os_min_version: String,
+    pub os_min_server_version: String,
     pub channel: String,
     pub shortcut_locations: String,
     pub shortcut_amuid: String,
     pub release_notes: String,
     pub release_notes_html: String,
+    pub uninstall_survey_url: String,
+    pub packages_dir: String,
+    pub side_by_side: String,
+    pub mandatory: String,
 }
 
+impl Manifest {
+    /// Returns true if each channel of this app is installed separately (eg. so stable and beta builds can be installed at
+    /// the same time), rather than every channel sharing one install.
+    pub fn is_side_by_side(&self) -> bool {
+        !self.channel.is_empty() && self.side_by_side.trim().eq_ignore_ascii_case("true")
+    }
+
+    /// Returns true if the publisher marked this release as mandatory, so it is flagged as such in the release feed.
+    pub fn is_mandatory(&self) -> bool {
+        self.mandatory.trim().eq_ignore_ascii_case("true")
+    }
+
+    /// Returns the id this app is installed as, which is used for the install directory, mutexes, shortcuts and
+    /// registry entries. This is the package id, suffixed with the channel (eg. "MyApp-beta") if channels are installed
+    /// side by side.
+    pub fn get_install_id(&self) -> String {
+        if self.is_side_by_side() {
+            format!("{}-{}", self.id, self.channel)
+        } else {
+            self.id.clone()
+        }
+    }
+
+    /// Returns the display name of this app, which is suffixed with the channel (eg. "My App (beta)") if channels are
+    /// installed side by side, so their shortcuts and uninstall entries can be told apart.
+    pub fn get_install_title(&self) -> String {
+        if self.is_side_by_side() && !self.title.is_empty() {
+            format!("{} ({})", self.title, self.channel)
+        } else {
+            self.title.clone()
+        }
+    }
+}
+
+/// Returns true if a relative symlink target, resolved from the directory containing the link, stays inside root_dir.
+/// Code signatures on macOS seal the target of each symlink, so targets are kept exactly as they were packaged, but links
+/// which are absolute or point outside of the app could be used to overwrite arbitrary files and are refused.
+fn is_symlink_target_in_dir(link_path: &Path, target: &Path, root_dir: &Path) -> bool {
+    let mut resolved: Vec<Component> = match link_path.parent() {
+        Some(parent) => parent.components().collect(),
+        None => return false,
+    };
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => resolved.push(component),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if resolved.pop().is_none() {
+                    return false;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+    resolved.iter().collect::<PathBuf>().starts_with(root_dir)
+}
+
+/// The largest manifest (.nuspec) which will be read. Manifests are a few kilobytes, even with long release notes, so
+/// anything larger is treated as corrupt rather than read into memory (eg. a zip bomb in a malicious package).
+pub const MAX_MANIFEST_SIZE: u64 = 4 * 1024 * 1024;
+/// Manifests are only a few elements deep, so anything deeper is treated as corrupt.
+const MAX_MANIFEST_DEPTH: usize = 32;
+
 /// Parse manifest object from an XML string.
 pub fn read_manifest_from_string(xml: &str) -> Result<Manifest, Error> {
+    read_manifest_from_reader(xml.as_bytes())
+}
+
+/// Parse manifest object from XML, reading it as a stream. Input which is larger than [`MAX_MANIFEST_SIZE`], nested
+/// too deeply, or is not well-formed XML, returns [`Error::InvalidNuspec`] instead of being partially read.
+pub fn read_manifest_from_reader<R: Read>(reader: R) -> Result<Manifest, Error> {
+    let config = ParserConfig::new().cdata_to_characters(true).ignore_comments(true);
+    let parser = EventReader::new_with_config(LimitedReader { inner: reader, remaining: MAX_MANIFEST_SIZE }, config);
     let mut obj: Manifest = Default::default();
-    let cursor = Cursor::new(xml);
-    let parser = EventReader::new(cursor);
-    let mut vec: Vec<String> = Vec::new();
+    // the name and text of each open element, as text may be split into several events (eg. around an entity)
+    let mut elements: Vec<(String, String)> = Vec::new();
     for e in parser {
-        match e {
-            Ok(XmlEvent::StartElement { name, .. }) => {
-                vec.push(name.local_name);
-            }
-            Ok(XmlEvent::Characters(text)) => {
-                if vec.is_empty() {
-                    continue;
-                }
-                let el_name = vec.last().unwrap();
-                if el_name == "id" {
-                    obj.id = text;
-                } else if el_name == "version" {
-                    obj.version = Version::parse(&text)?;
-                } else if el_name == "title" {
-                    obj.title = text;
-                } else if el_name == "authors" {
-                    obj.authors = text;
-                } else if el_name == "description" {
-                    obj.description = text;
-                } else if el_name == "machineArchitecture" {
-                    obj.machine_architecture = text;
-                } else if el_name == "runtimeDependencies" {
-                    obj.runtime_dependencies = text;
-                } else if el_name == "mainExe" {
-                    obj.main_exe = text;
-                } else if el_name == "os" {
-                    obj.os = text;
-                } else if el_name == "osMinVersion" {
-                    obj.os_min_version = text;
-                } else if el_name == "channel" {
-                    obj.channel = text;
-                } else if el_name == "shortcutLocations" {
-                    obj.shortcut_locations = text;
-                } else if el_name == "shortcutAmuid" {
-                    obj.shortcut_amuid = text;
-                } else if el_name == "releaseNotes" {
-                    obj.release_notes = text;
-                } else if el_name == "releaseNotesHtml" {
-                    obj.release_notes_html = text;
+        match e.map_err(|e| Error::InvalidNuspec(e.to_string()))? {
+            XmlEvent::StartElement { name, .. } => {
+                if elements.len() >= MAX_MANIFEST_DEPTH {
+                    return Err(Error::InvalidNuspec(format!("Elements are nested more than {} deep.", MAX_MANIFEST_DEPTH)));
                 }
+                elements.push((name.local_name, String::new()));
             }
-            Ok(XmlEvent::EndElement { .. }) => {
-                vec.pop();
+            XmlEvent::Characters(text) => {
+                if let Some((_, el_text)) = elements.last_mut() {
+                    el_text.push_str(&text);
+                }
             }
-            Err(e) => {
-                error!("Error: {e}");
-                break;
+            XmlEvent::EndElement { .. } => {
+                match elements.pop() {
+                    Some((el_name, text)) if !text.is_empty() => set_manifest_property(&mut obj, &el_name, text)?,
+                    _ => {}
+                }
             }
+            XmlEvent::EndDocument => break,
             // There's more: https://docs.rs/xml-rs/latest/xml/reader/enum.XmlEvent.html
             _ => {}
         }
@@ -439,6 +894,94 @@ pub fn read_manifest_from_string(xml: &str) -> Result<Manifest, Error> {
     Ok(obj)
 }
 
+fn set_manifest_property(obj: &mut Manifest, el_name: &str, text: String) -> Result<(), Error> {
+    match el_name {
+        "id" => obj.id = text,
+        "version" => obj.version = Version::parse(text.trim())?,
+        "title" => obj.title = text,
+        "authors" => obj.authors = text,
+        "description" => obj.description = text,
+        "machineArchitecture" => obj.machine_architecture = text,
+        "runtimeDependencies" => obj.runtime_dependencies = text,
+        "mainExe" => obj.main_exe = text,
+        "os" => obj.os = text,
+        "osMinVersion" => obj.os_min_version = text,
+        "osMinServerVersion" => obj.os_min_server_version = text,
+        "channel" => obj.channel = text,
+        "shortcutLocations" => obj.shortcut_locations = text,
+        "shortcutAmuid" => obj.shortcut_amuid = text,
+        "releaseNotes" => obj.release_notes = text,
+        "releaseNotesHtml" => obj.release_notes_html = text,
+        "uninstallSurveyUrl" => obj.uninstall_survey_url = text.trim().to_owned(),
+        "packagesDir" => obj.packages_dir = text.trim().to_owned(),
+        "sideBySide" => obj.side_by_side = text.trim().to_owned(),
+        "mandatory" => obj.mandatory = text.trim().to_owned(),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Reads up to a limit, and then fails rather than stopping silently, so that input which is too large is reported as
+/// such instead of as truncated XML.
+struct LimitedReader<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R: Read> Read for LimitedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.remaining == 0 {
+            return match self.inner.read(&mut [0u8; 1])? {
+                0 => Ok(0),
+                _ => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("larger than {} bytes", MAX_MANIFEST_SIZE))),
+            };
+        }
+        let len = buf.len().min(self.remaining as usize);
+        let read = self.inner.read(&mut buf[..len])?;
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+/// Write a manifest object to a nuspec XML string, which can be read back with [`read_manifest_from_string`]. Properties
+/// which are empty are left out.
+pub fn write_manifest_to_string(manifest: &Manifest) -> String {
+    let title = if manifest.title.is_empty() { &manifest.id } else { &manifest.title };
+    let description = if manifest.description.is_empty() { title } else { &manifest.description };
+    let authors = if manifest.authors.is_empty() { &manifest.id } else { &manifest.authors };
+    let version = manifest.version.to_string();
+    let properties = [
+        ("id", manifest.id.as_str()),
+        ("title", title.as_str()),
+        ("description", description.as_str()),
+        ("authors", authors.as_str()),
+        ("version", version.as_str()),
+        ("channel", manifest.channel.as_str()),
+        ("mainExe", manifest.main_exe.as_str()),
+        ("os", manifest.os.as_str()),
+        ("osMinVersion", manifest.os_min_version.as_str()),
+        ("osMinServerVersion", manifest.os_min_server_version.as_str()),
+        ("machineArchitecture", manifest.machine_architecture.as_str()),
+        ("runtimeDependencies", manifest.runtime_dependencies.as_str()),
+        ("shortcutLocations", manifest.shortcut_locations.as_str()),
+        ("shortcutAmuid", manifest.shortcut_amuid.as_str()),
+        ("releaseNotes", manifest.release_notes.as_str()),
+        ("releaseNotesHtml", manifest.release_notes_html.as_str()),
+        ("uninstallSurveyUrl", manifest.uninstall_survey_url.as_str()),
+        ("packagesDir", manifest.packages_dir.as_str()),
+        ("sideBySide", manifest.side_by_side.as_str()),
+        ("mandatory", manifest.mandatory.as_str()),
+    ];
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<package xmlns=\"http://schemas.microsoft.com/packaging/2010/07/nuspec.xsd\">\n<metadata>\n");
+    for (name, value) in properties.iter().filter(|(_, v)| !v.is_empty()) {
+        xml.push_str(&format!("<{}>{}</{}>\n", name, escape_xml(value), name));
+    }
+    xml.push_str("</metadata>\n</package>\n");
+    xml
+}
+
 #[derive(Debug, Clone, derivative::Derivative)]
 #[derivative(Default)]
 pub struct EntryNameInfo {
@@ -507,6 +1050,17 @@ fn parse_package_file_name<T: AsRef<str>>(name: T) -> Option<EntryNameInfo> {
     return Some(entry);
 }
 
+#[test]
+fn test_symlink_targets_must_stay_in_app_dir() {
+    let root = Path::new("/tmp/app/Contents");
+    let framework = root.join("Frameworks/Foo.framework");
+    assert!(is_symlink_target_in_dir(&framework.join("Foo"), Path::new("Versions/Current/Foo"), root));
+    assert!(is_symlink_target_in_dir(&framework.join("Versions/Current"), Path::new("A"), root));
+    assert!(is_symlink_target_in_dir(&framework.join("Foo"), Path::new("../../MacOS/Foo"), root));
+    assert!(!is_symlink_target_in_dir(&framework.join("Foo"), Path::new("../../../../etc/passwd"), root));
+    assert!(!is_symlink_target_in_dir(&framework.join("Foo"), Path::new("/etc/passwd"), root));
+}
+
 #[test]
 fn test_parse_package_file_name() {
     // test no rid
@@ -530,4 +1084,193 @@ fn test_parse_package_file_name() {
     assert!(parse_package_file_name("MyCoolApp-1.2.3-beta1-win7-x64-full.zip").is_none());
     assert!(parse_package_file_name("MyCoolApp-1.2.3.nupkg").is_none());
     assert!(parse_package_file_name("MyCoolApp-1.2-full.nupkg").is_none());
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_read_manifest_uninstall_survey_url() {
+    let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<package xmlns="http://schemas.microsoft.com/packaging/2010/07/nuspec.xsd">
+  <metadata>
+    <id>MyApp</id>
+    <version>1.0.0</version>
+    <mainExe>MyApp.exe</mainExe>
+    <uninstallSurveyUrl>https://example.com/survey?app=MyApp&amp;v=1</uninstallSurveyUrl>
+  </metadata>
+</package>"#;
+    let manifest = read_manifest_from_string(xml).unwrap();
+    assert_eq!(manifest.uninstall_survey_url, "https://example.com/survey?app=MyApp&v=1");
+}
+
+#[test]
+fn test_read_manifest_packages_dir() {
+    let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<package xmlns="http://schemas.microsoft.com/packaging/2010/07/nuspec.xsd">
+  <metadata>
+    <id>MyApp</id>
+    <version>1.0.0</version>
+    <mainExe>MyApp.exe</mainExe>
+    <packagesDir> D:\VelopackCache </packagesDir>
+  </metadata>
+</package>"#;
+    let manifest = read_manifest_from_string(xml).unwrap();
+    assert_eq!(manifest.packages_dir, "D:\\VelopackCache");
+}
+
+#[test]
+fn test_side_by_side_manifest_uses_channel_in_install_id() {
+    let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<package xmlns="http://schemas.microsoft.com/packaging/2010/07/nuspec.xsd">
+  <metadata>
+    <id>MyApp</id>
+    <version>1.0.0</version>
+    <title>My App</title>
+    <channel>beta</channel>
+    <sideBySide>true</sideBySide>
+  </metadata>
+</package>"#;
+    let manifest = read_manifest_from_string(xml).unwrap();
+    assert!(manifest.is_side_by_side());
+    assert_eq!(manifest.get_install_id(), "MyApp-beta");
+    assert_eq!(manifest.get_install_title(), "My App (beta)");
+    assert!(write_manifest_to_string(&manifest).contains("<sideBySide>true</sideBySide>"));
+
+    let shared = Manifest { side_by_side: String::new(), ..manifest.clone() };
+    assert_eq!(shared.get_install_id(), "MyApp");
+    assert_eq!(shared.get_install_title(), "My App");
+    let no_channel = Manifest { channel: String::new(), ..manifest };
+    assert_eq!(no_channel.get_install_id(), "MyApp");
+}
+
+#[test]
+fn test_write_manifest_round_trips() {
+    let manifest = Manifest {
+        id: "MyApp".to_owned(),
+        version: Version::parse("1.2.3-beta.1").unwrap(),
+        title: "My <App> & Co".to_owned(),
+        main_exe: "MyApp.exe".to_owned(),
+        channel: "win".to_owned(),
+        release_notes: "# Notes\n\n* fixed \"quotes\"".to_owned(),
+        ..Default::default()
+    };
+    let xml = write_manifest_to_string(&manifest);
+    assert!(!xml.contains("<shortcutAmuid>"));
+    let read = read_manifest_from_string(&xml).unwrap();
+    assert_eq!(read.id, "MyApp");
+    assert_eq!(read.version, manifest.version);
+    assert_eq!(read.title, "My <App> & Co");
+    assert_eq!(read.authors, "MyApp");
+    assert_eq!(read.release_notes, manifest.release_notes);
+    assert_eq!(get_suggested_package_file_name("MyApp", &read.version, "win", "win", false), "MyApp-1.2.3-beta.1-full.nupkg");
+    assert_eq!(get_suggested_package_file_name("MyApp", &read.version, "beta", "linux", true), "MyApp-1.2.3-beta.1-beta-delta.nupkg");
+}
+
+#[test]
+fn test_read_manifest_rejects_malformed_input() {
+    use rand::{Rng, SeedableRng};
+
+    let valid = "<?xml version=\"1.0\"?><package><metadata><id>MyApp</id><version>1.0.0</version><mainExe>MyApp.exe</mainExe>\
+                 <releaseNotes>a &amp; <![CDATA[<b>]]> c</releaseNotes></metadata></package>";
+    assert_eq!(read_manifest_from_string(valid).unwrap().release_notes, "a & <b> c");
+
+    let deep = "<a>".repeat(100_000);
+    let oversized = valid.replace("a &amp;", &"x".repeat(MAX_MANIFEST_SIZE as usize));
+    let laughs = "<?xml version=\"1.0\"?><!DOCTYPE p [<!ENTITY a \"aaaaaaaaaa\"><!ENTITY b \"&a;&a;&a;&a;&a;&a;&a;&a;&a;&a;\">\
+                  <!ENTITY c \"&b;&b;&b;&b;&b;&b;&b;&b;&b;&b;\"><!ENTITY d \"&c;&c;&c;&c;&c;&c;&c;&c;&c;&c;\">]>\
+                  <package><metadata><id>&d;&d;&d;&d;&d;&d;&d;&d;&d;&d;</id><version>1.0.0</version><mainExe>MyApp.exe</mainExe>\
+                  </metadata></package>";
+    for xml in [&valid[..valid.len() / 2], "", "<package><metadata><id>MyApp</id>", deep.as_str(), oversized.as_str(), laughs] {
+        assert!(matches!(read_manifest_from_string(xml), Err(Error::InvalidNuspec(_))));
+    }
+    assert!(matches!(read_manifest_from_reader(&[0xff, 0xfe, 0x00, 0x3c][..]), Err(Error::InvalidNuspec(_))));
+
+    // random corruption of a valid manifest may or may not parse, but must never panic or hang
+    let mut rng = rand::rngs::StdRng::seed_from_u64(709);
+    for _ in 0..2000 {
+        let mut bytes = valid.as_bytes().to_vec();
+        for _ in 0..rng.gen_range(1..8) {
+            let idx = rng.gen_range(0..bytes.len());
+            match rng.gen_range(0..3) {
+                0 => bytes[idx] = rng.gen(),
+                1 => bytes.truncate(idx),
+                _ => bytes.insert(idx, b"<>&;/\"!["[rng.gen_range(0..7)]),
+            }
+            if bytes.is_empty() {
+                break;
+            }
+        }
+        let _ = read_manifest_from_reader(&bytes[..]);
+    }
+}
+
+#[test]
+fn test_bundle_writer_creates_readable_package() {
+    let tmp = crate::testing::TempDir::new("velopack_bundle_writer").unwrap();
+    let app_dir = tmp.join("app");
+    fs::create_dir_all(app_dir.join("sub")).unwrap();
+    fs::write(app_dir.join("MyApp.exe"), b"exe").unwrap();
+    fs::write(app_dir.join("sub").join("data.json"), b"{}").unwrap();
+    let icon = tmp.join("icon.ico");
+    fs::write(&icon, b"ico").unwrap();
+
+    let manifest =
+        Manifest { id: "MyApp".to_owned(), version: Version::new(1, 0, 0), main_exe: "MyApp.exe".to_owned(), ..Default::default() };
+    let output = tmp.join("MyApp-1.0.0-full.nupkg");
+    let asset = BundleWriter::new(&app_dir, manifest).add_metadata_file("setup.ico", &icon).write_to_file(&output).unwrap();
+    assert_eq!(asset.FileName, "MyApp-1.0.0-full.nupkg");
+    assert_eq!(asset.Size, fs::metadata(&output).unwrap().len());
+    assert_eq!(asset.SHA1, util::calculate_file_sha1(&output).unwrap().to_uppercase());
+    assert_eq!(asset.SHA256, util::calculate_file_sha256(&output).unwrap().to_uppercase());
+
+    let mut bundle = load_bundle_from_file(&output).unwrap();
+    assert_eq!(bundle.read_manifest().unwrap().version, Version::new(1, 0, 0));
+    let names = bundle.get_file_names().unwrap();
+    for name in ["MyApp.nuspec", "setup.ico", "lib/app/MyApp.exe", "lib/app/sub/data.json", "[Content_Types].xml", "_rels/.rels"] {
+        assert!(names.contains(&name.to_owned()), "{} is missing from {:?}", name, names);
+    }
+    let files = bundle.read_file_list().unwrap();
+    assert_eq!(files.iter().map(|f| f.Path.as_str()).collect::<Vec<_>>(), ["MyApp.exe", "sub/data.json"]);
+    assert_eq!(files[0].SHA256, util::calculate_sha256(b"exe").to_uppercase());
+    assert_eq!(files[1].Size, 2);
+    assert!(BundleWriter::new(&app_dir, Manifest::default()).write_to_file(tmp.join("x.nupkg")).is_err());
+}
+
+#[test]
+fn test_bundle_writer_compression_options() {
+    let tmp = crate::testing::TempDir::new("velopack_bundle_compression").unwrap();
+    let app_dir = tmp.join("app");
+    fs::create_dir_all(app_dir.join("sub")).unwrap();
+    for i in 0..20 {
+        fs::write(app_dir.join(format!("file{}.txt", i)), format!("contents of file {}", i).repeat(100)).unwrap();
+    }
+    fs::write(app_dir.join("sub").join("MyApp"), b"binary").unwrap();
+    #[cfg(unix)]
+    fs::set_permissions(app_dir.join("sub").join("MyApp"), std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+
+    let manifest = Manifest { id: "MyApp".to_owned(), version: Version::new(1, 0, 0), ..Default::default() };
+    let write = |name: &str, compression: CompressionOptions| {
+        let output = tmp.join(name);
+        BundleWriter::new(&app_dir, manifest.clone()).set_compression(compression).write_to_file(&output).map(|_| fs::read(output).unwrap())
+    };
+
+    // compressing on several threads must produce exactly the same package as compressing on one
+    let single = write("single.nupkg", CompressionOptions { threads: Some(1), ..Default::default() }).unwrap();
+    let parallel = write("parallel.nupkg", CompressionOptions { threads: Some(4), ..Default::default() }).unwrap();
+    assert!(single == parallel);
+    let stored = write("stored.nupkg", CompressionOptions { method: CompressionMethod::Store, ..Default::default() }).unwrap();
+    assert!(stored.len() > parallel.len());
+    let fastest = write("fastest.nupkg", CompressionOptions { level: Some(1), ..Default::default() }).unwrap();
+    let smallest = write("smallest.nupkg", CompressionOptions { level: Some(9), ..Default::default() }).unwrap();
+    assert!(smallest.len() <= fastest.len());
+    assert!(write("bad.nupkg", CompressionOptions { level: Some(10), ..Default::default() }).is_err());
+    assert!(write("bad.nupkg", CompressionOptions { method: CompressionMethod::Store, level: Some(1), ..Default::default() }).is_err());
+
+    let listed = load_bundle_from_memory(&parallel).unwrap().read_file_list().unwrap();
+    assert_eq!(listed, load_bundle_from_memory(&single).unwrap().read_file_list().unwrap());
+    assert_eq!(listed.len(), 21);
+    let mut archive = ZipArchive::new(Cursor::new(parallel)).unwrap();
+    assert_eq!(archive.len(), 25);
+    #[cfg(unix)]
+    assert_eq!(archive.by_name("lib/app/sub/MyApp").unwrap().unix_mode().unwrap() & 0o777, 0o755);
+    assert!(fs::read_dir(&tmp).unwrap().flatten().all(|e| !e.file_name().to_string_lossy().contains(".part")));
+    assert_eq!("Store".parse::<CompressionMethod>().unwrap(), CompressionMethod::Store);
+}
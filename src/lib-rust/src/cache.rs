@@ -0,0 +1,258 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::{download, util, Error, VelopackAsset};
+
+/// The default size limit of a shared cache, if none is specified.
+pub const DEFAULT_MAX_SIZE_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Temporary files older than this were left behind by a process which exited while writing to the cache.
+const STALE_TEMP_FILE_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A machine-level cache of downloaded packages, which is shared by every app (and channel) configured to use the same
+/// directory, so identical packages are only downloaded once. Files are stored by their SHA256 checksum, so an asset is
+/// only found in the cache if its contents are exactly the same.
+///
+/// The cache is safe to use from several processes at once: files are written to a temporary file and then renamed into
+/// place, so an entry is never seen half written, and every file taken from the cache is verified against the checksum
+/// of the asset before it is used. The least recently used entries are evicted when the cache grows past its size limit.
+///
+/// The directories of the cache are created so that every user on the machine can add and evict entries, regardless of
+/// which user created them. This is safe because an entry is only ever used if it matches the checksum of the asset, so
+/// one user can not plant a package in the cache which is applied by another.
+#[derive(Debug, Clone)]
+pub struct SharedCache {
+    dir: PathBuf,
+    max_size_bytes: u64,
+}
+
+impl SharedCache {
+    /// Creates a cache which stores files in the specified directory, up to a total of max_size_bytes (zero uses the
+    /// default size limit). The directory is created when the first file is added.
+    pub fn new<P: AsRef<Path>>(dir: P, max_size_bytes: u64) -> Self {
+        let max_size_bytes = if max_size_bytes == 0 { DEFAULT_MAX_SIZE_BYTES } else { max_size_bytes };
+        SharedCache { dir: dir.as_ref().to_path_buf(), max_size_bytes }
+    }
+
+    /// The directory the cache is stored in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the path a file with the specified SHA256 checksum is stored at, or None if the checksum is not valid.
+    fn get_entry_path(&self, sha256: &str) -> Option<PathBuf> {
+        get_entry_name(sha256).map(|(dir, name)| self.dir.join(dir).join(name))
+    }
+
+    /// Copies the asset from the cache to the target file if it is cached, and returns true if it was. Entries which do not
+    /// match the checksum of the asset (eg. because the disk is corrupt) are removed, and false is returned.
+    pub fn try_get<P: AsRef<Path>>(&self, asset: &VelopackAsset, target_file: P) -> Result<bool, Error> {
+        let target_file = target_file.as_ref();
+        let entry = match self.get_entry_path(&asset.SHA256) {
+            Some(entry) if entry.is_file() => entry,
+            _ => return Ok(false),
+        };
+
+        if let Err(e) = fs::copy(&entry, target_file) {
+            // another process may have evicted the entry since it was found
+            debug!("Unable to copy '{}' from the shared cache: {}", entry.display(), e);
+            let _ = fs::remove_file(target_file);
+            return Ok(false);
+        }
+
+        if let Err(e) = download::verify_asset_file(asset, target_file) {
+            warn!("Removing invalid entry from the shared cache: {}", e);
+            let _ = fs::remove_file(target_file);
+            let _ = fs::remove_file(&entry);
+            return Ok(false);
+        }
+
+        // the modified time is used to decide which entries were used least recently
+        let _ = fs::File::options().write(true).open(&entry).and_then(|f| f.set_modified(SystemTime::now()));
+        Ok(true)
+    }
+
+    /// Adds a file to the cache, which must already have been verified to match the asset. Assets without a SHA256
+    /// checksum can not be cached, and are skipped. Old entries are evicted afterwards if the cache is too large.
+    pub fn insert<P: AsRef<Path>>(&self, asset: &VelopackAsset, source_file: P) -> Result<(), Error> {
+        let entry = match self.get_entry_path(&asset.SHA256) {
+            Some(entry) => entry,
+            None => {
+                debug!("Not adding '{}' to the shared cache, because it does not have a SHA256 checksum.", asset.FileName);
+                return Ok(());
+            }
+        };
+
+        if entry.is_file() {
+            let _ = fs::File::options().write(true).open(&entry).and_then(|f| f.set_modified(SystemTime::now()));
+        } else {
+            let temp_dir = self.dir.join("tmp");
+            create_shared_dir(&self.dir)?;
+            create_shared_dir(&temp_dir)?;
+            create_shared_dir(entry.parent().unwrap())?;
+            let temp_file = temp_dir.join(util::random_string(16));
+            let result = fs::copy(source_file.as_ref(), &temp_file).and_then(|_| fs::rename(&temp_file, &entry));
+            if let Err(e) = result {
+                let _ = fs::remove_file(&temp_file);
+                // if another process added the same file first, that is just as good
+                if !entry.is_file() {
+                    return Err(e.into());
+                }
+            }
+            info!("Added '{}' to the shared cache.", asset.FileName);
+        }
+
+        self.evict()?;
+        Ok(())
+    }
+
+    /// Deletes the least recently used entries until the cache is within its size limit, and returns the number of bytes
+    /// which were freed. Entries which can not be deleted (eg. because another process is reading them) are skipped.
+    pub fn evict(&self) -> Result<u64, Error> {
+        let mut entries = Vec::new();
+        let mut total_size = 0;
+        let top_level = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+
+        for dir in top_level.flatten() {
+            let is_temp_dir = dir.file_name() == "tmp";
+            for file in fs::read_dir(dir.path()).into_iter().flatten().flatten() {
+                let metadata = match file.metadata() {
+                    Ok(metadata) if metadata.is_file() => metadata,
+                    _ => continue,
+                };
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                if is_temp_dir {
+                    if modified.elapsed().unwrap_or_default() > STALE_TEMP_FILE_AGE {
+                        let _ = fs::remove_file(file.path());
+                    }
+                    continue;
+                }
+                total_size += metadata.len();
+                entries.push((file.path(), metadata.len(), modified));
+            }
+        }
+
+        let mut freed = 0;
+        entries.sort_by_key(|(_, _, modified)| *modified);
+        for (path, size, _) in entries {
+            if total_size - freed <= self.max_size_bytes {
+                break;
+            }
+            match fs::remove_file(&path) {
+                Ok(()) => {
+                    info!("Evicted '{}' from the shared cache.", path.display());
+                    freed += size;
+                }
+                Err(e) => debug!("Unable to evict '{}' from the shared cache: {}", path.display(), e),
+            }
+        }
+        Ok(freed)
+    }
+}
+
+/// Creates a directory of the cache if it does not exist, and allows every user to create and delete files in it. The
+/// permissions of a directory which already exists are left as they are, because it may belong to another user.
+fn create_shared_dir(dir: &Path) -> Result<(), Error> {
+    if dir.is_dir() {
+        return Ok(());
+    }
+    fs::create_dir_all(dir)?;
+
+    // the umask usually removes write access for other users, so the mode is set explicitly. there is no sticky bit,
+    // because entries added by one user must be evicted by another once they are the least recently used
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Err(e) = fs::set_permissions(dir, fs::Permissions::from_mode(0o777)) {
+            warn!("Unable to make '{}' writable by every user: {}", dir.display(), e);
+        }
+    }
+
+    // directories created under ProgramData only let other users add files, not modify or delete them. granting the
+    // Users group (by SID, as the name is localized) modify access is inherited by every file and directory in the cache
+    #[cfg(target_os = "windows")]
+    {
+        let mut command = std::process::Command::new("icacls");
+        command.arg(dir).args(["/grant", "*S-1-5-32-545:(OI)(CI)M", "/q"]);
+        match util::run_process_no_console_and_wait(command, Duration::from_secs(30)) {
+            Ok(output) if output.status.success() => {}
+            Ok(output) => {
+                warn!("Unable to make '{}' writable by every user: {}", dir.display(), String::from_utf8_lossy(&output.stdout).trim())
+            }
+            Err(e) => warn!("Unable to make '{}' writable by every user: {}", dir.display(), e),
+        }
+    }
+    Ok(())
+}
+
+/// Returns the sub-directory and file name an entry with the specified SHA256 checksum is stored as, which is the first
+/// two characters of the checksum, and the whole checksum in lower case. Returns None if the checksum is not valid.
+pub(crate) fn get_entry_name(sha256: &str) -> Option<(String, String)> {
+    if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let sha256 = sha256.to_ascii_lowercase();
+    Some((sha256[..2].to_owned(), sha256))
+}
+
+#[test]
+fn test_shared_cache_get_insert_and_evict() {
+    let dir = crate::testing::TempDir::new("velopack_cache").unwrap();
+    let cache = SharedCache::new(dir.join("cache"), 20);
+
+    let create_asset = |name: &str, content: &[u8]| {
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        let asset = VelopackAsset {
+            FileName: name.to_owned(),
+            Size: content.len() as u64,
+            SHA256: util::calculate_file_sha256(&path).unwrap().to_uppercase(),
+            ..Default::default()
+        };
+        (asset, path)
+    };
+    let (first, first_path) = create_asset("first.nupkg", b"first package");
+    let (second, second_path) = create_asset("second.nupkg", b"second package");
+
+    let target = dir.join("target.nupkg");
+    assert!(!cache.try_get(&first, &target).unwrap());
+    cache.insert(&first, &first_path).unwrap();
+    assert!(cache.try_get(&first, &target).unwrap());
+    assert_eq!(fs::read(&target).unwrap(), b"first package");
+
+    // an asset without a checksum is not cached
+    let unhashed = VelopackAsset { FileName: "unhashed.nupkg".to_owned(), ..Default::default() };
+    cache.insert(&unhashed, &first_path).unwrap();
+    assert!(!cache.try_get(&unhashed, &target).unwrap());
+
+    // a corrupt entry is removed rather than used
+    let entry = cache.get_entry_path(&first.SHA256).unwrap();
+    fs::write(&entry, b"first pockage").unwrap();
+    assert!(!cache.try_get(&first, &target).unwrap());
+    assert!(!entry.exists());
+
+    // adding the second package pushes the cache over its limit, so the least recently used entry is evicted
+    cache.insert(&first, &first_path).unwrap();
+    let old = SystemTime::now() - Duration::from_secs(60);
+    fs::File::options().write(true).open(&entry).unwrap().set_modified(old).unwrap();
+    cache.insert(&second, &second_path).unwrap();
+    assert!(!cache.try_get(&first, &target).unwrap());
+    assert!(cache.try_get(&second, &target).unwrap());
+
+    // every user can add and evict entries, whatever the umask of the user who created the cache
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let entry = cache.get_entry_path(&second.SHA256).unwrap();
+        for dir in [cache.dir(), &cache.dir().join("tmp"), entry.parent().unwrap()] {
+            assert_eq!(fs::metadata(dir).unwrap().permissions().mode() & 0o7777, 0o777, "{}", dir.display());
+        }
+    }
+}
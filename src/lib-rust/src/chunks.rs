@@ -0,0 +1,375 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::Sender,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+
+use crate::{
+    download::{self, CancellationToken},
+    progress::{ProgressEvent, ProgressPhase, ProgressTracker},
+    sources::UpdateSource,
+    Error, VelopackAsset,
+};
+
+/// Chunks are never smaller than this, except for the last chunk of a file.
+pub const MIN_CHUNK_SIZE: usize = 16 * 1024;
+/// The size chunks are aimed at, most chunks are close to this size.
+pub const AVG_CHUNK_SIZE: usize = 64 * 1024;
+/// Chunks are never larger than this.
+pub const MAX_CHUNK_SIZE: usize = 256 * 1024;
+
+/// Missing chunks which are next to each other are downloaded together, in requests of up to this many bytes.
+const MAX_RANGE_SIZE: u64 = 8 * 1024 * 1024;
+
+// chunk boundaries are harder to find before the average size, and easier after it, so chunk sizes cluster around it
+const MASK_SMALL: u64 = 0xffff_c000_0000_0000; // 18 bits
+const MASK_LARGE: u64 = 0xfffc_0000_0000_0000; // 14 bits
+
+/// Random values for the rolling hash, generated with splitmix64 so every client and packager agrees on them.
+const GEAR: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x5653_4b43_4843_4e4b;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+/// A chunk of a package, identified by the checksum of its contents.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ChunkInfo {
+    /// The offset of the chunk in the package.
+    pub Offset: u64,
+    /// The length of the chunk in bytes.
+    pub Length: u64,
+    /// The SHA256 checksum of the chunk.
+    pub SHA256: String,
+}
+
+/// Describes how a package is split into content-defined chunks. This is published next to each full package (as
+/// '{package}.chunks.json'), so that clients can download only the chunks which they do not already have in a package
+/// on disk, rather than the whole package, and without the feed needing a delta package for every old version.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ChunkIndex {
+    /// The file name of the package.
+    pub FileName: String,
+    /// The size of the package in bytes.
+    pub Size: u64,
+    /// The SHA256 checksum of the package.
+    pub SHA256: String,
+    /// The chunks of the package, in order.
+    pub Chunks: Vec<ChunkInfo>,
+}
+
+impl ChunkIndex {
+    /// Parses a chunk index from JSON.
+    pub fn from_json(json: &str) -> Result<ChunkIndex, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Serializes the chunk index to JSON.
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+/// Statistics about a package which was downloaded with [`download_with_chunks`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChunkStats {
+    /// The number of bytes which were copied from packages on disk.
+    pub reused_bytes: u64,
+    /// The number of bytes which were downloaded.
+    pub downloaded_bytes: u64,
+}
+
+/// Returns the file name of the chunk index of a package.
+pub fn get_chunk_index_file_name(package_file_name: &str) -> String {
+    format!("{}.chunks.json", package_file_name)
+}
+
+/// Returns the length of the first chunk in data, which must contain at least MAX_CHUNK_SIZE bytes unless it is the end
+/// of the file. This is a gear hash with normalized chunking, as described by FastCDC.
+fn find_chunk_length(data: &[u8]) -> usize {
+    if data.len() <= MIN_CHUNK_SIZE {
+        return data.len();
+    }
+    let end = data.len().min(MAX_CHUNK_SIZE);
+    let normal = AVG_CHUNK_SIZE.min(end);
+    let mut hash: u64 = 0;
+    for (i, byte) in data.iter().enumerate().take(end).skip(MIN_CHUNK_SIZE) {
+        hash = (hash << 1).wrapping_add(GEAR[*byte as usize]);
+        let mask = if i < normal { MASK_SMALL } else { MASK_LARGE };
+        if hash & mask == 0 {
+            return i + 1;
+        }
+    }
+    end
+}
+
+/// Splits the contents of a reader into chunks, and calls the callback with the offset and contents of each one.
+fn for_each_chunk<R: Read, F: FnMut(u64, &[u8]) -> Result<(), Error>>(mut reader: R, mut callback: F) -> Result<(), Error> {
+    let mut buffer = Vec::with_capacity(MAX_CHUNK_SIZE * 2);
+    let mut offset = 0u64;
+    let mut eof = false;
+    loop {
+        while !eof && buffer.len() < MAX_CHUNK_SIZE {
+            let start = buffer.len();
+            buffer.resize(MAX_CHUNK_SIZE, 0);
+            let read = reader.read(&mut buffer[start..])?;
+            buffer.truncate(start + read);
+            eof = read == 0;
+        }
+        if buffer.is_empty() {
+            return Ok(());
+        }
+        let length = find_chunk_length(&buffer);
+        callback(offset, &buffer[..length])?;
+        offset += length as u64;
+        buffer.drain(..length);
+    }
+}
+
+fn calculate_sha256(data: &[u8]) -> String {
+    format!("{:X}", sha2::Sha256::digest(data))
+}
+
+/// Splits a package into chunks, and returns the index which describes them.
+pub fn create_chunk_index<P: AsRef<Path>>(package: P) -> Result<ChunkIndex, Error> {
+    let package = package.as_ref();
+    let mut chunks = Vec::new();
+    let mut hasher = sha2::Sha256::new();
+    for_each_chunk(File::open(package)?, |offset, data| {
+        hasher.update(data);
+        chunks.push(ChunkInfo { Offset: offset, Length: data.len() as u64, SHA256: calculate_sha256(data) });
+        Ok(())
+    })?;
+    Ok(ChunkIndex {
+        FileName: package.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+        Size: chunks.last().map(|c| c.Offset + c.Length).unwrap_or(0),
+        SHA256: format!("{:X}", hasher.finalize()),
+        Chunks: chunks,
+    })
+}
+
+/// Creates the chunk index of a package, and writes it next to the package. Returns the path of the index.
+pub fn write_chunk_index<P: AsRef<Path>>(package: P) -> Result<PathBuf, Error> {
+    let package = package.as_ref();
+    let index = create_chunk_index(package)?;
+    let path = package.with_file_name(get_chunk_index_file_name(&index.FileName));
+    fs::write(&path, index.to_json()?)?;
+    Ok(path)
+}
+
+/// Downloads the chunk index which the source publishes next to a package. This fails if the source does not publish
+/// chunk indexes (eg. the feed was not built by the pack command), or if the index does not describe this package.
+pub fn download_chunk_index(
+    source: &dyn UpdateSource,
+    asset: &VelopackAsset,
+    temp_dir: &Path,
+    cancel: Option<&CancellationToken>,
+) -> Result<ChunkIndex, Error> {
+    fs::create_dir_all(temp_dir)?;
+    let index_asset = VelopackAsset { FileName: get_chunk_index_file_name(&asset.FileName), ..Default::default() };
+    let index_path = temp_dir.join(&index_asset.FileName);
+    source.download_release_entry_with_events(&index_asset, &index_path.to_string_lossy(), cancel, None)?;
+    let index = ChunkIndex::from_json(&fs::read_to_string(&index_path)?)?;
+    if index.Size != asset.Size || (!asset.SHA256.is_empty() && !index.SHA256.eq_ignore_ascii_case(&asset.SHA256)) {
+        return Err(Error::Generic(format!("The chunk index of '{}' does not match the package.", asset.FileName)));
+    }
+    Ok(index)
+}
+
+/// Downloads a package by copying the chunks it has in common with the seed packages already on disk (usually the package
+/// of the installed version), and downloading only the chunks which are missing. This requires the chunk index of the
+/// package (see [`download_chunk_index`]), and a source which supports downloading part of a package. The package is
+/// verified once it has been assembled, and any error means the package should be downloaded in full instead.
+pub fn download_with_chunks(
+    source: &dyn UpdateSource,
+    asset: &VelopackAsset,
+    index: &ChunkIndex,
+    seeds: &[PathBuf],
+    target_file: &Path,
+    cancel: Option<&CancellationToken>,
+    progress: Option<Sender<ProgressEvent>>,
+) -> Result<ChunkStats, Error> {
+
+    // every chunk of the seed packages, by checksum, so that identical chunks can be copied rather than downloaded
+    let mut local_chunks: HashMap<String, (usize, u64, u64)> = HashMap::new();
+    for (seed_index, seed) in seeds.iter().enumerate() {
+        let file = match File::open(seed) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("Unable to read '{}' to find chunks to reuse: {}", seed.display(), e);
+                continue;
+            }
+        };
+        for_each_chunk(file, |offset, data| {
+            local_chunks.entry(calculate_sha256(data)).or_insert((seed_index, offset, data.len() as u64));
+            Ok(())
+        })?;
+    }
+
+    let missing_bytes: u64 = index.Chunks.iter().filter(|c| !local_chunks.contains_key(&c.SHA256.to_uppercase())).map(|c| c.Length).sum();
+    info!(
+        "Downloading '{}' in chunks: {} of {} bytes are reused from {} package(s) on disk.",
+        asset.FileName,
+        index.Size - missing_bytes,
+        index.Size,
+        seeds.len()
+    );
+
+    let mut tracker = ProgressTracker::new(ProgressPhase::Download, Some(missing_bytes));
+    let mut seed_files: HashMap<usize, File> = HashMap::new();
+    let mut output = File::create(target_file)?;
+    let mut stats = ChunkStats::default();
+    let mut position = 0;
+    while position < index.Chunks.len() {
+        if let Some(cancel) = cancel {
+            cancel.check()?;
+        }
+
+        let chunk = &index.Chunks[position];
+        if let Some((seed_index, offset, length)) = local_chunks.get(&chunk.SHA256.to_uppercase()) {
+            let seed = match seed_files.entry(*seed_index) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(File::open(&seeds[*seed_index])?),
+            };
+            let mut data = vec![0; *length as usize];
+            seed.seek(SeekFrom::Start(*offset))?;
+            seed.read_exact(&mut data)?;
+            output.write_all(&data)?;
+            stats.reused_bytes += length;
+            position += 1;
+            continue;
+        }
+
+        // download this chunk along with any missing chunks which follow it
+        let mut end = position + 1;
+        let mut length = chunk.Length;
+        while let Some(next) = index.Chunks.get(end) {
+            if local_chunks.contains_key(&next.SHA256.to_uppercase()) || length + next.Length > MAX_RANGE_SIZE {
+                break;
+            }
+            length += next.Length;
+            end += 1;
+        }
+        let data = source.download_release_entry_range(asset, chunk.Offset, length)?;
+        if data.len() as u64 != length {
+            return Err(Error::Generic(format!("Expected {} bytes of '{}', but received {}.", length, asset.FileName, data.len())));
+        }
+        let mut data_offset = 0;
+        for chunk in &index.Chunks[position..end] {
+            let chunk_data = &data[data_offset..data_offset + chunk.Length as usize];
+            if !calculate_sha256(chunk_data).eq_ignore_ascii_case(&chunk.SHA256) {
+                return Err(Error::HashMismatch(format!("Chunk at offset {} of '{}' is corrupt.", chunk.Offset, asset.FileName)));
+            }
+            data_offset += chunk.Length as usize;
+        }
+        output.write_all(&data)?;
+        stats.downloaded_bytes += length;
+        if let Some(event) = tracker.advance(length) {
+            if let Some(progress) = &progress {
+                let _ = progress.send(event);
+            }
+        }
+        position = end;
+    }
+    drop(output);
+
+    download::verify_asset_file(asset, target_file)?;
+    if let Some(progress) = &progress {
+        let _ = progress.send(tracker.finish());
+    }
+    Ok(stats)
+}
+
+#[test]
+fn test_chunk_boundaries_are_content_defined() {
+    let mut state: u32 = 7;
+    let data: Vec<u8> = (0..2 * 1024 * 1024)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (state >> 16) as u8
+        })
+        .collect();
+
+    let mut lengths = Vec::new();
+    for_each_chunk(data.as_slice(), |_, chunk| {
+        lengths.push(chunk.len());
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(lengths.iter().sum::<usize>(), data.len());
+    assert!(lengths.iter().take(lengths.len() - 1).all(|l| *l >= MIN_CHUNK_SIZE && *l <= MAX_CHUNK_SIZE));
+
+    // inserting bytes at the start only changes the first chunk, the rest are found at the same place in the content
+    let mut shifted = b"inserted".to_vec();
+    shifted.extend_from_slice(&data);
+    let mut shifted_lengths = Vec::new();
+    for_each_chunk(shifted.as_slice(), |_, chunk| {
+        shifted_lengths.push(chunk.len());
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(shifted_lengths[0], lengths[0] + 8);
+    assert_eq!(shifted_lengths[1..], lengths[1..]);
+}
+
+#[test]
+fn test_download_with_chunks_reuses_local_chunks() {
+    use crate::sources::FileSource;
+
+    let dir = crate::testing::TempDir::new("velopack_chunks").unwrap();
+    let feed_dir = dir.join("feed");
+    fs::create_dir_all(&feed_dir).unwrap();
+    let mut state: u32 = 11;
+    let old: Vec<u8> = (0..1024 * 1024)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (state >> 16) as u8
+        })
+        .collect();
+    let mut new = old.clone();
+    new[500_000..500_100].fill(0);
+    new.extend_from_slice(b"appended to the new version");
+
+    let seed = dir.join("App-1.0.0-full.nupkg");
+    fs::write(&seed, &old).unwrap();
+    let package = feed_dir.join("App-2.0.0-full.nupkg");
+    fs::write(&package, &new).unwrap();
+    let index_path = write_chunk_index(&package).unwrap();
+    assert_eq!(index_path, feed_dir.join("App-2.0.0-full.nupkg.chunks.json"));
+
+    let asset = VelopackAsset {
+        FileName: "App-2.0.0-full.nupkg".to_owned(),
+        Size: new.len() as u64,
+        SHA256: crate::util::calculate_file_sha256(&package).unwrap().to_uppercase(),
+        ..Default::default()
+    };
+    let target = dir.join("downloaded.nupkg");
+    let source = FileSource::new(&feed_dir);
+    let index = download_chunk_index(&source, &asset, &dir.join("temp"), None).unwrap();
+    let stats = download_with_chunks(&source, &asset, &index, &[seed.clone()], &target, None, None).unwrap();
+    assert_eq!(fs::read(&target).unwrap(), new);
+    assert_eq!(stats.reused_bytes + stats.downloaded_bytes, new.len() as u64);
+    assert!(stats.downloaded_bytes < new.len() as u64 / 4);
+
+    // without a chunk index the package can not be downloaded in chunks
+    fs::remove_file(&index_path).unwrap();
+    assert!(download_chunk_index(&source, &asset, &dir.join("temp"), None).is_err());
+}
@@ -0,0 +1,181 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+
+use crate::{constants::COMPONENT_MANIFEST_FILE, manager::VelopackAsset, util, version, Error};
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+/// An optional component of an app (eg. a plugin or a language pack) which is listed in the release feed, and can be
+/// installed separately from the app itself.
+pub struct ComponentAsset {
+    /// The unique id of the component. The component is installed to a folder of the same name in the components directory.
+    pub ComponentId: String,
+    /// A SemVer requirement (eg. ">=1.2.0, <2.0.0") describing which versions of the app this release of the component is
+    /// compatible with. If this is empty, the component is compatible with every version of the app.
+    pub AppVersionRange: String,
+    /// The package containing this release of the component, which must be a zip archive. The PackageId is the id of the
+    /// app this component belongs to.
+    #[serde(flatten)]
+    pub Asset: VelopackAsset,
+}
+
+impl ComponentAsset {
+    /// Returns true if this release of the component can be used with the specified version of the app.
+    pub fn is_compatible_with(&self, app_version: &Version) -> bool {
+        if self.AppVersionRange.trim().is_empty() {
+            return true;
+        }
+        match VersionReq::parse(&self.AppVersionRange) {
+            Ok(req) => req.matches(app_version),
+            Err(e) => {
+                warn!("Component '{}' has an invalid app version range '{}': {}", self.ComponentId, self.AppVersionRange, e);
+                false
+            }
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+/// An optional component which is currently installed.
+pub struct InstalledComponent {
+    /// The unique id of the component.
+    pub ComponentId: String,
+    /// The installed version of the component.
+    pub Version: String,
+    /// The directory the component is installed to.
+    pub Path: PathBuf,
+    /// False if the installed version of the component is not compatible with the current version of the app, which
+    /// usually means the app has been updated and the component should be updated too.
+    pub IsCompatible: bool,
+}
+
+/// Returns the newest release of the specified component which is compatible with the specified app version.
+pub fn find_latest_compatible<'a>(components: &'a [ComponentAsset], component_id: &str, app_version: &Version) -> Option<&'a ComponentAsset> {
+    let mut latest: Option<(&ComponentAsset, Version)> = None;
+    for component in components.iter().filter(|c| c.ComponentId.eq_ignore_ascii_case(component_id)) {
+        if !component.is_compatible_with(app_version) {
+            continue;
+        }
+        if let Ok(v) = Version::parse(&component.Asset.Version) {
+            if latest.as_ref().map_or(true, |(_, latest_version)| version::is_newer(&v, latest_version)) {
+                latest = Some((component, v));
+            }
+        }
+    }
+    latest.map(|(c, _)| c)
+}
+
+/// Returns the components installed in the specified directory.
+pub fn read_installed(components_dir: &Path, app_version: &Version) -> Vec<InstalledComponent> {
+    let mut installed = Vec::new();
+    let entries = match fs::read_dir(components_dir) {
+        Ok(entries) => entries,
+        Err(_) => return installed,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let json = match fs::read_to_string(path.join(COMPONENT_MANIFEST_FILE)) {
+            Ok(json) => json,
+            Err(_) => continue,
+        };
+        match serde_json::from_str::<ComponentAsset>(&json) {
+            Ok(component) => installed.push(InstalledComponent {
+                IsCompatible: component.is_compatible_with(app_version),
+                ComponentId: component.ComponentId,
+                Version: component.Asset.Version,
+                Path: path,
+            }),
+            Err(e) => warn!("Unable to read component manifest in '{}': {}", path.display(), e),
+        }
+    }
+    installed.sort_by(|a, b| a.ComponentId.cmp(&b.ComponentId));
+    installed
+}
+
+/// Extracts a downloaded component package into the components directory, replacing any version already installed.
+pub fn install_from_package(components_dir: &Path, component: &ComponentAsset, package: &Path, app_version: &Version) -> Result<InstalledComponent, Error> {
+    validate_component_id(&component.ComponentId)?;
+    fs::create_dir_all(components_dir)?;
+
+    // extract next to the final location first, so that a failed extraction never leaves a half-installed component
+    let target = components_dir.join(&component.ComponentId);
+    let staging = components_dir.join(format!(".{}-{}", component.ComponentId, util::random_string(8)));
+    let result = extract_package(package, &staging).and_then(|_| {
+        fs::write(staging.join(COMPONENT_MANIFEST_FILE), serde_json::to_string_pretty(component)?)?;
+        if target.exists() {
+            util::retry_io(|| fs::remove_dir_all(&target))?;
+        }
+        util::retry_io(|| fs::rename(&staging, &target))?;
+        Ok(())
+    });
+    if let Err(e) = result {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+
+    info!("Installed component '{}' version {} to '{}'.", component.ComponentId, component.Asset.Version, target.display());
+    Ok(InstalledComponent {
+        ComponentId: component.ComponentId.clone(),
+        Version: component.Asset.Version.clone(),
+        Path: target,
+        IsCompatible: component.is_compatible_with(app_version),
+    })
+}
+
+/// Removes an installed component from the components directory. Does nothing if the component is not installed.
+pub fn remove(components_dir: &Path, component_id: &str) -> Result<(), Error> {
+    validate_component_id(component_id)?;
+    let target = components_dir.join(component_id);
+    if target.exists() {
+        info!("Removing component '{}' from '{}'.", component_id, target.display());
+        util::retry_io(|| fs::remove_dir_all(&target))?;
+    }
+    Ok(())
+}
+
+fn extract_package(package: &Path, output: &Path) -> Result<(), Error> {
+    let file = fs::File::open(package)?;
+    let mut archive = zip::ZipArchive::new(file)?;
+    // zip::ZipArchive::extract refuses entries which would be written outside of the output directory
+    archive.extract(output)?;
+    Ok(())
+}
+
+fn validate_component_id(component_id: &str) -> Result<(), Error> {
+    let valid = !component_id.is_empty()
+        && !component_id.starts_with('.')
+        && component_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::Generic(format!("'{}' is not a valid component id.", component_id)))
+    }
+}
+
+#[test]
+fn test_find_latest_compatible_component() {
+    let component = |id: &str, version: &str, range: &str| ComponentAsset {
+        ComponentId: id.to_owned(),
+        AppVersionRange: range.to_owned(),
+        Asset: VelopackAsset { Version: version.to_owned(), ..Default::default() },
+    };
+    let components = vec![
+        component("fr", "1.0.0", ">=1.0.0, <2.0.0"),
+        component("fr", "1.1.0", ">=1.0.0, <2.0.0"),
+        component("fr", "2.0.0", ">=2.0.0"),
+        component("de", "1.0.0", ""),
+    ];
+    let find = |id: &str, app: &str| find_latest_compatible(&components, id, &Version::parse(app).unwrap()).map(|c| c.Asset.Version.as_str());
+    assert_eq!(find("fr", "1.5.0"), Some("1.1.0"));
+    assert_eq!(find("fr", "2.1.0"), Some("2.0.0"));
+    assert_eq!(find("fr", "0.9.0"), None);
+    assert_eq!(find("de", "9.0.0"), Some("1.0.0"));
+    assert_eq!(find("es", "1.0.0"), None);
+}
@@ -3,7 +3,44 @@
 pub const HOOK_ENV_FIRSTRUN: &str = "VELOPACK_FIRSTRUN";
 pub const HOOK_ENV_DEBUG: &str = "VELOPACK_DEBUG";
 pub const HOOK_ENV_RESTART: &str = "VELOPACK_RESTART";
+/// Environment variable which redirects where downloaded packages and temporary files are stored.
+pub const PACKAGES_DIR_ENV: &str = "VELOPACK_PACKAGES_DIR";
+/// Environment variable with the SHA256 which the package passed to Update.exe apply must have for it to be applied.
+pub const PACKAGE_SHA256_ENV: &str = "VELOPACK_PACKAGE_SHA256";
+/// Environment variable with the url or directory of an intranet cache, which packages are downloaded from if possible.
+pub const CACHE_SERVER_ENV: &str = "VELOPACK_CACHE_SERVER";
+/// Environment variable with the sandbox directory of Update.exe (Windows only), which login items are recorded in
+/// instead of the registry.
+pub const SANDBOX_ENV: &str = "VELOPACK_SANDBOX";
 pub const HOOK_CLI_INSTALL: &str = "--veloapp-install";
 pub const HOOK_CLI_UPDATED: &str = "--veloapp-updated";
 pub const HOOK_CLI_OBSOLETE: &str = "--veloapp-obsolete";
-pub const HOOK_CLI_UNINSTALL: &str = "--veloapp-uninstall";
\ No newline at end of file
+pub const HOOK_CLI_UNINSTALL: &str = "--veloapp-uninstall";
+
+/// Exit code returned by Setup / Update when a prerequisite was installed but requires a system restart.
+pub const EXIT_CODE_RESTART_REQUIRED: i32 = 3010;
+/// Marker file written to the app root when a prerequisite requires a system restart.
+pub const RESTART_REQUIRED_MARKER: &str = ".restartrequired";
+/// Marker file written to the per-user data directory once the first-run hook has fired for that user.
+pub const USER_FIRSTRUN_MARKER: &str = ".firstrun";
+/// Marker file written to the app root containing a version which the user has chosen to skip.
+pub const SKIPPED_VERSION_MARKER: &str = ".skippedversion";
+/// State file written to the app root containing the time of the last update check, used to rate limit update checks.
+pub const LAST_UPDATE_CHECK_FILE: &str = ".lastupdatecheck";
+/// State file written to the packages dir containing the release feed of the last successful update check, used while offline.
+pub const CACHED_FEED_FILE: &str = ".cachedfeed.json";
+/// State file written to the packages dir when the release feed of a channel does not publish chunk indexes, so that
+/// downloads do not request a chunk index before every full download.
+pub const CHUNKS_UNAVAILABLE_FILE: &str = ".chunksunavailable.json";
+/// Entry in the root of a package created by BundleWriter, listing every file of the app with its SHA256 and size.
+pub const PACKAGE_FILE_LIST: &str = "velopack.files.json";
+/// File written to the folder of each installed optional component, describing which release of the component it contains.
+pub const COMPONENT_MANIFEST_FILE: &str = ".component.json";
+/// Marker file next to the app which switches it to portable mode, where nothing is written outside of the app's own folder.
+pub const PORTABLE_MARKER_FILE: &str = ".portable";
+/// Advisory lock file held while an install, update or uninstall is in progress (see `VelopackLocator::get_lock_file_path`).
+pub const ROOT_DIR_LOCK_FILE: &str = ".velopack.lock";
+/// A new updater extracted from a package, which replaces Update.exe once it has been verified.
+pub const STAGED_UPDATER_FILE: &str = "Update.new.exe";
+/// The previous updater, which is moved out of the way while it is replaced because it may still be running.
+pub const OLD_UPDATER_FILE: &str = "Update.old.exe";
@@ -1,5 +1,15 @@
-use std::{fs, io, path::Path};
-use crate::Error;
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+use semver::Version;
+use crate::{bundle, util, Error};
 
 /// Applies a zstd patch to a single file by loading the patch as a dictionary.
 pub fn zstd_patch_single<P1: AsRef<Path>, P2: AsRef<Path>, P3: AsRef<Path>>(old_file: P1, patch_file: P2, output_file: P3) -> Result<(), Error> {
@@ -45,4 +55,366 @@ fn fio_highbit64(v: u64) -> u32 {
         count += 1;
     }
     return count;
-}
\ No newline at end of file
+}
+
+/// The largest window zstd supports when creating a patch is 2^30 bytes, so files of 1 GiB or more can not be diffed.
+const MAX_DIFF_WINDOW_LOG: u32 = 30;
+
+/// Returns the window log needed for a patch to reference the whole of an old file of the specified size.
+fn get_diff_window_log(old_file_len: u64) -> u32 {
+    fio_highbit64(old_file_len) + 1
+}
+
+/// The number of files in each category when creating a delta package.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeltaStats {
+    /// Files which only exist in the new package, and are stored in the delta as they are.
+    pub new: usize,
+    /// Files which are identical in both packages.
+    pub same: usize,
+    /// Files which have changed, and are stored in the delta as a zstd patch.
+    pub changed: usize,
+    /// Files which only exist in the base package, and are removed when the delta is applied.
+    pub removed: usize,
+}
+
+/// Creates a delta package which turns `base_package` into `new_package`, in the same format vpk produces, so it can be
+/// applied by any version of the updater. Files are diffed in parallel. Returns an asset describing the delta package
+/// (including its checksums) which can be added to a release feed.
+pub fn create_delta_package<P1: AsRef<Path>, P2: AsRef<Path>, P3: AsRef<Path>>(
+    base_package: P1,
+    new_package: P2,
+    output_file: P3,
+) -> Result<(crate::VelopackAsset, DeltaStats), Error> {
+    create_delta_package_with_options(base_package, new_package, output_file, &bundle::CompressionOptions::default())
+}
+
+/// Creates a delta package like [create_delta_package], using the specified number of threads to diff files, and
+/// compression settings for the files which are stored in the delta as they are.
+pub fn create_delta_package_with_options<P1: AsRef<Path>, P2: AsRef<Path>, P3: AsRef<Path>>(
+    base_package: P1,
+    new_package: P2,
+    output_file: P3,
+    compression: &bundle::CompressionOptions,
+) -> Result<(crate::VelopackAsset, DeltaStats), Error> {
+    compression.validate()?;
+    let base_package = base_package.as_ref();
+    let new_package = new_package.as_ref();
+    let output_file = output_file.as_ref();
+
+    let base_manifest = bundle::load_bundle_from_file(base_package)?.read_manifest()?;
+    let new_manifest = bundle::load_bundle_from_file(new_package)?.read_manifest()?;
+    if base_manifest.version >= new_manifest.version {
+        return Err(Error::Generic(format!(
+            "Can not create a delta package from version {} to {}, the base version must be older.",
+            base_manifest.version, new_manifest.version
+        )));
+    }
+
+    let temp_dir = std::env::temp_dir().join(format!("velopack_delta_{}", util::random_string(8)));
+    let result = create_delta_package_in(base_package, new_package, output_file, &temp_dir, &new_manifest.version, compression);
+    let _ = fs::remove_dir_all(&temp_dir);
+    let stats = result?;
+
+    let asset = crate::VelopackAsset {
+        PackageId: new_manifest.id.clone(),
+        Version: new_manifest.version.to_string(),
+        Type: "Delta".to_owned(),
+        FileName: output_file.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+        // vpk writes upper case hashes, and older apps compare the SHA256 case-sensitively
+        SHA1: util::calculate_file_sha1(output_file)?.to_uppercase(),
+        SHA256: util::calculate_file_sha256(output_file)?.to_uppercase(),
+        Size: fs::metadata(output_file)?.len(),
+        NotesMarkdown: new_manifest.release_notes.clone(),
+        NotesHtml: new_manifest.release_notes_html.clone(),
+        IsMandatory: new_manifest.is_mandatory(),
+        Architecture: new_manifest.machine_architecture.clone(),
+    };
+    Ok((asset, stats))
+}
+
+fn create_delta_package_in(
+    base_package: &Path,
+    new_package: &Path,
+    output_file: &Path,
+    temp_dir: &Path,
+    version: &Version,
+    compression: &bundle::CompressionOptions,
+) -> Result<DeltaStats, Error> {
+    let base_dir = temp_dir.join("base");
+    let work_dir = temp_dir.join("new");
+    let base_files = extract_package(base_package, &base_dir, true)?;
+    let new_files = extract_package(new_package, &work_dir, false)?;
+    let new_lib_files: Vec<&String> = new_files.keys().filter(|name| name.starts_with("lib/")).collect();
+
+    // each thread holds a whole file in memory while diffing it, so by default no more than 8 are used
+    let num_threads = compression.get_threads();
+    info!("Creating delta for {} with {} threads ({} files).", version, num_threads, new_lib_files.len());
+
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let (new, same, changed) = (AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0));
+    let first_error: Mutex<Option<Error>> = Mutex::new(None);
+    std::thread::scope(|scope| {
+        for _ in 0..num_threads {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                if index >= new_lib_files.len() || failed.load(Ordering::SeqCst) {
+                    break;
+                }
+                let name = new_lib_files[index];
+                let target_file = work_dir.join(name);
+                let counter = match create_delta_for_single_file(base_files.get(name), &target_file) {
+                    Ok(DeltaFileKind::New) => &new,
+                    Ok(DeltaFileKind::Same) => &same,
+                    Ok(DeltaFileKind::Changed) => &changed,
+                    Err(e) => {
+                        error!("Failed to create a delta for '{}': {}", name, e);
+                        failed.store(true, Ordering::SeqCst);
+                        first_error.lock().unwrap().get_or_insert(e);
+                        break;
+                    }
+                };
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+    });
+
+    if let Some(e) = first_error.into_inner().unwrap() {
+        return Err(e);
+    }
+
+    let stats = DeltaStats {
+        new: new.into_inner(),
+        same: same.into_inner(),
+        changed: changed.into_inner(),
+        removed: base_files.keys().filter(|name| !new_files.contains_key(*name)).count(),
+    };
+    info!(
+        "Delta processed {} files. {} patched, {} unchanged, {} new, {} removed.",
+        new_lib_files.len(),
+        stats.changed,
+        stats.same,
+        stats.new,
+        stats.removed
+    );
+
+    write_dir_to_package(&work_dir, output_file, compression)?;
+    Ok(stats)
+}
+
+enum DeltaFileKind {
+    New,
+    Same,
+    Changed,
+}
+
+/// Replaces a file in the extracted new package with its delta. Unchanged files are replaced by an empty .diff and
+/// .shasum file, changed files by a .zsdiff patch and a .shasum file which the patched file is verified against.
+fn create_delta_for_single_file(base_file: Option<&PathBuf>, target_file: &Path) -> Result<DeltaFileKind, Error> {
+    let base_file = match base_file {
+        Some(f) => f,
+        None => return Ok(DeltaFileKind::New),
+    };
+
+    // files too large for a zstd patch are stored in full, as if they were new
+    if get_diff_window_log(fs::metadata(base_file)?.len()) > MAX_DIFF_WINDOW_LOG {
+        info!("'{}' is too large to diff, it will be stored in full.", target_file.to_string_lossy());
+        return Ok(DeltaFileKind::New);
+    }
+
+    let shasum_file = append_extension(target_file, "shasum");
+    let kind = if are_files_equal(base_file, target_file)? {
+        File::create(append_extension(target_file, "diff"))?;
+        File::create(&shasum_file)?;
+        DeltaFileKind::Same
+    } else {
+        zstd_diff_single(base_file, target_file, append_extension(target_file, "zsdiff"))?;
+        // the updater parses this as a release entry, and compares the SHA1 case-sensitively with its own (upper case) hash
+        let file_name = shasum_file.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let sha1 = util::calculate_file_sha1(target_file)?.to_uppercase();
+        fs::write(&shasum_file, format!("{} {} {}", sha1, file_name, fs::metadata(target_file)?.len()))?;
+        DeltaFileKind::Changed
+    };
+    fs::remove_file(target_file)?;
+    Ok(kind)
+}
+
+/// Creates a zstd patch which turns `old_file` into `new_file` (like `zstd --patch-from`), and can be applied with
+/// [zstd_patch_single].
+pub fn zstd_diff_single<P1: AsRef<Path>, P2: AsRef<Path>, P3: AsRef<Path>>(
+    old_file: P1,
+    new_file: P2,
+    output_file: P3,
+) -> Result<(), Error> {
+    let old_file = old_file.as_ref();
+    let new_file = new_file.as_ref();
+
+    let dict = fs::read(old_file)?;
+    let window_log = get_diff_window_log(dict.len() as u64);
+    if window_log > MAX_DIFF_WINDOW_LOG {
+        return Err(Error::Generic(format!("The file '{}' is too large for delta compression.", old_file.to_string_lossy())));
+    }
+
+    let output = fs::OpenOptions::new().write(true).create(true).truncate(true).open(output_file)?;
+    let mut encoder = zstd::Encoder::with_ref_prefix(io::BufWriter::new(output), 0, &dict)?;
+    // the window must cover the whole old file for it to be referenced, the patch side uses the same calculation
+    encoder.window_log(window_log.max(10))?;
+    if window_log >= 27 {
+        encoder.long_distance_matching(true)?;
+    }
+
+    let mut input = io::BufReader::new(File::open(new_file)?);
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?.flush()?;
+    Ok(())
+}
+
+fn are_files_equal(a: &Path, b: &Path) -> Result<bool, Error> {
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+
+    let mut reader_a = io::BufReader::new(File::open(a)?);
+    let mut reader_b = io::BufReader::new(File::open(b)?);
+    let mut buffer_a = [0; 64000];
+    let mut buffer_b = [0; 64000];
+    loop {
+        let len = reader_a.read(&mut buffer_a)?;
+        if len == 0 {
+            return Ok(true);
+        }
+        reader_b.read_exact(&mut buffer_b[..len])?;
+        if buffer_a[..len] != buffer_b[..len] {
+            return Ok(false);
+        }
+    }
+}
+
+fn append_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
+
+/// Extracts every entry (or only the entries in lib/) of a package to a directory, keeping their unix permissions, and
+/// returns a map from the name of each entry to the path it was extracted to.
+fn extract_package(package: &Path, dir: &Path, lib_only: bool) -> Result<HashMap<String, PathBuf>, Error> {
+    let mut archive = zip::ZipArchive::new(util::retry_io(|| File::open(package))?)?;
+    let mut files = HashMap::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = entry.name().replace('\\', "/");
+        if entry.is_dir() || (lib_only && !name.starts_with("lib/")) {
+            continue;
+        }
+        let relative = entry.enclosed_name().ok_or_else(|| Error::Generic(format!("Invalid file name in package: {}", name)))?;
+        let path = dir.join(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut entry, &mut File::create(&path)?)?;
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode & 0o777))?;
+        }
+        files.insert(name, path);
+    }
+    Ok(files)
+}
+
+fn write_dir_to_package(dir: &Path, output_file: &Path, compression: &bundle::CompressionOptions) -> Result<(), Error> {
+    let mut files = Vec::new();
+    bundle::collect_files_recursive(dir, &mut files)?;
+    files.sort();
+
+    let mut zip = zip::ZipWriter::new(File::create(output_file)?);
+    let options = compression.to_file_options();
+    for path in &files {
+        let relative = path.strip_prefix(dir).map_err(|e| Error::Generic(e.to_string()))?;
+        let name = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+        let meta = fs::metadata(path)?;
+        // patches are already compressed, there's no point in deflating them again
+        let file_options = match name.ends_with(".zsdiff") {
+            true => options.compression_method(zip::CompressionMethod::Stored),
+            false => options,
+        };
+        let file_options = file_options.large_file(meta.len() >= u32::MAX as u64);
+        #[cfg(unix)]
+        let file_options = {
+            use std::os::unix::fs::PermissionsExt;
+            file_options.unix_permissions(meta.permissions().mode())
+        };
+        zip.start_file(name, file_options)?;
+        io::copy(&mut File::open(path)?, &mut zip)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+#[test]
+fn test_create_delta_package_can_be_applied() {
+    let tmp = crate::testing::TempDir::new("velopack_delta_test").unwrap();
+    let write_package = |version: Version, files: &[(&str, &[u8])]| {
+        let app_dir = tmp.join(format!("app-{}", version));
+        for (name, contents) in files {
+            let path = app_dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        let output = tmp.join(format!("MyApp-{}-full.nupkg", version));
+        let manifest = bundle::Manifest { id: "MyApp".to_owned(), version, main_exe: "MyApp.exe".to_owned(), ..Default::default() };
+        bundle::BundleWriter::new(&app_dir, manifest).write_to_file(&output).unwrap();
+        output
+    };
+
+    let old_exe: Vec<u8> = (0..100_000u32).flat_map(|i| i.to_le_bytes()).collect();
+    let mut new_exe = old_exe.clone();
+    new_exe[1234] = 0xff;
+    new_exe.extend_from_slice(b"appended");
+    let base = write_package(Version::new(1, 0, 0), &[("MyApp.exe", &old_exe), ("same.txt", b"same"), ("removed.txt", b"bye")]);
+    let new = write_package(Version::new(1, 1, 0), &[("MyApp.exe", &new_exe), ("same.txt", b"same"), ("sub/added.txt", b"hi")]);
+
+    let output = tmp.join("MyApp-1.1.0-delta.nupkg");
+    let (asset, stats) = create_delta_package(&base, &new, &output).unwrap();
+    assert_eq!(stats, DeltaStats { new: 1, same: 1, changed: 1, removed: 1 });
+    assert_eq!(asset.Type, "Delta");
+    assert_eq!(asset.Version, "1.1.0");
+    assert_eq!(asset.SHA1, util::calculate_file_sha1(&output).unwrap().to_uppercase());
+
+    let extracted = tmp.join("extracted");
+    let files = extract_package(&output, &extracted, false).unwrap();
+    let mut names: Vec<&str> = files.keys().map(|n| n.as_str()).filter(|n| n.starts_with("lib/")).collect();
+    names.sort();
+    let expected = [
+        "lib/app/MyApp.exe.shasum",
+        "lib/app/MyApp.exe.zsdiff",
+        "lib/app/same.txt.diff",
+        "lib/app/same.txt.shasum",
+        "lib/app/sub/added.txt",
+    ];
+    assert_eq!(names, expected);
+    assert!(files.contains_key("MyApp.nuspec"));
+    assert_eq!(fs::read(&files["lib/app/sub/added.txt"]).unwrap(), b"hi");
+    assert_eq!(fs::metadata(&files["lib/app/same.txt.diff"]).unwrap().len(), 0);
+
+    let patched = tmp.join("patched.exe");
+    let old_file = tmp.join("app-1.0.0").join("MyApp.exe");
+    zstd_patch_single(&old_file, &files["lib/app/MyApp.exe.zsdiff"], &patched).unwrap();
+    assert_eq!(fs::read(&patched).unwrap(), new_exe);
+    let shasum = fs::read_to_string(&files["lib/app/MyApp.exe.shasum"]).unwrap();
+    let expected_sha1 = util::calculate_sha1(&new_exe).to_uppercase();
+    assert_eq!(shasum, format!("{} MyApp.exe.shasum {}", expected_sha1, new_exe.len()));
+
+    assert!(create_delta_package(&new, &base, tmp.join("backwards.nupkg")).is_err());
+}
+
+#[test]
+fn test_files_of_1gib_or_more_are_not_diffed() {
+    assert_eq!(get_diff_window_log(0), 1);
+    assert!(get_diff_window_log(1024 * 1024 * 1024 - 1) <= MAX_DIFF_WINDOW_LOG);
+    assert!(get_diff_window_log(1024 * 1024 * 1024) > MAX_DIFF_WINDOW_LOG);
+    assert!(get_diff_window_log(u64::MAX) > MAX_DIFF_WINDOW_LOG);
+}
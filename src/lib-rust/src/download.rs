@@ -1,56 +1,426 @@
 use std::fs::File;
 use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, OnceLock,
+};
 
-use crate::{Error, util};
+use sha2::Digest;
 
-/// Downloads a file from a URL and writes it to a file while reporting progress from 0-100.
-pub fn download_url_to_file<A>(url: &str, file_path: &str, mut progress: A) -> Result<(), Error>
-    where A: FnMut(i16),
-{
-    let agent = get_download_agent()?;
-    let response = agent.get(url).call()?;
+use crate::{
+    progress::{ProgressEvent, ProgressPhase, ProgressTracker},
+    util, Error, VelopackAsset,
+};
 
-    let total_size = response.header("Content-Length").and_then(|s| s.parse::<u64>().ok());
-    let mut file = util::retry_io(|| File::create(file_path))?;
+/// A handle which can be used to abort an in-flight download from another thread. Clones of a token share the
+/// same state, so cancelling any one of them cancels every operation which was given a clone of it.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
 
-    const CHUNK_SIZE: usize = 2 * 1024 * 1024; // 2MB
-    let mut downloaded: u64 = 0;
-    let mut buffer = vec![0; CHUNK_SIZE];
-    let mut reader = response.into_reader();
+impl CancellationToken {
+    /// Create a new token which has not been cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-    let mut last_progress = 0;
+    /// Request that any operation using this token stops as soon as possible.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns true if cancellation has been requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Returns Err(Error::Cancelled) if cancellation has been requested.
+    pub fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The response to a request made by a [`Downloader`].
+pub struct DownloadResponse {
+    /// A reader for the response body.
+    pub body: Box<dyn Read + Send>,
+    /// The length of the response body in bytes, if known (usually from the Content-Length header).
+    pub content_length: Option<u64>,
+}
+
+/// Information which identifies the app to the update server, so that server-side analytics or staged rollouts can tell
+/// groups of clients apart (eg. by app version, channel or a rollout cohort). Nothing is added to requests unless the
+/// app opts in by setting this on its update source.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RequestMetadata {
+    /// Replaces the default User-Agent of every request, eg. 'MyApp/1.2.3 (beta)'.
+    pub user_agent: Option<String>,
+    /// Extra headers which are sent with every request.
+    pub headers: Vec<(String, String)>,
+    /// Extra query parameters which are added to requests for the release feed.
+    pub query: Vec<(String, String)>,
+}
+
+/// Performs the HTTP requests needed to check for and download updates. Implement this to supply your own HTTP
+/// client, for example to use a different TLS stack or to sign requests. An implementation only needs to perform
+/// a single GET request: Velopack still takes care of retrying failed requests, reporting progress, cancellation,
+/// and verifying the checksum of downloaded packages.
+pub trait Downloader: Send + Sync {
+    /// Perform a GET request for the specified URL. A response with an unsuccessful status code should be returned
+    /// as an error, rather than as a response.
+    fn get(&self, url: &str) -> Result<DownloadResponse, Error>;
+    /// Perform a GET request for part of the specified URL, from offset and up to length bytes long. This is used to
+    /// download only the chunks of a package which are missing locally, and returns [`Error::Unsupported`] by default.
+    /// A server which ignores the range and returns the whole file should be returned as an error.
+    fn get_range(&self, url: &str, offset: u64, length: u64) -> Result<DownloadResponse, Error> {
+        let _ = (url, offset, length);
+        Err(Error::Unsupported("This downloader does not support range requests.".to_owned()))
+    }
+    /// Sets the User-Agent and extra headers to send with every request. The query parameters of the metadata are
+    /// added to the url by the update source. There is no default, because a server may rely on these headers (eg. for
+    /// authentication), so a downloader which ignores them must do so deliberately.
+    fn set_request_metadata(&mut self, metadata: &RequestMetadata);
+    /// Clone the downloader to create a new lifetime.
+    fn clone_boxed(&self) -> Box<dyn Downloader>;
+}
+
+impl Clone for Box<dyn Downloader> {
+    fn clone(&self) -> Self {
+        self.clone_boxed()
+    }
+}
+
+/// The default downloader, which uses ureq and the platform native TLS stack. Every instance shares one pool of
+/// keep-alive connections, so checking for updates and downloading the packages it found re-uses the same connection
+/// to each server, rather than paying for a new TCP and TLS handshake on every request.
+#[derive(Clone, Default)]
+pub struct DefaultDownloader {
+    metadata: RequestMetadata,
+}
+
+impl DefaultDownloader {
+    /// Sends a GET request, and returns the status code with the response. Error statuses are returned as errors.
+    #[cfg(not(feature = "http2"))]
+    fn send(&self, url: &str, range: Option<&str>) -> Result<(u16, DownloadResponse), Error> {
+        let mut request = get_download_agent()?.get(url);
+        if let Some(user_agent) = &self.metadata.user_agent {
+            request = request.set("User-Agent", user_agent);
+        }
+        for (name, value) in &self.metadata.headers {
+            request = request.set(name, value);
+        }
+        if let Some(range) = range {
+            request = request.set("Range", range);
+        }
+        let response = request.call()?;
+        let content_length = response.header("Content-Length").and_then(|s| s.parse::<u64>().ok());
+        Ok((response.status(), DownloadResponse { body: response.into_reader(), content_length }))
+    }
+
+    /// Sends a GET request, and returns the status code with the response. Error statuses are returned as errors.
+    #[cfg(feature = "http2")]
+    fn send(&self, url: &str, range: Option<&str>) -> Result<(u16, DownloadResponse), Error> {
+        let mut request = get_download_client()?.get(url);
+        if let Some(user_agent) = &self.metadata.user_agent {
+            request = request.header("User-Agent", user_agent);
+        }
+        for (name, value) in &self.metadata.headers {
+            request = request.header(name, value);
+        }
+        if let Some(range) = range {
+            request = request.header("Range", range);
+        }
+        let response = request.send()?.error_for_status()?;
+        let status = response.status().as_u16();
+        let content_length = response.content_length();
+        Ok((status, DownloadResponse { body: Box::new(response), content_length }))
+    }
+}
 
-    while let Ok(size) = reader.read(&mut buffer) {
-        if size == 0 {
-            break; // End of stream
+impl Downloader for DefaultDownloader {
+    fn get(&self, url: &str) -> Result<DownloadResponse, Error> {
+        Ok(self.send(url, None)?.1)
+    }
+
+    fn get_range(&self, url: &str, offset: u64, length: u64) -> Result<DownloadResponse, Error> {
+        let range = format!("bytes={}-{}", offset, offset + length.max(1) - 1);
+        let (status, response) = self.send(url, Some(&range))?;
+        if status != 206 {
+            return Err(Error::Unsupported(format!("The server did not return a partial response for '{}'.", url)));
         }
-        file.write_all(&buffer[..size])?;
-        downloaded += size as u64;
+        Ok(response)
+    }
+
+    fn set_request_metadata(&mut self, metadata: &RequestMetadata) {
+        self.metadata = metadata.clone();
+    }
 
-        if total_size.is_some() {
+    fn clone_boxed(&self) -> Box<dyn Downloader> {
+        Box::new(self.clone())
+    }
+}
+
+/// Downloads a file from a URL and writes it to a file while reporting progress from 0-100.
+pub fn download_url_to_file<A>(url: &str, file_path: &str, mut progress: A) -> Result<(), Error>
+    where A: FnMut(i16),
+{
+    let mut last_progress = 0;
+    download_url_to_file_with_events(url, file_path, None, |event| {
+        if event.BytesTotal.is_some() {
             // floor to nearest 5% to reduce message spam
-            let new_progress = (downloaded as f64 / total_size.unwrap() as f64 * 20.0).floor() as i16 * 5;
+            let new_progress = event.Percent / 5 * 5;
             if new_progress > last_progress {
                 last_progress = new_progress;
                 progress(last_progress);
             }
         }
+    })
+}
+
+/// Downloads a file from a URL and writes it to a file while reporting detailed progress (bytes, speed, and ETA).
+/// If a cancellation token is provided and is cancelled, the download stops and the partial file is removed.
+pub fn download_url_to_file_with_events<A>(url: &str, file_path: &str, cancel: Option<&CancellationToken>, progress: A) -> Result<(), Error>
+    where A: FnMut(ProgressEvent),
+{
+    download_to_file_with(&DefaultDownloader::default(), url, file_path, cancel, progress)
+}
+
+/// Downloads a file from a URL using the provided [`Downloader`], the same as [`download_url_to_file_with_events`].
+/// The download fails if the response ends before, or continues past, its Content-Length.
+pub fn download_to_file_with<A>(
+    downloader: &dyn Downloader,
+    url: &str,
+    file_path: &str,
+    cancel: Option<&CancellationToken>,
+    progress: A,
+) -> Result<(), Error>
+    where A: FnMut(ProgressEvent),
+{
+    download_to_file_impl(downloader, url, file_path, None, cancel, progress)
+}
+
+/// Downloads an asset from a URL using the provided [`Downloader`], and verifies it while it is downloaded: the download
+/// stops as soon as the response is known to be the wrong size (from its Content-Length, or because it runs past the
+/// size of the asset), and the checksum is calculated as the data arrives rather than by reading the file afterwards.
+/// The partial file is removed if the download fails.
+pub fn download_asset_to_file_with<A>(
+    downloader: &dyn Downloader,
+    url: &str,
+    asset: &VelopackAsset,
+    file_path: &str,
+    cancel: Option<&CancellationToken>,
+    progress: A,
+) -> Result<(), Error>
+    where A: FnMut(ProgressEvent),
+{
+    download_to_file_impl(downloader, url, file_path, Some(asset), cancel, progress)
+}
+
+fn download_to_file_impl<A>(
+    downloader: &dyn Downloader,
+    url: &str,
+    file_path: &str,
+    asset: Option<&VelopackAsset>,
+    cancel: Option<&CancellationToken>,
+    progress: A,
+) -> Result<(), Error>
+    where A: FnMut(ProgressEvent),
+{
+    if let Some(cancel) = cancel {
+        cancel.check()?;
+    }
+
+    let response = util::retry_io(|| downloader.get(url))?;
+    let expected_size = asset.map(|a| a.Size).filter(|size| *size > 0);
+    if let (Some(expected), Some(actual)) = (expected_size, response.content_length) {
+        if expected != actual {
+            return Err(Error::HashMismatch(format!(
+                "Content-Length of '{}' ({}) does not match the expected size ({}).",
+                url, actual, expected
+            )));
+        }
+    }
+
+    let file = util::retry_io(|| File::create(file_path))?;
+    let result = stream_to_file(response, file, url, asset, expected_size, cancel, progress);
+    if let Err(e) = &result {
+        if matches!(e, Error::Cancelled) {
+            info!("Download of '{}' was cancelled, removing partial file.", url);
+        }
+        let _ = std::fs::remove_file(file_path);
+    }
+    result
+}
+
+fn stream_to_file<A>(
+    response: DownloadResponse,
+    mut file: File,
+    url: &str,
+    asset: Option<&VelopackAsset>,
+    expected_size: Option<u64>,
+    cancel: Option<&CancellationToken>,
+    mut progress: A,
+) -> Result<(), Error>
+    where A: FnMut(ProgressEvent),
+{
+    // the checksum is chosen the same way as in verify_asset_file
+    let mut sha256 = asset.filter(|a| !a.SHA256.is_empty()).map(|_| sha2::Sha256::new());
+    let mut sha1 = asset.filter(|a| a.SHA256.is_empty() && !a.SHA1.is_empty()).map(|_| sha1::Sha1::new());
+    let expected_size = expected_size.or(response.content_length);
+
+    const CHUNK_SIZE: usize = 2 * 1024 * 1024; // 2MB
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut reader = response.body;
+    let mut tracker = ProgressTracker::new(ProgressPhase::Download, response.content_length);
+    let mut written: u64 = 0;
+
+    loop {
+        let size = match reader.read(&mut buffer) {
+            Ok(0) => break, // End of stream
+            Ok(size) => size,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        if cancel.is_some_and(|c| c.is_cancelled()) {
+            return Err(Error::Cancelled);
+        }
+        written += size as u64;
+        if let Some(expected) = expected_size.filter(|expected| written > *expected) {
+            return Err(Error::HashMismatch(format!("Download of '{}' is longer than the expected size ({}).", url, expected)));
+        }
+        let data = &buffer[..size];
+        file.write_all(data)?;
+        if let Some(sha256) = sha256.as_mut() {
+            sha256.update(data);
+        }
+        if let Some(sha1) = sha1.as_mut() {
+            sha1.update(data);
+        }
+        if let Some(event) = tracker.advance(size as u64) {
+            progress(event);
+        }
+    }
+
+    if let Some(expected) = expected_size.filter(|expected| written < *expected) {
+        return Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            format!("Download of '{}' ended after {} of {} bytes.", url, written, expected),
+        )));
+    }
+
+    let checksum = match (sha256, sha1, asset) {
+        (Some(sha256), _, Some(asset)) => Some(("SHA256", &asset.SHA256, format!("{:x}", sha256.finalize()))),
+        (_, Some(sha1), Some(asset)) => Some(("SHA1", &asset.SHA1, format!("{:x}", sha1.finalize()))),
+        _ => None,
+    };
+    if let Some((name, expected, actual)) = checksum {
+        if !expected.eq_ignore_ascii_case(&actual) {
+            return Err(Error::HashMismatch(format!(
+                "{} checksum of '{}' ({}) does not match the expected checksum ({}).",
+                name, url, actual, expected
+            )));
+        }
     }
 
+    progress(tracker.finish());
     Ok(())
 }
 
 /// Downloads a file from a URL and returns it as a string.
 pub fn download_url_as_string(url: &str) -> Result<String, Error> {
-    let agent = get_download_agent()?;
-    let r = agent.get(url).call()?.into_string()?;
+    download_as_string_with(&DefaultDownloader::default(), url)
+}
+
+/// Downloads a file from a URL using the provided [`Downloader`] and returns it as a string.
+pub fn download_as_string_with(downloader: &dyn Downloader, url: &str) -> Result<String, Error> {
+    let mut response = util::retry_io(|| downloader.get(url))?;
+    let mut r = String::new();
+    response.body.read_to_string(&mut r)?;
     Ok(r)
 }
 
+/// Returns an error if the file does not match the size and checksum of the asset it is supposed to contain.
+/// The SHA256 checksum is preferred, falling back to SHA1 for feeds which do not provide one.
+pub fn verify_asset_file<P: AsRef<Path>>(asset: &VelopackAsset, file_path: P) -> Result<(), Error> {
+    let file_path = file_path.as_ref();
+    if asset.Size > 0 {
+        let actual_size = file_path.metadata()?.len();
+        if actual_size != asset.Size {
+            return Err(Error::HashMismatch(format!(
+                "Size of '{}' ({}) does not match the expected size ({}).",
+                file_path.display(),
+                actual_size,
+                asset.Size
+            )));
+        }
+    }
+
+    let (name, expected, actual) = if !asset.SHA256.is_empty() {
+        ("SHA256", &asset.SHA256, util::calculate_file_sha256(file_path)?)
+    } else if !asset.SHA1.is_empty() {
+        ("SHA1", &asset.SHA1, util::calculate_file_sha1(file_path)?)
+    } else {
+        warn!("No checksum available for '{}', skipping verification.", file_path.display());
+        return Ok(());
+    };
+
+    if !expected.eq_ignore_ascii_case(&actual) {
+        return Err(Error::HashMismatch(format!(
+            "{} checksum of '{}' ({}) does not match the expected checksum ({}).",
+            name,
+            file_path.display(),
+            actual,
+            expected
+        )));
+    }
+    Ok(())
+}
+
+/// The number of idle connections kept open to each server. Each download makes its requests one at a time (a chunked
+/// download fetches its chunks in sequence), so this only needs to cover the downloads which run at the same time on
+/// different threads, eg. a background update check while a package is downloaded.
+const MAX_IDLE_CONNECTIONS_PER_HOST: usize = 4;
+
+// created on first use rather than up front, because building the TLS connector can fail
+#[cfg(not(feature = "http2"))]
+static DOWNLOAD_AGENT: OnceLock<ureq::Agent> = OnceLock::new();
+
+#[cfg(not(feature = "http2"))]
 fn get_download_agent() -> Result<ureq::Agent, Error> {
-    let tls_builder = native_tls::TlsConnector::builder();
-    let tls_connector = tls_builder.build()?;
-    Ok(ureq::AgentBuilder::new().tls_connector(tls_connector.into()).build())
+    // clones of an agent share its connection pool
+    if let Some(agent) = DOWNLOAD_AGENT.get() {
+        return Ok(agent.clone());
+    }
+    let tls_connector = native_tls::TlsConnector::builder().build()?;
+    let agent = ureq::AgentBuilder::new()
+        .tls_connector(tls_connector.into())
+        .max_idle_connections_per_host(MAX_IDLE_CONNECTIONS_PER_HOST)
+        .build();
+    // if another thread created an agent first, that one is kept so that there is only ever one pool
+    Ok(DOWNLOAD_AGENT.get_or_init(|| agent).clone())
+}
+
+#[cfg(feature = "http2")]
+static DOWNLOAD_CLIENT: OnceLock<reqwest::blocking::Client> = OnceLock::new();
+
+#[cfg(feature = "http2")]
+fn get_download_client() -> Result<reqwest::blocking::Client, Error> {
+    // clones of a client share its connection pool, and requests to a server which speaks HTTP/2 share one connection
+    if let Some(client) = DOWNLOAD_CLIENT.get() {
+        return Ok(client.clone());
+    }
+    // hyper sends lowercase header names over HTTP/1.1, which some older servers and proxies fail to match
+    let client = reqwest::blocking::Client::builder()
+        .pool_max_idle_per_host(MAX_IDLE_CONNECTIONS_PER_HOST)
+        .http1_title_case_headers()
+        .build()?;
+    Ok(DOWNLOAD_CLIENT.get_or_init(|| client).clone())
 }
 
 #[test]
@@ -58,6 +428,19 @@ fn test_download_uses_tls_and_encoding_correctly() {
     assert_eq!(download_url_as_string("https://dotnetcli.blob.core.windows.net/dotnet/WindowsDesktop/5.0/latest.version").unwrap(), "5.0.17");
 }
 
+#[test]
+fn test_download_cancelled_before_start_does_not_create_file() {
+    let token = CancellationToken::new();
+    token.clone().cancel();
+    assert!(token.is_cancelled());
+
+    let dir = crate::testing::TempDir::new("velopack_download").unwrap();
+    let file_path = dir.join("test_download_cancelled_before_start.txt");
+    let result = download_url_to_file_with_events("https://proof.ovh.net/files/10Mb.dat", &file_path.to_string_lossy(), Some(&token), |_| {});
+    assert!(matches!(result, Err(Error::Cancelled)));
+    assert!(!file_path.exists());
+}
+
 #[test]
 fn test_download_file_reports_progress() {
     // https://www.ip-toolbox.com/speedtest-files/
@@ -82,3 +465,124 @@ fn test_download_file_reports_progress() {
     assert_eq!(len, 10 * 1024 * 1024);
     std::fs::remove_file(p).unwrap();
 }
+
+#[test]
+fn test_default_downloader_reuses_connections() {
+    use std::io::BufRead;
+    use std::net::TcpListener;
+    use std::sync::atomic::AtomicUsize;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/RELEASES", listener.local_addr().unwrap());
+    let connections = Arc::new(AtomicUsize::new(0));
+    let accepted = connections.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            accepted.fetch_add(1, Ordering::SeqCst);
+            std::thread::spawn(move || {
+                let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+                let mut writer = stream;
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap_or(0) > 0 {
+                    if line == "\r\n" {
+                        writer.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello").unwrap();
+                    }
+                    line.clear();
+                }
+            });
+        }
+    });
+
+    for _ in 0..3 {
+        assert_eq!(download_as_string_with(&DefaultDownloader::default(), &url).unwrap(), "hello");
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[cfg(test)]
+#[derive(Clone)]
+struct MemoryDownloader {
+    body: Vec<u8>,
+}
+
+#[cfg(test)]
+impl Downloader for MemoryDownloader {
+    fn get(&self, url: &str) -> Result<DownloadResponse, Error> {
+        if url.ends_with("missing") {
+            return Err(Error::Generic("not found".to_owned()));
+        }
+        // a truncated response claims to be longer than its body, as if the connection dropped
+        let extra = if url.ends_with("truncated") { 10 } else { 0 };
+        let content_length = Some(self.body.len() as u64 + extra);
+        Ok(DownloadResponse { body: Box::new(std::io::Cursor::new(self.body.clone())), content_length })
+    }
+
+    fn set_request_metadata(&mut self, metadata: &RequestMetadata) {
+        let _ = metadata;
+    }
+
+    fn clone_boxed(&self) -> Box<dyn Downloader> {
+        Box::new(self.clone())
+    }
+}
+
+#[test]
+fn test_download_with_custom_downloader_and_verify() {
+    let downloader = MemoryDownloader { body: b"hello velopack".to_vec() };
+    assert_eq!(download_as_string_with(&downloader, "mem://feed").unwrap(), "hello velopack");
+
+    let dir = crate::testing::TempDir::new("velopack_download").unwrap();
+    let file_path = dir.join("test_download_with_custom_downloader.txt");
+    let mut last_percent = 0;
+    download_to_file_with(&downloader, "mem://package", &file_path.to_string_lossy(), None, |e| last_percent = e.Percent).unwrap();
+    assert_eq!(last_percent, 100);
+    assert_eq!(std::fs::read(&file_path).unwrap(), b"hello velopack");
+
+    let mut asset = VelopackAsset {
+        Size: 14,
+        SHA256: util::calculate_file_sha256(&file_path).unwrap().to_uppercase(),
+        ..Default::default()
+    };
+    verify_asset_file(&asset, &file_path).unwrap();
+
+    asset.SHA256 = "0".repeat(64);
+    assert!(matches!(verify_asset_file(&asset, &file_path), Err(Error::HashMismatch(_))));
+    asset.SHA256 = String::new();
+    asset.Size = 1;
+    assert!(matches!(verify_asset_file(&asset, &file_path), Err(Error::HashMismatch(_))));
+
+    let _ = std::fs::remove_file(&file_path);
+    assert!(download_to_file_with(&downloader, "mem://missing", &file_path.to_string_lossy(), None, |_| {}).is_err());
+}
+
+#[test]
+fn test_download_asset_is_verified_while_streaming() {
+    let downloader = MemoryDownloader { body: b"hello velopack".to_vec() };
+    let dir = crate::testing::TempDir::new("velopack_download").unwrap();
+    let file_path = dir.join("test_download_asset_verified.txt");
+    let file = file_path.to_string_lossy().to_string();
+    let sha256 = format!("{:x}", sha2::Sha256::digest(b"hello velopack"));
+    let mut asset = VelopackAsset { Size: 14, SHA256: sha256.to_uppercase(), ..Default::default() };
+    download_asset_to_file_with(&downloader, "mem://package", &asset, &file, None, |_| {}).unwrap();
+    assert_eq!(std::fs::read(&file_path).unwrap(), b"hello velopack");
+
+    // a response which is shorter than its Content-Length fails, even without an asset to verify against
+    assert!(download_to_file_with(&downloader, "mem://truncated", &file, None, |_| {}).is_err());
+    assert!(!file_path.exists());
+
+    // a Content-Length which does not match the asset fails before anything is written
+    asset.Size = 20;
+    assert!(matches!(download_asset_to_file_with(&downloader, "mem://package", &asset, &file, None, |_| {}), Err(Error::HashMismatch(_))));
+    assert!(!file_path.exists());
+
+    // corrupt data fails the checksum, and the partial file is removed
+    asset.Size = 14;
+    asset.SHA256 = "0".repeat(64);
+    assert!(matches!(download_asset_to_file_with(&downloader, "mem://package", &asset, &file, None, |_| {}), Err(Error::HashMismatch(_))));
+    assert!(!file_path.exists());
+
+    // the SHA1 checksum is used if there is no SHA256 checksum
+    asset.SHA256 = String::new();
+    asset.SHA1 = format!("{:x}", sha1::Sha1::digest(b"hello velopack"));
+    download_asset_to_file_with(&downloader, "mem://package", &asset, &file, None, |_| {}).unwrap();
+}
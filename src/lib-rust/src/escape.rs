@@ -0,0 +1,20 @@
+/// Escapes text for use in the content or attribute values of an XML document, such as a nuspec, a plist or a
+/// scheduled task definition.
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&apos;")
+}
+
+/// Quotes an argument for the Exec key of a desktop entry, so that it is passed to the program unchanged.
+/// https://specifications.freedesktop.org/desktop-entry-spec/latest/exec-variables.html
+pub fn escape_desktop_exec_arg(text: &str) -> String {
+    let escaped = text.replace('\\', "\\\\").replace('"', "\\\"").replace('`', "\\`").replace('$', "\\$");
+    // a backslash is also an escape character in desktop entry strings, so they are escaped a second time
+    format!("\"{}\"", escaped.replace('\\', "\\\\"))
+}
+
+#[test]
+fn test_escape_helpers() {
+    assert_eq!(escape_xml(r#"<a href="x">Tom & Jerry's</a>"#), "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/a&gt;");
+    assert_eq!(escape_desktop_exec_arg("/opt/My App/app"), "\"/opt/My App/app\"");
+    assert_eq!(escape_desktop_exec_arg(r#"/a\b"$c`"#), r#""/a\\\\b\\"\\$c\\`""#);
+}
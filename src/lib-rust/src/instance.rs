@@ -0,0 +1,446 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ipc::{self, IpcConnection, IpcServer},
+    Error,
+};
+
+/// A system-wide named lock. Only one process can hold a lock with a given name at a time,
+/// and the lock is released automatically when this is dropped or the process exits.
+/// On Windows this is a named mutex, on other platforms it is an exclusive lock on a file in a directory which only the
+/// current user can access, so it is only visible to processes running as the same user.
+pub struct GlobalMutex {
+    #[cfg(target_os = "windows")]
+    handle: windows::Win32::Foundation::HANDLE,
+    #[cfg(not(target_os = "windows"))]
+    _file: fs::File,
+    owner_file: Option<PathBuf>,
+}
+
+#[cfg(target_os = "windows")]
+unsafe impl Send for GlobalMutex {}
+#[cfg(target_os = "windows")]
+unsafe impl Sync for GlobalMutex {}
+
+impl GlobalMutex {
+    /// Try to acquire the named lock. Returns None if the lock is already held by another process.
+    #[cfg(target_os = "windows")]
+    pub fn try_acquire(name: &str) -> Result<Option<GlobalMutex>, Error> {
+        use windows::core::HSTRING;
+        use windows::Win32::Foundation::{CloseHandle, GetLastError, ERROR_ALREADY_EXISTS};
+        use windows::Win32::System::Threading::CreateMutexW;
+
+        let encoded = HSTRING::from(name);
+        let handle = unsafe { CreateMutexW(None, true, &encoded) }
+            .map_err(|e| Error::Generic(format!("Unable to create global mutex '{}': {}", name, e)))?;
+        if unsafe { GetLastError() } == ERROR_ALREADY_EXISTS {
+            unsafe { CloseHandle(handle).ok() };
+            return Ok(None);
+        }
+        Ok(Some(GlobalMutex::with_owner(name, handle)))
+    }
+
+    /// Try to acquire the named lock. Returns None if the lock is already held by another process.
+    #[cfg(not(target_os = "windows"))]
+    pub fn try_acquire(name: &str) -> Result<Option<GlobalMutex>, Error> {
+        use std::os::unix::io::AsRawFd;
+        let path = ipc::get_user_runtime_dir()?.join(format!("{}.lock", name));
+        let file = fs::OpenOptions::new().create(true).truncate(false).read(true).write(true).open(&path)?;
+        let ret = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+        if ret != 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::WouldBlock {
+                return Ok(None);
+            }
+            return Err(err.into());
+        }
+        Ok(Some(GlobalMutex::with_owner(name, file)))
+    }
+
+    /// Try to acquire the named lock, waiting up to `timeout` for another process to release it.
+    /// Returns None if the lock is still held by another process once the timeout has elapsed.
+    pub fn acquire_with_timeout(name: &str, timeout: Duration) -> Result<Option<GlobalMutex>, Error> {
+        let start = Instant::now();
+        let mut logged_owner = false;
+        loop {
+            if let Some(mutex) = Self::try_acquire(name)? {
+                return Ok(Some(mutex));
+            }
+            if start.elapsed() >= timeout {
+                return Ok(None);
+            }
+            if !logged_owner {
+                let owner = Self::get_owner(name).unwrap_or_else(|| "unknown process".to_owned());
+                info!("Global mutex '{}' is held by {}, waiting up to {:?} for it to be released...", name, owner, timeout);
+                logged_owner = true;
+            }
+            thread::sleep(Duration::from_millis(250));
+        }
+    }
+
+    /// Returns a description of the process which currently holds the named lock, if known.
+    pub fn get_owner(name: &str) -> Option<String> {
+        fs::read_to_string(get_owner_file(name)?).ok().map(|s| s.trim().to_owned()).filter(|s| !s.is_empty())
+    }
+
+    #[cfg(target_os = "windows")]
+    fn with_owner(name: &str, handle: windows::Win32::Foundation::HANDLE) -> GlobalMutex {
+        GlobalMutex { handle, owner_file: write_owner_file(name) }
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    fn with_owner(name: &str, file: fs::File) -> GlobalMutex {
+        GlobalMutex { _file: file, owner_file: write_owner_file(name) }
+    }
+}
+
+fn get_owner_file(name: &str) -> Option<PathBuf> {
+    ipc::get_user_runtime_dir().ok().map(|d| d.join(format!("{}.owner", name)))
+}
+
+fn write_owner_file(name: &str) -> Option<PathBuf> {
+    // this is only used for diagnostics, so failing to write it is not an error
+    let path = get_owner_file(name)?;
+    let _ = fs::write(&path, describe_current_process());
+    Some(path)
+}
+
+fn describe_current_process() -> String {
+    let exe = std::env::current_exe().ok().and_then(|p| p.file_name().map(|f| f.to_string_lossy().to_string())).unwrap_or_default();
+    let machine = std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .or_else(|_| fs::read_to_string("/etc/hostname").map(|h| h.trim().to_owned()))
+        .unwrap_or_default();
+    if machine.is_empty() {
+        format!("{} (pid {})", exe, std::process::id())
+    } else {
+        format!("{} (pid {} on {})", exe, std::process::id(), machine)
+    }
+}
+
+/// An advisory lock on a file. Unlike [`GlobalMutex`], which is only visible to processes on the local machine,
+/// this also protects directories which are shared by several machines (eg. an install on a network share).
+/// The lock is released automatically when this is dropped or the process exits.
+pub struct FileLock {
+    file: fs::File,
+}
+
+impl FileLock {
+    /// Try to lock the specified file, creating it if it does not exist. Returns None if the file is already
+    /// locked by another process.
+    pub fn try_acquire(path: &Path) -> Result<Option<FileLock>, Error> {
+        let file = match Self::open_exclusive(path) {
+            Ok(Some(f)) => f,
+            Ok(None) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut lock = FileLock { file };
+        // record who holds the lock, so that processes waiting for it can report something useful
+        lock.file.set_len(0)?;
+        lock.file.write_all(describe_current_process().as_bytes())?;
+        lock.file.flush()?;
+        Ok(Some(lock))
+    }
+
+    /// Try to lock the specified file, waiting up to `timeout` for another process to release it.
+    /// Returns None if the file is still locked by another process once the timeout has elapsed.
+    pub fn acquire_with_timeout(path: &Path, timeout: Duration) -> Result<Option<FileLock>, Error> {
+        let start = Instant::now();
+        let mut logged_owner = false;
+        loop {
+            if let Some(lock) = Self::try_acquire(path)? {
+                return Ok(Some(lock));
+            }
+            if start.elapsed() >= timeout {
+                return Ok(None);
+            }
+            if !logged_owner {
+                let owner = Self::get_owner(path).unwrap_or_else(|| "unknown process".to_owned());
+                info!("Lock file '{}' is held by {}, waiting up to {:?} for it to be released...", path.display(), owner, timeout);
+                logged_owner = true;
+            }
+            thread::sleep(Duration::from_millis(250));
+        }
+    }
+
+    /// Returns a description of the process which currently holds the lock on the specified file, if known.
+    pub fn get_owner(path: &Path) -> Option<String> {
+        fs::read_to_string(path).ok().map(|s| s.trim().to_owned()).filter(|s| !s.is_empty())
+    }
+
+    #[cfg(target_os = "windows")]
+    fn open_exclusive(path: &Path) -> std::io::Result<Option<fs::File>> {
+        use std::os::windows::fs::OpenOptionsExt;
+        const FILE_SHARE_READ: u32 = 0x00000001;
+        const ERROR_SHARING_VIOLATION: i32 = 32;
+        // other processes may read the owner description, but nobody else can open the file for writing
+        match fs::OpenOptions::new().create(true).truncate(false).read(true).write(true).share_mode(FILE_SHARE_READ).open(path) {
+            Ok(f) => Ok(Some(f)),
+            Err(e) if e.raw_os_error() == Some(ERROR_SHARING_VIOLATION) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    fn open_exclusive(path: &Path) -> std::io::Result<Option<fs::File>> {
+        use std::os::unix::io::AsRawFd;
+        let file = fs::OpenOptions::new().create(true).truncate(false).read(true).write(true).open(path)?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::WouldBlock {
+                return Ok(None);
+            }
+            return Err(err);
+        }
+        Ok(Some(file))
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // the file is left behind, since deleting it could race with another process acquiring it
+        let _ = self.file.set_len(0);
+    }
+}
+
+impl Drop for GlobalMutex {
+    fn drop(&mut self) {
+        // the owner file must be removed while we still hold the lock, so we don't delete the next owner's file
+        if let Some(owner_file) = &self.owner_file {
+            let _ = fs::remove_file(owner_file);
+        }
+        #[cfg(target_os = "windows")]
+        unsafe {
+            windows::Win32::Foundation::CloseHandle(self.handle).ok();
+        }
+    }
+}
+
+/// Keeps the current process registered as the primary instance of an application.
+/// While this is alive, activation arguments sent by other instances will be delivered
+/// to the callback provided to [`acquire_single_instance`].
+pub struct SingleInstanceGuard {
+    _mutex: GlobalMutex,
+    ipc_name: String,
+    token_file: PathBuf,
+    stopping: Arc<AtomicBool>,
+}
+
+impl Drop for SingleInstanceGuard {
+    fn drop(&mut self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        // wake up the listener thread so it can observe the stop flag and exit
+        let _ = IpcConnection::connect(&self.ipc_name);
+        let _ = fs::remove_file(&self.token_file);
+    }
+}
+
+/// Sent by a secondary instance to the primary one. The token is read from a file which only the current user can access,
+/// so other users (and anything else which can reach the socket or pipe) can not inject activation arguments.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize)]
+struct ActivationMessage {
+    Token: String,
+    Args: Vec<String>,
+}
+
+fn get_instance_names(app_id: &str) -> Result<(String, String, PathBuf), Error> {
+    let mutex_name = format!("velopack-instance-{}", app_id);
+    // named pipes are visible to every session on the machine, so the pipe name must be unique to the current user
+    #[cfg(target_os = "windows")]
+    let ipc_name = format!("{}-{}", mutex_name, std::env::var("USERNAME").unwrap_or_default());
+    #[cfg(not(target_os = "windows"))]
+    let ipc_name = mutex_name.clone();
+    let token_file = ipc::get_user_runtime_dir()?.join(format!("{}.token", mutex_name));
+    Ok((mutex_name, ipc_name, token_file))
+}
+
+/// Try to register the current process as the only running instance of the specified application.
+/// If no other instance is running, this returns a guard which must be kept alive for the lifetime of the
+/// process, and `on_activated` will be called (on a background thread) with the arguments of any other
+/// instance which is started later. If another instance is already running, `args` are forwarded to it
+/// and None is returned; the caller should then exit.
+pub fn acquire_single_instance<F>(app_id: &str, args: &[String], on_activated: F) -> Result<Option<SingleInstanceGuard>, Error>
+where
+    F: Fn(Vec<String>) + Send + 'static,
+{
+    let (mutex_name, ipc_name, token_file) = get_instance_names(app_id)?;
+
+    let mutex = match GlobalMutex::try_acquire(&mutex_name)? {
+        Some(m) => m,
+        None => {
+            info!("Another instance of '{}' is already running, forwarding activation arguments.", app_id);
+            forward_args_to_primary(&ipc_name, &token_file, args)?;
+            return Ok(None);
+        }
+    };
+
+    let token = crate::util::random_string(32);
+    write_private_file(&token_file, token.as_bytes())?;
+    let server = IpcServer::bind(&ipc_name)?;
+    info!("Registered as the primary instance of '{}' (listening on '{}').", app_id, ipc_name);
+
+    let stopping = Arc::new(AtomicBool::new(false));
+    let stopping_thread = stopping.clone();
+    let on_activated = Arc::new(Mutex::new(on_activated));
+    thread::spawn(move || loop {
+        let conn = server.accept();
+        if stopping_thread.load(Ordering::SeqCst) {
+            break;
+        }
+        let mut conn = match conn {
+            Ok(c) => c,
+            Err(e) => {
+                // on Windows only one pipe instance can exist at a time, so this fails until the last connection is closed
+                warn!("Failed to accept single-instance connection: {}", e);
+                thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+        };
+        // read each message on its own thread, so a client which never sends anything can't block later activations
+        let token = token.clone();
+        let on_activated = on_activated.clone();
+        thread::spawn(move || match conn.receive::<ActivationMessage>() {
+            Ok(Some(message)) if message.Token == token => {
+                if let Ok(on_activated) = on_activated.lock() {
+                    on_activated(message.Args);
+                }
+            }
+            Ok(Some(_)) => warn!("Rejected activation arguments with an invalid token."),
+            Ok(None) => {}
+            Err(e) => warn!("Received invalid activation arguments: {}", e),
+        });
+    });
+
+    Ok(Some(SingleInstanceGuard { _mutex: mutex, ipc_name, token_file, stopping }))
+}
+
+#[cfg(unix)]
+fn write_private_file(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    use std::os::unix::fs::OpenOptionsExt;
+    let mut file = fs::OpenOptions::new().create(true).write(true).truncate(true).mode(0o600).open(path)?;
+    file.write_all(contents)?;
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn write_private_file(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    use std::os::windows::io::{FromRawHandle, RawHandle};
+    use windows::core::HSTRING;
+    use windows::Win32::Foundation::{GENERIC_WRITE, HANDLE};
+    use windows::Win32::Storage::FileSystem::{CreateFileW, CREATE_NEW, FILE_ATTRIBUTE_NORMAL, FILE_SHARE_MODE};
+
+    // the security descriptor is only applied to new files, so a file left behind by an earlier instance is replaced
+    let _ = fs::remove_file(path);
+    let security = ipc::CurrentUserSecurity::new()?;
+    let attributes = security.get_attributes();
+    let encoded = HSTRING::from(path);
+    let handle = unsafe {
+        CreateFileW(
+            &encoded,
+            GENERIC_WRITE.0,
+            FILE_SHARE_MODE(0),
+            Some(&attributes as *const _),
+            CREATE_NEW,
+            FILE_ATTRIBUTE_NORMAL,
+            HANDLE::default(),
+        )
+    }
+    .map_err(|e| Error::Generic(format!("Unable to create '{}': {}", path.to_string_lossy(), e)))?;
+    let mut file = unsafe { fs::File::from_raw_handle(handle.0 as RawHandle) };
+    file.write_all(contents)?;
+    Ok(())
+}
+
+fn forward_args_to_primary(ipc_name: &str, token_file: &Path, args: &[String]) -> Result<(), Error> {
+    // the primary instance may have only just acquired the mutex and not yet started listening
+    let mut last_error = None;
+    for _ in 0..10 {
+        match try_forward_args(ipc_name, token_file, args) {
+            Ok(()) => return Ok(()),
+            Err(e) => last_error = Some(e),
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    Err(last_error.unwrap())
+}
+
+fn try_forward_args(ipc_name: &str, token_file: &Path, args: &[String]) -> Result<(), Error> {
+    let token = fs::read_to_string(token_file)?.trim().to_owned();
+    let conn = IpcConnection::connect(ipc_name)?;
+    conn.send(&ActivationMessage { Token: token, Args: args.to_vec() })?;
+    Ok(())
+}
+
+#[test]
+fn test_global_mutex_is_exclusive() {
+    let name = format!("velopack-test-{}", crate::util::random_string(8));
+    let first = GlobalMutex::try_acquire(&name).unwrap();
+    assert!(first.is_some());
+    assert!(GlobalMutex::try_acquire(&name).unwrap().is_none());
+    drop(first);
+    assert!(GlobalMutex::try_acquire(&name).unwrap().is_some());
+}
+
+#[test]
+fn test_global_mutex_waits_for_owner() {
+    let name = format!("velopack-test-{}", crate::util::random_string(8));
+    let first = GlobalMutex::try_acquire(&name).unwrap().unwrap();
+    assert!(GlobalMutex::get_owner(&name).unwrap().contains(&std::process::id().to_string()));
+    assert!(GlobalMutex::acquire_with_timeout(&name, Duration::from_millis(300)).unwrap().is_none());
+
+    let releaser = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(300));
+        drop(first);
+    });
+    assert!(GlobalMutex::acquire_with_timeout(&name, Duration::from_secs(10)).unwrap().is_some());
+    releaser.join().unwrap();
+}
+
+#[test]
+fn test_file_lock_is_exclusive() {
+    let dir = crate::testing::TempDir::new("velopack_lock").unwrap();
+    let path = dir.join("velopack-test.lock");
+    let first = FileLock::try_acquire(&path).unwrap();
+    assert!(first.is_some());
+    assert!(FileLock::get_owner(&path).unwrap().contains(&std::process::id().to_string()));
+    assert!(FileLock::try_acquire(&path).unwrap().is_none());
+    drop(first);
+    assert!(FileLock::get_owner(&path).is_none());
+    assert!(FileLock::try_acquire(&path).unwrap().is_some());
+}
+
+#[test]
+fn test_single_instance_forwards_args() {
+    use std::sync::mpsc;
+    let app_id = format!("test-{}", crate::util::random_string(8));
+    let (tx, rx) = mpsc::channel();
+    let guard = acquire_single_instance(&app_id, &[], move |args| tx.send(args).unwrap()).unwrap();
+    assert!(guard.is_some());
+
+    // activations which do not carry the token of the primary instance are ignored
+    let (_, ipc_name, token_file) = get_instance_names(&app_id).unwrap();
+    let conn = IpcConnection::connect(&ipc_name).unwrap();
+    conn.send(&ActivationMessage { Token: "wrong".to_owned(), Args: vec!["--evil".to_owned()] }).unwrap();
+    drop(conn);
+
+    let args = vec!["--open".to_string(), "file.txt".to_string()];
+    forward_args_to_primary(&ipc_name, &token_file, &args).unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), args);
+    assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+
+    drop(guard);
+    assert!(!token_file.exists());
+}
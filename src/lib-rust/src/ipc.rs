@@ -0,0 +1,463 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    sync::{Arc, Mutex},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{manager::UpdateInfo, progress::ProgressEvent, Error};
+
+/// Commands which can be sent from a running application to the updater.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "Command")]
+pub enum IpcCommand {
+    /// Check the update source for a newer release. The updater will reply with [`IpcEvent::UpdateCheck`].
+    CheckForUpdates,
+    /// Download the release found by the last successful check. The updater will reply with a stream of
+    /// [`IpcEvent::Progress`] events, followed by [`IpcEvent::DownloadCompleted`].
+    DownloadUpdates,
+    /// Apply the downloaded release as soon as the application exits. The updater will reply with
+    /// [`IpcEvent::ApplyQueued`] and close the connection.
+    ApplyOnExit {
+        /// Don't show any prompts / dialogs while applying.
+        Silent: bool,
+        /// Restart the application after the update has been applied.
+        Restart: bool,
+        /// Arguments to pass to the application when it is restarted.
+        RestartArgs: Vec<String>,
+    },
+    /// Abort any operation in progress and stop the updater.
+    Cancel,
+}
+
+/// Events sent from the updater back to the running application.
+#[allow(non_snake_case, clippy::large_enum_variant)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "Event")]
+pub enum IpcEvent {
+    /// The result of a [`IpcCommand::CheckForUpdates`]. Update is None if there is no update available.
+    UpdateCheck {
+        /// The available update, if any.
+        Update: Option<UpdateInfo>,
+    },
+    /// Download progress, from 0 to 100.
+    Progress {
+        /// The percentage complete.
+        Percent: i16,
+        /// Detailed progress (bytes transferred, speed and ETA), if available.
+        #[serde(default)]
+        Details: Option<ProgressEvent>,
+    },
+    /// The update package has been downloaded and is ready to be applied.
+    DownloadCompleted,
+    /// The update will be applied once the application exits.
+    ApplyQueued,
+    /// The updater is shutting down at the request of the application.
+    Cancelled,
+    /// The last command failed.
+    Error {
+        /// A description of the error.
+        Message: String,
+    },
+}
+
+/// A cloneable handle for writing messages to an IPC connection, which can be used from any thread.
+#[derive(Clone)]
+pub struct IpcSender {
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl IpcSender {
+    /// Serialize and send a message to the other side of the connection.
+    pub fn send<T: Serialize>(&self, message: &T) -> Result<(), Error> {
+        let mut json = serde_json::to_string(message)?;
+        json.push('\n');
+        let mut writer = self.writer.lock().map_err(|_| Error::Generic("IPC writer lock poisoned".to_owned()))?;
+        writer.write_all(json.as_bytes())?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// A connection between a running application and the updater. Messages are exchanged as newline-delimited json.
+pub struct IpcConnection {
+    reader: BufReader<Box<dyn Read + Send>>,
+    sender: IpcSender,
+}
+
+impl IpcConnection {
+    /// Create a connection from a separate reader and writer for the same underlying stream.
+    pub fn from_parts(reader: Box<dyn Read + Send>, writer: Box<dyn Write + Send>) -> Self {
+        IpcConnection { reader: BufReader::new(reader), sender: IpcSender { writer: Arc::new(Mutex::new(writer)) } }
+    }
+
+    /// Connect to an IPC server listening on the specified name.
+    #[cfg(target_os = "windows")]
+    pub fn connect(name: &str) -> Result<Self, Error> {
+        let (command_path, event_path) = pipe_paths(name);
+        let writer = std::fs::OpenOptions::new().write(true).open(command_path)?;
+        let reader = std::fs::OpenOptions::new().read(true).open(event_path)?;
+        Ok(Self::from_parts(Box::new(reader), Box::new(writer)))
+    }
+
+    /// Connect to an IPC server listening on the specified name.
+    #[cfg(unix)]
+    pub fn connect(name: &str) -> Result<Self, Error> {
+        let writer = std::os::unix::net::UnixStream::connect(socket_path(name)?)?;
+        check_peer_is_current_user(&writer)?;
+        let reader = writer.try_clone()?;
+        Ok(Self::from_parts(Box::new(reader), Box::new(writer)))
+    }
+
+    /// Serialize and send a message to the other side of the connection.
+    pub fn send<T: Serialize>(&self, message: &T) -> Result<(), Error> {
+        self.sender.send(message)
+    }
+
+    /// Returns a handle which can be used to send messages from another thread.
+    pub fn sender(&self) -> IpcSender {
+        self.sender.clone()
+    }
+
+    /// Block until the next message is received. Returns None if the other side has closed the connection.
+    pub fn receive<T: DeserializeOwned>(&mut self) -> Result<Option<T>, Error> {
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            let line = line.trim();
+            if !line.is_empty() {
+                return Ok(Some(serde_json::from_str(line)?));
+            }
+        }
+    }
+}
+
+/// Listens for incoming IPC connections. On Windows this is a pair of named pipes whose DACL only grants access to the
+/// current user, and on macOS and Linux it is a unix domain socket in a directory which only the current user can access.
+/// Connections from processes running as another user are rejected.
+pub struct IpcServer {
+    #[cfg(target_os = "windows")]
+    name: String,
+    #[cfg(unix)]
+    listener: std::os::unix::net::UnixListener,
+    #[cfg(unix)]
+    path: std::path::PathBuf,
+}
+
+impl IpcServer {
+    /// Create a server which listens on the specified name.
+    #[cfg(target_os = "windows")]
+    pub fn bind(name: &str) -> Result<Self, Error> {
+        Ok(IpcServer { name: name.to_owned() })
+    }
+
+    /// Create a server which listens on the specified name.
+    #[cfg(unix)]
+    pub fn bind(name: &str) -> Result<Self, Error> {
+        use std::os::unix::fs::PermissionsExt;
+
+        // a socket file left behind by an updater which crashed would make bind fail, and nothing can be listening on it
+        // because the name includes the pid of the host process
+        let path = socket_path(name)?;
+        let _ = std::fs::remove_file(&path);
+        let listener = std::os::unix::net::UnixListener::bind(&path)?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+        Ok(IpcServer { listener, path })
+    }
+
+    /// Block until a client connects, and return the connection.
+    #[cfg(target_os = "windows")]
+    pub fn accept(&self) -> Result<IpcConnection, Error> {
+        use windows::Win32::Storage::FileSystem::{PIPE_ACCESS_INBOUND, PIPE_ACCESS_OUTBOUND};
+
+        // synchronous pipe handles serialize all IO, so a pending read would block any concurrent write
+        // on a duplex pipe. Instead we use one pipe per direction so reads and writes are independent.
+        let (command_path, event_path) = pipe_paths(&self.name);
+        let command_pipe = create_pipe_instance(&command_path, PIPE_ACCESS_INBOUND)?;
+        let event_pipe = create_pipe_instance(&event_path, PIPE_ACCESS_OUTBOUND)?;
+        let reader = connect_pipe_instance(command_pipe)?;
+        let writer = connect_pipe_instance(event_pipe)?;
+        Ok(IpcConnection::from_parts(Box::new(reader), Box::new(writer)))
+    }
+
+    /// Block until a client connects, and return the connection.
+    #[cfg(unix)]
+    pub fn accept(&self) -> Result<IpcConnection, Error> {
+        loop {
+            // unlike synchronous pipe handles on Windows, a unix socket can be read and written from separate threads at once
+            let (writer, _) = self.listener.accept()?;
+            if let Err(e) = check_peer_is_current_user(&writer) {
+                warn!("Rejected IPC connection ({}).", e);
+                continue;
+            }
+            let reader = writer.try_clone()?;
+            return Ok(IpcConnection::from_parts(Box::new(reader), Box::new(writer)));
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(unix)]
+fn socket_path(name: &str) -> Result<std::path::PathBuf, Error> {
+    Ok(get_user_runtime_dir()?.join(format!("{}.sock", name)))
+}
+
+/// Returns a directory for sockets and other runtime files which only the current user can access. On Windows this is the
+/// temp directory, which is already private to each user.
+#[cfg(unix)]
+pub(crate) fn get_user_runtime_dir() -> Result<std::path::PathBuf, Error> {
+    // XDG_RUNTIME_DIR (Linux) and TMPDIR (macOS) are usually private to the current user, and the updater inherits them
+    // from the host process, so both sides agree on the path
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR").map(std::path::PathBuf::from).filter(|p| p.is_dir());
+    get_user_runtime_dir_in(&runtime_dir.unwrap_or_else(std::env::temp_dir))
+}
+
+#[cfg(unix)]
+fn get_user_runtime_dir_in(base_dir: &std::path::Path) -> Result<std::path::PathBuf, Error> {
+    let dir = base_dir.join(format!("velopack-{}", unsafe { libc::geteuid() }));
+    create_private_dir(&dir)?;
+    Ok(dir)
+}
+
+/// Returns a directory for sockets and other runtime files which only the current user can access. On Windows this is the
+/// temp directory, which is already private to each user.
+#[cfg(target_os = "windows")]
+pub(crate) fn get_user_runtime_dir() -> Result<std::path::PathBuf, Error> {
+    Ok(std::env::temp_dir())
+}
+
+/// Creates a directory which only the current user can access, or checks that an existing one still is, since the base
+/// directory may be shared by every user (eg. /tmp), and another user could have created it first.
+#[cfg(unix)]
+fn create_private_dir(dir: &std::path::Path) -> Result<(), Error> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    if let Err(e) = std::fs::DirBuilder::new().mode(0o700).create(dir) {
+        if e.kind() != std::io::ErrorKind::AlreadyExists {
+            return Err(e.into());
+        }
+    }
+    let meta = std::fs::symlink_metadata(dir)?;
+    if !meta.is_dir() || meta.uid() != unsafe { libc::geteuid() } || meta.mode() & 0o077 != 0 {
+        return Err(Error::Generic(format!("The IPC directory '{}' is not private to the current user.", dir.to_string_lossy())));
+    }
+    Ok(())
+}
+
+/// Returns an error if the process at the other end of a unix socket is running as a different user.
+#[cfg(unix)]
+fn check_peer_is_current_user(stream: &std::os::unix::net::UnixStream) -> Result<(), Error> {
+    use std::os::unix::io::AsRawFd;
+
+    let uid = get_peer_uid(stream.as_raw_fd()).map_err(|e| Error::Generic(format!("Unable to read IPC peer credentials: {}", e)))?;
+    let euid = unsafe { libc::geteuid() };
+    if uid != euid {
+        return Err(Error::Generic(format!("The IPC peer is running as user {}, not {}.", uid, euid)));
+    }
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn get_peer_uid(fd: std::os::unix::io::RawFd) -> std::io::Result<libc::uid_t> {
+    let mut cred: libc::ucred = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let cred_ptr = &mut cred as *mut libc::ucred as *mut libc::c_void;
+    // SAFETY: cred is a valid buffer of len bytes for the SO_PEERCRED result
+    let ret = unsafe { libc::getsockopt(fd, libc::SOL_SOCKET, libc::SO_PEERCRED, cred_ptr, &mut len) };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(cred.uid)
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn get_peer_uid(fd: std::os::unix::io::RawFd) -> std::io::Result<libc::uid_t> {
+    let mut uid: libc::uid_t = 0;
+    let mut gid: libc::gid_t = 0;
+    // SAFETY: uid and gid are valid out pointers
+    if unsafe { libc::getpeereid(fd, &mut uid, &mut gid) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(uid)
+}
+
+#[cfg(target_os = "windows")]
+fn pipe_paths(name: &str) -> (String, String) {
+    (format!(r"\\.\pipe\{}-cmd", name), format!(r"\\.\pipe\{}-evt", name))
+}
+
+#[cfg(target_os = "windows")]
+fn create_pipe_instance(
+    path: &str,
+    access: windows::Win32::Storage::FileSystem::FILE_FLAGS_AND_ATTRIBUTES,
+) -> Result<windows::Win32::Foundation::HANDLE, Error> {
+    use windows::core::HSTRING;
+    use windows::Win32::Storage::FileSystem::FILE_FLAG_FIRST_PIPE_INSTANCE;
+    use windows::Win32::System::Pipes::{CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_WAIT};
+
+    // pipe names are visible to every user and session, so fail rather than share a name which another process created
+    // first, and only let the current user connect
+    let encoded = HSTRING::from(path);
+    let mode = PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS;
+    let security = CurrentUserSecurity::new()?;
+    let attributes = security.get_attributes();
+    let access = access | FILE_FLAG_FIRST_PIPE_INSTANCE;
+    let handle = unsafe { CreateNamedPipeW(&encoded, access, mode, 1, 65536, 65536, 0, Some(&attributes as *const _)) };
+    if handle.is_invalid() {
+        return Err(Error::Generic(format!("Unable to create named pipe '{}': {}", path, windows::core::Error::from_win32())));
+    }
+    Ok(handle)
+}
+
+#[cfg(target_os = "windows")]
+fn connect_pipe_instance(handle: windows::Win32::Foundation::HANDLE) -> Result<std::fs::File, Error> {
+    use std::os::windows::io::{FromRawHandle, RawHandle};
+    use windows::Win32::Foundation::{CloseHandle, ERROR_PIPE_CONNECTED};
+    use windows::Win32::System::Pipes::ConnectNamedPipe;
+
+    if let Err(e) = unsafe { ConnectNamedPipe(handle, None) } {
+        // the client may connect between CreateNamedPipe and ConnectNamedPipe, which is not an error
+        if e.code() != ERROR_PIPE_CONNECTED.to_hresult() {
+            unsafe { CloseHandle(handle).ok() };
+            return Err(Error::Generic(format!("Unable to accept named pipe connection: {}", e)));
+        }
+    }
+    Ok(unsafe { std::fs::File::from_raw_handle(handle.0 as RawHandle) })
+}
+
+/// A security descriptor whose DACL only grants access to the current user, for pipes and files which must not be
+/// readable by other users or sessions on the same machine.
+#[cfg(target_os = "windows")]
+pub(crate) struct CurrentUserSecurity {
+    descriptor: windows::Win32::Security::PSECURITY_DESCRIPTOR,
+}
+
+#[cfg(target_os = "windows")]
+impl CurrentUserSecurity {
+    pub(crate) fn new() -> Result<Self, Error> {
+        use windows::core::HSTRING;
+        use windows::Win32::Security::Authorization::{ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1};
+        use windows::Win32::Security::PSECURITY_DESCRIPTOR;
+
+        // a protected DACL with a single entry, so nothing is inherited from the parent directory
+        let sddl = HSTRING::from(format!("D:P(A;;GA;;;{})", get_current_user_sid()?));
+        let mut descriptor = PSECURITY_DESCRIPTOR::default();
+        unsafe { ConvertStringSecurityDescriptorToSecurityDescriptorW(&sddl, SDDL_REVISION_1, &mut descriptor, None) }
+            .map_err(|e| Error::Generic(format!("Unable to create a security descriptor: {}", e)))?;
+        Ok(CurrentUserSecurity { descriptor })
+    }
+
+    /// Returns security attributes which refer to this descriptor, so they must not outlive it.
+    pub(crate) fn get_attributes(&self) -> windows::Win32::Security::SECURITY_ATTRIBUTES {
+        windows::Win32::Security::SECURITY_ATTRIBUTES {
+            nLength: std::mem::size_of::<windows::Win32::Security::SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: self.descriptor.0,
+            bInheritHandle: false.into(),
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl Drop for CurrentUserSecurity {
+    fn drop(&mut self) {
+        let _ = unsafe { windows::Win32::Foundation::LocalFree(windows::Win32::Foundation::HLOCAL(self.descriptor.0)) };
+    }
+}
+
+/// Returns the SID of the user the current process is running as, in its string form (eg. "S-1-5-21-...").
+#[cfg(target_os = "windows")]
+fn get_current_user_sid() -> Result<String, Error> {
+    use windows::core::PWSTR;
+    use windows::Win32::Foundation::{CloseHandle, LocalFree, HANDLE, HLOCAL};
+    use windows::Win32::Security::Authorization::ConvertSidToStringSidW;
+    use windows::Win32::Security::{GetTokenInformation, TokenUser, TOKEN_QUERY, TOKEN_USER};
+    use windows::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    let to_error = |e: windows::core::Error| Error::Generic(format!("Unable to read the current user: {}", e));
+    let mut token = HANDLE::default();
+    unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) }.map_err(to_error)?;
+    // the first call fails, and returns the size of the buffer which is needed
+    let mut size = 0u32;
+    let _ = unsafe { GetTokenInformation(token, TokenUser, None, 0, &mut size) };
+    // a u64 buffer, so the TOKEN_USER structure at the start of it is aligned
+    let mut buffer = vec![0u64; (size as usize).div_ceil(8)];
+    let result = unsafe { GetTokenInformation(token, TokenUser, Some(buffer.as_mut_ptr().cast()), size, &mut size) };
+    unsafe { CloseHandle(token).ok() };
+    result.map_err(to_error)?;
+
+    let user = unsafe { &*(buffer.as_ptr() as *const TOKEN_USER) };
+    let mut sid = PWSTR::null();
+    unsafe { ConvertSidToStringSidW(user.User.Sid, &mut sid) }.map_err(to_error)?;
+    let sid_string = unsafe { sid.to_string() };
+    let _ = unsafe { LocalFree(HLOCAL(sid.0.cast())) };
+    sid_string.map_err(|e| Error::Generic(format!("Unable to read the current user: {}", e)))
+}
+
+/// Returns the IPC channel name used by the updater serving the specified app and host process.
+pub fn get_ipc_name(app_id: &str, host_pid: u32) -> String {
+    format!("velopack-ipc-{}-{}", app_id, host_pid)
+}
+
+#[test]
+fn test_ipc_messages_are_newline_delimited_json() {
+    let input = "{\"Command\":\"CheckForUpdates\"}\n\n{\"Command\":\"ApplyOnExit\",\"Silent\":true,\"Restart\":false,\"RestartArgs\":[\"a\"]}\n";
+    let mut conn = IpcConnection::from_parts(Box::new(std::io::Cursor::new(input.as_bytes().to_vec())), Box::new(std::io::sink()));
+    assert_eq!(conn.receive::<IpcCommand>().unwrap(), Some(IpcCommand::CheckForUpdates));
+    assert_eq!(
+        conn.receive::<IpcCommand>().unwrap(),
+        Some(IpcCommand::ApplyOnExit { Silent: true, Restart: false, RestartArgs: vec!["a".to_string()] })
+    );
+    assert_eq!(conn.receive::<IpcCommand>().unwrap(), None);
+}
+
+#[test]
+fn test_ipc_progress_details_are_optional() {
+    let input = "{\"Event\":\"Progress\",\"Percent\":42}\n";
+    let mut conn = IpcConnection::from_parts(Box::new(std::io::Cursor::new(input.as_bytes().to_vec())), Box::new(std::io::sink()));
+    match conn.receive::<IpcEvent>().unwrap() {
+        Some(IpcEvent::Progress { Percent: 42, Details: None }) => {}
+        other => panic!("unexpected event: {:?}", other),
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_ipc_round_trip_over_unix_socket() {
+    let name = get_ipc_name("velopack-ipc-test", std::process::id());
+    let server = IpcServer::bind(&name).unwrap();
+    let client = std::thread::spawn(move || {
+        let mut conn = IpcConnection::connect(&name).unwrap();
+        conn.send(&IpcCommand::CheckForUpdates).unwrap();
+        conn.receive::<IpcEvent>().unwrap()
+    });
+
+    let mut conn = server.accept().unwrap();
+    assert_eq!(conn.receive::<IpcCommand>().unwrap(), Some(IpcCommand::CheckForUpdates));
+    conn.sender().send(&IpcEvent::DownloadCompleted).unwrap();
+    assert!(matches!(client.join().unwrap(), Some(IpcEvent::DownloadCompleted)));
+
+    let path = server.path.clone();
+    drop(server);
+    assert!(!path.exists());
+
+    // the socket is created in a directory which only the current user can access
+    use std::os::unix::fs::PermissionsExt;
+    assert_eq!(std::fs::metadata(path.parent().unwrap()).unwrap().permissions().mode() & 0o777, 0o700);
+
+    // other tests use the real runtime directory at the same time, so a directory which is no longer private is tested
+    // in a base directory of its own
+    let base = crate::testing::TempDir::new("velopack_ipc_runtime").unwrap();
+    let dir = get_user_runtime_dir_in(base.path()).unwrap();
+    assert_eq!(std::fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+    std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+    assert!(get_user_runtime_dir_in(base.path()).is_err());
+}
@@ -17,7 +17,13 @@
 //! - **update binary**: Bundled with your application by vpk, handles
 //!
 //! ## Optional Rust Features
-//! - `async`: Enables async support using async-std.
+//! - `async`: Enables async versions of the UpdateManager methods (eg. `check_for_updates_async`, and
+//!   `download_updates_stream` which reports progress as a `futures` stream). Each operation runs on a thread of its
+//!   own and the returned futures do not depend on a runtime, so they can be awaited from tokio, async-std, or any
+//!   other executor. Progress is sent on `futures::channel::mpsc` channels.
+//! - `http2`: Downloads with an HTTP client which negotiates HTTP/2 with servers that support it (eg. GitHub), so the
+//!   release feed and every package are multiplexed over a single connection. This adds a dependency on tokio, which the
+//!   client runs on its own background thread. Without it, downloads use HTTP/1.1 with keep-alive connections.
 //!
 //! ## Quick Start
 //! 1. Add Velopack to your `Cargo.toml`:
@@ -98,9 +104,62 @@ pub mod locator;
 /// Sources contains abstractions for custom update sources (eg. url, local file, github releases, etc).
 pub mod sources;
 
+/// Helpers for enforcing a single running instance of an application.
+pub mod instance;
+
+/// Local IPC channel used by a running application to control the updater.
+pub mod ipc;
+
+/// Detailed progress reporting (phase, bytes, speed, and ETA) for long running operations.
+pub mod progress;
+
+/// Semantic version comparison, following SemVer 2.0 precedence rules.
+pub mod version;
+
+/// Optional components of an app (eg. plugins or language packs) which are installed separately from the app itself.
+pub mod components;
+
+/// Policies which control how many old packages are kept in the packages directory.
+pub mod retention;
+
+/// A content-addressed cache of downloaded packages which can be shared by several apps on the same machine.
+pub mod cache;
+
+/// Hooks for observing update checks, downloads, and applies, so apps can feed their own analytics.
+pub mod observer;
+
+/// Detection of the machine's CPU architecture, and selection of the best matching release for it.
+pub mod arch;
+
+/// Registering the app to start automatically when the user logs in.
+pub mod login_item;
+
 /// Functions to patch files and reconstruct Velopack delta packages.
 pub mod delta;
 
+/// Content-defined chunking of packages, so that only the parts of a package which are missing locally are downloaded.
+pub mod chunks;
+
+/// Creating and updating the release index (releases.{channel}.json and RELEASES) of a directory of packages.
+pub mod releases;
+
+/// Escaping text for the XML documents and desktop entries written by Velopack.
+pub mod escape;
+
+/// Verifying and safely replacing the updater binary (Update.exe) with a newer one shipped in a package.
+pub mod updater;
+
+/// Starting background processes (eg. Update.exe) through a launcher which can be replaced in tests.
+pub mod process;
+
+/// Reading and writing the app's state files and the registry through abstractions which can be replaced in tests.
+pub mod system;
+
+/// In-memory fakes (an installed app, update source, downloader, process launcher, file system and registry) for testing
+/// an app's update logic. Enable the `testing` feature to use them outside of this crate.
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
 pub use app::*;
 pub use manager::*;
 
@@ -113,6 +172,9 @@ pub enum NetworkError
 {
     #[error("Http error: {0}")]
     Http(#[from] ureq::Error),
+    #[cfg(feature = "http2")]
+    #[error("Http error: {0}")]
+    HttpClient(#[from] reqwest::Error),
     #[error("Tls error: {0}")]
     Tls(#[from] native_tls::Error),
     #[error("Url error: {0}")]
@@ -121,6 +183,7 @@ pub enum NetworkError
 
 #[derive(thiserror::Error, Debug)]
 #[allow(missing_docs)]
+#[non_exhaustive]
 pub enum Error
 {
     #[error("File does not exist: {0}")]
@@ -139,12 +202,32 @@ pub enum Error
     MissingNuspec,
     #[error("This application is missing a required property in its package manifest: {0}")]
     MissingNuspecProperty(String),
+    #[error("The package manifest (.nuspec) is corrupt or invalid: {0}")]
+    InvalidNuspec(String),
     #[error("This application is missing an Update.exe/UpdateNix/UpdateMac binary.")]
     MissingUpdateExe,
     #[error("This application is not properly installed: {0}")]
     NotInstalled(String),
     #[error("Generic error: {0}")]
     Generic(String),
+    #[error("Switching channels would downgrade the app, which must be explicitly confirmed: {0}")]
+    DowngradeNotConfirmed(String),
+    #[error("The operation was cancelled.")]
+    Cancelled,
+    #[error("Another update operation ({0}) is already in progress.")]
+    OperationInProgress(UpdateOperation),
+    #[error("Another installer or updater for this application is running ({0}), quit that process and try again.")]
+    Locked(String),
+    #[error("The downloaded file is corrupt or has been tampered with: {0}")]
+    HashMismatch(String),
+    #[error("The release feed could not be parsed: {0}")]
+    FeedParse(String),
+    #[error("This operation is not supported: {0}")]
+    Unsupported(String),
+    #[error("The app is translocated ({0}) and can not be updated until it is moved to the Applications folder.")]
+    AppTranslocated(String),
+    #[error("Updates for this app are managed by {0}, so it can not update itself.")]
+    UpdatesManagedExternally(String),
 }
 
 impl From<url::ParseError> for Error {
@@ -159,6 +242,13 @@ impl From<ureq::Error> for Error {
     }
 }
 
+#[cfg(feature = "http2")]
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Network(Box::new(NetworkError::HttpClient(err)))
+    }
+}
+
 impl From<native_tls::Error> for Error {
     fn from(err: native_tls::Error) -> Self {
         Error::Network(Box::new(NetworkError::Tls(err)))
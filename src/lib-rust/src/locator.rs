@@ -1,8 +1,16 @@
-use std::path::PathBuf;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
 use semver::Version;
 use crate::{
     bundle::{self, Manifest},
-    util, Error,
+    constants::{
+        CACHED_FEED_FILE, CHUNKS_UNAVAILABLE_FILE, LAST_UPDATE_CHECK_FILE, PACKAGES_DIR_ENV, PORTABLE_MARKER_FILE, RESTART_REQUIRED_MARKER,
+        ROOT_DIR_LOCK_FILE, SKIPPED_VERSION_MARKER, USER_FIRSTRUN_MARKER,
+    },
+    system::{DefaultFileSystem, DefaultRegistry, FileSystem, Registry},
+    util, Error, VelopackAssetFeed,
 };
 
 /// Returns the default channel name for the current OS.
@@ -15,7 +23,18 @@ pub fn default_channel_name() -> String {
     return "osx".to_owned();
 }
 
-/// Default log location for Velopack on the current OS.
+/// Returns the home directory of the current user, from $HOME or /etc/passwd on Linux and macOS.
+pub fn get_home_dir() -> Result<PathBuf, Error> {
+    #[allow(deprecated)]
+    std::env::home_dir().ok_or_else(home_dir_not_found)
+}
+
+fn home_dir_not_found() -> Error {
+    Error::Generic("Could not locate user home directory via $HOME or /etc/passwd".to_owned())
+}
+
+/// Default log location for Velopack on the current OS. On Linux and macOS the log is kept next to the app if it is in
+/// portable mode, which is only checked the first time this is called.
 #[allow(unused_variables)]
 pub fn default_log_location(context: LocationContext) -> PathBuf {
     #[cfg(target_os = "windows")]
@@ -31,14 +50,22 @@ pub fn default_log_location(context: LocationContext) -> PathBuf {
         my_exe.pop();
         return my_exe.join("Velopack.log");
     }
+    #[cfg(not(target_os = "windows"))]
+    {
+        // locating the app reads its manifest from disk, and whether it is portable can not change while it is running
+        static PORTABLE_DIR: std::sync::OnceLock<Option<PathBuf>> = std::sync::OnceLock::new();
+        let portable_dir = PORTABLE_DIR.get_or_init(|| auto_locate_app_manifest(context).ok().and_then(|l| l.get_portable_dir()));
+        if let Some(dir) = portable_dir {
+            return dir.join("Velopack.log");
+        }
+    }
     #[cfg(target_os = "linux")]
     {
         return std::path::Path::new("/tmp/velopack.log").to_path_buf();
     }
     #[cfg(target_os = "macos")]
     {
-        #[allow(deprecated)]
-        let mut user_home = std::env::home_dir().expect("Could not locate user home directory via $HOME or /etc/passwd");
+        let mut user_home = get_home_dir().unwrap_or_else(|_| std::env::temp_dir());
         user_home.push("Library");
         user_home.push("Logs");
         user_home.push("velopack.log");
@@ -115,12 +142,76 @@ impl VelopackLocatorConfig {
 pub struct VelopackLocator {
     paths: VelopackLocatorConfig,
     manifest: Manifest,
+    fs: Box<dyn FileSystem>,
+    registry: Box<dyn Registry>,
 }
 
 impl VelopackLocator {
     /// Creates a new VelopackLocator from the given paths and manifest.
     pub fn new(paths: VelopackLocatorConfig, manifest: Manifest) -> Self {
-        Self { paths, manifest }
+        Self { paths, manifest, fs: Box::new(DefaultFileSystem::default()), registry: Box::new(DefaultRegistry::default()) }
+    }
+
+    /// Sets the file system which the state of the app (eg. the skipped version, or the first-run marker) is read from
+    /// and written to, so that it can be kept in memory in tests. See [`crate::testing::MemoryFileSystem`].
+    pub fn set_file_system<F: FileSystem + 'static>(&mut self, fs: F) {
+        self.fs = Box::new(fs);
+    }
+
+    /// Returns the file system which the state of the app is read from and written to.
+    pub fn get_file_system(&self) -> &dyn FileSystem {
+        self.fs.as_ref()
+    }
+
+    /// Sets the registry which the app's login item is registered in on Windows, so that it can be kept in memory in
+    /// tests. See [`crate::testing::MemoryRegistry`].
+    pub fn set_registry<R: Registry + 'static>(&mut self, registry: R) {
+        self.registry = Box::new(registry);
+    }
+
+    /// Returns the registry which the app's login item is registered in on Windows.
+    pub fn get_registry(&self) -> &dyn Registry {
+        self.registry.as_ref()
+    }
+
+    /// Returns the directory which an update is extracted to by UpdateManager::stage_updates, so that it can
+    /// replace the current version quickly the next time the app is started.
+    pub fn get_staged_dir(&self) -> PathBuf {
+        self.paths.RootAppDir.join("staged")
+    }
+
+    /// Returns the directory which optional components are installed to. On Windows this is a folder in the install root,
+    /// on other platforms the app bundle is replaced wholesale during updates, so it is a folder in the user state directory
+    /// of the app (or next to a portable app). The packages directory may be a cache shared by several apps or users, so
+    /// components are never kept there.
+    pub fn get_components_dir(&self) -> PathBuf {
+        #[cfg(target_os = "windows")]
+        return self.paths.RootAppDir.join("components");
+        #[cfg(not(target_os = "windows"))]
+        {
+            let dir = self.get_portable_dir().or_else(|| self.get_user_state_dir()).unwrap_or_else(|| self.paths.PackagesDir.clone());
+            dir.join("components")
+        }
+    }
+
+    /// Returns the manifest of the staged update, if there is one and it is newer than the current version.
+    pub fn get_staged_manifest(&self) -> Option<Manifest> {
+        let manifest = read_current_manifest(&self.get_staged_dir().join("sq.version")).ok()?;
+        if crate::version::is_newer(&manifest.version, &self.manifest.version) {
+            Some(manifest)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the path to the advisory lock file which is held while an install, update or uninstall is in progress. On
+    /// Windows this is in the install root, on other platforms the app bundle or AppImage is replaced wholesale during
+    /// updates (and an AppImage is mounted read-only), so it is in the packages directory instead.
+    pub fn get_lock_file_path(&self) -> PathBuf {
+        #[cfg(target_os = "windows")]
+        return self.paths.RootAppDir.join(ROOT_DIR_LOCK_FILE);
+        #[cfg(not(target_os = "windows"))]
+        return self.paths.PackagesDir.join(ROOT_DIR_LOCK_FILE);
     }
 
     /// Returns the path to the current app's packages directory.
@@ -137,7 +228,7 @@ impl VelopackLocator {
     pub fn get_ideal_local_nupkg_path(&self, id: Option<&str>, version: Option<Version>) -> PathBuf {
         let id = id.unwrap_or(&self.manifest.id);
         let version = version.unwrap_or(self.manifest.version.clone());
-        self.paths.RootAppDir.join("packages").join(format!("{}-{}-full.nupkg", id, version))
+        self.paths.PackagesDir.join(format!("{}-{}-full.nupkg", id, version))
     }
 
     /// Returns the path to the ideal local nupkg path as a string.
@@ -227,14 +318,27 @@ impl VelopackLocator {
         self.manifest.channel.clone()
     }
 
+    /// Returns the paths this locator was created with.
+    pub fn get_paths(&self) -> VelopackLocatorConfig {
+        self.paths.clone()
+    }
+
     /// Returns the current app's Id.
     pub fn get_manifest_id(&self) -> String {
         self.manifest.id.clone()
     }
 
-    /// Returns the current app's friendly / display name.
+    /// Returns the id the current app is installed as, which is used for its directories, mutexes, shortcuts and
+    /// registry entries. This is the app's Id, suffixed with the channel (eg. "MyApp-beta") if channels are installed
+    /// side by side.
+    pub fn get_install_id(&self) -> String {
+        self.manifest.get_install_id()
+    }
+
+    /// Returns the current app's friendly / display name. If channels are installed side by side, this includes the
+    /// channel (eg. "My App (beta)").
     pub fn get_manifest_title(&self) -> String {
-        self.manifest.title.clone()
+        self.manifest.get_install_title()
     }
 
     /// Returns the current app authors / publishers string.
@@ -258,16 +362,50 @@ impl VelopackLocator {
         if self.manifest.shortcut_amuid.is_empty() {
             return None;
         }
+        if self.manifest.is_side_by_side() {
+            return Some(format!("{}.{}", self.manifest.shortcut_amuid, self.manifest.channel));
+        }
         Some(self.manifest.shortcut_amuid.clone())
     }
 
+    /// Returns the web page which should be opened after the app has been uninstalled, if any.
+    pub fn get_manifest_uninstall_survey_url(&self) -> Option<String> {
+        if self.manifest.uninstall_survey_url.is_empty() {
+            return None;
+        }
+        Some(self.manifest.uninstall_survey_url.clone())
+    }
+
     /// Returns a copy of the current VelopackLocator with the manifest field set to the given manifest.
     pub fn clone_self_with_new_manifest(&self, manifest: &Manifest) -> VelopackLocator
     {
         VelopackLocator {
             paths: self.paths.clone(),
             manifest: manifest.clone(),
+            fs: self.fs.clone(),
+            registry: self.registry.clone(),
+        }
+    }
+
+    /// Returns a copy of the current VelopackLocator with the packages directory redirected, if a custom location has been
+    /// configured. The location is taken from `explicit` (usually UpdateOptions::PackagesDir), then the VELOPACK_PACKAGES_DIR
+    /// environment variable, and then the packagesDir property of the app manifest. Temporary files are redirected too,
+    /// since they are always stored in the packages directory. Portable apps always keep their packages next to the app.
+    pub fn clone_self_with_packages_dir_override(&self, explicit: Option<&str>) -> VelopackLocator
+    {
+        self.clone_self_with_packages_dir_override_in(self.get_portable_dir().as_deref(), explicit)
+    }
+
+    fn clone_self_with_packages_dir_override_in(&self, portable_dir: Option<&Path>, explicit: Option<&str>) -> VelopackLocator {
+        let mut locator = self.clone();
+        if portable_dir.is_some() {
+            return locator;
+        }
+        if let Some(dir) = get_packages_dir_override(&self.paths.RootAppDir, &self.manifest, explicit) {
+            info!("Packages directory has been redirected to: '{}'", dir.to_string_lossy());
+            locator.paths.PackagesDir = dir;
         }
+        locator
     }
 
     /// Returns whether the app is portable or installed.
@@ -275,6 +413,180 @@ impl VelopackLocator {
         self.paths.IsPortable
     }
 
+    /// Returns the folder the app keeps its packages and logs in if it is in portable mode, or None if it is not. An app is
+    /// in portable mode if there is a .portable marker file next to it: in the app root on Windows, next to the AppImage on
+    /// Linux, or next to the .app bundle on macOS. Portable apps never create shortcuts, registry entries or login items,
+    /// so they can be run from a removable drive without leaving anything behind.
+    pub fn get_portable_dir(&self) -> Option<PathBuf> {
+        #[cfg(target_os = "windows")]
+        return self.paths.IsPortable.then(|| self.paths.RootAppDir.clone());
+        #[cfg(not(target_os = "windows"))]
+        return find_portable_dir(&self.paths.RootAppDir, get_appimage_path().as_deref());
+    }
+
+    /// Returns true if macOS has translocated the app, which happens when a quarantined app is launched from the
+    /// location it was downloaded or mounted to (eg. a DMG) instead of being moved to /Applications first. The
+    /// translocated copy is read-only and is discarded once the app exits, so it can not be updated.
+    pub fn get_is_translocated(&self) -> bool {
+        cfg!(target_os = "macos") && is_translocated_path(&self.paths.RootAppDir)
+    }
+
+    /// Returns the sandbox the app is running in, if any. Sandboxed apps are updated by their store (eg. Flathub or the
+    /// Snap Store), and their files are read-only, so they can not update themselves.
+    pub fn get_sandbox(&self) -> Option<Sandbox> {
+        if cfg!(target_os = "linux") {
+            detect_sandbox(|name| std::env::var_os(name).is_some(), Path::new("/.flatpak-info").exists())
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if a prerequisite installed for this app requires a system restart, and the system has not been restarted since.
+    pub fn get_is_restart_required(&self) -> bool {
+        let marker = self.paths.RootAppDir.join(RESTART_REQUIRED_MARKER);
+        let contents = match self.fs.read_to_string(&marker) {
+            Ok(c) => c,
+            Err(_) => return false,
+        };
+        // the marker contains the boot time of the system when it was written. if the boot time
+        // has changed since then, the system has been restarted and the marker is stale.
+        match (contents.trim().parse::<u64>(), util::get_system_boot_time()) {
+            (Ok(marker_boot), Some(current_boot)) => marker_boot.abs_diff(current_boot) < 60,
+            _ => true,
+        }
+    }
+
+    /// Records whether a prerequisite installed for this app requires a system restart.
+    pub fn set_restart_required(&self, required: bool) -> Result<(), Error> {
+        let marker = self.paths.RootAppDir.join(RESTART_REQUIRED_MARKER);
+        if required {
+            let boot_time = util::get_system_boot_time().map(|t| t.to_string()).unwrap_or_default();
+            self.fs.write(&marker, boot_time.as_bytes())?;
+        } else {
+            self.fs.remove_file(&marker)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the version which the user has chosen to skip, if any.
+    pub fn get_skipped_version(&self) -> Option<Version> {
+        let marker = self.paths.RootAppDir.join(SKIPPED_VERSION_MARKER);
+        let contents = self.fs.read_to_string(&marker).ok()?;
+        Version::parse(contents.trim()).ok()
+    }
+
+    /// Records a version which the user has chosen to skip, or clears it if None.
+    pub fn set_skipped_version(&self, version: Option<&Version>) -> Result<(), Error> {
+        let marker = self.paths.RootAppDir.join(SKIPPED_VERSION_MARKER);
+        if let Some(version) = version {
+            self.fs.write(&marker, version.to_string().as_bytes())?;
+        } else {
+            self.fs.remove_file(&marker)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the time of the last update check in seconds since the unix epoch, and the random jitter (in seconds)
+    /// which was chosen at that time to delay the next check.
+    pub fn get_last_update_check(&self) -> Option<(u64, u64)> {
+        let contents = self.fs.read_to_string(&self.paths.RootAppDir.join(LAST_UPDATE_CHECK_FILE)).ok()?;
+        let mut parts = contents.split_whitespace().map(|p| p.parse::<u64>());
+        match (parts.next(), parts.next()) {
+            (Some(Ok(time)), Some(Ok(jitter))) => Some((time, jitter)),
+            (Some(Ok(time)), None) => Some((time, 0)),
+            _ => None,
+        }
+    }
+
+    /// Records the time of an update check in seconds since the unix epoch, and the jitter to apply to the next check.
+    pub fn set_last_update_check(&self, time: u64, jitter: u64) -> Result<(), Error> {
+        self.fs.write(&self.paths.RootAppDir.join(LAST_UPDATE_CHECK_FILE), format!("{} {}", time, jitter).as_bytes())?;
+        Ok(())
+    }
+
+    /// Returns the release feed which was cached by the last successful update check, if it was for the specified channel.
+    pub fn get_cached_release_feed(&self, channel: &str) -> Option<VelopackAssetFeed> {
+        let contents = self.fs.read_to_string(&self.paths.PackagesDir.join(CACHED_FEED_FILE)).ok()?;
+        let mut cached: serde_json::Value = serde_json::from_str(&contents).ok()?;
+        if cached["Channel"].as_str() != Some(channel) {
+            return None;
+        }
+        serde_json::from_value(cached["Feed"].take()).ok()
+    }
+
+    /// Caches the release feed of a successful update check, so that it can be used when the update source can not be reached.
+    /// The feed is kept in the packages directory, because the app root may not be writable (eg. a per-machine install).
+    pub fn set_cached_release_feed(&self, channel: &str, feed: &VelopackAssetFeed) -> Result<(), Error> {
+        let json = serde_json::to_string(&serde_json::json!({ "Channel": channel, "Feed": feed }))?;
+        self.fs.create_dir_all(&self.paths.PackagesDir)?;
+        self.fs.write(&self.paths.PackagesDir.join(CACHED_FEED_FILE), json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Returns the time (in seconds since the unix epoch) at which the release feed of the specified channel was found not
+    /// to publish chunk indexes, if it has been.
+    pub fn get_chunks_unavailable_since(&self, channel: &str) -> Option<u64> {
+        let contents = self.fs.read_to_string(&self.paths.PackagesDir.join(CHUNKS_UNAVAILABLE_FILE)).ok()?;
+        let marker: serde_json::Value = serde_json::from_str(&contents).ok()?;
+        if marker["Channel"].as_str() != Some(channel) {
+            return None;
+        }
+        marker["Time"].as_u64()
+    }
+
+    /// Records the time at which the release feed of a channel was found not to publish chunk indexes, or clears it if None.
+    pub fn set_chunks_unavailable_since(&self, channel: &str, time: Option<u64>) -> Result<(), Error> {
+        let marker = self.paths.PackagesDir.join(CHUNKS_UNAVAILABLE_FILE);
+        if let Some(time) = time {
+            let json = serde_json::to_string(&serde_json::json!({ "Channel": channel, "Time": time }))?;
+            self.fs.create_dir_all(&self.paths.PackagesDir)?;
+            self.fs.write(&marker, json.as_bytes())?;
+        } else {
+            self.fs.remove_file(&marker)?;
+        }
+        Ok(())
+    }
+
+    /// Returns true if the app is installed somewhere outside of the current user's profile (eg. Program Files),
+    /// and may therefore be shared by several users on the same machine.
+    pub fn get_is_machine_wide(&self) -> bool {
+        if self.paths.IsPortable {
+            return false;
+        }
+        match get_home_dir() {
+            Ok(home) => !self.paths.RootAppDir.starts_with(home),
+            Err(_) => false,
+        }
+    }
+
+    /// Returns the directory used to store state for this app which is specific to the current user.
+    pub fn get_user_state_dir(&self) -> Option<PathBuf> {
+        util::get_user_data_dir().map(|d| d.join("velopack").join(self.get_install_id()))
+    }
+
+    /// Returns true if the current user has not yet launched this app. This is only tracked for
+    /// machine-wide installs, for per-user installs the installer triggers the first-run hook instead.
+    pub fn get_is_first_run_for_user(&self) -> bool {
+        self.get_is_machine_wide() && !self.get_first_run_completed_for_user()
+    }
+
+    /// Returns true if the first-run hook has already fired for the current user. The marker is kept in the user state
+    /// directory rather than next to the app binaries, so it survives updates which replace the current version.
+    pub fn get_first_run_completed_for_user(&self) -> bool {
+        match self.get_user_state_dir() {
+            Some(dir) => self.fs.exists(&dir.join(USER_FIRSTRUN_MARKER)),
+            None => false,
+        }
+    }
+
+    /// Records that the first-run hook has fired for the current user.
+    pub fn set_first_run_completed_for_user(&self) -> Result<(), Error> {
+        let dir = self.get_user_state_dir().ok_or_else(|| Error::Generic("Unable to locate user data directory.".to_owned()))?;
+        self.fs.create_dir_all(&dir)?;
+        self.fs.write(&dir.join(USER_FIRSTRUN_MARKER), self.get_manifest_version_full_string().as_bytes())?;
+        Ok(())
+    }
+
     fn path_as_string(path: &PathBuf) -> String {
         path.to_string_lossy().to_string()
     }
@@ -292,7 +604,7 @@ pub fn create_config_from_root_dir<P: AsRef<std::path::Path>>(root_dir: P) -> Ve
         PackagesDir: root_dir.join("packages"),
         ManifestPath: root_dir.join("current").join("sq.version"),
         CurrentBinaryDir: root_dir.join("current"),
-        IsPortable: root_dir.join(".portable").exists(),
+        IsPortable: root_dir.join(PORTABLE_MARKER_FILE).exists(),
     }
 }
 
@@ -306,7 +618,49 @@ fn config_to_locator(config: &VelopackLocatorConfig) -> Result<VelopackLocator,
     }
 
     let manifest = read_current_manifest(&config.ManifestPath)?;
-    Ok(VelopackLocator::new(config.clone(), manifest))
+    Ok(VelopackLocator::new(config.clone(), manifest).clone_self_with_packages_dir_override(None))
+}
+
+/// Relative paths are resolved against the app root directory, and packages are stored in a sub-directory named
+/// after the app id, so that several apps can share the same cache directory without deleting each other's packages.
+fn get_packages_dir_override(root_dir: &Path, manifest: &Manifest, explicit: Option<&str>) -> Option<PathBuf> {
+    let env = std::env::var(PACKAGES_DIR_ENV).ok();
+    let dir = [explicit, env.as_deref(), Some(manifest.packages_dir.as_str())]
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .find(|dir| !dir.is_empty())?;
+    Some(root_dir.join(dir).join(manifest.get_install_id()))
+}
+
+/// A sandboxed package format which delivers updates through its own store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sandbox {
+    /// The app is running inside a Flatpak.
+    Flatpak,
+    /// The app is running inside a Snap.
+    Snap,
+}
+
+impl fmt::Display for Sandbox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Sandbox::Flatpak => "Flatpak",
+            Sandbox::Snap => "Snap",
+        };
+        f.write_str(name)
+    }
+}
+
+fn detect_sandbox<F: Fn(&str) -> bool>(has_env: F, has_flatpak_info: bool) -> Option<Sandbox> {
+    // flatpak always mounts /.flatpak-info into the sandbox, and snapd sets SNAP and SNAP_NAME for every confined app
+    if has_flatpak_info || has_env("FLATPAK_ID") {
+        Some(Sandbox::Flatpak)
+    } else if has_env("SNAP") && has_env("SNAP_NAME") {
+        Some(Sandbox::Snap)
+    } else {
+        None
+    }
 }
 
 /// LocationContext is an enumeration of possible contexts for locating the current app manifest.
@@ -410,7 +764,15 @@ pub fn auto_locate_app_manifest(context: LocationContext) -> Result<VelopackLoca
     }
 
     let app = read_current_manifest(&metadata_path)?;
-    let packages_dir = PathBuf::from("/var/tmp/velopack").join(&app.id).join("packages");
+    let packages_dir = match find_portable_dir(&root_app_dir, get_appimage_path().as_deref()) {
+        Some(dir) => dir.join("packages"),
+        // packages are downloaded by the user running the app, so they are kept in a directory only that user can write to
+        None => util::get_user_cache_dir()
+            .ok_or_else(home_dir_not_found)?
+            .join("velopack")
+            .join(app.get_install_id())
+            .join("packages"),
+    };
 
     let config = VelopackLocatorConfig {
         RootAppDir: root_app_dir,
@@ -453,13 +815,18 @@ pub fn auto_locate_app_manifest(context: LocationContext) -> Result<VelopackLoca
 
     let app = read_current_manifest(&metadata_path)?;
 
-    #[allow(deprecated)]
-    let mut packages_dir = std::env::home_dir().expect("Could not locate user home directory via $HOME or /etc/passwd");
-    packages_dir.push("Library");
-    packages_dir.push("Caches");
-    packages_dir.push("velopack");
-    packages_dir.push(&app.id);
-    packages_dir.push("packages");
+    let packages_dir = match find_portable_dir(&root_app_dir, get_appimage_path().as_deref()) {
+        Some(dir) => dir.join("packages"),
+        None => {
+            let mut packages_dir = get_home_dir()?;
+            packages_dir.push("Library");
+            packages_dir.push("Caches");
+            packages_dir.push("velopack");
+            packages_dir.push(app.get_install_id());
+            packages_dir.push("packages");
+            packages_dir
+        }
+    };
 
     let config = VelopackLocatorConfig {
         RootAppDir: root_app_dir,
@@ -473,10 +840,28 @@ pub fn auto_locate_app_manifest(context: LocationContext) -> Result<VelopackLoca
     config_to_locator(&config)
 }
 
+/// Returns the folder containing the .portable marker file if the app is in portable mode. The app root is read-only on
+/// Linux (a mounted AppImage) and macOS (a signed bundle), so the marker sits next to the AppImage or .app bundle.
+#[cfg(not(target_os = "windows"))]
+#[allow(unused_variables)]
+fn find_portable_dir(root_app_dir: &Path, appimage_path: Option<&Path>) -> Option<PathBuf> {
+    #[cfg(target_os = "linux")]
+    let dir = appimage_path.and_then(Path::parent).map(Path::to_path_buf);
+    #[cfg(not(target_os = "linux"))]
+    let dir = root_app_dir.parent().map(Path::to_path_buf);
+    dir.filter(|dir| dir.join(PORTABLE_MARKER_FILE).is_file())
+}
+
+/// Returns the path of the AppImage the app is running from, which the AppImage runtime passes in the environment.
+#[cfg(not(target_os = "windows"))]
+fn get_appimage_path() -> Option<PathBuf> {
+    std::env::var_os("APPIMAGE").map(PathBuf::from)
+}
+
 fn read_current_manifest(nuspec_path: &PathBuf) -> Result<Manifest, Error> {
     if nuspec_path.exists() {
-        if let Ok(nuspec) = util::retry_io(|| std::fs::read_to_string(&nuspec_path)) {
-            return Ok(bundle::read_manifest_from_string(&nuspec)?);
+        if let Ok(nuspec) = util::retry_io(|| std::fs::File::open(nuspec_path)) {
+            return bundle::read_manifest_from_reader(std::io::BufReader::new(nuspec));
         }
     }
     Err(Error::MissingNuspec)
@@ -495,7 +880,7 @@ pub fn find_latest_full_package(packages_dir: &PathBuf) -> Option<(PathBuf, Mani
                 trace!("Checking package: '{}'", path.to_string_lossy());
                 if let Ok(mut bun) = bundle::load_bundle_from_file(&path) {
                     if let Ok(mani) = bun.read_manifest() {
-                        if package.is_none() || mani.version > package.clone().unwrap().1.version {
+                        if package.as_ref().map_or(true, |(_, p)| crate::version::is_newer(&mani.version, &p.version)) {
                             info!("Found {}: '{}'", mani.version, path.to_string_lossy());
                             package = Some((path, mani));
                         }
@@ -506,3 +891,101 @@ pub fn find_latest_full_package(packages_dir: &PathBuf) -> Option<(PathBuf, Mani
     }
     package
 }
+
+/// Returns true if the path is inside a macOS App Translocation mount point.
+fn is_translocated_path(path: &Path) -> bool {
+    path.components().any(|c| c.as_os_str() == "AppTranslocation")
+}
+
+#[test]
+fn test_sandbox_is_detected() {
+    assert_eq!(detect_sandbox(|_| false, false), None);
+    assert_eq!(detect_sandbox(|_| false, true), Some(Sandbox::Flatpak));
+    assert_eq!(detect_sandbox(|name| name == "FLATPAK_ID", false), Some(Sandbox::Flatpak));
+    assert_eq!(detect_sandbox(|name| name == "SNAP" || name == "SNAP_NAME", false), Some(Sandbox::Snap));
+    // SNAP on its own is too generic a name to rely on
+    assert_eq!(detect_sandbox(|name| name == "SNAP", false), None);
+}
+
+#[test]
+fn test_translocated_paths_are_detected() {
+    assert!(is_translocated_path(Path::new("/private/var/folders/x7/abc123/T/AppTranslocation/5A1B-77C2/d/MyApp.app")));
+    assert!(!is_translocated_path(Path::new("/Applications/MyApp.app")));
+    assert!(!is_translocated_path(Path::new("/Users/me/AppTranslocationTest/MyApp.app")));
+}
+
+#[test]
+fn test_packages_dir_can_be_redirected() {
+    let root = std::env::temp_dir().join("velopack_locator_root");
+    let config = VelopackLocatorConfig { RootAppDir: root.clone(), PackagesDir: root.join("packages"), ..Default::default() };
+    let mut manifest = Manifest { id: "MyApp".to_owned(), ..Default::default() };
+    let shared = std::env::temp_dir().join("velopack_shared_cache");
+    let shared_str = shared.to_string_lossy().to_string();
+
+    // without any override the packages directory is left alone
+    if std::env::var(PACKAGES_DIR_ENV).is_err() {
+        let locator = VelopackLocator::new(config.clone(), manifest.clone()).clone_self_with_packages_dir_override(None);
+        assert_eq!(locator.get_packages_dir(), root.join("packages"));
+        assert_eq!(locator.get_temp_dir_root(), root.join("packages").join("VelopackTemp"));
+    }
+
+    // an explicit directory wins over the manifest, and relative paths are resolved against the root directory
+    manifest.packages_dir = "cache".to_owned();
+    let locator = VelopackLocator::new(config.clone(), manifest.clone());
+    let redirected = locator.clone_self_with_packages_dir_override(Some(&shared_str));
+    assert_eq!(redirected.get_packages_dir(), shared.join("MyApp"));
+    assert_eq!(redirected.get_temp_dir_root(), shared.join("MyApp").join("VelopackTemp"));
+    assert_eq!(redirected.get_ideal_local_nupkg_path(None, None), shared.join("MyApp").join("MyApp-0.0.0-full.nupkg"));
+    if std::env::var(PACKAGES_DIR_ENV).is_err() {
+        let redirected = locator.clone_self_with_packages_dir_override(Some(" "));
+        assert_eq!(redirected.get_packages_dir(), root.join("cache").join("MyApp"));
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_portable_apps_keep_packages_next_to_the_app() {
+    let dir = crate::testing::TempDir::new("velopack_portable").unwrap();
+    let appimage = dir.join("MyApp.AppImage");
+    std::fs::write(&appimage, b"").unwrap();
+
+    // the AppImage is passed in rather than set in the environment, which other tests running at the same time read
+    let root = PathBuf::from("/tmp/.mount_MyApp");
+    let config = VelopackLocatorConfig { RootAppDir: root.clone(), PackagesDir: root.join("packages"), ..Default::default() };
+    let locator = VelopackLocator::new(config, Manifest { id: "MyApp".to_owned(), ..Default::default() });
+    assert_eq!(find_portable_dir(&root, Some(&appimage)), None);
+    assert_eq!(find_portable_dir(&root, None), None);
+
+    std::fs::write(dir.join(PORTABLE_MARKER_FILE), b"").unwrap();
+    let portable_dir = find_portable_dir(&root, Some(&appimage));
+    assert_eq!(portable_dir, Some(dir.to_path_buf()));
+    // a configured packages directory is ignored, because it would be outside of the portable folder
+    let redirected = locator.clone_self_with_packages_dir_override_in(portable_dir.as_deref(), Some("/var/cache/velopack"));
+    assert_eq!(redirected.get_packages_dir(), root.join("packages"));
+    let redirected = locator.clone_self_with_packages_dir_override_in(None, Some("/var/cache/velopack"));
+    assert_eq!(redirected.get_packages_dir(), PathBuf::from("/var/cache/velopack/MyApp"));
+
+}
+
+#[test]
+fn test_first_run_marker_outlives_updates() {
+    let root = std::env::temp_dir().join("velopack_firstrun_root");
+    let config = VelopackLocatorConfig { RootAppDir: root.clone(), PackagesDir: root.join("packages"), ..Default::default() };
+    let manifest = Manifest { id: "MyApp".to_owned(), version: semver::Version::new(1, 0, 0), ..Default::default() };
+    let fs = crate::testing::MemoryFileSystem::new();
+
+    let mut locator = VelopackLocator::new(config.clone(), manifest.clone());
+    locator.set_file_system(fs.clone());
+    if locator.get_user_state_dir().is_none() {
+        return;
+    }
+    assert!(!locator.get_first_run_completed_for_user());
+    locator.set_first_run_completed_for_user().unwrap();
+    assert!(locator.get_first_run_completed_for_user());
+
+    // the marker is not stored with the app binaries, so a newer version of the app still finds it
+    let mut updated = VelopackLocator::new(config, Manifest { version: semver::Version::new(2, 0, 0), ..manifest });
+    updated.set_file_system(fs);
+    assert!(updated.get_first_run_completed_for_user());
+    assert!(!updated.get_is_first_run_for_user());
+}
@@ -0,0 +1,267 @@
+#[cfg(target_os = "macos")]
+use std::process::Command as Process;
+
+use crate::{locator::VelopackLocator, Error};
+
+#[cfg(target_os = "windows")]
+const RUN_KEY: &str = "HKCU\\Software\\Microsoft\\Windows\\CurrentVersion\\Run";
+
+/// Registers the app to start automatically when the current user logs in. On Windows this adds a value to the
+/// per-user Run key, on macOS the app bundle is registered with SMAppService (macOS 13+), falling back to a login
+/// item added through System Events on older versions, and on Linux an XDG autostart entry is written for the AppImage.
+/// If Update.exe is running in its sandbox, the Windows login item is recorded in the sandbox directory instead.
+pub fn register_login_item(locator: &VelopackLocator) -> Result<(), Error> {
+    if locator.get_portable_dir().is_some() {
+        return Err(Error::Unsupported("Portable apps can not be started at login.".to_owned()));
+    }
+    #[cfg(target_os = "windows")]
+    {
+        let command = format!("\"{}\"", locator.get_main_exe_path_as_string());
+        if let Some(path) = get_sandbox_login_item_path(locator) {
+            if let Some(dir) = path.parent() {
+                locator.get_file_system().create_dir_all(dir)?;
+            }
+            locator.get_file_system().write(&path, command.as_bytes())?;
+            info!("Registered '{}' to start at login in the sandbox ({:?}).", locator.get_install_id(), path);
+            return Ok(());
+        }
+        locator.get_registry().set_value(RUN_KEY, &locator.get_install_id(), &command)?;
+        info!("Registered '{}' to start at login.", locator.get_install_id());
+        Ok(())
+    }
+    #[cfg(target_os = "macos")]
+    {
+        match macos::set_main_app_registered(true) {
+            Some(true) => {
+                info!("Registered the app to start at login with SMAppService.");
+                Ok(())
+            }
+            Some(false) => Err(Error::Generic("SMAppService refused to register the app as a login item.".to_owned())),
+            None => {
+                let script = format!(
+                    "tell application \"System Events\" to make login item at end with properties {{path:\"{}\", hidden:false}}",
+                    escape_applescript(&locator.get_root_dir_as_string())
+                );
+                run_osascript(&script)?;
+                info!("Registered the app to start at login with System Events.");
+                Ok(())
+            }
+        }
+    }
+    #[cfg(target_os = "linux")]
+    {
+        let appimage_path = std::env::var_os("APPIMAGE").map(std::path::PathBuf::from).filter(|p| p.is_file()).ok_or_else(|| {
+            Error::Unsupported("The app is not running from an AppImage, so it can not be started at login.".to_owned())
+        })?;
+        let entry_path = linux::get_autostart_entry_path(locator)?;
+        let entry = linux::create_autostart_entry(&locator.get_manifest_title(), &appimage_path.to_string_lossy());
+        if let Some(dir) = entry_path.parent() {
+            locator.get_file_system().create_dir_all(dir)?;
+        }
+        locator.get_file_system().write(&entry_path, entry.as_bytes())?;
+        info!("Registered '{}' to start at login ({:?}).", locator.get_install_id(), entry_path);
+        Ok(())
+    }
+}
+
+/// Removes the login item added by [`register_login_item`], if there is one.
+pub fn unregister_login_item(locator: &VelopackLocator) -> Result<(), Error> {
+    #[cfg(target_os = "windows")]
+    {
+        if let Some(path) = get_sandbox_login_item_path(locator) {
+            return locator.get_file_system().remove_file(&path);
+        }
+        if is_login_item_registered(locator)? {
+            locator.get_registry().delete_value(RUN_KEY, &locator.get_install_id())?;
+            info!("Unregistered '{}' from starting at login.", locator.get_install_id());
+        }
+        Ok(())
+    }
+    #[cfg(target_os = "macos")]
+    {
+        match macos::set_main_app_registered(false) {
+            Some(_) => Ok(()),
+            None => {
+                if is_login_item_registered(locator)? {
+                    let script = format!(
+                        "tell application \"System Events\" to delete login item \"{}\"",
+                        escape_applescript(&get_bundle_name(locator))
+                    );
+                    run_osascript(&script)?;
+                }
+                Ok(())
+            }
+        }
+    }
+    #[cfg(target_os = "linux")]
+    {
+        let entry_path = linux::get_autostart_entry_path(locator)?;
+        if locator.get_file_system().exists(&entry_path) {
+            locator.get_file_system().remove_file(&entry_path)?;
+            info!("Unregistered '{}' from starting at login.", locator.get_install_id());
+        }
+        Ok(())
+    }
+}
+
+/// Returns true if the app is currently registered to start at login. On macOS 13+ this is also true if the user
+/// still needs to approve the login item in System Settings.
+pub fn is_login_item_registered(locator: &VelopackLocator) -> Result<bool, Error> {
+    #[cfg(target_os = "windows")]
+    {
+        if let Some(path) = get_sandbox_login_item_path(locator) {
+            return Ok(locator.get_file_system().exists(&path));
+        }
+        Ok(locator.get_registry().get_value(RUN_KEY, &locator.get_install_id())?.is_some())
+    }
+    #[cfg(target_os = "macos")]
+    {
+        match macos::is_main_app_registered() {
+            Some(registered) => Ok(registered),
+            None => {
+                let names = run_osascript("tell application \"System Events\" to get the name of every login item")?;
+                let bundle_name = get_bundle_name(locator);
+                Ok(names.split(", ").any(|name| name.trim() == bundle_name))
+            }
+        }
+    }
+    #[cfg(target_os = "linux")]
+    {
+        // an entry can be switched off without being removed (eg. by the desktop's startup settings) by setting Hidden
+        match locator.get_file_system().read_to_string(&linux::get_autostart_entry_path(locator)?) {
+            Ok(contents) => Ok(!linux::is_entry_hidden(&contents)),
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// When Update.exe runs in its sandbox, the login item is written to a file in the sandbox instead of the Run key.
+#[cfg(target_os = "windows")]
+fn get_sandbox_login_item_path(locator: &VelopackLocator) -> Option<std::path::PathBuf> {
+    let dir = std::env::var_os(crate::constants::SANDBOX_ENV).filter(|d| !d.is_empty())?;
+    Some(std::path::PathBuf::from(dir).join("login_items").join(locator.get_install_id()))
+}
+
+#[cfg(target_os = "macos")]
+fn get_bundle_name(locator: &VelopackLocator) -> String {
+    locator.get_root_dir().file_stem().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+#[cfg(target_os = "macos")]
+fn escape_applescript(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(target_os = "macos")]
+fn run_osascript(script: &str) -> Result<String, Error> {
+    let output = Process::new("/usr/bin/osascript").arg("-e").arg(script).output()?;
+    if !output.status.success() {
+        return Err(Error::Generic(format!("osascript failed: {}", String::from_utf8_lossy(&output.stderr).trim())));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// XDG autostart entries, which are started by the desktop when the user logs in.
+/// https://specifications.freedesktop.org/autostart-spec/latest/
+#[cfg(target_os = "linux")]
+mod linux {
+    use crate::{escape::escape_desktop_exec_arg, locator::{self, VelopackLocator}, Error};
+    use std::path::PathBuf;
+
+    pub fn get_autostart_entry_path(locator: &VelopackLocator) -> Result<PathBuf, Error> {
+        let config_dir = match std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from).filter(|p| p.is_absolute()) {
+            Some(dir) => dir,
+            None => locator::get_home_dir()?.join(".config"),
+        };
+        // this is the same file the Startup shortcut location writes for the current user, so both manage the same entry
+        Ok(config_dir.join("autostart").join(format!("{}.desktop", locator.get_install_id())))
+    }
+
+    pub fn create_autostart_entry(title: &str, appimage_path: &str) -> String {
+        format!(
+            "[Desktop Entry]\nType=Application\nName={}\nExec={}\nTryExec={}\nTerminal=false\nX-GNOME-Autostart-enabled=true\n",
+            title.replace('\n', " "),
+            escape_desktop_exec_arg(appimage_path),
+            appimage_path
+        )
+    }
+
+    pub fn is_entry_hidden(contents: &str) -> bool {
+        contents.lines().any(|l| l.trim().eq_ignore_ascii_case("Hidden=true") || l.trim() == "X-GNOME-Autostart-enabled=false")
+    }
+
+    #[test]
+    fn test_autostart_entry_quotes_appimage_path() {
+        let entry = create_autostart_entry("My App", "/home/me/Apps/My $App.AppImage");
+        assert!(entry.starts_with("[Desktop Entry]\n"));
+        assert!(entry.contains("\nName=My App\n"));
+        assert!(entry.contains("\nExec=\"/home/me/Apps/My \\\\$App.AppImage\"\n"));
+        assert!(entry.contains("\nTryExec=/home/me/Apps/My $App.AppImage\n"));
+        assert!(!is_entry_hidden(&entry));
+        assert!(is_entry_hidden(&entry.replace("X-GNOME-Autostart-enabled=true", "X-GNOME-Autostart-enabled=false")));
+        assert!(is_entry_hidden(&format!("{}Hidden=true\n", entry)));
+    }
+}
+
+/// Minimal bindings to SMAppService, called through the Objective-C runtime. Each function returns None if
+/// SMAppService is not available (macOS 12 and older), so the caller can fall back to System Events.
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::ffi::{c_char, c_void};
+
+    type Id = *mut c_void;
+    type Sel = *mut c_void;
+
+    // SMAppServiceStatus
+    const STATUS_ENABLED: isize = 1;
+    const STATUS_REQUIRES_APPROVAL: isize = 2;
+
+    #[link(name = "objc")]
+    extern "C" {
+        fn objc_getClass(name: *const c_char) -> Id;
+        fn sel_registerName(name: *const c_char) -> Sel;
+        fn objc_msgSend();
+    }
+
+    #[link(name = "ServiceManagement", kind = "framework")]
+    extern "C" {}
+
+    unsafe fn sel(name: &[u8]) -> Sel {
+        sel_registerName(name.as_ptr() as *const c_char)
+    }
+
+    fn main_app_service() -> Option<Id> {
+        unsafe {
+            let class = objc_getClass(b"SMAppService\0".as_ptr() as *const c_char);
+            if class.is_null() {
+                return None;
+            }
+            let send: unsafe extern "C" fn(Id, Sel) -> Id = std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+            let service = send(class, sel(b"mainAppService\0"));
+            if service.is_null() {
+                None
+            } else {
+                Some(service)
+            }
+        }
+    }
+
+    pub fn set_main_app_registered(registered: bool) -> Option<bool> {
+        let service = main_app_service()?;
+        let name: &[u8] = if registered { b"registerAndReturnError:\0" } else { b"unregisterAndReturnError:\0" };
+        unsafe {
+            let send: unsafe extern "C" fn(Id, Sel, *mut Id) -> i8 = std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+            Some(send(service, sel(name), std::ptr::null_mut()) != 0)
+        }
+    }
+
+    pub fn is_main_app_registered() -> Option<bool> {
+        let service = main_app_service()?;
+        unsafe {
+            let send: unsafe extern "C" fn(Id, Sel) -> isize = std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+            let status = send(service, sel(b"status\0"));
+            Some(status == STATUS_ENABLED || status == STATUS_REQUIRES_APPROVAL)
+        }
+    }
+}
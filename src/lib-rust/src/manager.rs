@@ -1,25 +1,74 @@
-#[cfg(target_os = "windows")]
-use std::os::windows::process::CommandExt;
 use std::{
-    fs,
-    process::{exit, Command as Process},
-    sync::mpsc::Sender,
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    process::exit,
+    sync::{mpsc::Sender, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
 };
 
 #[cfg(feature = "async")]
-use async_std::channel::Sender as AsyncSender;
+use futures_channel::mpsc::{UnboundedReceiver as AsyncReceiver, UnboundedSender as AsyncSender};
 #[cfg(feature = "async")]
-use async_std::task::JoinHandle;
+use std::future::Future;
+use rand::Rng;
 use semver::Version;
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    arch, bundle,
+    cache::SharedCache,
+    chunks,
+    components::{self, ComponentAsset, InstalledComponent},
+    constants::{CACHE_SERVER_ENV, PACKAGES_DIR_ENV, PACKAGE_SHA256_ENV},
+    download::{self, CancellationToken, RequestMetadata},
+    instance::FileLock,
+    ipc::{self, IpcConnection},
     locator::{self, VelopackLocatorConfig, LocationContext, VelopackLocator},
-    sources::UpdateSource,
+    observer::{ErrorClass, UpdateObserver},
+    process::{DefaultProcessLauncher, ProcessCommand, ProcessLauncher},
+    progress::{self, ProgressEvent, ProgressPhase},
+    retention::{self, RetentionPolicy},
+    sources::{CacheServerSource, UpdateSource},
+    system::FileSystem,
     Error,
-    util,
+    util, version,
 };
 
+/// How long to wait before asking a release feed for chunk indexes again, after finding that it does not publish them.
+const CHUNKS_UNAVAILABLE_RETRY_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Generates an `_async` variant of a blocking UpdateManager method, so there is only one implementation of each
+/// operation. The blocking method is run on a thread of its own, with any borrowed arguments cloned so that the
+/// returned future is 'static. Methods which report progress take `; progress: T` after their arguments (where T is
+/// the type of progress value), and accept an async Sender in place of the blocking one.
+macro_rules! async_variant {
+    ($(#[$attr:meta])* $async_name:ident => $sync_name:ident($($arg:ident: &$arg_ty:ty),*) -> $ret:ty) => {
+        $(#[$attr])*
+        #[cfg(feature = "async")]
+        pub fn $async_name(&self, $($arg: &$arg_ty),*) -> impl Future<Output = Result<$ret, Error>> + Send + 'static {
+            let self_clone = self.clone();
+            $(let $arg = $arg.clone();)*
+            spawn_blocking(move || self_clone.$sync_name($(&$arg),*))
+        }
+    };
+    ($(#[$attr:meta])* $async_name:ident => $sync_name:ident($($arg:ident: &$arg_ty:ty),*; progress: $progress_ty:ty) -> $ret:ty) => {
+        $(#[$attr])*
+        #[cfg(feature = "async")]
+        pub fn $async_name(
+            &self,
+            $($arg: &$arg_ty,)*
+            progress: Option<AsyncSender<$progress_ty>>,
+        ) -> impl Future<Output = Result<$ret, Error>> + Send + 'static {
+            let sync_progress = progress.map(bridge_progress_to_async);
+            let self_clone = self.clone();
+            $(let $arg = $arg.clone();)*
+            spawn_blocking(move || self_clone.$sync_name($(&$arg,)* sync_progress))
+        }
+    };
+}
+
 #[allow(non_snake_case)]
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 #[serde(default)]
@@ -27,13 +76,31 @@ use crate::{
 pub struct VelopackAssetFeed {
     /// The list of assets in the (probably remote) update feed.
     pub Assets: Vec<VelopackAsset>,
+    /// The optional components (eg. plugins or language packs) which can be installed alongside the app.
+    pub Components: Vec<ComponentAsset>,
 }
 
 impl VelopackAssetFeed {
+    /// Parses a release feed from its json representation, returning [`Error::FeedParse`] if it is not a valid feed.
+    pub fn from_json(json: &str) -> Result<VelopackAssetFeed, Error> {
+        serde_json::from_str(json).map_err(|e| Error::FeedParse(e.to_string()))
+    }
+
     /// Finds a release by name and returns a reference to the VelopackAsset in the feed, or None if not found.
     pub fn find(&self, release_name: &str) -> Option<&VelopackAsset> {
         self.Assets.iter().find(|x| x.FileName.eq_ignore_ascii_case(release_name))
     }
+
+    /// Removes any assets which belong to a different package, so that a single feed can host releases for several apps.
+    /// Assets which do not specify a PackageId are assumed to belong to every package, and are kept.
+    pub fn retain_package(&mut self, package_id: &str) {
+        let before = self.Assets.len();
+        self.Assets.retain(|x| x.PackageId.is_empty() || x.PackageId.eq_ignore_ascii_case(package_id));
+        if self.Assets.len() < before {
+            debug!("Ignoring {} asset(s) in the feed which belong to other packages.", before - self.Assets.len());
+        }
+        self.Components.retain(|x| x.Asset.PackageId.is_empty() || x.Asset.PackageId.eq_ignore_ascii_case(package_id));
+    }
 }
 
 #[allow(non_snake_case)]
@@ -60,6 +127,11 @@ pub struct VelopackAsset {
     pub NotesMarkdown: String,
     /// The release notes in HTML format, transformed from Markdown when packaging the release. This may be an empty string.
     pub NotesHtml: String,
+    /// The release has been marked as mandatory by the publisher, and should not be skipped or postponed.
+    pub IsMandatory: bool,
+    /// The CPU architecture this release was built for (eg. "x64", "arm64" or "universal"). This may be an empty string,
+    /// in which case the release is assumed to run on any machine.
+    pub Architecture: String,
 }
 
 #[allow(non_snake_case)]
@@ -73,7 +145,48 @@ pub struct UpdateInfo {
     /// True if the update is a version downgrade or lateral move (such as when switching channels to the same version number).
     /// In this case, only full updates are allowed, and any local packages on disk newer than the downloaded version will be
     /// deleted.
+    /// A release of the same version built for an architecture which suits this machine better (eg. a native ARM64 build
+    /// replacing an x64 build running under emulation) is also a lateral move.
     pub IsDowngrade: bool,
+    /// The release notes for the target version and every intermediate version between the current version and the
+    /// target version, ordered from newest to oldest. This is useful for showing a "What's new" summary of everything
+    /// that will change, rather than only the notes for the latest release.
+    pub ReleaseNotes: Vec<ReleaseNotes>,
+    /// True if the target release, or any release between the current version and the target version, is marked as mandatory.
+    /// Apps should not allow this update to be skipped, and may prevent usage until it has been applied.
+    pub IsMandatory: bool,
+    /// True if the update source could not be reached, and this update was found in the release feed cached by the last
+    /// successful update check (see UseCachedFeedWhenOffline). The update may no longer be available, or may be out of date.
+    pub IsStale: bool,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[serde(default)]
+/// The release notes for a single version in the update feed.
+pub struct ReleaseNotes {
+    /// The version these release notes belong to.
+    pub Version: String,
+    /// The release notes in markdown format. This may be an empty string.
+    pub NotesMarkdown: String,
+    /// The release notes in HTML format, if they were available in the feed. This may be an empty string.
+    pub NotesHtml: String,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[serde(default)]
+/// Describes what is required to switch the app to a different update channel.
+pub struct ChannelSwitch {
+    /// The channel being switched to.
+    pub Channel: String,
+    /// The release which needs to be installed to complete the switch, or None if the current version is already
+    /// the latest release on the new channel (or the channel has no releases).
+    pub Update: Option<UpdateInfo>,
+    /// True if the latest release on the new channel is older than the current version, so switching would downgrade the app.
+    pub RequiresDowngrade: bool,
 }
 
 impl AsRef<VelopackAsset> for UpdateInfo {
@@ -107,6 +220,80 @@ pub struct UpdateOptions {
     /// allows you to explicitly switch channels, for example if the user wished to switch back to the 'stable' channel
     /// without having to reinstall the application.
     pub ExplicitChannel: Option<String>,
+    /// Allows UpdateManager to update to prerelease versions (eg. '2.0.0-beta.1') from the feed, so users can opt into
+    /// prerelease builds without switching to a separate channel. Prereleases are always allowed if the current version is
+    /// itself a prerelease, or if ExplicitChannel selects a channel other than the one this version was packaged for.
+    pub AllowPrerelease: bool,
+    /// If greater than zero, check_for_updates will not contact the update source if the last check was less than this many
+    /// seconds ago, and will report that no update is available instead. The time of the last check is persisted next to the
+    /// app, and a random delay of up to 10% of the interval is added so that clients started at the same time spread out
+    /// their checks rather than polling the feed all at once.
+    #[cfg_attr(feature = "typescript", ts(type = "number"))]
+    pub UpdateCheckIntervalSeconds: u64,
+    /// The number of full packages to keep in the packages directory once an update has been applied, including the package
+    /// for the installed version. Keeping more than one allows rolling back to a previous version without a network connection.
+    /// Zero uses the default, which is to keep only the package for the installed version.
+    pub PackagesToKeep: u32,
+    /// If greater than zero, the oldest packages are deleted until the packages kept add up to less than this many bytes. The
+    /// package for the installed version is never deleted.
+    #[cfg_attr(feature = "typescript", ts(type = "number"))]
+    pub MaxPackagesSizeBytes: u64,
+    /// If greater than zero, packages which were downloaded more than this many days ago are deleted. The package for the
+    /// installed version is never deleted.
+    pub MaxPackageAgeDays: u32,
+    /// Overrides the directory downloaded packages and temporary files are stored in (eg. a different drive with more free
+    /// space, or a cache directory shared by several apps). Packages are stored in a sub-directory named after the app id, and
+    /// relative paths are resolved against the app install directory. This takes priority over the VELOPACK_PACKAGES_DIR
+    /// environment variable and the packages directory specified when the app was packaged.
+    pub PackagesDir: Option<String>,
+    /// A machine-level directory where downloaded packages are cached by their checksum, so that apps (or channels of the
+    /// same app) which are configured with the same directory do not download identical packages again. The directory is
+    /// created so that every user can write to it, but one which already exists must be writable by every user which runs
+    /// these apps. Nothing is cached if this is None.
+    pub SharedCacheDir: Option<String>,
+    /// The maximum size of the shared cache, the least recently used packages are deleted once it grows past this size.
+    /// Zero uses the default, which is 2 GB.
+    #[cfg_attr(feature = "typescript", ts(type = "number"))]
+    pub SharedCacheMaxSizeBytes: u64,
+    /// The url (or network directory) of an intranet cache which packages are downloaded from before trying the update
+    /// source, see [`CacheServerSource`] for the protocol. If this is None, the VELOPACK_CACHE_SERVER environment
+    /// variable is used instead, so the cache can be configured for every app on a machine.
+    pub CacheServerUrl: Option<String>,
+    /// Replaces the User-Agent sent with every request to the update source, eg. 'MyApp/1.2.3 (beta)', so that the server
+    /// can tell which app (and version) is asking for updates. The default User-Agent is used if this is None.
+    pub UserAgent: Option<String>,
+    /// Extra query parameters added to requests for the release feed, as a query string (eg. 'cohort=3&region=eu'), so
+    /// that server-side analytics or staged rollouts can target groups of clients. Nothing is added if this is None.
+    pub FeedQueryParameters: Option<String>,
+    /// Extra headers sent with every request to the update source, one per line as 'Name: value' (eg. 'X-Api-Key: abc123'),
+    /// so that servers which need authentication or routing headers can be used. Nothing is added if this is None.
+    pub RequestHeaders: Option<String>,
+    /// If true, the release feed of each successful update check is cached in the packages directory, and check_for_updates uses the
+    /// cached feed if the update source can not be reached (eg. because the machine is offline). Updates found in the cached
+    /// feed are marked with IsStale, and [`UpdateCheck::NoUpdateAvailableOffline`] is returned if there are none.
+    pub UseCachedFeedWhenOffline: bool,
+}
+
+impl UpdateOptions {
+    /// Returns the metadata which identifies this app in requests to the update source.
+    fn get_request_metadata(&self) -> RequestMetadata {
+        let query = self.FeedQueryParameters.as_deref().unwrap_or_default().trim_start_matches('?');
+        RequestMetadata {
+            user_agent: self.UserAgent.clone().filter(|s| !s.trim().is_empty()),
+            headers: parse_request_headers(self.RequestHeaders.as_deref().unwrap_or_default()),
+            query: url::form_urlencoded::parse(query.as_bytes()).into_owned().collect(),
+        }
+    }
+}
+
+/// Parses headers written one per line as 'Name: value'. Lines without a name are ignored.
+fn parse_request_headers(headers: &str) -> Vec<(String, String)> {
+    headers
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_owned(), value.trim().to_owned()))
+        .filter(|(name, _)| !name.is_empty())
+        .collect()
 }
 
 /// Provides functionality for checking for updates, downloading updates, and applying updates to the current application.
@@ -115,9 +302,13 @@ pub struct UpdateManager {
     options: UpdateOptions,
     source: Box<dyn UpdateSource>,
     locator: VelopackLocator,
+    observer: Option<Arc<dyn UpdateObserver>>,
+    launcher: Box<dyn ProcessLauncher>,
 }
 
-/// Represents the result of a call to check for updates.
+/// Represents the result of a call to check for updates. More results may be added in future versions, so matches
+/// should handle any other result as no update being available.
+#[non_exhaustive]
 pub enum UpdateCheck {
     /// The remote feed is empty, so no update check was performed
     RemoteIsEmpty,
@@ -125,6 +316,67 @@ pub enum UpdateCheck {
     NoUpdateAvailable,
     /// The remote feed had an update available
     UpdateAvailable(UpdateInfo),
+    /// The app is running in a sandbox (eg. Flatpak or Snap) which delivers its updates, so no update check was performed
+    UpdatesManagedExternally,
+    /// The update source could not be reached, and the release feed cached by the last successful update check did not have
+    /// an update available either. This is only returned if UseCachedFeedWhenOffline is enabled.
+    NoUpdateAvailableOffline,
+}
+
+/// An operation which modifies the app or its packages, and so can only be run by one UpdateManager at a time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateOperation {
+    /// An update package is being downloaded.
+    Download,
+    /// A downloaded update is being extracted into the staging directory.
+    Stage,
+    /// The updater is being started to apply an update.
+    Apply,
+    /// Optional components are being installed, updated or removed.
+    Components,
+}
+
+impl fmt::Display for UpdateOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            UpdateOperation::Download => "download",
+            UpdateOperation::Stage => "stage",
+            UpdateOperation::Apply => "apply",
+            UpdateOperation::Components => "components",
+        };
+        f.write_str(name)
+    }
+}
+
+lazy_static::lazy_static! {
+    // keyed by the app root directory, so that every UpdateManager for the same app in this process shares the same state
+    static ref OPERATIONS_IN_PROGRESS: Mutex<HashMap<PathBuf, UpdateOperation>> = Mutex::new(HashMap::new());
+}
+
+/// Marks an operation as in progress until it is dropped.
+struct OperationGuard {
+    root_dir: PathBuf,
+}
+
+impl OperationGuard {
+    fn begin(root_dir: PathBuf, operation: UpdateOperation) -> Result<OperationGuard, Error> {
+        let mut operations = OPERATIONS_IN_PROGRESS.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(current) = operations.get(&root_dir) {
+            return Err(Error::OperationInProgress(*current));
+        }
+        operations.insert(root_dir.clone(), operation);
+        Ok(OperationGuard { root_dir })
+    }
+
+    fn current(root_dir: &PathBuf) -> Option<UpdateOperation> {
+        OPERATIONS_IN_PROGRESS.lock().unwrap_or_else(|e| e.into_inner()).get(root_dir).copied()
+    }
+}
+
+impl Drop for OperationGuard {
+    fn drop(&mut self) {
+        OPERATIONS_IN_PROGRESS.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.root_dir);
+    }
 }
 
 impl UpdateManager {
@@ -148,14 +400,98 @@ impl UpdateManager {
         } else {
             locator::auto_locate_app_manifest(LocationContext::FromCurrentExe)?
         };
+        let options = options.unwrap_or_default();
+        let locator = locator.clone_self_with_packages_dir_override(options.PackagesDir.as_deref());
+        let env = std::env::var(CACHE_SERVER_ENV).ok();
+        let cache_server = [options.CacheServerUrl.as_deref(), env.as_deref()].into_iter().flatten().map(str::trim).find(|s| !s.is_empty());
+        let mut source = match cache_server {
+            Some(cache) => {
+                info!("Packages will be downloaded from the cache at '{}' if possible.", cache);
+                CacheServerSource::new(cache, source).clone_boxed()
+            }
+            None => source.clone_boxed(),
+        };
+        let metadata = options.get_request_metadata();
+        if metadata != RequestMetadata::default() {
+            source.set_request_metadata(&metadata);
+        }
         Ok(UpdateManager {
-            options: options.unwrap_or_default(),
-            source: source.clone_boxed(),
+            options,
+            source,
             locator,
+            observer: None,
+            launcher: Box::new(DefaultProcessLauncher::default()),
         })
     }
 
-    fn get_practical_channel(&self) -> String {
+    /// Sets an observer which is notified when update checks, downloads, and staging start and finish, and when the updater
+    /// is started to apply an update, so that the app can record these events in its own analytics. Replaces any observer
+    /// which was set previously.
+    pub fn set_observer<O: UpdateObserver + 'static>(&mut self, observer: O) {
+        self.observer = Some(Arc::new(observer));
+    }
+
+    /// Sets the launcher which is used to start Update.exe (eg. to apply an update, or to create shortcuts), so that an
+    /// app's update flow can be tested without starting the real updater. See [`crate::testing::RecordingLauncher`].
+    pub fn set_process_launcher<L: ProcessLauncher + 'static>(&mut self, launcher: L) {
+        self.launcher = Box::new(launcher);
+    }
+
+    /// Sets the file system which the state of the app (eg. the skipped version, the time of the last update check, or
+    /// the cached release feed) is kept in, so that it can be tested in memory. See [`crate::testing::MemoryFileSystem`].
+    pub fn set_file_system<F: FileSystem + 'static>(&mut self, fs: F) {
+        self.locator.set_file_system(fs);
+    }
+
+    /// Returns the operation which is currently in progress for this app, if any. Only one download, stage, apply or
+    /// component operation can run at a time across every UpdateManager for the same app in this process, and calling
+    /// another while one is in progress fails with [`Error::OperationInProgress`]. These operations also fail with
+    /// [`Error::Locked`] while an installer or updater in another process is working on the app.
+    pub fn get_operation_in_progress(&self) -> Option<UpdateOperation> {
+        OperationGuard::current(&self.locator.get_root_dir())
+    }
+
+    fn begin_operation(&self, operation: UpdateOperation) -> Result<OperationGuard, Error> {
+        self.ensure_can_update()?;
+        self.ensure_not_locked()?;
+        OperationGuard::begin(self.locator.get_root_dir(), operation)
+    }
+
+    /// Returns [`Error::Locked`] if an installer or updater holds the lock on the root directory of the app. The lock is
+    /// released straight away, since Update.exe takes it again for itself. If the lock file can not be opened (eg. the
+    /// app is installed for every user in a read-only location), this is not an error.
+    fn ensure_not_locked(&self) -> Result<(), Error> {
+        let lock_path = self.locator.get_lock_file_path();
+        match FileLock::try_acquire(&lock_path) {
+            Ok(Some(_)) => Ok(()),
+            Ok(None) => Err(Error::Locked(FileLock::get_owner(&lock_path).unwrap_or_else(|| "unknown process".to_owned()))),
+            Err(e) => {
+                debug!("Unable to check the lock file '{}': {}", lock_path.to_string_lossy(), e);
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns an error if updates can not be applied to this copy of the app, regardless of which update is chosen.
+    fn ensure_can_update(&self) -> Result<(), Error> {
+        if self.locator.get_is_translocated() {
+            return Err(Error::AppTranslocated(self.locator.get_root_dir_as_string()));
+        }
+        if let Some(sandbox) = self.locator.get_sandbox() {
+            return Err(Error::UpdatesManagedExternally(sandbox.to_string()));
+        }
+        Ok(())
+    }
+
+    fn notify<F: FnOnce(&dyn UpdateObserver)>(&self, f: F) {
+        if let Some(observer) = &self.observer {
+            f(observer.as_ref());
+        }
+    }
+
+    /// The channel which updates are retrieved from. This is the ExplicitChannel option if it was set, otherwise the
+    /// channel the current version was packaged for (or the default channel for this operating system).
+    pub fn get_channel(&self) -> String {
         let options_channel = self.options.ExplicitChannel.as_deref();
         let app_channel = self.locator.get_manifest_channel();
         let mut channel = options_channel.unwrap_or(&app_channel).to_string();
@@ -180,29 +516,110 @@ impl UpdateManager {
         self.locator.get_manifest_id()
     }
 
+    /// The id the app is installed as. This is the app id, suffixed with the channel (eg. "MyApp-beta") if channels
+    /// are installed side by side.
+    pub fn get_install_id(&self) -> String {
+        self.locator.get_install_id()
+    }
+
     /// Check if the app is in portable mode. This can be true or false on Windows.
     /// On Linux and MacOS, this will always return true.
     pub fn get_is_portable(&self) -> bool {
         self.locator.get_is_portable()
     }
 
+    /// Check if macOS is running this app from a translocated (read-only) location, which happens when it is launched
+    /// from a DMG or the downloads folder without being moved to /Applications first. Updates can not be downloaded
+    /// or applied while this is true and fail with [`Error::AppTranslocated`], so you should ask the user to move the app.
+    pub fn get_is_translocated(&self) -> bool {
+        self.locator.get_is_translocated()
+    }
+
+    /// Check if the app is running in a sandbox such as Flatpak or Snap, which delivers updates through its own store.
+    /// If this is true, check_for_updates returns [`UpdateCheck::UpdatesManagedExternally`] and downloading or applying
+    /// updates fails with [`Error::UpdatesManagedExternally`], so you should hide any update UI.
+    pub fn get_is_managed_externally(&self) -> bool {
+        self.locator.get_sandbox().is_some()
+    }
+
+    /// Check if a prerequisite installed during the last install or update requires a system restart
+    /// before the application can run correctly. If this is true, you should inform the user.
+    pub fn get_is_restart_required(&self) -> bool {
+        self.locator.get_is_restart_required()
+    }
+
+    /// Check if the current user is launching this app for the first time. This is only tracked for machine-wide
+    /// installs, where the installer can only trigger the first-run hook for the user who installed the app.
+    pub fn get_is_first_run_for_user(&self) -> bool {
+        self.locator.get_is_first_run_for_user()
+    }
+
+    /// Returns the version which the user has chosen to skip with [`UpdateManager::skip_version`], if any.
+    pub fn get_skipped_version(&self) -> Option<Version> {
+        self.locator.get_skipped_version()
+    }
+
+    /// Remember that the user does not want to install the specified version. Subsequent calls to check_for_updates
+    /// will not return this version, but will still return any newer version which is published later. Updates which are
+    /// marked as mandatory are always returned, even if they have been skipped.
+    pub fn skip_version<A>(&self, to_skip: A) -> Result<(), Error>
+    where
+        A: AsRef<VelopackAsset>,
+    {
+        let version = Version::parse(&to_skip.as_ref().Version)?;
+        info!("Skipping version {} in future update checks.", version);
+        self.locator.set_skipped_version(Some(&version))
+    }
+
+    /// Forget any version previously skipped with [`UpdateManager::skip_version`].
+    pub fn clear_skipped_version(&self) -> Result<(), Error> {
+        self.locator.set_skipped_version(None)
+    }
+
+    pub(crate) fn get_first_run_completed_for_user(&self) -> bool {
+        self.locator.get_first_run_completed_for_user()
+    }
+
+    /// Portable apps are never installed, so they have no first run to record.
+    pub(crate) fn set_first_run_completed_for_user(&self) -> Result<(), Error> {
+        if !self.locator.get_is_portable() {
+            self.locator.set_first_run_completed_for_user()?;
+        }
+        Ok(())
+    }
+
+    /// The installer only creates shortcuts for the user who installed the app, so on machine-wide installs
+    /// we ask the updater to create them for each additional user on their first launch.
+    pub(crate) fn create_shortcuts_for_user(&self) -> Result<(), Error> {
+        let mut p = ProcessCommand::new(self.locator.get_update_path());
+        p.args.push("shortcuts".to_owned());
+        p.current_dir = Some(self.locator.get_root_dir());
+        self.set_packages_dir_env(&mut p);
+
+        info!("About to run Update: {} shortcuts", self.locator.get_update_path_as_string());
+        self.launcher.spawn(&p)?;
+        Ok(())
+    }
+
     /// Returns None if there is no local package waiting to be applied. Returns a VelopackAsset 
     /// if there is an update downloaded which has not yet been applied. In that case, the
     /// VelopackAsset can be applied by calling apply_updates_and_restart or wait_exit_then_apply_updates.
     pub fn get_update_pending_restart(&self) -> Option<VelopackAsset> {
         let packages_dir = self.locator.get_packages_dir();
         if let Some((path, manifest)) = locator::find_latest_full_package(&packages_dir) {
-            if manifest.version > self.locator.get_manifest_version() {
+            if version::is_newer(&manifest.version, &self.locator.get_manifest_version()) {
                 return Some(VelopackAsset {
-                    PackageId: manifest.id,
+                    PackageId: manifest.id.clone(),
                     Version: manifest.version.to_string(),
                     Type: "Full".to_string(),
                     FileName: path.file_name().unwrap().to_string_lossy().to_string(),
+                    IsMandatory: manifest.is_mandatory(),
                     SHA1: util::calculate_file_sha1(&path).unwrap_or_default(),
                     SHA256: util::calculate_file_sha256(&path).unwrap_or_default(),
                     Size: path.metadata().map(|m| m.len()).unwrap_or(0),
                     NotesMarkdown: manifest.release_notes,
                     NotesHtml: manifest.release_notes_html,
+                    Architecture: manifest.machine_architecture,
                 });
             }
         }
@@ -211,50 +628,152 @@ impl UpdateManager {
 
     /// Get a list of available remote releases from the package source.
     pub fn get_release_feed(&self) -> Result<VelopackAssetFeed, Error> {
-        let channel = self.get_practical_channel();
+        let channel = self.get_channel();
         self.source.get_release_feed(&channel, &self.locator.get_manifest())
     }
 
-    #[cfg(feature = "async")]
-    /// Get a list of available remote releases from the package source.
-    pub fn get_release_feed_async(&self) -> JoinHandle<Result<VelopackAssetFeed, Error>>
-    {
-        let self_clone = self.clone();
-        async_std::task::spawn_blocking(move || self_clone.get_release_feed())
+    async_variant! {
+        /// Get a list of available remote releases from the package source.
+        get_release_feed_async => get_release_feed() -> VelopackAssetFeed
+    }
+
+    /// Returns the release feed cached by the last successful update check on the current channel, so that apps can show
+    /// the last known releases while offline. This is always None unless UseCachedFeedWhenOffline is enabled.
+    pub fn get_cached_release_feed(&self) -> Option<VelopackAssetFeed> {
+        self.locator.get_cached_release_feed(&self.get_channel())
     }
 
     /// Checks for updates, returning None if there are none available. If there are updates available, this method will return an
     /// UpdateInfo object containing the latest available release, and any delta updates that can be applied if they are available.
     pub fn check_for_updates(&self) -> Result<UpdateCheck, Error> {
-        let allow_downgrade = self.options.AllowVersionDowngrade;
-        let app_channel = self.locator.get_manifest_channel();
-        let app_version = self.locator.get_manifest_version();
-        let feed = self.get_release_feed()?;
-        let assets = feed.Assets;
+        self.notify(|o| o.on_check_started());
+        let start = Instant::now();
+        let result = self.check_for_updates_impl();
+        self.notify(|o| o.on_check_completed(result.as_ref().map_err(ErrorClass::from), start.elapsed()));
+        result
+    }
 
-        let practical_channel = self.get_practical_channel();
-        let is_non_default_channel = practical_channel != app_channel;
+    fn check_for_updates_impl(&self) -> Result<UpdateCheck, Error> {
+        if let Some(sandbox) = self.locator.get_sandbox() {
+            info!("Skipping update check, updates for this app are managed by {}.", sandbox);
+            return Ok(UpdateCheck::UpdatesManagedExternally);
+        }
+        if self.is_update_check_throttled() {
+            info!("Skipping update check, the last check was less than {} seconds ago.", self.options.UpdateCheckIntervalSeconds);
+            return Ok(UpdateCheck::NoUpdateAvailable);
+        }
+        let result = self.check_for_updates_on_channel(&self.get_channel(), self.options.AllowVersionDowngrade);
+        let result = result?;
+        // a failed check is not recorded, so that the next start tries again rather than waiting out the interval
+        self.record_update_check();
+        if let UpdateCheck::UpdateAvailable(update) = &result {
+            let skipped = self.locator.get_skipped_version();
+            if let (Some(skipped), Ok(target)) = (skipped, Version::parse(&update.TargetFullRelease.Version)) {
+                // mandatory updates can not be skipped, so the skipped version is only honored for optional updates
+                if version::is_same(&skipped, &target) && !update.IsMandatory {
+                    info!("Latest remote release {} has been skipped by the user.", target);
+                    return Ok(UpdateCheck::NoUpdateAvailable);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    fn check_for_updates_on_channel(&self, channel: &str, allow_downgrade: bool) -> Result<UpdateCheck, Error> {
+        let (feed, is_stale) = self.get_release_feed_or_cached(channel)?;
+        let result = self.check_feed_for_updates(channel, feed, allow_downgrade)?;
+        if !is_stale {
+            return Ok(result);
+        }
+        Ok(match result {
+            UpdateCheck::UpdateAvailable(update) => UpdateCheck::UpdateAvailable(UpdateInfo { IsStale: true, ..update }),
+            UpdateCheck::RemoteIsEmpty | UpdateCheck::NoUpdateAvailable => UpdateCheck::NoUpdateAvailableOffline,
+            result => result,
+        })
+    }
 
-        if assets.is_empty() {
+    /// Downloads the release feed of a channel, and caches it if UseCachedFeedWhenOffline is enabled. If the update source
+    /// can not be reached, the cached feed is returned instead, along with true to show that it may be out of date.
+    fn get_release_feed_or_cached(&self, channel: &str) -> Result<(VelopackAssetFeed, bool), Error> {
+        let use_cache = self.options.UseCachedFeedWhenOffline;
+        match self.source.get_release_feed(channel, &self.locator.get_manifest()) {
+            Ok(feed) => {
+                if use_cache {
+                    if let Err(e) = self.locator.set_cached_release_feed(channel, &feed) {
+                        warn!("Unable to cache the release feed: {}", e);
+                    }
+                }
+                Ok((feed, false))
+            }
+            Err(e) if use_cache && matches!(ErrorClass::from(&e), ErrorClass::Network | ErrorClass::Io) => {
+                match self.locator.get_cached_release_feed(channel) {
+                    Some(feed) => {
+                        warn!("Unable to reach the update source ({}), using the release feed cached by the last successful check.", e);
+                        Ok((feed, true))
+                    }
+                    None => Err(e),
+                }
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn check_feed_for_updates(&self, channel: &str, feed: VelopackAssetFeed, allow_downgrade: bool) -> Result<UpdateCheck, Error> {
+        let mut app_channel = self.locator.get_manifest_channel();
+        if app_channel.is_empty() {
+            app_channel = locator::default_channel_name();
+        }
+        let app_version = self.locator.get_manifest_version();
+        let installed_arch = self.locator.get_manifest().machine_architecture;
+        let is_non_default_channel = channel != app_channel;
+
+        if feed.Assets.is_empty() {
             return Ok(UpdateCheck::RemoteIsEmpty);
         }
 
+        // prereleases are only offered to users who opted in, who are already running a prerelease, or who have
+        // explicitly chosen a different channel (which is an opt-in to whatever is published there)
+        let allow_prerelease = self.options.AllowPrerelease || !app_version.pre.is_empty() || is_non_default_channel;
+        let feed_len = feed.Assets.len();
+        let assets: Vec<VelopackAsset> = feed
+            .Assets
+            .into_iter()
+            .filter(|a| allow_prerelease || Version::parse(&a.Version).map_or(true, |v| v.pre.is_empty()))
+            .collect();
+        if assets.len() < feed_len {
+            debug!("Ignoring {} prerelease(s) in the feed, as prereleases are not allowed.", feed_len - assets.len());
+        }
+
+        // releases built for a CPU architecture this machine can not run are ignored, and if the same version is
+        // published for several architectures, the best match for this machine is chosen (eg. arm64 over x64 on Apple Silicon)
+        let machine_arch = arch::get_machine_arch();
+        let compatible_len = assets.len();
+        let assets: Vec<VelopackAsset> =
+            assets.into_iter().filter(|a| arch::get_arch_preference(&a.Architecture, machine_arch).is_some()).collect();
+        if assets.len() < compatible_len {
+            debug!("Ignoring {} release(s) in the feed, which were built for a different CPU architecture.", compatible_len - assets.len());
+        }
+
         let mut latest: Option<VelopackAsset> = None;
         let mut latest_version: Version = Version::parse("0.0.0")?;
-        for asset in assets {
+        let mut latest_preference = 0;
+        for asset in assets.iter().cloned() {
             if let Ok(sv) = Version::parse(&asset.Version) {
                 if asset.Type.eq_ignore_ascii_case("Full") {
                     debug!("Found full release: {} ({}).", asset.FileName, sv.to_string());
-                    if latest.is_none() || (sv > latest_version) {
+                    let preference = arch::get_arch_preference(&asset.Architecture, machine_arch).unwrap_or_default();
+                    let is_better_arch = version::is_same(&sv, &latest_version) && preference > latest_preference;
+                    if latest.is_none() || version::is_newer(&sv, &latest_version) || is_better_arch {
                         latest = Some(asset);
                         latest_version = sv;
+                        latest_preference = preference;
                     }
                 }
             }
         }
 
         if latest.is_none() {
-            return Ok(UpdateCheck::RemoteIsEmpty);
+            return Ok(if assets.len() < feed_len { UpdateCheck::NoUpdateAvailable } else { UpdateCheck::RemoteIsEmpty });
         }
 
         let remote_version = latest_version;
@@ -262,30 +781,240 @@ impl UpdateManager {
 
         debug!("Latest remote release: {} ({}).", remote_asset.FileName, remote_version.to_string());
 
-        if remote_version > app_version {
+        if version::is_newer(&remote_version, &app_version) {
             info!("Found newer remote release available ({} -> {}).", app_version, remote_version);
-            Ok(UpdateCheck::UpdateAvailable(UpdateInfo { TargetFullRelease: remote_asset, IsDowngrade: false }))
-        } else if remote_version < app_version && allow_downgrade {
+            let notes = collect_release_notes(&assets, &app_version, &remote_version);
+            let mandatory = contains_mandatory_release(&assets, &app_version, &remote_version);
+            Ok(UpdateCheck::UpdateAvailable(UpdateInfo {
+                TargetFullRelease: remote_asset,
+                IsDowngrade: false,
+                ReleaseNotes: notes,
+                IsMandatory: mandatory,
+                IsStale: false,
+            }))
+        } else if version::is_newer(&app_version, &remote_version) && allow_downgrade {
             info!("Found older remote release available and downgrade is enabled ({} -> {}).", app_version, remote_version);
-            Ok(UpdateCheck::UpdateAvailable(UpdateInfo { TargetFullRelease: remote_asset, IsDowngrade: true }))
-        } else if remote_version == app_version && allow_downgrade && is_non_default_channel {
+            let notes = vec![ReleaseNotes::from(&remote_asset)];
+            let mandatory = remote_asset.IsMandatory;
+            Ok(UpdateCheck::UpdateAvailable(UpdateInfo {
+                TargetFullRelease: remote_asset,
+                IsDowngrade: true,
+                ReleaseNotes: notes,
+                IsMandatory: mandatory,
+                IsStale: false,
+            }))
+        } else if version::is_same(&remote_version, &app_version) && allow_downgrade && is_non_default_channel {
             info!(
                 "Latest remote release is the same version of a different channel, and downgrade is enabled ({} -> {}).",
                 app_version, remote_version
             );
-            Ok(UpdateCheck::UpdateAvailable(UpdateInfo { TargetFullRelease: remote_asset, IsDowngrade: true }))
+            let notes = vec![ReleaseNotes::from(&remote_asset)];
+            let mandatory = remote_asset.IsMandatory;
+            Ok(UpdateCheck::UpdateAvailable(UpdateInfo {
+                TargetFullRelease: remote_asset,
+                IsDowngrade: true,
+                ReleaseNotes: notes,
+                IsMandatory: mandatory,
+                IsStale: false,
+            }))
+        } else if version::is_same(&remote_version, &app_version) && is_better_arch(&remote_asset, &installed_arch, machine_arch) {
+            // eg. an Intel build running under emulation on an ARM machine, which can now move to the native build. this is a
+            // lateral move like a channel switch, so it is flagged as a downgrade, which only allows the full package to be used
+            info!(
+                "Latest remote release is the same version built for a better architecture for this machine ({} -> {}).",
+                installed_arch, remote_asset.Architecture
+            );
+            Ok(UpdateCheck::UpdateAvailable(UpdateInfo {
+                TargetFullRelease: remote_asset,
+                IsDowngrade: true,
+                ReleaseNotes: Vec::new(),
+                IsMandatory: false,
+                IsStale: false,
+            }))
         } else {
             Ok(UpdateCheck::NoUpdateAvailable)
         }
     }
 
-    #[cfg(feature = "async")]
-    /// Checks for updates, returning None if there are none available. If there are updates available, this method will return an
-    /// UpdateInfo object containing the latest available release, and any delta updates that can be applied if they are available.
-    pub fn check_for_updates_async(&self) -> JoinHandle<Result<UpdateCheck, Error>>
-    {
-        let self_clone = self.clone();
-        async_std::task::spawn_blocking(move || self_clone.check_for_updates())
+    fn is_update_check_throttled(&self) -> bool {
+        let interval = self.options.UpdateCheckIntervalSeconds;
+        if interval == 0 {
+            return false;
+        }
+        match (self.locator.get_last_update_check(), util::get_unix_time()) {
+            // if the clock has gone backwards since the last check, we can't trust the stored time so we check anyway
+            (Some((last, jitter)), Some(now)) => now >= last && now - last < interval.saturating_add(jitter),
+            _ => false,
+        }
+    }
+
+    fn record_update_check(&self) {
+        let interval = self.options.UpdateCheckIntervalSeconds;
+        if interval == 0 {
+            return;
+        }
+        if let Some(now) = util::get_unix_time() {
+            let jitter = rand::thread_rng().gen_range(0..=interval / 10);
+            if let Err(e) = self.locator.set_last_update_check(now, jitter) {
+                warn!("Unable to record the time of this update check: {}", e);
+            }
+        }
+    }
+
+    async_variant! {
+        /// Checks for updates, returning None if there are none available. If there are updates available, this method will return an
+        /// UpdateInfo object containing the latest available release, and any delta updates that can be applied if they are available.
+        check_for_updates_async => check_for_updates() -> UpdateCheck
+    }
+
+    /// Checks what would happen if this app switched to the specified channel (eg. from "stable" to "beta"), without changing
+    /// anything. The result contains the release which would need to be installed to complete the switch, and whether that
+    /// release is an older version than the one currently installed.
+    pub fn check_channel_switch(&self, channel: &str) -> Result<ChannelSwitch, Error> {
+        let update = match self.check_for_updates_on_channel(channel, true)? {
+            UpdateCheck::UpdateAvailable(update) => Some(update),
+            _ => None,
+        };
+        let current_version = self.locator.get_manifest_version();
+        let requires_downgrade = update
+            .as_ref()
+            .and_then(|u| Version::parse(&u.TargetFullRelease.Version).ok())
+            .is_some_and(|v| version::is_newer(&current_version, &v));
+        Ok(ChannelSwitch { Channel: channel.to_owned(), Update: update, RequiresDowngrade: requires_downgrade })
+    }
+
+    /// Switches this UpdateManager to retrieve updates from the specified channel, and returns the release which needs to be
+    /// installed to complete the switch (if any). If the latest release on the new channel is older than the current version,
+    /// the switch is a downgrade, and this will fail with [`Error::DowngradeNotConfirmed`] unless `confirm_downgrade` is true.
+    /// This only affects this UpdateManager; once the release from the new channel is installed, future updates will come
+    /// from the new channel by default. Apps whose channels are installed side by side can not switch channels, because
+    /// each channel is a separate install, so this fails with [`Error::Unsupported`].
+    pub fn switch_channel(&mut self, channel: &str, confirm_downgrade: bool) -> Result<ChannelSwitch, Error> {
+        let manifest = self.locator.get_manifest();
+        if manifest.is_side_by_side() && channel != manifest.channel {
+            return Err(Error::Unsupported(format!(
+                "channels of this app are installed side by side, so '{}' can not switch to channel '{}'; install it separately instead",
+                manifest.get_install_id(),
+                channel
+            )));
+        }
+        let switch = self.check_channel_switch(channel)?;
+        if switch.RequiresDowngrade && !confirm_downgrade {
+            let target = switch.Update.as_ref().map(|u| u.TargetFullRelease.Version.clone()).unwrap_or_default();
+            return Err(Error::DowngradeNotConfirmed(format!(
+                "the latest release on channel '{}' is {}, but {} is installed",
+                channel,
+                target,
+                self.locator.get_manifest_version()
+            )));
+        }
+        info!("Switching update channel from '{}' to '{}'.", self.get_channel(), channel);
+        self.options.ExplicitChannel = Some(channel.to_owned());
+        if switch.RequiresDowngrade {
+            self.options.AllowVersionDowngrade = true;
+        }
+        Ok(switch)
+    }
+
+    /// Deletes old packages from the packages directory according to the package retention settings in UpdateOptions,
+    /// and returns the paths which were deleted. The package for the installed version is never deleted. This is done
+    /// automatically after an update is downloaded or applied, so it usually only needs to be called if the settings change.
+    pub fn clean_packages(&self) -> Result<Vec<PathBuf>, Error> {
+        let protected = [self.locator.get_ideal_local_nupkg_path(None, None)];
+        retention::enforce(&self.locator.get_packages_dir(), &RetentionPolicy::from_options(&self.options), &protected)
+    }
+
+    /// Returns the newest release of each optional component (eg. plugins or language packs) listed in the release feed
+    /// which is compatible with the current version of the app.
+    pub fn get_available_components(&self) -> Result<Vec<ComponentAsset>, Error> {
+        let feed = self.get_release_feed()?;
+        let app_version = self.locator.get_manifest_version();
+        let mut ids: Vec<String> = feed.Components.iter().map(|c| c.ComponentId.to_ascii_lowercase()).collect();
+        ids.sort();
+        ids.dedup();
+        Ok(ids.iter().filter_map(|id| components::find_latest_compatible(&feed.Components, id, &app_version)).cloned().collect())
+    }
+
+    /// Returns the optional components which are currently installed.
+    pub fn get_installed_components(&self) -> Vec<InstalledComponent> {
+        components::read_installed(&self.locator.get_components_dir(), &self.locator.get_manifest_version())
+    }
+
+    /// Downloads and installs the newest release of the specified optional component which is compatible with the current
+    /// version of the app, replacing any version of the component which is already installed.
+    pub fn install_component(&self, component_id: &str) -> Result<InstalledComponent, Error> {
+        let _guard = self.begin_operation(UpdateOperation::Components)?;
+        let feed = self.get_release_feed()?;
+        let app_version = self.locator.get_manifest_version();
+        let component = components::find_latest_compatible(&feed.Components, component_id, &app_version).ok_or_else(|| {
+            Error::Generic(format!("No release of component '{}' compatible with version {} was found.", component_id, app_version))
+        })?;
+        self.install_component_release(component)
+    }
+
+    /// Removes an installed optional component. Does nothing if the component is not installed.
+    pub fn remove_component(&self, component_id: &str) -> Result<(), Error> {
+        let _guard = self.begin_operation(UpdateOperation::Components)?;
+        components::remove(&self.locator.get_components_dir(), component_id)
+    }
+
+    /// Updates every installed optional component to the newest release which is compatible with the current version of the
+    /// app. This should be called after the app has been updated (eg. when it is restarted after an update), so that components
+    /// which were built for the previous version are replaced by compatible ones. Returns the components which were changed.
+    pub fn update_components(&self) -> Result<Vec<InstalledComponent>, Error> {
+        let _guard = self.begin_operation(UpdateOperation::Components)?;
+        let installed = self.get_installed_components();
+        if installed.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let feed = self.get_release_feed()?;
+        let app_version = self.locator.get_manifest_version();
+        let mut updated = Vec::new();
+        for current in installed {
+            match components::find_latest_compatible(&feed.Components, &current.ComponentId, &app_version) {
+                Some(latest) => {
+                    // an incompatible component is replaced even if that means installing an older release of it
+                    let latest_version = Version::parse(&latest.Asset.Version)?;
+                    let is_newer = Version::parse(&current.Version).map_or(true, |v| version::is_newer(&latest_version, &v));
+                    if is_newer || !current.IsCompatible {
+                        updated.push(self.install_component_release(latest)?);
+                    }
+                }
+                None if !current.IsCompatible => {
+                    warn!(
+                        "Component '{}' ({}) is not compatible with version {} of the app, and no compatible release was found.",
+                        current.ComponentId, current.Version, app_version
+                    );
+                }
+                None => {}
+            }
+        }
+        Ok(updated)
+    }
+
+    async_variant! {
+        /// Updates every installed optional component to the newest release which is compatible with the current version of the
+        /// app. This should be called after the app has been updated (eg. when it is restarted after an update), so that components
+        /// which were built for the previous version are replaced by compatible ones. Returns the components which were changed.
+        update_components_async => update_components() -> Vec<InstalledComponent>
+    }
+
+    fn install_component_release(&self, component: &ComponentAsset) -> Result<InstalledComponent, Error> {
+        let temp_dir = self.locator.get_temp_dir_rand16();
+        fs::create_dir_all(&temp_dir)?;
+        let package = temp_dir.join("component.zip");
+        info!("Downloading component '{}' version {}.", component.ComponentId, component.Asset.Version);
+        let result = self
+            .source
+            .download_release_entry_with_events(&component.Asset, &package.to_string_lossy(), None, None)
+            .and_then(|_| download::verify_asset_file(&component.Asset, &package))
+            .and_then(|_| {
+                let components_dir = self.locator.get_components_dir();
+                components::install_from_package(&components_dir, component, &package, &self.locator.get_manifest_version())
+            });
+        let _ = fs::remove_dir_all(&temp_dir);
+        result
     }
 
     /// Downloads the specified updates to the local app packages directory. Progress is reported back to the caller via an optional Sender.
@@ -295,6 +1024,32 @@ impl UpdateManager {
     /// - If there is no delta update available, or there is an error preparing delta
     ///   packages, this method will fall back to downloading the full version of the update.
     pub fn download_updates(&self, update: &UpdateInfo, progress: Option<Sender<i16>>) -> Result<(), Error> {
+        self.download_updates_with_events(update, progress.map(progress::forward_percent))
+    }
+
+    /// Downloads the specified updates to the local app packages directory, the same as [`UpdateManager::download_updates`],
+    /// but reports detailed progress events (bytes transferred, speed, and estimated time remaining) via an optional Sender.
+    pub fn download_updates_with_events(&self, update: &UpdateInfo, progress: Option<Sender<ProgressEvent>>) -> Result<(), Error> {
+        self.download_updates_cancellable(update, &CancellationToken::new(), progress)
+    }
+
+    /// Downloads the specified updates to the local app packages directory, the same as [`UpdateManager::download_updates_with_events`],
+    /// but the download can be aborted at any time by cancelling the provided token from another thread. If the download
+    /// is cancelled, any partially downloaded file is removed and this returns [`Error::Cancelled`].
+    pub fn download_updates_cancellable(
+        &self,
+        update: &UpdateInfo,
+        cancel: &CancellationToken,
+        progress: Option<Sender<ProgressEvent>>,
+    ) -> Result<(), Error> {
+        let start = Instant::now();
+        let result = self.download_updates_impl(update, cancel, progress);
+        self.notify(|o| o.on_download_completed(&update.TargetFullRelease, result.as_ref().map_err(ErrorClass::from).copied(), start.elapsed()));
+        result
+    }
+
+    fn download_updates_impl(&self, update: &UpdateInfo, cancel: &CancellationToken, progress: Option<Sender<ProgressEvent>>) -> Result<(), Error> {
+        let _guard = self.begin_operation(UpdateOperation::Download)?;
         let name = &update.TargetFullRelease.FileName;
         let packages_dir = &self.locator.get_packages_dir();
 
@@ -302,38 +1057,58 @@ impl UpdateManager {
         let target_file = packages_dir.join(name);
 
         if target_file.exists() {
-            info!("Package already exists on disk, skipping download: '{}'", target_file.to_string_lossy());
-            return Ok(());
-        }
-
-        let g = format!("{}/*.nupkg", packages_dir.to_string_lossy());
-        info!("Searching for packages to clean in: '{}'", g);
-        let mut to_delete = Vec::new();
-        match glob::glob(&g) {
-            Ok(paths) => {
-                for path in paths {
-                    if let Ok(path) = path {
-                        to_delete.push(path.clone());
-                        debug!("Will delete: '{}'", path.to_string_lossy());
-                    }
+            match download::verify_asset_file(&update.TargetFullRelease, &target_file) {
+                Ok(()) => {
+                    info!("Package already exists on disk, skipping download: '{}'", target_file.to_string_lossy());
+                    return Ok(());
+                }
+                Err(e) => {
+                    warn!("Package already exists on disk but is not valid, it will be downloaded again: {}", e);
+                    util::retry_io(|| fs::remove_file(&target_file))?;
                 }
-            }
-            Err(e) => {
-                error!("Error while searching for packages to clean: {}", e);
             }
         }
 
-        self.source.download_release_entry(&update.TargetFullRelease, &target_file.to_string_lossy(), progress)?;
+        let shared_cache = self.options.SharedCacheDir.as_ref().map(|dir| SharedCache::new(dir, self.options.SharedCacheMaxSizeBytes));
+        let cached = match &shared_cache {
+            Some(cache) => cache.try_get(&update.TargetFullRelease, &target_file).unwrap_or_else(|e| {
+                warn!("Unable to read from the shared cache: {}", e);
+                false
+            }),
+            None => false,
+        };
+
+        if cached {
+            info!("Package was found in the shared cache, skipping download: '{}'", target_file.to_string_lossy());
+        } else {
+            if self.download_update_chunks(&update.TargetFullRelease, &target_file, cancel, progress.clone())? {
+                info!("Package was assembled from chunks: '{}'", target_file.to_string_lossy());
+            } else {
+                let downloaded = self
+                    .source
+                    .download_release_entry_with_events(&update.TargetFullRelease, &target_file.to_string_lossy(), Some(cancel), progress)
+                    .and_then(|_| download::verify_asset_file(&update.TargetFullRelease, &target_file));
+                if let Err(e) = downloaded {
+                    // never leave a partial package behind, or it would be mistaken for a complete download next time
+                    let _ = fs::remove_file(&target_file);
+                    return Err(e);
+                }
+            }
+            if let Some(cache) = &shared_cache {
+                if let Err(e) = cache.insert(&update.TargetFullRelease, &target_file) {
+                    warn!("Unable to add the package to the shared cache: {}", e);
+                }
+            }
+        }
         info!("Successfully placed file: '{}'", target_file.to_string_lossy());
 
-        // extract new Update.exe on Windows only
+        // replace Update.exe with the one in the package on Windows only, if it is newer and works
         #[cfg(target_os = "windows")]
         match crate::bundle::load_bundle_from_file(&target_file) {
             Ok(bundle) => {
                 info!("Bundle loaded successfully.");
-                let update_exe_path = self.locator.get_update_path();
-                if let Err(e) = bundle.extract_zip_predicate_to_path(|f| f.ends_with("Squirrel.exe"), update_exe_path) {
-                    error!("Error extracting Update.exe from bundle: {}", e);
+                if let Err(e) = crate::updater::stage_updater_from_bundle(&bundle, &self.locator.get_update_path()) {
+                    error!("Error replacing Update.exe from bundle: {}", e);
                 }
             }
             Err(e) => {
@@ -341,40 +1116,183 @@ impl UpdateManager {
             }
         }
 
-        for path in to_delete {
-            info!("Cleaning up old package: '{}'", path.to_string_lossy());
-            let _ = fs::remove_file(&path);
+        // the package for the installed version is kept until the update is applied, in case applying it fails
+        let protected = [target_file, self.locator.get_ideal_local_nupkg_path(None, None)];
+        if let Err(e) = retention::enforce(packages_dir, &RetentionPolicy::from_options(&self.options), &protected) {
+            warn!("Unable to clean up old packages: {}", e);
         }
 
         Ok(())
     }
 
+    /// Tries to download a package by reusing the chunks it has in common with the packages already on disk, and returns
+    /// false if that is not possible (eg. there are no packages on disk, or the source does not publish chunk indexes), in
+    /// which case the package should be downloaded in full. Feeds built by vpk do not publish chunk indexes, so once a
+    /// feed is found without one, it is not asked for another until CHUNKS_UNAVAILABLE_RETRY_SECS have passed.
+    fn download_update_chunks(
+        &self,
+        asset: &VelopackAsset,
+        target_file: &Path,
+        cancel: &CancellationToken,
+        progress: Option<Sender<ProgressEvent>>,
+    ) -> Result<bool, Error> {
+        let seeds: Vec<PathBuf> = fs::read_dir(self.locator.get_packages_dir())?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path != target_file)
+            .filter(|path| bundle::parse_package_file_path(path).is_some_and(|info| !info.is_delta))
+            .collect();
+        if seeds.is_empty() {
+            return Ok(false);
+        }
+
+        let channel = self.get_channel();
+        let now = util::get_unix_time().unwrap_or_default();
+        let unavailable_since = self.locator.get_chunks_unavailable_since(&channel);
+        if unavailable_since.is_some_and(|since| now.saturating_sub(since) < CHUNKS_UNAVAILABLE_RETRY_SECS) {
+            debug!("The feed of channel '{}' does not publish chunk indexes, '{}' will be downloaded in full.", channel, asset.FileName);
+            return Ok(false);
+        }
+
+        let temp_dir = self.locator.get_temp_dir_rand16();
+        let index = match chunks::download_chunk_index(self.source.as_ref(), asset, &temp_dir, Some(cancel)) {
+            Ok(index) => index,
+            Err(Error::Cancelled) => {
+                let _ = fs::remove_dir_all(&temp_dir);
+                return Err(Error::Cancelled);
+            }
+            Err(e) => {
+                let _ = fs::remove_dir_all(&temp_dir);
+                info!("There is no chunk index for '{}', it will be downloaded in full: {}", asset.FileName, e);
+                if let Err(e) = self.locator.set_chunks_unavailable_since(&channel, Some(now)) {
+                    warn!("Unable to record that the release feed does not publish chunk indexes: {}", e);
+                }
+                return Ok(false);
+            }
+        };
+        if unavailable_since.is_some() {
+            let _ = self.locator.set_chunks_unavailable_since(&channel, None);
+        }
+
+        let result = chunks::download_with_chunks(self.source.as_ref(), asset, &index, &seeds, target_file, Some(cancel), progress);
+        let _ = fs::remove_dir_all(&temp_dir);
+        match result {
+            Ok(stats) => {
+                info!("Downloaded {} bytes, and reused {} bytes from packages on disk.", stats.downloaded_bytes, stats.reused_bytes);
+                Ok(true)
+            }
+            Err(Error::Cancelled) => {
+                let _ = fs::remove_file(target_file);
+                Err(Error::Cancelled)
+            }
+            Err(e) => {
+                info!("Unable to download '{}' in chunks, it will be downloaded in full: {}", asset.FileName, e);
+                let _ = fs::remove_file(target_file);
+                Ok(false)
+            }
+        }
+    }
+
+    async_variant! {
+        /// Downloads the specified updates to the local app packages directory. Progress is reported back to the caller via an optional Sender.
+        /// This function will acquire a global update lock so may fail if there is already another update operation in progress.
+        /// - If the update contains delta packages and the delta feature is enabled
+        ///   this method will attempt to unpack and prepare them.
+        /// - If there is no delta update available, or there is an error preparing delta
+        ///   packages, this method will fall back to downloading the full version of the update.
+        download_updates_async => download_updates(update: &UpdateInfo; progress: i16) -> ()
+    }
+
+    async_variant! {
+        /// Downloads the specified updates to the local app packages directory, the same as [`UpdateManager::download_updates`],
+        /// but reports detailed progress events (bytes transferred, speed, and estimated time remaining) via an optional Sender.
+        download_updates_with_events_async => download_updates_with_events(update: &UpdateInfo; progress: ProgressEvent) -> ()
+    }
+
+    async_variant! {
+        /// Downloads the specified updates to the local app packages directory, the same as [`UpdateManager::download_updates_with_events`],
+        /// but the download can be aborted at any time by cancelling the provided token from another thread. If the download
+        /// is cancelled, any partially downloaded file is removed and this returns [`Error::Cancelled`].
+        download_updates_cancellable_async => download_updates_cancellable(update: &UpdateInfo, cancel: &CancellationToken; progress: ProgressEvent) -> ()
+    }
+
     #[cfg(feature = "async")]
-    /// Downloads the specified updates to the local app packages directory. Progress is reported back to the caller via an optional Sender.
-    /// This function will acquire a global update lock so may fail if there is already another update operation in progress.
-    /// - If the update contains delta packages and the delta feature is enabled
-    ///   this method will attempt to unpack and prepare them.
-    /// - If there is no delta update available, or there is an error preparing delta
-    ///   packages, this method will fall back to downloading the full version of the update.
-    pub fn download_updates_async(&self, update: &UpdateInfo, progress: Option<AsyncSender<i16>>) -> JoinHandle<Result<(), Error>> {
-        let mut sync_progress: Option<Sender<i16>> = None;
-
-        if let Some(async_sender) = progress {
-            let (sync_sender, sync_receiver) = std::sync::mpsc::channel::<i16>();
-            sync_progress = Some(sync_sender);
-
-            // Spawn an async task to bridge from sync to async
-            async_std::task::spawn(async move {
-                for progress_value in sync_receiver {
-                    // Send to the async_std channel, ignore errors (e.g., receiver dropped)
-                    let _ = async_sender.send(progress_value).await;
+    /// Downloads the specified updates to the local app packages directory, returning a [`futures_core::Stream`] of
+    /// progress values (0-100) alongside the download. The download runs whether or not it is awaited, the stream ends
+    /// when it completes or fails, and the result of the download is returned by awaiting the future.
+    pub fn download_updates_stream(
+        &self,
+        update: &UpdateInfo,
+    ) -> (AsyncReceiver<i16>, impl Future<Output = Result<(), Error>> + Send + 'static) {
+        let (sender, receiver) = futures_channel::mpsc::unbounded::<i16>();
+        (receiver, self.download_updates_async(update, Some(sender)))
+    }
+
+    /// Extracts a downloaded update into the staging directory while your app is still running, so that the next
+    /// time your app is started the update can be swapped in almost instantly. Progress of the extraction is
+    /// reported back to the caller via an optional Sender. This is currently only supported on Windows.
+    pub fn stage_updates<A>(&self, to_apply: A, progress: Option<Sender<i16>>) -> Result<(), Error>
+    where
+        A: AsRef<VelopackAsset>,
+    {
+        let to_apply = to_apply.as_ref();
+        let start = Instant::now();
+        let result = self.stage_updates_impl(to_apply, progress);
+        self.notify(|o| o.on_stage_completed(to_apply, result.as_ref().map_err(ErrorClass::from).copied(), start.elapsed()));
+        result
+    }
+
+    fn stage_updates_impl(&self, to_apply: &VelopackAsset, progress: Option<Sender<i16>>) -> Result<(), Error> {
+        let _guard = self.begin_operation(UpdateOperation::Stage)?;
+        #[cfg(target_os = "windows")]
+        {
+            let pkg_path = self.locator.get_packages_dir().join(&to_apply.FileName);
+            let bundle = crate::bundle::load_bundle_from_file(&pkg_path)?;
+
+            // extract somewhere temporary first, so that a partially extracted update is never picked up
+            let temp_dir = self.locator.get_temp_dir_rand16();
+            let staged_dir = self.locator.get_staged_dir();
+            fs::create_dir_all(&temp_dir)?;
+            info!("Staging update {} to '{}'", to_apply.Version, staged_dir.to_string_lossy());
+            let extracted = bundle.extract_lib_contents_to_path(&temp_dir, |p| {
+                if let Some(progress) = &progress {
+                    let _ = progress.send(p);
                 }
             });
+            if let Err(e) = extracted {
+                let _ = fs::remove_dir_all(&temp_dir);
+                return Err(e);
+            }
+
+            if staged_dir.exists() {
+                fs::remove_dir_all(&staged_dir)?;
+            }
+            util::retry_io(|| fs::rename(&temp_dir, &staged_dir))?;
+            info!("Update staged successfully, it will be applied the next time the app starts.");
+            Ok(())
         }
+        #[cfg(not(target_os = "windows"))]
+        {
+            let _ = (to_apply, progress);
+            Err(Error::Unsupported("Staging updates is only supported on Windows.".to_owned()))
+        }
+    }
 
-        let self_clone = self.clone();
-        let update_clone = update.clone();
-        async_std::task::spawn_blocking(move || self_clone.download_updates(&update_clone, sync_progress))
+    async_variant! {
+        /// Extracts a downloaded update into the staging directory while your app is still running, so that the next
+        /// time your app is started the update can be swapped in almost instantly. Progress of the extraction is
+        /// reported back to the caller via an optional Sender. This is currently only supported on Windows.
+        stage_updates_async => stage_updates(to_apply: &VelopackAsset; progress: i16) -> ()
+    }
+
+    /// Extracts a downloaded update into the staging directory, the same as [`UpdateManager::stage_updates`],
+    /// but reports progress as events in the Extract phase via an optional Sender.
+    pub fn stage_updates_with_events<A>(&self, to_apply: A, progress: Option<Sender<ProgressEvent>>) -> Result<(), Error>
+    where
+        A: AsRef<VelopackAsset>,
+    {
+        let percent_sender = progress.map(|s| progress::percent_to_events(ProgressPhase::Extract, None, s));
+        self.stage_updates(to_apply, percent_sender)
     }
 
     /// This will exit your app immediately, apply updates, and then relaunch the app.
@@ -422,7 +1340,33 @@ impl UpdateManager {
         S: AsRef<str>,
         C: IntoIterator<Item=S>,
     {
-        let to_apply = to_apply.as_ref();
+        self.spawn_apply_waiting_for_exit(to_apply.as_ref(), silent, restart, restart_args, false)
+    }
+
+    /// Queue an update to be applied whenever your app exits. Unlike wait_exit_then_apply_updates, your app can
+    /// call this as soon as the update has been downloaded and keep running for as long as it likes; the updater
+    /// will wait in the background until the app exits, then apply the update and optionally restart the app.
+    pub fn apply_updates_on_exit<A, C, S>(&self, to_apply: A, silent: bool, restart: bool, restart_args: C) -> Result<(), Error>
+    where
+        A: AsRef<VelopackAsset>,
+        S: AsRef<str>,
+        C: IntoIterator<Item=S>,
+    {
+        self.spawn_apply_waiting_for_exit(to_apply.as_ref(), silent, restart, restart_args, true)
+    }
+
+    /// Update.exe locates the packages directory on its own, so a directory set in UpdateOptions is passed along to it.
+    fn set_packages_dir_env(&self, p: &mut ProcessCommand) {
+        if let Some(dir) = self.options.PackagesDir.as_deref().filter(|d| !d.trim().is_empty()) {
+            p.env.push((PACKAGES_DIR_ENV.to_owned(), dir.to_owned()));
+        }
+    }
+
+    fn spawn_apply_waiting_for_exit<C, S>(&self, to_apply: &VelopackAsset, silent: bool, restart: bool, restart_args: C, on_exit: bool) -> Result<(), Error>
+    where
+        S: AsRef<str>,
+        C: IntoIterator<Item=S>,
+    {
         let pkg_path = self.locator.get_packages_dir().join(&to_apply.FileName);
         let pkg_path_str = pkg_path.to_string_lossy();
 
@@ -430,6 +1374,9 @@ impl UpdateManager {
         args.push("apply".to_string());
         args.push("--waitPid".to_string());
         args.push(format!("{}", std::process::id()));
+        if on_exit {
+            args.push("--onExit".to_string());
+        }
         args.push("--package".to_string());
         args.push(pkg_path_str.into_owned());
 
@@ -439,6 +1386,7 @@ impl UpdateManager {
         if !restart {
             args.push("--norestart".to_string());
         }
+        args.extend(RetentionPolicy::from_options(&self.options).to_apply_args());
 
         let restart_args: Vec<String> = restart_args.into_iter().map(|item| item.as_ref().to_string()).collect();
 
@@ -449,18 +1397,257 @@ impl UpdateManager {
             }
         }
 
-        let mut p = Process::new(&self.locator.get_update_path());
-        p.args(&args);
-        p.current_dir(&self.locator.get_root_dir());
-
-        #[cfg(target_os = "windows")]
-        {
-            const CREATE_NO_WINDOW: u32 = 0x08000000;
-            p.creation_flags(CREATE_NO_WINDOW);
+        let mut p = ProcessCommand::new(self.locator.get_update_path());
+        p.args = args.clone();
+        p.current_dir = Some(self.locator.get_root_dir());
+        self.set_packages_dir_env(&mut p);
+        // the package is checked again before it is applied, in case it was changed after it was downloaded
+        if !to_apply.SHA256.is_empty() {
+            p.env.push((PACKAGE_SHA256_ENV.to_owned(), to_apply.SHA256.clone()));
         }
 
         info!("About to run Update.exe: {} {:?}", self.locator.get_update_path_as_string(), args);
-        p.spawn()?;
-        Ok(())
+        let result = self.begin_operation(UpdateOperation::Apply).and_then(|_guard| self.launcher.spawn(&p));
+        self.notify(|o| o.on_apply_started(to_apply, result.as_ref().map_err(ErrorClass::from).copied()));
+        result
     }
-}
\ No newline at end of file
+
+    /// Launch the updater in the background and connect to it over a local IPC channel. The updater will
+    /// check for updates from the specified url or local directory, and will exit when this process exits.
+    /// Commands such as check, download, and apply-on-exit can be sent with IpcConnection::send, and
+    /// results and progress events are read with IpcConnection::receive.
+    pub fn start_updater_ipc(&self, url_or_path: &str) -> Result<IpcConnection, Error> {
+        let host_pid = std::process::id();
+        let name = ipc::get_ipc_name(&self.get_install_id(), host_pid);
+
+        let mut p = ProcessCommand::new(self.locator.get_update_path());
+        p.args = ["serve", "--source", url_or_path, "--hostPid", &host_pid.to_string()].map(str::to_owned).to_vec();
+        p.current_dir = Some(self.locator.get_root_dir());
+        self.set_packages_dir_env(&mut p);
+
+        info!("About to run Update.exe: {} serve", self.locator.get_update_path_as_string());
+        self.launcher.spawn(&p)?;
+
+        // the updater needs a moment to start up and create the channel
+        let mut last_error = None;
+        for _ in 0..50 {
+            match IpcConnection::connect(&name) {
+                Ok(conn) => return Ok(conn),
+                Err(e) => last_error = Some(e),
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        Err(last_error.unwrap())
+    }
+}
+
+impl From<&VelopackAsset> for ReleaseNotes {
+    fn from(asset: &VelopackAsset) -> Self {
+        ReleaseNotes { Version: asset.Version.clone(), NotesMarkdown: asset.NotesMarkdown.clone(), NotesHtml: asset.NotesHtml.clone() }
+    }
+}
+
+/// Returns the release notes for every full release newer than the current version, up to and including the target version,
+/// ordered from newest to oldest.
+fn collect_release_notes(assets: &[VelopackAsset], current: &Version, target: &Version) -> Vec<ReleaseNotes> {
+    let mut releases: Vec<(Version, &VelopackAsset)> = assets
+        .iter()
+        .filter(|a| a.Type.eq_ignore_ascii_case("Full"))
+        .filter_map(|a| Version::parse(&a.Version).ok().map(|v| (v, a)))
+        .filter(|(v, _)| version::is_newer(v, current) && !version::is_newer(v, target))
+        .collect();
+    releases.sort_by(|a, b| version::compare(&b.0, &a.0));
+    releases.dedup_by(|a, b| version::is_same(&a.0, &b.0));
+    releases.into_iter().map(|(_, a)| ReleaseNotes::from(a)).collect()
+}
+
+/// Returns true if any full release newer than the current version, up to and including the target version, is mandatory.
+/// A mandatory release which was published between two optional ones must still be installed, so it makes the whole update mandatory.
+fn contains_mandatory_release(assets: &[VelopackAsset], current: &Version, target: &Version) -> bool {
+    assets
+        .iter()
+        .filter(|a| a.IsMandatory && a.Type.eq_ignore_ascii_case("Full"))
+        .filter_map(|a| Version::parse(&a.Version).ok())
+        .any(|v| version::is_newer(&v, current) && !version::is_newer(&v, target))
+}
+
+/// Returns true if a release of the installed version suits this machine better than the installed release does. This is
+/// only the case if the architecture of the installed release is known, so apps packaged without one are never switched.
+fn is_better_arch(remote: &VelopackAsset, installed_arch: &str, machine_arch: Option<arch::PackageArch>) -> bool {
+    let remote_parsed = arch::PackageArch::parse(&remote.Architecture);
+    let installed_parsed = arch::PackageArch::parse(installed_arch);
+    if remote_parsed.is_none() || installed_parsed.is_none() {
+        return false;
+    }
+    let remote_preference = arch::get_arch_preference(&remote.Architecture, machine_arch);
+    let installed_preference = arch::get_arch_preference(installed_arch, machine_arch);
+    remote_parsed != installed_parsed && remote_preference > installed_preference
+}
+
+#[cfg(feature = "async")]
+/// Runs a blocking operation on a thread of its own, and returns a future which resolves to its result. The future is
+/// woken by a channel rather than a runtime, so it can be awaited from any executor (eg. tokio or async-std), and the
+/// operation does not occupy one of the executor's threads.
+fn spawn_blocking<T, F>(operation: F) -> impl Future<Output = Result<T, Error>> + Send + 'static
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, Error> + Send + 'static,
+{
+    let (sender, receiver) = futures_channel::oneshot::channel();
+    thread::spawn(move || {
+        // ignore errors, the future may have been dropped if the caller does not care about the result
+        let _ = sender.send(operation());
+    });
+    async move { receiver.await.unwrap_or_else(|_| Err(Error::Generic("The update operation panicked.".to_owned()))) }
+}
+
+#[cfg(feature = "async")]
+/// The blocking operations report progress on a std channel, so forward it to the async channel from a
+/// dedicated thread rather than blocking one of the executor's threads while waiting for progress.
+fn bridge_progress_to_async<T: Send + 'static>(async_sender: AsyncSender<T>) -> Sender<T> {
+    let (sync_sender, sync_receiver) = std::sync::mpsc::channel::<T>();
+    thread::spawn(move || {
+        for progress_value in sync_receiver {
+            // ignore errors, the receiver may have been dropped if the caller does not care about progress
+            let _ = async_sender.unbounded_send(progress_value);
+        }
+    });
+    sync_sender
+}
+
+#[cfg(all(test, feature = "async"))]
+/// Polls a future on the current thread until it completes, without any async runtime.
+fn block_on<F: Future>(future: F) -> F::Output {
+    use std::task::{Context, Poll, Wake};
+
+    struct ThreadWaker(thread::Thread);
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let mut future = std::pin::pin!(future);
+    let waker = Arc::new(ThreadWaker(thread::current())).into();
+    let mut context = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+        thread::park();
+    }
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn test_bridge_progress_to_async_forwards_all_values() {
+    let (sender, mut receiver) = futures_channel::mpsc::unbounded::<i16>();
+    let sync_sender = bridge_progress_to_async(sender);
+    for p in [0, 50, 100] {
+        sync_sender.send(p).unwrap();
+    }
+    drop(sync_sender);
+
+    let received = block_on(async move {
+        let mut values = Vec::new();
+        while let Some(v) = std::future::poll_fn(|cx| futures_core::Stream::poll_next(std::pin::Pin::new(&mut receiver), cx)).await {
+            values.push(v);
+        }
+        values
+    });
+    assert_eq!(received, vec![0, 50, 100]);
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn test_async_operations_do_not_need_a_runtime() {
+    let app = crate::testing::TestApp::new("TestApp", "1.0.0").unwrap();
+    let source = crate::testing::TestSource::new().with_release("TestApp", "2.0.0", b"v2".to_vec());
+    let um = app.create_manager(source, None, crate::testing::RecordingLauncher::new()).unwrap();
+    let update = match block_on(um.check_for_updates_async()).unwrap() {
+        UpdateCheck::UpdateAvailable(update) => update,
+        _ => panic!("expected an update"),
+    };
+    let (progress, download) = um.download_updates_stream(&update);
+    block_on(download).unwrap();
+    assert!(app.get_root_dir().join("packages").join("TestApp-2.0.0-full.nupkg").is_file());
+    drop(progress);
+
+    assert!(matches!(block_on(spawn_blocking(|| -> Result<(), Error> { panic!("failed") })), Err(Error::Generic(_))));
+}
+
+#[test]
+fn test_collect_release_notes_includes_intermediate_versions() {
+    let asset = |version: &str, kind: &str, notes: &str| VelopackAsset {
+        Version: version.to_owned(),
+        Type: kind.to_owned(),
+        NotesMarkdown: notes.to_owned(),
+        ..Default::default()
+    };
+    let assets = vec![
+        asset("1.0.0", "Full", "initial"),
+        asset("1.1.0", "Full", "one one"),
+        asset("1.1.0", "Delta", "one one delta"),
+        asset("1.2.0", "Full", "one two"),
+        asset("2.0.0", "Full", "two"),
+    ];
+    let notes = collect_release_notes(&assets, &Version::parse("1.0.0").unwrap(), &Version::parse("1.2.0").unwrap());
+    let versions: Vec<&str> = notes.iter().map(|n| n.Version.as_str()).collect();
+    assert_eq!(versions, vec!["1.2.0", "1.1.0"]);
+    assert_eq!(notes[1].NotesMarkdown, "one one");
+}
+
+#[test]
+fn test_side_by_side_app_keeps_its_id_and_can_not_switch_channel() {
+    use crate::testing::{RecordingLauncher, TestApp, TestSource};
+    let manifest = crate::bundle::Manifest {
+        id: "TestApp".to_owned(),
+        version: Version::new(1, 0, 0),
+        main_exe: "TestApp.exe".to_owned(),
+        channel: "beta".to_owned(),
+        side_by_side: "true".to_owned(),
+        ..Default::default()
+    };
+    let app = TestApp::from_manifest(manifest).unwrap();
+    let mut um = app.create_manager(TestSource::new(), None, RecordingLauncher::new()).unwrap();
+    assert_eq!(um.get_app_id(), "TestApp");
+    assert_eq!(um.get_install_id(), "TestApp-beta");
+    assert!(matches!(um.switch_channel("stable", true), Err(Error::Unsupported(_))));
+}
+
+#[test]
+fn test_operations_fail_while_another_process_holds_the_lock() {
+    use crate::testing::{RecordingLauncher, TestApp, TestSource};
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let um = app.create_manager(TestSource::new(), None, RecordingLauncher::new()).unwrap();
+    let asset = VelopackAsset { FileName: "TestApp-1.1.0-full.nupkg".to_owned(), ..Default::default() };
+
+    let lock = FileLock::try_acquire(&um.locator.get_lock_file_path()).unwrap().unwrap();
+    let result = um.wait_exit_then_apply_updates(&asset, true, false, Vec::<String>::new());
+    match result {
+        Err(Error::Locked(owner)) => assert!(owner.contains(&std::process::id().to_string())),
+        other => panic!("expected Error::Locked, got {:?}", other),
+    }
+    assert_eq!(ErrorClass::from(&Error::Locked(String::new())), ErrorClass::Busy);
+
+    drop(lock);
+    um.wait_exit_then_apply_updates(&asset, true, false, Vec::<String>::new()).unwrap();
+}
+
+#[test]
+fn test_only_one_operation_runs_at_a_time() {
+    let dir = crate::testing::TempDir::new("velopack_operations").unwrap();
+    let root = dir.to_path_buf();
+    let guard = OperationGuard::begin(root.clone(), UpdateOperation::Download).unwrap();
+    assert_eq!(OperationGuard::current(&root), Some(UpdateOperation::Download));
+    let second = OperationGuard::begin(root.clone(), UpdateOperation::Apply);
+    assert!(matches!(second, Err(Error::OperationInProgress(UpdateOperation::Download))));
+
+    // operations for other apps are tracked separately
+    let other = OperationGuard::begin(root.join("other"), UpdateOperation::Stage).unwrap();
+    drop(other);
+
+    drop(guard);
+    assert_eq!(OperationGuard::current(&root), None);
+    assert!(OperationGuard::begin(root, UpdateOperation::Apply).is_ok());
+}
@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+use crate::{
+    manager::{UpdateCheck, VelopackAsset},
+    Error,
+};
+
+/// A broad classification of an error, which can be recorded in analytics without including any details
+/// (such as file paths or urls) from the error message itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorClass {
+    /// The update source could not be reached, or returned an error.
+    Network,
+    /// A file could not be read or written.
+    Io,
+    /// A package or release feed was corrupt or could not be parsed.
+    InvalidData,
+    /// The app is not installed, or is missing some of the files Velopack needs.
+    NotInstalled,
+    /// The operation was cancelled by the caller.
+    Cancelled,
+    /// Another update operation was already in progress, in this process or in an installer or updater.
+    Busy,
+    /// Any other error.
+    Other,
+}
+
+impl From<&Error> for ErrorClass {
+    fn from(error: &Error) -> Self {
+        match error {
+            Error::Network(_) => ErrorClass::Network,
+            Error::Io(_) | Error::FileNotFound(_) => ErrorClass::Io,
+            Error::Zip(_)
+            | Error::Json(_)
+            | Error::Semver(_)
+            | Error::HashMismatch(_)
+            | Error::FeedParse(_)
+            | Error::InvalidNuspec(_) => ErrorClass::InvalidData,
+            Error::MissingNuspec
+            | Error::MissingNuspecProperty(_)
+            | Error::MissingUpdateExe
+            | Error::NotInstalled(_)
+            | Error::AppTranslocated(_) => ErrorClass::NotInstalled,
+            Error::Cancelled => ErrorClass::Cancelled,
+            Error::OperationInProgress(_) | Error::Locked(_) => ErrorClass::Busy,
+            Error::Generic(_) | Error::DowngradeNotConfirmed(_) | Error::Unsupported(_) | Error::UpdatesManagedExternally(_) => {
+                ErrorClass::Other
+            }
+        }
+    }
+}
+
+/// Receives a notification at key moments of the update process, so that an app can feed its own analytics or
+/// telemetry without scraping the log. Velopack never sends any of this data anywhere by itself. Every method has
+/// an empty default implementation, so only the notifications of interest need to be implemented.
+///
+/// Notifications are delivered on the thread which is running the operation, so implementations should return
+/// quickly and must not call back into the UpdateManager.
+pub trait UpdateObserver: Send + Sync {
+    /// Called when a check for updates begins.
+    fn on_check_started(&self) {}
+
+    /// Called when a check for updates has finished, with the result of the check or the class of error which caused it to fail.
+    fn on_check_completed(&self, _result: Result<&UpdateCheck, ErrorClass>, _duration: Duration) {}
+
+    /// Called when a package has finished downloading (or was already on disk), or the download has failed.
+    fn on_download_completed(&self, _asset: &VelopackAsset, _result: Result<(), ErrorClass>, _duration: Duration) {}
+
+    /// Called when the updater has been started to apply an update once the app exits, or could not be started. The update
+    /// has not been applied yet, and the updater does not report back whether it succeeded.
+    fn on_apply_started(&self, _asset: &VelopackAsset, _result: Result<(), ErrorClass>) {}
+
+    /// Called when an update has been extracted by stage_updates to be swapped in the next time the app starts, or this has failed.
+    fn on_stage_completed(&self, _asset: &VelopackAsset, _result: Result<(), ErrorClass>, _duration: Duration) {}
+}
@@ -0,0 +1,77 @@
+use std::{path::PathBuf, process::Command as Process};
+
+use crate::Error;
+
+/// A process to start, described as plain data so that it can be started by a [`ProcessLauncher`], or inspected in tests.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcessCommand {
+    /// The path to the executable.
+    pub program: PathBuf,
+    /// The arguments to pass to the executable.
+    pub args: Vec<String>,
+    /// The working directory of the process, or None to use the working directory of this process.
+    pub current_dir: Option<PathBuf>,
+    /// Environment variables to set for the process, in addition to the environment of this process.
+    pub env: Vec<(String, String)>,
+}
+
+impl ProcessCommand {
+    /// Create a new ProcessCommand which starts the specified executable without any arguments.
+    pub fn new<P: Into<PathBuf>>(program: P) -> ProcessCommand {
+        ProcessCommand { program: program.into(), ..Default::default() }
+    }
+
+    /// Returns true if the process is started with the specified argument.
+    pub fn has_arg(&self, arg: &str) -> bool {
+        self.args.iter().any(|a| a == arg)
+    }
+
+    /// Returns the argument following the specified one (eg. the value of an option such as --package), if there is one.
+    pub fn get_arg_value(&self, arg: &str) -> Option<&str> {
+        self.args.iter().position(|a| a == arg).and_then(|i| self.args.get(i + 1)).map(|a| a.as_str())
+    }
+}
+
+/// Abstraction for starting processes in the background, such as when UpdateManager starts Update.exe to apply an update.
+/// An implementation may start the process, or only record it, so that update logic can be tested without side effects.
+pub trait ProcessLauncher: Send + Sync {
+    /// Start the process without waiting for it to exit. On Windows, the process should not open a console window.
+    fn spawn(&self, command: &ProcessCommand) -> Result<(), Error>;
+    /// Clone the launcher to create a new lifetime.
+    fn clone_boxed(&self) -> Box<dyn ProcessLauncher>;
+}
+
+impl Clone for Box<dyn ProcessLauncher> {
+    fn clone(&self) -> Self {
+        self.clone_boxed()
+    }
+}
+
+/// The default launcher, which starts processes with std::process::Command.
+#[derive(Clone, Default)]
+pub struct DefaultProcessLauncher {}
+
+impl ProcessLauncher for DefaultProcessLauncher {
+    fn spawn(&self, command: &ProcessCommand) -> Result<(), Error> {
+        let mut p = Process::new(&command.program);
+        p.args(&command.args);
+        if let Some(dir) = &command.current_dir {
+            p.current_dir(dir);
+        }
+        p.envs(command.env.iter().map(|(k, v)| (k, v)));
+
+        #[cfg(target_os = "windows")]
+        {
+            use std::os::windows::process::CommandExt;
+            const CREATE_NO_WINDOW: u32 = 0x08000000;
+            p.creation_flags(CREATE_NO_WINDOW);
+        }
+
+        p.spawn()?;
+        Ok(())
+    }
+
+    fn clone_boxed(&self) -> Box<dyn ProcessLauncher> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    sync::mpsc::Sender,
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// The stage of an operation which a [`ProgressEvent`] refers to. Updates are applied by Update.exe after the app has
+/// exited, so there is no phase for applying them.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub enum ProgressPhase {
+    /// The update package is being downloaded from the update source.
+    Download,
+    /// The update package is being extracted to disk.
+    Extract,
+}
+
+/// A detailed progress report for a long running operation.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub struct ProgressEvent {
+    /// The stage of the operation which is in progress.
+    pub Phase: ProgressPhase,
+    /// The percentage complete of the current phase, from 0 to 100.
+    pub Percent: i16,
+    /// The number of bytes processed so far.
+    #[cfg_attr(feature = "typescript", ts(type = "number"))]
+    pub BytesDone: u64,
+    /// The total number of bytes to process, if known.
+    #[cfg_attr(feature = "typescript", ts(type = "number | null"))]
+    pub BytesTotal: Option<u64>,
+    /// The recent processing speed, in bytes per second.
+    #[cfg_attr(feature = "typescript", ts(type = "number"))]
+    pub BytesPerSecond: u64,
+    /// The estimated number of seconds remaining, if it can be calculated.
+    #[cfg_attr(feature = "typescript", ts(type = "number | null"))]
+    pub EtaSeconds: Option<u64>,
+}
+
+/// Computes speed and ETA for an operation which processes a stream of bytes, and decides when a new
+/// [`ProgressEvent`] is worth reporting (so that callers are not flooded with an event for every chunk).
+pub struct ProgressTracker {
+    phase: ProgressPhase,
+    total: Option<u64>,
+    done: u64,
+    last_percent: i16,
+    last_report: Option<Instant>,
+    sample_time: Instant,
+    sample_bytes: u64,
+    speed: f64,
+}
+
+impl ProgressTracker {
+    const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+    /// Start tracking a new operation. The total may be None if the size is not known up front.
+    pub fn new(phase: ProgressPhase, total: Option<u64>) -> Self {
+        ProgressTracker {
+            phase,
+            total: total.filter(|t| *t > 0),
+            done: 0,
+            last_percent: -1,
+            last_report: None,
+            sample_time: Instant::now(),
+            sample_bytes: 0,
+            speed: 0.0,
+        }
+    }
+
+    /// Record that more bytes have been processed. Returns an event if the percentage has changed, or if
+    /// enough time has passed since the last event that the speed / ETA should be refreshed.
+    pub fn advance(&mut self, bytes: u64) -> Option<ProgressEvent> {
+        self.done += bytes;
+        let now = Instant::now();
+
+        let elapsed = now.duration_since(self.sample_time);
+        if elapsed >= Self::SAMPLE_INTERVAL {
+            let instant_speed = (self.done - self.sample_bytes) as f64 / elapsed.as_secs_f64();
+            // smooth the speed so the ETA doesn't jump around with every chunk
+            self.speed = if self.speed == 0.0 { instant_speed } else { self.speed * 0.7 + instant_speed * 0.3 };
+            self.sample_time = now;
+            self.sample_bytes = self.done;
+        }
+
+        let percent = self.percent();
+        let stale = self.last_report.map_or(true, |t| now.duration_since(t) >= Self::SAMPLE_INTERVAL);
+        if percent > self.last_percent || stale {
+            self.last_percent = percent;
+            self.last_report = Some(now);
+            return Some(self.event());
+        }
+        None
+    }
+
+    /// Returns an event reporting the operation as complete.
+    pub fn finish(&mut self) -> ProgressEvent {
+        if self.total.is_none() {
+            self.total = Some(self.done);
+        }
+        self.done = self.total.unwrap_or(self.done);
+        let mut event = self.event();
+        event.Percent = 100;
+        event.EtaSeconds = Some(0);
+        event
+    }
+
+    fn percent(&self) -> i16 {
+        match self.total {
+            Some(total) => ((self.done as f64 / total as f64) * 100.0).clamp(0.0, 100.0).floor() as i16,
+            None => 0,
+        }
+    }
+
+    fn event(&self) -> ProgressEvent {
+        let eta = match self.total {
+            Some(total) if self.speed > 0.0 => Some((total.saturating_sub(self.done) as f64 / self.speed).ceil() as u64),
+            _ => None,
+        };
+        ProgressEvent {
+            Phase: self.phase,
+            Percent: self.percent(),
+            BytesDone: self.done,
+            BytesTotal: self.total,
+            BytesPerSecond: self.speed as u64,
+            EtaSeconds: eta,
+        }
+    }
+}
+
+/// Forwards only the percentage of each event to a sender which expects a bare percentage, skipping duplicates.
+pub(crate) fn forward_percent(sender: Sender<i16>) -> Sender<ProgressEvent> {
+    let (event_sender, event_receiver) = std::sync::mpsc::channel::<ProgressEvent>();
+    thread::spawn(move || {
+        let mut last = -1;
+        for event in event_receiver {
+            if event.Percent != last {
+                last = event.Percent;
+                let _ = sender.send(event.Percent);
+            }
+        }
+    });
+    event_sender
+}
+
+/// Converts bare percentages into events, for sources which can only report a percentage.
+pub(crate) fn percent_to_events(phase: ProgressPhase, total: Option<u64>, sender: Sender<ProgressEvent>) -> Sender<i16> {
+    let (percent_sender, percent_receiver) = std::sync::mpsc::channel::<i16>();
+    thread::spawn(move || {
+        let mut tracker = ProgressTracker::new(phase, total);
+        for percent in percent_receiver {
+            let event = match total {
+                Some(total) => {
+                    let target = total * percent.clamp(0, 100) as u64 / 100;
+                    tracker.advance(target.saturating_sub(tracker.done))
+                }
+                None => Some(ProgressEvent { Percent: percent, ..tracker.event() }),
+            };
+            if let Some(event) = event {
+                let _ = sender.send(event);
+            }
+        }
+    });
+    percent_sender
+}
+
+#[test]
+fn test_progress_tracker_reports_percent_changes() {
+    let mut tracker = ProgressTracker::new(ProgressPhase::Download, Some(1000));
+    let first = tracker.advance(100).unwrap();
+    assert_eq!(first.Percent, 10);
+    assert_eq!(first.BytesDone, 100);
+    assert_eq!(first.BytesTotal, Some(1000));
+
+    // no change in percent and not enough time has passed, so nothing to report
+    assert!(tracker.advance(1).is_none());
+    assert_eq!(tracker.advance(399).unwrap().Percent, 50);
+
+    let done = tracker.finish();
+    assert_eq!(done.Percent, 100);
+    assert_eq!(done.BytesDone, 1000);
+    assert_eq!(done.EtaSeconds, Some(0));
+}
+
+#[test]
+fn test_progress_tracker_computes_speed_and_eta() {
+    let mut tracker = ProgressTracker::new(ProgressPhase::Download, Some(10_000));
+    tracker.advance(0);
+    thread::sleep(Duration::from_millis(300));
+    let event = tracker.advance(1000).unwrap();
+    assert!(event.BytesPerSecond > 0);
+    assert!(event.EtaSeconds.unwrap() > 0);
+}
@@ -0,0 +1,282 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use semver::Version;
+
+use crate::{bundle, util, version, Error, VelopackAsset, VelopackAssetFeed};
+
+/// The release index of a single channel in a directory of packages, which is written to 'releases.{channel}.json' (read
+/// by Velopack apps) and to the legacy RELEASES file (read by apps which have not migrated to Velopack yet), in the same
+/// format vpk writes them.
+#[derive(Debug, Clone)]
+pub struct ReleaseIndex {
+    dir: PathBuf,
+    channel: String,
+    feed: VelopackAssetFeed,
+}
+
+impl ReleaseIndex {
+    /// Loads the existing index for a channel from a directory, or creates an empty index if there isn't one yet.
+    pub fn load<P: AsRef<Path>>(dir: P, channel: &str) -> Result<Self, Error> {
+        let dir = dir.as_ref().to_path_buf();
+        let index_path = dir.join(get_index_file_name(channel));
+        let feed = match fs::read_to_string(&index_path) {
+            Ok(json) => VelopackAssetFeed::from_json(&json)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => VelopackAssetFeed::default(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(ReleaseIndex { dir, channel: channel.to_owned(), feed })
+    }
+
+    /// Creates a new index for a channel from every package in a directory which belongs to that channel, ignoring any
+    /// index which already exists. Files which are not Velopack packages (eg. other NuGet packages) are skipped with a
+    /// warning, rather than failing the whole index.
+    pub fn from_directory<P: AsRef<Path>>(dir: P, channel: &str) -> Result<Self, Error> {
+        let dir = dir.as_ref().to_path_buf();
+        let mut index = ReleaseIndex { dir, channel: channel.to_owned(), feed: VelopackAssetFeed::default() };
+        let mut packages: Vec<PathBuf> = fs::read_dir(&index.dir)?
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|e| e.eq_ignore_ascii_case("nupkg")))
+            .collect();
+        packages.sort();
+
+        for path in packages {
+            let manifest = match bundle::load_bundle_from_file(&path).and_then(|mut b| b.read_manifest()) {
+                Ok(manifest) => manifest,
+                Err(e) => {
+                    warn!("Skipping '{}', it is not a Velopack package: {}", path.to_string_lossy(), e);
+                    continue;
+                }
+            };
+            if get_manifest_channel(&manifest) == channel {
+                index.add_package_with_manifest(&path, &manifest)?;
+            } else {
+                debug!("Skipping '{}', it belongs to channel '{}'.", path.to_string_lossy(), get_manifest_channel(&manifest));
+            }
+        }
+        Ok(index)
+    }
+
+    /// The channel this index is for.
+    pub fn channel(&self) -> &str {
+        &self.channel
+    }
+
+    /// The assets currently in the index.
+    pub fn feed(&self) -> &VelopackAssetFeed {
+        &self.feed
+    }
+
+    /// Adds a package (which must be in the index directory) to the index, replacing any asset with the same file name.
+    /// Returns an error if the package belongs to a different channel.
+    pub fn add_package<P: AsRef<Path>>(&mut self, package: P) -> Result<VelopackAsset, Error> {
+        let package = package.as_ref();
+        let manifest = bundle::load_bundle_from_file(package)?.read_manifest()?;
+        let package_channel = get_manifest_channel(&manifest);
+        if package_channel != self.channel {
+            return Err(Error::Generic(format!(
+                "'{}' belongs to channel '{}', and can not be added to the index of channel '{}'.",
+                package.to_string_lossy(),
+                package_channel,
+                self.channel
+            )));
+        }
+        self.add_package_with_manifest(package, &manifest)
+    }
+
+    fn add_package_with_manifest(&mut self, package: &Path, manifest: &bundle::Manifest) -> Result<VelopackAsset, Error> {
+        let file_name = package.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        if !self.dir.join(&file_name).is_file() {
+            return Err(Error::FileNotFound(self.dir.join(&file_name).to_string_lossy().to_string()));
+        }
+
+        let is_delta = bundle::parse_package_file_path(package).is_some_and(|i| i.is_delta);
+        let mut asset = VelopackAsset {
+            PackageId: manifest.id.clone(),
+            Version: manifest.version.to_string(),
+            Type: if is_delta { "Delta".to_owned() } else { "Full".to_owned() },
+            FileName: file_name,
+            NotesMarkdown: manifest.release_notes.clone(),
+            NotesHtml: manifest.release_notes_html.clone(),
+            IsMandatory: manifest.is_mandatory(),
+            Architecture: manifest.machine_architecture.clone(),
+            ..Default::default()
+        };
+        self.update_checksums(&mut asset)?;
+
+        info!("Adding '{}' to the release index of channel '{}'.", asset.FileName, self.channel);
+        self.feed.Assets.retain(|a| !a.FileName.eq_ignore_ascii_case(&asset.FileName));
+        self.feed.Assets.push(asset.clone());
+        Ok(asset)
+    }
+
+    /// Removes every asset of the versions older than the newest `keep_full_releases` full releases, and returns the assets
+    /// which were removed. The package files are not deleted.
+    pub fn prune(&mut self, keep_full_releases: usize) -> Vec<VelopackAsset> {
+        let full_assets = self.feed.Assets.iter().filter(|a| a.Type.eq_ignore_ascii_case("Full"));
+        let mut full_versions: Vec<Version> = full_assets.filter_map(|a| Version::parse(&a.Version).ok()).collect();
+        full_versions.sort_by(|a, b| version::compare(b, a));
+        full_versions.dedup_by(|a, b| version::is_same(a, b));
+
+        let oldest_kept = match full_versions.get(keep_full_releases.max(1) - 1) {
+            Some(v) => v.clone(),
+            None => return Vec::new(),
+        };
+
+        let (kept, removed): (Vec<_>, Vec<_>) = self.feed.Assets.drain(..).partition(|a| match Version::parse(&a.Version) {
+            Ok(v) => version::compare(&v, &oldest_kept) != std::cmp::Ordering::Less,
+            Err(_) => true,
+        });
+        self.feed.Assets = kept;
+        for asset in &removed {
+            info!("Pruned '{}' from the release index of channel '{}'.", asset.FileName, self.channel);
+        }
+        removed
+    }
+
+    /// Recalculates the size and checksums of every asset in the index from the package files, which is needed after the
+    /// packages were modified once they were added. This does not sign anything: packages which were re-signed by another
+    /// tool must be refreshed with this, or apps will reject them. Returns an error if a package is missing.
+    pub fn refresh_checksums(&mut self) -> Result<(), Error> {
+        let mut assets = std::mem::take(&mut self.feed.Assets);
+        let result = assets.iter_mut().try_for_each(|a| self.update_checksums(a));
+        self.feed.Assets = assets;
+        result
+    }
+
+    fn update_checksums(&self, asset: &mut VelopackAsset) -> Result<(), Error> {
+        let path = self.dir.join(&asset.FileName);
+        if !path.is_file() {
+            return Err(Error::FileNotFound(path.to_string_lossy().to_string()));
+        }
+        // vpk writes upper case hashes, and older apps compare the SHA256 case-sensitively
+        asset.SHA1 = util::calculate_file_sha1(&path)?.to_uppercase();
+        asset.SHA256 = util::calculate_file_sha256(&path)?.to_uppercase();
+        asset.Size = fs::metadata(&path)?.len();
+        Ok(())
+    }
+
+    /// Writes 'releases.{channel}.json' and the legacy RELEASES file for this channel to the index directory.
+    pub fn save(&mut self) -> Result<(), Error> {
+        self.feed.Assets.sort_by(|a, b| {
+            let version_a = Version::parse(&a.Version).unwrap_or_else(|_| Version::new(0, 0, 0));
+            let version_b = Version::parse(&b.Version).unwrap_or_else(|_| Version::new(0, 0, 0));
+            version::compare(&version_b, &version_a).then_with(|| a.Type.cmp(&b.Type))
+        });
+
+        let index_path = self.dir.join(get_index_file_name(&self.channel));
+        fs::write(&index_path, serde_json::to_string_pretty(&self.feed)?)?;
+        info!("Wrote {} assets to '{}'.", self.feed.Assets.len(), index_path.to_string_lossy());
+
+        let legacy_path = self.dir.join(get_legacy_releases_file_name(&self.channel));
+        fs::write(&legacy_path, self.to_legacy_releases_string())?;
+        Ok(())
+    }
+
+    /// Returns the contents of the legacy RELEASES file, which lists the full releases from oldest to newest. Versions
+    /// which the legacy format can't represent (with a '.' in the pre-release tag, or build metadata) are left out.
+    pub fn to_legacy_releases_string(&self) -> String {
+        let mut entries: Vec<(Version, &VelopackAsset)> = self
+            .feed
+            .Assets
+            .iter()
+            .filter(|a| a.Type.eq_ignore_ascii_case("Full"))
+            .filter_map(|a| Version::parse(&a.Version).ok().map(|v| (v, a)))
+            .filter(|(v, a)| {
+                let supported = !v.pre.as_str().contains('.') && v.build.is_empty();
+                if !supported {
+                    warn!("Excluding '{}' from the legacy RELEASES file, its version can not be represented.", a.FileName);
+                }
+                supported
+            })
+            .collect();
+        entries.sort_by(|a, b| version::compare(&a.0, &b.0));
+        entries.iter().map(|(_, a)| format!("{} {} {}", a.SHA1.to_uppercase(), a.FileName, a.Size)).collect::<Vec<_>>().join("\n")
+    }
+}
+
+/// Returns the name of the index file for a channel, eg. 'releases.win.json'.
+pub fn get_index_file_name(channel: &str) -> String {
+    format!("releases.{}.json", channel)
+}
+
+/// Returns the name of the legacy RELEASES file for a channel, which is 'RELEASES' for the default Windows channel.
+pub fn get_legacy_releases_file_name(channel: &str) -> String {
+    if channel.is_empty() || channel.eq_ignore_ascii_case("win") {
+        "RELEASES".to_owned()
+    } else {
+        format!("RELEASES-{}", channel.to_lowercase())
+    }
+}
+
+fn get_manifest_channel(manifest: &bundle::Manifest) -> String {
+    if !manifest.channel.is_empty() {
+        manifest.channel.clone()
+    } else if !manifest.os.is_empty() {
+        manifest.os.clone()
+    } else if cfg!(target_os = "windows") {
+        "win".to_owned()
+    } else if cfg!(target_os = "macos") {
+        "osx".to_owned()
+    } else {
+        "linux".to_owned()
+    }
+}
+
+#[test]
+fn test_release_index_add_prune_and_save() {
+    let tmp = crate::testing::TempDir::new("velopack_releases").unwrap();
+    let app_dir = tmp.join("app");
+    fs::create_dir_all(&app_dir).unwrap();
+    fs::write(app_dir.join("MyApp.exe"), b"exe").unwrap();
+    let write_package = |version: &str, channel: &str| {
+        let version = Version::parse(version).unwrap();
+        let manifest = bundle::Manifest {
+            id: "MyApp".to_owned(),
+            version: version.clone(),
+            channel: channel.to_owned(),
+            os: "win".to_owned(),
+            mandatory: if version.minor == 1 { "true".to_owned() } else { String::new() },
+            ..Default::default()
+        };
+        let name = bundle::get_suggested_package_file_name("MyApp", &version, channel, "win", false);
+        bundle::BundleWriter::new(&app_dir, manifest).write_to_file(tmp.join(&name)).unwrap();
+        name
+    };
+    write_package("1.0.0", "win");
+    write_package("1.1.0", "win");
+    write_package("1.1.0", "beta");
+    write_package("2.0.0-rc.1", "win");
+    // packages which are not Velopack packages are skipped
+    fs::write(tmp.join("Newtonsoft.Json.13.0.1.nupkg"), b"not a zip").unwrap();
+
+    let mut index = ReleaseIndex::from_directory(&tmp, "win").unwrap();
+    assert_eq!(index.feed().Assets.len(), 3);
+    assert!(index.add_package(tmp.join("MyApp-1.1.0-beta-full.nupkg")).is_err());
+    let asset = index.feed().find("MyApp-1.1.0-full.nupkg").unwrap();
+    assert_eq!(asset.SHA256, util::calculate_file_sha256(tmp.join("MyApp-1.1.0-full.nupkg")).unwrap().to_uppercase());
+    assert!(asset.IsMandatory);
+    assert!(!index.feed().find("MyApp-1.0.0-full.nupkg").unwrap().IsMandatory);
+
+    index.save().unwrap();
+    let legacy = fs::read_to_string(tmp.join("RELEASES")).unwrap();
+    let lines: Vec<&str> = legacy.lines().map(|l| l.split(' ').nth(1).unwrap()).collect();
+    assert_eq!(lines, vec!["MyApp-1.0.0-full.nupkg", "MyApp-1.1.0-full.nupkg"]);
+
+    let mut index = ReleaseIndex::load(&tmp, "win").unwrap();
+    assert_eq!(index.feed().Assets[0].Version, "2.0.0-rc.1");
+    let removed = index.prune(2);
+    assert_eq!(removed.len(), 1);
+    assert_eq!(removed[0].FileName, "MyApp-1.0.0-full.nupkg");
+
+    fs::write(app_dir.join("MyApp.exe"), b"changed").unwrap();
+    write_package("1.1.0", "win");
+    let before = index.feed().find("MyApp-1.1.0-full.nupkg").unwrap().SHA1.clone();
+    index.refresh_checksums().unwrap();
+    assert_ne!(index.feed().find("MyApp-1.1.0-full.nupkg").unwrap().SHA1, before);
+    fs::remove_file(tmp.join("MyApp-1.1.0-full.nupkg")).unwrap();
+    assert!(index.refresh_checksums().is_err());
+}
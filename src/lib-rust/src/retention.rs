@@ -0,0 +1,188 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use semver::Version;
+
+use crate::{bundle, manager::UpdateOptions, version, Error};
+
+/// Controls which packages are kept in the packages directory once an update has been downloaded or applied. Keeping
+/// more than one full package allows an app to roll back to a previous version without a network connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// The number of full packages to keep, newest first. This is always at least one.
+    pub keep_full_packages: usize,
+    /// If set, the oldest full packages are deleted until all of the packages kept add up to less than this many bytes.
+    pub max_total_bytes: Option<u64>,
+    /// If set, full packages which were last modified longer ago than this are deleted.
+    pub max_age: Option<Duration>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy { keep_full_packages: 1, max_total_bytes: None, max_age: None }
+    }
+}
+
+impl RetentionPolicy {
+    /// Creates a policy from the package retention settings in UpdateOptions, where zero means the default.
+    pub fn from_options(options: &UpdateOptions) -> Self {
+        RetentionPolicy {
+            keep_full_packages: (options.PackagesToKeep as usize).max(1),
+            max_total_bytes: Some(options.MaxPackagesSizeBytes).filter(|b| *b > 0),
+            max_age: Some(options.MaxPackageAgeDays).filter(|d| *d > 0).map(|d| Duration::from_secs(d as u64 * 24 * 60 * 60)),
+        }
+    }
+
+    /// Returns the command line arguments which pass this policy to the 'apply' command of the updater.
+    pub fn to_apply_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.keep_full_packages > 1 {
+            args.push("--keepPackages".to_owned());
+            args.push(self.keep_full_packages.to_string());
+        }
+        if let Some(bytes) = self.max_total_bytes {
+            args.push("--maxPackagesSize".to_owned());
+            args.push(bytes.to_string());
+        }
+        if let Some(age) = self.max_age {
+            args.push("--maxPackageAgeDays".to_owned());
+            args.push((age.as_secs() / (24 * 60 * 60)).max(1).to_string());
+        }
+        args
+    }
+}
+
+struct PackageFile {
+    path: PathBuf,
+    version: Option<Version>,
+    size: u64,
+    modified: Option<SystemTime>,
+    protected: bool,
+}
+
+/// Deletes packages from the packages directory according to the specified policy, and returns the paths which were deleted.
+/// Delta packages are always deleted, because they are only needed while an update is being downloaded. Protected packages
+/// (such as the package for the installed version, or an update which is about to be applied) are never deleted, but
+/// still count towards the number and size of packages kept.
+pub fn enforce(packages_dir: &Path, policy: &RetentionPolicy, protected: &[PathBuf]) -> Result<Vec<PathBuf>, Error> {
+    let entries = match fs::read_dir(packages_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut to_delete = Vec::new();
+    let mut full_packages = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.extension().is_some_and(|e| e.eq_ignore_ascii_case("nupkg")) {
+            continue;
+        }
+        let info = bundle::parse_package_file_path(&path);
+        let protected = protected.iter().any(|p| p == &path);
+        if info.as_ref().is_some_and(|i| i.is_delta) {
+            if !protected {
+                to_delete.push(path);
+            }
+            continue;
+        }
+        let metadata = entry.metadata().ok();
+        full_packages.push(PackageFile {
+            version: info.map(|i| i.version),
+            size: metadata.as_ref().map_or(0, |m| m.len()),
+            modified: metadata.and_then(|m| m.modified().ok()),
+            path,
+            protected,
+        });
+    }
+
+    // newest first, and packages whose version could not be determined are treated as the oldest
+    full_packages.sort_by(|a, b| match (&a.version, &b.version) {
+        (Some(a), Some(b)) => version::compare(b, a),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+
+    let now = SystemTime::now();
+    let mut kept = Vec::new();
+    for (i, package) in full_packages.into_iter().enumerate() {
+        let too_old = match (policy.max_age, package.modified) {
+            (Some(max_age), Some(modified)) => now.duration_since(modified).is_ok_and(|age| age > max_age),
+            _ => false,
+        };
+        if package.protected || (i < policy.keep_full_packages && !too_old) {
+            kept.push(package);
+        } else {
+            to_delete.push(package.path);
+        }
+    }
+
+    if let Some(max_total_bytes) = policy.max_total_bytes {
+        let mut total: u64 = kept.iter().map(|p| p.size).sum();
+        while total > max_total_bytes {
+            match kept.iter().rposition(|p| !p.protected) {
+                Some(idx) => {
+                    let package = kept.remove(idx);
+                    total -= package.size;
+                    to_delete.push(package.path);
+                }
+                None => break,
+            }
+        }
+    }
+
+    let mut deleted = Vec::new();
+    for path in to_delete {
+        info!("Cleaning up old package: '{}'", path.to_string_lossy());
+        match fs::remove_file(&path) {
+            Ok(()) => deleted.push(path),
+            Err(e) => warn!("Unable to delete old package '{}': {}", path.to_string_lossy(), e),
+        }
+    }
+    Ok(deleted)
+}
+
+#[test]
+fn test_retention_policy_keeps_newest_packages() {
+    let dir = crate::testing::TempDir::new("velopack_retention").unwrap();
+    let write = |name: &str, size: usize| {
+        let path = dir.join(name);
+        fs::write(&path, vec![0u8; size]).unwrap();
+        path
+    };
+    let names = |deleted: &[PathBuf]| {
+        let mut names: Vec<String> = deleted.iter().map(|p| p.file_name().unwrap().to_string_lossy().to_string()).collect();
+        names.sort();
+        names
+    };
+
+    let v1 = write("App-1.0.0-full.nupkg", 100);
+    write("App-2.0.0-full.nupkg", 100);
+    write("App-2.0.0-delta.nupkg", 10);
+    write("App-3.0.0-full.nupkg", 100);
+    write("App-10.0.0-full.nupkg", 100);
+    write("readme.txt", 10);
+
+    // the protected package is kept even though it is the oldest
+    let policy = RetentionPolicy { keep_full_packages: 2, ..Default::default() };
+    let deleted = enforce(dir.path(), &policy, &[v1.clone()]).unwrap();
+    assert_eq!(names(&deleted), vec!["App-2.0.0-delta.nupkg", "App-2.0.0-full.nupkg"]);
+
+    let policy = RetentionPolicy { keep_full_packages: 3, max_total_bytes: Some(250), max_age: None };
+    let deleted = enforce(dir.path(), &policy, &[]).unwrap();
+    assert_eq!(names(&deleted), vec!["App-1.0.0-full.nupkg"]);
+
+    let deleted = enforce(dir.path(), &RetentionPolicy::default(), &[]).unwrap();
+    assert_eq!(names(&deleted), vec!["App-3.0.0-full.nupkg"]);
+    assert!(dir.join("App-10.0.0-full.nupkg").exists());
+    assert!(dir.join("readme.txt").exists());
+
+    let options = UpdateOptions { PackagesToKeep: 3, MaxPackageAgeDays: 7, ..Default::default() };
+    let policy = RetentionPolicy::from_options(&options);
+    assert_eq!(policy.keep_full_packages, 3);
+    assert_eq!(policy.to_apply_args(), vec!["--keepPackages", "3", "--maxPackageAgeDays", "7"]);
+}
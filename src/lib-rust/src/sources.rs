@@ -1,10 +1,14 @@
 use std::{
+    io::{Read, Seek, SeekFrom},
     path::{Path, PathBuf},
-    sync::mpsc::Sender,
+    sync::{mpsc::Sender, Arc},
 };
 
 use crate::*;
 use crate::bundle::Manifest;
+use crate::components::ComponentAsset;
+use crate::download::{CancellationToken, DefaultDownloader, Downloader, RequestMetadata};
+use crate::progress::{self, ProgressEvent, ProgressPhase, ProgressTracker};
 
 /// Abstraction for finding and downloading updates from a package source / repository.
 /// An implementation may copy a file from a local repository, download from a web address,
@@ -15,6 +19,40 @@ pub trait UpdateSource: Send + Sync {
     fn get_release_feed(&self, channel: &str, app: &bundle::Manifest) -> Result<VelopackAssetFeed, Error>;
     /// Download the specified VelopackAsset to the provided local file path.
     fn download_release_entry(&self, asset: &VelopackAsset, local_file: &str, progress_sender: Option<Sender<i16>>) -> Result<(), Error>;
+    /// Download the specified VelopackAsset to the provided local file path, reporting detailed progress events.
+    /// The default implementation derives the events from the percentage reported by download_release_entry,
+    /// so sources only need to override this if they can measure the transferred bytes directly. Cancellation is
+    /// only checked before and after the download, so sources which can stop part way through should override this too.
+    fn download_release_entry_with_events(
+        &self,
+        asset: &VelopackAsset,
+        local_file: &str,
+        cancel: Option<&CancellationToken>,
+        progress_sender: Option<Sender<ProgressEvent>>,
+    ) -> Result<(), Error> {
+        if let Some(cancel) = cancel {
+            cancel.check()?;
+        }
+        let total = if asset.Size > 0 { Some(asset.Size) } else { None };
+        let percent_sender = progress_sender.map(|s| progress::percent_to_events(ProgressPhase::Download, total, s));
+        self.download_release_entry(asset, local_file, percent_sender)?;
+        if let Some(cancel) = cancel {
+            cancel.check()?;
+        }
+        Ok(())
+    }
+    /// Download part of the specified VelopackAsset, from offset and up to length bytes long. This is used to download
+    /// only the chunks of a package which are missing locally (see [`crate::chunks`]), and returns
+    /// [`Error::Unsupported`] by default, in which case the whole package is downloaded instead.
+    fn download_release_entry_range(&self, asset: &VelopackAsset, offset: u64, length: u64) -> Result<Vec<u8>, Error> {
+        let _ = (asset, offset, length);
+        Err(Error::Unsupported("This update source does not support downloading part of a package.".to_owned()))
+    }
+    /// Sets the metadata which identifies the app to the update server (see [`RequestMetadata`]). This is ignored by
+    /// default, and by sources which do not make HTTP requests.
+    fn set_request_metadata(&mut self, metadata: &RequestMetadata) {
+        let _ = metadata;
+    }
     /// Clone the source to create a new lifetime.
     fn clone_boxed(&self) -> Box<dyn UpdateSource>;
 }
@@ -76,6 +114,24 @@ impl UpdateSource for AutoSource {
         self.source.download_release_entry(asset, local_file, progress_sender)
     }
 
+    fn download_release_entry_with_events(
+        &self,
+        asset: &VelopackAsset,
+        local_file: &str,
+        cancel: Option<&CancellationToken>,
+        progress_sender: Option<Sender<ProgressEvent>>,
+    ) -> Result<(), Error> {
+        self.source.download_release_entry_with_events(asset, local_file, cancel, progress_sender)
+    }
+
+    fn download_release_entry_range(&self, asset: &VelopackAsset, offset: u64, length: u64) -> Result<Vec<u8>, Error> {
+        self.source.download_release_entry_range(asset, offset, length)
+    }
+
+    fn set_request_metadata(&mut self, metadata: &RequestMetadata) {
+        self.source.set_request_metadata(metadata);
+    }
+
     fn clone_boxed(&self) -> Box<dyn UpdateSource> {
         self.source.clone_boxed()
     }
@@ -87,12 +143,20 @@ impl UpdateSource for AutoSource {
 /// and provides query parameters to specify the name of the requested package.
 pub struct HttpSource {
     url: String,
+    downloader: Box<dyn Downloader>,
+    query: Vec<(String, String)>,
 }
 
 impl HttpSource {
     /// Create a new HttpSource with the specified base URL.
     pub fn new(url: &str) -> HttpSource {
-        HttpSource { url: url.to_owned() }
+        Self::with_downloader(url, DefaultDownloader::default())
+    }
+
+    /// Create a new HttpSource with the specified base URL, which performs requests using the provided [`Downloader`]
+    /// instead of the built-in HTTP client.
+    pub fn with_downloader<D: Downloader + 'static>(url: &str, downloader: D) -> HttpSource {
+        HttpSource { url: url.to_owned(), downloader: Box::new(downloader), query: Vec::new() }
     }
 }
 
@@ -104,27 +168,54 @@ impl UpdateSource for HttpSource {
         let url = url::Url::parse(&path)?;
         let mut releases_url = url.join(&releases_name)?;
         releases_url.set_query(Some(format!("localVersion={}&id={}", app.version, app.id).as_str()));
+        releases_url.query_pairs_mut().extend_pairs(&self.query);
 
         info!("Downloading releases for channel {} from: {}", channel, releases_url.to_string());
-        let json = download::download_url_as_string(releases_url.as_str())?;
-        let feed: VelopackAssetFeed = serde_json::from_str(&json)?;
+        let json = download::download_as_string_with(self.downloader.as_ref(), releases_url.as_str())?;
+        let mut feed = VelopackAssetFeed::from_json(&json)?;
+        feed.retain_package(&app.id);
         Ok(feed)
     }
 
     fn download_release_entry(&self, asset: &VelopackAsset, local_file: &str, progress_sender: Option<Sender<i16>>) -> Result<(), Error> {
+        self.download_release_entry_with_events(asset, local_file, None, progress_sender.map(progress::forward_percent))
+    }
+
+    fn download_release_entry_with_events(
+        &self,
+        asset: &VelopackAsset,
+        local_file: &str,
+        cancel: Option<&CancellationToken>,
+        progress_sender: Option<Sender<ProgressEvent>>,
+    ) -> Result<(), Error> {
         let path = self.url.trim_end_matches('/').to_owned() + "/";
         let url = url::Url::parse(&path)?;
         let asset_url = url.join(&asset.FileName)?;
 
         info!("About to download from URL '{}' to file '{}'", asset_url, local_file);
-        download::download_url_to_file(asset_url.as_str(), local_file, move |p| {
+        download::download_asset_to_file_with(self.downloader.as_ref(), asset_url.as_str(), asset, local_file, cancel, move |e| {
             if let Some(progress_sender) = &progress_sender {
-                let _ = progress_sender.send(p);
+                let _ = progress_sender.send(e);
             }
         })?;
         Ok(())
     }
 
+    fn download_release_entry_range(&self, asset: &VelopackAsset, offset: u64, length: u64) -> Result<Vec<u8>, Error> {
+        let path = self.url.trim_end_matches('/').to_owned() + "/";
+        let asset_url = url::Url::parse(&path)?.join(&asset.FileName)?;
+        debug!("Downloading {} bytes at offset {} from URL '{}'", length, offset, asset_url);
+        let response = util::retry_io(|| self.downloader.get_range(asset_url.as_str(), offset, length))?;
+        let mut data = Vec::with_capacity(length as usize);
+        response.body.take(length).read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn set_request_metadata(&mut self, metadata: &RequestMetadata) {
+        self.query = metadata.query.clone();
+        self.downloader.set_request_metadata(metadata);
+    }
+
     fn clone_boxed(&self) -> Box<dyn UpdateSource> {
         Box::new(self.clone())
     }
@@ -146,13 +237,14 @@ impl FileSource {
 }
 
 impl UpdateSource for FileSource {
-    fn get_release_feed(&self, channel: &str, _: &bundle::Manifest) -> Result<VelopackAssetFeed, Error> {
+    fn get_release_feed(&self, channel: &str, app: &bundle::Manifest) -> Result<VelopackAssetFeed, Error> {
         let releases_name = format!("releases.{}.json", channel);
         let releases_path = self.path.join(&releases_name);
 
         info!("Reading releases from file: {}", releases_path.display());
         let json = std::fs::read_to_string(releases_path)?;
-        let feed: VelopackAssetFeed = serde_json::from_str(&json)?;
+        let mut feed = VelopackAssetFeed::from_json(&json)?;
+        feed.retain_package(&app.id);
         Ok(feed)
     }
 
@@ -169,7 +261,937 @@ impl UpdateSource for FileSource {
         Ok(())
     }
 
+    fn download_release_entry_range(&self, asset: &VelopackAsset, offset: u64, length: u64) -> Result<Vec<u8>, Error> {
+        let mut file = std::fs::File::open(self.path.join(&asset.FileName))?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut data = Vec::with_capacity(length as usize);
+        file.take(length).read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn clone_boxed(&self) -> Box<dyn UpdateSource> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Clone)]
+/// Downloads packages from an intranet cache before falling back to another source, so that the clients of an organization
+/// do not each download the same packages over the internet. The release feed is always read from the other source.
+///
+/// The cache is addressed by the SHA256 checksum of each package, which is requested from '{cache}/{ab}/{sha256}', where
+/// sha256 is the lower case checksum and ab is its first two characters. This is the same layout as a shared cache
+/// directory (see [`crate::cache::SharedCache`]), so the cache can be a directory on a network share, or any static web
+/// server. Packages which are missing from the cache, or which do not match their checksum, are downloaded from the
+/// other source instead.
+pub struct CacheServerSource {
+    cache: String,
+    source: Box<dyn UpdateSource>,
+    downloader: Box<dyn Downloader>,
+}
+
+impl CacheServerSource {
+    /// Create a new CacheServerSource which tries the cache at the specified url or directory first, and then the source.
+    pub fn new<T: UpdateSource>(cache: &str, source: T) -> CacheServerSource {
+        Self::with_downloader(cache, source, DefaultDownloader::default())
+    }
+
+    /// Create a new CacheServerSource which requests packages from the cache using the provided [`Downloader`].
+    pub fn with_downloader<T: UpdateSource, D: Downloader + 'static>(cache: &str, source: T, downloader: D) -> CacheServerSource {
+        let cache = cache.trim_end_matches(['/', '\\']).to_owned();
+        CacheServerSource { cache, source: source.clone_boxed(), downloader: Box::new(downloader) }
+    }
+
+    fn download_from_cache(
+        &self,
+        asset: &VelopackAsset,
+        local_file: &str,
+        cancel: Option<&CancellationToken>,
+        progress_sender: &Option<Sender<ProgressEvent>>,
+    ) -> Result<(), Error> {
+        let (dir, name) = cache::get_entry_name(&asset.SHA256)
+            .ok_or_else(|| Error::Generic(format!("'{}' does not have a SHA256 checksum.", asset.FileName)))?;
+        if AutoSource::is_http_url(&self.cache) {
+            let url = format!("{}/{}/{}", self.cache, dir, name);
+            info!("Trying to download '{}' from the cache server: {}", asset.FileName, url);
+            download::download_asset_to_file_with(self.downloader.as_ref(), &url, asset, local_file, cancel, |e| {
+                if let Some(progress_sender) = progress_sender {
+                    let _ = progress_sender.send(e);
+                }
+            })
+        } else {
+            let path = Path::new(&self.cache).join(dir).join(name);
+            info!("Trying to copy '{}' from the cache directory: {}", asset.FileName, path.display());
+            std::fs::copy(path, local_file)?;
+            download::verify_asset_file(asset, local_file)
+        }
+    }
+}
+
+impl UpdateSource for CacheServerSource {
+    fn get_release_feed(&self, channel: &str, app: &bundle::Manifest) -> Result<VelopackAssetFeed, Error> {
+        self.source.get_release_feed(channel, app)
+    }
+
+    fn download_release_entry(&self, asset: &VelopackAsset, local_file: &str, progress_sender: Option<Sender<i16>>) -> Result<(), Error> {
+        self.download_release_entry_with_events(asset, local_file, None, progress_sender.map(progress::forward_percent))
+    }
+
+    fn download_release_entry_with_events(
+        &self,
+        asset: &VelopackAsset,
+        local_file: &str,
+        cancel: Option<&CancellationToken>,
+        progress_sender: Option<Sender<ProgressEvent>>,
+    ) -> Result<(), Error> {
+        match self.download_from_cache(asset, local_file, cancel, &progress_sender) {
+            Ok(()) => return Ok(()),
+            Err(Error::Cancelled) => return Err(Error::Cancelled),
+            Err(e) => {
+                info!("'{}' is not available from the cache ({}), downloading it from the update source.", asset.FileName, e);
+                let _ = std::fs::remove_file(local_file);
+            }
+        }
+        self.source.download_release_entry_with_events(asset, local_file, cancel, progress_sender)
+    }
+
+    fn download_release_entry_range(&self, asset: &VelopackAsset, offset: u64, length: u64) -> Result<Vec<u8>, Error> {
+        self.source.download_release_entry_range(asset, offset, length)
+    }
+
+    fn set_request_metadata(&mut self, metadata: &RequestMetadata) {
+        self.source.set_request_metadata(metadata);
+        self.downloader.set_request_metadata(metadata);
+    }
+
+    fn clone_boxed(&self) -> Box<dyn UpdateSource> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Clone, Default)]
+/// Serves a synthetic release feed and packages from memory, so that an application's update flow can be exercised
+/// in automated tests without a real update feed. Each scenario is built up from releases, for example:
+/// - No update: add a release with the same version as the installed app (or no releases at all).
+/// - An update (eg. one the app treats as mandatory): add a release with a newer version, and any release notes.
+/// - A corrupt package: add a release with [`TestSource::with_corrupt_release`], so the download fails verification.
+/// - An unreachable feed: use [`TestSource::with_feed_error`].
+/// - Switching channels: add releases to specific channels with [`TestSource::with_channel_release`].
+/// - Optional components: add component releases with [`TestSource::with_component`].
+pub struct TestSource {
+    releases: Vec<TestRelease>,
+    components: Vec<(ComponentAsset, Arc<Vec<u8>>)>,
+    feed_error: Option<String>,
+}
+
+#[derive(Clone)]
+struct TestRelease {
+    asset: VelopackAsset,
+    package: Arc<Vec<u8>>,
+    /// The channel this release is served on, or None to serve it on every channel.
+    channel: Option<String>,
+}
+
+impl TestSource {
+    /// Create a new TestSource with an empty release feed.
+    pub fn new() -> TestSource {
+        Self::default()
+    }
+
+    /// Create a new TestSource serving every package (files ending in '-full.nupkg' or '-delta.nupkg') in the specified
+    /// directory. The package id and version are read from each file name, and the release notes from its manifest.
+    pub fn from_directory<P: AsRef<Path>>(path: P) -> Result<TestSource, Error> {
+        let mut source = Self::new();
+        for entry in std::fs::read_dir(path)? {
+            let path = entry?.path();
+            if let Some(info) = bundle::parse_package_file_path(&path) {
+                let package = std::fs::read(&path)?;
+                let manifest = info.load_manifest().unwrap_or_default();
+                let asset = VelopackAsset {
+                    PackageId: info.name.clone(),
+                    Version: info.version.to_string(),
+                    Type: if info.is_delta { "Delta".to_owned() } else { "Full".to_owned() },
+                    FileName: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+                    IsMandatory: manifest.is_mandatory(),
+                    NotesMarkdown: manifest.release_notes,
+                    NotesHtml: manifest.release_notes_html,
+                    Architecture: manifest.machine_architecture,
+                    ..Default::default()
+                };
+                source = source.with_asset(asset, package);
+            }
+        }
+        Ok(source)
+    }
+
+    /// Add a full release of the specified package id and version to the feed, which will serve the provided package.
+    pub fn with_release(self, package_id: &str, version: &str, package: Vec<u8>) -> TestSource {
+        let asset = VelopackAsset {
+            PackageId: package_id.to_owned(),
+            Version: version.to_owned(),
+            Type: "Full".to_owned(),
+            FileName: format!("{}-{}-full.nupkg", package_id, version),
+            ..Default::default()
+        };
+        self.with_asset(asset, package)
+    }
+
+    /// Add a full release of the specified package id and version which is only served on the specified channel.
+    pub fn with_channel_release(self, channel: &str, package_id: &str, version: &str, package: Vec<u8>) -> TestSource {
+        let mut source = self.with_release(package_id, version, package);
+        if let Some(release) = source.releases.last_mut() {
+            release.channel = Some(channel.to_owned());
+        }
+        source
+    }
+
+    /// Add a full release of the specified package id and version which is marked as mandatory in the feed.
+    pub fn with_mandatory_release(self, package_id: &str, version: &str, package: Vec<u8>) -> TestSource {
+        let mut source = self.with_release(package_id, version, package);
+        if let Some(release) = source.releases.last_mut() {
+            release.asset.IsMandatory = true;
+        }
+        source
+    }
+
+    /// Add a full release to the feed whose download is corrupted, so that it does not match the size and checksums
+    /// advertised in the feed, and downloading it fails.
+    pub fn with_corrupt_release(self, package_id: &str, version: &str, package: Vec<u8>) -> TestSource {
+        let mut source = self.with_release(package_id, version, package);
+        if let Some(release) = source.releases.last_mut() {
+            let mut corrupt = release.package.as_ref().clone();
+            corrupt.iter_mut().for_each(|b| *b = !*b);
+            corrupt.push(0);
+            release.package = Arc::new(corrupt);
+        }
+        source
+    }
+
+    /// Add an asset to the feed, which will serve the provided package. If the asset size or checksums are empty, they
+    /// are calculated from the package.
+    pub fn with_asset(mut self, mut asset: VelopackAsset, package: Vec<u8>) -> TestSource {
+        if asset.Size == 0 {
+            asset.Size = package.len() as u64;
+        }
+        if asset.SHA1.is_empty() {
+            asset.SHA1 = util::calculate_sha1(&package);
+        }
+        if asset.SHA256.is_empty() {
+            asset.SHA256 = util::calculate_sha256(&package);
+        }
+        self.releases.push(TestRelease { asset, package: Arc::new(package), channel: None });
+        self
+    }
+
+    /// Add a release of an optional component to the feed, which is compatible with the app versions matched by the
+    /// specified SemVer requirement (or every version if it is empty). The package should be a zip archive.
+    pub fn with_component(mut self, package_id: &str, component_id: &str, version: &str, app_version_range: &str, package: Vec<u8>) -> TestSource {
+        let asset = VelopackAsset {
+            PackageId: package_id.to_owned(),
+            Version: version.to_owned(),
+            Type: "Component".to_owned(),
+            FileName: format!("{}-{}-{}.zip", package_id, component_id, version),
+            Size: package.len() as u64,
+            SHA1: util::calculate_sha1(&package),
+            SHA256: util::calculate_sha256(&package),
+            ..Default::default()
+        };
+        let component = ComponentAsset { ComponentId: component_id.to_owned(), AppVersionRange: app_version_range.to_owned(), Asset: asset };
+        self.components.push((component, Arc::new(package)));
+        self
+    }
+
+    /// Make every request for the release feed fail with the specified message, to simulate an unreachable feed.
+    pub fn with_feed_error(mut self, message: &str) -> TestSource {
+        self.feed_error = Some(message.to_owned());
+        self
+    }
+
+    fn find_package(&self, asset: &VelopackAsset) -> Result<Arc<Vec<u8>>, Error> {
+        let releases = self.releases.iter().map(|r| (&r.asset, &r.package));
+        let components = self.components.iter().map(|(c, p)| (&c.Asset, p));
+        releases
+            .chain(components)
+            .find(|(a, _)| a.FileName.eq_ignore_ascii_case(&asset.FileName))
+            .map(|(_, p)| p.clone())
+            .ok_or_else(|| Error::Generic(format!("Test source does not contain a package named '{}'", asset.FileName)))
+    }
+}
+
+impl UpdateSource for TestSource {
+    fn get_release_feed(&self, channel: &str, app: &bundle::Manifest) -> Result<VelopackAssetFeed, Error> {
+        if let Some(message) = &self.feed_error {
+            return Err(Error::Generic(message.clone()));
+        }
+        let assets = self.releases.iter().filter(|r| r.channel.as_ref().map_or(true, |c| c == channel)).map(|r| r.asset.clone()).collect();
+        let components = self.components.iter().map(|(c, _)| c.clone()).collect();
+        let mut feed = VelopackAssetFeed { Assets: assets, Components: components };
+        feed.retain_package(&app.id);
+        Ok(feed)
+    }
+
+    fn download_release_entry(&self, asset: &VelopackAsset, local_file: &str, progress_sender: Option<Sender<i16>>) -> Result<(), Error> {
+        self.download_release_entry_with_events(asset, local_file, None, progress_sender.map(progress::forward_percent))
+    }
+
+    fn download_release_entry_with_events(
+        &self,
+        asset: &VelopackAsset,
+        local_file: &str,
+        cancel: Option<&CancellationToken>,
+        progress_sender: Option<Sender<ProgressEvent>>,
+    ) -> Result<(), Error> {
+        if let Some(cancel) = cancel {
+            cancel.check()?;
+        }
+        let package = self.find_package(asset)?;
+        std::fs::write(local_file, package.as_slice())?;
+        if let Some(progress_sender) = &progress_sender {
+            let mut tracker = ProgressTracker::new(ProgressPhase::Download, Some(package.len() as u64));
+            if let Some(event) = tracker.advance(package.len() as u64) {
+                let _ = progress_sender.send(event);
+            }
+            let _ = progress_sender.send(tracker.finish());
+        }
+        Ok(())
+    }
+
     fn clone_boxed(&self) -> Box<dyn UpdateSource> {
         Box::new(self.clone())
     }
 }
+
+#[cfg(test)]
+use crate::testing::{RecordingLauncher, TempDir, TestApp};
+
+#[test]
+fn test_test_source_simulates_update_scenarios() {
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let source = TestSource::new().with_release("TestApp", "1.0.0", b"v1".to_vec());
+    let um = app.create_manager(source, None, RecordingLauncher::new()).unwrap();
+    assert!(matches!(um.check_for_updates().unwrap(), UpdateCheck::NoUpdateAvailable));
+
+    let source = TestSource::new().with_release("TestApp", "1.0.0", b"v1".to_vec()).with_release("TestApp", "2.0.0", b"v2".to_vec());
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let um = app.create_manager(source, None, RecordingLauncher::new()).unwrap();
+    let update = match um.check_for_updates().unwrap() {
+        UpdateCheck::UpdateAvailable(update) => update,
+        _ => panic!("expected an update"),
+    };
+    assert_eq!(update.TargetFullRelease.Version, "2.0.0");
+    um.download_updates(&update, None).unwrap();
+    assert_eq!(std::fs::read(app.get_root_dir().join("packages").join("TestApp-2.0.0-full.nupkg")).unwrap(), b"v2");
+
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let source = TestSource::new().with_corrupt_release("TestApp", "2.0.0", b"v2".to_vec());
+    let um = app.create_manager(source, None, RecordingLauncher::new()).unwrap();
+    let update = match um.check_for_updates().unwrap() {
+        UpdateCheck::UpdateAvailable(update) => update,
+        _ => panic!("expected an update"),
+    };
+    assert!(um.download_updates(&update, None).is_err());
+    assert!(!app.get_root_dir().join("packages").join("TestApp-2.0.0-full.nupkg").exists());
+
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let um = app.create_manager(TestSource::new().with_feed_error("offline"), None, RecordingLauncher::new()).unwrap();
+    assert!(um.check_for_updates().is_err());
+}
+
+#[test]
+fn test_test_source_prefers_higher_prerelease_precedence() {
+    let source = TestSource::new()
+        .with_release("TestApp", "2.0.0-beta.10", b"b10".to_vec())
+        .with_release("TestApp", "2.0.0-beta.2", b"b2".to_vec())
+        .with_release("TestApp", "2.0.0-beta.9+build.1", b"b9".to_vec());
+    let app = TestApp::new("TestApp", "2.0.0-beta.2").unwrap();
+    let um = app.create_manager(source, None, RecordingLauncher::new()).unwrap();
+    match um.check_for_updates().unwrap() {
+        UpdateCheck::UpdateAvailable(update) => assert_eq!(update.TargetFullRelease.Version, "2.0.0-beta.10"),
+        _ => panic!("expected an update"),
+    }
+
+    // a different build of the same version is not an update
+    let app = TestApp::new("TestApp", "1.0.0+build.1").unwrap();
+    let source = TestSource::new().with_release("TestApp", "1.0.0+build.2", b"v1".to_vec());
+    let um = app.create_manager(source, None, RecordingLauncher::new()).unwrap();
+    assert!(matches!(um.check_for_updates().unwrap(), UpdateCheck::NoUpdateAvailable));
+}
+
+#[test]
+fn test_switch_channel_requires_confirmation_to_downgrade() {
+    let source = TestSource::new()
+        .with_channel_release("stable", "TestApp", "1.0.0", b"v1".to_vec())
+        .with_channel_release("beta", "TestApp", "1.1.0-beta.1", b"b1".to_vec())
+        .with_channel_release("old", "TestApp", "0.9.0", b"v09".to_vec());
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let mut um = app.create_manager(source, None, RecordingLauncher::new()).unwrap();
+    // the same version on a different channel is a lateral move, which does not need to be confirmed
+    let switch = um.switch_channel("stable", false).unwrap();
+    assert!(!switch.RequiresDowngrade);
+    assert_eq!(switch.Update.unwrap().TargetFullRelease.Version, "1.0.0");
+    assert_eq!(um.get_channel(), "stable");
+    assert!(matches!(um.check_for_updates().unwrap(), UpdateCheck::NoUpdateAvailable));
+
+    let preview = um.check_channel_switch("beta").unwrap();
+    assert!(!preview.RequiresDowngrade);
+    assert_eq!(preview.Update.unwrap().TargetFullRelease.Version, "1.1.0-beta.1");
+    assert_eq!(um.get_channel(), "stable");
+
+    assert!(um.check_channel_switch("old").unwrap().RequiresDowngrade);
+    assert!(matches!(um.switch_channel("old", false), Err(Error::DowngradeNotConfirmed(_))));
+    assert_eq!(um.get_channel(), "stable");
+
+    let switch = um.switch_channel("old", true).unwrap();
+    assert_eq!(switch.Update.unwrap().TargetFullRelease.Version, "0.9.0");
+    assert_eq!(um.get_channel(), "old");
+    match um.check_for_updates().unwrap() {
+        UpdateCheck::UpdateAvailable(update) => assert!(update.IsDowngrade),
+        _ => panic!("expected a downgrade"),
+    }
+}
+
+#[test]
+fn test_prereleases_are_only_offered_when_allowed() {
+    let source = TestSource::new().with_release("TestApp", "1.1.0", b"v11".to_vec()).with_release("TestApp", "2.0.0-beta.1", b"b1".to_vec());
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let um = app.create_manager(source.clone(), None, RecordingLauncher::new()).unwrap();
+    let update = match um.check_for_updates().unwrap() {
+        UpdateCheck::UpdateAvailable(update) => update,
+        _ => panic!("expected an update"),
+    };
+    assert_eq!(update.TargetFullRelease.Version, "1.1.0");
+    assert_eq!(update.ReleaseNotes.len(), 1);
+
+    let options = UpdateOptions { AllowPrerelease: true, ..Default::default() };
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let um = app.create_manager(source.clone(), Some(options), RecordingLauncher::new()).unwrap();
+    match um.check_for_updates().unwrap() {
+        UpdateCheck::UpdateAvailable(update) => assert_eq!(update.TargetFullRelease.Version, "2.0.0-beta.1"),
+        _ => panic!("expected a prerelease update"),
+    }
+
+    // users already running a prerelease keep receiving them
+    let app = TestApp::new("TestApp", "2.0.0-alpha").unwrap();
+    let um = app.create_manager(source, None, RecordingLauncher::new()).unwrap();
+    match um.check_for_updates().unwrap() {
+        UpdateCheck::UpdateAvailable(update) => assert_eq!(update.TargetFullRelease.Version, "2.0.0-beta.1"),
+        _ => panic!("expected a prerelease update"),
+    }
+
+    let only_prereleases = TestSource::new().with_release("TestApp", "2.0.0-beta.1", b"b1".to_vec());
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let um = app.create_manager(only_prereleases, None, RecordingLauncher::new()).unwrap();
+    assert!(matches!(um.check_for_updates().unwrap(), UpdateCheck::NoUpdateAvailable));
+}
+
+#[test]
+fn test_skipped_version_is_not_offered_again() {
+    let source = TestSource::new().with_release("TestApp", "1.1.0", b"v11".to_vec());
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let um = app.create_manager(source, None, RecordingLauncher::new()).unwrap();
+    let update = match um.check_for_updates().unwrap() {
+        UpdateCheck::UpdateAvailable(update) => update,
+        _ => panic!("expected an update"),
+    };
+    um.skip_version(&update).unwrap();
+    assert_eq!(um.get_skipped_version(), Some(semver::Version::parse("1.1.0").unwrap()));
+    assert!(matches!(um.check_for_updates().unwrap(), UpdateCheck::NoUpdateAvailable));
+
+    // the skip is persisted, so a new manager for the same app also respects it
+    let source = TestSource::new().with_release("TestApp", "1.1.0", b"v11".to_vec()).with_release("TestApp", "1.2.0", b"v12".to_vec());
+    let um = app.create_manager(source, None, RecordingLauncher::new()).unwrap();
+    match um.check_for_updates().unwrap() {
+        UpdateCheck::UpdateAvailable(update) => assert_eq!(update.TargetFullRelease.Version, "1.2.0"),
+        _ => panic!("expected a newer release to be offered"),
+    }
+
+    um.skip_version(&VelopackAsset { Version: "1.2.0".to_owned(), ..Default::default() }).unwrap();
+    assert!(matches!(um.check_for_updates().unwrap(), UpdateCheck::NoUpdateAvailable));
+    um.clear_skipped_version().unwrap();
+    assert!(um.get_skipped_version().is_none());
+    assert!(matches!(um.check_for_updates().unwrap(), UpdateCheck::UpdateAvailable(_)));
+}
+
+#[test]
+fn test_mandatory_intermediate_release_makes_update_mandatory() {
+    let source = TestSource::new().with_release("TestApp", "1.1.0", b"v11".to_vec()).with_release("TestApp", "1.2.0", b"v12".to_vec());
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let um = app.create_manager(source, None, RecordingLauncher::new()).unwrap();
+    match um.check_for_updates().unwrap() {
+        UpdateCheck::UpdateAvailable(update) => assert!(!update.IsMandatory),
+        _ => panic!("expected an update"),
+    }
+
+    let source = TestSource::new()
+        .with_mandatory_release("TestApp", "1.1.0", b"v11".to_vec())
+        .with_release("TestApp", "1.2.0", b"v12".to_vec());
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let um = app.create_manager(source.clone(), None, RecordingLauncher::new()).unwrap();
+    let update = match um.check_for_updates().unwrap() {
+        UpdateCheck::UpdateAvailable(update) => update,
+        _ => panic!("expected an update"),
+    };
+    assert_eq!(update.TargetFullRelease.Version, "1.2.0");
+    assert!(!update.TargetFullRelease.IsMandatory);
+    assert!(update.IsMandatory);
+
+    // mandatory updates are still offered after the user has tried to skip them
+    um.skip_version(&update).unwrap();
+    assert!(matches!(um.check_for_updates().unwrap(), UpdateCheck::UpdateAvailable(_)));
+
+    // once the mandatory release is installed, the remaining update is optional again
+    let app = TestApp::new("TestApp", "1.1.0").unwrap();
+    let um = app.create_manager(source, None, RecordingLauncher::new()).unwrap();
+    match um.check_for_updates().unwrap() {
+        UpdateCheck::UpdateAvailable(update) => assert!(!update.IsMandatory),
+        _ => panic!("expected an update"),
+    }
+}
+
+#[test]
+fn test_update_checks_are_rate_limited() {
+    let source = TestSource::new().with_release("TestApp", "1.1.0", b"v11".to_vec());
+    let options = UpdateOptions { UpdateCheckIntervalSeconds: 3600, ..Default::default() };
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let um = app.create_manager(source.clone(), Some(options.clone()), RecordingLauncher::new()).unwrap();
+    assert!(matches!(um.check_for_updates().unwrap(), UpdateCheck::UpdateAvailable(_)));
+    // a second check within the interval does not contact the feed, even from a new manager
+    let offline = source.clone().with_feed_error("feed should not be contacted");
+    let um = app.create_manager(offline, Some(options.clone()), RecordingLauncher::new()).unwrap();
+    assert!(matches!(um.check_for_updates().unwrap(), UpdateCheck::NoUpdateAvailable));
+
+    let last_check = std::fs::read_to_string(app.get_root_dir().join(constants::LAST_UPDATE_CHECK_FILE)).unwrap();
+    let jitter: u64 = last_check.split_whitespace().nth(1).unwrap().parse().unwrap();
+    assert!(jitter <= 360);
+
+    // a failed check is not recorded, so the next check contacts the feed again
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let offline = source.clone().with_feed_error("offline");
+    let um = app.create_manager(offline, Some(options.clone()), RecordingLauncher::new()).unwrap();
+    assert!(um.check_for_updates().is_err());
+    assert!(!app.get_root_dir().join(constants::LAST_UPDATE_CHECK_FILE).exists());
+    let um = app.create_manager(source.clone(), Some(options), RecordingLauncher::new()).unwrap();
+    assert!(matches!(um.check_for_updates().unwrap(), UpdateCheck::UpdateAvailable(_)));
+
+    // checks are not throttled without an interval
+    let um = app.create_manager(source, None, RecordingLauncher::new()).unwrap();
+    assert!(matches!(um.check_for_updates().unwrap(), UpdateCheck::UpdateAvailable(_)));
+}
+
+#[test]
+fn test_feed_is_filtered_by_package_id() {
+    let source = TestSource::new()
+        .with_release("TestApp", "1.1.0", b"v11".to_vec())
+        .with_release("OtherApp", "5.0.0", b"o5".to_vec())
+        .with_release("testapp", "1.2.0", b"v12".to_vec());
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let um = app.create_manager(source, None, RecordingLauncher::new()).unwrap();
+    let feed = um.get_release_feed().unwrap();
+    assert_eq!(feed.Assets.len(), 2);
+    assert!(feed.Assets.iter().all(|a| a.PackageId.eq_ignore_ascii_case("TestApp")));
+    match um.check_for_updates().unwrap() {
+        UpdateCheck::UpdateAvailable(update) => assert_eq!(update.TargetFullRelease.Version, "1.2.0"),
+        _ => panic!("expected an update"),
+    }
+
+    let dir = TempDir::new("velopack_shared_feed").unwrap();
+    let json = r#"{"Assets":[{"PackageId":"AppA","Version":"1.0.0","Type":"Full","FileName":"AppA-1.0.0-full.nupkg"},
+        {"PackageId":"AppB","Version":"2.0.0","Type":"Full","FileName":"AppB-2.0.0-full.nupkg"},
+        {"Version":"3.0.0","Type":"Full","FileName":"legacy-3.0.0-full.nupkg"}]}"#;
+    std::fs::write(dir.join("releases.stable.json"), json).unwrap();
+    let manifest = Manifest { id: "AppB".to_owned(), ..Default::default() };
+    let feed = FileSource::new(&dir).get_release_feed("stable", &manifest).unwrap();
+    let versions: Vec<&str> = feed.Assets.iter().map(|a| a.Version.as_str()).collect();
+    assert_eq!(versions, vec!["2.0.0", "3.0.0"]);
+
+    std::fs::write(dir.join("releases.stable.json"), "<html>not a feed</html>").unwrap();
+    let result = FileSource::new(&dir).get_release_feed("stable", &manifest);
+    assert!(matches!(result, Err(Error::FeedParse(_))));
+}
+
+#[test]
+fn test_best_architecture_is_chosen_for_the_machine() {
+    let machine = arch::get_machine_arch();
+    if machine.is_none() {
+        return;
+    }
+    let native = match machine {
+        Some(arch::PackageArch::Arm64) => "arm64",
+        Some(arch::PackageArch::Arm) => "arm",
+        _ => "x64",
+    };
+    let release = |version: &str, arch: &str| VelopackAsset {
+        PackageId: "TestApp".to_owned(),
+        Version: version.to_owned(),
+        Type: "Full".to_owned(),
+        FileName: format!("TestApp-{}-{}-full.nupkg", version, arch),
+        Architecture: arch.to_owned(),
+        ..Default::default()
+    };
+    let mut source = TestSource::new()
+        .with_asset(release("2.0.0", "universal"), b"u2".to_vec())
+        .with_asset(release("2.0.0", native), b"n2".to_vec())
+        .with_asset(release("2.0.0", ""), b"a2".to_vec());
+    if machine == Some(arch::PackageArch::X64) {
+        // an Intel machine can not run a newer release which was only built for Apple Silicon
+        source = source.with_asset(release("3.0.0", "arm64"), b"a3".to_vec());
+    }
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let um = app.create_manager(source, None, RecordingLauncher::new()).unwrap();
+    match um.check_for_updates().unwrap() {
+        UpdateCheck::UpdateAvailable(update) => {
+            assert_eq!(update.TargetFullRelease.Version, "2.0.0");
+            assert_eq!(update.TargetFullRelease.Architecture, native);
+        }
+        _ => panic!("expected an update"),
+    }
+}
+
+#[test]
+fn test_install_is_switched_to_the_native_architecture() {
+    let native = match arch::get_machine_arch() {
+        Some(arch::PackageArch::Arm64) => "arm64",
+        Some(arch::PackageArch::Arm) => "arm",
+        Some(arch::PackageArch::X64) => "x64",
+        _ => return,
+    };
+    let release = |arch: &str| VelopackAsset {
+        PackageId: "TestApp".to_owned(),
+        Version: "2.0.0".to_owned(),
+        Type: "Full".to_owned(),
+        FileName: format!("TestApp-2.0.0-{}-full.nupkg", arch),
+        Architecture: arch.to_owned(),
+        ..Default::default()
+    };
+    let source = TestSource::new().with_asset(release("universal"), b"u2".to_vec()).with_asset(release(native), b"n2".to_vec());
+    let check_installed = |installed_arch: &str| {
+        let app = TestApp::new("TestApp", "2.0.0").unwrap().with_arch(installed_arch).unwrap();
+        let um = app.create_manager(source.clone(), None, RecordingLauncher::new()).unwrap();
+        um.check_for_updates().unwrap()
+    };
+
+    // the same version built for this machine is preferred over the universal build which is installed
+    match check_installed("universal") {
+        UpdateCheck::UpdateAvailable(update) => {
+            assert_eq!(update.TargetFullRelease.Architecture, native);
+            assert!(update.IsDowngrade);
+        }
+        _ => panic!("expected a switch to the native release"),
+    }
+    assert!(matches!(check_installed(native), UpdateCheck::NoUpdateAvailable));
+    // apps packaged without an architecture are never switched
+    assert!(matches!(check_installed(""), UpdateCheck::NoUpdateAvailable));
+}
+
+#[cfg(test)]
+fn create_test_zip(files: &[(&str, &str)]) -> Vec<u8> {
+    use std::io::Write;
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (name, contents) in files {
+        writer.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+        writer.write_all(contents.as_bytes()).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+#[test]
+fn test_components_are_installed_and_kept_compatible() {
+    let source = TestSource::new()
+        .with_release("TestApp", "2.0.0", b"v2".to_vec())
+        .with_component("TestApp", "fr", "1.0.0", ">=1.0.0, <2.0.0", create_test_zip(&[("strings.txt", "bonjour v1")]))
+        .with_component("TestApp", "fr", "2.0.0", ">=2.0.0", create_test_zip(&[("strings.txt", "bonjour v2"), ("extra/readme.txt", "x")]))
+        .with_component("OtherApp", "de", "1.0.0", "", create_test_zip(&[("strings.txt", "hallo")]));
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let um = app.create_manager(source.clone(), None, RecordingLauncher::new()).unwrap();
+
+    let available = um.get_available_components().unwrap();
+    assert_eq!(available.len(), 1);
+    assert_eq!(available[0].Asset.Version, "1.0.0");
+    assert!(um.install_component("de").is_err());
+
+    let installed = um.install_component("fr").unwrap();
+    assert_eq!(installed.Version, "1.0.0");
+    assert_eq!(std::fs::read_to_string(installed.Path.join("strings.txt")).unwrap(), "bonjour v1");
+    assert_eq!(um.get_installed_components(), vec![installed.clone()]);
+    assert!(um.update_components().unwrap().is_empty());
+
+    // after the app is updated, the installed component is no longer compatible and is replaced
+    let app = app.with_version("2.0.0").unwrap();
+    let um = app.create_manager(source, None, RecordingLauncher::new()).unwrap();
+    assert!(!um.get_installed_components()[0].IsCompatible);
+    let updated = um.update_components().unwrap();
+    assert_eq!(updated.len(), 1);
+    assert_eq!(updated[0].Version, "2.0.0");
+    assert!(updated[0].IsCompatible);
+    assert_eq!(std::fs::read_to_string(installed.Path.join("strings.txt")).unwrap(), "bonjour v2");
+    assert!(installed.Path.join("extra").join("readme.txt").exists());
+
+    um.remove_component("fr").unwrap();
+    assert!(um.get_installed_components().is_empty());
+    assert!(!installed.Path.exists());
+    assert!(um.remove_component("../escape").is_err());
+    // outside of Windows components are kept in the user state directory of the app, rather than with its packages
+    let components_dir = installed.Path.parent().unwrap();
+    assert_eq!(components_dir.file_name().unwrap(), "components");
+    if cfg!(not(target_os = "windows")) {
+        assert!(!components_dir.starts_with(app.get_root_dir().join("packages")));
+        let _ = std::fs::remove_dir_all(components_dir.parent().unwrap());
+    }
+}
+
+#[test]
+fn test_observer_is_notified_of_each_update_step() {
+    use crate::observer::{ErrorClass, UpdateObserver};
+    use std::sync::Mutex;
+
+    struct RecordingObserver(Arc<Mutex<Vec<String>>>);
+    impl UpdateObserver for RecordingObserver {
+        fn on_check_started(&self) {
+            self.0.lock().unwrap().push("check started".to_owned());
+        }
+        fn on_check_completed(&self, result: Result<&UpdateCheck, ErrorClass>, _duration: std::time::Duration) {
+            let result = match result {
+                Ok(UpdateCheck::UpdateAvailable(update)) => format!("update {}", update.TargetFullRelease.Version),
+                Ok(_) => "no update".to_owned(),
+                Err(class) => format!("{:?}", class),
+            };
+            self.0.lock().unwrap().push(format!("check completed: {}", result));
+        }
+        fn on_download_completed(&self, asset: &VelopackAsset, result: Result<(), ErrorClass>, _duration: std::time::Duration) {
+            self.0.lock().unwrap().push(format!("download completed: {} {:?}", asset.Version, result));
+        }
+        fn on_apply_started(&self, asset: &VelopackAsset, result: Result<(), ErrorClass>) {
+            self.0.lock().unwrap().push(format!("apply started: {} {:?}", asset.Version, result));
+        }
+        fn on_stage_completed(&self, asset: &VelopackAsset, result: Result<(), ErrorClass>, _duration: std::time::Duration) {
+            self.0.lock().unwrap().push(format!("stage completed: {} {:?}", asset.Version, result.is_ok()));
+        }
+    }
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let source = TestSource::new().with_corrupt_release("TestApp", "2.0.0", b"v2".to_vec());
+    let mut um = app.create_manager(source, None, RecordingLauncher::new()).unwrap();
+    um.set_observer(RecordingObserver(events.clone()));
+    let update = match um.check_for_updates().unwrap() {
+        UpdateCheck::UpdateAvailable(update) => update,
+        _ => panic!("expected an update"),
+    };
+    assert!(um.download_updates(&update, None).is_err());
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let mut um = app.create_manager(TestSource::new().with_feed_error("offline"), None, RecordingLauncher::new()).unwrap();
+    um.set_observer(RecordingObserver(events.clone()));
+    assert!(um.check_for_updates().is_err());
+
+    // starting the updater is only reported as started, since the update is applied after the app exits
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let source = TestSource::new().with_release("TestApp", "2.0.0", app.create_release_package("2.0.0").unwrap());
+    let mut um = app.create_manager(source, None, RecordingLauncher::new()).unwrap();
+    um.set_observer(RecordingObserver(events.clone()));
+    let update = match um.check_for_updates().unwrap() {
+        UpdateCheck::UpdateAvailable(update) => update,
+        _ => panic!("expected an update"),
+    };
+    um.download_updates(&update, None).unwrap();
+    // staging is only supported on Windows, but it is reported either way
+    let staged = um.stage_updates(&update, None).is_ok();
+    um.wait_exit_then_apply_updates(&update, true, false, Vec::<String>::new()).unwrap();
+
+    assert_eq!(
+        events.lock().unwrap()[..5],
+        [
+            "check started",
+            "check completed: update 2.0.0",
+            "download completed: 2.0.0 Err(InvalidData)",
+            "check started",
+            "check completed: Other",
+        ]
+    );
+    assert_eq!(
+        events.lock().unwrap()[7..],
+        [
+            "download completed: 2.0.0 Ok(())".to_owned(),
+            format!("stage completed: 2.0.0 {}", staged),
+            "apply started: 2.0.0 Ok(())".to_owned(),
+        ]
+    );
+}
+
+#[test]
+fn test_cache_server_is_tried_before_the_source() {
+    let cache_dir = TempDir::new("velopack_cache_server").unwrap();
+    let package = cache_dir.join("package.nupkg");
+    std::fs::write(&package, b"v2").unwrap();
+    let asset = VelopackAsset { SHA256: util::calculate_file_sha256(&package).unwrap(), Size: 2, ..Default::default() };
+    cache::SharedCache::new(&cache_dir, 0).insert(&asset, &package).unwrap();
+
+    // the source only serves a corrupt package, so the download can only succeed if it came from the cache
+    let options = UpdateOptions { CacheServerUrl: Some(cache_dir.path().to_string_lossy().to_string()), ..Default::default() };
+    let source = TestSource::new().with_corrupt_release("TestApp", "2.0.0", b"v2".to_vec());
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let um = app.create_manager(source, Some(options.clone()), RecordingLauncher::new()).unwrap();
+    let update = match um.check_for_updates().unwrap() {
+        UpdateCheck::UpdateAvailable(update) => update,
+        _ => panic!("expected an update"),
+    };
+    um.download_updates(&update, None).unwrap();
+    assert_eq!(std::fs::read(app.get_root_dir().join("packages").join("TestApp-2.0.0-full.nupkg")).unwrap(), b"v2");
+
+    // packages which are not in the cache are downloaded from the source
+    let source = TestSource::new().with_release("TestApp", "3.0.0", b"v3".to_vec());
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let um = app.create_manager(source, Some(options), RecordingLauncher::new()).unwrap();
+    let update = match um.check_for_updates().unwrap() {
+        UpdateCheck::UpdateAvailable(update) => update,
+        _ => panic!("expected an update"),
+    };
+    um.download_updates(&update, None).unwrap();
+    assert_eq!(std::fs::read(app.get_root_dir().join("packages").join("TestApp-3.0.0-full.nupkg")).unwrap(), b"v3");
+}
+
+#[test]
+fn test_request_metadata_is_sent_with_feed_requests() {
+    use std::io::{BufRead, Write};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/updates", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+        let mut request = String::new();
+        while reader.read_line(&mut request).unwrap() > 2 && !request.ends_with("\r\n\r\n") {}
+        let body = r#"{"Assets":[]}"#;
+        let mut writer = stream;
+        write!(writer, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body).unwrap();
+        request
+    });
+
+    let options = UpdateOptions {
+        ExplicitChannel: Some("beta".to_owned()),
+        UserAgent: Some("TestApp/1.0.0 (beta)".to_owned()),
+        FeedQueryParameters: Some("cohort=3&region=eu".to_owned()),
+        RequestHeaders: Some("X-Api-Key: abc123".to_owned()),
+        ..Default::default()
+    };
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let um = app.create_manager(HttpSource::new(&url), Some(options), RecordingLauncher::new()).unwrap();
+    assert!(matches!(um.check_for_updates().unwrap(), UpdateCheck::RemoteIsEmpty));
+    let request = server.join().unwrap();
+    assert!(request.starts_with("GET /updates/releases.beta.json?localVersion=1.0.0&id=TestApp&cohort=3&region=eu HTTP/1.1"));
+    assert!(request.contains("\r\nUser-Agent: TestApp/1.0.0 (beta)\r\n"));
+    assert!(request.contains("\r\nX-Api-Key: abc123\r\n"));
+}
+
+#[test]
+fn test_cached_feed_is_used_while_offline() {
+    let options = UpdateOptions { UseCachedFeedWhenOffline: true, ..Default::default() };
+    let source = TestSource::new().with_release("TestApp", "1.1.0", b"v11".to_vec());
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let um = app.create_manager(source, Some(options.clone()), RecordingLauncher::new()).unwrap();
+    match um.check_for_updates().unwrap() {
+        UpdateCheck::UpdateAvailable(update) => assert!(!update.IsStale),
+        _ => panic!("expected an update"),
+    }
+
+    // the update found by the last successful check is still offered while offline, but flagged as stale
+    let offline = FileSource::new(app.get_root_dir().join("unreachable"));
+    let um = app.create_manager(offline.clone(), Some(options.clone()), RecordingLauncher::new()).unwrap();
+    match um.check_for_updates().unwrap() {
+        UpdateCheck::UpdateAvailable(update) => {
+            assert!(update.IsStale);
+            assert_eq!(update.TargetFullRelease.Version, "1.1.0");
+        }
+        _ => panic!("expected a stale update"),
+    }
+    assert_eq!(um.get_cached_release_feed().unwrap().Assets.len(), 1);
+    // the cache is kept with the packages, because the app root may not be writable
+    let config = app.get_locator_config();
+    assert!(config.PackagesDir.join(crate::constants::CACHED_FEED_FILE).is_file());
+    assert!(!config.RootAppDir.join(crate::constants::CACHED_FEED_FILE).exists());
+
+    // without the option, being offline is an error as before
+    let um = app.create_manager(offline.clone(), None, RecordingLauncher::new()).unwrap();
+    assert!(um.check_for_updates().is_err());
+
+    // offline without an update in the cached feed is reported as such, rather than as no update
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let um = app.create_manager(TestSource::new(), Some(options.clone()), RecordingLauncher::new()).unwrap();
+    assert!(matches!(um.check_for_updates().unwrap(), UpdateCheck::RemoteIsEmpty));
+    let um = app.create_manager(offline, Some(options), RecordingLauncher::new()).unwrap();
+    assert!(matches!(um.check_for_updates().unwrap(), UpdateCheck::NoUpdateAvailableOffline));
+}
+
+#[test]
+fn test_chunked_downloads_are_cached_and_skipped_for_feeds_without_indexes() {
+    let feed_dir = TempDir::new("velopack_chunk_feed").unwrap();
+    let cache_dir = TempDir::new("velopack_chunk_cache").unwrap();
+    let mut state: u32 = 7;
+    let old: Vec<u8> = (0..256 * 1024)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (state >> 16) as u8
+        })
+        .collect();
+    let mut new = old.clone();
+    new[100_000..100_100].fill(0);
+
+    let package = feed_dir.join("TestApp-2.0.0-full.nupkg");
+    std::fs::write(&package, &new).unwrap();
+    let index_path = chunks::write_chunk_index(&package).unwrap();
+    let asset = VelopackAsset {
+        PackageId: "TestApp".to_owned(),
+        Version: "2.0.0".to_owned(),
+        Type: "Full".to_owned(),
+        FileName: "TestApp-2.0.0-full.nupkg".to_owned(),
+        SHA256: util::calculate_file_sha256(&package).unwrap(),
+        Size: new.len() as u64,
+        ..Default::default()
+    };
+    let feed = VelopackAssetFeed { Assets: vec![asset.clone()], ..Default::default() };
+    std::fs::write(feed_dir.join("releases.beta.json"), serde_json::to_string(&feed).unwrap()).unwrap();
+
+    let options = UpdateOptions {
+        ExplicitChannel: Some("beta".to_owned()),
+        SharedCacheDir: Some(cache_dir.path().to_string_lossy().to_string()),
+        ..Default::default()
+    };
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let um = app.create_manager(FileSource::new(&feed_dir), Some(options.clone()), RecordingLauncher::new()).unwrap();
+    let packages_dir = app.get_root_dir().join("packages");
+    std::fs::write(packages_dir.join("TestApp-1.0.0-full.nupkg"), &old).unwrap();
+    let target = packages_dir.join("TestApp-2.0.0-full.nupkg");
+    let update = match um.check_for_updates().unwrap() {
+        UpdateCheck::UpdateAvailable(update) => update,
+        _ => panic!("expected an update"),
+    };
+
+    // a package assembled from chunks is shared with other installs through the cache, like a full download
+    um.download_updates(&update, None).unwrap();
+    assert_eq!(std::fs::read(&target).unwrap(), new);
+    let cached = cache_dir.join("cached.nupkg");
+    assert!(cache::SharedCache::new(&cache_dir, 0).try_get(&asset, &cached).unwrap());
+    assert_eq!(std::fs::read(&cached).unwrap(), new);
+
+    // a feed without chunk indexes (eg. built by vpk) is remembered, so the index is not requested before every download
+    let options = UpdateOptions { SharedCacheDir: None, ..options };
+    let config = app.get_locator_config();
+    let um = UpdateManager::new(FileSource::new(&feed_dir), Some(options), Some(config.clone())).unwrap();
+    let locator = locator::VelopackLocator::new(config.clone(), config.load_manifest().unwrap());
+    std::fs::remove_file(&index_path).unwrap();
+    std::fs::remove_file(&target).unwrap();
+    um.download_updates(&update, None).unwrap();
+    assert_eq!(std::fs::read(&target).unwrap(), new);
+    assert!(locator.get_chunks_unavailable_since("beta").is_some());
+    assert!(locator.get_chunks_unavailable_since("stable").is_none());
+
+    // the index is asked for again once the feed has been remembered for long enough
+    chunks::write_chunk_index(&package).unwrap();
+    std::fs::remove_file(&target).unwrap();
+    um.download_updates(&update, None).unwrap();
+    assert!(locator.get_chunks_unavailable_since("beta").is_some());
+    locator.set_chunks_unavailable_since("beta", Some(0)).unwrap();
+    std::fs::remove_file(&target).unwrap();
+    um.download_updates(&update, None).unwrap();
+    assert_eq!(std::fs::read(&target).unwrap(), new);
+    assert!(locator.get_chunks_unavailable_since("beta").is_none());
+}
@@ -0,0 +1,180 @@
+use std::path::Path;
+#[cfg(target_os = "windows")]
+use std::process::Command as Process;
+
+use crate::Error;
+
+/// Abstraction for the files which record the state of an installed app between launches, such as the version the user
+/// has chosen to skip, the time of the last update check, the cached release feed, the first-run marker of each user,
+/// and login items which are stored as files. Packages are always read from disk, because they are streamed through
+/// zip readers, so an implementation may keep this state in memory to test update logic without touching the disk.
+pub trait FileSystem: Send + Sync {
+    /// Read the contents of a file. Fails with an [`Error::Io`] of kind NotFound if it does not exist.
+    fn read_to_string(&self, path: &Path) -> Result<String, Error>;
+    /// Create or replace a file with the specified contents.
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<(), Error>;
+    /// Create a directory and all of its missing parents.
+    fn create_dir_all(&self, path: &Path) -> Result<(), Error>;
+    /// Delete a file. This is not an error if the file does not exist.
+    fn remove_file(&self, path: &Path) -> Result<(), Error>;
+    /// Returns true if a file or directory exists at the specified path.
+    fn exists(&self, path: &Path) -> bool;
+    /// Clone the file system to create a new lifetime.
+    fn clone_boxed(&self) -> Box<dyn FileSystem>;
+}
+
+impl Clone for Box<dyn FileSystem> {
+    fn clone(&self) -> Self {
+        self.clone_boxed()
+    }
+}
+
+/// The default file system, which reads and writes files on disk with std::fs.
+#[derive(Clone, Default)]
+pub struct DefaultFileSystem {}
+
+impl FileSystem for DefaultFileSystem {
+    fn read_to_string(&self, path: &Path) -> Result<String, Error> {
+        Ok(std::fs::read_to_string(path)?)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<(), Error> {
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<(), Error> {
+        std::fs::create_dir_all(path)?;
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<(), Error> {
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn clone_boxed(&self) -> Box<dyn FileSystem> {
+        Box::new(self.clone())
+    }
+}
+
+/// Abstraction for the string values in the current user's registry, such as the Run key which starts an app at login.
+/// Keys are written the same way as they are passed to reg.exe, eg. "HKCU\Software\Microsoft\Windows\CurrentVersion\Run".
+pub trait Registry: Send + Sync {
+    /// Returns the value with the specified name in a key, or None if the key or value does not exist.
+    fn get_value(&self, key: &str, name: &str) -> Result<Option<String>, Error>;
+    /// Create or replace a value in a key, creating the key if it does not exist.
+    fn set_value(&self, key: &str, name: &str, value: &str) -> Result<(), Error>;
+    /// Delete a value from a key. This is not an error if the value does not exist.
+    fn delete_value(&self, key: &str, name: &str) -> Result<(), Error>;
+    /// Clone the registry to create a new lifetime.
+    fn clone_boxed(&self) -> Box<dyn Registry>;
+}
+
+impl Clone for Box<dyn Registry> {
+    fn clone(&self) -> Self {
+        self.clone_boxed()
+    }
+}
+
+/// The default registry, which reads and writes the Windows registry with reg.exe. There is no registry on other
+/// operating systems, so every operation fails with [`Error::Unsupported`].
+#[derive(Clone, Default)]
+pub struct DefaultRegistry {}
+
+impl Registry for DefaultRegistry {
+    fn get_value(&self, key: &str, name: &str) -> Result<Option<String>, Error> {
+        #[cfg(target_os = "windows")]
+        {
+            // reg.exe exits with an error if the key or value does not exist
+            Ok(run_reg(&["query", key, "/v", name]).ok().and_then(|output| parse_reg_query(&output, name)))
+        }
+        #[cfg(not(target_os = "windows"))]
+        {
+            let _ = (key, name);
+            Err(registry_unsupported())
+        }
+    }
+
+    fn set_value(&self, key: &str, name: &str, value: &str) -> Result<(), Error> {
+        #[cfg(target_os = "windows")]
+        {
+            run_reg(&["add", key, "/v", name, "/t", "REG_SZ", "/d", value, "/f"])?;
+            Ok(())
+        }
+        #[cfg(not(target_os = "windows"))]
+        {
+            let _ = (key, name, value);
+            Err(registry_unsupported())
+        }
+    }
+
+    fn delete_value(&self, key: &str, name: &str) -> Result<(), Error> {
+        #[cfg(target_os = "windows")]
+        {
+            if self.get_value(key, name)?.is_some() {
+                run_reg(&["delete", key, "/v", name, "/f"])?;
+            }
+            Ok(())
+        }
+        #[cfg(not(target_os = "windows"))]
+        {
+            let _ = (key, name);
+            Err(registry_unsupported())
+        }
+    }
+
+    fn clone_boxed(&self) -> Box<dyn Registry> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn registry_unsupported() -> Error {
+    Error::Unsupported("The registry is only available on Windows.".to_owned())
+}
+
+#[cfg(target_os = "windows")]
+fn run_reg(args: &[&str]) -> Result<String, Error> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+    let output = Process::new("reg").args(args).creation_flags(CREATE_NO_WINDOW).output()?;
+    if !output.status.success() {
+        return Err(Error::Generic(format!("reg.exe failed: {}", String::from_utf8_lossy(&output.stderr).trim())));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Finds a value in the output of "reg query", where each value is listed as its name, type and data separated by four
+/// spaces (eg. "    MyApp    REG_SZ    "C:\MyApp\MyApp.exe"").
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+fn parse_reg_query(output: &str, name: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let mut parts = line.trim_start().splitn(3, "    ");
+        let value_name = parts.next()?;
+        let value_type = parts.next()?;
+        if !value_name.eq_ignore_ascii_case(name) || !value_type.starts_with("REG_") {
+            return None;
+        }
+        Some(parts.next().unwrap_or_default().to_owned())
+    })
+}
+
+#[test]
+fn test_parse_reg_query_finds_value() {
+    let output = concat!(
+        "\r\nHKEY_CURRENT_USER\\Software\\Microsoft\\Windows\\CurrentVersion\\Run\r\n",
+        "    OneDrive    REG_SZ    \"C:\\OneDrive.exe\" /background\r\n",
+        "    MyApp    REG_SZ    \"C:\\My App\\MyApp.exe\"\r\n\r\n"
+    );
+    assert_eq!(parse_reg_query(output, "myapp").as_deref(), Some("\"C:\\My App\\MyApp.exe\""));
+    assert_eq!(parse_reg_query(output, "OneDrive").as_deref(), Some("\"C:\\OneDrive.exe\" /background"));
+    assert_eq!(parse_reg_query(output, "My"), None);
+    assert_eq!(parse_reg_query("    Empty    REG_SZ    ", "Empty").as_deref(), Some(""));
+}
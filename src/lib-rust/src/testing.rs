@@ -0,0 +1,475 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::{self, Cursor},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use semver::Version;
+
+use crate::{
+    bundle::{self, BundleWriter, Manifest},
+    download::{DownloadResponse, Downloader, RequestMetadata},
+    locator::VelopackLocatorConfig,
+    process::{ProcessCommand, ProcessLauncher},
+    sources::UpdateSource,
+    system::{FileSystem, Registry},
+    util, Error, UpdateManager, UpdateOptions,
+};
+
+pub use crate::sources::TestSource;
+
+/// An installed app in a temporary directory, laid out the same way as an app installed by Update.exe (a manifest in
+/// 'current', a packages directory, and an updater), so that an UpdateManager can be created for it in tests. The updater
+/// is an empty placeholder, so processes the UpdateManager starts should be captured with a [`RecordingLauncher`].
+/// The directory is deleted when the TestApp is dropped.
+pub struct TestApp {
+    root: TempDir,
+    manifest: Manifest,
+}
+
+impl TestApp {
+    /// Create a new installed app with the specified package id and version.
+    pub fn new(package_id: &str, version: &str) -> Result<TestApp, Error> {
+        Self::from_manifest(Manifest {
+            id: package_id.to_owned(),
+            version: Version::parse(version)?,
+            main_exe: format!("{}.exe", package_id),
+            ..Default::default()
+        })
+    }
+
+    /// Create a new installed app with the specified manifest (eg. to test an app on a specific channel).
+    pub fn from_manifest(manifest: Manifest) -> Result<TestApp, Error> {
+        let app = TestApp { root: TempDir::new("velopack_testapp")?, manifest };
+        let config = app.get_locator_config();
+        fs::create_dir_all(&config.CurrentBinaryDir)?;
+        fs::create_dir_all(&config.PackagesDir)?;
+        fs::write(&config.ManifestPath, bundle::write_manifest_to_string(&app.manifest))?;
+        fs::write(config.CurrentBinaryDir.join(&app.manifest.main_exe), b"")?;
+        fs::write(&config.UpdateExePath, b"")?;
+        Ok(app)
+    }
+
+    /// Replace the installed manifest with the specified version, as if the app had been updated to it.
+    pub fn with_version(mut self, version: &str) -> Result<TestApp, Error> {
+        self.manifest.version = Version::parse(version)?;
+        fs::write(self.get_locator_config().ManifestPath, bundle::write_manifest_to_string(&self.manifest))?;
+        Ok(self)
+    }
+
+    /// Replace the installed manifest with the specified machine architecture (eg. "x64" or "universal"), or none if empty.
+    pub fn with_arch(mut self, arch: &str) -> Result<TestApp, Error> {
+        self.manifest.machine_architecture = arch.to_owned();
+        fs::write(self.get_locator_config().ManifestPath, bundle::write_manifest_to_string(&self.manifest))?;
+        Ok(self)
+    }
+
+    /// Returns the root directory of the app.
+    pub fn get_root_dir(&self) -> &Path {
+        self.root.path()
+    }
+
+    /// Returns the manifest of the installed version.
+    pub fn get_manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// Returns the paths of the app, to pass to [`UpdateManager::new`].
+    pub fn get_locator_config(&self) -> VelopackLocatorConfig {
+        VelopackLocatorConfig {
+            RootAppDir: self.root.to_path_buf(),
+            UpdateExePath: self.root.join("Update.exe"),
+            PackagesDir: self.root.join("packages"),
+            ManifestPath: self.root.join("current").join("sq.version"),
+            CurrentBinaryDir: self.root.join("current"),
+            IsPortable: false,
+        }
+    }
+
+    /// Create an UpdateManager for the app, which checks for updates from the specified source, and records the processes
+    /// it would start with the specified launcher instead of starting them.
+    pub fn create_manager<T: UpdateSource>(
+        &self,
+        source: T,
+        options: Option<UpdateOptions>,
+        launcher: RecordingLauncher,
+    ) -> Result<UpdateManager, Error> {
+        let mut um = UpdateManager::new(source, options, Some(self.get_locator_config()))?;
+        um.set_process_launcher(launcher);
+        Ok(um)
+    }
+
+    /// Create a full release package of the app's current files with the specified version, which can be served by a
+    /// [`TestSource`] or a [`MemoryDownloader`].
+    pub fn create_release_package(&self, version: &str) -> Result<Vec<u8>, Error> {
+        let manifest = Manifest { version: Version::parse(version)?, ..self.manifest.clone() };
+        let package_path = self.root.join(format!("{}-{}-full.nupkg", manifest.id, version));
+        BundleWriter::new(self.root.join("current"), manifest).write_to_file(&package_path)?;
+        let package = fs::read(&package_path)?;
+        let _ = fs::remove_file(&package_path);
+        Ok(package)
+    }
+}
+
+/// A new, empty directory with a unique name in the system temp directory, for tests which need real files (eg. to
+/// write and read packages). The directory and everything in it is deleted when the TempDir is dropped.
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Create a new directory, whose name starts with the specified prefix so that it can be identified if it is leaked.
+    pub fn new(prefix: &str) -> Result<TempDir, Error> {
+        let path = std::env::temp_dir().join(format!("{}_{}", prefix, util::random_string(8)));
+        fs::create_dir_all(&path)?;
+        Ok(TempDir { path })
+    }
+
+    /// Returns the path of the directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the path of a file or directory inside the directory.
+    pub fn join<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.path.join(path)
+    }
+
+    /// Returns the path of the directory as an owned PathBuf.
+    pub fn to_path_buf(&self) -> PathBuf {
+        self.path.clone()
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Records the processes it is asked to start (eg. Update.exe applying an update) instead of starting them, so that
+/// tests can check what the UpdateManager would have done. Clones share the same record.
+#[derive(Clone, Default)]
+pub struct RecordingLauncher {
+    commands: Arc<Mutex<Vec<ProcessCommand>>>,
+    error: Option<String>,
+}
+
+impl RecordingLauncher {
+    /// Create a new RecordingLauncher which records every process and reports it as started.
+    pub fn new() -> RecordingLauncher {
+        Self::default()
+    }
+
+    /// Create a new RecordingLauncher which records every process but fails to start it with the specified message,
+    /// to simulate an updater which is missing or blocked.
+    pub fn failing(message: &str) -> RecordingLauncher {
+        RecordingLauncher { error: Some(message.to_owned()), ..Default::default() }
+    }
+
+    /// Returns the processes which were started, in order.
+    pub fn get_commands(&self) -> Vec<ProcessCommand> {
+        self.commands.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Returns the process which was started most recently, if any.
+    pub fn get_last_command(&self) -> Option<ProcessCommand> {
+        self.get_commands().pop()
+    }
+}
+
+impl ProcessLauncher for RecordingLauncher {
+    fn spawn(&self, command: &ProcessCommand) -> Result<(), Error> {
+        self.commands.lock().unwrap_or_else(|e| e.into_inner()).push(command.clone());
+        match &self.error {
+            Some(message) => Err(Error::Generic(message.clone())),
+            None => Ok(()),
+        }
+    }
+
+    fn clone_boxed(&self) -> Box<dyn ProcessLauncher> {
+        Box::new(self.clone())
+    }
+}
+
+/// Serves files from memory instead of making HTTP requests, so an [`crate::sources::HttpSource`] (or any other source
+/// built on a [`Downloader`]) can be tested without a web server. Files are matched by url, ignoring the query string,
+/// and every request is recorded. Clones share the same files and record.
+#[derive(Clone, Default)]
+pub struct MemoryDownloader {
+    files: HashMap<String, Arc<Vec<u8>>>,
+    requests: Arc<Mutex<Vec<String>>>,
+    metadata: Arc<Mutex<RequestMetadata>>,
+}
+
+impl MemoryDownloader {
+    /// Create a new MemoryDownloader which does not serve any files.
+    pub fn new() -> MemoryDownloader {
+        Self::default()
+    }
+
+    /// Serve the provided contents at the specified url.
+    pub fn with_file(mut self, url: &str, contents: Vec<u8>) -> MemoryDownloader {
+        self.files.insert(strip_query(url).to_owned(), Arc::new(contents));
+        self
+    }
+
+    /// Returns the urls which were requested, including their query string, in order.
+    pub fn get_requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Returns the User-Agent and headers which the update source asked to send with every request.
+    pub fn get_request_metadata(&self) -> RequestMetadata {
+        self.metadata.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn find(&self, url: &str) -> Result<Arc<Vec<u8>>, Error> {
+        self.requests.lock().unwrap_or_else(|e| e.into_inner()).push(url.to_owned());
+        self.files.get(strip_query(url)).cloned().ok_or_else(|| Error::Generic(format!("The url '{}' was not found.", url)))
+    }
+}
+
+impl Downloader for MemoryDownloader {
+    fn get(&self, url: &str) -> Result<DownloadResponse, Error> {
+        let contents = self.find(url)?;
+        Ok(DownloadResponse { content_length: Some(contents.len() as u64), body: Box::new(Cursor::new(contents.to_vec())) })
+    }
+
+    fn get_range(&self, url: &str, offset: u64, length: u64) -> Result<DownloadResponse, Error> {
+        let contents = self.find(url)?;
+        let start = (offset as usize).min(contents.len());
+        let end = start.saturating_add(length as usize).min(contents.len());
+        let part = contents[start..end].to_vec();
+        Ok(DownloadResponse { content_length: Some(part.len() as u64), body: Box::new(Cursor::new(part)) })
+    }
+
+    fn set_request_metadata(&mut self, metadata: &RequestMetadata) {
+        *self.metadata.lock().unwrap_or_else(|e| e.into_inner()) = metadata.clone();
+    }
+
+    fn clone_boxed(&self) -> Box<dyn Downloader> {
+        Box::new(self.clone())
+    }
+}
+
+/// Keeps the state files of an app (see [`FileSystem`]) in memory instead of on disk, so that tests can set up and
+/// inspect state such as the skipped version or the first-run marker without leaving files behind. Clones share the same
+/// files.
+#[derive(Clone, Default)]
+pub struct MemoryFileSystem {
+    // a directory is an entry without contents
+    entries: Arc<Mutex<BTreeMap<PathBuf, Option<Vec<u8>>>>>,
+}
+
+impl MemoryFileSystem {
+    /// Create a new MemoryFileSystem which does not contain any files.
+    pub fn new() -> MemoryFileSystem {
+        Self::default()
+    }
+
+    /// Add a file with the provided contents.
+    pub fn with_file<P: Into<PathBuf>>(self, path: P, contents: &[u8]) -> MemoryFileSystem {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).insert(path.into(), Some(contents.to_vec()));
+        self
+    }
+
+    /// Returns the contents of a file, if it exists.
+    pub fn get_file(&self, path: &Path) -> Option<Vec<u8>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).get(path).cloned().flatten()
+    }
+
+    /// Returns the paths of every file, in order.
+    pub fn get_files(&self) -> Vec<PathBuf> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).iter().filter(|(_, c)| c.is_some()).map(|(p, _)| p.clone()).collect()
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn read_to_string(&self, path: &Path) -> Result<String, Error> {
+        let contents = self.get_file(path).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{:?} does not exist.", path)))?;
+        String::from_utf8(contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e).into())
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<(), Error> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).insert(path.to_path_buf(), Some(contents.to_vec()));
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<(), Error> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        for dir in path.ancestors().filter(|p| !p.as_os_str().is_empty()) {
+            entries.entry(dir.to_path_buf()).or_insert(None);
+        }
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<(), Error> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(Some(_)) = entries.get(path) {
+            entries.remove(path);
+        }
+        Ok(())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).contains_key(path)
+    }
+
+    fn clone_boxed(&self) -> Box<dyn FileSystem> {
+        Box::new(self.clone())
+    }
+}
+
+/// Keeps registry values in memory instead of in the Windows registry, so that login items can be tested on any operating
+/// system. Keys and value names are case-insensitive, as they are in the registry. Clones share the same values.
+#[derive(Clone, Default)]
+pub struct MemoryRegistry {
+    values: Arc<Mutex<HashMap<(String, String), String>>>,
+}
+
+impl MemoryRegistry {
+    /// Create a new MemoryRegistry which does not contain any values.
+    pub fn new() -> MemoryRegistry {
+        Self::default()
+    }
+
+    fn get_id(key: &str, name: &str) -> (String, String) {
+        (key.to_ascii_lowercase(), name.to_ascii_lowercase())
+    }
+}
+
+impl Registry for MemoryRegistry {
+    fn get_value(&self, key: &str, name: &str) -> Result<Option<String>, Error> {
+        Ok(self.values.lock().unwrap_or_else(|e| e.into_inner()).get(&Self::get_id(key, name)).cloned())
+    }
+
+    fn set_value(&self, key: &str, name: &str, value: &str) -> Result<(), Error> {
+        self.values.lock().unwrap_or_else(|e| e.into_inner()).insert(Self::get_id(key, name), value.to_owned());
+        Ok(())
+    }
+
+    fn delete_value(&self, key: &str, name: &str) -> Result<(), Error> {
+        self.values.lock().unwrap_or_else(|e| e.into_inner()).remove(&Self::get_id(key, name));
+        Ok(())
+    }
+
+    fn clone_boxed(&self) -> Box<dyn Registry> {
+        Box::new(self.clone())
+    }
+}
+
+fn strip_query(url: &str) -> &str {
+    url.split(['?', '#']).next().unwrap_or(url)
+}
+
+#[test]
+fn test_update_flow_runs_against_fakes() {
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let package = app.create_release_package("2.0.0").unwrap();
+    assert_eq!(bundle::load_bundle_from_memory(&package).unwrap().read_manifest().unwrap().version, Version::new(2, 0, 0));
+
+    let launcher = RecordingLauncher::new();
+    let source = TestSource::new().with_release("TestApp", "2.0.0", package);
+    let um = app.create_manager(source, None, launcher.clone()).unwrap();
+    let update = match um.check_for_updates().unwrap() {
+        crate::UpdateCheck::UpdateAvailable(update) => update,
+        _ => panic!("expected an update"),
+    };
+    um.download_updates(&update, None).unwrap();
+    assert!(app.get_root_dir().join("packages").join("TestApp-2.0.0-full.nupkg").is_file());
+
+    um.wait_exit_then_apply_updates(&update, true, false, ["--launched"]).unwrap();
+    let command = launcher.get_last_command().unwrap();
+    assert_eq!(command.program, app.get_locator_config().UpdateExePath);
+    assert_eq!(command.current_dir.as_deref(), Some(app.get_root_dir()));
+    assert_eq!(command.args.first().map(|a| a.as_str()), Some("apply"));
+    assert!(command.get_arg_value("--package").unwrap().ends_with("TestApp-2.0.0-full.nupkg"));
+    assert!(command.has_arg("--silent") && command.has_arg("--norestart"));
+    assert_eq!(command.args.last().map(|a| a.as_str()), Some("--launched"));
+
+    // an updater which can not be started is reported to the app
+    let launcher = RecordingLauncher::failing("blocked");
+    let um = app.create_manager(TestSource::new(), None, launcher.clone()).unwrap();
+    assert!(um.wait_exit_then_apply_updates(&update, true, false, Vec::<String>::new()).is_err());
+    assert_eq!(launcher.get_commands().len(), 1);
+}
+
+#[test]
+fn test_memory_downloader_serves_http_source() {
+    let feed = r#"{"Assets":[{"PackageId":"TestApp","Version":"2.0.0","Type":"Full","FileName":"TestApp-2.0.0-full.nupkg"}]}"#;
+    let downloader = MemoryDownloader::new()
+        .with_file("mem://updates/releases.stable.json", feed.as_bytes().to_vec())
+        .with_file("mem://updates/TestApp-2.0.0-full.nupkg", b"package".to_vec());
+    let source = crate::sources::HttpSource::with_downloader("mem://updates", downloader.clone());
+    let manifest = Manifest { id: "TestApp".to_owned(), version: Version::new(1, 0, 0), ..Default::default() };
+
+    let feed = source.get_release_feed("stable", &manifest).unwrap();
+    assert_eq!(feed.Assets.len(), 1);
+    assert_eq!(source.download_release_entry_range(&feed.Assets[0], 2, 3).unwrap(), b"cka");
+    assert!(source.get_release_feed("beta", &manifest).is_err());
+    let requests = downloader.get_requests();
+    assert!(requests[1].ends_with("TestApp-2.0.0-full.nupkg"));
+    assert!(requests[0].starts_with("mem://updates/releases.stable.json?localVersion=1.0.0&id=TestApp"));
+}
+
+#[test]
+fn test_request_headers_are_passed_to_custom_downloaders() {
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let downloader = MemoryDownloader::new();
+    let source = crate::sources::HttpSource::with_downloader("mem://updates", downloader.clone());
+    let options = UpdateOptions {
+        UserAgent: Some("TestApp/1.0.0".to_owned()),
+        RequestHeaders: Some("X-Api-Key: abc123\r\nX-Tenant:  contoso \n\nnot a header".to_owned()),
+        ..Default::default()
+    };
+    let _um = app.create_manager(source, Some(options), RecordingLauncher::new()).unwrap();
+    let metadata = downloader.get_request_metadata();
+    assert_eq!(metadata.user_agent.as_deref(), Some("TestApp/1.0.0"));
+    let expected = [("X-Api-Key", "abc123"), ("X-Tenant", "contoso")];
+    assert_eq!(metadata.headers, expected.map(|(n, v)| (n.to_owned(), v.to_owned())));
+}
+
+#[test]
+fn test_app_state_is_kept_in_memory() {
+    let app = TestApp::new("TestApp", "1.0.0").unwrap();
+    let memory = MemoryFileSystem::new();
+    let mut um = app.create_manager(TestSource::new(), None, RecordingLauncher::new()).unwrap();
+    um.set_file_system(memory.clone());
+
+    um.skip_version(&crate::VelopackAsset { Version: "2.0.0".to_owned(), ..Default::default() }).unwrap();
+    assert_eq!(um.get_skipped_version(), Some(Version::new(2, 0, 0)));
+    let marker = app.get_root_dir().join(crate::constants::SKIPPED_VERSION_MARKER);
+    assert_eq!(memory.get_file(&marker).as_deref(), Some(&b"2.0.0"[..]));
+    assert!(!marker.exists());
+    um.clear_skipped_version().unwrap();
+    assert!(memory.get_files().is_empty());
+
+    let memory = memory.with_file(&marker, b"3.0.0");
+    assert_eq!(um.get_skipped_version(), Some(Version::new(3, 0, 0)));
+    assert!(matches!(memory.read_to_string(&app.get_root_dir().join("missing")), Err(Error::Io(e)) if e.kind() == io::ErrorKind::NotFound));
+    memory.create_dir_all(&app.get_root_dir().join("a").join("b")).unwrap();
+    assert!(memory.exists(&app.get_root_dir().join("a")));
+    assert_eq!(memory.get_files(), vec![marker]);
+
+    let registry = MemoryRegistry::new();
+    registry.set_value("HKCU\\Software\\TestApp", "Name", "value").unwrap();
+    assert_eq!(registry.get_value("hkcu\\software\\testapp", "NAME").unwrap().as_deref(), Some("value"));
+    registry.delete_value("HKCU\\Software\\TestApp", "Name").unwrap();
+    registry.delete_value("HKCU\\Software\\TestApp", "Name").unwrap();
+    assert_eq!(registry.get_value("HKCU\\Software\\TestApp", "Name").unwrap(), None);
+
+    let dir = TempDir::new("velopack_tempdir").unwrap();
+    let path = dir.to_path_buf();
+    fs::write(dir.join("file"), b"").unwrap();
+    drop(dir);
+    assert!(!path.exists());
+}
@@ -0,0 +1,211 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command as Process,
+    time::Duration,
+};
+
+use semver::Version;
+
+use crate::{bundle::BundleZip, constants, util, Error};
+
+/// How long a new updater is given to print its version before it is considered broken.
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Returns the path a new updater is staged at before it replaces the current one.
+pub fn get_staged_updater_path(update_exe: &Path) -> PathBuf {
+    update_exe.with_file_name(constants::STAGED_UPDATER_FILE)
+}
+
+/// Returns the path the current updater is moved to while it is being replaced.
+pub fn get_old_updater_path(update_exe: &Path) -> PathBuf {
+    update_exe.with_file_name(constants::OLD_UPDATER_FILE)
+}
+
+/// Extracts the updater from a package and replaces the current updater with it, if it is newer. Returns true if the
+/// updater was replaced. If the current updater can not be replaced right now, the new one is left staged and is swapped
+/// in by [apply_staged_updater] the next time an update is applied. Packages without an updater are ignored.
+pub fn stage_updater_from_bundle(bundle: &BundleZip, update_exe: &Path) -> Result<bool, Error> {
+    if bundle.find_zip_file(|name| name.ends_with("Squirrel.exe")).is_none() {
+        debug!("The package does not contain an updater, skipping.");
+        return Ok(false);
+    }
+    let staged = get_staged_updater_path(update_exe);
+    let temp_file = update_exe.with_file_name(format!("Update.{}.exe", util::random_string(8)));
+    bundle.extract_zip_predicate_to_path(|name| name.ends_with("Squirrel.exe"), &temp_file)?;
+    let result = stage_updater(&temp_file, update_exe, &staged);
+    let _ = fs::remove_file(&temp_file);
+    result?;
+    apply_staged_updater(update_exe)
+}
+
+/// Verifies a new updater and moves it to the staged path if it is newer than the current updater.
+fn stage_updater(new_file: &Path, update_exe: &Path, staged: &Path) -> Result<(), Error> {
+    // the publisher is checked before the new updater is run for the first time
+    #[cfg(target_os = "windows")]
+    verify_updater_publisher(new_file, update_exe)?;
+
+    let new_version = get_updater_version(new_file)
+        .map_err(|e| Error::Generic(format!("The updater in the package could not be verified, it will not be used ({}).", e)))?;
+    match get_updater_version(update_exe) {
+        Ok(current_version) if new_version <= current_version => {
+            info!("The current updater ({}) is up to date with the package ({}).", current_version, new_version);
+            let _ = fs::remove_file(staged);
+            return Ok(());
+        }
+        Ok(current_version) => info!("Staging updater {} to replace {}.", new_version, current_version),
+        Err(e) => warn!("The current updater could not report its version, it will be replaced with {} ({}).", new_version, e),
+    }
+    util::retry_io(|| fs::rename(new_file, staged))?;
+    Ok(())
+}
+
+/// Replaces the current updater with a staged one, if there is one, and returns true if it was replaced. A running
+/// executable can be renamed but not overwritten, so the current updater is moved out of the way first, and restored if
+/// the new one can not be moved into place. The old updater is deleted once it is no longer running.
+pub fn apply_staged_updater(update_exe: &Path) -> Result<bool, Error> {
+    let staged = get_staged_updater_path(update_exe);
+    let old = get_old_updater_path(update_exe);
+    let _ = fs::remove_file(&old);
+    if !staged.is_file() {
+        return Ok(false);
+    }
+    if old.exists() {
+        info!("The previous updater is still running, the new updater will be used after the next update.");
+        return Ok(false);
+    }
+
+    if update_exe.exists() {
+        util::retry_io(|| fs::rename(update_exe, &old))?;
+    }
+    if let Err(e) = util::retry_io(|| fs::rename(&staged, update_exe)) {
+        let _ = fs::rename(&old, update_exe);
+        return Err(e.into());
+    }
+    let _ = fs::remove_file(&old);
+    info!("The updater at '{}' was replaced successfully.", update_exe.to_string_lossy());
+    Ok(true)
+}
+
+/// Runs an updater with --version to check that it starts, and returns the version it reports.
+fn get_updater_version(path: &Path) -> Result<Version, Error> {
+    #[cfg(target_os = "windows")]
+    {
+        use std::io::Read;
+        let mut header = [0u8; 2];
+        fs::File::open(path)?.read_exact(&mut header)?;
+        if &header != b"MZ" {
+            return Err(Error::Generic("The file is not an executable.".to_owned()));
+        }
+    }
+
+    let mut process = Process::new(path);
+    process.arg("--version");
+    let output = util::run_process_no_console_and_wait(process, VERIFY_TIMEOUT)?;
+    if !output.status.success() {
+        return Err(Error::Generic(format!("The updater exited with {}.", output.status)));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = stdout.split_whitespace().last().ok_or_else(|| Error::Generic("The updater did not print a version.".to_owned()))?;
+    Ok(Version::parse(version)?)
+}
+
+/// If the current updater has a valid Authenticode signature, the new updater must have a valid signature from the same
+/// publisher, so that a package can not replace a signed updater with one built by someone else. Unsigned updaters (eg.
+/// local development builds) are not verified.
+#[cfg(target_os = "windows")]
+fn verify_updater_publisher(new_file: &Path, update_exe: &Path) -> Result<(), Error> {
+    let current = match get_authenticode_signer(update_exe) {
+        Ok(Some(subject)) => subject,
+        Ok(None) => {
+            warn!("The current updater is not signed, so the publisher of the new updater will not be verified.");
+            return Ok(());
+        }
+        Err(e) => {
+            warn!("The signature of the current updater could not be read, the new updater will not be verified ({}).", e);
+            return Ok(());
+        }
+    };
+    match get_authenticode_signer(new_file)? {
+        Some(subject) if subject == current => {
+            info!("Verified the publisher of the new updater ({}).", subject);
+            Ok(())
+        }
+        Some(subject) => Err(Error::Generic(format!("The new updater is signed by '{}', but the current one by '{}'.", subject, current))),
+        None => Err(Error::Generic(format!("The new updater is not signed, but the current one is signed by '{}'.", current))),
+    }
+}
+
+/// Returns the subject of the certificate which signed a file, or None if it is not signed. A signature which is
+/// present but not valid is an error.
+#[cfg(target_os = "windows")]
+fn get_authenticode_signer(path: &Path) -> Result<Option<String>, Error> {
+    // the path is passed in the environment, so it never needs to be escaped for PowerShell
+    let mut process = Process::new("powershell.exe");
+    process.args(["-NoProfile", "-NonInteractive", "-Command"]);
+    process.arg("$s = Get-AuthenticodeSignature -LiteralPath $env:VELOPACK_SIGNED_FILE; \"$($s.Status)|$($s.SignerCertificate.Subject)\"");
+    process.env("VELOPACK_SIGNED_FILE", path);
+    let output = util::run_process_no_console_and_wait(process, VERIFY_TIMEOUT)?;
+    if !output.status.success() {
+        return Err(Error::Generic(format!("Get-AuthenticodeSignature exited with {}.", output.status)));
+    }
+    parse_authenticode_signer(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(target_os = "windows")]
+fn parse_authenticode_signer(output: &str) -> Result<Option<String>, Error> {
+    let (status, subject) = output.trim().split_once('|').ok_or_else(|| Error::Generic("No signature status was returned.".to_owned()))?;
+    match status {
+        "NotSigned" => Ok(None),
+        "Valid" if !subject.is_empty() => Ok(Some(subject.to_owned())),
+        _ => Err(Error::Generic(format!("The signature is not valid ({}).", status))),
+    }
+}
+
+#[cfg(target_os = "windows")]
+#[test]
+fn test_parse_authenticode_signer() {
+    assert_eq!(
+        parse_authenticode_signer("Valid|CN=Velopack Ltd, O=Velopack Ltd\r\n").unwrap().as_deref(),
+        Some("CN=Velopack Ltd, O=Velopack Ltd")
+    );
+    assert_eq!(parse_authenticode_signer("NotSigned|").unwrap(), None);
+    assert!(parse_authenticode_signer("HashMismatch|CN=Velopack Ltd").is_err());
+    assert!(parse_authenticode_signer("").is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_updater_is_only_replaced_by_a_newer_working_updater() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = crate::testing::TempDir::new("velopack_updater").unwrap();
+    let write_updater = |name: &str, script: &str| {
+        let path = dir.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    };
+    let update_exe = write_updater("Update.exe", "echo Update 1.0.0");
+    let staged = get_staged_updater_path(&update_exe);
+
+    // an updater which does not run is never trusted
+    let broken = write_updater("broken.exe", "exit 1");
+    assert!(stage_updater(&broken, &update_exe, &staged).is_err());
+    assert!(!staged.exists());
+
+    // an older updater is ignored
+    let older = write_updater("older.exe", "echo Update 0.9.0");
+    stage_updater(&older, &update_exe, &staged).unwrap();
+    assert!(!staged.exists());
+    assert!(!apply_staged_updater(&update_exe).unwrap());
+
+    // a newer updater is staged, and then swapped in without leaving the old one behind
+    let newer = write_updater("newer.exe", "echo Update 1.1.0");
+    stage_updater(&newer, &update_exe, &staged).unwrap();
+    assert!(staged.exists());
+    assert!(apply_staged_updater(&update_exe).unwrap());
+    assert_eq!(get_updater_version(&update_exe).unwrap(), Version::new(1, 1, 0));
+    assert!(!staged.exists());
+    assert!(!get_old_updater_path(&update_exe).exists());
+}
@@ -2,9 +2,12 @@ use crate::Error;
 use rand::distributions::{Alphanumeric, DistString};
 use sha2::Digest;
 use std::fs::File;
-use std::path::Path;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command as Process, Output, Stdio};
+use std::sync::mpsc::{self, Receiver};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 pub fn retry_io<F, T, E>(op: F) -> Result<T, E>
 where
@@ -38,10 +41,74 @@ where
     op()
 }
 
+/// Returns the current time, in seconds since the unix epoch.
+pub fn get_unix_time() -> Option<u64> {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).ok().map(|d| d.as_secs())
+}
+
+/// Returns the time the system was last booted, in seconds since the unix epoch.
+#[cfg(target_os = "windows")]
+pub fn get_system_boot_time() -> Option<u64> {
+    let now = get_unix_time()?;
+    let uptime = unsafe { windows::Win32::System::SystemInformation::GetTickCount64() } / 1000;
+    Some(now.saturating_sub(uptime))
+}
+
+/// Returns the time the system was last booted, in seconds since the unix epoch.
+#[cfg(target_os = "linux")]
+pub fn get_system_boot_time() -> Option<u64> {
+    let stat = std::fs::read_to_string("/proc/stat").ok()?;
+    stat.lines().find_map(|l| l.strip_prefix("btime ")).and_then(|v| v.trim().parse().ok())
+}
+
+/// Returns the time the system was last booted, in seconds since the unix epoch.
+#[cfg(target_os = "macos")]
+pub fn get_system_boot_time() -> Option<u64> {
+    None
+}
+
+/// Returns the per-user directory for local application data (eg. %LOCALAPPDATA% on Windows).
+#[cfg(target_os = "windows")]
+pub fn get_user_data_dir() -> Option<PathBuf> {
+    std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+}
+
+/// Returns the per-user directory for local application data (eg. ~/.local/share on Linux).
+#[cfg(target_os = "linux")]
+pub fn get_user_data_dir() -> Option<PathBuf> {
+    if let Some(xdg) = std::env::var_os("XDG_DATA_HOME").filter(|v| !v.is_empty()) {
+        return Some(PathBuf::from(xdg));
+    }
+    crate::locator::get_home_dir().ok().map(|h| h.join(".local").join("share"))
+}
+
+/// Returns the per-user directory for cached files which can be recreated (eg. ~/.cache on Linux).
+#[cfg(target_os = "linux")]
+pub fn get_user_cache_dir() -> Option<PathBuf> {
+    if let Some(xdg) = std::env::var_os("XDG_CACHE_HOME").filter(|v| !v.is_empty()) {
+        return Some(PathBuf::from(xdg));
+    }
+    crate::locator::get_home_dir().ok().map(|h| h.join(".cache"))
+}
+
+/// Returns the per-user directory for local application data (eg. ~/Library/Application Support on MacOS).
+#[cfg(target_os = "macos")]
+pub fn get_user_data_dir() -> Option<PathBuf> {
+    crate::locator::get_home_dir().ok().map(|h| h.join("Library").join("Application Support"))
+}
+
 pub fn random_string(len: usize) -> String {
     Alphanumeric.sample_string(&mut rand::thread_rng(), len)
 }
 
+pub fn calculate_sha256(bytes: &[u8]) -> String {
+    format!("{:x}", sha2::Sha256::digest(bytes))
+}
+
+pub fn calculate_sha1(bytes: &[u8]) -> String {
+    format!("{:x}", sha1::Sha1::digest(bytes))
+}
+
 pub fn calculate_file_sha256<P: AsRef<Path>>(file: P) -> Result<String, Error> {
     let mut file = File::open(file)?;
     let mut sha256 = sha2::Sha256::new();
@@ -56,4 +123,77 @@ pub fn calculate_file_sha1<P: AsRef<Path>>(file: P) -> Result<String, Error> {
     std::io::copy(&mut file, &mut sha1o)?;
     let hash = sha1o.finalize();
     Ok(format!("{:x}", hash))
-}
\ No newline at end of file
+}
+
+/// Runs a process without a console window and waits for it to exit, returning its output. Both pipes are drained while
+/// waiting, because a process which writes more than the pipe buffer blocks until it is read, and would never exit. If
+/// the process has not exited and closed its output by the timeout (eg. a process it started is still writing to it),
+/// it is killed and an error is returned.
+pub fn run_process_no_console_and_wait(mut command: Process, timeout: Duration) -> Result<Output, Error> {
+    command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    let deadline = Instant::now() + timeout;
+    let mut child = command.spawn()?;
+    let stdout = drain_pipe(child.stdout.take());
+    let stderr = drain_pipe(child.stderr.take());
+    let timed_out = || Error::Generic(format!("The process did not exit within {}s.", timeout.as_secs_f32()));
+
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(timed_out());
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+
+    // the reader threads are left behind if the pipes are never closed, rather than blocking here forever
+    let read = |rx: Receiver<Vec<u8>>| rx.recv_timeout(deadline.saturating_duration_since(Instant::now())).map_err(|_| timed_out());
+    Ok(Output { status, stdout: read(stdout)?, stderr: read(stderr)? })
+}
+
+fn drain_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> Receiver<Vec<u8>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut output);
+        }
+        let _ = tx.send(output);
+    });
+    rx
+}
+
+#[cfg(unix)]
+#[test]
+fn test_run_process_drains_chatty_output_without_deadlock() {
+    // each stream is far larger than a pipe buffer, which used to block the child until the timeout
+    let mut command = Process::new("/bin/sh");
+    command.args(["-c", "head -c 1000000 /dev/zero; head -c 700000 /dev/zero >&2; echo done"]);
+    let started = Instant::now();
+    let output = run_process_no_console_and_wait(command, Duration::from_secs(30)).unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout.len(), 1_000_005);
+    assert!(output.stdout.ends_with(b"done\n"));
+    assert_eq!(output.stderr.len(), 700_000);
+    assert!(started.elapsed() < Duration::from_secs(10));
+
+    // the timeout is still enforced, for the process itself and for anything it leaves writing to its output
+    let mut command = Process::new("/bin/sh");
+    command.args(["-c", "sleep 10"]);
+    let started = Instant::now();
+    assert!(run_process_no_console_and_wait(command, Duration::from_millis(300)).is_err());
+    let mut command = Process::new("/bin/sh");
+    command.args(["-c", "sleep 10 & echo started"]);
+    assert!(run_process_no_console_and_wait(command, Duration::from_millis(300)).is_err());
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
@@ -0,0 +1,41 @@
+use std::cmp::Ordering;
+
+use semver::Version;
+
+/// Compares two versions by SemVer 2.0 precedence. Numeric prerelease identifiers are compared as numbers (so
+/// '2.0.0-beta.2' < '2.0.0-beta.10'), a prerelease is lower than the same version without one, and build metadata
+/// is ignored. This differs from the `Ord` implementation of [`Version`], which also orders by build metadata, so
+/// two builds of the same release would otherwise be seen as an upgrade or downgrade of one another.
+pub fn compare(a: &Version, b: &Version) -> Ordering {
+    (a.major, a.minor, a.patch, &a.pre).cmp(&(b.major, b.minor, b.patch, &b.pre))
+}
+
+/// Returns true if the candidate version has a higher precedence than the current version.
+pub fn is_newer(candidate: &Version, current: &Version) -> bool {
+    compare(candidate, current) == Ordering::Greater
+}
+
+/// Returns true if the two versions have the same precedence (ie. they only differ by build metadata, if at all).
+pub fn is_same(a: &Version, b: &Version) -> bool {
+    compare(a, b) == Ordering::Equal
+}
+
+#[test]
+fn test_compare_orders_prereleases_by_precedence() {
+    let mut versions: Vec<Version> = ["2.0.0", "2.0.0-beta.10", "1.0.0", "2.0.0-alpha", "2.0.0-beta.2", "2.0.0-beta", "2.0.0-rc.1", "2.0.0-alpha.1"]
+        .iter()
+        .map(|v| Version::parse(v).unwrap())
+        .collect();
+    versions.sort_by(compare);
+    let sorted: Vec<String> = versions.iter().map(|v| v.to_string()).collect();
+    assert_eq!(sorted, vec!["1.0.0", "2.0.0-alpha", "2.0.0-alpha.1", "2.0.0-beta", "2.0.0-beta.2", "2.0.0-beta.10", "2.0.0-rc.1", "2.0.0"]);
+}
+
+#[test]
+fn test_compare_ignores_build_metadata() {
+    let a = Version::parse("1.2.3+build.5").unwrap();
+    let b = Version::parse("1.2.3+build.4").unwrap();
+    assert!(is_same(&a, &b));
+    assert!(!is_newer(&a, &b));
+    assert!(is_newer(&Version::parse("1.2.4-beta+abc").unwrap(), &a));
+}
@@ -51,6 +51,12 @@ public class PackTask : MSBuildAsyncTask
 
     public string? Exclude { get; set; }
 
+    public string? PackagesDir { get; set; }
+
+    public bool Mandatory { get; set; }
+
+    public bool SideBySide { get; set; }
+
     public bool NoPortable { get; private set; }
 
     public bool NoInst { get; private set; }
@@ -92,6 +98,12 @@ public class PackTask : MSBuildAsyncTask
     public string? Categories { get; set; }
 
     public string? Shortcuts { get; set; }
+
+    public string? UninstallSurveyUrl { get; set; }
+
+    public string? OsMinServerVersion { get; set; }
+
+    public bool ExternalPayload { get; set; }
     
     public string? Compression { get; set; }
 
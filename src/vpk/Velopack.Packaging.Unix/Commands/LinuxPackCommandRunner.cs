@@ -1,5 +1,6 @@
 ﻿using ELFSharp.ELF;
 using Microsoft.Extensions.Logging;
+using NuGet.Versioning;
 using Velopack.Packaging.Abstractions;
 using Velopack.Util;
 
@@ -83,6 +84,7 @@ protected override Task<string> PreprocessPackDir(Action<int> progress, string p
             : GetMachineForBinary(MainExePath);
 
         // velopack required files
+        ExtraNuspecMetadata["shortcutLocations"] = GetShortcutLocations(Options.Shortcuts);
         File.WriteAllText(Path.Combine(bin.FullName, "sq.version"), GenerateNuspecContent());
         File.Copy(HelperFile.GetUpdatePath(Options.TargetRuntime, Log), Path.Combine(bin.FullName, "UpdateNix"), true);
         progress(100);
@@ -127,6 +129,31 @@ protected override Task CreateReleasePackage(Action<int> progress, string packDi
         return base.CreateReleasePackage(progress, dir.FullName, outputPath);
     }
 
+    protected override IEnumerable<(string Format, string FileName)> GetNativePackages()
+    {
+        var version = SemanticVersion.Parse(Options.PackVersion).ToNormalizedString();
+        foreach (var format in new[] { LinuxPackageTool.FormatDeb, LinuxPackageTool.FormatRpm }) {
+            if (format == LinuxPackageTool.FormatDeb ? Options.Deb : Options.Rpm) {
+                yield return (format, LinuxPackageTool.GetSuggestedPackageName(Options.PackId, version, Options.Channel, format));
+            }
+        }
+    }
+
+    protected override Task CreateNativePackage(Action<int> progress, string format, string packDir, string outputPath)
+    {
+        progress(-1);
+        var version = SemanticVersion.Parse(Options.PackVersion).ToNormalizedString();
+        var tool = new LinuxPackageTool(Log, Options.PackId, version, Options.PackTitle, Options.PackAuthors, Options.TargetRuntime.Architecture);
+        if (format == LinuxPackageTool.FormatDeb) {
+            tool.CreateDebPackage(PortablePackagePath, packDir, outputPath);
+        } else {
+            var workDir = TempDir.CreateSubdirectory("CreateNativePackage." + format);
+            tool.CreateRpmPackage(PortablePackagePath, packDir, outputPath, workDir.FullName);
+        }
+        progress(100);
+        return Task.CompletedTask;
+    }
+
     protected override Task<string> CreateDeltaPackage(Action<int> progress, string releasePkg, string prevReleasePkg, string outputPkg, DeltaMode mode)
     {
         progress(-1); // there is only one "file", so progress will not work
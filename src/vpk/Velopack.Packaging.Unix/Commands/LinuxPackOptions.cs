@@ -30,6 +30,12 @@ public class LinuxPackOptions : IPackOptions
 
     public string Exclude { get; set; }
 
+    public string PackagesDir { get; set; }
+
+    public bool Mandatory { get; set; }
+
+    public bool SideBySide { get; set; }
+
     public bool NoPortable { get; set; }
 
     public bool NoInst { get; set; }
@@ -37,4 +43,10 @@ public class LinuxPackOptions : IPackOptions
     public string Categories { get; set; }
     
     public string Compression { get; set; }
+
+    public bool Deb { get; set; }
+
+    public bool Rpm { get; set; }
+
+    public string Shortcuts { get; set; }
 }
@@ -46,6 +46,7 @@ protected override Task<string> PreprocessPackDir(Action<int> progress, string p
 
         var structure = new OsxStructureBuilder(dir.FullName);
         var macosdir = structure.MacosDirectory;
+        ExtraNuspecMetadata["shortcutLocations"] = GetShortcutLocations(Options.Shortcuts);
         File.WriteAllText(Path.Combine(macosdir, "sq.version"), GenerateNuspecContent());
         File.Copy(HelperFile.GetUpdatePath(Options.TargetRuntime, Log), Path.Combine(macosdir, "UpdateMac"), true);
 
@@ -35,4 +35,12 @@ public class OsxPackOptions : OsxBundleOptions, IPackOptions
     public string Channel { get; set; }
 
     public string Exclude { get; set; }
+
+    public string PackagesDir { get; set; }
+
+    public bool Mandatory { get; set; }
+
+    public bool SideBySide { get; set; }
+
+    public string Shortcuts { get; set; }
 }
@@ -0,0 +1,298 @@
+using System.Text;
+using System.Text.RegularExpressions;
+using ICSharpCode.SharpZipLib.GZip;
+using ICSharpCode.SharpZipLib.Tar;
+using Microsoft.Extensions.Logging;
+using Velopack.Compression;
+using Velopack.Util;
+
+namespace Velopack.Packaging.Unix;
+
+/// <summary>
+/// Wraps a Velopack AppImage into a .deb or .rpm package. The package installs the AppImage to /opt/{id}, along with a
+/// launcher entry and a command on the PATH, and its scripts create the Velopack packages directory so that the app can
+/// still download and apply its own updates after being installed by the system package manager.
+/// </summary>
+public class LinuxPackageTool
+{
+    public const string FormatDeb = "deb";
+
+    public const string FormatRpm = "rpm";
+
+    private readonly ILogger _logger;
+
+    private readonly string _packId;
+
+    private readonly string _version;
+
+    private readonly string _title;
+
+    private readonly string _authors;
+
+    private readonly RuntimeCpu _machine;
+
+    public LinuxPackageTool(ILogger logger, string packId, string version, string title, string authors, RuntimeCpu machine)
+    {
+        _logger = logger;
+        _packId = packId;
+        _version = version;
+        _title = title ?? packId;
+        _authors = authors ?? packId;
+        _machine = machine;
+    }
+
+    /// <summary> The path the AppImage is installed to by the native package. </summary>
+    public string InstallPath => $"/opt/{_packId}/{_packId}.AppImage";
+
+    /// <summary> The package name, which must be lower-case and only contain characters allowed by dpkg and rpm. </summary>
+    public string PackageName => Regex.Replace(_packId.ToLowerInvariant(), "[^a-z0-9.+-]", "-");
+
+    /// <summary> The version, with any pre-release label converted so that it sorts before the release itself. </summary>
+    public string PackageVersion => _version.Replace('-', '~');
+
+    public static string GetSuggestedPackageName(string id, string version, string channel, string format)
+    {
+        return $"{id}-{version}-{channel}.{format}";
+    }
+
+    public void CreateDebPackage(string appImagePath, string appDir, string outputFile)
+    {
+        var arch = _machine switch {
+            RuntimeCpu.x86 => "i386",
+            RuntimeCpu.x64 => "amd64",
+            RuntimeCpu.arm64 => "arm64",
+            _ => throw new ArgumentOutOfRangeException(nameof(_machine), _machine, null)
+        };
+
+        var files = GetPackageFiles(appImagePath, appDir);
+        var installedSize = files.Sum(f => f.Contents.LongLength) / 1024 + 1;
+
+        var control = $"""
+            Package: {PackageName}
+            Version: {PackageVersion}
+            Architecture: {arch}
+            Maintainer: {_authors}
+            Installed-Size: {installedSize}
+            Section: misc
+            Priority: optional
+            Description: {_title}
+             {_title} {_version}, which keeps itself up to date with Velopack.
+
+            """.Replace("\r", "");
+
+        var controlFiles = new List<PackageFile> {
+            new("control", Encoding.UTF8.GetBytes(control), 420),
+            new("postinst", Encoding.UTF8.GetBytes("#!/bin/sh\nset -e\n" + GetPostInstallScript()), 493),
+            new("postrm", Encoding.UTF8.GetBytes($"#!/bin/sh\nset -e\nif [ \"$1\" = \"remove\" ] || [ \"$1\" = \"purge\" ]; then\n{GetRemoveScript()}fi\n"), 493),
+        };
+
+        _logger.Info($"Creating .deb package for {arch}");
+        using var output = File.Create(outputFile);
+        WriteArHeader(output);
+        WriteArMember(output, "debian-binary", Encoding.ASCII.GetBytes("2.0\n"));
+        WriteArMember(output, "control.tar.gz", CreateTarGz(controlFiles, false));
+        WriteArMember(output, "data.tar.gz", CreateTarGz(files, true));
+    }
+
+    public void CreateRpmPackage(string appImagePath, string appDir, string outputFile, string workDir)
+    {
+        Exe.AssertSystemBinaryExists("rpmbuild", "sudo apt install rpm", "brew install rpm");
+
+        var arch = _machine switch {
+            RuntimeCpu.x86 => "i686",
+            RuntimeCpu.x64 => "x86_64",
+            RuntimeCpu.arm64 => "aarch64",
+            _ => throw new ArgumentOutOfRangeException(nameof(_machine), _machine, null)
+        };
+
+        var buildRoot = Path.Combine(workDir, "root");
+        var files = GetPackageFiles(appImagePath, appDir);
+        foreach (var file in files) {
+            var path = Path.Combine(buildRoot, file.Path.TrimStart('/'));
+            Directory.CreateDirectory(Path.GetDirectoryName(path)!);
+            File.WriteAllBytes(path, file.Contents);
+            if (file.Mode == 493) {
+                Chmod.ChmodFileAsExecutable(path);
+            }
+        }
+
+        // the AppImage must not be stripped or have its build-id rewritten, as this would corrupt the squashfs
+        // filesystem which is appended to the runtime.
+        var spec = $$"""
+            %global __os_install_post %{nil}
+            %global debug_package %{nil}
+            %define _build_id_links none
+            Name: {{PackageName}}
+            Version: {{PackageVersion}}
+            Release: 1
+            Summary: {{_title}}
+            License: Proprietary
+            Packager: {{_authors}}
+            AutoReqProv: no
+
+            %description
+            {{_title}} {{_version}}, which keeps itself up to date with Velopack.
+
+            %install
+            cp -a "{{buildRoot}}/." "%{buildroot}/"
+
+            %post
+            {{GetPostInstallScript()}}
+            %postun
+            if [ "$1" -eq 0 ]; then
+            {{GetRemoveScript()}}fi
+
+            %files
+            {{String.Join("\n", files.Select(f => f.Path))}}
+
+            """.Replace("\r", "");
+
+        var specPath = Path.Combine(workDir, PackageName + ".spec");
+        File.WriteAllText(specPath, spec);
+
+        var rpmDir = Path.Combine(workDir, "rpms");
+        _logger.Info($"Creating .rpm package for {arch}");
+        _logger.Debug(Exe.InvokeAndThrowIfNonZero("rpmbuild", [
+            "-bb",
+            "--target", arch,
+            "--define", $"_topdir {Path.Combine(workDir, "rpmbuild")}",
+            "--define", $"_rpmdir {rpmDir}",
+            specPath,
+        ], workDir));
+
+        var rpm = Directory.EnumerateFiles(rpmDir, "*.rpm", SearchOption.AllDirectories).FirstOrDefault()
+            ?? throw new Exception("rpmbuild did not produce a package.");
+        File.Copy(rpm, outputFile, true);
+    }
+
+    private List<PackageFile> GetPackageFiles(string appImagePath, string appDir)
+    {
+        var files = new List<PackageFile> {
+            new(InstallPath, File.ReadAllBytes(appImagePath), 493),
+        };
+
+        // the AppDir contains the desktop file and icon which were embedded in the AppImage, they are installed
+        // system-wide and pointed at the installed AppImage so that the app shows up in application launchers.
+        var desktopFile = Path.Combine(appDir, _packId + ".desktop");
+        if (!File.Exists(desktopFile)) {
+            desktopFile = Directory.EnumerateFiles(appDir, "*.desktop").FirstOrDefault();
+        }
+
+        if (desktopFile != null) {
+            var desktop = File.ReadAllText(desktopFile).Replace("\r", "");
+            desktop = Regex.Replace(desktop, @"^TryExec=.*\n", "", RegexOptions.Multiline);
+            desktop = Regex.Replace(desktop, @"^Exec=\S+", "Exec=" + InstallPath, RegexOptions.Multiline);
+            files.Add(new($"/usr/share/applications/{_packId}.desktop", Encoding.UTF8.GetBytes(desktop), 420));
+
+            var icon = Regex.Match(desktop, @"^Icon=(.+)$", RegexOptions.Multiline).Groups[1].Value.Trim();
+            foreach (var ext in new[] { ".png", ".svg" }) {
+                var iconPath = Path.Combine(appDir, icon + ext);
+                if (!String.IsNullOrEmpty(icon) && File.Exists(iconPath)) {
+                    files.Add(new($"/usr/share/pixmaps/{icon}{ext}", File.ReadAllBytes(iconPath), 420));
+                    break;
+                }
+            }
+        } else {
+            _logger.Warn("No .desktop file was found in the AppDir, the package will not add a launcher entry.");
+        }
+
+        return files;
+    }
+
+    private string GetPostInstallScript()
+    {
+        // updates are downloaded by each user to their own cache directory (~/.cache/velopack), so nothing needs to be
+        // created for them here, and no directory is left writable by every user.
+        return $"""
+            ln -sf "{InstallPath}" "/usr/bin/{PackageName}"
+            update-desktop-database -q /usr/share/applications >/dev/null 2>&1 || true
+
+            """.Replace("\r", "");
+    }
+
+    private string GetRemoveScript()
+    {
+        // in-app updates replace the AppImage outside of the package manager, so it might not be removed with the package
+        return $"""
+            rm -f "/usr/bin/{PackageName}" "{InstallPath}"
+            rmdir "/opt/{_packId}" >/dev/null 2>&1 || true
+            update-desktop-database -q /usr/share/applications >/dev/null 2>&1 || true
+
+            """.Replace("\r", "");
+    }
+
+    private static byte[] CreateTarGz(IEnumerable<PackageFile> files, bool includeDirectories)
+    {
+        using var ms = new MemoryStream();
+        using (var gz = new GZipOutputStream(ms) { IsStreamOwner = false })
+        using (var tar = new TarOutputStream(gz, Encoding.UTF8)) {
+            var directories = new HashSet<string>();
+            foreach (var file in files) {
+                var name = "./" + file.Path.TrimStart('/');
+                if (includeDirectories) {
+                    var parts = name.Split('/');
+                    for (int i = 1; i < parts.Length; i++) {
+                        var dir = String.Join("/", parts.Take(i)) + "/";
+                        if (directories.Add(dir)) {
+                            var dirEntry = CreateTarEntry(dir, 493, 0);
+                            dirEntry.TarHeader.TypeFlag = TarHeader.LF_DIR;
+                            tar.PutNextEntry(dirEntry);
+                            tar.CloseEntry();
+                        }
+                    }
+                }
+
+                tar.PutNextEntry(CreateTarEntry(name, file.Mode, file.Contents.LongLength));
+                tar.Write(file.Contents, 0, file.Contents.Length);
+                tar.CloseEntry();
+            }
+        }
+        return ms.ToArray();
+    }
+
+    private static TarEntry CreateTarEntry(string name, int mode, long size)
+    {
+        var entry = TarEntry.CreateTarEntry(name);
+        entry.TarHeader.Magic = "ustar";
+        entry.TarHeader.Version = "00";
+        entry.TarHeader.ModTime = EasyZip.ZipFormatMinDate;
+        entry.TarHeader.Mode = mode;
+        entry.TarHeader.UserName = "root";
+        entry.TarHeader.GroupName = "root";
+        entry.Size = size;
+        return entry;
+    }
+
+    private static void WriteArHeader(Stream output)
+    {
+        var magic = Encoding.ASCII.GetBytes("!<arch>\n");
+        output.Write(magic, 0, magic.Length);
+    }
+
+    private static void WriteArMember(Stream output, string name, byte[] contents)
+    {
+        var header = $"{name,-16}{0,-12}{0,-6}{0,-6}{"100644",-8}{contents.Length,-10}`\n";
+        var headerBytes = Encoding.ASCII.GetBytes(header);
+        output.Write(headerBytes, 0, headerBytes.Length);
+        output.Write(contents, 0, contents.Length);
+        if (contents.Length % 2 != 0) {
+            output.WriteByte((byte) '\n');
+        }
+    }
+
+    private class PackageFile
+    {
+        public string Path { get; }
+
+        public byte[] Contents { get; }
+
+        public int Mode { get; }
+
+        public PackageFile(string path, byte[] contents, int mode)
+        {
+            Path = path;
+            Contents = contents;
+            Mode = mode;
+        }
+    }
+}
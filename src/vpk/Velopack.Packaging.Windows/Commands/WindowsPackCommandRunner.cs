@@ -1,4 +1,6 @@
-﻿using Microsoft.Extensions.Logging;
+﻿using System.Security;
+using System.Text.RegularExpressions;
+using Microsoft.Extensions.Logging;
 using Velopack.Compression;
 using Velopack.NuGet;
 using Velopack.Packaging.Abstractions;
@@ -37,8 +39,10 @@ protected override Task<string> PreprocessPackDir(Action<int> progress, string p
 
         // add nuspec metadata
         ExtraNuspecMetadata["runtimeDependencies"] = GetRuntimeDependencies();
-        ExtraNuspecMetadata["shortcutLocations"] = GetShortcutLocations();
+        ExtraNuspecMetadata["shortcutLocations"] = GetShortcutLocations(Options.Shortcuts);
         ExtraNuspecMetadata["shortcutAmuid"] = CoreUtil.GetAppUserModelId(Options.PackId);
+        ExtraNuspecMetadata["uninstallSurveyUrl"] = GetUninstallSurveyUrl();
+        ExtraNuspecMetadata["osMinServerVersion"] = GetOsMinServerVersion();
 
         // copy files to temp dir, so we can modify them
         var dir = TempDir.CreateSubdirectory("PreprocessPackDirWin");
@@ -81,29 +85,36 @@ protected override Task<string> PreprocessPackDir(Action<int> progress, string p
         return Task.FromResult(packDir);
     }
 
-    protected string GetShortcutLocations()
+    protected string GetUninstallSurveyUrl()
     {
-        if (String.IsNullOrWhiteSpace(Options.Shortcuts))
+        if (String.IsNullOrWhiteSpace(Options.UninstallSurveyUrl))
             return null;
 
-        try {
-            var shortcuts = Options.Shortcuts.Split(new[] { ',', ';' }, StringSplitOptions.RemoveEmptyEntries)
-                .Select(x => x.Trim())
-                .Select(x => (ShortcutLocation) Enum.Parse(typeof(ShortcutLocation), x, true))
-                .ToList();
+        if (!Uri.TryCreate(Options.UninstallSurveyUrl, UriKind.Absolute, out var uri) || (uri.Scheme != Uri.UriSchemeHttp && uri.Scheme != Uri.UriSchemeHttps)) {
+            throw new UserInfoException($"Invalid uninstall survey url '{Options.UninstallSurveyUrl}', it must be an absolute http or https url.");
+        }
 
-            if (shortcuts.Count == 0)
-                return null;
+        // query strings commonly contain '&', which must be escaped to be valid in the nuspec
+        return SecurityElement.Escape(uri.AbsoluteUri);
+    }
 
-            var shortcutString = string.Join(",", shortcuts.Select(x => x.ToString()));
-            Log.Debug($"Shortcut Locations: {shortcutString}");
-            return shortcutString;
-        } catch (Exception ex) {
+    protected string GetOsMinServerVersion()
+    {
+        if (String.IsNullOrWhiteSpace(Options.OsMinServerVersion))
+            return null;
+
+        // the updater accepts a server release (eg. "2019", "Server 2012 R2") or the version of its kernel (eg. "10.0.17763")
+        var version = Options.OsMinServerVersion.Trim();
+        var name = Regex.Replace(version.ToLowerInvariant(), @"\s+", "");
+        name = Regex.Replace(name, "^(windows)?(server)?", "");
+        var releases = new[] { "2008", "2008r2", "2012", "2012r2", "2016", "2019", "2022", "2025" };
+        if (!releases.Contains(name) && (!Version.TryParse(version, out var parsed) || parsed.Major > 10)) {
             throw new UserInfoException(
-                $"Invalid shortcut locations '{Options.Shortcuts}'. " +
-                $"Valid values for comma delimited list are: {string.Join(", ", Enum.GetNames(typeof(ShortcutLocation)))}." +
-                $"Error was {ex.Message}");
+                $"Invalid minimum Windows Server version '{Options.OsMinServerVersion}'. " +
+                $"It must be a release (eg. 2019 or 2012 R2) or a version number (eg. 10.0.17763).");
         }
+
+        return version;
     }
 
     protected string GetRuntimeDependencies()
@@ -185,8 +196,13 @@ protected override Task CreateSetupPackage(Action<int> progress, string releaseP
         editor.Commit();
 
         progress(25);
-        Log.Debug($"Creating Setup bundle");
-        SetupBundle.CreatePackageBundle(targetSetupExe, releasePkg);
+        if (Options.ExternalPayload) {
+            Log.Info($"Creating Setup bundle, which must be shipped next to '{Path.GetFileName(releasePkg)}'.");
+            SetupBundle.CreateExternalPayloadBundle(targetSetupExe, releasePkg);
+        } else {
+            Log.Debug($"Creating Setup bundle");
+            SetupBundle.CreatePackageBundle(targetSetupExe, releasePkg);
+        }
         progress(50);
         Log.Debug("Signing Setup bundle");
         SignFilesImpl(Options, CoreUtil.CreateProgressDelegate(progress, 50, 100), targetSetupExe);
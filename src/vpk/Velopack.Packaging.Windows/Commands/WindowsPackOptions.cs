@@ -18,9 +18,21 @@ public class WindowsPackOptions : WindowsReleasifyOptions, INugetPackCommand, IP
 
     public string Exclude { get; set; }
 
+    public string PackagesDir { get; set; }
+
+    public bool Mandatory { get; set; }
+
+    public bool SideBySide { get; set; }
+
     public bool NoPortable { get; set; }
 
     public bool NoInst { get; set; }
 
     public string Shortcuts { get; set; }
+
+    public string UninstallSurveyUrl { get; set; }
+
+    public string OsMinServerVersion { get; set; }
+
+    public bool ExternalPayload { get; set; }
 }
@@ -1,4 +1,5 @@
 ﻿using System.IO.MemoryMappedFiles;
+using System.Text;
 using Velopack.Util;
 
 namespace Velopack.Packaging.Windows;
@@ -40,13 +41,35 @@ void FindBundleHeader()
         return bundleOffset != 0 && bundleLength != 0;
     }
 
+    // marks the bundle as the description of a package shipped next to setup.exe, rather than the package itself (which
+    // is a zip, so always starts with "PK"). this must match the format read by Update.exe in external_payload.rs
+    private const string ExternalPayloadMagic = "velopack-external-payload";
+
     public static long CreatePackageBundle(string setupPath, string packagePath)
+    {
+        return CreateBundle(setupPath, () => IoUtil.Retry(() => File.OpenRead(packagePath), retries: 10));
+    }
+
+    /// <summary>
+    /// Appends a description of the package (its file name, size and SHA256) to setup.exe instead of the package
+    /// itself, so the package must be shipped next to setup.exe. It is only installed if its size and SHA256 match.
+    /// </summary>
+    public static long CreateExternalPayloadBundle(string setupPath, string packagePath)
+    {
+        var description = $"{ExternalPayloadMagic}\n" +
+            $"file={Path.GetFileName(packagePath)}\n" +
+            $"size={new FileInfo(packagePath).Length}\n" +
+            $"sha256={IoUtil.CalculateFileSHA256(packagePath)}\n";
+        return CreateBundle(setupPath, () => new MemoryStream(Encoding.UTF8.GetBytes(description)));
+    }
+
+    private static long CreateBundle(string setupPath, Func<Stream> openPayload)
     {
         long bundleOffset, bundleLength;
         Stream pkgStream = null, setupStream = null;
 
         try {
-            pkgStream = IoUtil.Retry(() => File.OpenRead(packagePath), retries: 10);
+            pkgStream = openPayload();
             setupStream = IoUtil.Retry(() => File.Open(setupPath, FileMode.Append, FileAccess.Write), retries: 10);
             bundleOffset = setupStream.Position;
             bundleLength = pkgStream.Length;
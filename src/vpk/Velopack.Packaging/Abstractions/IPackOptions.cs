@@ -7,6 +7,9 @@ public interface IPackOptions : INugetPackCommand, IPlatformOptions
     string EntryExecutableName { get; set; }
     string Icon { get; set; }
     string Exclude { get; set; }
+    string PackagesDir { get; set; }
+    bool Mandatory { get; set; }
+    bool SideBySide { get; set; }
     bool NoPortable { get; set; }
     bool NoInst { get; set; }
 }
@@ -9,6 +9,7 @@
 using Velopack.Packaging.Abstractions;
 using Velopack.Packaging.Exceptions;
 using Velopack.Util;
+using Velopack.Windows;
 
 namespace Velopack.Packaging;
 
@@ -157,6 +158,12 @@ string getIncompletePath(string fileName)
                 });
             }
 
+            foreach (var (format, fileName) in GetNativePackages()) {
+                await ctx.RunTask($"Building .{format} package", async (progress) => {
+                    await CreateNativePackage(progress, format, packDirectory, getIncompletePath(fileName));
+                });
+            }
+
             if (TargetOs != RuntimeOs.Linux && portableTask != null) await portableTask;
             if (setupTask != null) await setupTask;
 
@@ -173,6 +180,34 @@ string getIncompletePath(string fileName)
         });
     }
 
+    /// <summary>
+    /// Validates a comma delimited list of shortcut locations, and returns it in the format expected by the updater.
+    /// </summary>
+    protected string GetShortcutLocations(string shortcuts)
+    {
+        if (String.IsNullOrWhiteSpace(shortcuts))
+            return null;
+
+        try {
+            var locations = shortcuts.Split(new[] { ',', ';' }, StringSplitOptions.RemoveEmptyEntries)
+                .Select(x => x.Trim())
+                .Select(x => (ShortcutLocation) Enum.Parse(typeof(ShortcutLocation), x, true))
+                .ToList();
+
+            if (locations.Count == 0)
+                return null;
+
+            var shortcutString = string.Join(",", locations.Select(x => x.ToString()));
+            Log.Debug($"Shortcut Locations: {shortcutString}");
+            return shortcutString;
+        } catch (Exception ex) {
+            throw new UserInfoException(
+                $"Invalid shortcut locations '{shortcuts}'. " +
+                $"Valid values for comma delimited list are: {string.Join(", ", Enum.GetNames(typeof(ShortcutLocation)))}." +
+                $"Error was {ex.Message}");
+        }
+    }
+
     protected virtual string ExtractPackDir(string packDirectory) => packDirectory;
 
     protected abstract string[] GetMainExeSearchPaths(string packDirectory, string mainExeName);
@@ -197,6 +232,18 @@ void addMetadata(string key, string value)
             }
         }
 
+        if (!String.IsNullOrWhiteSpace(Options.PackagesDir)) {
+            addMetadata("packagesDir", Options.PackagesDir.Trim());
+        }
+
+        if (Options.Mandatory) {
+            addMetadata("mandatory", "true");
+        }
+
+        if (Options.SideBySide) {
+            addMetadata("sideBySide", "true");
+        }
+
         if (ExtraNuspecMetadata.Any()) {
             foreach (var kvp in ExtraNuspecMetadata) {
                 addMetadata(kvp.Key, kvp.Value);
@@ -259,6 +306,17 @@ protected virtual Task CreateSetupPackage(Action<int> progress, string releasePk
         return Task.CompletedTask;
     }
 
+    /// <summary> Returns the format and file name of any native packages (eg. .deb) which should be built alongside the release. </summary>
+    protected virtual IEnumerable<(string Format, string FileName)> GetNativePackages()
+    {
+        return [];
+    }
+
+    protected virtual Task CreateNativePackage(Action<int> progress, string format, string packDir, string outputPath)
+    {
+        return Task.CompletedTask;
+    }
+
     protected virtual async Task CreateReleasePackage(Action<int> progress, string packDir, string outputPath)
     {
         var stagingDir = TempDir.CreateSubdirectory("CreateReleasePackage");
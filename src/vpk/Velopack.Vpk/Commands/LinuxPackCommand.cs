@@ -8,6 +8,12 @@ public class LinuxPackCommand : PackCommand
 
     public string Compression { get; private set; }
 
+    public bool Deb { get; private set; }
+
+    public bool Rpm { get; private set; }
+
+    public string Shortcuts { get; private set; }
+
     public LinuxPackCommand()
         : base("pack", "Create a Linux .AppImage bundle from application files.", RuntimeOs.Linux)
     {
@@ -23,5 +29,15 @@ public LinuxPackCommand()
             .SetDefault(AppImageTool.DefaultCompressionAlgorithm)
             .SetArgumentHelpName("ALGO")
             .MustBeOneOfStringValues(["gzip", "xz"]);
+
+        AddOption<bool>((v) => Deb = v, "--deb")
+            .SetDescription("Also create a .deb package which installs the AppImage system-wide.");
+
+        AddOption<bool>((v) => Rpm = v, "--rpm")
+            .SetDescription("Also create a .rpm package which installs the AppImage system-wide (requires rpmbuild).");
+
+        AddOption<string>((v) => Shortcuts = v, "--shortcuts")
+            .SetDescription("List of .desktop entries to create: StartMenu/StartMenuRoot (app launcher), Desktop or Startup (autostart).")
+            .SetArgumentHelpName("LOC");
     }
 }
\ No newline at end of file
@@ -20,6 +20,8 @@ public class OsxPackCommand : OsxBundleCommand
 
     public string Keychain { get; private set; }
 
+    public string Shortcuts { get; private set; }
+
     public OsxPackCommand()
         : base("pack", "Converts application files into a release and installer.")
     {
@@ -66,5 +68,9 @@ public OsxPackCommand()
             .SetArgumentHelpName("PATH")
             .MustExist()
             .SetHidden(true);
+
+        AddOption<string>((v) => Shortcuts = v, "--shortcuts")
+            .SetDescription("List of shortcuts to create: StartMenu/StartMenuRoot (~/Applications alias), Desktop (Dock) or Startup (login item).")
+            .SetArgumentHelpName("LOC");
     }
 }
\ No newline at end of file
@@ -18,6 +18,12 @@ public class WindowsPackCommand : PackCommand
 
     public string Shortcuts { get; private set; }
 
+    public string UninstallSurveyUrl { get; private set; }
+
+    public string OsMinServerVersion { get; private set; }
+
+    public bool ExternalPayload { get; private set; }
+
     public WindowsPackCommand()
         : base("pack", "Creates a release from a folder containing application files.", RuntimeOs.Windows)
     {
@@ -57,6 +63,17 @@ public WindowsPackCommand()
             .SetArgumentHelpName("LOC")
             .SetDefault("Desktop,StartMenuRoot");
 
+        AddOption<string>((v) => UninstallSurveyUrl = v, "--uninstallSurveyUrl")
+            .SetDescription("A web page to open in the browser after the app has been uninstalled.")
+            .SetArgumentHelpName("URL");
+
+        AddOption<string>((v) => OsMinServerVersion = v, "--osMinServerVersion")
+            .SetDescription("The minimum version of Windows Server, eg. '2019', if it differs from the version in the target runtime.")
+            .SetArgumentHelpName("VERSION");
+
+        AddOption<bool>((v) => ExternalPayload = v, "--externalPayload")
+            .SetDescription("Create a Setup.exe which installs the full package from its own directory, instead of containing it.");
+
         if (VelopackRuntimeInfo.IsWindows) {
             var signParams = AddOption<string>((v) => SignParameters = v, "--signParams", "-n")
                 .SetDescription("Sign files via signtool.exe using these parameters.")
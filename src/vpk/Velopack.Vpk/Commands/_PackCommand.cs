@@ -45,6 +45,18 @@ public abstract class PackCommand : PlatformCommand
 
     protected CliOption<string> ExcludeOption { get; private set; }
 
+    public string PackagesDir { get; private set; }
+
+    protected CliOption<string> PackagesDirOption { get; private set; }
+
+    public bool Mandatory { get; private set; }
+
+    protected CliOption<bool> MandatoryOption { get; private set; }
+
+    public bool SideBySide { get; private set; }
+
+    protected CliOption<bool> SideBySideOption { get; private set; }
+
     public bool NoPortable { get; private set; }
 
     protected CliOption<bool> NoPortableOption { get; private set; }
@@ -105,6 +117,16 @@ public PackCommand(string name, string description, RuntimeOs targetOs = Runtime
             .SetArgumentHelpName("REGEX")
             .SetDefault(@".*\.pdb");
 
+        PackagesDirOption = AddOption<string>((v) => PackagesDir = v, "--packagesDir")
+            .SetDescription("Directory installed apps store downloaded packages in, instead of the default location.")
+            .SetArgumentHelpName("DIR");
+
+        MandatoryOption = AddOption<bool>((v) => Mandatory = v, "--mandatory")
+            .SetDescription("Mark this release as mandatory, so apps do not let the user skip it.");
+
+        SideBySideOption = AddOption<bool>((v) => SideBySide = v, "--sideBySide")
+            .SetDescription("Install each channel separately, so eg. stable and beta builds can be installed at the same time.");
+
         NoPortableOption = AddOption<bool>((v) => NoPortable = v, "--noPortable")
             .SetDescription("Skip generating a portable bundle.")
             .SetHidden(true);
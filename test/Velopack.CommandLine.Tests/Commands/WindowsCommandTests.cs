@@ -227,6 +227,50 @@ public void ReleaseNotes_WithoutFile_ShowsError()
         //Assert.Contains(releaseNotes, parseResult.Errors[0].Message);
     }
 
+    [Fact]
+    public void Mandatory_BareOption_SetsFlag()
+    {
+        var command = new WindowsPackCommand();
+
+        string cli = GetRequiredDefaultOptions() + "--mandatory";
+        ParseResult parseResult = command.ParseAndApply(cli);
+
+        Assert.True(command.Mandatory);
+    }
+
+    [Fact]
+    public void SideBySide_BareOption_SetsFlag()
+    {
+        var command = new WindowsPackCommand();
+
+        string cli = GetRequiredDefaultOptions() + "--sideBySide --channel beta";
+        ParseResult parseResult = command.ParseAndApply(cli);
+
+        Assert.True(command.SideBySide);
+        Assert.Equal("beta", command.Channel);
+    }
+
+    [Fact]
+    public void OsMinServerVersion_WithValue_ParsesValue()
+    {
+        var command = new WindowsPackCommand();
+
+        string cli = GetRequiredDefaultOptions() + "--osMinServerVersion \"2012 R2\"";
+        ParseResult parseResult = command.ParseAndApply(cli);
+
+        Assert.Equal("2012 R2", command.OsMinServerVersion);
+    }
+
+    [Fact]
+    public void ExternalPayload_BareOption_SetsFlag()
+    {
+        var command = new WindowsPackCommand();
+
+        string cli = GetRequiredDefaultOptions() + "--externalPayload";
+        ParseResult parseResult = command.ParseAndApply(cli);
+
+        Assert.True(command.ExternalPayload);
+    }
 
     [Fact]
     public void SignTemplate_WithTemplate_ParsesValue()